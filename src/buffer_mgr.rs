@@ -6,13 +6,16 @@
 use crate::{
     file::{
         block_id::BlockId,
-        file_mgr::{FileMgr, FileMgrError},
+        file_mgr::{FileMgr, FileMgrError, SyncMode},
         page::Page,
     },
     log_mgr::{LogMgr, LogMgrError, LSN},
 };
 use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
     iter::repeat_with,
+    ops::Add,
     sync::{Arc, Condvar, Mutex},
     time::{Duration, SystemTime},
 };
@@ -20,13 +23,13 @@ use thiserror::Error;
 
 #[derive(Debug, Error)]
 pub enum BufferError {
-    #[error("{0:?}")]
+    #[error("{0}")]
     IO(#[from] std::io::Error),
 
-    #[error("{0:?}")]
+    #[error("{0}")]
     LogMgr(#[from] LogMgrError),
 
-    #[error("{0:?}")]
+    #[error("{0}")]
     FileMgr(#[from] FileMgrError),
 
     #[error("unpinned buffer not found")]
@@ -35,18 +38,24 @@ pub enum BufferError {
 
 pub type Result<T> = core::result::Result<T, BufferError>;
 
-pub struct Buffer<'b, 'lm> {
+pub struct Buffer {
     fm: Arc<FileMgr>,
-    lm: Arc<LogMgr<'lm>>,
-    contents: Page<'b>,
+    lm: Arc<LogMgr>,
+    contents: Page<'static>,
     blk: Option<BlockId>,
     pins: i32,
     txnum: i32,
     lsn: LSN,
+    // NOTE: for the replacement policies
+    last_pinned: u64,
+    referenced: bool,
+    shard: usize,
+    // NOTE: set by PinPriority::Keep
+    keep: bool,
 }
 
-impl<'b, 'lm> Buffer<'b, 'lm> {
-    pub fn new(fm: Arc<FileMgr>, lm: Arc<LogMgr<'lm>>) -> Self {
+impl Buffer {
+    pub fn new(fm: Arc<FileMgr>, lm: Arc<LogMgr>) -> Self {
         let blocksize = fm.blocksize();
         Self {
             fm,
@@ -56,14 +65,18 @@ impl<'b, 'lm> Buffer<'b, 'lm> {
             pins: 0,
             txnum: -1,
             lsn: -1,
+            last_pinned: 0,
+            referenced: false,
+            shard: 0,
+            keep: false,
         }
     }
 
-    pub fn contents_as_ref(&self) -> &Page<'b> {
+    pub fn contents_as_ref(&self) -> &Page<'static> {
         &self.contents
     }
 
-    pub fn contents_as_mut(&mut self) -> &mut Page<'b> {
+    pub fn contents_as_mut(&mut self) -> &mut Page<'static> {
         &mut self.contents
     }
 
@@ -73,7 +86,7 @@ impl<'b, 'lm> Buffer<'b, 'lm> {
 
     pub fn set_modified(&mut self, txnum: i32, lsn: LSN) {
         self.txnum = txnum;
-        if self.lsn >= 0 {
+        if lsn >= 0 {
             self.lsn = lsn;
         }
     }
@@ -86,12 +99,18 @@ impl<'b, 'lm> Buffer<'b, 'lm> {
         self.txnum
     }
 
+    fn is_replaceable(&self, include_kept: bool) -> bool {
+        !self.is_pinned() && (include_kept || !self.keep)
+    }
+
     pub(crate) fn assign_to_block(&mut self, b: BlockId) -> Result<()> {
         self.flush()?;
-        self.blk = Some(b);
-        self.fm
-            .read(self.blk.as_ref().unwrap(), &mut self.contents)?;
+        // NOTE: a failed read leaves the buffer unassigned
+        self.blk = None;
+        self.keep = false;
         self.pins = 0;
+        self.fm.read(&b, &mut self.contents)?;
+        self.blk = Some(b);
         Ok(())
     }
 
@@ -105,6 +124,12 @@ impl<'b, 'lm> Buffer<'b, 'lm> {
         Ok(())
     }
 
+    pub(crate) fn discard(&mut self) {
+        self.blk = None;
+        self.keep = false;
+        self.txnum = -1;
+    }
+
     pub(crate) fn pin(&mut self) {
         self.pins += 1;
     }
@@ -114,80 +139,317 @@ impl<'b, 'lm> Buffer<'b, 'lm> {
     }
 }
 
-pub struct BufferMgr<'b, 'lm> {
-    data: Mutex<BufferMgrData<'b, 'lm>>,
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReplacementPolicy {
+    #[default]
+    Naive,
+    Lru,
+    Clock,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PinPriority {
+    #[default]
+    Normal,
+    Keep,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct BufferStats {
+    pub hits: usize,
+    pub misses: usize,
+    pub evictions: usize,
+    pub waits: usize,
+    pub wait_time: Duration,
+    pub leaked_pins: usize,
+}
+
+impl Add for BufferStats {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            hits: self.hits + other.hits,
+            misses: self.misses + other.misses,
+            evictions: self.evictions + other.evictions,
+            waits: self.waits + other.waits,
+            wait_time: self.wait_time + other.wait_time,
+            leaked_pins: self.leaked_pins + other.leaked_pins,
+        }
+    }
+}
+
+impl BufferStats {
+    pub fn hit_ratio(&self) -> f64 {
+        let total = self.hits + self.misses;
+        if total == 0 {
+            0.0
+        } else {
+            self.hits as f64 / total as f64
+        }
+    }
+}
+
+pub struct BufferMgr {
+    fm: Arc<FileMgr>,
+    shards: Vec<Shard>,
+}
+
+struct Shard {
+    data: Mutex<BufferMgrData>,
     waiting: Condvar,
 }
 
-struct BufferMgrData<'b, 'lm> {
-    bufferpool: Vec<Arc<Mutex<Buffer<'b, 'lm>>>>,
+struct BufferMgrData {
+    bufferpool: Vec<Arc<Mutex<Buffer>>>,
     num_available: usize,
+    policy: ReplacementPolicy,
+    clock_hand: usize,
+    ticks: u64,
+    stats: BufferStats,
+    // NOTE: txnum -> block -> pin count
+    pins_by_tx: HashMap<i32, HashMap<BlockId, usize>>,
 }
 
 const MAX_TIME: u64 = 10_000; // 10 seconds
 
-impl<'b, 'lm> BufferMgr<'b, 'lm> {
-    pub fn new(fm: Arc<FileMgr>, lm: Arc<LogMgr<'lm>>, numbuffs: usize) -> Self {
-        let pool = repeat_with(|| Arc::new(Mutex::new(Buffer::new(fm.clone(), lm.clone()))))
-            .take(numbuffs)
-            .collect::<Vec<_>>();
-        Self {
-            data: Mutex::new(BufferMgrData::<'b, 'lm> {
-                bufferpool: pool,
-                num_available: numbuffs,
-            }),
-            waiting: Condvar::new(),
-        }
+impl BufferMgr {
+    pub fn new(
+        fm: Arc<FileMgr>,
+        lm: Arc<LogMgr>,
+        numbuffs: usize,
+        policy: ReplacementPolicy,
+    ) -> Self {
+        Self::new_sharded(fm, lm, numbuffs, policy, 1)
+    }
+
+    pub fn new_sharded(
+        fm: Arc<FileMgr>,
+        lm: Arc<LogMgr>,
+        numbuffs: usize,
+        policy: ReplacementPolicy,
+        num_shards: usize,
+    ) -> Self {
+        assert!(
+            num_shards > 0 && num_shards <= numbuffs,
+            "invalid number of shards: {num_shards}"
+        );
+        let shards = (0..num_shards)
+            .map(|i| {
+                let size = numbuffs / num_shards + usize::from(i < numbuffs % num_shards);
+                let pool = repeat_with(|| {
+                    let mut buff = Buffer::new(fm.clone(), lm.clone());
+                    buff.shard = i;
+                    Arc::new(Mutex::new(buff))
+                })
+                .take(size)
+                .collect::<Vec<_>>();
+                Shard {
+                    data: Mutex::new(BufferMgrData {
+                        bufferpool: pool,
+                        num_available: size,
+                        policy,
+                        clock_hand: 0,
+                        ticks: 0,
+                        stats: BufferStats::default(),
+                        pins_by_tx: HashMap::new(),
+                    }),
+                    waiting: Condvar::new(),
+                }
+            })
+            .collect();
+        Self { fm, shards }
+    }
+
+    fn shard_index(&self, blk: &BlockId) -> usize {
+        let mut hasher = DefaultHasher::new();
+        blk.hash(&mut hasher);
+        (hasher.finish() % self.shards.len() as u64) as usize
+    }
+
+    fn shard_of(&self, blk: &BlockId) -> &Shard {
+        &self.shards[self.shard_index(blk)]
+    }
+
+    pub fn num_shards(&self) -> usize {
+        self.shards.len()
     }
 
     pub fn available(&self) -> usize {
-        let data = self.data.lock().unwrap();
-        data.num_available
+        self.shards
+            .iter()
+            .map(|shard| shard.data.lock().unwrap().num_available)
+            .sum()
+    }
+
+    pub fn stats(&self) -> BufferStats {
+        self.shards
+            .iter()
+            .map(|shard| shard.data.lock().unwrap().stats)
+            .fold(BufferStats::default(), |acc, stats| acc + stats)
     }
 
     pub fn flush_all(&self, txnum: i32) -> Result<()> {
-        let data = self.data.lock().unwrap();
-        for buff in data.bufferpool.iter() {
-            let mut b = buff.lock().unwrap();
-            if b.modifying_tx() == txnum {
-                b.flush()?;
+        for shard in self.shards.iter() {
+            let data = shard.data.lock().unwrap();
+            for buff in data.bufferpool.iter() {
+                let mut b = buff.lock().unwrap();
+                if b.modifying_tx() == txnum {
+                    b.flush()?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn flush_modified(&self) -> Result<()> {
+        for shard in self.shards.iter() {
+            let data = shard.data.lock().unwrap();
+            for buff in data.bufferpool.iter() {
+                buff.lock().unwrap().flush()?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn sync(&self) -> Result<()> {
+        if self.fm.sync_mode() == SyncMode::FsyncOnCommit {
+            self.fm.sync_all()?;
+        }
+        Ok(())
+    }
+
+    pub fn prefetch(&self, blocks: &[BlockId]) {
+        let blocks = blocks
+            .iter()
+            .filter(|blk| {
+                let data = self.shard_of(blk).data.lock().unwrap();
+                data.find_existing_buffer(blk).is_none()
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        self.fm.prefetch(blocks);
+    }
+
+    pub(crate) fn discard(&self, blk: &BlockId) {
+        let data = self.shard_of(blk).data.lock().unwrap();
+        if let Some(buff) = data.find_existing_buffer(blk) {
+            buff.lock().unwrap().discard();
+        }
+    }
+
+    pub(crate) fn flush_file(&self, filename: &str) -> Result<()> {
+        for shard in self.shards.iter() {
+            let data = shard.data.lock().unwrap();
+            for buff in data.bufferpool.iter() {
+                let mut b = buff.lock().unwrap();
+                if b.block()
+                    .as_ref()
+                    .is_some_and(|blk| blk.filename() == filename)
+                {
+                    debug_assert!(!b.is_pinned(), "{} is pinned", filename);
+                    b.flush()?;
+                    b.discard();
+                }
             }
         }
         Ok(())
     }
 
-    pub fn unpin(&self, buff: Arc<Mutex<Buffer<'b, 'lm>>>) {
-        let mut data = self.data.lock().unwrap();
+    pub fn unpin(&self, txnum: i32, buff: Arc<Mutex<Buffer>>) {
+        let shard = &self.shards[buff.lock().unwrap().shard];
+        let mut data = shard.data.lock().unwrap();
 
         let mut b = buff.lock().unwrap();
+        if let Some(blk) = b.block() {
+            data.forget_pin(txnum, blk);
+        }
         b.unpin();
         if !b.is_pinned() {
             data.num_available += 1;
-            self.waiting.notify_all();
+            shard.waiting.notify_all();
+        }
+    }
+
+    pub fn pinned_blocks(&self, txnum: i32) -> Vec<BlockId> {
+        let mut blocks = Vec::new();
+        for shard in self.shards.iter() {
+            let data = shard.data.lock().unwrap();
+            if let Some(pins) = data.pins_by_tx.get(&txnum) {
+                blocks.extend(pins.keys().cloned());
+            }
+        }
+        blocks
+    }
+
+    pub(crate) fn release_pins(&self, txnum: i32) -> Vec<BlockId> {
+        let mut blocks = Vec::new();
+        for shard in self.shards.iter() {
+            let mut data = shard.data.lock().unwrap();
+            let pins = data.pins_by_tx.remove(&txnum).unwrap_or_default();
+            for (blk, count) in pins.iter() {
+                if let Some(buff) = data.find_existing_buffer(blk) {
+                    let mut b = buff.lock().unwrap();
+                    for _ in 0..*count {
+                        b.unpin();
+                    }
+                    if !b.is_pinned() {
+                        data.num_available += 1;
+                    }
+                }
+                data.stats.leaked_pins += count;
+            }
+            if !pins.is_empty() {
+                shard.waiting.notify_all();
+            }
+            blocks.extend(pins.into_keys());
         }
+        blocks
     }
 
-    pub fn pin(&self, blk: &BlockId) -> Result<Arc<Mutex<Buffer<'b, 'lm>>>> {
-        let mut data = self.data.lock().unwrap();
+    pub fn pin(&self, txnum: i32, blk: &BlockId) -> Result<Arc<Mutex<Buffer>>> {
+        self.pin_with_priority(txnum, blk, PinPriority::Normal)
+    }
+
+    pub fn pin_with_priority(
+        &self,
+        txnum: i32,
+        blk: &BlockId,
+        priority: PinPriority,
+    ) -> Result<Arc<Mutex<Buffer>>> {
+        let shard = self.shard_of(blk);
+        let mut data = shard.data.lock().unwrap();
 
         let begintime = SystemTime::now();
 
-        let mut buff = data.try_to_pin(blk);
+        let mut buff = data.try_to_pin(blk, priority)?;
+        let waited = buff.is_none();
         while buff.is_none() && !self.waiting_too_long(begintime) {
-            let result = self
+            let result = shard
                 .waiting
                 .wait_timeout(data, Duration::from_millis(MAX_TIME))
                 .unwrap();
+            data = result.0;
             if result.1.timed_out() {
-                return Err(BufferError::Aborted);
-            } else {
-                data = result.0;
-                buff = data.try_to_pin(blk);
+                break;
             }
+            buff = data.try_to_pin(blk, priority)?;
+        }
+        if waited {
+            data.stats.waits += 1;
+            data.stats.wait_time += begintime.elapsed().unwrap_or_default();
         }
 
         match buff {
-            Some(found) => Ok(found.clone()),
+            Some(found) => {
+                *data
+                    .pins_by_tx
+                    .entry(txnum)
+                    .or_default()
+                    .entry(blk.clone())
+                    .or_default() += 1;
+                Ok(found.clone())
+            }
             None => Err(BufferError::Aborted),
         }
     }
@@ -201,32 +463,60 @@ impl<'b, 'lm> BufferMgr<'b, 'lm> {
     }
 }
 
-impl<'b, 'lm> BufferMgrData<'b, 'lm> {
-    pub(crate) fn try_to_pin(&mut self, blk: &BlockId) -> Option<Arc<Mutex<Buffer<'b, 'lm>>>> {
+impl BufferMgrData {
+    fn forget_pin(&mut self, txnum: i32, blk: &BlockId) {
+        if let Some(pins) = self.pins_by_tx.get_mut(&txnum) {
+            if let Some(count) = pins.get_mut(blk) {
+                *count -= 1;
+                if *count == 0 {
+                    pins.remove(blk);
+                }
+            }
+            if pins.is_empty() {
+                self.pins_by_tx.remove(&txnum);
+            }
+        }
+    }
+
+    pub(crate) fn try_to_pin(
+        &mut self,
+        blk: &BlockId,
+        priority: PinPriority,
+    ) -> Result<Option<Arc<Mutex<Buffer>>>> {
         let buff = if let Some(buff) = self.find_existing_buffer(blk) {
+            self.stats.hits += 1;
             buff
         } else {
             if let Some(buff) = self.choose_unpinned_buffer() {
-                buff.lock().unwrap().assign_to_block(blk.clone()).unwrap();
+                let mut b = buff.lock().unwrap();
+                if b.block().is_some() {
+                    self.stats.evictions += 1;
+                }
+                b.assign_to_block(blk.clone())?;
+                self.stats.misses += 1;
+                drop(b);
                 buff
             } else {
-                return None;
+                return Ok(None);
             }
         };
 
+        self.ticks += 1;
         let mut b = buff.lock().unwrap();
         if !b.is_pinned() {
             self.num_available -= 1;
         }
         b.pin();
+        b.last_pinned = self.ticks;
+        b.referenced = true;
+        if priority == PinPriority::Keep {
+            b.keep = true;
+        }
 
-        Some(buff.clone())
+        Ok(Some(buff.clone()))
     }
 
-    pub(crate) fn find_existing_buffer(
-        &self,
-        blk: &BlockId,
-    ) -> Option<Arc<Mutex<Buffer<'b, 'lm>>>> {
+    pub(crate) fn find_existing_buffer(&self, blk: &BlockId) -> Option<Arc<Mutex<Buffer>>> {
         for buff in self.bufferpool.iter() {
             let b = buff.lock().unwrap();
             if let Some(bufblk) = b.block() {
@@ -238,10 +528,55 @@ impl<'b, 'lm> BufferMgrData<'b, 'lm> {
         None
     }
 
-    pub(crate) fn choose_unpinned_buffer(&self) -> Option<Arc<Mutex<Buffer<'b, 'lm>>>> {
+    pub(crate) fn choose_unpinned_buffer(&mut self) -> Option<Arc<Mutex<Buffer>>> {
+        // NOTE: the buffers to keep are replaced last
+        self.choose_victim(false)
+            .or_else(|| self.choose_victim(true))
+    }
+
+    fn choose_victim(&mut self, include_kept: bool) -> Option<Arc<Mutex<Buffer>>> {
+        match self.policy {
+            ReplacementPolicy::Naive => self.choose_first_unpinned(include_kept),
+            ReplacementPolicy::Lru => self.choose_least_recently_pinned(include_kept),
+            ReplacementPolicy::Clock => self.choose_by_clock(include_kept),
+        }
+    }
+
+    fn choose_first_unpinned(&self, include_kept: bool) -> Option<Arc<Mutex<Buffer>>> {
         for buff in self.bufferpool.iter() {
             let b = buff.lock().unwrap();
-            if !b.is_pinned() {
+            if b.is_replaceable(include_kept) {
+                return Some(buff.clone());
+            }
+        }
+        None
+    }
+
+    fn choose_least_recently_pinned(&self, include_kept: bool) -> Option<Arc<Mutex<Buffer>>> {
+        self.bufferpool
+            .iter()
+            .filter_map(|buff| {
+                let b = buff.lock().unwrap();
+                b.is_replaceable(include_kept)
+                    .then_some((b.last_pinned, buff))
+            })
+            .min_by_key(|(last_pinned, _)| *last_pinned)
+            .map(|(_, buff)| buff.clone())
+    }
+
+    fn choose_by_clock(&mut self, include_kept: bool) -> Option<Arc<Mutex<Buffer>>> {
+        let n = self.bufferpool.len();
+        // NOTE: the first round may only clear the reference bits
+        for _ in 0..(2 * n) {
+            let buff = &self.bufferpool[self.clock_hand];
+            self.clock_hand = (self.clock_hand + 1) % n;
+            let mut b = buff.lock().unwrap();
+            if !b.is_replaceable(include_kept) {
+                continue;
+            }
+            if b.referenced {
+                b.referenced = false;
+            } else {
                 return Some(buff.clone());
             }
         }
@@ -252,18 +587,20 @@ impl<'b, 'lm> BufferMgrData<'b, 'lm> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::fs;
     use std::path::Path;
+    use std::thread;
     use tempfile::tempdir;
 
-    fn buffer_mgr<'b, 'lm>(
-        dir_path: &Path,
-        blocksize: usize,
-        logfile: &str,
-        numbuffs: usize,
-    ) -> BufferMgr<'b, 'lm> {
+    fn buffer_mgr(dir_path: &Path, blocksize: usize, logfile: &str, numbuffs: usize) -> BufferMgr {
         let fm = Arc::new(FileMgr::new(dir_path, blocksize));
-        let lm = Arc::new(LogMgr::new(fm.clone(), logfile));
-        BufferMgr::new(fm.clone(), lm.clone(), numbuffs)
+        let lm = Arc::new(LogMgr::new(fm.clone(), logfile).unwrap());
+        BufferMgr::new(
+            fm.clone(),
+            lm.clone(),
+            numbuffs,
+            ReplacementPolicy::default(),
+        )
     }
 
     #[test]
@@ -273,7 +610,7 @@ mod tests {
         {
             let bm = buffer_mgr(dir.path(), 400, "test_buffer.log", 3);
 
-            let buff1 = bm.pin(&BlockId::new("test_buffer_mgr", 1))?;
+            let buff1 = bm.pin(1, &BlockId::new("test_buffer_mgr", 1))?;
             {
                 let mut b1 = buff1.lock().unwrap();
                 let p = b1.contents_as_mut();
@@ -282,22 +619,22 @@ mod tests {
                 b1.set_modified(1, 0);
                 // what's n + 1?
             }
-            bm.unpin(buff1);
+            bm.unpin(1, buff1);
 
-            let mut buff2 = bm.pin(&BlockId::new("test_buffer_mgr", 2))?;
-            let _buff3 = bm.pin(&BlockId::new("test_buffer_mgr", 3))?;
-            let _buff4 = bm.pin(&BlockId::new("test_buffer_mgr", 4))?;
+            let mut buff2 = bm.pin(1, &BlockId::new("test_buffer_mgr", 2))?;
+            let _buff3 = bm.pin(1, &BlockId::new("test_buffer_mgr", 3))?;
+            let _buff4 = bm.pin(1, &BlockId::new("test_buffer_mgr", 4))?;
 
-            bm.unpin(buff2);
+            bm.unpin(1, buff2);
 
-            buff2 = bm.pin(&BlockId::new("test_buffer_mgr", 1))?;
+            buff2 = bm.pin(1, &BlockId::new("test_buffer_mgr", 1))?;
             {
                 let mut b2 = buff2.lock().unwrap();
                 let p2 = b2.contents_as_mut();
                 p2.set_i32(80, 9999).unwrap();
                 b2.set_modified(1, 0);
             }
-            bm.unpin(buff2);
+            bm.unpin(1, buff2);
         }
         dir.close()?;
         Ok(())
@@ -313,18 +650,18 @@ mod tests {
             let bm = buffer_mgr(dir.path(), 400, "test_buffermgr.log", 3);
 
             let mut buff = array![None; 6];
-            buff[0] = Some(bm.pin(&BlockId::new("test_buffer_mgr", 0))?);
-            buff[1] = Some(bm.pin(&BlockId::new("test_buffer_mgr", 1))?);
-            buff[2] = Some(bm.pin(&BlockId::new("test_buffer_mgr", 2))?);
+            buff[0] = Some(bm.pin(1, &BlockId::new("test_buffer_mgr", 0))?);
+            buff[1] = Some(bm.pin(1, &BlockId::new("test_buffer_mgr", 1))?);
+            buff[2] = Some(bm.pin(1, &BlockId::new("test_buffer_mgr", 2))?);
 
             let b1 = buff[1].take().unwrap();
-            bm.unpin(b1);
+            bm.unpin(1, b1);
 
-            buff[3] = Some(bm.pin(&BlockId::new("test_buffer_mgr", 0))?);
-            buff[4] = Some(bm.pin(&BlockId::new("test_buffer_mgr", 1))?);
+            buff[3] = Some(bm.pin(1, &BlockId::new("test_buffer_mgr", 0))?);
+            buff[4] = Some(bm.pin(1, &BlockId::new("test_buffer_mgr", 1))?);
             assert_eq!(bm.available(), 0); // TODO
             {
-                let result = bm.pin(&BlockId::new("test_buffer_mgr", 3));
+                let result = bm.pin(1, &BlockId::new("test_buffer_mgr", 3));
                 assert_eq!(result.is_err(), true);
                 assert_eq!(
                     result.err().unwrap().to_string(),
@@ -333,9 +670,207 @@ mod tests {
             }
 
             let b2 = buff[2].take().unwrap();
-            bm.unpin(b2);
+            bm.unpin(1, b2);
+
+            buff[5] = Some(bm.pin(1, &BlockId::new("test_buffer_mgr", 3))?);
 
-            buff[5] = Some(bm.pin(&BlockId::new("test_buffer_mgr", 3))?);
+            let stats = bm.stats();
+            assert_eq!(stats.hits, 2);
+            assert_eq!(stats.misses, 4);
+            assert_eq!(stats.evictions, 1); // NOTE: block 2 for block 3
+            assert_eq!(stats.waits, 1);
+            assert!(stats.wait_time >= Duration::from_millis(MAX_TIME));
+        }
+        dir.close()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_corrupt_page() -> Result<()> {
+        let dir = tempdir()?;
+        {
+            let bm = buffer_mgr(dir.path(), 400, "test_corrupt_page.log", 2);
+            let blk = BlockId::new("test_buffer_mgr", 1);
+            let buff = bm.pin(1, &blk)?;
+            {
+                let mut b = buff.lock().unwrap();
+                b.contents_as_mut().set_i32(80, 42).unwrap();
+                b.set_modified(1, 0);
+            }
+            bm.unpin(1, buff);
+            bm.flush_all(1)?;
+            bm.discard(&blk);
+            // NOTE: corrupt the block on the disk
+            let mut bytes = fs::read(dir.path().join("test_buffer_mgr"))?;
+            bytes[400 + 80] ^= 0xff;
+            fs::write(dir.path().join("test_buffer_mgr"), bytes)?;
+
+            assert!(matches!(
+                bm.pin(1, &blk),
+                Err(BufferError::FileMgr(FileMgrError::CorruptPage(b))) if b == blk
+            ));
+            assert_eq!(bm.available(), 2);
+            assert!(bm.pinned_blocks(1).is_empty());
+
+            let buff = bm.pin(1, &BlockId::new("test_buffer_mgr", 0))?;
+            assert_eq!(
+                buff.lock().unwrap().contents_as_ref().get_i32(80).unwrap(),
+                0
+            );
+            bm.unpin(1, buff);
+            assert!(bm.pin(1, &blk).is_err());
+            assert_eq!(bm.available(), 2);
+        }
+        dir.close()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_stats() -> Result<()> {
+        let dir = tempdir()?;
+        {
+            let bm = buffer_mgr(dir.path(), 400, "test_buffer_stats.log", 2);
+            let block = |n| BlockId::new("test_buffer_stats", n);
+
+            let b0 = bm.pin(1, &block(0))?;
+            let b1 = bm.pin(1, &block(1))?;
+            bm.unpin(1, b0);
+            let b2 = bm.pin(1, &block(2))?;
+
+            // NOTE: waits until block 1 is unpinned
+            let b3 = thread::scope(|s| {
+                let waiter = s.spawn(|| bm.pin(2, &block(3)));
+                thread::sleep(Duration::from_millis(100));
+                bm.unpin(1, b1);
+                waiter.join().unwrap()
+            })?;
+            bm.unpin(1, b2);
+            let b0 = bm.pin(1, &block(0))?;
+            let b3_again = bm.pin(1, &block(3))?;
+
+            let stats = bm.stats();
+            assert_eq!(stats.hits, 1);
+            assert_eq!(stats.misses, 5);
+            assert_eq!(stats.evictions, 3); // NOTE: block 0 for 2, block 1 for 3, and block 2 for 0
+            assert_eq!(stats.waits, 1);
+            assert!(stats.wait_time >= Duration::from_millis(100));
+            assert!(stats.wait_time < Duration::from_millis(MAX_TIME));
+            bm.unpin(1, b0);
+            bm.unpin(1, b3_again);
+            bm.unpin(2, b3);
+        }
+        dir.close()?;
+        Ok(())
+    }
+
+    fn scan_with_lookups(policy: ReplacementPolicy) -> Result<BufferStats> {
+        let dir = tempdir()?;
+        let stats = {
+            let fm = Arc::new(FileMgr::new(dir.path(), 400));
+            let lm = Arc::new(LogMgr::new(fm.clone(), "test_replacement.log").unwrap());
+            let bm = BufferMgr::new(fm, lm, 4, policy);
+            for _ in 0..3 {
+                for i in 0..20 {
+                    let buff = bm.pin(1, &BlockId::new("test_replacement_scan", i))?;
+                    bm.unpin(1, buff);
+                    let buff = bm.pin(1, &BlockId::new("test_replacement_hot", i % 2))?;
+                    bm.unpin(1, buff);
+                }
+            }
+            bm.stats()
+        };
+        dir.close()?;
+        Ok(stats)
+    }
+
+    #[test]
+    fn test_replacement_policies() -> Result<()> {
+        let naive = scan_with_lookups(ReplacementPolicy::Naive)?;
+        let lru = scan_with_lookups(ReplacementPolicy::Lru)?;
+        let clock = scan_with_lookups(ReplacementPolicy::Clock)?;
+        for stats in [naive, lru, clock] {
+            assert_eq!(stats.hits + stats.misses, 120);
+        }
+
+        // NOTE: Naive keeps evicting the hot pages
+        assert_eq!(naive.hits, 0);
+        assert_eq!(lru.misses, 60 + 2);
+        assert_eq!(lru.evictions, lru.misses - 4);
+        assert!(naive.hit_ratio() < clock.hit_ratio());
+        assert!(clock.hit_ratio() <= lru.hit_ratio());
+        Ok(())
+    }
+
+    #[test]
+    fn test_shards() -> Result<()> {
+        let dir = tempdir()?;
+        {
+            let fm = Arc::new(FileMgr::new(dir.path(), 400));
+            let lm = Arc::new(LogMgr::new(fm.clone(), "test_shards.log").unwrap());
+            let bm = BufferMgr::new_sharded(fm, lm, 10, ReplacementPolicy::default(), 4);
+            assert_eq!(bm.num_shards(), 4);
+            assert_eq!(bm.available(), 10);
+
+            // NOTE: 10 buffers are split into shards of 3, 3, 2 and 2
+            let blocks = (0..200)
+                .map(|i| BlockId::new("test_shards_file", i))
+                .collect::<Vec<_>>();
+            let in_shard = |shard: usize| {
+                blocks
+                    .iter()
+                    .filter(|blk| bm.shard_index(blk) == shard)
+                    .cloned()
+                    .collect::<Vec<_>>()
+            };
+            let (shard3, shard0) = (in_shard(3), in_shard(0));
+
+            let pinned = shard3[..2]
+                .iter()
+                .map(|blk| bm.pin(1, blk))
+                .collect::<Result<Vec<_>>>()?;
+            assert_eq!(bm.available(), 8);
+
+            let buff = bm.pin(2, &shard0[0])?;
+            bm.unpin(2, buff);
+            assert_eq!(bm.stats().waits, 0);
+
+            for buff in pinned {
+                bm.unpin(1, buff);
+            }
+            let buff = bm.pin(2, &shard3[2])?;
+            bm.unpin(2, buff);
+            assert_eq!(bm.available(), 10);
+            assert_eq!(bm.stats().misses, 4);
+        }
+        dir.close()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_pin_priority() -> Result<()> {
+        let dir = tempdir()?;
+        {
+            let bm = buffer_mgr(dir.path(), 400, "test_pin_priority.log", 3);
+            let block = |n| BlockId::new("test_pin_priority_file", n);
+
+            let buff = bm.pin_with_priority(1, &block(0), PinPriority::Keep)?;
+            bm.unpin(1, buff);
+            for n in 1..10 {
+                let buff = bm.pin(1, &block(n))?;
+                bm.unpin(1, buff);
+            }
+            // NOTE: Naive would replace block 0 first
+            let misses = bm.stats().misses;
+            let buff = bm.pin(1, &block(0))?;
+            bm.unpin(1, buff);
+            assert_eq!(bm.stats().misses, misses);
+
+            for n in 10..13 {
+                let buff = bm.pin_with_priority(1, &block(n), PinPriority::Keep)?;
+                bm.unpin(1, buff);
+            }
+            let buff = bm.pin(1, &block(13))?;
+            bm.unpin(1, buff);
         }
         dir.close()?;
         Ok(())
@@ -0,0 +1,225 @@
+// Copyright (c) 2022 Sho Kuroda <krdlab@gmail.com>
+//
+// This software is released under the MIT License.
+// https://opensource.org/licenses/MIT
+
+use crate::{
+    log_mgr::LSN,
+    metadata::table_mgr::{TABLE_CATALOG_TABLE_NAME, TABLE_NAME_FIELD},
+    query::scan::Scan,
+    record::table_scan::TableScan,
+    server::simple_db::SimpleDB,
+};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+};
+
+pub struct CrashInjector {
+    image_dir: PathBuf,
+    writes_left: Mutex<Option<usize>>,
+    crash_lsn: Option<LSN>,
+    crashed: AtomicBool,
+}
+
+impl CrashInjector {
+    pub fn after_writes(n: usize, image_dir: &Path) -> Self {
+        Self {
+            image_dir: image_dir.to_path_buf(),
+            writes_left: Mutex::new(Some(n)),
+            crash_lsn: None,
+            crashed: AtomicBool::new(false),
+        }
+    }
+
+    pub fn at_lsn(lsn: LSN, image_dir: &Path) -> Self {
+        Self {
+            image_dir: image_dir.to_path_buf(),
+            writes_left: Mutex::new(None),
+            crash_lsn: Some(lsn),
+            crashed: AtomicBool::new(false),
+        }
+    }
+
+    pub fn is_crashed(&self) -> bool {
+        self.crashed.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn before_write(&self, db_dir: &Path) {
+        let mut writes_left = self.writes_left.lock().unwrap();
+        match *writes_left {
+            Some(0) => {
+                self.crash(db_dir);
+                *writes_left = None;
+            }
+            Some(n) => *writes_left = Some(n - 1),
+            None => {}
+        }
+    }
+
+    pub(crate) fn on_log_append(&self, lsn: LSN, db_dir: &Path) {
+        if self.crash_lsn == Some(lsn) {
+            self.crash(db_dir);
+        }
+    }
+
+    fn crash(&self, db_dir: &Path) {
+        if self.crashed.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        fs::create_dir_all(&self.image_dir).expect("failed to create the crash image directory");
+        for entry in fs::read_dir(db_dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_file() {
+                fs::copy(&path, self.image_dir.join(path.file_name().unwrap())).unwrap();
+            }
+        }
+    }
+}
+
+pub fn reopen_and_verify(dir: &Path, logfile: &str, verify: impl FnOnce(&SimpleDB)) {
+    let mut db = SimpleDB::new_for_test(dir, logfile);
+    db.init().expect("failed to recover the database");
+
+    let mm = db.metadata_mgr();
+    let tx = db.new_tx();
+    let tables = {
+        let layout = mm
+            .table_layout(TABLE_CATALOG_TABLE_NAME, tx.clone())
+            .expect("the table catalog is broken");
+        let mut ts = TableScan::new(tx.clone(), TABLE_CATALOG_TABLE_NAME.into(), layout).unwrap();
+        let mut tables = Vec::new();
+        while ts.next().unwrap() {
+            tables.push(ts.get_string(TABLE_NAME_FIELD).unwrap());
+        }
+        ts.close();
+        tables
+    };
+    for table in tables {
+        let layout = mm
+            .table_layout(&table, tx.clone())
+            .unwrap_or_else(|e| panic!("the layout of {} is broken: {:?}", table, e));
+        let mut ts = TableScan::new(tx.clone(), table.clone(), layout.clone()).unwrap();
+        while ts.next().unwrap() {
+            for field in layout.schema().fields_iter() {
+                ts.get_val(field)
+                    .unwrap_or_else(|e| panic!("{}.{} is broken: {:?}", table, field, e));
+            }
+        }
+        ts.close();
+    }
+    tx.lock().unwrap().commit().unwrap();
+
+    verify(&db);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{reopen_and_verify, CrashInjector};
+    use crate::{log_mgr::LSN, server::simple_db::SimpleDB};
+    use std::{collections::HashMap, ops::RangeInclusive, path::Path, sync::Arc};
+    use tempfile::tempdir;
+
+    const NUM_TXS: i32 = 20;
+
+    fn run_workload(
+        dir: &Path,
+        logfile: &str,
+        ci: Arc<CrashInjector>,
+    ) -> (i32, RangeInclusive<LSN>) {
+        let mut db = SimpleDB::new_for_test(dir, logfile);
+        db.init().unwrap();
+        let planner = db.planner();
+
+        let tx = db.new_tx();
+        planner
+            .execute_update("create table T(A int, B int)", tx.clone())
+            .unwrap();
+        tx.lock().unwrap().commit().unwrap();
+
+        db.file_mgr().set_crash_injector(ci.clone());
+        let first_lsn = db.log_mgr().latest_lsn() + 1;
+        let mut committed = 0;
+        for i in 0..NUM_TXS {
+            let tx = db.new_tx();
+            for b in 0..2 {
+                let cmd = format!("insert into T(A, B) values ({}, {})", i, b);
+                planner.execute_update(&cmd, tx.clone()).unwrap();
+            }
+            tx.lock().unwrap().commit().unwrap();
+            if !ci.is_crashed() {
+                committed = i + 1;
+            }
+        }
+        (committed, first_lsn..=db.log_mgr().latest_lsn())
+    }
+
+    fn verify_workload(db: &SimpleDB, committed: i32) {
+        let planner = db.planner();
+        let tx = db.new_tx();
+        let mut rows: HashMap<i32, usize> = HashMap::new();
+        {
+            let plan = planner
+                .create_query_plan("select A, B from T", tx.clone())
+                .unwrap();
+            let mut scan = plan.open(tx.clone()).unwrap();
+            while scan.next().unwrap() {
+                *rows.entry(scan.get_i32("a").unwrap()).or_default() += 1;
+            }
+            scan.close();
+        }
+        tx.lock().unwrap().commit().unwrap();
+
+        // NOTE: the transactions committed before the crash survive it
+        let survived = rows.len() as i32;
+        assert!(survived >= committed);
+        for i in 0..NUM_TXS {
+            let expected = if i < survived { Some(&2) } else { None };
+            assert_eq!(rows.get(&i), expected, "rows of A = {}", i);
+        }
+    }
+
+    #[test]
+    fn test_crash_after_writes() {
+        for n in [0, 1, 2, 3, 5, 8, 13, 21] {
+            let dir = tempdir().unwrap();
+            let image_dir = dir.path().join("image");
+            let ci = Arc::new(CrashInjector::after_writes(n, &image_dir));
+            let (committed, _) = run_workload(&dir.path().join("db"), "crash_test.log", ci.clone());
+            assert!(ci.is_crashed());
+            reopen_and_verify(&image_dir, "crash_test.log", |db| {
+                verify_workload(db, committed)
+            });
+            dir.close().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_crash_at_lsn() {
+        // NOTE: a dry run finds the LSNs of the records
+        let dir = tempdir().unwrap();
+        let ci = Arc::new(CrashInjector::after_writes(usize::MAX, dir.path()));
+        let (committed, lsns) = run_workload(&dir.path().join("db"), "crash_test.log", ci.clone());
+        assert!(!ci.is_crashed());
+        assert_eq!(committed, NUM_TXS);
+        dir.close().unwrap();
+
+        // NOTE: crashes at the first and the last record, and at four in between
+        let (first, last) = lsns.into_inner();
+        for lsn in (0..=5).map(|k| first + (last - first) * k / 5) {
+            let dir = tempdir().unwrap();
+            let image_dir = dir.path().join("image");
+            let ci = Arc::new(CrashInjector::at_lsn(lsn, &image_dir));
+            let (committed, _) = run_workload(&dir.path().join("db"), "crash_test.log", ci.clone());
+            assert!(ci.is_crashed());
+            reopen_and_verify(&image_dir, "crash_test.log", |db| {
+                verify_workload(db, committed)
+            });
+            dir.close().unwrap();
+        }
+    }
+}
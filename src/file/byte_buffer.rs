@@ -28,6 +28,19 @@ pub trait ByteBuffer {
     fn get_i32_from(&self, pos: usize) -> Result<i32>;
     fn put_i32_to(&mut self, pos: usize, n: i32) -> Result<()>;
 
+    fn get_i64_from(&self, pos: usize) -> Result<i64>;
+    fn put_i64_to(&mut self, pos: usize, n: i64) -> Result<()>;
+
+    fn get_f64_from(&self, pos: usize) -> Result<f64> {
+        Ok(f64::from_bits(self.get_i64_from(pos)? as u64))
+    }
+    fn put_f64_to(&mut self, pos: usize, n: f64) -> Result<()> {
+        self.put_i64_to(pos, n.to_bits() as i64)
+    }
+
+    fn get_bytes_from(&self, pos: usize, len: usize) -> Result<&[u8]>;
+    fn put_bytes_to(&mut self, pos: usize, src: &[u8]) -> Result<()>;
+
     fn get_i32(&mut self) -> Result<i32>;
     fn put_i32(&mut self, n: i32) -> Result<()>;
     fn get(&mut self, dst: &mut [u8]) -> Result<()>;
@@ -79,6 +92,27 @@ impl ByteBuffer for AllocatedBuffer {
         Ok(self.buf.write_with(&mut pos, n, BE)?)
     }
 
+    fn get_i64_from(&self, mut pos: usize) -> Result<i64> {
+        check_len(&self.buf, pos + 8)?;
+        Ok(self.buf.read_with(&mut pos, BE)?)
+    }
+
+    fn put_i64_to(&mut self, mut pos: usize, n: i64) -> Result<()> {
+        check_len(&self.buf, pos + 8)?;
+        Ok(self.buf.write_with(&mut pos, n, BE)?)
+    }
+
+    fn get_bytes_from(&self, pos: usize, len: usize) -> Result<&[u8]> {
+        check_len(&self.buf, pos + len)?;
+        Ok(&self.buf[pos..pos + len])
+    }
+
+    fn put_bytes_to(&mut self, pos: usize, src: &[u8]) -> Result<()> {
+        check_len(&self.buf, pos + src.len())?;
+        self.buf[pos..pos + src.len()].copy_from_slice(src);
+        Ok(())
+    }
+
     fn get_i32(&mut self) -> Result<i32> {
         check_len(&self.buf, self.pos + 4)?;
         Ok(self.buf.read_with(&mut self.pos, BE)?)
@@ -144,6 +178,27 @@ impl<'a> ByteBuffer for WrappedBuffer<'a> {
         Ok(self.buf.write_with(&mut pos, n, BE)?)
     }
 
+    fn get_i64_from(&self, mut pos: usize) -> Result<i64> {
+        check_len(self.buf, pos + 8)?;
+        Ok(self.buf.read_with(&mut pos, BE)?)
+    }
+
+    fn put_i64_to(&mut self, mut pos: usize, n: i64) -> Result<()> {
+        check_len(self.buf, pos + 8)?;
+        Ok(self.buf.write_with(&mut pos, n, BE)?)
+    }
+
+    fn get_bytes_from(&self, pos: usize, len: usize) -> Result<&[u8]> {
+        check_len(self.buf, pos + len)?;
+        Ok(&self.buf[pos..pos + len])
+    }
+
+    fn put_bytes_to(&mut self, pos: usize, src: &[u8]) -> Result<()> {
+        check_len(self.buf, pos + src.len())?;
+        self.buf[pos..pos + src.len()].copy_from_slice(src);
+        Ok(())
+    }
+
     fn get_i32(&mut self) -> Result<i32> {
         check_len(self.buf, self.pos + 4)?;
         Ok(self.buf.read_with(&mut self.pos, BE)?)
@@ -194,6 +249,18 @@ mod tests {
         test_put_and_get_i32(&mut b)
     }
 
+    #[test]
+    fn test_allocated_buffer_uses_i64_and_f64() -> Result<()> {
+        let mut b = AllocatedBuffer::new(20);
+        test_put_and_get_i64_and_f64(&mut b)
+    }
+
+    #[test]
+    fn test_allocated_buffer_uses_bytes_at() -> Result<()> {
+        let mut b = AllocatedBuffer::new(10);
+        test_put_and_get_bytes_at(&mut b)
+    }
+
     #[test]
     fn test_allocated_buffer_uses_bytes() -> Result<()> {
         let mut b = AllocatedBuffer::new(10);
@@ -226,6 +293,28 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_wrapped_buffer_uses_i64_and_f64() -> Result<()> {
+        let mut buf = [0u8; 20];
+        {
+            let mut b = WrappedBuffer::new(&mut buf);
+            test_put_and_get_i64_and_f64(&mut b)?;
+        }
+        assert_eq!(buf[..8], [0x01, 0x23, 0x45, 0x67, 0x89, 0xab, 0xcd, 0xef]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_wrapped_buffer_uses_bytes_at() -> Result<()> {
+        let mut buf = [0u8; 10];
+        {
+            let mut b = WrappedBuffer::new(&mut buf);
+            test_put_and_get_bytes_at(&mut b)?;
+        }
+        assert_eq!(buf, [0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x0, 0x7, 0x8, 0x9]);
+        Ok(())
+    }
+
     #[test]
     fn test_wrapped_buffer_uses_bytes() -> Result<()> {
         let mut buf = [0u8; 10];
@@ -268,6 +357,43 @@ mod tests {
         Ok(())
     }
 
+    fn test_put_and_get_i64_and_f64<'a, B>(b: &mut B) -> Result<()>
+    where
+        B: ByteBuffer,
+    {
+        b.put_i64_to(0, 0x0123456789abcdef)?;
+        b.put_f64_to(8, -1.5)?;
+        assert_eq!(b.get_position(), 0);
+
+        assert_eq!(b.get_i64_from(0)?, 0x0123456789abcdef);
+        assert_eq!(b.get_f64_from(8)?, -1.5);
+        assert_eq!(b.get_i64_from(1)?, 0x23456789abcdefbf);
+
+        assert!(b.put_i64_to(13, 0).is_err());
+        assert!(b.get_f64_from(13).is_err());
+        Ok(())
+    }
+
+    fn test_put_and_get_bytes_at<'a, B>(b: &mut B) -> Result<()>
+    where
+        B: ByteBuffer,
+    {
+        b.put_bytes_to(5, &[1, 2, 3, 4, 5])?;
+        assert_eq!(b.get_position(), 0);
+        assert_eq!(b.get_bytes_from(4, 3)?, [0, 1, 2]);
+
+        b.put_bytes_to(7, &[7, 8, 9])?;
+        assert_eq!(b.get_bytes_from(5, 5)?, [1, 2, 7, 8, 9]);
+        assert_eq!(b.get_bytes_from(10, 0)?, []);
+
+        assert!(b.put_bytes_to(8, &[0, 0, 0]).is_err());
+        assert!(b.get_bytes_from(8, 3).is_err());
+        assert_eq!(b.get_bytes_from(8, 2)?, [8, 9]);
+
+        b.put_bytes_to(5, &[0, 0])?;
+        Ok(())
+    }
+
     fn test_put_and_get_bytes<'a, B>(b: &mut B) -> Result<()>
     where
         B: ByteBuffer,
@@ -8,49 +8,106 @@ use super::{
     page::{Page, PageError},
     random_access_file::FileError,
 };
+#[cfg(any(test, feature = "crash-injection"))]
+use crate::crash::CrashInjector;
 use crate::file::block_id::BlockId;
 use crate::file::random_access_file::RandomAccessFile;
+use memmap2::{MmapMut, MmapOptions};
 use std::{
-    collections::{hash_map::Entry, HashMap},
+    collections::HashMap,
     fs::{self, File},
     io::{Seek, SeekFrom, Write},
     num::TryFromIntError,
     path::{Path, PathBuf},
-    sync::Mutex,
+    sync::{
+        mpsc::{channel, Sender},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
 };
 use thiserror::Error;
 
+const MAX_PREFETCHED: usize = 32;
+
+pub const PAGE_CHECKSUM_SIZE: usize = 4;
+
 #[derive(Debug, Error)]
 pub enum FileMgrError {
-    #[error("{0:?}")]
+    #[error("{0}")]
     IO(#[from] std::io::Error),
 
-    #[error("{0:?}")]
+    #[error("{0}")]
     File(#[from] FileError),
 
-    #[error("{0:?}")]
+    #[error("{0}")]
     Byte(#[from] ByteBufferError),
 
-    #[error("{0:?}")]
+    #[error("{0}")]
     Page(#[from] PageError),
+
+    #[error("corrupted page: file {}, block {}", .0.filename(), .0.number())]
+    CorruptPage(BlockId),
+
+    #[error("unknown tablespace: {0}")]
+    UnknownTablespace(String),
 }
 
 pub type Result<T> = core::result::Result<T, FileMgrError>;
 
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SyncMode {
+    Off,
+    #[default]
+    FlushOnCommit,
+    FsyncOnCommit,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum StorageBackend {
+    #[default]
+    File,
+    Mmap,
+}
+
 pub struct FileMgr {
+    // NOTE: without the checksum
     blocksize: usize,
     is_new: bool,
-    data: Mutex<FileMgrData>,
+    data: Arc<Mutex<FileMgrData>>,
+    prefetcher: Mutex<Option<(Sender<BlockId>, JoinHandle<()>)>>,
+}
+
+struct FileMap {
+    mmap: MmapMut,
+    len: usize,
 }
 
 struct FileMgrData {
     db_dir_path: PathBuf,
+    // NOTE: tablespace -> directory, and file -> tablespace
+    tablespaces: HashMap<String, PathBuf>,
+    placements: HashMap<String, String>,
+    // NOTE: with the checksum
     blocksize: usize,
     open_files: HashMap<String, File>,
+    backend: StorageBackend,
+    // NOTE: for StorageBackend::Mmap
+    mmaps: HashMap<String, FileMap>,
+    sync_mode: SyncMode,
+    // NOTE: dropped when the block is written
+    prefetched: HashMap<BlockId, Vec<u8>>,
+    prefetched_reads: usize,
+    #[cfg(any(test, feature = "crash-injection"))]
+    crash_injector: Option<Arc<CrashInjector>>,
 }
 
 impl FileMgr {
     pub fn new(db_dir_path: &Path, blocksize: usize) -> Self {
+        Self::new_with_backend(db_dir_path, blocksize, StorageBackend::default())
+    }
+
+    pub fn new_with_backend(db_dir_path: &Path, blocksize: usize, backend: StorageBackend) -> Self {
+        assert!(blocksize > PAGE_CHECKSUM_SIZE);
         if !db_dir_path.exists() {
             fs::create_dir_all(db_dir_path).expect("failed to create db directory");
         }
@@ -66,9 +123,42 @@ impl FileMgr {
         }
         let is_new = FileMgr::is_empty_dir(db_dir_path);
         FileMgr {
-            blocksize,
+            blocksize: blocksize - PAGE_CHECKSUM_SIZE,
             is_new,
-            data: Mutex::new(FileMgrData::new(db_dir_path.to_path_buf(), blocksize)),
+            data: Arc::new(Mutex::new(FileMgrData::new(
+                db_dir_path.to_path_buf(),
+                blocksize,
+                backend,
+            ))),
+            prefetcher: Mutex::new(None),
+        }
+    }
+
+    pub(crate) fn destroy(&self) -> Result<()> {
+        self.stop_prefetcher();
+        let mut data = self.data.lock().unwrap();
+        data.mmaps.clear();
+        data.open_files.clear();
+        data.prefetched.clear();
+        for filename in data.placements.keys() {
+            let path = data.path_of(filename);
+            if path.exists() {
+                fs::remove_file(path)?;
+            }
+        }
+        for dir in data.tablespaces.values() {
+            if !dir.starts_with(&data.db_dir_path) && FileMgr::is_empty_dir(dir) {
+                fs::remove_dir(dir)?;
+            }
+        }
+        fs::remove_dir_all(&data.db_dir_path)?;
+        Ok(())
+    }
+
+    fn stop_prefetcher(&self) {
+        if let Some((tx, handle)) = self.prefetcher.lock().unwrap().take() {
+            drop(tx);
+            let _ = handle.join();
         }
     }
 
@@ -103,14 +193,171 @@ impl FileMgr {
         let mut data = self.data.lock().unwrap();
         data.length(filename)
     }
+
+    pub(crate) fn truncate(&self, filename: &str, num_blocks: u64) -> Result<()> {
+        let mut data = self.data.lock().unwrap();
+        data.truncate(filename, num_blocks)
+    }
+
+    pub fn backend(&self) -> StorageBackend {
+        self.data.lock().unwrap().backend
+    }
+
+    pub fn sync_mode(&self) -> SyncMode {
+        self.data.lock().unwrap().sync_mode
+    }
+
+    pub fn set_sync_mode(&self, mode: SyncMode) {
+        self.data.lock().unwrap().sync_mode = mode;
+    }
+
+    pub fn sync(&self, filename: &str) -> Result<()> {
+        let mut data = self.data.lock().unwrap();
+        if let Some(map) = data.mmaps.get(filename) {
+            map.mmap.flush_range(0, map.len)?;
+        }
+        Ok(data.get_file(filename)?.sync_all()?)
+    }
+
+    pub fn sync_all(&self) -> Result<()> {
+        let data = self.data.lock().unwrap();
+        for map in data.mmaps.values() {
+            map.mmap.flush_range(0, map.len)?;
+        }
+        for file in data.open_files.values() {
+            file.sync_all()?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn remove(&self, filename: &str) -> Result<()> {
+        let mut data = self.data.lock().unwrap();
+        data.remove(filename)
+    }
+
+    pub(crate) fn rename(&self, from: &str, to: &str) -> Result<()> {
+        let mut data = self.data.lock().unwrap();
+        data.rename(from, to)
+    }
+
+    pub(crate) fn is_empty(&self, filename: &str) -> Result<bool> {
+        let path = self.path_of(filename);
+        Ok(!path.exists() || fs::metadata(path)?.len() == 0)
+    }
+
+    pub fn add_tablespace(&self, name: &str, path: &Path) -> Result<()> {
+        let mut data = self.data.lock().unwrap();
+        let path = data.db_dir_path.join(path);
+        fs::create_dir_all(&path)?;
+        data.tablespaces.insert(name.to_string(), path);
+        Ok(())
+    }
+
+    pub fn place_file(&self, filename: &str, tablespace: &str) -> Result<()> {
+        let mut data = self.data.lock().unwrap();
+        if !data.tablespaces.contains_key(tablespace) {
+            return Err(FileMgrError::UnknownTablespace(tablespace.into()));
+        }
+        data.placements
+            .insert(filename.to_string(), tablespace.to_string());
+        Ok(())
+    }
+
+    pub(crate) fn unplace_file(&self, filename: &str) {
+        let mut data = self.data.lock().unwrap();
+        data.placements.remove(filename);
+    }
+
+    pub(crate) fn clear_placements(&self) {
+        let mut data = self.data.lock().unwrap();
+        data.placements.clear();
+    }
+
+    pub fn path_of(&self, filename: &str) -> PathBuf {
+        self.data.lock().unwrap().path_of(filename)
+    }
+
+    pub(crate) fn filenames(&self) -> Result<Vec<String>> {
+        let data = self.data.lock().unwrap();
+        let mut names = Vec::new();
+        for entry in fs::read_dir(&data.db_dir_path)? {
+            let entry = entry?;
+            if entry.file_type()?.is_file() {
+                names.extend(entry.file_name().to_str().map(|s| s.to_string()));
+            }
+        }
+        Ok(names)
+    }
+
+    pub fn prefetch(&self, blocks: impl IntoIterator<Item = BlockId>) {
+        let mut prefetcher = self.prefetcher.lock().unwrap();
+        let (tx, _) = prefetcher.get_or_insert_with(|| {
+            let (tx, rx) = channel::<BlockId>();
+            let data = self.data.clone();
+            let handle = thread::spawn(move || {
+                for block in rx {
+                    // NOTE: left to the read that follows
+                    let _ = data.lock().unwrap().read_ahead(&block);
+                }
+            });
+            (tx, handle)
+        });
+        for block in blocks {
+            let _ = tx.send(block);
+        }
+    }
+
+    pub fn prefetched_reads(&self) -> usize {
+        self.data.lock().unwrap().prefetched_reads
+    }
+
+    #[cfg(test)]
+    fn num_prefetched(&self) -> usize {
+        self.data.lock().unwrap().prefetched.len()
+    }
+
+    #[cfg(any(test, feature = "crash-injection"))]
+    pub fn set_crash_injector(&self, ci: Arc<CrashInjector>) {
+        self.data.lock().unwrap().crash_injector = Some(ci);
+    }
+
+    #[cfg(any(test, feature = "crash-injection"))]
+    pub(crate) fn on_log_append(&self, lsn: i64) {
+        let data = self.data.lock().unwrap();
+        if let Some(ci) = data.crash_injector.as_ref() {
+            ci.on_log_append(lsn, &data.db_dir_path);
+        }
+    }
+}
+
+impl Drop for FileMgr {
+    fn drop(&mut self) {
+        self.stop_prefetcher();
+    }
 }
 
 impl FileMgrData {
-    pub(crate) fn new(db_dir_path: PathBuf, blocksize: usize) -> Self {
+    pub(crate) fn new(db_dir_path: PathBuf, blocksize: usize, backend: StorageBackend) -> Self {
         Self {
             db_dir_path,
+            tablespaces: HashMap::new(),
+            placements: HashMap::new(),
             blocksize,
             open_files: HashMap::new(),
+            backend,
+            mmaps: HashMap::new(),
+            sync_mode: SyncMode::default(),
+            prefetched: HashMap::new(),
+            prefetched_reads: 0,
+            #[cfg(any(test, feature = "crash-injection"))]
+            crash_injector: None,
+        }
+    }
+
+    #[cfg(any(test, feature = "crash-injection"))]
+    fn before_write(&self) {
+        if let Some(ci) = self.crash_injector.as_ref() {
+            ci.before_write(&self.db_dir_path);
         }
     }
 
@@ -126,16 +373,50 @@ impl FileMgrData {
         }
     }
 
+    fn path_of(&self, filename: &str) -> PathBuf {
+        let dir = self
+            .placements
+            .get(filename)
+            .and_then(|ts| self.tablespaces.get(ts))
+            .unwrap_or(&self.db_dir_path);
+        dir.join(filename)
+    }
+
     fn get_file(&mut self, filename: &str) -> Result<&mut File> {
-        let file = match self.open_files.entry(filename.to_string()) {
-            Entry::Occupied(o) => o.into_mut(),
-            Entry::Vacant(v) => {
-                let path = self.db_dir_path.join(filename);
-                let file = Self::open_file(&path)?;
-                v.insert(file)
+        if !self.open_files.contains_key(filename) {
+            let file = Self::open_file(&self.path_of(filename))?;
+            self.open_files.insert(filename.to_string(), file);
+        }
+        Ok(self.open_files.get_mut(filename).unwrap())
+    }
+
+    fn get_mmap(&mut self, filename: &str) -> Result<Option<&mut FileMap>> {
+        if !self.mmaps.contains_key(filename) {
+            let file = self.get_file(filename)?;
+            let len = usize::try_from(file.metadata()?.len()).unwrap();
+            if len == 0 {
+                return Ok(None);
             }
-        };
-        Ok(file)
+            // SAFETY: only this file manager resizes the files, and it remaps them then
+            let mmap = unsafe { MmapOptions::new().len(len * 2).map_mut(&*file)? };
+            self.mmaps
+                .insert(filename.to_string(), FileMap { mmap, len });
+        }
+        Ok(self.mmaps.get_mut(filename))
+    }
+
+    fn resized(&mut self, filename: &str) -> Result<()> {
+        if !self.mmaps.contains_key(filename) {
+            return Ok(());
+        }
+        let len = usize::try_from(self.get_file(filename)?.metadata()?.len()).unwrap();
+        let map = self.mmaps.get_mut(filename).unwrap();
+        if len == 0 || len > map.mmap.len() {
+            self.mmaps.remove(filename);
+        } else {
+            map.len = len;
+        }
+        Ok(())
     }
 
     fn calc_seek_pos(
@@ -146,21 +427,117 @@ impl FileMgrData {
         Ok(SeekFrom::Start(block.number_as_u64() * blocksize))
     }
 
-    fn read(&mut self, block: &BlockId, page: &mut Page) -> Result<()> {
+    fn checksum(bytes: &[u8]) -> [u8; PAGE_CHECKSUM_SIZE] {
+        crc32fast::hash(&bytes[..bytes.len() - PAGE_CHECKSUM_SIZE]).to_be_bytes()
+    }
+
+    fn verify(block: &BlockId, bytes: &[u8]) -> Result<()> {
+        let stored = &bytes[bytes.len() - PAGE_CHECKSUM_SIZE..];
+        if stored == Self::checksum(bytes) || bytes.iter().all(|b| *b == 0) {
+            Ok(())
+        } else {
+            Err(FileMgrError::CorruptPage(block.clone()))
+        }
+    }
+
+    fn read_block(&mut self, block: &BlockId) -> Result<Vec<u8>> {
+        let mut bytes = vec![0u8; self.blocksize];
         let pos = FileMgrData::calc_seek_pos(self.blocksize, block).unwrap();
         let file = self.get_file(block.filename())?;
-        file.read_to(pos, page.contents()?)?;
+        file.read_to(pos, Page::for_log(&mut bytes).contents()?)?;
+        Ok(bytes)
+    }
+
+    fn read(&mut self, block: &BlockId, page: &mut Page) -> Result<()> {
+        if self.backend == StorageBackend::Mmap {
+            return self.read_mmap(block, page);
+        }
+        let bytes = match self.prefetched.remove(block) {
+            Some(bytes) => {
+                self.prefetched_reads += 1;
+                bytes
+            }
+            None => self.read_block(block)?,
+        };
+        Self::verify(block, &bytes)?;
+        page.contents()?
+            .put(&bytes[..self.blocksize - PAGE_CHECKSUM_SIZE])?;
+        Ok(())
+    }
+
+    fn read_mmap(&mut self, block: &BlockId, page: &mut Page) -> Result<()> {
+        let blocksize = self.blocksize;
+        let pos = usize::try_from(block.number()).unwrap() * blocksize;
+        match self.get_mmap(block.filename())? {
+            Some(map) if pos + blocksize <= map.len => {
+                let bytes = &map.mmap[pos..pos + blocksize];
+                Self::verify(block, bytes)?;
+                page.contents()?
+                    .put(&bytes[..blocksize - PAGE_CHECKSUM_SIZE])?;
+            }
+            // NOTE: beyond the end of the file
+            _ => page
+                .contents()?
+                .put(&vec![0u8; blocksize - PAGE_CHECKSUM_SIZE])?,
+        }
+        Ok(())
+    }
+
+    fn read_ahead(&mut self, block: &BlockId) -> Result<()> {
+        if self.backend == StorageBackend::Mmap {
+            // NOTE: the OS reads the mapped files ahead by itself
+            return Ok(());
+        }
+        if self.prefetched.len() >= MAX_PREFETCHED
+            || self.prefetched.contains_key(block)
+            || block.number_as_u64() >= self.length(block.filename())?
+        {
+            return Ok(());
+        }
+        let bytes = self.read_block(block)?;
+        self.prefetched.insert(block.clone(), bytes);
         Ok(())
     }
 
     fn write(&mut self, block: &BlockId, page: &mut Page) -> Result<()> {
+        #[cfg(any(test, feature = "crash-injection"))]
+        self.before_write();
+        self.prefetched.remove(block);
+        if self.backend == StorageBackend::Mmap {
+            return self.write_mmap(block, page);
+        }
+        let mut bytes = vec![0u8; self.blocksize];
+        let pagesize = self.blocksize - PAGE_CHECKSUM_SIZE;
+        page.contents()?.get(&mut bytes[..pagesize])?;
+        let checksum = Self::checksum(&bytes);
+        bytes[pagesize..].copy_from_slice(&checksum);
+
         let pos = FileMgrData::calc_seek_pos(self.blocksize, block).unwrap();
         let file = self.get_file(block.filename())?;
-        file.write_from(pos, page.contents()?)?;
+        file.write_from(pos, Page::for_log(&mut bytes).contents()?)?;
+        Ok(())
+    }
+
+    fn write_mmap(&mut self, block: &BlockId, page: &mut Page) -> Result<()> {
+        let blocksize = self.blocksize;
+        let pos = usize::try_from(block.number()).unwrap() * blocksize;
+        let file = self.get_file(block.filename())?;
+        if file.metadata()?.len() < u64::try_from(pos + blocksize).unwrap() {
+            file.set_len(u64::try_from(pos + blocksize).unwrap())?;
+            self.resized(block.filename())?;
+        }
+        let map = self.get_mmap(block.filename())?.unwrap();
+        let bytes = &mut map.mmap[pos..pos + blocksize];
+        page.contents()?
+            .get(&mut bytes[..blocksize - PAGE_CHECKSUM_SIZE])?;
+        let checksum = Self::checksum(bytes);
+        bytes[blocksize - PAGE_CHECKSUM_SIZE..].copy_from_slice(&checksum);
         Ok(())
     }
 
     fn append(&mut self, filename: &str) -> Result<BlockId> {
+        #[cfg(any(test, feature = "crash-injection"))]
+        self.before_write();
         let blocksize = self.blocksize;
         let newblocknum = self.length(filename)?.try_into().unwrap();
         let block = BlockId::new(filename, newblocknum);
@@ -172,6 +549,7 @@ impl FileMgrData {
         let b = vec![0u8; blocksize];
         file.write(&b)?;
 
+        self.resized(filename)?;
         Ok(block)
     }
 
@@ -181,6 +559,54 @@ impl FileMgrData {
         let file = self.get_file(filename)?;
         Ok(file.metadata()?.len() / blocksize)
     }
+
+    fn truncate(&mut self, filename: &str, num_blocks: u64) -> Result<()> {
+        #[cfg(any(test, feature = "crash-injection"))]
+        self.before_write();
+        self.prefetched
+            .retain(|b, _| b.filename() != filename || b.number_as_u64() < num_blocks);
+        let len = num_blocks * u64::try_from(self.blocksize).unwrap();
+        let file = self.get_file(filename)?;
+        if file.metadata()?.len() > len {
+            file.set_len(len)?;
+            self.resized(filename)?;
+        }
+        Ok(())
+    }
+
+    fn remove(&mut self, filename: &str) -> Result<()> {
+        #[cfg(any(test, feature = "crash-injection"))]
+        self.before_write();
+        self.prefetched.retain(|b, _| b.filename() != filename);
+        self.mmaps.remove(filename);
+        self.open_files.remove(filename);
+        let path = self.path_of(filename);
+        if path.exists() {
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+
+    fn rename(&mut self, from: &str, to: &str) -> Result<()> {
+        #[cfg(any(test, feature = "crash-injection"))]
+        self.before_write();
+        self.prefetched
+            .retain(|b, _| b.filename() != from && b.filename() != to);
+        for filename in [from, to] {
+            self.mmaps.remove(filename);
+            self.open_files.remove(filename);
+        }
+        let from_path = self.path_of(from);
+        if !from_path.exists() {
+            return Ok(());
+        }
+        match self.placements.remove(from) {
+            Some(tsname) => self.placements.insert(to.to_string(), tsname),
+            None => self.placements.remove(to),
+        };
+        fs::rename(from_path, self.path_of(to))?;
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -196,7 +622,7 @@ mod tests {
         let dir = tempdir().unwrap();
         let mut db =
             SimpleDB::new_for_test(dir.path().join("testdb").as_path(), "test_file_mgr.log");
-        db.init();
+        db.init().unwrap();
 
         let fm = db.file_mgr();
         {
@@ -228,7 +654,7 @@ mod tests {
         let dir = tempdir().unwrap();
         let mut db =
             SimpleDB::new_for_test(dir.path().join("testdb").as_path(), "test_file_mgr.log");
-        db.init();
+        db.init().unwrap();
 
         let fm = db.file_mgr();
         {
@@ -313,4 +739,181 @@ mod tests {
         db_dir.close()?;
         Ok(())
     }
+
+    #[test]
+    fn test_prefetch() -> Result<()> {
+        let db_dir = tempdir()?;
+        {
+            let fm = FileMgr::new(db_dir.path(), 400);
+            let blocks = (0..4)
+                .map(|i| BlockId::new(TEST_FILE, i))
+                .collect::<Vec<_>>();
+            for (i, block) in blocks.iter().enumerate() {
+                let mut p = Page::for_data(fm.blocksize());
+                p.set_i32(0, i as i32)?;
+                fm.write(block, &mut p)?;
+            }
+
+            // NOTE: block 10 is beyond the end of the file
+            fm.prefetch(blocks.iter().cloned().chain([BlockId::new(TEST_FILE, 10)]));
+            let begin = std::time::Instant::now();
+            while fm.num_prefetched() < 4 {
+                assert!(begin.elapsed().as_secs() < 10, "prefetch timed out");
+                std::thread::yield_now();
+            }
+
+            // NOTE: a write drops the contents read ahead
+            let mut p = Page::for_data(fm.blocksize());
+            p.set_i32(0, 100)?;
+            fm.write(&blocks[1], &mut p)?;
+
+            for (i, block) in blocks.iter().enumerate() {
+                let mut p = Page::for_data(fm.blocksize());
+                fm.read(block, &mut p)?;
+                let expected = if i == 1 { 100 } else { i as i32 };
+                assert_eq!(p.get_i32(0)?, expected);
+            }
+            assert_eq!(fm.prefetched_reads(), 3);
+            assert_eq!(fm.num_prefetched(), 0);
+        }
+        db_dir.close()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_corrupt_page() -> Result<()> {
+        let db_dir = tempdir()?;
+        let fm = FileMgr::new(db_dir.path(), 400);
+        assert_eq!(fm.blocksize(), 400 - PAGE_CHECKSUM_SIZE);
+
+        let block = BlockId::new(TEST_FILE, 1);
+        let mut p = Page::for_data(fm.blocksize());
+        p.set_string(10, "abc")?;
+        fm.write(&block, &mut p)?;
+
+        // NOTE: a block never written is all zero
+        let mut p = Page::for_data(fm.blocksize());
+        fm.read(&BlockId::new(TEST_FILE, 0), &mut p)?;
+        fm.read(&block, &mut p)?;
+        assert_eq!(p.get_string(10)?, "abc");
+
+        // NOTE: corrupt the block on the disk
+        {
+            let mut file = File::options()
+                .write(true)
+                .open(db_dir.path().join(TEST_FILE))?;
+            file.seek(SeekFrom::Start(400 + 15))?;
+            file.write_all(&[0xff])?;
+        }
+        let result = fm.read(&block, &mut Page::for_data(fm.blocksize()));
+        assert!(matches!(&result, Err(FileMgrError::CorruptPage(b)) if *b == block));
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            format!("corrupted page: file {}, block 1", TEST_FILE)
+        );
+
+        db_dir.close()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_rename() -> Result<()> {
+        let db_dir = tempdir()?;
+        let fm = FileMgr::new(db_dir.path(), 400);
+        fm.add_tablespace("ts", Path::new("ts"))?;
+        fm.place_file(TEST_FILE, "ts")?;
+
+        let mut p = Page::for_data(fm.blocksize());
+        p.set_string(10, "abc")?;
+        fm.write(&BlockId::new(TEST_FILE, 0), &mut p)?;
+        assert!(!fm.is_empty(TEST_FILE)?);
+
+        fm.rename(TEST_FILE, "renamed.db")?;
+        assert!(fm.is_empty(TEST_FILE)?);
+        assert!(!db_dir.path().join("ts").join(TEST_FILE).exists());
+        assert_eq!(
+            fm.path_of("renamed.db"),
+            db_dir.path().join("ts/renamed.db")
+        );
+        let mut p = Page::for_data(fm.blocksize());
+        fm.read(&BlockId::new("renamed.db", 0), &mut p)?;
+        assert_eq!(p.get_string(10)?, "abc");
+
+        fm.rename(TEST_FILE, "renamed.db")?;
+        assert_eq!(fm.length("renamed.db")?, 1);
+
+        db_dir.close()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_mmap_backend() -> Result<()> {
+        let db_dir = tempdir()?;
+        {
+            let fm = FileMgr::new_with_backend(db_dir.path(), 400, StorageBackend::Mmap);
+            let block = fm.append(TEST_FILE)?;
+            assert_eq!(block.number(), 0);
+
+            let mut p = Page::for_data(fm.blocksize());
+            p.set_string(10, "abc")?;
+            fm.write(&BlockId::new(TEST_FILE, 2), &mut p)?;
+            assert_eq!(fm.length(TEST_FILE)?, 3);
+            let mut p = Page::for_data(fm.blocksize());
+            fm.read(&BlockId::new(TEST_FILE, 5), &mut p)?;
+            assert_eq!(p.get_i32(10)?, 0);
+
+            fm.read(&BlockId::new(TEST_FILE, 2), &mut p)?;
+            assert_eq!(p.get_string(10)?, "abc");
+            assert_eq!(fm.append(TEST_FILE)?.number(), 3);
+            fm.sync_all()?;
+        }
+
+        // NOTE: the file grows into the map until it outgrows it
+        {
+            let fm = FileMgr::new_with_backend(db_dir.path(), 400, StorageBackend::Mmap);
+            let disk = fm.data.lock().unwrap().blocksize;
+            let mapped = |fm: &FileMgr| {
+                let data = fm.data.lock().unwrap();
+                data.mmaps
+                    .get(TEST_FILE)
+                    .map(|map| (map.len / disk, map.mmap.len() / disk))
+            };
+            let mut p = Page::for_data(fm.blocksize());
+            fm.read(&BlockId::new(TEST_FILE, 2), &mut p)?;
+            assert_eq!(mapped(&fm), Some((4, 8)));
+            for _ in 0..4 {
+                fm.append(TEST_FILE)?;
+            }
+            assert_eq!(mapped(&fm), Some((8, 8)));
+            fm.write(&BlockId::new(TEST_FILE, 7), &mut p)?;
+            fm.read(&BlockId::new(TEST_FILE, 7), &mut p)?;
+            assert_eq!(p.get_string(10)?, "abc");
+
+            fm.append(TEST_FILE)?;
+            assert_eq!(mapped(&fm), None);
+            fm.read(&BlockId::new(TEST_FILE, 7), &mut p)?;
+            assert_eq!(mapped(&fm), Some((9, 18)));
+
+            // NOTE: the blocks cut off read as zeros
+            fm.truncate(TEST_FILE, 3)?;
+            assert_eq!(mapped(&fm), Some((3, 18)));
+            fm.read(&BlockId::new(TEST_FILE, 7), &mut p)?;
+            assert_eq!(p.get_i32(10)?, 0);
+            fm.read(&BlockId::new(TEST_FILE, 2), &mut p)?;
+            assert_eq!(p.get_string(10)?, "abc");
+            fm.append(TEST_FILE)?;
+            fm.sync_all()?;
+        }
+
+        let fm = FileMgr::new(db_dir.path(), 400);
+        let mut p = Page::for_data(fm.blocksize());
+        fm.read(&BlockId::new(TEST_FILE, 2), &mut p)?;
+        assert_eq!(p.get_string(10)?, "abc");
+        assert_eq!(fm.length(TEST_FILE)?, 4);
+        fm.read(&BlockId::new(TEST_FILE, 3), &mut p)?;
+        assert_eq!(p.get_i32(10)?, 0);
+
+        db_dir.close()?;
+        Ok(())
+    }
 }
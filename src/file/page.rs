@@ -4,17 +4,17 @@
 // https://opensource.org/licenses/MIT
 
 use super::byte_buffer::{AllocatedBuffer, ByteBuffer, ByteBufferError, WrappedBuffer};
-use std::string::FromUtf8Error;
+use std::str::{self, Utf8Error};
 use std::vec;
 use thiserror::Error;
 
 #[derive(Debug, Error)]
 pub enum PageError {
-    #[error("{0:?}")]
+    #[error("{0}")]
     Byte(#[from] ByteBufferError),
 
-    #[error("{0:?}")]
-    InvalidUtf8(#[from] FromUtf8Error),
+    #[error("{0}")]
+    InvalidUtf8(#[from] Utf8Error),
 }
 
 pub type Result<T> = core::result::Result<T, PageError>;
@@ -44,6 +44,30 @@ impl<'a> Page<'a> {
         Ok(self.buf.get_i32_from(offset)?)
     }
 
+    pub fn set_i64(&mut self, offset: usize, n: i64) -> Result<()> {
+        Ok(self.buf.put_i64_to(offset, n)?)
+    }
+
+    pub fn get_i64(&self, offset: usize) -> Result<i64> {
+        Ok(self.buf.get_i64_from(offset)?)
+    }
+
+    pub fn set_f64(&mut self, offset: usize, n: f64) -> Result<()> {
+        Ok(self.buf.put_f64_to(offset, n)?)
+    }
+
+    pub fn get_f64(&self, offset: usize) -> Result<f64> {
+        Ok(self.buf.get_f64_from(offset)?)
+    }
+
+    pub fn set_raw_bytes(&mut self, offset: usize, bytes: &[u8]) -> Result<()> {
+        Ok(self.buf.put_bytes_to(offset, bytes)?)
+    }
+
+    pub fn get_raw_bytes(&self, offset: usize, len: usize) -> Result<&[u8]> {
+        Ok(self.buf.get_bytes_from(offset, len)?)
+    }
+
     pub fn set_bytes(&mut self, offset: usize, bytes: &[u8]) -> Result<()> {
         self.buf.set_position(offset)?;
         self.buf.put_i32(bytes.len().try_into().unwrap())?;
@@ -64,12 +88,14 @@ impl<'a> Page<'a> {
         self.set_bytes(offset, bs)
     }
 
-    pub fn get_string(&mut self, offset: usize) -> Result<String> {
-        let bs = self.get_bytes(offset)?;
-        match String::from_utf8(bs) {
-            Ok(str) => Ok(str),
-            Err(err) => Err(PageError::InvalidUtf8(err)),
-        }
+    pub fn get_string(&self, offset: usize) -> Result<String> {
+        Ok(self.get_str(offset)?.to_owned())
+    }
+
+    pub fn get_str(&self, offset: usize) -> Result<&str> {
+        let len = self.buf.get_i32_from(offset)?.try_into().unwrap();
+        let bs = self.buf.get_bytes_from(offset + 4, len)?;
+        Ok(str::from_utf8(bs)?)
     }
 
     pub(crate) fn max_length(strlen: usize) -> usize {
@@ -95,7 +121,7 @@ mod tests {
         assert_eq!(result.is_err(), true);
         assert_eq!(
             result.unwrap_err().to_string(),
-            format!("{:?}", ByteBufferError(byte::Error::Incomplete))
+            ByteBufferError(byte::Error::Incomplete).to_string()
         );
     }
 
@@ -109,6 +135,19 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_for_data_set_and_get_i64_and_f64() -> Result<()> {
+        let mut p = Page::for_data(20);
+
+        p.set_i64(1, -0x123456789abc)?;
+        p.set_f64(9, 3.25)?;
+
+        assert_eq!(p.get_i64(1)?, -0x123456789abc);
+        assert_eq!(p.get_f64(9)?, 3.25);
+        assert_eq!(p.set_i64(13, 0).is_err(), true);
+        Ok(())
+    }
+
     #[test]
     fn test_for_data_set_and_get_bytes() -> Result<()> {
         let mut p = Page::for_data(10);
@@ -120,6 +159,19 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_for_data_set_and_get_raw_bytes() -> Result<()> {
+        let mut p = Page::for_data(10);
+
+        p.set_raw_bytes(6, &[0x1, 0x2, 0x3, 0x4])?;
+
+        assert_eq!(p.get_raw_bytes(5, 3)?, [0x0, 0x1, 0x2]);
+        assert_eq!(p.get_i32(6)?, 0x01020304);
+        assert_eq!(p.set_raw_bytes(8, &[0x0; 3]).is_err(), true);
+        assert_eq!(p.get_raw_bytes(7, 4).is_err(), true);
+        Ok(())
+    }
+
     #[test]
     fn test_for_data_set_and_get_string() -> Result<()> {
         let mut p = Page::for_data(40);
@@ -129,6 +181,18 @@ mod tests {
 
         assert_eq!(p.get_string(0)?, "abcd");
         assert_eq!(p.get_string(20)?, "efgh");
+        assert_eq!(p.get_str(20)?, "efgh");
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_str_invalid_utf8() -> Result<()> {
+        let mut p = Page::for_data(10);
+
+        p.set_bytes(0, &[0xff, 0xfe])?;
+
+        assert!(matches!(p.get_str(0), Err(PageError::InvalidUtf8(_))));
+        assert!(p.get_str(8).is_err());
         Ok(())
     }
 }
@@ -10,10 +10,10 @@ use thiserror::Error;
 
 #[derive(Debug, Error)]
 pub enum FileError {
-    #[error("{0:?}")]
+    #[error("{0}")]
     IO(#[from] std::io::Error),
 
-    #[error("{0:?}")]
+    #[error("{0}")]
     Byte(#[from] ByteBufferError),
 }
 
@@ -0,0 +1,157 @@
+// Copyright (c) 2023 Sho Kuroda <krdlab@gmail.com>
+//
+// This software is released under the MIT License.
+// https://opensource.org/licenses/MIT
+
+use super::{btree_page::BTreePage, KeyOrder, Result};
+use crate::{
+    buffer_mgr::PinPriority, file::block_id::BlockId, query::predicate::Constant,
+    record::schema::Layout, tx::transaction::Transaction,
+};
+use std::{
+    cmp::Ordering,
+    sync::{Arc, Mutex},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DirEntry {
+    data_val: Constant,
+    block_num: i32,
+}
+
+impl DirEntry {
+    pub fn new(data_val: Constant, block_num: i64) -> Self {
+        Self {
+            data_val,
+            block_num: block_num.try_into().unwrap(),
+        }
+    }
+
+    pub fn data_val(&self) -> &Constant {
+        &self.data_val
+    }
+
+    pub fn block_num(&self) -> i32 {
+        self.block_num
+    }
+}
+
+// NOTE: the flag of a directory page is its level
+pub struct BTreeDir {
+    tx: Arc<Mutex<Transaction>>,
+    layout: Layout,
+    order: KeyOrder,
+    contents: BTreePage,
+}
+
+impl BTreeDir {
+    pub fn new(
+        tx: Arc<Mutex<Transaction>>,
+        block: BlockId,
+        layout: Layout,
+        order: KeyOrder,
+    ) -> Result<Self> {
+        BTreeDir::new_with_priority(tx, block, layout, order, PinPriority::Normal)
+    }
+
+    pub fn new_with_priority(
+        tx: Arc<Mutex<Transaction>>,
+        block: BlockId,
+        layout: Layout,
+        order: KeyOrder,
+        priority: PinPriority,
+    ) -> Result<Self> {
+        let contents = BTreePage::new_with_priority(tx.clone(), block, layout.clone(), priority)?;
+        Ok(Self {
+            tx,
+            layout,
+            order,
+            contents,
+        })
+    }
+
+    pub fn close(&mut self) {
+        self.contents.close();
+    }
+
+    pub fn search(&mut self, search_key: &Constant) -> Result<i64> {
+        let mut child_block = self.find_child_block(search_key)?;
+        while self.contents.get_flag()? > 0 {
+            self.contents = BTreePage::new(self.tx.clone(), child_block, self.layout.clone())?;
+            child_block = self.find_child_block(search_key)?;
+        }
+        Ok(child_block.number())
+    }
+
+    pub fn make_new_root(&mut self, e: &DirEntry) -> Result<()> {
+        let first_val = self.contents.get_data_val(0)?;
+        let level = self.contents.get_flag()?;
+        // NOTE: the root stays at block 0
+        let new_block = self.contents.split(0, level)?;
+        self.contents
+            .insert_dir(0, &first_val, new_block.number().try_into().unwrap())?;
+        self.contents.insert_dir(1, e.data_val(), e.block_num())?;
+        self.contents.set_flag(level + 1)
+    }
+
+    pub fn insert(&mut self, e: &DirEntry) -> Result<Option<DirEntry>> {
+        if self.contents.get_flag()? == 0 {
+            return self.insert_entry(e);
+        }
+        let child_block = self.find_child_block(e.data_val())?;
+        let mut child = BTreeDir::new(
+            self.tx.clone(),
+            child_block,
+            self.layout.clone(),
+            self.order,
+        )?;
+        let my_entry = child.insert(e)?;
+        child.close();
+        match my_entry {
+            Some(my_entry) => self.insert_entry(&my_entry),
+            None => Ok(None),
+        }
+    }
+
+    fn insert_entry(&mut self, e: &DirEntry) -> Result<Option<DirEntry>> {
+        let new_slot = 1 + self.find_slot_before(e.data_val())?;
+        self.contents
+            .insert_dir(new_slot, e.data_val(), e.block_num())?;
+        if !self.contents.is_full()? {
+            return Ok(None);
+        }
+        let level = self.contents.get_flag()?;
+        let split_pos = self.contents.get_num_recs()? / 2;
+        let split_val = self.contents.get_data_val(split_pos)?;
+        let new_block = self.contents.split(split_pos, level)?;
+        Ok(Some(DirEntry::new(split_val, new_block.number())))
+    }
+
+    fn find_slot_before(&self, search_key: &Constant) -> Result<i32> {
+        let num_recs = self.contents.get_num_recs()?;
+        let mut slot = 1;
+        while slot < num_recs
+            && self
+                .order
+                .compare(&self.contents.get_data_val(slot)?, search_key)
+                == Ordering::Less
+        {
+            slot += 1;
+        }
+        Ok(slot - 1)
+    }
+
+    fn find_child_block(&self, search_key: &Constant) -> Result<BlockId> {
+        let mut slot = self.find_slot_before(search_key)?;
+        if slot + 1 < self.contents.get_num_recs()?
+            && self.contents.get_data_val(slot + 1)? == *search_key
+        {
+            slot += 1;
+        }
+        let block_num = self.contents.get_child_num(slot)?;
+        Ok(BlockId::new(
+            self.contents.block().filename(),
+            block_num.into(),
+        ))
+    }
+}
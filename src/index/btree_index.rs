@@ -0,0 +1,551 @@
+// Copyright (c) 2023 Sho Kuroda <krdlab@gmail.com>
+//
+// This software is released under the MIT License.
+// https://opensource.org/licenses/MIT
+
+use super::{
+    btree_dir::{BTreeDir, DirEntry},
+    btree_leaf::BTreeLeaf,
+    btree_page::BTreePage,
+    Index, IndexError, KeyOrder, Result,
+};
+use crate::{
+    buffer_mgr::PinPriority,
+    file::block_id::BlockId,
+    query::{
+        predicate::Constant,
+        scan::{Scan, RID},
+    },
+    record::{
+        schema::{Layout, Schema, SqlType},
+        table_scan::TableScan,
+    },
+    tx::transaction::Transaction,
+};
+use std::sync::{Arc, Mutex};
+
+pub struct BTreeIndex {
+    tx: Arc<Mutex<Transaction>>,
+    dir_layout: Layout,
+    leaf_layout: Layout,
+    order: KeyOrder,
+    leaf_file: String,
+    leaf: Option<BTreeLeaf>,
+    root_block: BlockId,
+}
+
+impl BTreeIndex {
+    pub fn filenames(index_name: &str) -> (String, String) {
+        (
+            TableScan::filename(&format!("{index_name}leaf")),
+            TableScan::filename(&format!("{index_name}dir")),
+        )
+    }
+
+    pub fn new(
+        tx: Arc<Mutex<Transaction>>,
+        index_name: &str,
+        leaf_layout: Layout,
+        order: KeyOrder,
+    ) -> Result<Self> {
+        let (leaf_file, dir_file) = BTreeIndex::filenames(index_name);
+
+        if tx.lock().unwrap().size(&leaf_file)? == 0 {
+            let block = tx.lock().unwrap().append(&leaf_file)?;
+            let node = BTreePage::new(tx.clone(), block.clone(), leaf_layout.clone())?;
+            node.format(&block, -1)?;
+        }
+
+        let dir_layout = {
+            let mut schema = Schema::new();
+            schema.add_field_from("block", leaf_layout.schema());
+            schema.add_field_from("dataval", leaf_layout.schema());
+            Layout::new(schema)
+        };
+        let root_block = BlockId::new(&dir_file, 0);
+        if tx.lock().unwrap().size(&dir_file)? == 0 {
+            tx.lock().unwrap().append(&dir_file)?;
+            let mut node = BTreePage::new_with_priority(
+                tx.clone(),
+                root_block.clone(),
+                dir_layout.clone(),
+                PinPriority::Keep,
+            )?;
+            node.format(&root_block, 0)?;
+            // NOTE: the key of the first entry is never compared
+            let min_val = BTreeIndex::min_val(&dir_layout);
+            node.insert_dir(0, &min_val, 0)?;
+        }
+
+        Ok(Self {
+            tx,
+            dir_layout,
+            leaf_layout,
+            order,
+            leaf_file,
+            leaf: None,
+            root_block,
+        })
+    }
+
+    pub fn search_cost(num_blocks: usize, rec_per_blk: usize) -> usize {
+        if num_blocks <= 1 || rec_per_blk <= 1 {
+            return 1;
+        }
+        1 + ((num_blocks as f64).ln() / (rec_per_blk as f64).ln()) as usize
+    }
+
+    pub fn height(&self) -> Result<usize> {
+        let root = BTreePage::new_with_priority(
+            self.tx.clone(),
+            self.root_block.clone(),
+            self.dir_layout.clone(),
+            PinPriority::Keep,
+        )?;
+        Ok(usize::try_from(root.get_flag()?).unwrap() + 2)
+    }
+
+    pub fn num_leaves(&self) -> Result<usize> {
+        Ok(self
+            .tx
+            .lock()
+            .unwrap()
+            .size(&self.leaf_file)?
+            .try_into()
+            .unwrap())
+    }
+
+    fn min_val(dir_layout: &Layout) -> Constant {
+        if dir_layout.schema().field_type("dataval") == Some(SqlType::Integer) {
+            Constant::Int(i32::MIN)
+        } else {
+            Constant::String("".into())
+        }
+    }
+
+    fn is_empty(&self) -> Result<bool> {
+        if self.tx.lock().unwrap().size(&self.leaf_file)? > 1 {
+            return Ok(false);
+        }
+        let block = BlockId::new(&self.leaf_file, 0);
+        let leaf = BTreePage::new(self.tx.clone(), block, self.leaf_layout.clone())?;
+        Ok(leaf.get_num_recs()? == 0)
+    }
+
+    fn load_leaves(&mut self, src: &mut dyn Scan) -> Result<Vec<DirEntry>> {
+        let mut entries = vec![DirEntry::new(BTreeIndex::min_val(&self.dir_layout), 0)];
+        let block = BlockId::new(&self.leaf_file, 0);
+        let mut page = BTreePage::new(self.tx.clone(), block, self.leaf_layout.clone())?;
+        let mut last_key: Option<Constant> = None;
+        let mut in_overflow = false;
+
+        src.before_first()?;
+        while src.next()? {
+            let key = src.get_val("dataval")?;
+            let block_num = src.get_i32("block")?;
+            let id = src.get_i32("id")?;
+
+            let new_key = last_key.as_ref() != Some(&key);
+            // NOTE: an overflow page holds a single key
+            if !page.has_room()? || (in_overflow && new_key) {
+                let new_block = if new_key {
+                    in_overflow = false;
+                    let new_block = page.append_new(-1)?;
+                    entries.push(DirEntry::new(key.clone(), new_block.number()));
+                    new_block
+                } else if page.get_data_val(0)? == key {
+                    let new_block = page.append_new(-1)?;
+                    page.set_flag(new_block.number().try_into().unwrap())?;
+                    in_overflow = true;
+                    new_block
+                } else {
+                    // NOTE: a key must stay in one leaf
+                    let mut split_pos = page.get_num_recs()? - 1;
+                    while page.get_data_val(split_pos - 1)? == key {
+                        split_pos -= 1;
+                    }
+                    let new_block = page.split(split_pos, -1)?;
+                    entries.push(DirEntry::new(key.clone(), new_block.number()));
+                    new_block
+                };
+                page = BTreePage::new(self.tx.clone(), new_block, self.leaf_layout.clone())?;
+            }
+
+            let slot = page.get_num_recs()?;
+            page.insert_leaf(slot, &key, &RID::new(block_num.into(), Some(id)))?;
+            last_key = Some(key);
+        }
+        Ok(entries)
+    }
+
+    fn load_directory(&mut self, mut entries: Vec<DirEntry>) -> Result<()> {
+        let mut root = BTreePage::new_with_priority(
+            self.tx.clone(),
+            self.root_block.clone(),
+            self.dir_layout.clone(),
+            PinPriority::Keep,
+        )?;
+        let mut level = 0;
+        loop {
+            root.set_flag(level)?;
+            while root.get_num_recs()? > 0 {
+                root.delete(root.get_num_recs()? - 1)?;
+            }
+
+            let mut rest = entries.into_iter().peekable();
+            while let Some(e) = rest.peek() {
+                if !root.has_room()? {
+                    break;
+                }
+                let slot = root.get_num_recs()?;
+                root.insert_dir(slot, e.data_val(), e.block_num())?;
+                rest.next();
+            }
+            if rest.peek().is_none() {
+                return Ok(());
+            }
+
+            // NOTE: the entries don't fit in the root
+            let first_val = root.get_data_val(0)?;
+            let new_block = root.split(0, level)?;
+            let mut parents = vec![DirEntry::new(first_val, new_block.number())];
+            let mut page = BTreePage::new(self.tx.clone(), new_block, self.dir_layout.clone())?;
+            for e in rest {
+                if !page.has_room()? {
+                    let new_block = page.append_new(level)?;
+                    parents.push(DirEntry::new(e.data_val().clone(), new_block.number()));
+                    page = BTreePage::new(self.tx.clone(), new_block, self.dir_layout.clone())?;
+                }
+                let slot = page.get_num_recs()?;
+                page.insert_dir(slot, e.data_val(), e.block_num())?;
+            }
+            entries = parents;
+            level += 1;
+        }
+    }
+}
+
+impl Index for BTreeIndex {
+    fn before_first(&mut self, search_key: &Constant) -> Result<()> {
+        self.close();
+        let mut root = BTreeDir::new_with_priority(
+            self.tx.clone(),
+            self.root_block.clone(),
+            self.dir_layout.clone(),
+            self.order,
+            PinPriority::Keep,
+        )?;
+        let block_num = root.search(search_key)?;
+        root.close();
+        let leaf_block = BlockId::new(&self.leaf_file, block_num);
+        self.leaf = Some(BTreeLeaf::new(
+            self.tx.clone(),
+            leaf_block,
+            self.leaf_layout.clone(),
+            self.order,
+            search_key,
+        )?);
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<bool> {
+        self.leaf.as_mut().ok_or(IndexError::NotPositioned)?.next()
+    }
+
+    fn get_data_rid(&self) -> Result<RID> {
+        self.leaf
+            .as_ref()
+            .ok_or(IndexError::NotPositioned)?
+            .get_data_rid()
+    }
+
+    fn insert(&mut self, data_val: &Constant, data_rid: &RID) -> Result<()> {
+        // NOTE: fail before a split has moved any entries
+        data_rid.block_number_i32()?;
+        self.before_first(data_val)?;
+        let e = self
+            .leaf
+            .as_mut()
+            .ok_or(IndexError::NotPositioned)?
+            .insert(data_rid)?;
+        self.close();
+
+        if let Some(e) = e {
+            let mut root = BTreeDir::new_with_priority(
+                self.tx.clone(),
+                self.root_block.clone(),
+                self.dir_layout.clone(),
+                self.order,
+                PinPriority::Keep,
+            )?;
+            if let Some(e2) = root.insert(&e)? {
+                root.make_new_root(&e2)?;
+            }
+            root.close();
+        }
+        Ok(())
+    }
+
+    fn delete(&mut self, data_val: &Constant, data_rid: &RID) -> Result<()> {
+        self.before_first(data_val)?;
+        self.leaf
+            .as_mut()
+            .ok_or(IndexError::NotPositioned)?
+            .delete(data_rid)?;
+        self.close();
+        Ok(())
+    }
+
+    fn bulk_load(&mut self, src: &mut dyn Scan) -> Result<()> {
+        self.close();
+        if !self.is_empty()? {
+            return Err(IndexError::NotEmpty);
+        }
+        let entries = self.load_leaves(src)?;
+        self.load_directory(entries)
+    }
+
+    fn close(&mut self) {
+        if let Some(mut leaf) = self.leaf.take() {
+            leaf.close();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BTreeIndex;
+    use crate::{
+        file::{block_id::BlockId, file_mgr::StorageBackend},
+        index::{btree_page::BTreePage, Index, IndexError, KeyOrder},
+        materialize::{
+            sort::{RecordComparator, SortScan},
+            temp_table::TempTable,
+        },
+        query::{
+            predicate::Constant,
+            scan::{Scan, ScanError, RID},
+        },
+        record::schema::{Layout, Schema},
+        server::simple_db::SimpleDB,
+    };
+    use tempfile::tempdir;
+
+    fn leaf_layout() -> Layout {
+        let mut schema = Schema::new();
+        schema.add_i32_field("block");
+        schema.add_i32_field("id");
+        schema.add_i32_field("dataval");
+        Layout::new(schema)
+    }
+
+    fn count(idx: &mut BTreeIndex, key: i32) -> usize {
+        let mut n = 0;
+        idx.before_first(&Constant::Int(key)).unwrap();
+        while idx.next().unwrap() {
+            n += 1;
+        }
+        n
+    }
+
+    #[test]
+    fn test_insert_and_delete() {
+        let dir = tempdir().unwrap();
+        {
+            // NOTE: enough entries that leaves and directory pages are split
+            let db = SimpleDB::builder()
+                .block_size(1024)
+                .open(dir.path())
+                .unwrap();
+            let tx = db.new_tx();
+            {
+                let mut idx =
+                    BTreeIndex::new(tx.clone(), "bidx", leaf_layout(), KeyOrder::Ascending)
+                        .unwrap();
+                for i in 0..5000 {
+                    let n = (i * 7) % 5000;
+                    idx.insert(&Constant::Int(n % 500), &RID::new(n.into(), Some(n)))
+                        .unwrap();
+                }
+                // NOTE: more duplicates than a leaf can hold
+                for i in 0..100 {
+                    idx.insert(&Constant::Int(25), &RID::new(1000, Some(i)))
+                        .unwrap();
+                }
+
+                for key in 0..500 {
+                    let expected = if key == 25 { 110 } else { 10 };
+                    assert_eq!(count(&mut idx, key), expected);
+                }
+                assert_eq!(count(&mut idx, 500), 0);
+
+                idx.delete(&Constant::Int(3), &RID::new(503, Some(503)))
+                    .unwrap();
+                idx.delete(&Constant::Int(25), &RID::new(1000, Some(99)))
+                    .unwrap();
+                assert_eq!(count(&mut idx, 3), 9);
+                assert_eq!(count(&mut idx, 25), 109);
+                idx.close();
+            }
+            tx.lock().unwrap().commit().unwrap();
+        }
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_bulk_load() {
+        let dir = tempdir().unwrap();
+        {
+            // NOTE: on memory maps
+            let db = SimpleDB::builder()
+                .block_size(1024)
+                .storage_backend(StorageBackend::Mmap)
+                .open(dir.path())
+                .unwrap();
+            let tx = db.new_tx();
+            {
+                let layout = leaf_layout();
+                let entries = TempTable::new(tx.clone(), layout.schema().clone());
+                {
+                    let mut ts = entries.open().unwrap();
+                    for i in 0..8000 {
+                        let n = (i * 7) % 8000;
+                        // NOTE: key 100 has more duplicates than a leaf can hold
+                        let key = if n < 100 { 100 } else { n / 4 };
+                        ts.insert().unwrap();
+                        ts.set_i32("dataval", key).unwrap();
+                        ts.set_i32("block", n).unwrap();
+                        ts.set_i32("id", n).unwrap();
+                    }
+                }
+                let comp = RecordComparator::new(vec!["dataval".into()]);
+                let mut sorted = SortScan::sort(
+                    tx.clone(),
+                    Box::new(entries.open().unwrap()),
+                    layout.schema(),
+                    &comp,
+                )
+                .unwrap();
+
+                let mut idx =
+                    BTreeIndex::new(tx.clone(), "bulkidx", layout, KeyOrder::Ascending).unwrap();
+                idx.bulk_load(&mut sorted).unwrap();
+
+                assert_eq!(count(&mut idx, 24), 0);
+                assert_eq!(count(&mut idx, 100), 104);
+                assert_eq!(count(&mut idx, 1999), 4);
+                assert_eq!(count(&mut idx, 2000), 0);
+                for key in 25..2000 {
+                    let expected = if key == 100 { 104 } else { 4 };
+                    assert_eq!(count(&mut idx, key), expected, "key={key}");
+                }
+
+                idx.before_first(&Constant::Int(300)).unwrap();
+                let mut rids = Vec::new();
+                while idx.next().unwrap() {
+                    rids.push(idx.get_data_rid().unwrap());
+                }
+                rids.sort_by_key(|rid| rid.block_number());
+                let expected: Vec<RID> =
+                    (1200..1204).map(|n| RID::new(n, Some(n as i32))).collect();
+                assert_eq!(rids, expected);
+
+                idx.insert(&Constant::Int(300), &RID::new(9999, Some(0)))
+                    .unwrap();
+                idx.delete(&Constant::Int(30), &RID::new(120, Some(120)))
+                    .unwrap();
+                assert_eq!(count(&mut idx, 300), 5);
+                assert_eq!(count(&mut idx, 30), 3);
+
+                assert!(matches!(
+                    idx.bulk_load(&mut sorted),
+                    Err(IndexError::NotEmpty)
+                ));
+                idx.close();
+                sorted.close();
+            }
+            tx.lock().unwrap().commit().unwrap();
+        }
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_descending() {
+        let dir = tempdir().unwrap();
+        {
+            let db = SimpleDB::builder()
+                .block_size(1024)
+                .open(dir.path())
+                .unwrap();
+            let tx = db.new_tx();
+            {
+                let mut idx =
+                    BTreeIndex::new(tx.clone(), "descidx", leaf_layout(), KeyOrder::Descending)
+                        .unwrap();
+                for i in 0..500 {
+                    let n = (i * 7) % 500;
+                    idx.insert(&Constant::Int(n % 50), &RID::new(n.into(), Some(n)))
+                        .unwrap();
+                }
+                for key in 0..50 {
+                    assert_eq!(count(&mut idx, key), 10);
+                }
+                assert_eq!(count(&mut idx, -1), 0);
+                assert_eq!(count(&mut idx, 50), 0);
+                let rid = RID::new(i64::from(i32::MAX) + 1, Some(0));
+                assert!(matches!(
+                    idx.insert(&Constant::Int(1), &rid),
+                    Err(IndexError::Scan(ScanError::BlockNumberOverflow(_)))
+                ));
+                assert_eq!(count(&mut idx, 1), 10);
+                idx.close();
+
+                // NOTE: the first leaf holds the largest keys
+                let block = BlockId::new("descidxleaf.tbl", 0);
+                let page = BTreePage::new(tx.clone(), block, leaf_layout()).unwrap();
+                assert_eq!(page.get_data_val(0).unwrap(), Constant::Int(49));
+            }
+            {
+                let layout = leaf_layout();
+                let entries = TempTable::new(tx.clone(), layout.schema().clone());
+                {
+                    let mut ts = entries.open().unwrap();
+                    for n in 0..1000 {
+                        ts.insert().unwrap();
+                        ts.set_i32("dataval", n / 2).unwrap();
+                        ts.set_i32("block", n).unwrap();
+                        ts.set_i32("id", n).unwrap();
+                    }
+                }
+                let comp = RecordComparator::new(vec!["dataval".into()]).reversed();
+                let mut sorted = SortScan::sort(
+                    tx.clone(),
+                    Box::new(entries.open().unwrap()),
+                    layout.schema(),
+                    &comp,
+                )
+                .unwrap();
+
+                let mut idx =
+                    BTreeIndex::new(tx.clone(), "descbulkidx", layout, KeyOrder::Descending)
+                        .unwrap();
+                idx.bulk_load(&mut sorted).unwrap();
+                for key in 0..500 {
+                    assert_eq!(count(&mut idx, key), 2, "key={key}");
+                }
+                assert_eq!(count(&mut idx, 500), 0);
+                idx.close();
+                sorted.close();
+            }
+            tx.lock().unwrap().commit().unwrap();
+        }
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_search_cost() {
+        assert_eq!(BTreeIndex::search_cost(0, 100), 1);
+        assert_eq!(BTreeIndex::search_cost(50, 100), 1);
+        assert_eq!(BTreeIndex::search_cost(1000, 100), 2);
+    }
+}
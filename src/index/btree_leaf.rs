@@ -0,0 +1,145 @@
+// Copyright (c) 2023 Sho Kuroda <krdlab@gmail.com>
+//
+// This software is released under the MIT License.
+// https://opensource.org/licenses/MIT
+
+use super::{btree_dir::DirEntry, btree_page::BTreePage, KeyOrder, Result};
+use crate::{
+    file::block_id::BlockId,
+    query::{predicate::Constant, scan::RID},
+    record::schema::Layout,
+    tx::transaction::Transaction,
+};
+use std::{
+    cmp::Ordering,
+    sync::{Arc, Mutex},
+};
+
+// NOTE: the flag of a leaf page is its overflow block, or -1
+pub struct BTreeLeaf {
+    tx: Arc<Mutex<Transaction>>,
+    layout: Layout,
+    order: KeyOrder,
+    search_key: Constant,
+    contents: BTreePage,
+    current_slot: i32,
+}
+
+impl BTreeLeaf {
+    pub fn new(
+        tx: Arc<Mutex<Transaction>>,
+        block: BlockId,
+        layout: Layout,
+        order: KeyOrder,
+        search_key: &Constant,
+    ) -> Result<Self> {
+        let contents = BTreePage::new(tx.clone(), block, layout.clone())?;
+        let current_slot = contents.find_slot_before(search_key, order)?;
+        Ok(Self {
+            tx,
+            layout,
+            order,
+            search_key: search_key.clone(),
+            contents,
+            current_slot,
+        })
+    }
+
+    pub fn close(&mut self) {
+        self.contents.close();
+    }
+
+    pub fn next(&mut self) -> Result<bool> {
+        self.current_slot += 1;
+        if self.current_slot >= self.contents.get_num_recs()? {
+            self.try_overflow()
+        } else if self.contents.get_data_val(self.current_slot)? == self.search_key {
+            Ok(true)
+        } else {
+            self.try_overflow()
+        }
+    }
+
+    pub fn get_data_rid(&self) -> Result<RID> {
+        self.contents.get_data_rid(self.current_slot)
+    }
+
+    pub fn delete(&mut self, data_rid: &RID) -> Result<()> {
+        while self.next()? {
+            if self.get_data_rid()? == *data_rid {
+                self.contents.delete(self.current_slot)?;
+                return Ok(());
+            }
+        }
+        Ok(())
+    }
+
+    pub fn insert(&mut self, data_rid: &RID) -> Result<Option<DirEntry>> {
+        if self.contents.get_flag()? >= 0
+            && self
+                .order
+                .compare(&self.contents.get_data_val(0)?, &self.search_key)
+                == Ordering::Greater
+        {
+            // NOTE: move the overflow chain to a new page
+            let first_val = self.contents.get_data_val(0)?;
+            let flag = self.contents.get_flag()?;
+            let new_block = self.contents.split(0, flag)?;
+            self.current_slot = 0;
+            self.contents.set_flag(-1)?;
+            self.contents
+                .insert_leaf(self.current_slot, &self.search_key, data_rid)?;
+            return Ok(Some(DirEntry::new(first_val, new_block.number())));
+        }
+
+        self.current_slot += 1;
+        self.contents
+            .insert_leaf(self.current_slot, &self.search_key, data_rid)?;
+        if !self.contents.is_full()? {
+            return Ok(None);
+        }
+
+        let first_key = self.contents.get_data_val(0)?;
+        let last_key = self
+            .contents
+            .get_data_val(self.contents.get_num_recs()? - 1)?;
+        if last_key == first_key {
+            // NOTE: create an overflow page to hold all but the first record
+            let flag = self.contents.get_flag()?;
+            let new_block = self.contents.split(1, flag)?;
+            self.contents
+                .set_flag(new_block.number().try_into().unwrap())?;
+            Ok(None)
+        } else {
+            let mut split_pos = self.contents.get_num_recs()? / 2;
+            let mut split_key = self.contents.get_data_val(split_pos)?;
+            if split_key == first_key {
+                while self.contents.get_data_val(split_pos)? == split_key {
+                    split_pos += 1;
+                }
+                split_key = self.contents.get_data_val(split_pos)?;
+            } else {
+                while self.contents.get_data_val(split_pos - 1)? == split_key {
+                    split_pos -= 1;
+                }
+            }
+            let new_block = self.contents.split(split_pos, -1)?;
+            Ok(Some(DirEntry::new(split_key, new_block.number())))
+        }
+    }
+
+    fn try_overflow(&mut self) -> Result<bool> {
+        let flag = self.contents.get_flag()?;
+        if flag < 0 {
+            return Ok(false);
+        }
+        // NOTE: an emptied page stays in its overflow chain
+        if self.contents.get_num_recs()? > 0 && self.contents.get_data_val(0)? != self.search_key {
+            return Ok(false);
+        }
+        let next_block = BlockId::new(self.contents.block().filename(), flag.into());
+        self.contents = BTreePage::new(self.tx.clone(), next_block, self.layout.clone())?;
+        self.current_slot = -1;
+        self.next()
+    }
+}
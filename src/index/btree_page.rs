@@ -0,0 +1,281 @@
+// Copyright (c) 2023 Sho Kuroda <krdlab@gmail.com>
+//
+// This software is released under the MIT License.
+// https://opensource.org/licenses/MIT
+
+use super::{KeyOrder, Result};
+use crate::{
+    buffer_mgr::PinPriority,
+    constants::I32_BYTE_SIZE,
+    file::block_id::BlockId,
+    query::{predicate::Constant, scan::RID},
+    record::schema::{Layout, SqlType},
+    tx::transaction::Transaction,
+};
+use std::{
+    cmp::Ordering,
+    sync::{Arc, Mutex},
+};
+
+const FLAG_OFFSET: usize = 0;
+const NUM_RECS_OFFSET: usize = I32_BYTE_SIZE as usize;
+const RECORDS_OFFSET: usize = 2 * I32_BYTE_SIZE as usize;
+
+// NOTE: [flag] [number of records] [records sorted by dataval]
+pub struct BTreePage {
+    tx: Arc<Mutex<Transaction>>,
+    block: BlockId,
+    layout: Layout,
+    closed: bool,
+}
+
+impl BTreePage {
+    pub fn new(tx: Arc<Mutex<Transaction>>, block: BlockId, layout: Layout) -> Result<Self> {
+        BTreePage::new_with_priority(tx, block, layout, PinPriority::Normal)
+    }
+
+    pub fn new_with_priority(
+        tx: Arc<Mutex<Transaction>>,
+        block: BlockId,
+        layout: Layout,
+        priority: PinPriority,
+    ) -> Result<Self> {
+        tx.lock().unwrap().pin_with_priority(&block, priority)?;
+        Ok(Self {
+            tx,
+            block,
+            layout,
+            closed: false,
+        })
+    }
+
+    pub fn block(&self) -> &BlockId {
+        &self.block
+    }
+
+    pub fn find_slot_before(&self, search_key: &Constant, order: KeyOrder) -> Result<i32> {
+        let num_recs = self.get_num_recs()?;
+        let mut slot = 0;
+        while slot < num_recs
+            && order.compare(&self.get_data_val(slot)?, search_key) == Ordering::Less
+        {
+            slot += 1;
+        }
+        Ok(slot - 1)
+    }
+
+    pub fn close(&mut self) {
+        if !self.closed {
+            self.tx.lock().unwrap().unpin(&self.block);
+            self.closed = true;
+        }
+    }
+
+    pub fn is_full(&self) -> Result<bool> {
+        let num_recs = self.get_num_recs()?;
+        Ok(self.slot_pos(num_recs + 1) >= self.tx.lock().unwrap().block_size())
+    }
+
+    // NOTE: a page is split as soon as it becomes full
+    pub fn has_room(&self) -> Result<bool> {
+        let num_recs = self.get_num_recs()?;
+        Ok(self.slot_pos(num_recs + 2) < self.tx.lock().unwrap().block_size())
+    }
+
+    pub fn split(&mut self, split_pos: i32, flag: i32) -> Result<BlockId> {
+        let new_block = self.append_new(flag)?;
+        let mut new_page = BTreePage::new(self.tx.clone(), new_block.clone(), self.layout.clone())?;
+        self.transfer_records(split_pos, &mut new_page)?;
+        new_page.set_flag(flag)?;
+        new_page.close();
+        Ok(new_block)
+    }
+
+    pub fn get_data_val(&self, slot: i32) -> Result<Constant> {
+        self.get_val(slot, "dataval")
+    }
+
+    pub fn get_flag(&self) -> Result<i32> {
+        Ok(self.tx.lock().unwrap().get_i32(&self.block, FLAG_OFFSET)?)
+    }
+
+    pub fn set_flag(&mut self, val: i32) -> Result<()> {
+        Ok(self
+            .tx
+            .lock()
+            .unwrap()
+            .set_i32(&self.block, FLAG_OFFSET, val, true)?)
+    }
+
+    pub fn append_new(&mut self, flag: i32) -> Result<BlockId> {
+        let block = self.tx.lock().unwrap().append(self.block.filename())?;
+        self.tx.lock().unwrap().pin(&block)?;
+        self.format(&block, flag)?;
+        self.tx.lock().unwrap().unpin(&block);
+        Ok(block)
+    }
+
+    pub fn format(&self, block: &BlockId, flag: i32) -> Result<()> {
+        let mut tx = self.tx.lock().unwrap();
+        tx.set_i32(block, FLAG_OFFSET, flag, false)?;
+        tx.set_i32(block, NUM_RECS_OFFSET, 0, false)?;
+
+        let rec_size = self.layout.slotsize();
+        let mut pos = RECORDS_OFFSET;
+        while pos + rec_size <= tx.block_size() {
+            for fname in self.layout.schema().fields_iter() {
+                let offset = pos + self.layout.field_offset(fname).unwrap();
+                if self.layout.schema().field_type(fname) == Some(SqlType::Integer) {
+                    tx.set_i32(block, offset, 0, false)?;
+                } else {
+                    tx.set_string(block, offset, "", false)?;
+                }
+            }
+            pos += rec_size;
+        }
+        Ok(())
+    }
+
+    // methods called only by BTreeDir
+
+    pub fn get_child_num(&self, slot: i32) -> Result<i32> {
+        self.get_i32(slot, "block")
+    }
+
+    pub fn insert_dir(&mut self, slot: i32, val: &Constant, block_num: i32) -> Result<()> {
+        self.insert(slot)?;
+        self.set_val(slot, "dataval", val)?;
+        self.set_i32(slot, "block", block_num)
+    }
+
+    // methods called only by BTreeLeaf
+
+    pub fn get_data_rid(&self, slot: i32) -> Result<RID> {
+        let block_num = self.get_i32(slot, "block")?;
+        let id = self.get_i32(slot, "id")?;
+        Ok(RID::new(block_num.into(), Some(id)))
+    }
+
+    pub fn insert_leaf(&mut self, slot: i32, val: &Constant, rid: &RID) -> Result<()> {
+        let block = rid.block_number_i32()?;
+        self.insert(slot)?;
+        self.set_val(slot, "dataval", val)?;
+        self.set_i32(slot, "block", block)?;
+        self.set_i32(slot, "id", rid.slot().unwrap_or(-1))
+    }
+
+    pub fn delete(&mut self, slot: i32) -> Result<()> {
+        let num_recs = self.get_num_recs()?;
+        for i in (slot + 1)..num_recs {
+            self.copy_record(i, i - 1)?;
+        }
+        self.set_num_recs(num_recs - 1)
+    }
+
+    pub fn get_num_recs(&self) -> Result<i32> {
+        Ok(self
+            .tx
+            .lock()
+            .unwrap()
+            .get_i32(&self.block, NUM_RECS_OFFSET)?)
+    }
+
+    // private methods
+
+    fn get_i32(&self, slot: i32, fname: &str) -> Result<i32> {
+        let pos = self.field_pos(slot, fname);
+        Ok(self.tx.lock().unwrap().get_i32(&self.block, pos)?)
+    }
+
+    fn get_string(&self, slot: i32, fname: &str) -> Result<String> {
+        let pos = self.field_pos(slot, fname);
+        Ok(self.tx.lock().unwrap().get_string(&self.block, pos)?)
+    }
+
+    fn get_val(&self, slot: i32, fname: &str) -> Result<Constant> {
+        if self.layout.schema().field_type(fname) == Some(SqlType::Integer) {
+            self.get_i32(slot, fname).map(Constant::Int)
+        } else {
+            self.get_string(slot, fname).map(Constant::String)
+        }
+    }
+
+    fn set_i32(&mut self, slot: i32, fname: &str, val: i32) -> Result<()> {
+        let pos = self.field_pos(slot, fname);
+        Ok(self
+            .tx
+            .lock()
+            .unwrap()
+            .set_i32(&self.block, pos, val, true)?)
+    }
+
+    fn set_string(&mut self, slot: i32, fname: &str, val: &str) -> Result<()> {
+        let pos = self.field_pos(slot, fname);
+        Ok(self
+            .tx
+            .lock()
+            .unwrap()
+            .set_string(&self.block, pos, val, true)?)
+    }
+
+    fn set_val(&mut self, slot: i32, fname: &str, val: &Constant) -> Result<()> {
+        match val {
+            Constant::Int(n) => self.set_i32(slot, fname, *n),
+            Constant::String(s) => self.set_string(slot, fname, s),
+        }
+    }
+
+    fn set_num_recs(&mut self, n: i32) -> Result<()> {
+        Ok(self
+            .tx
+            .lock()
+            .unwrap()
+            .set_i32(&self.block, NUM_RECS_OFFSET, n, true)?)
+    }
+
+    fn insert(&mut self, slot: i32) -> Result<()> {
+        let num_recs = self.get_num_recs()?;
+        for i in ((slot + 1)..=num_recs).rev() {
+            self.copy_record(i - 1, i)?;
+        }
+        self.set_num_recs(num_recs + 1)
+    }
+
+    fn copy_record(&mut self, from: i32, to: i32) -> Result<()> {
+        let fields: Vec<String> = self.layout.schema().fields_iter().cloned().collect();
+        for fname in fields.iter() {
+            let val = self.get_val(from, fname)?;
+            self.set_val(to, fname, &val)?;
+        }
+        Ok(())
+    }
+
+    fn transfer_records(&mut self, slot: i32, dest: &mut BTreePage) -> Result<()> {
+        let fields: Vec<String> = self.layout.schema().fields_iter().cloned().collect();
+        let mut dest_slot = 0;
+        while slot < self.get_num_recs()? {
+            dest.insert(dest_slot)?;
+            for fname in fields.iter() {
+                let val = self.get_val(slot, fname)?;
+                dest.set_val(dest_slot, fname, &val)?;
+            }
+            self.delete(slot)?;
+            dest_slot += 1;
+        }
+        Ok(())
+    }
+
+    fn field_pos(&self, slot: i32, fname: &str) -> usize {
+        self.slot_pos(slot) + self.layout.field_offset(fname).unwrap()
+    }
+
+    fn slot_pos(&self, slot: i32) -> usize {
+        RECORDS_OFFSET + (slot as usize) * self.layout.slotsize()
+    }
+}
+
+impl Drop for BTreePage {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
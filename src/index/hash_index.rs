@@ -0,0 +1,251 @@
+// Copyright (c) 2023 Sho Kuroda <krdlab@gmail.com>
+//
+// This software is released under the MIT License.
+// https://opensource.org/licenses/MIT
+
+use super::{Index, IndexError, Result};
+use crate::{
+    query::{predicate::Constant, scan::RID},
+    record::{schema::Layout, table_scan::TableScan},
+    tx::transaction::Transaction,
+};
+use std::sync::{Arc, Mutex};
+
+pub const DEFAULT_BUCKETS: usize = 100;
+
+pub struct HashIndex {
+    tx: Arc<Mutex<Transaction>>,
+    index_name: String,
+    layout: Layout,
+    // NOTE: the bucket of a key depends on it
+    buckets: usize,
+    search_key: Option<Constant>,
+    ts: Option<TableScan>,
+}
+
+impl HashIndex {
+    pub fn new(
+        tx: Arc<Mutex<Transaction>>,
+        index_name: &str,
+        layout: Layout,
+        buckets: usize,
+    ) -> Result<Self> {
+        HashIndex::check_buckets(buckets)?;
+        Ok(Self {
+            tx,
+            index_name: index_name.into(),
+            layout,
+            buckets,
+            search_key: None,
+            ts: None,
+        })
+    }
+
+    pub fn check_buckets(buckets: usize) -> Result<()> {
+        if buckets == 0 || i32::try_from(buckets).is_err() {
+            return Err(IndexError::InvalidBuckets(
+                i64::try_from(buckets).unwrap_or(i64::MAX),
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn filenames(index_name: &str, buckets: usize) -> Vec<String> {
+        (0..buckets)
+            .map(|bucket| TableScan::filename(&HashIndex::bucket_table(index_name, bucket)))
+            .collect()
+    }
+
+    fn bucket_table(index_name: &str, bucket: usize) -> String {
+        format!("{}{}", index_name, bucket)
+    }
+
+    pub fn search_cost(num_blocks: usize, buckets: usize) -> usize {
+        num_blocks / buckets
+    }
+
+    // NOTE: persisted in file names, so the hash must be stable
+    fn bucket_of(key: &Constant, buckets: usize) -> usize {
+        let h: i64 = match key {
+            Constant::Int(n) => (*n).into(),
+            Constant::String(s) => s
+                .chars()
+                .fold(0i32, |h, c| h.wrapping_mul(31).wrapping_add(c as i32))
+                .into(),
+        };
+        h.rem_euclid(buckets as i64) as usize
+    }
+
+    fn scan(&self) -> Result<&TableScan> {
+        self.ts.as_ref().ok_or(IndexError::NotPositioned)
+    }
+
+    fn scan_mut(&mut self) -> Result<&mut TableScan> {
+        self.ts.as_mut().ok_or(IndexError::NotPositioned)
+    }
+}
+
+impl Index for HashIndex {
+    fn before_first(&mut self, search_key: &Constant) -> Result<()> {
+        self.close();
+        let bucket = HashIndex::bucket_of(search_key, self.buckets);
+        let table_name = HashIndex::bucket_table(&self.index_name, bucket);
+        self.ts = Some(TableScan::new(
+            self.tx.clone(),
+            table_name,
+            self.layout.clone(),
+        )?);
+        self.search_key = Some(search_key.clone());
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<bool> {
+        let key = self.search_key.clone().ok_or(IndexError::NotPositioned)?;
+        let ts = self.scan_mut()?;
+        while ts.next()? {
+            if ts.get_val("dataval")? == key {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    fn get_data_rid(&self) -> Result<RID> {
+        let ts = self.scan()?;
+        let blknum = ts.get_i32("block")?;
+        let id = ts.get_i32("id")?;
+        Ok(RID::new(blknum.into(), Some(id)))
+    }
+
+    fn insert(&mut self, data_val: &Constant, data_rid: &RID) -> Result<()> {
+        let block = data_rid.block_number_i32()?;
+        self.before_first(data_val)?;
+        let ts = self.scan_mut()?;
+        ts.insert()?;
+        ts.set_i32("block", block)?;
+        ts.set_i32("id", data_rid.slot().unwrap_or(-1))?;
+        ts.set_val("dataval", data_val.clone())?;
+        Ok(())
+    }
+
+    fn delete(&mut self, data_val: &Constant, data_rid: &RID) -> Result<()> {
+        self.before_first(data_val)?;
+        while self.next()? {
+            if self.get_data_rid()? == *data_rid {
+                self.scan_mut()?.delete()?;
+                return Ok(());
+            }
+        }
+        Ok(())
+    }
+
+    fn close(&mut self) {
+        self.ts = None;
+        self.search_key = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HashIndex, DEFAULT_BUCKETS};
+    use crate::{
+        index::{Index, IndexError},
+        query::{
+            predicate::Constant,
+            scan::{ScanError, RID},
+        },
+        record::schema::{Layout, Schema},
+        server::simple_db::SimpleDB,
+    };
+    use tempfile::tempdir;
+
+    #[test]
+    fn test() {
+        let dir = tempdir().unwrap();
+        {
+            let db = SimpleDB::new_for_test(dir.path(), "hash_index_test.log");
+            let layout = {
+                let mut schema = Schema::new();
+                schema.add_i32_field("block");
+                schema.add_i32_field("id");
+                schema.add_string_field("dataval", 9);
+                Layout::new(schema)
+            };
+
+            let tx = db.new_tx();
+            {
+                assert!(matches!(
+                    HashIndex::new(tx.clone(), "hidx", layout.clone(), 0),
+                    Err(IndexError::InvalidBuckets(0))
+                ));
+                let mut idx = HashIndex::new(tx.clone(), "hidx", layout, 3).unwrap();
+                for i in 0..20 {
+                    let key = Constant::String(format!("key{}", i % 4));
+                    idx.insert(&key, &RID::new(i / 5, Some((i % 5) as i32)))
+                        .unwrap();
+                }
+
+                let key = Constant::String("key1".into());
+                let mut rids = Vec::new();
+                idx.before_first(&key).unwrap();
+                while idx.next().unwrap() {
+                    rids.push(idx.get_data_rid().unwrap());
+                }
+                assert_eq!(rids.len(), 5);
+                assert!(rids.contains(&RID::new(0, Some(1))));
+
+                idx.delete(&key, &RID::new(0, Some(1))).unwrap();
+                let mut count = 0;
+                idx.before_first(&key).unwrap();
+                while idx.next().unwrap() {
+                    assert_ne!(idx.get_data_rid().unwrap(), RID::new(0, Some(1)));
+                    count += 1;
+                }
+                assert_eq!(count, 4);
+
+                idx.before_first(&Constant::String("none".into())).unwrap();
+                assert!(!idx.next().unwrap());
+                idx.close();
+            }
+            tx.lock().unwrap().commit().unwrap();
+        }
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_block_number_overflow() {
+        let dir = tempdir().unwrap();
+        {
+            let db = SimpleDB::new_for_test(dir.path(), "hash_index_test.log");
+            let layout = {
+                let mut schema = Schema::new();
+                schema.add_i32_field("block");
+                schema.add_i32_field("id");
+                schema.add_string_field("dataval", 9);
+                Layout::new(schema)
+            };
+
+            let tx = db.new_tx();
+            {
+                let mut idx = HashIndex::new(tx.clone(), "hidx", layout, 3).unwrap();
+                let rid = RID::new(i64::from(i32::MAX) + 1, Some(0));
+                assert!(matches!(
+                    idx.insert(&Constant::Int(1), &rid),
+                    Err(IndexError::Scan(ScanError::BlockNumberOverflow(_)))
+                ));
+                idx.close();
+            }
+            tx.lock().unwrap().rollback().unwrap();
+        }
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_bucket_of_is_stable() {
+        let bucket_of = |key| HashIndex::bucket_of(&key, DEFAULT_BUCKETS);
+        assert_eq!(bucket_of(Constant::Int(123)), 23);
+        assert_eq!(bucket_of(Constant::Int(-1)), 99);
+        assert_eq!(bucket_of(Constant::String("abc".into())), 54); // NOTE: 96354 % 100
+        assert_eq!(HashIndex::bucket_of(&Constant::Int(123), 7), 4);
+    }
+}
@@ -0,0 +1,177 @@
+// Copyright (c) 2023 Sho Kuroda <krdlab@gmail.com>
+//
+// This software is released under the MIT License.
+// https://opensource.org/licenses/MIT
+
+pub mod btree_dir;
+pub mod btree_index;
+pub mod btree_leaf;
+pub mod btree_page;
+pub mod hash_index;
+
+use self::{btree_index::BTreeIndex, hash_index::HashIndex};
+use crate::{
+    query::{
+        predicate::Constant,
+        scan::{Scan, ScanError, RID},
+    },
+    record::schema::Collation,
+    tx::transaction::TransactionError,
+};
+use std::cmp::Ordering;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum IndexError {
+    #[error("{0}")]
+    Scan(#[from] ScanError),
+
+    #[error("{0}")]
+    Transaction(#[from] TransactionError),
+
+    #[error("index is not positioned: call before_first first")]
+    NotPositioned,
+
+    #[error("bulk loading requires an empty index")]
+    NotEmpty,
+
+    #[error("unknown key order: {0}")]
+    UnknownKeyOrder(i32),
+
+    #[error("unknown index type: {0}")]
+    UnknownIndexType(i32),
+
+    #[error("invalid number of buckets: {0} (1 to {max})", max = i32::MAX)]
+    InvalidBuckets(i64),
+}
+
+pub type Result<T> = core::result::Result<T, IndexError>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum KeyOrder {
+    #[default]
+    Ascending = 0,
+    Descending = 1,
+}
+
+impl KeyOrder {
+    pub fn compare(&self, lhs: &Constant, rhs: &Constant) -> Ordering {
+        let ord = lhs.partial_cmp(rhs).unwrap_or(Ordering::Equal);
+        match self {
+            KeyOrder::Ascending => ord,
+            KeyOrder::Descending => ord.reverse(),
+        }
+    }
+}
+
+impl From<KeyOrder> for i32 {
+    fn from(order: KeyOrder) -> Self {
+        order as i32
+    }
+}
+
+impl TryFrom<i32> for KeyOrder {
+    type Error = IndexError;
+
+    fn try_from(value: i32) -> Result<Self> {
+        match value {
+            0 => Ok(KeyOrder::Ascending),
+            1 => Ok(KeyOrder::Descending),
+            _ => Err(IndexError::UnknownKeyOrder(value)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IndexType {
+    #[default]
+    BTree = 0,
+    Hash = 1,
+}
+
+impl From<IndexType> for i32 {
+    fn from(index_type: IndexType) -> Self {
+        index_type as i32
+    }
+}
+
+impl IndexType {
+    pub fn filenames(&self, index_name: &str, buckets: usize) -> Vec<String> {
+        match self {
+            IndexType::BTree => {
+                let (leaf_file, dir_file) = BTreeIndex::filenames(index_name);
+                vec![leaf_file, dir_file]
+            }
+            IndexType::Hash => HashIndex::filenames(index_name, buckets),
+        }
+    }
+}
+
+impl TryFrom<i32> for IndexType {
+    type Error = IndexError;
+
+    fn try_from(value: i32) -> Result<Self> {
+        match value {
+            0 => Ok(IndexType::BTree),
+            1 => Ok(IndexType::Hash),
+            _ => Err(IndexError::UnknownIndexType(value)),
+        }
+    }
+}
+
+pub trait Index {
+    fn before_first(&mut self, search_key: &Constant) -> Result<()>;
+    fn next(&mut self) -> Result<bool>;
+    fn get_data_rid(&self) -> Result<RID>;
+    fn insert(&mut self, data_val: &Constant, data_rid: &RID) -> Result<()>;
+    fn delete(&mut self, data_val: &Constant, data_rid: &RID) -> Result<()>;
+    fn close(&mut self);
+
+    fn bulk_load(&mut self, src: &mut dyn Scan) -> Result<()> {
+        src.before_first()?;
+        while src.next()? {
+            let rid = RID::new(src.get_i32("block")?.into(), Some(src.get_i32("id")?));
+            self.insert(&src.get_val("dataval")?, &rid)?;
+        }
+        Ok(())
+    }
+}
+
+pub struct CollatedIndex {
+    inner: Box<dyn Index>,
+    collation: Collation,
+}
+
+impl CollatedIndex {
+    pub fn new(inner: Box<dyn Index>, collation: Collation) -> Self {
+        Self { inner, collation }
+    }
+}
+
+impl Index for CollatedIndex {
+    fn before_first(&mut self, search_key: &Constant) -> Result<()> {
+        self.inner.before_first(&self.collation.key(search_key))
+    }
+
+    fn next(&mut self) -> Result<bool> {
+        self.inner.next()
+    }
+
+    fn get_data_rid(&self) -> Result<RID> {
+        self.inner.get_data_rid()
+    }
+
+    fn insert(&mut self, data_val: &Constant, data_rid: &RID) -> Result<()> {
+        self.inner.insert(&self.collation.key(data_val), data_rid)
+    }
+
+    fn delete(&mut self, data_val: &Constant, data_rid: &RID) -> Result<()> {
+        self.inner.delete(&self.collation.key(data_val), data_rid)
+    }
+
+    fn close(&mut self) {
+        self.inner.close()
+    }
+
+    // NOTE: `inner.bulk_load` would take the values of `src` as keys
+}
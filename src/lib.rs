@@ -5,8 +5,12 @@
 
 mod buffer_mgr;
 mod constants;
+#[cfg(any(test, feature = "crash-injection"))]
+pub mod crash;
 mod file;
+mod index;
 mod log_mgr;
+mod materialize;
 mod metadata;
 mod parse;
 mod plan;
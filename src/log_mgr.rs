@@ -7,83 +7,285 @@ use crate::{
     constants::I32_BYTE_SIZE,
     file::{
         block_id::BlockId,
-        file_mgr::{FileMgr, FileMgrError},
+        file_mgr::{FileMgr, FileMgrError, SyncMode},
         page::{Page, PageError},
     },
 };
-use std::sync::{Arc, Mutex, MutexGuard};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 use thiserror::Error;
 
 #[derive(Debug, Error)]
 pub enum LogMgrError {
-    #[error("{0:?}")]
+    #[error("{0}")]
     IO(#[from] std::io::Error),
 
-    #[error("{0:?}")]
+    #[error("{0}")]
     Page(#[from] PageError),
 
-    #[error("{0:?}")]
+    #[error("{0}")]
     FileMgr(#[from] FileMgrError),
+
+    #[error("the log {0} is a single file of an older version, which this one cannot read")]
+    LegacyLog(String),
 }
 
 pub type Result<T> = core::result::Result<T, LogMgrError>;
 
 pub type LSN = i64;
 
-pub struct LogMgr<'p> {
-    fm: Arc<FileMgr>,
+// NOTE: [length] [bytes] [CRC], to detect torn writes
+const CHECKSUM_SIZE: i32 = I32_BYTE_SIZE;
+
+fn checksum(logrec: &[u8]) -> i32 {
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(&(logrec.len() as u32).to_be_bytes());
+    hasher.update(logrec);
+    hasher.finalize() as i32
+}
+
+fn read_record(page: &mut Page, pos: i32, blocksize: i32) -> Option<(Vec<u8>, i32)> {
+    if pos < I32_BYTE_SIZE || pos > blocksize - I32_BYTE_SIZE - CHECKSUM_SIZE {
+        return None;
+    }
+    let len = page.get_i32(pos.try_into().unwrap()).ok()?;
+    if len < 0 || len > blocksize - pos - I32_BYTE_SIZE - CHECKSUM_SIZE {
+        return None;
+    }
+    let rec = page.get_bytes(pos.try_into().unwrap()).ok()?;
+    let end = pos + I32_BYTE_SIZE + len;
+    let crc = page.get_i32(end.try_into().unwrap()).ok()?;
+    if crc != checksum(&rec) {
+        return None;
+    }
+    Some((rec, end + CHECKSUM_SIZE))
+}
+
+fn read_block(fm: &FileMgr, block: &BlockId, page: &mut Page) -> Result<bool> {
+    match fm.read(block, page) {
+        Ok(()) => Ok(true),
+        Err(FileMgrError::CorruptPage(_)) => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn is_valid_boundary(boundary: i32, blocksize: i32) -> bool {
+    (I32_BYTE_SIZE..=blocksize).contains(&boundary)
+}
+
+pub const DEFAULT_SEGMENT_BLOCKS: i64 = 256;
+
+pub const DEFAULT_CHECKPOINT_BLOCKS: i64 = 1024;
+
+#[derive(Clone)]
+struct Segments {
     logfile: String,
-    data: Mutex<LogMgrData<'p>>,
+    blocks_per_segment: i64,
 }
 
-struct LogMgrData<'p> {
-    logpage: Page<'p>,
-    currentblk: Option<BlockId>,
+impl Segments {
+    fn filename(&self, segno: i64) -> String {
+        format!("{}.{:04}", self.logfile, segno)
+    }
+
+    fn parse(&self, filename: &str) -> Option<i64> {
+        let suffix = filename.strip_prefix(&self.logfile)?.strip_prefix('.')?;
+        if suffix.is_empty() || !suffix.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        suffix.parse().ok()
+    }
+
+    fn segment_of(&self, blknum: i64) -> i64 {
+        blknum / self.blocks_per_segment + 1
+    }
+
+    fn first_block_of(&self, segno: i64) -> i64 {
+        (segno - 1) * self.blocks_per_segment
+    }
+
+    fn block(&self, blknum: i64) -> BlockId {
+        BlockId::new(
+            &self.filename(self.segment_of(blknum)),
+            blknum % self.blocks_per_segment,
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LogStats {
+    pub appended_records: usize,
+    pub bytes_written: usize,
+    pub flushes: usize,
+    pub flush_time: Duration,
+    pub checkpoints: usize,
+    pub failed_checkpoints: usize,
+}
+
+impl LogStats {
+    pub fn avg_flush_latency(&self) -> Duration {
+        if self.flushes == 0 {
+            Duration::ZERO
+        } else {
+            self.flush_time / self.flushes.try_into().unwrap()
+        }
+    }
+}
+
+pub struct LogMgr {
+    fm: Arc<FileMgr>,
+    segments: Segments,
+    data: Mutex<LogMgrData>,
+    epoch: u64,
+    // NOTE: the segments are copied there before they are deleted
+    archive: Option<PathBuf>,
+    checkpoint_interval: Option<i64>,
+}
+
+struct LogMgrData {
+    logpage: Page<'static>,
+    // NOTE: counted through all the segments
+    currentblk: i64,
+    // NOTE: the block of the latest checkpoint
+    checkpoint_blk: i64,
+    oldest_segment: i64,
     latest_lsn: LSN,
     last_saved_lsn: LSN,
+    // NOTE: first LSN of a block -> (block number, boundary)
+    first_lsns: BTreeMap<LSN, (i64, i32)>,
+    stats: LogStats,
 }
 
-impl<'p> LogMgrData<'p> {
-    pub(crate) fn new(logpage: Page<'p>) -> Self {
+impl LogMgrData {
+    pub(crate) fn new(logpage: Page<'static>) -> Self {
         Self {
             logpage,
-            currentblk: None,
+            currentblk: -1,
+            checkpoint_blk: -1,
+            oldest_segment: 1,
             latest_lsn: 0,
             last_saved_lsn: 0,
+            first_lsns: BTreeMap::new(),
+            stats: LogStats::default(),
         }
     }
 }
 
-impl<'p> LogMgr<'p> {
-    pub fn new(fm: Arc<FileMgr>, logfile: &str) -> Self {
+impl LogMgr {
+    pub fn new(fm: Arc<FileMgr>, logfile: &str) -> Result<Self> {
+        Self::new_with_segment_size(fm, logfile, DEFAULT_SEGMENT_BLOCKS)
+    }
+
+    pub fn new_with_segment_size(
+        fm: Arc<FileMgr>,
+        logfile: &str,
+        blocks_per_segment: i64,
+    ) -> Result<Self> {
+        // NOTE: DbConfig::validate rejects the other sizes
+        debug_assert!(blocks_per_segment > 0);
         let blocksize = fm.blocksize();
-        let logsize: i64 = fm.length(logfile).unwrap().try_into().unwrap();
+        let segments = Segments {
+            logfile: logfile.to_string(),
+            blocks_per_segment,
+        };
+        let filenames = fm.filenames()?;
+        let segnos: Vec<i64> = filenames.iter().filter_map(|f| segments.parse(f)).collect();
+        // NOTE: a torn write can't be detected without checksums
+        if segnos.is_empty() && filenames.iter().any(|f| f == logfile) {
+            return Err(LogMgrError::LegacyLog(logfile.to_string()));
+        }
 
         let lm = Self {
             fm: fm.clone(),
-            logfile: logfile.to_string(),
+            segments: segments.clone(),
             data: Mutex::new(LogMgrData::new(Page::for_data(blocksize))),
+            epoch: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_nanos() as u64,
+            archive: None,
+            checkpoint_interval: Some(DEFAULT_CHECKPOINT_BLOCKS),
         };
         {
             let mut lmd = lm.data.lock().unwrap();
-            if logsize == 0 {
-                let new_blk = lm.append_new_block(&mut lmd).unwrap().clone();
-                lmd.currentblk = Some(new_blk);
-            } else {
-                let block = BlockId::new(logfile, logsize - 1);
-                fm.read(&block, &mut lmd.logpage).unwrap();
-                lmd.currentblk = Some(block);
+            match (segnos.iter().min(), segnos.iter().max()) {
+                (Some(oldest), Some(latest)) => {
+                    lmd.oldest_segment = *oldest;
+                    let logsize: i64 = fm.length(&segments.filename(*latest))?.try_into().unwrap();
+                    lmd.currentblk = segments.first_block_of(*latest) + logsize - 1;
+                    if logsize == 0 {
+                        // NOTE: a crash right after creating the segment leaves it empty
+                        lm.append_new_block(&mut lmd)?;
+                    } else {
+                        let block = segments.block(lmd.currentblk);
+                        let intact = read_block(&fm, &block, &mut lmd.logpage)?;
+                        let boundary = lmd.logpage.get_i32(0)?;
+                        if !intact || !is_valid_boundary(boundary, blocksize.try_into().unwrap()) {
+                            // NOTE: zero-filled by a crash, or anything by a torn write
+                            lmd.logpage.set_i32(0, blocksize.try_into().unwrap())?;
+                            fm.write(&block, &mut lmd.logpage)?;
+                        }
+                    }
+                }
+                _ => lm.append_new_block(&mut lmd)?,
             }
+            let first_lsn = lmd.latest_lsn + 1;
+            let blknum = lmd.currentblk;
+            let boundary = lmd.logpage.get_i32(0)?;
+            lmd.first_lsns.insert(first_lsn, (blknum, boundary));
+            lmd.checkpoint_blk = blknum;
         }
-        lm
+        Ok(lm)
+    }
+
+    pub fn with_checkpoint_interval(mut self, blocks: Option<i64>) -> Self {
+        assert!(blocks.is_none_or(|n| n > 0));
+        self.checkpoint_interval = blocks;
+        self
+    }
+
+    pub fn checkpoint_due(&self) -> bool {
+        let data = self.data.lock().unwrap();
+        self.checkpoint_interval
+            .is_some_and(|n| data.currentblk - data.checkpoint_blk >= n)
+    }
+
+    pub fn mark_checkpoint(&self) {
+        let mut data = self.data.lock().unwrap();
+        data.checkpoint_blk = data.currentblk;
+        data.stats.checkpoints += 1;
+    }
+
+    pub fn mark_failed_checkpoint(&self) {
+        self.data.lock().unwrap().stats.failed_checkpoints += 1;
     }
 
-    fn append_new_block(&self, data: &mut MutexGuard<LogMgrData>) -> Result<BlockId> {
-        let block = self.fm.append(&self.logfile)?;
+    pub fn with_archive(mut self, dir: &Path) -> Result<Self> {
+        fs::create_dir_all(dir)?;
+        self.archive = Some(dir.to_path_buf());
+        Ok(self)
+    }
+
+    fn append_new_block(&self, data: &mut MutexGuard<LogMgrData>) -> Result<()> {
+        let blknum = data.currentblk + 1;
+        let segment = self.segments.filename(self.segments.segment_of(blknum));
+        let block = self.fm.append(&segment)?;
+        debug_assert_eq!(block, self.segments.block(blknum));
         let blocksize = self.fm.blocksize().try_into().unwrap();
         data.logpage.set_i32(0, blocksize)?;
         self.fm.write(&block, &mut data.logpage)?;
-        Ok(block)
+        data.stats.bytes_written += self.fm.blocksize();
+        data.currentblk = blknum;
+        Ok(())
+    }
+
+    pub fn max_record_len(&self) -> usize {
+        usize::try_from(self.fm.blocksize() as i32 - 2 * I32_BYTE_SIZE - CHECKSUM_SIZE).unwrap()
     }
 
     pub fn apppend(&self, logrec: &[u8]) -> Result<LSN> {
@@ -91,18 +293,28 @@ impl<'p> LogMgr<'p> {
 
         let mut boundary = data.logpage.get_i32(0)?;
         let recsize: i32 = logrec.len().try_into().unwrap();
-        let bytesneeded: i32 = recsize + I32_BYTE_SIZE;
+        let bytesneeded: i32 = recsize + I32_BYTE_SIZE + CHECKSUM_SIZE;
         if boundary - bytesneeded < I32_BYTE_SIZE {
             self._flush(&mut data)?;
-            data.currentblk = Some(self.append_new_block(&mut data)?);
+            self.append_new_block(&mut data)?;
+            let first_lsn = data.latest_lsn + 1;
+            let blknum = data.currentblk;
             boundary = data.logpage.get_i32(0)?;
+            data.first_lsns.insert(first_lsn, (blknum, boundary));
         }
 
         let recpos = boundary - bytesneeded;
         let recpos_usize = usize::try_from(boundary - bytesneeded).unwrap();
         data.logpage.set_bytes(recpos_usize, logrec)?;
+        data.logpage.set_i32(
+            recpos_usize + usize::try_from(I32_BYTE_SIZE + recsize).unwrap(),
+            checksum(logrec),
+        )?;
         data.logpage.set_i32(0, recpos)?;
         data.latest_lsn += 1;
+        data.stats.appended_records += 1;
+        #[cfg(any(test, feature = "crash-injection"))]
+        self.fm.on_log_append(data.latest_lsn);
         Ok(data.latest_lsn)
     }
 
@@ -114,89 +326,311 @@ impl<'p> LogMgr<'p> {
         Ok(())
     }
 
+    pub fn flush_for_commit(&self, lsn: LSN) -> Result<()> {
+        if self.fm.sync_mode() == SyncMode::Off {
+            return Ok(());
+        }
+        self.flush(lsn)
+    }
+
     fn _flush(&self, data: &mut MutexGuard<LogMgrData>) -> Result<()> {
-        let block = data.currentblk.as_ref().unwrap().clone();
+        let begintime = SystemTime::now();
+        let block = self.segments.block(data.currentblk);
         self.fm.write(&block, &mut data.logpage)?;
+        if self.fm.sync_mode() == SyncMode::FsyncOnCommit {
+            // NOTE: also syncs a full segment
+            self.fm.sync(block.filename())?;
+        }
         data.last_saved_lsn = data.latest_lsn;
+        data.stats.bytes_written += self.fm.blocksize();
+        data.stats.flushes += 1;
+        data.stats.flush_time += begintime.elapsed().unwrap_or_default();
         Ok(())
     }
 
-    pub fn reverse_iter(&self) -> Result<LogIterator<'_>> {
+    pub fn truncate_before(&self, lsn: LSN) -> Result<()> {
+        let mut data = self.data.lock().unwrap();
+        let blknum = match data.first_lsns.range(..=lsn).next_back() {
+            Some((_, (blknum, _))) => *blknum,
+            None => return Ok(()),
+        };
+        // NOTE: oldest first, so a crash leaves no gap
+        let segno = self.segments.segment_of(blknum);
+        for n in data.oldest_segment..segno {
+            let filename = self.segments.filename(n);
+            if let Some(archive) = &self.archive {
+                fs::copy(self.fm.path_of(&filename), archive.join(&filename))?;
+            }
+            self.fm.remove(&filename)?;
+        }
+        data.oldest_segment = data.oldest_segment.max(segno);
+
+        let first = self.segments.first_block_of(data.oldest_segment);
+        data.first_lsns.retain(|_, (blknum, _)| *blknum >= first);
+        Ok(())
+    }
+
+    pub fn restore_archived(&self) -> Result<usize> {
+        let Some(archive) = &self.archive else {
+            return Ok(0);
+        };
+        let mut data = self.data.lock().unwrap();
+        let mut restored = 0;
+        while data.oldest_segment > 1 {
+            let filename = self.segments.filename(data.oldest_segment - 1);
+            let path = archive.join(&filename);
+            if !path.exists() {
+                break;
+            }
+            fs::copy(path, self.fm.path_of(&filename))?;
+            data.oldest_segment -= 1;
+            restored += 1;
+        }
+        Ok(restored)
+    }
+
+    pub fn stats(&self) -> LogStats {
+        self.data.lock().unwrap().stats
+    }
+
+    pub fn epoch(&self) -> u64 {
+        self.epoch
+    }
+
+    pub fn latest_lsn(&self) -> LSN {
+        self.data.lock().unwrap().latest_lsn
+    }
+
+    pub fn segment_files(&self) -> Vec<String> {
+        let data = self.data.lock().unwrap();
+        let latest = self.segments.segment_of(data.currentblk);
+        (data.oldest_segment..=latest)
+            .map(|n| self.segments.filename(n))
+            .collect()
+    }
+
+    pub fn reverse_iter(&self) -> Result<LogIterator> {
+        let mut data = self.data.lock().unwrap();
+        self._flush(&mut data)?;
+
+        let first = self.segments.first_block_of(data.oldest_segment);
+        Ok(LogIterator::new(
+            self.fm.clone(),
+            self.segments.clone(),
+            data.currentblk,
+            first,
+            data.latest_lsn,
+        ))
+    }
+
+    pub fn forward_iter(&self, lsn: LSN) -> Result<ForwardLogIterator> {
         let mut data = self.data.lock().unwrap();
         self._flush(&mut data)?;
 
-        let block = data.currentblk.as_ref().unwrap().clone();
-        Ok(LogIterator::new(self.fm.clone(), block))
+        let start = data
+            .first_lsns
+            .range(..=lsn)
+            .next_back()
+            .or_else(|| data.first_lsns.iter().next())
+            .map(|(first_lsn, start)| (*first_lsn, *start));
+        let mut iter = ForwardLogIterator {
+            fm: self.fm.clone(),
+            segments: self.segments.clone(),
+            blknum: data.currentblk,
+            last: data.currentblk,
+            page: Page::for_data(self.fm.blocksize()),
+            records: Vec::new(),
+            next_lsn: data.latest_lsn + 1,
+            corrupted: false,
+        };
+        if let Some((first_lsn, (blknum, boundary))) = start {
+            iter.blknum = blknum;
+            iter.next_lsn = first_lsn;
+            iter.load_block(boundary)?;
+            while iter.next_lsn < lsn && iter.next().is_some() {}
+        }
+        Ok(iter)
     }
 }
 
-pub struct LogIterator<'lm> {
+pub struct LogIterator {
     fm: Arc<FileMgr>,
-    block: BlockId,
-    page: Page<'lm>,
+    segments: Segments,
+    blknum: i64,
+    // NOTE: the first block of the oldest segment
+    first: i64,
+    latest_lsn: LSN,
+    page: Page<'static>,
     currentpos: i32,
-    boundary: i32,
+    next_rec: Option<Vec<u8>>,
+    corrupted: bool,
 }
 
-impl<'lm> LogIterator<'lm> {
-    pub fn new(fm: Arc<FileMgr>, blk: BlockId) -> Self {
+impl LogIterator {
+    fn new(fm: Arc<FileMgr>, segments: Segments, blknum: i64, first: i64, latest_lsn: LSN) -> Self {
         let blocksize = fm.blocksize();
 
         let mut iter = Self {
             fm,
-            block: BlockId::new(blk.filename(), blk.number()),
+            segments,
+            blknum,
+            first,
+            latest_lsn,
             page: Page::for_data(blocksize),
             currentpos: 0,
-            boundary: 0,
+            next_rec: None,
+            corrupted: false,
         };
-        iter.move_to_block(&blk);
+        iter.move_to_block(blknum);
+        iter.read_next();
         iter
     }
 
-    fn move_to_block(&mut self, block: &BlockId) {
-        self.fm
-            .read(block, &mut self.page)
-            .expect(format!("failed to read the block at {:?}", block).as_str());
-        self.boundary = self
+    fn move_to_block(&mut self, blknum: i64) {
+        let block = self.segments.block(blknum);
+        let intact = read_block(&self.fm, &block, &mut self.page)
+            .unwrap_or_else(|_| panic!("failed to read the block at {:?}", block));
+        self.blknum = blknum;
+        if !intact {
+            self.corrupted = true;
+            return;
+        }
+        let boundary = self
             .page
             .get_i32(0)
             .expect("failed to get a boundary value from the current page");
-        self.currentpos = self.boundary;
+        if is_valid_boundary(boundary, self.blocksize()) {
+            self.currentpos = boundary;
+        } else {
+            self.corrupted = true;
+        }
+    }
+
+    fn read_next(&mut self) {
+        self.next_rec = None;
+        while !self.corrupted {
+            if self.currentpos == self.blocksize() {
+                if self.blknum <= self.first {
+                    return;
+                }
+                self.move_to_block(self.blknum - 1);
+                continue;
+            }
+            let blocksize = self.blocksize();
+            match read_record(&mut self.page, self.currentpos, blocksize) {
+                Some((rec, nextpos)) => {
+                    self.currentpos = nextpos;
+                    self.next_rec = Some(rec);
+                    return;
+                }
+                None => self.corrupted = true,
+            }
+        }
+    }
+
+    fn blocksize(&self) -> i32 {
+        self.fm.blocksize().try_into().unwrap()
     }
 
     pub fn has_next(&self) -> bool {
-        usize::try_from(self.currentpos).unwrap() < self.fm.blocksize() || self.block.number() > 0
+        self.next_rec.is_some()
+    }
+
+    pub fn latest_lsn(&self) -> LSN {
+        self.latest_lsn
+    }
+
+    pub fn is_corrupted(&self) -> bool {
+        self.corrupted
     }
 }
 
-impl Iterator for LogIterator<'_> {
+impl Iterator for LogIterator {
     type Item = Vec<u8>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if !self.has_next() {
-            return None;
+        let rec = self.next_rec.take()?;
+        self.read_next();
+        Some(rec)
+    }
+}
+
+pub struct ForwardLogIterator {
+    fm: Arc<FileMgr>,
+    segments: Segments,
+    blknum: i64,
+    last: i64,
+    page: Page<'static>,
+    // NOTE: the rest of the current block, then the next one
+    records: Vec<Vec<u8>>,
+    next_lsn: LSN,
+    corrupted: bool,
+}
+
+impl ForwardLogIterator {
+    fn load_block(&mut self, end: i32) -> Result<()> {
+        if !read_block(&self.fm, &self.segments.block(self.blknum), &mut self.page)? {
+            self.corrupted = true;
+            return Ok(());
+        }
+        let blocksize = self.fm.blocksize().try_into().unwrap();
+        let mut pos = self.page.get_i32(0)?;
+        if !is_valid_boundary(pos, blocksize) {
+            self.corrupted = true;
+        }
+        while !self.corrupted && pos < end {
+            match read_record(&mut self.page, pos, blocksize) {
+                Some((rec, nextpos)) => {
+                    pos = nextpos;
+                    self.records.push(rec);
+                }
+                None => self.corrupted = true,
+            }
+        }
+        if self.corrupted {
+            self.records.clear();
         }
+        Ok(())
+    }
 
-        let blocksize = self.fm.blocksize();
-        if self.currentpos == blocksize.try_into().unwrap() {
-            let newblock = BlockId::new(self.block.filename(), self.block.number() - 1);
-            self.move_to_block(&newblock);
-            self.block = newblock;
+    pub fn is_corrupted(&self) -> bool {
+        self.corrupted
+    }
+}
+
+impl Iterator for ForwardLogIterator {
+    type Item = (LSN, Vec<u8>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.records.is_empty() {
+            if self.corrupted || self.blknum >= self.last {
+                return None;
+            }
+            self.blknum += 1;
+            let blocksize = self.fm.blocksize().try_into().unwrap();
+            self.load_block(blocksize).unwrap_or_else(|_| {
+                panic!(
+                    "failed to read the block at {:?}",
+                    self.segments.block(self.blknum)
+                )
+            });
         }
-        let rec = self
-            .page
-            .get_bytes(self.currentpos.try_into().unwrap())
-            .expect(format!("failed to get a record at {}", self.currentpos).as_str());
-        self.currentpos += I32_BYTE_SIZE + i32::try_from(rec.len()).unwrap();
-        Some(rec)
+        let rec = self.records.pop()?;
+        let lsn = self.next_lsn;
+        self.next_lsn += 1;
+        Some((lsn, rec))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::file::file_mgr::PAGE_CHECKSUM_SIZE;
     use tempfile::tempdir;
 
+    // NOTE: pages of 64 bytes
+    const BLOCK_SIZE: usize = 64 + PAGE_CHECKSUM_SIZE;
+
     #[test]
     fn test_append() -> Result<()> {
         let dir = tempdir()?;
@@ -205,7 +639,7 @@ mod tests {
         let fm = Arc::new(FileMgr::new(dir.path(), 4096));
         assert_eq!(fm.is_new(), true); // dir is empty
 
-        let lm = LogMgr::new(fm, "test_logmgr.log");
+        let lm = LogMgr::new(fm, "test_logmgr.log")?;
 
         let logrec1 = [1u8, 2u8, 3u8];
         let logrec2 = [4u8, 5u8, 6u8];
@@ -221,4 +655,279 @@ mod tests {
         dir.close()?;
         Ok(())
     }
+
+    #[test]
+    fn test_forward_iter() -> Result<()> {
+        let dir = tempdir()?;
+        let fm = Arc::new(FileMgr::new(dir.path(), BLOCK_SIZE));
+        {
+            let lm = LogMgr::new(fm.clone(), "test_logmgr.log")?;
+            let lsn = lm.apppend(&[100u8; 4])?;
+            lm.flush(lsn)?;
+        }
+
+        // NOTE: the record of the previous run has no LSN in this one
+        let lm = LogMgr::new(fm.clone(), "test_logmgr.log")?;
+        let mut lsns = Vec::new();
+        for i in 0..12u8 {
+            lsns.push(lm.apppend(&[i; 4])?);
+        }
+        assert_eq!(fm.length("test_logmgr.log.0001")?, 3);
+
+        let recs: Vec<(LSN, Vec<u8>)> = lm.forward_iter(lsns[0])?.collect();
+        let expected: Vec<(LSN, Vec<u8>)> =
+            (0..12u8).map(|i| (lsns[i as usize], vec![i; 4])).collect();
+        assert_eq!(recs, expected);
+
+        for from in [3, 4, 5, 11] {
+            let recs: Vec<(LSN, Vec<u8>)> = lm.forward_iter(lsns[from])?.collect();
+            assert_eq!(recs, expected[from..]);
+        }
+        assert_eq!(lm.forward_iter(0)?.count(), 12);
+        assert_eq!(lm.forward_iter(lsns[11] + 1)?.count(), 0);
+
+        // NOTE: the LSNs stay valid after the head of the log is truncated
+        lm.truncate_before(lsns[6])?;
+        let recs: Vec<(LSN, Vec<u8>)> = lm.forward_iter(lsns[7])?.collect();
+        assert_eq!(recs, expected[7..]);
+
+        dir.close()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_stats() -> Result<()> {
+        let dir = tempdir()?;
+        let fm = Arc::new(FileMgr::new(dir.path(), BLOCK_SIZE));
+        let lm = LogMgr::new(fm, "test_logmgr.log")?;
+        // NOTE: the first block is written when the log is created
+        assert_eq!(lm.stats().bytes_written, 64);
+
+        // NOTE: 5 records per block
+        for i in 0..6u8 {
+            lm.apppend(&[i; 4])?;
+        }
+        lm.flush(6)?;
+        let stats = lm.stats();
+        assert_eq!(stats.appended_records, 6);
+        assert_eq!(stats.flushes, 2);
+        assert_eq!(stats.bytes_written, 64 * 4);
+        assert_eq!(stats.avg_flush_latency(), stats.flush_time / 2);
+
+        lm.flush(1)?;
+        assert_eq!(lm.stats().flushes, 2);
+
+        dir.close()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_sync_modes() -> Result<()> {
+        let dir = tempdir()?;
+        let fm = Arc::new(FileMgr::new(dir.path(), BLOCK_SIZE));
+        for (i, mode) in [
+            SyncMode::Off,
+            SyncMode::FlushOnCommit,
+            SyncMode::FsyncOnCommit,
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            fm.set_sync_mode(mode);
+            let logfile = format!("test_logmgr_{}.log", i);
+            let lm = LogMgr::new(fm.clone(), &logfile)?;
+            let lsn = lm.apppend(&[1u8; 4])?;
+            lm.flush_for_commit(lsn)?;
+
+            // NOTE: as if it crashed here
+            let recs: Vec<Vec<u8>> = LogMgr::new(fm.clone(), &logfile)?.reverse_iter()?.collect();
+            let expected = if mode == SyncMode::Off { 0 } else { 1 };
+            assert_eq!(recs.len(), expected, "{:?}", mode);
+        }
+
+        dir.close()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_truncate_before() -> Result<()> {
+        let dir = tempdir()?;
+        let fm = Arc::new(FileMgr::new(dir.path(), BLOCK_SIZE));
+        let lm = LogMgr::new_with_segment_size(fm.clone(), "test_logmgr.log", 2)?;
+
+        // NOTE: 5 records per block, and 2 blocks per segment
+        let mut lsns = Vec::new();
+        for i in 0..20u8 {
+            lsns.push(lm.apppend(&[i; 4])?);
+        }
+        assert_eq!(
+            lm.segment_files(),
+            vec!["test_logmgr.log.0001", "test_logmgr.log.0002"]
+        );
+        assert_eq!(fm.length("test_logmgr.log.0002")?, 2);
+
+        // NOTE: the segment containing the LSN is kept as a whole
+        lm.truncate_before(lsns[12])?;
+        assert_eq!(lm.segment_files(), vec!["test_logmgr.log.0002"]);
+        assert!(!fm
+            .filenames()?
+            .contains(&"test_logmgr.log.0001".to_string()));
+
+        let recs: Vec<Vec<u8>> = lm.reverse_iter()?.collect();
+        let expected: Vec<Vec<u8>> = (10..20u8).rev().map(|i| vec![i; 4]).collect();
+        assert_eq!(recs, expected);
+
+        let lsn = lm.apppend(&[20u8; 4])?;
+        lm.flush(lsn)?;
+        drop(lm);
+        let lm = LogMgr::new_with_segment_size(fm.clone(), "test_logmgr.log", 2)?;
+        assert_eq!(
+            lm.segment_files(),
+            vec!["test_logmgr.log.0002", "test_logmgr.log.0003"]
+        );
+        let recs: Vec<Vec<u8>> = lm.reverse_iter()?.collect();
+        assert_eq!(recs.len(), 11);
+        assert_eq!(recs[0], vec![20u8; 4]);
+
+        dir.close()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_segments() -> Result<()> {
+        let dir = tempdir()?;
+        let fm = Arc::new(FileMgr::new(dir.path(), BLOCK_SIZE));
+        {
+            // NOTE: the 11th record starts the next segment
+            let lm = LogMgr::new_with_segment_size(fm.clone(), "test_logmgr.log", 2)?;
+            for i in 0..10u8 {
+                lm.apppend(&[i; 4])?;
+            }
+            assert_eq!(lm.segment_files(), vec!["test_logmgr.log.0001"]);
+            let lsn = lm.apppend(&[10u8; 4])?;
+            assert_eq!(
+                lm.segment_files(),
+                vec!["test_logmgr.log.0001", "test_logmgr.log.0002"]
+            );
+            assert_eq!(fm.length("test_logmgr.log.0002")?, 1);
+            lm.flush(lsn)?;
+        }
+
+        let lm = LogMgr::new_with_segment_size(fm.clone(), "test_logmgr.log", 2)?;
+        let recs: Vec<Vec<u8>> = lm.reverse_iter()?.collect();
+        let expected: Vec<Vec<u8>> = (0..11u8).rev().map(|i| vec![i; 4]).collect();
+        assert_eq!(recs, expected);
+        let mut lsns = Vec::new();
+        for i in 11..20u8 {
+            lsns.push(lm.apppend(&[i; 4])?);
+        }
+        assert_eq!(fm.length("test_logmgr.log.0002")?, 2);
+        lm.apppend(&[20u8; 4])?;
+        assert_eq!(lm.segment_files().len(), 3);
+
+        // NOTE: the current segment is always kept
+        lm.truncate_before(lm.latest_lsn())?;
+        assert_eq!(lm.segment_files(), vec!["test_logmgr.log.0003"]);
+        let recs: Vec<Vec<u8>> = lm.reverse_iter()?.collect();
+        assert_eq!(recs, vec![vec![20u8; 4]]);
+        lm.truncate_before(lsns[0])?;
+        assert_eq!(lm.segment_files(), vec!["test_logmgr.log.0003"]);
+
+        // NOTE: refused instead of being taken for a new log
+        fs::write(dir.path().join("test_logmgr_legacy.log"), [0u8; BLOCK_SIZE])?;
+        assert!(matches!(
+            LogMgr::new(fm.clone(), "test_logmgr_legacy.log"),
+            Err(LogMgrError::LegacyLog(f)) if f == "test_logmgr_legacy.log"
+        ));
+
+        dir.close()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_archive() -> Result<()> {
+        let dir = tempdir()?;
+        let archive = dir.path().join("archive");
+        let fm = Arc::new(FileMgr::new(dir.path(), BLOCK_SIZE));
+        let lm = LogMgr::new_with_segment_size(fm.clone(), "test_logmgr.log", 2)?
+            .with_archive(&archive)?;
+
+        let mut lsns = Vec::new();
+        for i in 0..20u8 {
+            lsns.push(lm.apppend(&[i; 4])?);
+        }
+        lm.truncate_before(lsns[12])?;
+        assert_eq!(lm.segment_files(), vec!["test_logmgr.log.0002"]);
+        assert!(archive.join("test_logmgr.log.0001").exists());
+
+        assert_eq!(lm.restore_archived()?, 1);
+        assert_eq!(lm.restore_archived()?, 0);
+        let recs: Vec<Vec<u8>> = lm.reverse_iter()?.collect();
+        let expected: Vec<Vec<u8>> = (0..20u8).rev().map(|i| vec![i; 4]).collect();
+        assert_eq!(recs, expected);
+
+        dir.close()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_reopen_after_torn_append() -> Result<()> {
+        let dir = tempdir()?;
+        let fm = Arc::new(FileMgr::new(dir.path(), BLOCK_SIZE));
+        {
+            let lm = LogMgr::new(fm.clone(), "test_logmgr.log")?;
+            let lsn = lm.apppend(&[1u8; 8])?;
+            lm.flush(lsn)?;
+        }
+        // NOTE: as if it crashed before writing the boundary
+        fm.append("test_logmgr.log.0001")?;
+
+        let lm = LogMgr::new(fm.clone(), "test_logmgr.log")?;
+        let recs: Vec<Vec<u8>> = lm.reverse_iter()?.collect();
+        assert_eq!(recs, vec![vec![1u8; 8]]);
+        lm.apppend(&[2u8; 8])?;
+        let recs: Vec<Vec<u8>> = lm.reverse_iter()?.collect();
+        assert_eq!(recs, vec![vec![2u8; 8], vec![1u8; 8]]);
+
+        dir.close()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_corrupted_record() -> Result<()> {
+        let dir = tempdir()?;
+        let fm = Arc::new(FileMgr::new(dir.path(), BLOCK_SIZE));
+        {
+            let lm = LogMgr::new(fm.clone(), "test_logmgr.log")?;
+            for i in 1..=3u8 {
+                lm.apppend(&[i; 4])?;
+            }
+            lm.flush(3)?;
+        }
+
+        // NOTE: the second record is at 40 (length), 44 (bytes), 48 (CRC)
+        let block = BlockId::new("test_logmgr.log.0001", 0);
+        let mut page = Page::for_data(fm.blocksize());
+        fm.read(&block, &mut page)?;
+        page.set_i32(44, 0x7f7f7f7f)?;
+        fm.write(&block, &mut page)?;
+
+        let lm = LogMgr::new(fm.clone(), "test_logmgr.log")?;
+        let mut it = lm.reverse_iter()?;
+        assert_eq!(it.next(), Some(vec![3u8; 4]));
+        assert_eq!(it.next(), None);
+        assert!(it.is_corrupted());
+
+        // NOTE: the records appended after the corruption can still be read
+        let lsn = lm.apppend(&[4u8; 4])?;
+        let recs: Vec<Vec<u8>> = lm.reverse_iter()?.collect();
+        assert_eq!(recs, vec![vec![4u8; 4], vec![3u8; 4]]);
+        let mut it = lm.forward_iter(lsn)?;
+        assert_eq!(it.next(), Some((lsn, vec![4u8; 4])));
+        assert_eq!(it.next(), None);
+        assert!(!it.is_corrupted());
+
+        dir.close()?;
+        Ok(())
+    }
 }
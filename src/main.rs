@@ -3,72 +3,844 @@
 // This software is released under the MIT License.
 // https://opensource.org/licenses/MIT
 
+use clap::{Parser, ValueEnum};
+use rustyline::error::ReadlineError;
+use rustyline::DefaultEditor;
+use simpledb::rdbc::api::Constant;
 use simpledb::record::schema::SqlType;
-use simpledb::server::simple_db::SimpleDB;
-use std::env;
-use std::io;
+use simpledb::server::db_config::DbConfig;
+use simpledb::server::simple_db::{IndexType, KeyOrder, SimpleDB, Transaction};
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
-fn main() {
-    let curr_dir = env::current_dir().unwrap();
-    let mut db = SimpleDB::new(&curr_dir.join("db"), 400, 8);
-    db.init();
-    {
-        let mut line = String::new();
-        while io::stdin().read_line(&mut line).is_ok() {
-            line = line.trim_end().into();
-            if line == "quit" {
-                break;
-            } else {
-                run_sql(&db, &line);
+const HISTORY_FILE: &str = ".simpledb_history";
+
+/// SQL shell of SimpleDB
+#[derive(Debug, Parser)]
+#[command(version)]
+struct Args {
+    /// Database directory
+    #[arg(long, env = "SIMPLEDB_DB_PATH", default_value = "db")]
+    db_path: PathBuf,
+    /// Block size of a new database
+    #[arg(long, env = "SIMPLEDB_BLOCK_SIZE", default_value_t = 1024, value_parser = parse_block_size)]
+    block_size: usize,
+    /// Number of buffers
+    #[arg(long, env = "SIMPLEDB_BUFFERS", default_value_t = 8)]
+    buffers: usize,
+    /// Run the statements and exit
+    #[arg(short, long, value_name = "SQL", conflicts_with = "file")]
+    execute: Option<String>,
+    /// Run the script and exit
+    #[arg(short, long, value_name = "PATH")]
+    file: Option<PathBuf>,
+    /// Output mode of query results
+    #[arg(long, value_enum, default_value_t = OutputMode::Table)]
+    mode: OutputMode,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum OutputMode {
+    /// Fields separated by ` | `
+    Table,
+    /// CSV with a header
+    Csv,
+    /// Array of JSON objects
+    Json,
+}
+
+fn parse_block_size(s: &str) -> Result<usize, String> {
+    let block_size = s.parse().map_err(|e| format!("{e}"))?;
+    let min_blocksize = DbConfig::new(block_size, 1).min_blocksize();
+    if block_size < min_blocksize {
+        return Err(format!("must be at least {min_blocksize}"));
+    }
+    Ok(block_size)
+}
+
+fn main() -> anyhow::Result<ExitCode> {
+    let args = Args::parse();
+    let mut db = SimpleDB::builder()
+        .block_size(args.block_size)
+        .buffers(args.buffers)
+        .open(&args.db_path)?;
+    if let Some(requested) = db.ignored_config() {
+        eprintln!(
+            "warning: the database is opened with its own config {:?} instead of {:?}",
+            db.config(),
+            requested
+        );
+    }
+    db.init()?;
+    if let Some(report) = db.recovery_report() {
+        eprintln!("warning: the database was not shut down cleanly, and has been recovered");
+        if report.corrupted {
+            eprintln!("warning: recovery stopped at a corrupted log record");
+        }
+    }
+    let mut session = Session::new(&db);
+    session.mode = args.mode;
+    let result = if let Some(sql) = &args.execute {
+        session.run_script(sql, "--execute")
+    } else if let Some(path) = &args.file {
+        session.read(path)
+    } else {
+        repl(&mut session)?;
+        Ok(())
+    };
+    let code = match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("ERROR: {}", e);
+            ExitCode::FAILURE
+        }
+    };
+    session.finish()?;
+    db.close()?;
+    if args.execute.is_none() && args.file.is_none() {
+        println!("OK");
+    }
+    Ok(code)
+}
+
+fn repl(session: &mut Session) -> anyhow::Result<()> {
+    let mut rl = DefaultEditor::new()?;
+    let history = PathBuf::from(HISTORY_FILE);
+    let _ = rl.load_history(&history);
+
+    let mut pending = String::new();
+    loop {
+        let prompt = match (pending.is_empty(), session.in_transaction()) {
+            (true, false) => "SQL> ",
+            (true, true) => "SQL*> ",
+            (false, _) => "  -> ",
+        };
+        let line = match rl.readline(prompt) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => {
+                pending.clear();
+                continue;
+            }
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(e.into()),
+        };
+        session.terminal_rows = rl.dimensions().map(|(_, rows)| rows);
+        if pending.is_empty() && line.trim() == "quit" {
+            break;
+        }
+        // NOTE: a meta-command isn't terminated by `;`
+        if pending.is_empty() && line.trim_start().starts_with(['\\', '.']) {
+            rl.add_history_entry(line.trim())?;
+            if let Err(e) = session.meta_command(&line) {
+                println!("ERROR: {}", e);
             }
-            line.clear();
+            continue;
         }
+        if !pending.is_empty() {
+            pending.push('\n');
+        }
+        pending.push_str(&line);
+
+        let (stmts, rest) = split_statements(&pending);
+        for (_, stmt) in stmts {
+            rl.add_history_entry(stmt.split_whitespace().collect::<Vec<_>>().join(" "))?;
+            if let Err(e) = session.run(&stmt) {
+                println!("ERROR: {}", e);
+            }
+        }
+        pending = rest;
+    }
+    if let Err(e) = rl.save_history(&history) {
+        eprintln!("warning: failed to save the history: {}", e);
     }
-    println!("OK");
+    Ok(())
 }
 
-fn run_sql(db: &SimpleDB, line: &String) {
+struct Session<'db> {
+    db: &'db SimpleDB,
+    tx: Option<Arc<Mutex<Transaction>>>,
+    timing: bool,
+    mode: OutputMode,
+    paging: bool,
+    terminal_rows: Option<usize>,
+}
+
+impl<'db> Session<'db> {
+    fn new(db: &'db SimpleDB) -> Self {
+        Self {
+            db,
+            tx: None,
+            timing: false,
+            mode: OutputMode::Table,
+            paging: true,
+            terminal_rows: None,
+        }
+    }
+
+    fn in_transaction(&self) -> bool {
+        self.tx.is_some()
+    }
+
+    fn run(&mut self, stmt: &str) -> anyhow::Result<()> {
+        match stmt.to_lowercase().as_str() {
+            "begin" => {
+                if self.tx.is_some() {
+                    anyhow::bail!("a transaction is already in progress");
+                }
+                self.tx = Some(self.db.new_tx());
+            }
+            "commit" => match self.tx.take() {
+                Some(tx) => tx.lock().unwrap().commit()?,
+                None => anyhow::bail!("no transaction is in progress"),
+            },
+            "rollback" => match self.tx.take() {
+                Some(tx) => tx.lock().unwrap().rollback()?,
+                None => anyhow::bail!("no transaction is in progress"),
+            },
+            _ => {
+                let (tx, autocommit) = match &self.tx {
+                    Some(tx) => (tx.clone(), false),
+                    None => (self.db.new_tx(), true),
+                };
+                let page_rows = self.page_rows();
+                if let Err(e) =
+                    run_sql(self.db, stmt, tx.clone(), self.timing, self.mode, page_rows)
+                {
+                    tx.lock().unwrap().rollback()?;
+                    if !autocommit {
+                        self.tx = None;
+                        anyhow::bail!("{} (the transaction is rolled back)", e);
+                    }
+                    return Err(e);
+                }
+                if autocommit {
+                    tx.lock().unwrap().commit()?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn page_rows(&self) -> Option<usize> {
+        match self.terminal_rows {
+            Some(rows) if self.paging => Some(rows.saturating_sub(2).max(1)),
+            _ => None,
+        }
+    }
+
+    fn run_script(&mut self, script: &str, source: &str) -> anyhow::Result<()> {
+        let (mut stmts, rest) = split_statements(script);
+        if !rest.is_empty() {
+            stmts.push((script.lines().count(), rest));
+        }
+        for (line, stmt) in stmts {
+            self.run(&stmt)
+                .map_err(|e| anyhow::anyhow!("{}:{}: {}\n    {}", source, line, e, stmt))?;
+        }
+        Ok(())
+    }
+
+    fn read(&mut self, path: &Path) -> anyhow::Result<()> {
+        let script = fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("failed to read {}: {}", path.display(), e))?;
+        self.run_script(&script, &path.display().to_string())
+    }
+
+    fn finish(self) -> anyhow::Result<()> {
+        if let Some(tx) = self.tx {
+            eprintln!("warning: the transaction in progress is rolled back");
+            tx.lock().unwrap().rollback()?;
+        }
+        Ok(())
+    }
+
+    fn meta_command(&mut self, line: &str) -> anyhow::Result<()> {
+        let db = self.db;
+        let mut words = line.split_whitespace();
+        let command = words.next().unwrap_or_default().to_lowercase();
+        let args = words.collect::<Vec<_>>();
+        // NOTE: table names are case-insensitive
+        let name = args.first().map(|name| name.to_lowercase());
+        match (command.as_str(), args.len()) {
+            (".help", 0) => {
+                println!(".tables            lists the tables and views");
+                println!(
+                    ".schema [TABLE]    shows the definitions of the tables and views, or of one"
+                );
+                println!(".indexes TABLE     lists the indexes of the table");
+                println!(
+                    ".wal [N]           shows the records of the log, or the latest N of them"
+                );
+                println!(".read PATH         runs the statements of the script");
+                println!(".mode MODE         prints query results as a table, csv or json");
+                println!(".pager [on|off]    shows query results a page at a time on a terminal");
+                println!("\\timing [on|off]   shows how long each statement takes");
+                println!(
+                    "begin, commit and rollback control the transaction that statements run in"
+                );
+                println!("quit               quits the shell");
+            }
+            (".tables", 0) => {
+                for name in db.table_names()? {
+                    println!("{}", name);
+                }
+                for name in db.view_names()? {
+                    println!("{} (view)", name);
+                }
+            }
+            (".schema", 0) => {
+                for name in db.table_names()? {
+                    print_table_def(db, &name)?;
+                }
+                for name in db.view_names()? {
+                    println!("create view {} as {};", name, db.view_def(&name)?);
+                }
+                for name in db.sequence_names()? {
+                    println!("create sequence {};", name);
+                }
+            }
+            (".schema", 1) => {
+                let name = name.unwrap();
+                if db.view_names()?.contains(&name) {
+                    println!("create view {} as {};", name, db.view_def(&name)?);
+                } else {
+                    print_table_def(db, &name)?;
+                }
+            }
+            (".indexes", 1) => {
+                let indexes = db.table_indexes(&name.unwrap())?;
+                println!("name | field | type | order");
+                for index in indexes {
+                    println!(
+                        "{} | {} | {} | {}",
+                        index.name,
+                        index.field,
+                        index_type_def(index.index_type, index.buckets),
+                        key_order_name(index.key_order)
+                    );
+                }
+            }
+            (".wal", 0 | 1) => {
+                let latest = match args.first() {
+                    Some(arg) => Some(arg.parse::<usize>().map_err(|_| {
+                        anyhow::anyhow!(".wal takes the number of records, not {:?}", arg)
+                    })?),
+                    None => None,
+                };
+                let entries = db.log_dump()?.collect::<anyhow::Result<Vec<_>>>()?;
+                let skipped = latest.map_or(0, |n| entries.len().saturating_sub(n));
+                for entry in &entries[skipped..] {
+                    println!("{}", entry);
+                }
+            }
+            (".read", 1) => self.read(Path::new(args[0]))?,
+            (".mode", 0) => println!("{}", mode_name(self.mode)),
+            (".mode", 1) => {
+                self.mode = OutputMode::from_str(args[0], true).map_err(|_| {
+                    anyhow::anyhow!("unknown mode: {} (table, csv or json)", args[0])
+                })?;
+            }
+            (".pager", 0 | 1) => {
+                self.paging = match name.as_deref() {
+                    None => !self.paging,
+                    Some("on") => true,
+                    Some("off") => false,
+                    Some(arg) => anyhow::bail!(".pager takes on or off, not {:?}", arg),
+                };
+                println!("Pager is {}.", if self.paging { "on" } else { "off" });
+            }
+            ("\\timing", 0 | 1) => {
+                self.timing = match name.as_deref() {
+                    None => !self.timing,
+                    Some("on") => true,
+                    Some("off") => false,
+                    Some(arg) => anyhow::bail!("\\timing takes on or off, not {:?}", arg),
+                };
+                println!("Timing is {}.", if self.timing { "on" } else { "off" });
+            }
+            _ => anyhow::bail!("unknown command: {} (see .help)", line.trim()),
+        }
+        Ok(())
+    }
+}
+
+fn print_table_def(db: &SimpleDB, table_name: &str) -> anyhow::Result<()> {
+    let schema = db.table_schema(table_name)?;
+    let fields = schema
+        .fields_iter()
+        .map(|f| match schema.field_type(f) {
+            Some(SqlType::VarChar) => format!("{} varchar({})", f, schema.field_length(f).unwrap()),
+            _ => format!("{} int", f),
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    println!("create table {}({});", table_name, fields);
+    for index in db.table_indexes(table_name)? {
+        let order = match index.key_order {
+            KeyOrder::Ascending => "",
+            KeyOrder::Descending => " desc",
+        };
+        println!(
+            "create index {} on {}({}{}) using {};",
+            index.name,
+            table_name,
+            index.field,
+            order,
+            index_type_def(index.index_type, index.buckets)
+        );
+    }
+    Ok(())
+}
+
+fn index_type_def(index_type: IndexType, buckets: Option<usize>) -> String {
+    match (index_type, buckets) {
+        (IndexType::BTree, _) => "btree".into(),
+        (IndexType::Hash, Some(buckets)) => format!("hash({})", buckets),
+        (IndexType::Hash, None) => "hash".into(),
+    }
+}
+
+fn key_order_name(key_order: KeyOrder) -> &'static str {
+    match key_order {
+        KeyOrder::Ascending => "asc",
+        KeyOrder::Descending => "desc",
+    }
+}
+
+fn split_statements(input: &str) -> (Vec<(usize, String)>, String) {
+    let mut stmts = Vec::new();
+    let mut stmt = String::new();
+    let (mut line, mut start_line) = (1, None);
+    let (mut quoted, mut comment) = (false, false);
+    let mut chars = input.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\n' {
+            line += 1;
+            comment = false;
+        }
+        if comment {
+            continue;
+        }
+        match c {
+            '-' if !quoted && chars.peek() == Some(&'-') => {
+                comment = true;
+                continue;
+            }
+            ';' if !quoted => {
+                if let Some(start_line) = start_line.take() {
+                    stmts.push((start_line, stmt.trim().to_string()));
+                }
+                stmt.clear();
+                continue;
+            }
+            '\'' => quoted = !quoted,
+            _ => {}
+        }
+        if start_line.is_none() && !c.is_whitespace() {
+            start_line = Some(line);
+        }
+        stmt.push(c);
+    }
+    (stmts, stmt.trim().to_string())
+}
+
+fn run_sql(
+    db: &SimpleDB,
+    sql: &str,
+    tx: Arc<Mutex<Transaction>>,
+    timing: bool,
+    mode: OutputMode,
+    page_rows: Option<usize>,
+) -> anyhow::Result<()> {
     let planner = db.planner();
-    let tx = db.new_tx();
-    let count = {
-        if let Ok(p) = planner.create_query_plan(line, tx.clone()) {
-            let mut count = 0;
-            let schema = p.schema();
-
-            let header = schema
-                .fields_iter()
-                .map(|f| f.into())
-                .collect::<Vec<String>>()
-                .join(" | ");
-            println!("{}", header);
-
-            let mut s = p.open(tx.clone());
-            while s.next().unwrap() {
-                let record = schema
-                    .fields_iter()
-                    .map(|f| match schema.field_type(f) {
-                        Some(SqlType::Integer) => s.get_i32(f).unwrap().to_string(),
-                        Some(SqlType::VarChar) => s.get_string(f).unwrap(),
-                        _ => format!("unknown {}", f),
-                    })
-                    .collect::<Vec<String>>()
-                    .join(" | ");
-                println!("{}", record);
-                count += 1;
+    let is_query = sql
+        .split_whitespace()
+        .next()
+        .is_some_and(|word| word.eq_ignore_ascii_case("select"));
+    let (count, steps, query, stopped) = if is_query {
+        let (p, t) = planner.create_query_plan_timed(sql, tx.clone())?;
+        let start = Instant::now();
+        let mut s = p.open(tx.clone())?;
+        let mut printer = RowPrinter::new(mode, s.fields());
+        let mut pager = page_rows.map(Pager::new);
+        let mut stopped = false;
+        printer.begin();
+
+        while s.next()? {
+            if let Some(pager) = &mut pager {
+                if !pager.next_row(&mut io::stdin().lock(), &mut io::stdout())? {
+                    stopped = true;
+                    break;
+                }
             }
-            count
-        } else {
-            planner.execute_update(line, tx.clone()).unwrap()
+            let row = printer
+                .fields
+                .iter()
+                .map(|f| match s.field_type(f) {
+                    Some(SqlType::Integer) => s.get_i32(f).map(Constant::Int),
+                    Some(SqlType::VarChar) => s.get_string(f).map(Constant::String),
+                    _ => Ok(Constant::String(format!("unknown {}", f))),
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            printer.row(&row);
         }
+        s.close();
+        printer.end();
+        let steps = format!(
+            "parse {}, plan {}, execute {}",
+            millis(t.parse),
+            millis(t.plan),
+            millis(start.elapsed())
+        );
+        (printer.rows, steps, true, stopped)
+    } else {
+        let (count, t) = planner.execute_update_timed(sql, tx.clone())?;
+        let steps = format!("parse {}, execute {}", millis(t.parse), millis(t.plan));
+        (count, steps, false, false)
     };
-    tx.borrow_mut().commit().unwrap();
-    print_affected(count);
+    if stopped && mode == OutputMode::Table {
+        println!("Query stopped, {} {} shown", count, rows(count));
+    } else if !query || mode == OutputMode::Table {
+        print_affected(count);
+    }
+    if timing {
+        println!("Time: {} ({} {})", steps, count, rows(count));
+    }
+    Ok(())
+}
+
+struct Pager {
+    page_rows: usize,
+    shown: usize,
+}
+
+impl Pager {
+    fn new(page_rows: usize) -> Self {
+        Self {
+            page_rows,
+            shown: 0,
+        }
+    }
+
+    fn next_row(&mut self, input: &mut impl BufRead, output: &mut impl Write) -> io::Result<bool> {
+        if self.shown > 0 && self.shown % self.page_rows == 0 {
+            write!(output, "-- More -- (Enter for more, q to stop) ")?;
+            output.flush()?;
+            let mut answer = String::new();
+            if input.read_line(&mut answer)? == 0 || answer.trim().eq_ignore_ascii_case("q") {
+                return Ok(false);
+            }
+        }
+        self.shown += 1;
+        Ok(true)
+    }
+}
+
+struct RowPrinter {
+    mode: OutputMode,
+    fields: Vec<String>,
+    rows: u64,
+}
+
+impl RowPrinter {
+    fn new(mode: OutputMode, fields: Vec<String>) -> Self {
+        Self {
+            mode,
+            fields,
+            rows: 0,
+        }
+    }
+
+    fn begin(&self) {
+        match self.mode {
+            OutputMode::Table => println!("{}", self.fields.join(" | ")),
+            OutputMode::Csv => {
+                let header = self.fields.iter().map(|f| csv_field(f)).collect::<Vec<_>>();
+                println!("{}", header.join(","));
+            }
+            OutputMode::Json => print!("["),
+        }
+    }
+
+    fn row(&mut self, values: &[Constant]) {
+        match self.mode {
+            OutputMode::Table => {
+                let values = values
+                    .iter()
+                    .map(|v| match v {
+                        Constant::Int(n) => n.to_string(),
+                        Constant::String(s) => s.clone(),
+                    })
+                    .collect::<Vec<_>>();
+                println!("{}", values.join(" | "));
+            }
+            OutputMode::Csv => {
+                let values = values
+                    .iter()
+                    .map(|v| match v {
+                        Constant::Int(n) => n.to_string(),
+                        Constant::String(s) => csv_field(s),
+                    })
+                    .collect::<Vec<_>>();
+                println!("{}", values.join(","));
+            }
+            OutputMode::Json => {
+                let members = self
+                    .fields
+                    .iter()
+                    .zip(values)
+                    .map(|(f, v)| match v {
+                        Constant::Int(n) => format!("{}:{}", json_string(f), n),
+                        Constant::String(s) => format!("{}:{}", json_string(f), json_string(s)),
+                    })
+                    .collect::<Vec<_>>();
+                let sep = if self.rows == 0 { "" } else { "," };
+                print!("{}\n{{{}}}", sep, members.join(","));
+            }
+        }
+        self.rows += 1;
+    }
+
+    fn end(&self) {
+        if self.mode == OutputMode::Json {
+            println!("{}]", if self.rows == 0 { "" } else { "\n" });
+        }
+    }
+}
+
+fn mode_name(mode: OutputMode) -> &'static str {
+    match mode {
+        OutputMode::Table => "table",
+        OutputMode::Csv => "csv",
+        OutputMode::Json => "json",
+    }
+}
+
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.into()
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut json = String::from('"');
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if c.is_control() => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
 }
 
 fn print_affected(count: u64) {
-    println!(
-        "Query OK, {} {} affected",
-        count,
-        (if count < 2 { "row" } else { "rows" })
-    );
+    println!("Query OK, {} {} affected", count, rows(count));
+}
+
+fn rows(count: u64) -> &'static str {
+    if count < 2 {
+        "row"
+    } else {
+        "rows"
+    }
+}
+
+fn millis(d: Duration) -> String {
+    format!("{:.3} ms", d.as_secs_f64() * 1000.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{csv_field, json_string, split_statements, Args, OutputMode, Pager, Session};
+    use clap::{CommandFactory, Parser};
+    use serde::Deserialize;
+    use simpledb::server::simple_db::SimpleDB;
+    use std::fs;
+    use std::io;
+    use std::path::PathBuf;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_args() {
+        Args::command().debug_assert();
+
+        let args = Args::try_parse_from([
+            "simpledb",
+            "--db-path",
+            "data/shop",
+            "--block-size",
+            "4096",
+            "--buffers",
+            "64",
+            "-e",
+            "select A from T",
+            "--mode",
+            "csv",
+        ])
+        .unwrap();
+        assert_eq!(args.db_path, PathBuf::from("data/shop"));
+        assert_eq!((args.block_size, args.buffers), (4096, 64));
+        assert_eq!(args.execute.as_deref(), Some("select A from T"));
+        assert_eq!(args.file, None);
+        assert_eq!(args.mode, OutputMode::Csv);
+
+        let args = Args::try_parse_from(["simpledb"]).unwrap();
+        assert_eq!((args.execute, args.file), (None, None));
+        assert_eq!(args.mode, OutputMode::Table);
+
+        assert!(
+            Args::try_parse_from(["simpledb", "-e", "select A from T", "-f", "a.sql"]).is_err()
+        );
+        assert!(Args::try_parse_from(["simpledb", "--buffers", "many"]).is_err());
+        let err = Args::try_parse_from(["simpledb", "--block-size", "400"]).unwrap_err();
+        assert!(err.to_string().contains("must be at least"));
+        assert!(Args::try_parse_from(["simpledb", "--mode", "xml"]).is_err());
+    }
+
+    #[test]
+    fn test_split_statements() {
+        let (stmts, rest) = split_statements("create table T(\n  A int,\n  B varchar(9)\n)");
+        assert!(stmts.is_empty());
+        assert_eq!(rest, "create table T(\n  A int,\n  B varchar(9)\n)");
+
+        let input = "insert into T(A, B) values (1, 'a;b--c'); ;\n-- the rows; all of them\n\n  select A -- of T\nfrom T;\nselect";
+        let (stmts, rest) = split_statements(input);
+        assert_eq!(
+            stmts,
+            vec![
+                (1, "insert into T(A, B) values (1, 'a;b--c')".into()),
+                (4, "select A \nfrom T".into())
+            ]
+        );
+        assert_eq!(rest, "select");
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(csv_field("plain text"), "plain text");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\"\n"), "\"say \"\"hi\"\"\n\"");
+        assert_eq!(json_string("plain"), "\"plain\"");
+        assert_eq!(json_string("a\"b\\c\nd\u{1}"), "\"a\\\"b\\\\c\\nd\\u0001\"");
+    }
+
+    #[test]
+    fn test_pager() {
+        let mut pager = Pager::new(2);
+        let mut input = "\n q\n".as_bytes();
+        let mut output = Vec::new();
+        let shown = (0..10)
+            .take_while(|_| pager.next_row(&mut input, &mut output).unwrap())
+            .count();
+        assert_eq!(shown, 4);
+        let prompt = "-- More -- (Enter for more, q to stop) ";
+        assert_eq!(String::from_utf8(output).unwrap(), prompt.repeat(2));
+
+        let mut pager = Pager::new(3);
+        let shown = (0..10)
+            .take_while(|_| pager.next_row(&mut "".as_bytes(), &mut io::sink()).unwrap())
+            .count();
+        assert_eq!(shown, 3);
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Row {
+        a: i32,
+    }
+
+    #[test]
+    fn test_run_script() {
+        let dir = tempdir().unwrap();
+        let mut db = SimpleDB::new_for_test(dir.path(), "main_test.log");
+        db.init().unwrap();
+        let script = dir.path().join("script.sql");
+        fs::write(
+            &script,
+            "create table T(A int);\n\
+             begin;\n\
+             insert into T(A) values (1);\n\
+             rollback;\n\
+             begin;\n\
+             insert into T(A) values (2);\n\
+             commit;\n\
+             insert into T(A) values (3);\n\
+             begin;\n\
+             insert into T(A) values (4);\n\
+             insert into T(X) values (5);\n\
+             insert into T(A) values (6);\n",
+        )
+        .unwrap();
+        {
+            let mut session = Session::new(&db);
+            let err = session.read(&script).unwrap_err().to_string();
+            assert!(err.starts_with(&format!("{}:11: ", script.display())));
+            assert!(err.contains("(the transaction is rolled back)"));
+            assert!(err.ends_with("insert into T(X) values (5)"));
+            assert!(!session.in_transaction());
+
+            session
+                .run_script("begin; insert into T(A) values (7)", "test")
+                .unwrap();
+            assert!(session.in_transaction());
+            let err = session.run("begin").unwrap_err();
+            assert!(err.to_string().contains("already in progress"));
+
+            let err = session.run("select X from T").unwrap_err().to_string();
+            assert!(err.starts_with("field not found: x"));
+            assert!(err.ends_with("(the transaction is rolled back)"));
+            let err = session.run("select A from T where").unwrap_err();
+            assert_eq!(err.to_string(), "Bad syntax");
+            let err = session.run("create table T(B int)").unwrap_err();
+            assert_eq!(err.to_string(), "table already exists: t");
+            session.run("insert into T(A) values (8)").unwrap();
+            session.finish().unwrap();
+        }
+        let tx = db.new_tx();
+        let rows: Vec<Row> = db.query_as("select A from T", tx.clone()).unwrap();
+        tx.lock().unwrap().commit().unwrap();
+        assert_eq!(rows.iter().map(|r| r.a).collect::<Vec<_>>(), vec![2, 3, 8]);
+        db.close().unwrap();
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_timing() {
+        let dir = tempdir().unwrap();
+        let mut db = SimpleDB::new_for_test(dir.path(), "main_timing_test.log");
+        db.init().unwrap();
+        {
+            let mut session = Session::new(&db);
+            assert!(!session.timing);
+            session.meta_command("\\timing").unwrap();
+            assert!(session.timing);
+            session.meta_command("\\TIMING on").unwrap();
+            assert!(session.timing);
+            session.meta_command("\\timing off").unwrap();
+            assert!(!session.timing);
+            let err = session.meta_command("\\timing maybe").unwrap_err();
+            assert_eq!(err.to_string(), "\\timing takes on or off, not \"maybe\"");
+            assert!(!session.timing);
+
+            session.meta_command("\\timing on").unwrap();
+            session.run("create table T(A int)").unwrap();
+            session.run("insert into T(A) values (1)").unwrap();
+            session.run("select A from T").unwrap();
+            assert!(session.run("select A from T where").is_err());
+            session.finish().unwrap();
+        }
+        db.close().unwrap();
+        dir.close().unwrap();
+    }
 }
@@ -0,0 +1,382 @@
+// Copyright (c) 2023 Sho Kuroda <krdlab@gmail.com>
+//
+// This software is released under the MIT License.
+// https://opensource.org/licenses/MIT
+
+use super::temp_table::TempTable;
+use crate::{
+    query::{
+        predicate::Constant,
+        scan::{Result, Scan, ScanError, UpdateScan, RID},
+    },
+    record::{
+        schema::{Collation, Schema, SqlType},
+        table_scan::TableScan,
+    },
+    tx::transaction::Transaction,
+};
+use std::{
+    cmp::Ordering,
+    sync::{Arc, Mutex},
+};
+
+// record comparator
+
+#[derive(Clone)]
+pub struct RecordComparator {
+    fields: Vec<String>,
+    collations: Vec<Collation>,
+    descending: bool,
+}
+
+impl RecordComparator {
+    pub fn new(fields: Vec<String>) -> Self {
+        let collations = vec![Collation::Binary; fields.len()];
+        Self {
+            fields,
+            collations,
+            descending: false,
+        }
+    }
+
+    pub fn with_collations_of(self, schema: &Schema) -> Self {
+        let collations = self
+            .fields
+            .iter()
+            .map(|f| schema.field_collation(f).unwrap_or_default())
+            .collect();
+        Self { collations, ..self }
+    }
+
+    pub fn reversed(self) -> Self {
+        Self {
+            descending: !self.descending,
+            ..self
+        }
+    }
+
+    pub fn compare<S1, S2>(&self, s1: &S1, s2: &S2) -> Result<Ordering>
+    where
+        S1: Scan + ?Sized,
+        S2: Scan + ?Sized,
+    {
+        for (field, collation) in self.fields.iter().zip(self.collations.iter()) {
+            let val1 = s1.get_val(field)?;
+            let val2 = s2.get_val(field)?;
+            match collation.compare(&val1, &val2) {
+                Some(Ordering::Equal) | None => continue,
+                Some(ord) if self.descending => return Ok(ord.reverse()),
+                Some(ord) => return Ok(ord),
+            }
+        }
+        Ok(Ordering::Equal)
+    }
+}
+
+// sort scan
+
+pub struct SortScan {
+    scans: Vec<TableScan>,
+    has_more: Vec<bool>,
+    current: Option<usize>,
+    comp: RecordComparator,
+    // NOTE: for the fields of an empty source
+    schema: Schema,
+}
+
+impl SortScan {
+    pub fn sort(
+        tx: Arc<Mutex<Transaction>>,
+        mut src: Box<dyn UpdateScan>,
+        schema: &Schema,
+        comp: &RecordComparator,
+    ) -> Result<Self> {
+        let mut runs = SortScan::split_into_runs(&tx, &mut src, schema, comp)?;
+        src.close();
+        while runs.len() > 2 {
+            runs = SortScan::do_a_merge_iteration(&tx, runs, schema, comp)?;
+        }
+        SortScan::new(runs, schema.clone(), comp.clone())
+    }
+
+    fn new(runs: Vec<TempTable>, schema: Schema, comp: RecordComparator) -> Result<Self> {
+        let scans: Vec<TableScan> = runs.iter().map(|run| run.open()).collect::<Result<_>>()?;
+        let has_more = vec![false; scans.len()];
+        let mut scan = Self {
+            scans,
+            has_more,
+            current: None,
+            comp,
+            schema,
+        };
+        scan.before_first()?;
+        Ok(scan)
+    }
+
+    fn split_into_runs(
+        tx: &Arc<Mutex<Transaction>>,
+        src: &mut Box<dyn UpdateScan>,
+        schema: &Schema,
+        comp: &RecordComparator,
+    ) -> Result<Vec<TempTable>> {
+        let mut temps = Vec::new();
+        src.before_first()?;
+        if !src.next()? {
+            return Ok(temps);
+        }
+
+        let mut current_temp = TempTable::new(tx.clone(), schema.clone());
+        let mut current_scan = current_temp.open()?;
+        while SortScan::copy(src.as_mut(), &mut current_scan, schema)? {
+            if comp.compare(src.as_ref(), &current_scan)? == Ordering::Less {
+                // NOTE: start a new run
+                temps.push(current_temp);
+                current_temp = TempTable::new(tx.clone(), schema.clone());
+                current_scan = current_temp.open()?;
+            }
+        }
+        temps.push(current_temp);
+        Ok(temps)
+    }
+
+    fn do_a_merge_iteration(
+        tx: &Arc<Mutex<Transaction>>,
+        mut runs: Vec<TempTable>,
+        schema: &Schema,
+        comp: &RecordComparator,
+    ) -> Result<Vec<TempTable>> {
+        let mut result = Vec::new();
+        while runs.len() > 1 {
+            let p1 = runs.remove(0);
+            let p2 = runs.remove(0);
+            result.push(SortScan::merge_two_runs(tx, p1, p2, schema, comp)?);
+        }
+        if let Some(p) = runs.pop() {
+            result.push(p);
+        }
+        Ok(result)
+    }
+
+    fn merge_two_runs(
+        tx: &Arc<Mutex<Transaction>>,
+        p1: TempTable,
+        p2: TempTable,
+        schema: &Schema,
+        comp: &RecordComparator,
+    ) -> Result<TempTable> {
+        let mut src1 = p1.open()?;
+        let mut src2 = p2.open()?;
+        let result = TempTable::new(tx.clone(), schema.clone());
+        let mut dest = result.open()?;
+
+        let mut has_more1 = src1.next()?;
+        let mut has_more2 = src2.next()?;
+        while has_more1 && has_more2 {
+            if comp.compare(&src1, &src2)? == Ordering::Less {
+                has_more1 = SortScan::copy(&mut src1, &mut dest, schema)?;
+            } else {
+                has_more2 = SortScan::copy(&mut src2, &mut dest, schema)?;
+            }
+        }
+        while has_more1 {
+            has_more1 = SortScan::copy(&mut src1, &mut dest, schema)?;
+        }
+        while has_more2 {
+            has_more2 = SortScan::copy(&mut src2, &mut dest, schema)?;
+        }
+        Ok(result)
+    }
+
+    fn copy<S: UpdateScan + ?Sized>(
+        src: &mut S,
+        dest: &mut TableScan,
+        schema: &Schema,
+    ) -> Result<bool> {
+        dest.insert()?;
+        for field in schema.fields_iter() {
+            dest.set_val(field, src.get_val(field)?)?;
+        }
+        src.next()
+    }
+
+    fn current_scan(&self) -> Result<&TableScan> {
+        self.current
+            .map(|i| &self.scans[i])
+            .ok_or(ScanError::NoCurrentRecord)
+    }
+}
+
+impl Scan for SortScan {
+    fn before_first(&mut self) -> Result<()> {
+        self.current = None;
+        for (scan, has_more) in self.scans.iter_mut().zip(self.has_more.iter_mut()) {
+            scan.before_first()?;
+            *has_more = scan.next()?;
+        }
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<bool> {
+        if let Some(i) = self.current {
+            self.has_more[i] = self.scans[i].next()?;
+        }
+
+        let mut next = None;
+        for i in 0..self.scans.len() {
+            if !self.has_more[i] {
+                continue;
+            }
+            next = match next {
+                Some(j)
+                    if self.comp.compare(&self.scans[j], &self.scans[i])? != Ordering::Greater =>
+                {
+                    Some(j)
+                }
+                _ => Some(i),
+            };
+        }
+        self.current = next;
+        Ok(self.current.is_some())
+    }
+
+    fn get_i32(&self, field_name: &str) -> Result<i32> {
+        self.current_scan()?.get_i32(field_name)
+    }
+
+    fn get_string(&self, field_name: &str) -> Result<String> {
+        self.current_scan()?.get_string(field_name)
+    }
+
+    fn read_string(&self, field_name: &str, buf: &mut String) -> Result<()> {
+        self.current_scan()?.read_string(field_name, buf)
+    }
+
+    fn get_val(&self, field_name: &str) -> Result<Constant> {
+        self.current_scan()?.get_val(field_name)
+    }
+
+    fn has_field(&self, field_name: &str) -> bool {
+        self.schema.has_field(field_name)
+    }
+
+    fn fields(&self) -> Vec<String> {
+        self.schema.fields_iter().cloned().collect()
+    }
+
+    fn field_type(&self, field_name: &str) -> Option<SqlType> {
+        self.schema.field_type(field_name)
+    }
+
+    fn close(&mut self) {
+        for scan in self.scans.iter_mut() {
+            Scan::close(scan);
+        }
+    }
+}
+
+impl UpdateScan for SortScan {
+    fn set_val(&mut self, _field_name: &str, _value: Constant) -> Result<()> {
+        Err(ScanError::UnsupportedOperation("set_val".into()))
+    }
+
+    fn set_i32(&mut self, _field_name: &str, _value: i32) -> Result<()> {
+        Err(ScanError::UnsupportedOperation("set_i32".into()))
+    }
+
+    fn set_string(&mut self, _field_name: &str, _value: String) -> Result<()> {
+        Err(ScanError::UnsupportedOperation("set_string".into()))
+    }
+
+    fn insert(&mut self) -> Result<()> {
+        Err(ScanError::UnsupportedOperation("insert".into()))
+    }
+
+    fn delete(&mut self) -> Result<()> {
+        Err(ScanError::UnsupportedOperation("delete".into()))
+    }
+
+    fn get_rid(&self) -> Result<RID> {
+        Err(ScanError::UnsupportedOperation("get_rid".into()))
+    }
+
+    fn move_to_rid(&mut self, _rid: RID) -> Result<()> {
+        Err(ScanError::UnsupportedOperation("move_to_rid".into()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RecordComparator, SortScan};
+    use crate::{
+        materialize::temp_table::TempTable, query::scan::Scan, record::schema::Schema,
+        server::simple_db::SimpleDB,
+    };
+    use tempfile::tempdir;
+
+    #[test]
+    fn test() {
+        let dir = tempdir().unwrap();
+        {
+            let db = SimpleDB::new_for_test(dir.path(), "sort_test.log");
+            let tx = db.new_tx();
+            {
+                let mut schema = Schema::new();
+                schema.add_i32_field("A");
+                schema.add_string_field("B", 5);
+
+                let src = TempTable::new(tx.clone(), schema.clone());
+                {
+                    let mut ts = src.open().unwrap();
+                    for i in 0..200 {
+                        ts.insert().unwrap();
+                        ts.set_i32("A", (i * 37) % 10).unwrap();
+                        ts.set_string("B", format!("b{}", (i * 53) % 200)).unwrap();
+                    }
+                }
+
+                let comp = RecordComparator::new(vec!["A".into(), "B".into()]);
+                let mut s =
+                    SortScan::sort(tx.clone(), Box::new(src.open().unwrap()), &schema, &comp)
+                        .unwrap();
+                let mut rows = Vec::new();
+                while s.next().unwrap() {
+                    rows.push((s.get_i32("A").unwrap(), s.get_string("B").unwrap()));
+                }
+                assert_eq!(rows.len(), 200);
+                assert!(rows.windows(2).all(|w| w[0] <= w[1]));
+
+                s.before_first().unwrap();
+                assert!(s.next().unwrap());
+                assert_eq!(s.get_i32("A").unwrap(), 0);
+                assert!(s.has_field("B"));
+                s.close();
+            }
+            tx.lock().unwrap().commit().unwrap();
+        }
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_empty() {
+        let dir = tempdir().unwrap();
+        {
+            let db = SimpleDB::new_for_test(dir.path(), "sort_empty_test.log");
+            let tx = db.new_tx();
+            {
+                let mut schema = Schema::new();
+                schema.add_i32_field("A");
+                let src = TempTable::new(tx.clone(), schema.clone());
+                let comp = RecordComparator::new(vec!["A".into()]);
+                let mut s =
+                    SortScan::sort(tx.clone(), Box::new(src.open().unwrap()), &schema, &comp)
+                        .unwrap();
+                assert!(!s.next().unwrap());
+                assert!(s.get_val("A").is_err());
+                assert!(s.has_field("A"));
+                assert_eq!(s.fields(), ["A"]);
+            }
+            tx.lock().unwrap().commit().unwrap();
+        }
+        dir.close().unwrap();
+    }
+}
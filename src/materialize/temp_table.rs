@@ -0,0 +1,89 @@
+// Copyright (c) 2023 Sho Kuroda <krdlab@gmail.com>
+//
+// This software is released under the MIT License.
+// https://opensource.org/licenses/MIT
+
+use crate::{
+    query::scan::Result,
+    record::{
+        schema::{Layout, Schema},
+        table_scan::TableScan,
+    },
+    tx::transaction::Transaction,
+};
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc, Mutex,
+};
+
+static NEXT_TABLE_NUM: AtomicUsize = AtomicUsize::new(0);
+
+// NOTE: FileMgr removes "temp*" files on startup
+pub struct TempTable {
+    tx: Arc<Mutex<Transaction>>,
+    table_name: String,
+    layout: Layout,
+}
+
+impl TempTable {
+    pub fn new(tx: Arc<Mutex<Transaction>>, schema: Schema) -> Self {
+        Self {
+            tx,
+            table_name: TempTable::next_table_name(),
+            layout: Layout::new(schema),
+        }
+    }
+
+    pub fn open(&self) -> Result<TableScan> {
+        TableScan::new(
+            self.tx.clone(),
+            self.table_name.clone(),
+            self.layout.clone(),
+        )
+    }
+
+    fn next_table_name() -> String {
+        let num = NEXT_TABLE_NUM.fetch_add(1, Ordering::SeqCst) + 1;
+        format!("temp{num}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TempTable;
+    use crate::{record::schema::Schema, server::simple_db::SimpleDB};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test() {
+        let dir = tempdir().unwrap();
+        {
+            let db = SimpleDB::new_for_test(dir.path(), "temp_table_test.log");
+            let tx = db.new_tx();
+            {
+                let mut schema = Schema::new();
+                schema.add_i32_field("A");
+
+                let t1 = TempTable::new(tx.clone(), schema.clone());
+                let t2 = TempTable::new(tx.clone(), schema);
+                assert!(t1.table_name.starts_with("temp"));
+                assert_ne!(t1.table_name, t2.table_name);
+
+                let mut ts = t1.open().unwrap();
+                for i in 0..10 {
+                    ts.insert().unwrap();
+                    ts.set_i32("A", i).unwrap();
+                }
+                ts.before_first().unwrap();
+                let mut count = 0;
+                while ts.next().unwrap() {
+                    assert_eq!(ts.get_i32("A").unwrap(), count);
+                    count += 1;
+                }
+                assert_eq!(count, 10);
+            }
+            tx.lock().unwrap().commit().unwrap();
+        }
+        dir.close().unwrap();
+    }
+}
@@ -0,0 +1,495 @@
+// Copyright (c) 2023 Sho Kuroda <krdlab@gmail.com>
+//
+// This software is released under the MIT License.
+// https://opensource.org/licenses/MIT
+
+use super::common::{MetadataError, Result};
+use super::table_mgr::TableMgr;
+use crate::{
+    record::{schema::Schema, table_scan::TableScan},
+    tx::transaction::Transaction,
+};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::hash_map::RandomState,
+    fmt::Display,
+    hash::{BuildHasher, Hasher},
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Privilege {
+    Select,
+    Insert,
+    Update,
+    Delete,
+}
+
+impl Privilege {
+    pub const ALL: [Privilege; 4] = [
+        Privilege::Select,
+        Privilege::Insert,
+        Privilege::Update,
+        Privilege::Delete,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Privilege::Select => "select",
+            Privilege::Insert => "insert",
+            Privilege::Update => "update",
+            Privilege::Delete => "delete",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|p| p.name() == name)
+    }
+}
+
+impl Display for Privilege {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+pub struct AuthMgr {
+    tm: Arc<TableMgr>,
+}
+
+pub(super) const USER_CATALOG_TABLE_NAME: &str = "usercat";
+pub(super) const PRIVILEGE_CATALOG_TABLE_NAME: &str = "privcat";
+// NOTE: in hexadecimal
+const SALT_LENGTH: usize = 16;
+const HASH_LENGTH: usize = 64;
+// NOTE: iterated to slow down guessing
+const HASH_ROUNDS: usize = 4096;
+const MAX_PRIVILEGE_LENGTH: usize = 6;
+
+impl AuthMgr {
+    pub fn new(tm: Arc<TableMgr>) -> Self {
+        Self { tm }
+    }
+
+    pub fn init(&self, tx: Arc<Mutex<Transaction>>) {
+        for (tblname, schema) in Self::catalog_schemas(self.tm.max_name_length()) {
+            self.tm.create_table(tblname, schema, tx.clone()).unwrap();
+        }
+    }
+
+    pub(super) fn catalog_schemas(max_name_length: usize) -> Vec<(&'static str, Schema)> {
+        vec![
+            (USER_CATALOG_TABLE_NAME, Self::user_schema(max_name_length)),
+            (
+                PRIVILEGE_CATALOG_TABLE_NAME,
+                Self::privilege_schema(max_name_length),
+            ),
+        ]
+    }
+
+    fn user_schema(max_name_length: usize) -> Schema {
+        // NOTE: user -> salt, hash
+        let mut schema = Schema::new();
+        schema.add_string_field("username", max_name_length);
+        schema.add_string_field("salt", SALT_LENGTH);
+        schema.add_string_field("pwhash", HASH_LENGTH);
+        schema
+    }
+
+    fn privilege_schema(max_name_length: usize) -> Schema {
+        // NOTE: user -> table or view -> privilege
+        let mut schema = Schema::new();
+        schema.add_string_field("username", max_name_length);
+        schema.add_string_field("tblname", max_name_length);
+        schema.add_string_field("privilege", MAX_PRIVILEGE_LENGTH);
+        schema
+    }
+
+    pub fn create_user(
+        &self,
+        user_name: &str,
+        password: &str,
+        tx: Arc<Mutex<Transaction>>,
+    ) -> Result<()> {
+        self.tm.check_name(user_name)?;
+        let mut ts = self.open_or_create(
+            USER_CATALOG_TABLE_NAME,
+            Self::user_schema(self.tm.max_name_length()),
+            tx,
+        )?;
+        while ts.next()? {
+            if ts.get_string("username")? == user_name {
+                return Err(MetadataError::UserAlreadyExists(user_name.into()));
+            }
+        }
+        let salt = new_salt();
+        ts.insert()?;
+        ts.set_string("username", user_name.into())?;
+        ts.set_string("pwhash", hash_password(&salt, password))?;
+        ts.set_string("salt", salt)?;
+        Ok(())
+    }
+
+    pub fn alter_password(
+        &self,
+        user_name: &str,
+        password: &str,
+        tx: Arc<Mutex<Transaction>>,
+    ) -> Result<()> {
+        if let Some(mut ts) = self.open_catalog(USER_CATALOG_TABLE_NAME, tx)? {
+            while ts.next()? {
+                if ts.get_string("username")? == user_name {
+                    let salt = new_salt();
+                    ts.set_string("pwhash", hash_password(&salt, password))?;
+                    ts.set_string("salt", salt)?;
+                    return Ok(());
+                }
+            }
+        }
+        Err(MetadataError::UserNotFound(user_name.into()))
+    }
+
+    pub fn authenticate(
+        &self,
+        user_name: &str,
+        password: &str,
+        tx: Arc<Mutex<Transaction>>,
+    ) -> Result<bool> {
+        if let Some(mut ts) = self.read_catalog(USER_CATALOG_TABLE_NAME, tx)? {
+            while ts.next()? {
+                if ts.get_string("username")? == user_name {
+                    let hash = hash_password(&ts.get_string("salt")?, password);
+                    return Ok(constant_time_eq(
+                        hash.as_bytes(),
+                        ts.get_string("pwhash")?.as_bytes(),
+                    ));
+                }
+            }
+        }
+        Ok(false)
+    }
+
+    pub fn user_names(&self, tx: Arc<Mutex<Transaction>>) -> Result<Vec<String>> {
+        let mut names = Vec::new();
+        if let Some(mut ts) = self.read_catalog(USER_CATALOG_TABLE_NAME, tx)? {
+            while ts.next()? {
+                names.push(ts.get_string("username")?);
+            }
+        }
+        Ok(names)
+    }
+
+    pub fn grant(
+        &self,
+        user_name: &str,
+        table_name: &str,
+        privileges: &[Privilege],
+        tx: Arc<Mutex<Transaction>>,
+    ) -> Result<()> {
+        self.check_user(user_name, tx.clone())?;
+        let granted = self.privileges_on(user_name, table_name, tx.clone())?;
+        let schema = Self::privilege_schema(self.tm.max_name_length());
+        let mut ts = self.open_or_create(PRIVILEGE_CATALOG_TABLE_NAME, schema, tx)?;
+        for p in privileges {
+            if !granted.contains(p) {
+                ts.insert()?;
+                ts.set_string("username", user_name.into())?;
+                ts.set_string("tblname", table_name.into())?;
+                ts.set_string("privilege", p.name().into())?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn revoke(
+        &self,
+        user_name: &str,
+        table_name: &str,
+        privileges: &[Privilege],
+        tx: Arc<Mutex<Transaction>>,
+    ) -> Result<()> {
+        self.check_user(user_name, tx.clone())?;
+        self.delete_privileges(tx, |ts| {
+            Ok(ts.get_string("username")? == user_name
+                && ts.get_string("tblname")? == table_name
+                && Privilege::from_name(&ts.get_string("privilege")?)
+                    .is_some_and(|p| privileges.contains(&p)))
+        })
+    }
+
+    pub fn has_privilege(
+        &self,
+        user_name: &str,
+        table_name: &str,
+        privilege: Privilege,
+        tx: Arc<Mutex<Transaction>>,
+    ) -> Result<bool> {
+        Ok(self
+            .privileges_on(user_name, table_name, tx)?
+            .contains(&privilege))
+    }
+
+    pub fn privileges(
+        &self,
+        user_name: &str,
+        tx: Arc<Mutex<Transaction>>,
+    ) -> Result<Vec<(String, Privilege)>> {
+        let mut privileges = Vec::new();
+        if let Some(mut ts) = self.read_catalog(PRIVILEGE_CATALOG_TABLE_NAME, tx)? {
+            while ts.next()? {
+                if ts.get_string("username")? == user_name {
+                    if let Some(p) = Privilege::from_name(&ts.get_string("privilege")?) {
+                        privileges.push((ts.get_string("tblname")?, p));
+                    }
+                }
+            }
+        }
+        Ok(privileges)
+    }
+
+    fn privileges_on(
+        &self,
+        user_name: &str,
+        table_name: &str,
+        tx: Arc<Mutex<Transaction>>,
+    ) -> Result<Vec<Privilege>> {
+        Ok(self
+            .privileges(user_name, tx)?
+            .into_iter()
+            .filter(|(t, _)| t == table_name)
+            .map(|(_, p)| p)
+            .collect())
+    }
+
+    pub fn drop_privileges(&self, table_name: &str, tx: Arc<Mutex<Transaction>>) -> Result<()> {
+        self.delete_privileges(tx, |ts| Ok(ts.get_string("tblname")? == table_name))
+    }
+
+    pub fn rename_privileges(
+        &self,
+        table_name: &str,
+        new_name: &str,
+        tx: Arc<Mutex<Transaction>>,
+    ) -> Result<()> {
+        if let Some(mut ts) = self.read_catalog(PRIVILEGE_CATALOG_TABLE_NAME, tx)? {
+            while ts.next()? {
+                if ts.get_string("tblname")? == table_name {
+                    ts.set_string("tblname", new_name.into())?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn delete_privileges(
+        &self,
+        tx: Arc<Mutex<Transaction>>,
+        matches: impl Fn(&TableScan) -> Result<bool>,
+    ) -> Result<()> {
+        if let Some(mut ts) = self.read_catalog(PRIVILEGE_CATALOG_TABLE_NAME, tx)? {
+            while ts.next()? {
+                if matches(&ts)? {
+                    ts.delete()?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn check_user(&self, user_name: &str, tx: Arc<Mutex<Transaction>>) -> Result<()> {
+        if self.user_names(tx)?.iter().any(|u| u == user_name) {
+            Ok(())
+        } else {
+            Err(MetadataError::UserNotFound(user_name.into()))
+        }
+    }
+
+    fn read_catalog(
+        &self,
+        catalog_name: &str,
+        tx: Arc<Mutex<Transaction>>,
+    ) -> Result<Option<TableScan>> {
+        if tx
+            .lock()
+            .unwrap()
+            .size(&TableScan::filename(catalog_name))?
+            == 0
+        {
+            return Ok(None);
+        }
+        self.open_catalog(catalog_name, tx)
+    }
+
+    fn open_catalog(
+        &self,
+        catalog_name: &str,
+        tx: Arc<Mutex<Transaction>>,
+    ) -> Result<Option<TableScan>> {
+        match self.tm.layout(catalog_name, tx.clone()) {
+            Ok(layout) => Ok(Some(TableScan::new(tx, catalog_name.into(), layout)?)),
+            Err(MetadataError::TableNotFound(_)) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn open_or_create(
+        &self,
+        catalog_name: &str,
+        schema: Schema,
+        tx: Arc<Mutex<Transaction>>,
+    ) -> Result<TableScan> {
+        if let Some(ts) = self.open_catalog(catalog_name, tx.clone())? {
+            return Ok(ts);
+        }
+        self.tm.create_table(catalog_name, schema, tx.clone())?;
+        Ok(self.open_catalog(catalog_name, tx)?.unwrap())
+    }
+}
+
+fn new_salt() -> String {
+    // NOTE: the time tells apart the salts of the same seed
+    let mut hasher = RandomState::new().build_hasher();
+    if let Ok(d) = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH) {
+        hasher.write_u128(d.as_nanos());
+    }
+    format!("{:016x}", hasher.finish())
+}
+
+fn hash_password(salt: &str, password: &str) -> String {
+    let mut hash = Sha256::new()
+        .chain_update(salt)
+        .chain_update(password)
+        .finalize();
+    for _ in 1..HASH_ROUNDS {
+        hash = Sha256::new()
+            .chain_update(hash)
+            .chain_update(password)
+            .finalize();
+    }
+    hash.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// NOTE: constant time
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{hash_password, AuthMgr, Privilege, HASH_LENGTH};
+    use crate::{
+        metadata::{
+            common::MetadataError,
+            table_mgr::{TableMgr, MAX_NAME_LENGTH},
+        },
+        server::simple_db::SimpleDB,
+    };
+    use std::sync::Arc;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test() {
+        let dir = tempdir().unwrap();
+        {
+            let db = SimpleDB::new_for_test(dir.path(), "auth_test.log");
+            let tm = Arc::new(TableMgr::new(MAX_NAME_LENGTH));
+            let am = AuthMgr::new(tm.clone());
+            {
+                let tx = db.new_tx();
+                tm.init(tx.clone());
+                assert!(am.user_names(tx.clone()).unwrap().is_empty());
+                assert!(!am.authenticate("alice", "", tx.clone()).unwrap());
+                am.create_user("alice", "secret", tx.clone()).unwrap();
+                am.create_user("bob", "secret", tx.clone()).unwrap();
+                assert!(matches!(
+                    am.create_user("alice", "other", tx.clone()),
+                    Err(MetadataError::UserAlreadyExists(u)) if u == "alice"
+                ));
+                tx.lock().unwrap().commit().unwrap();
+            }
+            {
+                let tx = db.new_tx();
+                assert!(am.authenticate("alice", "secret", tx.clone()).unwrap());
+                assert!(!am.authenticate("alice", "Secret", tx.clone()).unwrap());
+                assert!(!am.authenticate("carol", "secret", tx.clone()).unwrap());
+
+                am.alter_password("alice", "changed", tx.clone()).unwrap();
+                assert!(!am.authenticate("alice", "secret", tx.clone()).unwrap());
+                assert!(am.authenticate("alice", "changed", tx.clone()).unwrap());
+                assert!(am.authenticate("bob", "secret", tx.clone()).unwrap());
+                assert!(matches!(
+                    am.alter_password("carol", "secret", tx.clone()),
+                    Err(MetadataError::UserNotFound(u)) if u == "carol"
+                ));
+                assert_eq!(am.user_names(tx.clone()).unwrap(), ["alice", "bob"]);
+                tx.lock().unwrap().commit().unwrap();
+            }
+        }
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_privileges() {
+        let dir = tempdir().unwrap();
+        {
+            let db = SimpleDB::new_for_test(dir.path(), "auth_test_privileges.log");
+            let tm = Arc::new(TableMgr::new(MAX_NAME_LENGTH));
+            let am = AuthMgr::new(tm.clone());
+            let tx = db.new_tx();
+            tm.init(tx.clone());
+            am.init(tx.clone());
+            am.create_user("alice", "secret", tx.clone()).unwrap();
+            assert!(matches!(
+                am.grant("bob", "t", &[Privilege::Select], tx.clone()),
+                Err(MetadataError::UserNotFound(u)) if u == "bob"
+            ));
+
+            am.grant(
+                "alice",
+                "t",
+                &[Privilege::Select, Privilege::Insert],
+                tx.clone(),
+            )
+            .unwrap();
+            am.grant("alice", "t", &Privilege::ALL, tx.clone()).unwrap();
+            am.grant("alice", "u", &[Privilege::Select], tx.clone())
+                .unwrap();
+            assert_eq!(am.privileges("alice", tx.clone()).unwrap().len(), 5);
+            assert!(am
+                .has_privilege("alice", "t", Privilege::Delete, tx.clone())
+                .unwrap());
+
+            am.revoke(
+                "alice",
+                "t",
+                &[Privilege::Insert, Privilege::Delete],
+                tx.clone(),
+            )
+            .unwrap();
+            assert!(!am
+                .has_privilege("alice", "t", Privilege::Insert, tx.clone())
+                .unwrap());
+            assert!(am
+                .has_privilege("alice", "t", Privilege::Update, tx.clone())
+                .unwrap());
+
+            am.rename_privileges("u", "v", tx.clone()).unwrap();
+            am.drop_privileges("t", tx.clone()).unwrap();
+            assert_eq!(
+                am.privileges("alice", tx.clone()).unwrap(),
+                [("v".to_string(), Privilege::Select)]
+            );
+            tx.lock().unwrap().commit().unwrap();
+        }
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_hash_password() {
+        let hash = hash_password("0123456789abcdef", "secret");
+        assert_eq!(hash.len(), HASH_LENGTH);
+        assert_eq!(hash, hash_password("0123456789abcdef", "secret"));
+        assert_ne!(hash, hash_password("0123456789abcdee", "secret"));
+    }
+}
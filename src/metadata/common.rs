@@ -3,7 +3,7 @@
 // This software is released under the MIT License.
 // https://opensource.org/licenses/MIT
 
-use crate::query::scan::ScanError;
+use crate::{index::IndexError, query::scan::ScanError, tx::transaction::TransactionError};
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -14,11 +14,62 @@ pub enum MetadataError {
     #[error("table already exists: {0}")]
     TableAlreadyExists(String),
 
+    #[error("a record of {table} takes {slotsize} bytes, which doesn't fit in a block; its fields can take at most {max} bytes")]
+    RecordTooLarge {
+        table: String,
+        slotsize: usize,
+        max: usize,
+    },
+
+    #[error("field not found: {0}.{1}")]
+    FieldNotFound(String, String),
+
+    #[error("field already exists: {0}.{1}")]
+    FieldAlreadyExists(String, String),
+
+    #[error("field {0}.{1} is indexed by {2}")]
+    FieldIndexed(String, String, String),
+
+    #[error("name too long: {0} (at most {1} characters)")]
+    NameTooLong(String, usize),
+
     #[error("view not found: {0}")]
     ViewNotFound(String),
 
-    #[error("{0:?}")]
+    #[error("view {0} depends on {1}")]
+    DependentView(String, String),
+
+    #[error("sequence not found: {0}")]
+    SequenceNotFound(String),
+
+    #[error("sequence already exists: {0}")]
+    SequenceAlreadyExists(String),
+
+    #[error("user not found: {0}")]
+    UserNotFound(String),
+
+    #[error("user already exists: {0}")]
+    UserAlreadyExists(String),
+
+    #[error("tablespace not found: {0}")]
+    TablespaceNotFound(String),
+
+    #[error("tablespace already exists: {0}")]
+    TablespaceAlreadyExists(String),
+
+    #[error(
+        "the index catalog of this database cannot keep the buckets of {0}, which must have {1}"
+    )]
+    BucketsNotKept(String, usize),
+
+    #[error("{0}")]
+    Transaction(#[from] TransactionError),
+
+    #[error("{0}")]
     Scan(#[from] ScanError),
+
+    #[error("{0}")]
+    Index(#[from] IndexError),
 }
 
 pub type Result<T> = core::result::Result<T, MetadataError>;
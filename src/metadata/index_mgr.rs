@@ -4,55 +4,67 @@
 // https://opensource.org/licenses/MIT
 
 use super::{
-    common::Result,
+    common::{MetadataError, Result},
     stat_mgr::{StatInfo, StatMgr},
-    table_mgr::{TableMgr, MAX_NAME_LENGTH},
+    table_mgr::TableMgr,
 };
 use crate::{
+    index::{
+        self,
+        btree_index::BTreeIndex,
+        hash_index::{HashIndex, DEFAULT_BUCKETS},
+        CollatedIndex, Index, IndexError, IndexType, KeyOrder,
+    },
     record::{
-        schema::{Layout, Schema, SqlType},
+        schema::{Collation, Layout, Schema, SqlType},
         table_scan::TableScan,
     },
     tx::transaction::Transaction,
 };
-use std::{cell::RefCell, collections::HashMap, rc::Rc, sync::Arc};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
 
-/* FIXME: begin draft implementations */
-pub trait Index {}
-struct HashIndex<'ly> {
-    _index_name: String,
-    _layout: &'ly Layout,
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IndexStatInfo {
+    height: usize,
+    num_leaves: usize,
 }
-impl<'ly> HashIndex<'ly> {
-    const NUM_BUCKETS: usize = 100;
 
-    pub fn new(index_name: impl Into<String>, layout: &'ly Layout) -> Self {
-        Self {
-            _index_name: index_name.into(),
-            _layout: layout,
-        }
+impl IndexStatInfo {
+    pub fn new(height: usize, num_leaves: usize) -> Self {
+        Self { height, num_leaves }
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
     }
 
-    pub fn search_cost(num_blocks: usize, _rec_per_blk: usize) -> usize {
-        num_blocks / HashIndex::NUM_BUCKETS
+    pub fn num_leaves(&self) -> usize {
+        self.num_leaves
     }
 }
-impl<'ly> Index for HashIndex<'ly> {}
-/* FIXME: end draft implementations */
 
 pub struct IndexInfo {
     index_name: String,
     field_name: String,
+    index_type: IndexType,
+    key_order: KeyOrder,
+    buckets: usize,
     _table_schema: Schema,
     block_size: usize,
     index_layout: Layout,
     stat_info: StatInfo,
+    index_stat_info: Option<IndexStatInfo>,
 }
 
 impl IndexInfo {
     pub fn new(
         index_name: &str,
         field_name: &str,
+        index_type: IndexType,
+        key_order: KeyOrder,
         table_schema: Schema,
         block_size: usize,
         stat_info: StatInfo,
@@ -61,13 +73,28 @@ impl IndexInfo {
         Self {
             index_name: index_name.into(),
             field_name: field_name.into(),
+            index_type,
+            key_order,
+            buckets: DEFAULT_BUCKETS,
             _table_schema: table_schema,
             block_size,
             index_layout,
             stat_info,
+            index_stat_info: None,
+        }
+    }
+
+    pub fn with_index_stat_info(self, index_stat_info: Option<IndexStatInfo>) -> Self {
+        Self {
+            index_stat_info,
+            ..self
         }
     }
 
+    pub fn with_buckets(self, buckets: usize) -> Self {
+        Self { buckets, ..self }
+    }
+
     fn create_index_layout(table_schema: &Schema, field_name: &str) -> Layout {
         let mut schema = Schema::new();
         schema.add_i32_field("block");
@@ -78,23 +105,104 @@ impl IndexInfo {
         } else {
             let flength = table_schema.field_length(field_name).unwrap();
             schema.add_string_field("dataval", flength);
+            schema.set_collation("dataval", table_schema.field_collation(field_name).unwrap());
         }
 
         Layout::new(schema)
     }
 
-    pub fn open(&self) -> Box<dyn Index + '_> {
-        Box::new(HashIndex::new(&self.index_name, &self.index_layout))
+    pub fn index_name(&self) -> &str {
+        &self.index_name
+    }
+
+    pub fn index_type(&self) -> IndexType {
+        self.index_type
+    }
+
+    pub fn key_order(&self) -> KeyOrder {
+        self.key_order
+    }
+
+    pub fn buckets(&self) -> Option<usize> {
+        (self.index_type == IndexType::Hash).then_some(self.buckets)
+    }
+
+    pub fn filenames(&self) -> Vec<String> {
+        self.index_type.filenames(&self.index_name, self.buckets)
+    }
+
+    pub fn index_layout(&self) -> &Layout {
+        &self.index_layout
+    }
+
+    pub fn collation(&self) -> Collation {
+        self.index_layout
+            .schema()
+            .field_collation("dataval")
+            .unwrap()
+    }
+
+    pub fn open(&self, tx: Arc<Mutex<Transaction>>) -> index::Result<Box<dyn Index>> {
+        let idx: Box<dyn Index> = match self.index_type {
+            IndexType::BTree => Box::new(BTreeIndex::new(
+                tx,
+                &self.index_name,
+                self.index_layout.clone(),
+                self.key_order,
+            )?),
+            IndexType::Hash => Box::new(HashIndex::new(
+                tx,
+                &self.index_name,
+                self.index_layout.clone(),
+                self.buckets,
+            )?),
+        };
+        match self.collation() {
+            Collation::Binary => Ok(idx),
+            collation => Ok(Box::new(CollatedIndex::new(idx, collation))),
+        }
+    }
+
+    pub fn index_stat_info(&self) -> Option<IndexStatInfo> {
+        self.index_stat_info
+    }
+
+    pub fn calc_index_stats(
+        &self,
+        tx: Arc<Mutex<Transaction>>,
+    ) -> index::Result<Option<IndexStatInfo>> {
+        if self.index_type != IndexType::BTree {
+            return Ok(None);
+        }
+        let idx = BTreeIndex::new(
+            tx,
+            &self.index_name,
+            self.index_layout.clone(),
+            self.key_order,
+        )?;
+        Ok(Some(IndexStatInfo::new(idx.height()?, idx.num_leaves()?)))
     }
 
     pub fn blocks_accessed(&self) -> usize {
-        let rec_per_blk = self.block_size / self.index_layout.slotsize();
-        let num_blocks = self.stat_info.records_output() / rec_per_blk;
-        HashIndex::search_cost(num_blocks, rec_per_blk)
+        match self.index_stat_info {
+            Some(si) => {
+                // NOTE: the path from the root, and the matching leaves
+                let num_leaves = si.num_leaves().div_ceil(self.distinct_values_of_key());
+                (si.height() - 1) + num_leaves.max(1)
+            }
+            None => {
+                let rec_per_blk = self.block_size / self.index_layout.slotsize();
+                let num_blocks = self.stat_info.records_output() / rec_per_blk;
+                match self.index_type {
+                    IndexType::BTree => BTreeIndex::search_cost(num_blocks, rec_per_blk),
+                    IndexType::Hash => HashIndex::search_cost(num_blocks, self.buckets),
+                }
+            }
+        }
     }
 
     pub fn records_output(&self) -> usize {
-        self.stat_info.records_output() / self.stat_info.distinct_values(&self.field_name)
+        self.stat_info.records_output() / self.distinct_values_of_key()
     }
 
     pub fn distinct_values(&self, fname: &str) -> usize {
@@ -104,9 +212,13 @@ impl IndexInfo {
             self.stat_info.distinct_values(&self.field_name)
         }
     }
+
+    fn distinct_values_of_key(&self) -> usize {
+        self.stat_info.distinct_values(&self.field_name)
+    }
 }
 
-const INDEX_CATALOG_TABLE_NAME: &str = "idxcat";
+pub(super) const INDEX_CATALOG_TABLE_NAME: &str = "idxcat";
 
 pub struct IndexMgr {
     // layout: Layout,
@@ -119,73 +231,200 @@ impl IndexMgr {
         Self { tm, sm }
     }
 
-    pub fn init(&self, tx: Rc<RefCell<Transaction>>) {
+    pub fn init(&self, tx: Arc<Mutex<Transaction>>) {
+        for (tblname, schema) in Self::catalog_schemas(self.tm.max_name_length()) {
+            self.tm.create_table(tblname, schema, tx.clone()).unwrap();
+        }
+    }
+
+    pub(super) fn catalog_schemas(max_name_length: usize) -> Vec<(&'static str, Schema)> {
         let mut schema = Schema::new();
-        schema.add_string_field("indexname", MAX_NAME_LENGTH);
-        schema.add_string_field("tablename", MAX_NAME_LENGTH);
-        schema.add_string_field("fieldname", MAX_NAME_LENGTH);
-        self.tm
-            .create_table(INDEX_CATALOG_TABLE_NAME, schema, tx)
-            .unwrap();
+        schema.add_string_field("indexname", max_name_length);
+        schema.add_string_field("tablename", max_name_length);
+        schema.add_string_field("fieldname", max_name_length);
+        schema.add_i32_field("indextype");
+        schema.add_i32_field("keyorder");
+        schema.add_i32_field("buckets");
+        schema.add_i32_field("height");
+        schema.add_i32_field("numleaves");
+        vec![(INDEX_CATALOG_TABLE_NAME, schema)]
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn create_index(
         &self,
         index_name: &str,
         table_name: &str,
         field_name: &str,
-        tx: Rc<RefCell<Transaction>>,
+        index_type: IndexType,
+        key_order: KeyOrder,
+        buckets: usize,
+        tx: Arc<Mutex<Transaction>>,
     ) -> Result<()> {
+        self.tm.check_name(index_name)?;
+        let buckets_val = match index_type {
+            IndexType::Hash => {
+                HashIndex::check_buckets(buckets)?;
+                buckets as i32
+            }
+            IndexType::BTree => 0,
+        };
         let layout = self.index_catalog_layout(&tx)?;
-        let mut ts = TableScan::new(tx, INDEX_CATALOG_TABLE_NAME.into(), layout);
+        // NOTE: an older catalog has no buckets
+        if !layout.schema().has_field("buckets")
+            && index_type == IndexType::Hash
+            && buckets != DEFAULT_BUCKETS
+        {
+            return Err(MetadataError::BucketsNotKept(
+                index_name.into(),
+                DEFAULT_BUCKETS,
+            ));
+        }
+        let mut ts = TableScan::new(tx, INDEX_CATALOG_TABLE_NAME.into(), layout)?;
         ts.insert()?;
         ts.set_string("indexname", index_name.into())?;
         ts.set_string("tablename", table_name.into())?;
         ts.set_string("fieldname", field_name.into())?;
+        ts.set_i32("indextype", index_type.into())?;
+        ts.set_i32("keyorder", key_order.into())?;
+        if ts.has_field("buckets") {
+            ts.set_i32("buckets", buckets_val)?;
+        }
+        if ts.has_field("height") {
+            ts.set_i32("height", 0)?; // NOTE: 0 means unknown
+            ts.set_i32("numleaves", 0)?;
+        }
         Ok(())
     }
 
-    fn index_catalog_layout(&self, tx: &Rc<RefCell<Transaction>>) -> Result<Layout> {
+    pub fn update_index_stats(
+        &self,
+        index_name: &str,
+        stats: IndexStatInfo,
+        tx: Arc<Mutex<Transaction>>,
+    ) -> Result<()> {
+        let height: i32 = stats.height().try_into().unwrap();
+        let num_leaves: i32 = stats.num_leaves().try_into().unwrap();
+
+        let layout = self.index_catalog_layout(&tx)?;
+        // NOTE: an older catalog has no shapes of B-trees
+        if !layout.schema().has_field("height") {
+            return Ok(());
+        }
+        let mut ts = TableScan::new(tx, INDEX_CATALOG_TABLE_NAME.into(), layout)?;
+        while ts.next()? {
+            if ts.get_string("indexname")? == index_name {
+                if ts.get_i32("height")? != height || ts.get_i32("numleaves")? != num_leaves {
+                    ts.set_i32("height", height)?;
+                    ts.set_i32("numleaves", num_leaves)?;
+                }
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn drop_indexes(&self, table_name: &str, tx: Arc<Mutex<Transaction>>) -> Result<()> {
+        let layout = self.index_catalog_layout(&tx)?;
+        let mut ts = TableScan::new(tx, INDEX_CATALOG_TABLE_NAME.into(), layout)?;
+        while ts.next()? {
+            if ts.get_string("tablename")? == table_name {
+                ts.delete()?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn rename_table(
+        &self,
+        table_name: &str,
+        new_name: &str,
+        tx: Arc<Mutex<Transaction>>,
+    ) -> Result<()> {
+        let layout = self.index_catalog_layout(&tx)?;
+        let mut ts = TableScan::new(tx, INDEX_CATALOG_TABLE_NAME.into(), layout)?;
+        while ts.next()? {
+            if ts.get_string("tablename")? == table_name {
+                ts.set_string("tablename", new_name.into())?;
+            }
+        }
+        Ok(())
+    }
+
+    // NOTE: an older catalog has no buckets
+    fn buckets(ts: &TableScan, index_type: IndexType) -> Result<usize> {
+        if index_type != IndexType::Hash || !ts.has_field("buckets") {
+            return Ok(DEFAULT_BUCKETS);
+        }
+        let buckets = ts.get_i32("buckets")?;
+        let invalid = IndexError::InvalidBuckets(buckets.into());
+        let buckets = usize::try_from(buckets).map_err(|_| invalid)?;
+        HashIndex::check_buckets(buckets)?;
+        Ok(buckets)
+    }
+
+    fn index_stat_info(ts: &TableScan) -> Result<Option<IndexStatInfo>> {
+        if !ts.has_field("height") {
+            return Ok(None);
+        }
+        let height = usize::try_from(ts.get_i32("height")?).unwrap_or(0);
+        let num_leaves = usize::try_from(ts.get_i32("numleaves")?).unwrap_or(0);
+        Ok((height > 0).then(|| IndexStatInfo::new(height, num_leaves)))
+    }
+
+    fn index_catalog_layout(&self, tx: &Arc<Mutex<Transaction>>) -> Result<Layout> {
         self.tm.layout(INDEX_CATALOG_TABLE_NAME, tx.clone())
     }
 
     pub fn index_info(
         &self,
         table_name: &str,
-        tx: Rc<RefCell<Transaction>>,
+        tx: Arc<Mutex<Transaction>>,
     ) -> Result<HashMap<String, IndexInfo>> {
         let mut result = HashMap::new();
 
-        let idx_fld_pairs = {
+        let catalog_entries = {
             let tblname: String = table_name.into();
-            let mut names = Vec::new();
+            let mut entries = Vec::new();
 
             let layout = self.index_catalog_layout(&tx)?;
-            let mut ts = TableScan::new(tx.clone(), INDEX_CATALOG_TABLE_NAME.into(), layout);
+            let mut ts = TableScan::new(tx.clone(), INDEX_CATALOG_TABLE_NAME.into(), layout)?;
             while ts.next()? {
                 if ts.get_string("tablename").unwrap() == tblname {
-                    names.push((
+                    let index_type = IndexType::try_from(ts.get_i32("indextype")?)?;
+                    let key_order = KeyOrder::try_from(ts.get_i32("keyorder")?)?;
+                    let buckets = IndexMgr::buckets(&ts, index_type)?;
+                    let index_stat_info = IndexMgr::index_stat_info(&ts)?;
+                    entries.push((
                         ts.get_string("indexname").unwrap(),
                         ts.get_string("fieldname").unwrap(),
+                        index_type,
+                        key_order,
+                        buckets,
+                        index_stat_info,
                     ));
                 }
             }
 
-            names
+            entries
         };
 
-        for (idxname, fldname) in idx_fld_pairs {
-            let tbl_layout = self.tm.layout(table_name, tx.clone()).unwrap();
-            let tbl_stat_info = self
-                .sm
-                .table_stat_info(table_name, tbl_layout.clone(), tx.clone());
+        for (idxname, fldname, index_type, key_order, buckets, index_stat_info) in catalog_entries {
+            let tbl_layout = self.tm.layout(table_name, tx.clone())?;
+            let tbl_stat_info =
+                self.sm
+                    .table_stat_info(table_name, tbl_layout.clone(), tx.clone())?;
             let index_info = IndexInfo::new(
                 &idxname,
                 &fldname,
+                index_type,
+                key_order,
                 tbl_layout.schema().clone(),
-                tx.borrow().block_size(),
+                tx.lock().unwrap().block_size(),
                 tbl_stat_info,
-            );
+            )
+            .with_buckets(buckets)
+            .with_index_stat_info(index_stat_info);
             result.insert(fldname, index_info);
         }
 
@@ -195,10 +434,16 @@ impl IndexMgr {
 
 #[cfg(test)]
 mod tests {
-    use super::IndexMgr;
+    use super::{IndexMgr, IndexStatInfo, INDEX_CATALOG_TABLE_NAME};
     use crate::{
-        metadata::{stat_mgr::StatMgr, table_mgr::TableMgr},
-        record::schema::Schema,
+        index::{hash_index::DEFAULT_BUCKETS, IndexError, IndexType, KeyOrder},
+        metadata::{
+            common::MetadataError,
+            stat_mgr::StatMgr,
+            table_mgr::{TableMgr, MAX_NAME_LENGTH},
+        },
+        query::scan::Scan,
+        record::{schema::Schema, table_scan::TableScan},
         server::simple_db::SimpleDB,
     };
     use std::sync::Arc;
@@ -211,7 +456,7 @@ mod tests {
             let db = SimpleDB::new_for_test(dir.path(), "index_mgr_test.log");
             let tx = db.new_tx();
             {
-                let tm = Arc::new(TableMgr::new());
+                let tm = Arc::new(TableMgr::new(MAX_NAME_LENGTH));
                 tm.init(tx.clone());
                 let sm = Arc::new(StatMgr::new(tm.clone()));
                 sm.init(tx.clone());
@@ -223,16 +468,195 @@ mod tests {
 
                 let im = IndexMgr::new(tm.clone(), sm.clone());
                 im.init(tx.clone());
-                im.create_index("my-index", "MyTable", "id", tx.clone())
-                    .unwrap();
+                im.create_index(
+                    "my-index",
+                    "MyTable",
+                    "id",
+                    IndexType::BTree,
+                    KeyOrder::Ascending,
+                    0,
+                    tx.clone(),
+                )
+                .unwrap();
 
                 let ii_map = im.index_info("MyTable", tx.clone()).unwrap();
                 assert_eq!(ii_map.len(), 1);
 
                 let id = ii_map.get("id").unwrap();
                 assert_eq!(id.index_name, "my-index");
+                assert_eq!(id.index_stat_info(), None);
+            }
+            tx.lock().unwrap().commit().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_index_stats() {
+        let dir = tempdir().unwrap();
+        {
+            let db = SimpleDB::new_for_test(dir.path(), "index_mgr_stats_test.log");
+            let tx = db.new_tx();
+            {
+                let tm = Arc::new(TableMgr::new(MAX_NAME_LENGTH));
+                tm.init(tx.clone());
+                let sm = Arc::new(StatMgr::new(tm.clone()));
+                sm.init(tx.clone());
+                {
+                    let mut schema = Schema::new();
+                    schema.add_i32_field("id");
+                    tm.create_table("MyTable", schema, tx.clone()).unwrap();
+                }
+
+                let im = IndexMgr::new(tm.clone(), sm.clone());
+                im.init(tx.clone());
+                im.create_index(
+                    "my-index",
+                    "MyTable",
+                    "id",
+                    IndexType::BTree,
+                    KeyOrder::Ascending,
+                    0,
+                    tx.clone(),
+                )
+                .unwrap();
+
+                let ii_map = im.index_info("MyTable", tx.clone()).unwrap();
+                let stats = ii_map
+                    .get("id")
+                    .unwrap()
+                    .calc_index_stats(tx.clone())
+                    .unwrap();
+                assert_eq!(stats, Some(IndexStatInfo::new(2, 1)));
+
+                im.update_index_stats("my-index", IndexStatInfo::new(3, 40), tx.clone())
+                    .unwrap();
+                let ii_map = im.index_info("MyTable", tx.clone()).unwrap();
+                let ii = ii_map.get("id").unwrap();
+                assert_eq!(ii.index_stat_info(), Some(IndexStatInfo::new(3, 40)));
+                // NOTE: 2 directory levels + 40 leaves / 1 distinct value
+                assert_eq!(ii.blocks_accessed(), 42);
+            }
+            tx.lock().unwrap().commit().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_catalog_without_buckets() {
+        let dir = tempdir().unwrap();
+        {
+            let db = SimpleDB::new_for_test(dir.path(), "index_mgr_old_catalog_test.log");
+            let tx = db.new_tx();
+            {
+                let tm = Arc::new(TableMgr::new(MAX_NAME_LENGTH));
+                tm.init(tx.clone());
+                let sm = Arc::new(StatMgr::new(tm.clone()));
+                sm.init(tx.clone());
+                {
+                    let mut schema = Schema::new();
+                    schema.add_i32_field("id");
+                    tm.create_table("MyTable", schema, tx.clone()).unwrap();
+                }
+
+                // NOTE: an older catalog
+                let mut schema = Schema::new();
+                schema.add_string_field("indexname", MAX_NAME_LENGTH);
+                schema.add_string_field("tablename", MAX_NAME_LENGTH);
+                schema.add_string_field("fieldname", MAX_NAME_LENGTH);
+                schema.add_i32_field("indextype");
+                schema.add_i32_field("keyorder");
+                tm.create_table(INDEX_CATALOG_TABLE_NAME, schema, tx.clone())
+                    .unwrap();
+
+                let im = IndexMgr::new(tm.clone(), sm.clone());
+                im.create_index(
+                    "hash-index",
+                    "MyTable",
+                    "id",
+                    IndexType::Hash,
+                    KeyOrder::Ascending,
+                    DEFAULT_BUCKETS,
+                    tx.clone(),
+                )
+                .unwrap();
+                assert!(matches!(
+                    im.create_index(
+                        "other-index",
+                        "MyTable",
+                        "id",
+                        IndexType::Hash,
+                        KeyOrder::Ascending,
+                        16,
+                        tx.clone(),
+                    ),
+                    Err(MetadataError::BucketsNotKept(name, DEFAULT_BUCKETS)) if name == "other-index"
+                ));
+                im.update_index_stats("hash-index", IndexStatInfo::new(3, 40), tx.clone())
+                    .unwrap();
+
+                let ii_map = im.index_info("MyTable", tx.clone()).unwrap();
+                let ii = ii_map.get("id").unwrap();
+                assert_eq!(ii.buckets(), Some(DEFAULT_BUCKETS));
+                assert_eq!(ii.index_stat_info(), None);
+            }
+            tx.lock().unwrap().commit().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_invalid_buckets() {
+        let dir = tempdir().unwrap();
+        {
+            let db = SimpleDB::new_for_test(dir.path(), "index_mgr_invalid_buckets_test.log");
+            let tx = db.new_tx();
+            {
+                let tm = Arc::new(TableMgr::new(MAX_NAME_LENGTH));
+                tm.init(tx.clone());
+                let sm = Arc::new(StatMgr::new(tm.clone()));
+                sm.init(tx.clone());
+                {
+                    let mut schema = Schema::new();
+                    schema.add_i32_field("id");
+                    tm.create_table("MyTable", schema, tx.clone()).unwrap();
+                }
+
+                let im = IndexMgr::new(tm.clone(), sm.clone());
+                im.init(tx.clone());
+                assert!(matches!(
+                    im.create_index(
+                        "my-index",
+                        "MyTable",
+                        "id",
+                        IndexType::Hash,
+                        KeyOrder::Ascending,
+                        0,
+                        tx.clone(),
+                    ),
+                    Err(MetadataError::Index(IndexError::InvalidBuckets(0)))
+                ));
+                im.create_index(
+                    "my-index",
+                    "MyTable",
+                    "id",
+                    IndexType::Hash,
+                    KeyOrder::Ascending,
+                    8,
+                    tx.clone(),
+                )
+                .unwrap();
+
+                let layout = tm.layout(INDEX_CATALOG_TABLE_NAME, tx.clone()).unwrap();
+                let mut ts =
+                    TableScan::new(tx.clone(), INDEX_CATALOG_TABLE_NAME.into(), layout).unwrap();
+                while ts.next().unwrap() {
+                    ts.set_i32("buckets", -1).unwrap();
+                }
+                ts.close();
+                assert!(matches!(
+                    im.index_info("MyTable", tx.clone()),
+                    Err(MetadataError::Index(IndexError::InvalidBuckets(-1)))
+                ));
             }
-            tx.borrow_mut().commit().unwrap();
+            tx.lock().unwrap().commit().unwrap();
         }
     }
 }
@@ -4,28 +4,82 @@
 // https://opensource.org/licenses/MIT
 
 use super::{
-    common::Result,
-    index_mgr::{IndexInfo, IndexMgr},
-    stat_mgr::{StatInfo, StatMgr},
-    table_mgr::TableMgr,
-    view_mgr::ViewMgr,
+    auth::{AuthMgr, Privilege, PRIVILEGE_CATALOG_TABLE_NAME, USER_CATALOG_TABLE_NAME},
+    common::{MetadataError, Result},
+    index_mgr::{IndexInfo, IndexMgr, INDEX_CATALOG_TABLE_NAME},
+    sequence_mgr::{SequenceMgr, SEQUENCE_CATALOG_TABLE_NAME},
+    stat_mgr::{StatInfo, StatMgr, COLUMN_STAT_CATALOG_TABLE_NAME, STAT_CATALOG_TABLE_NAME},
+    table_mgr::{TableMgr, FIELD_CATALOG_TABLE_NAME, TABLE_CATALOG_TABLE_NAME},
+    tablespace_mgr::{
+        TablespaceMgr, TABLESPACE_CATALOG_TABLE_NAME, TABLESPACE_FILE_CATALOG_TABLE_NAME,
+    },
+    view_mgr::{ViewMgr, VIEW_CATALOG_TABLE_NAME, VIEW_DEPENDENCY_CATALOG_TABLE_NAME},
 };
 use crate::{
-    record::schema::{Layout, Schema},
+    index::{hash_index::DEFAULT_BUCKETS, IndexType, KeyOrder},
+    record::{
+        schema::{Collation, Layout, Schema, SqlType},
+        table_scan::TableScan,
+    },
     tx::transaction::Transaction,
 };
-use std::{cell::RefCell, collections::HashMap, rc::Rc, sync::Arc};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
 
 pub struct MetadataMgr {
     tm: Arc<TableMgr>,
     vm: Arc<ViewMgr>,
     sm: Arc<StatMgr>,
     im: Arc<IndexMgr>,
+    tsm: Arc<TablespaceMgr>,
+    seqm: Arc<SequenceMgr>,
+    am: Arc<AuthMgr>,
+    layouts: Arc<Mutex<LayoutCache>>,
+}
+
+#[derive(Default)]
+struct LayoutCache {
+    layouts: HashMap<String, Layout>,
+    // NOTE: table name -> unfinished transactions changing its layout
+    changing: HashMap<String, usize>,
+    // NOTE: a layout read before a change is not cached after it
+    generation: u64,
+}
+
+impl LayoutCache {
+    fn get(&self, table_name: &str) -> Option<Layout> {
+        self.layouts.get(table_name).cloned()
+    }
+
+    fn insert(&mut self, table_name: &str, layout: Layout, generation: u64) {
+        if generation == self.generation && !self.changing.contains_key(table_name) {
+            self.layouts.insert(table_name.into(), layout);
+        }
+    }
+
+    fn begin_change(&mut self, table_name: &str) {
+        *self.changing.entry(table_name.into()).or_insert(0) += 1;
+        self.layouts.remove(table_name);
+        self.generation += 1;
+    }
+
+    fn end_change(&mut self, table_name: &str) {
+        if let Some(n) = self.changing.get_mut(table_name) {
+            *n -= 1;
+            if *n == 0 {
+                self.changing.remove(table_name);
+            }
+        }
+        self.layouts.remove(table_name);
+        self.generation += 1;
+    }
 }
 
 impl MetadataMgr {
-    pub fn new(is_new: bool, tx: Rc<RefCell<Transaction>>) -> Self {
-        let tm = Arc::new(TableMgr::new());
+    pub fn new(is_new: bool, max_name_length: usize, tx: Arc<Mutex<Transaction>>) -> Self {
+        let tm = Arc::new(TableMgr::new(max_name_length));
         if is_new {
             tm.init(tx.clone());
         }
@@ -45,32 +99,269 @@ impl MetadataMgr {
             im.init(tx.clone());
         }
 
-        Self { tm, vm, sm, im }
+        let tsm = Arc::new(TablespaceMgr::new(tm.clone()));
+        if is_new {
+            tsm.init(tx.clone());
+        }
+
+        let seqm = Arc::new(SequenceMgr::new(tm.clone()));
+        if is_new {
+            seqm.init(tx.clone());
+        }
+
+        let am = Arc::new(AuthMgr::new(tm.clone()));
+        if is_new {
+            am.init(tx.clone());
+        }
+
+        Self {
+            tm,
+            vm,
+            sm,
+            im,
+            tsm,
+            seqm,
+            am,
+            layouts: Arc::new(Mutex::new(LayoutCache::default())),
+        }
+    }
+
+    pub fn min_page_size(max_name_length: usize) -> usize {
+        TableMgr::catalog_schemas(max_name_length)
+            .into_iter()
+            .chain(ViewMgr::catalog_schemas(max_name_length))
+            .chain(StatMgr::catalog_schemas(max_name_length))
+            .chain(IndexMgr::catalog_schemas(max_name_length))
+            .chain(TablespaceMgr::catalog_schemas(max_name_length))
+            .chain(SequenceMgr::catalog_schemas(max_name_length))
+            .chain(AuthMgr::catalog_schemas(max_name_length))
+            .map(|(_, schema)| Layout::new(schema).slotsize() + Layout::PAGE_HEADER_SIZE)
+            .max()
+            .unwrap_or(Layout::PAGE_HEADER_SIZE)
+    }
+
+    fn change_layout(&self, table_name: &str, tx: &Arc<Mutex<Transaction>>) {
+        self.layouts.lock().unwrap().begin_change(table_name);
+        let end_change = || {
+            let layouts = self.layouts.clone();
+            let table_name = table_name.to_string();
+            move || layouts.lock().unwrap().end_change(&table_name)
+        };
+        let mut tx = tx.lock().unwrap();
+        tx.on_commit(end_change());
+        tx.on_rollback(end_change());
     }
 
     pub fn create_table(
         &self,
         table_name: &str,
         schema: Schema,
-        tx: Rc<RefCell<Transaction>>,
+        tx: Arc<Mutex<Transaction>>,
+    ) -> Result<()> {
+        self.create_table_in(table_name, schema, None, tx)
+    }
+
+    pub fn create_table_in(
+        &self,
+        table_name: &str,
+        schema: Schema,
+        tablespace_name: Option<&str>,
+        tx: Arc<Mutex<Transaction>>,
+    ) -> Result<()> {
+        if let Some(tsname) = tablespace_name {
+            self.tsm.check_exists(tsname, tx.clone())?;
+        }
+        self.change_layout(table_name, &tx);
+        self.tm.create_table(table_name, schema, tx.clone())?;
+        if let Some(tsname) = tablespace_name {
+            self.tsm
+                .place(&[TableScan::filename(table_name)], tsname, tx)?;
+        }
+        Ok(())
+    }
+
+    pub fn table_layout(&self, table_name: &str, tx: Arc<Mutex<Transaction>>) -> Result<Layout> {
+        if tx.lock().unwrap().is_snapshot() {
+            return self.tm.layout(table_name, tx);
+        }
+        let generation = {
+            let layouts = self.layouts.lock().unwrap();
+            if let Some(layout) = layouts.get(table_name) {
+                return Ok(layout);
+            }
+            layouts.generation
+        };
+        let layout = self.tm.layout(table_name, tx)?;
+        self.layouts
+            .lock()
+            .unwrap()
+            .insert(table_name, layout.clone(), generation);
+        Ok(layout)
+    }
+
+    pub fn table_names(&self, tx: Arc<Mutex<Transaction>>) -> Result<Vec<String>> {
+        self.tm.table_names(tx)
+    }
+
+    pub fn view_names(&self, tx: Arc<Mutex<Transaction>>) -> Result<Vec<String>> {
+        self.vm.view_names(tx)
+    }
+
+    pub fn is_catalog(table_name: &str) -> bool {
+        [
+            TABLE_CATALOG_TABLE_NAME,
+            FIELD_CATALOG_TABLE_NAME,
+            VIEW_CATALOG_TABLE_NAME,
+            VIEW_DEPENDENCY_CATALOG_TABLE_NAME,
+            INDEX_CATALOG_TABLE_NAME,
+            STAT_CATALOG_TABLE_NAME,
+            COLUMN_STAT_CATALOG_TABLE_NAME,
+            TABLESPACE_CATALOG_TABLE_NAME,
+            TABLESPACE_FILE_CATALOG_TABLE_NAME,
+            SEQUENCE_CATALOG_TABLE_NAME,
+            USER_CATALOG_TABLE_NAME,
+            PRIVILEGE_CATALOG_TABLE_NAME,
+        ]
+        .contains(&table_name)
+    }
+
+    pub fn drop_table(
+        &self,
+        table_name: &str,
+        cascade: bool,
+        tx: Arc<Mutex<Transaction>>,
     ) -> Result<()> {
-        self.tm.create_table(table_name, schema, tx)
+        self.tm.layout(table_name, tx.clone())?;
+        let dependents = self.dependent_views(table_name, cascade, tx.clone())?;
+
+        let mut filenames = vec![TableScan::filename(table_name)];
+        for ii in self.im.index_info(table_name, tx.clone())?.values() {
+            filenames.extend(ii.filenames());
+        }
+        self.im.drop_indexes(table_name, tx.clone())?;
+        self.sm.drop_table_stats(table_name, tx.clone())?;
+        self.am.drop_privileges(table_name, tx.clone())?;
+        self.change_layout(table_name, &tx);
+        self.tm.drop_table(table_name, tx.clone())?;
+        for filename in filenames {
+            tx.lock().unwrap().truncate_on_commit(&filename, 0)?;
+        }
+        self.drop_views(&dependents, tx)
     }
 
-    pub fn table_layout(&self, table_name: &str, tx: Rc<RefCell<Transaction>>) -> Result<Layout> {
-        self.tm.layout(table_name, tx)
+    pub fn rename_table(
+        &self,
+        table_name: &str,
+        new_name: &str,
+        tx: Arc<Mutex<Transaction>>,
+    ) -> Result<()> {
+        self.tm.layout(table_name, tx.clone())?;
+        if self.vm.view_def(new_name, tx.clone()).is_ok() {
+            return Err(MetadataError::TableAlreadyExists(new_name.into()));
+        }
+        self.dependent_views(table_name, false, tx.clone())?;
+
+        self.change_layout(table_name, &tx);
+        self.change_layout(new_name, &tx);
+        self.tm.rename_table(table_name, new_name, tx.clone())?;
+        self.im.rename_table(table_name, new_name, tx.clone())?;
+        self.sm
+            .rename_table_stats(table_name, new_name, tx.clone())?;
+        self.am
+            .rename_privileges(table_name, new_name, tx.clone())?;
+        let from = TableScan::filename(table_name);
+        let to = TableScan::filename(new_name);
+        self.tsm.rename_file(&from, &to, tx.clone())?;
+        tx.lock().unwrap().rename_file(&from, &to)?;
+        Ok(())
+    }
+
+    fn dependent_views(
+        &self,
+        name: &str,
+        cascade: bool,
+        tx: Arc<Mutex<Transaction>>,
+    ) -> Result<Vec<String>> {
+        let views = self.vm.dependent_views(name, tx)?;
+        match views.first() {
+            Some(view_name) if !cascade => {
+                Err(MetadataError::DependentView(view_name.clone(), name.into()))
+            }
+            _ => Ok(views),
+        }
+    }
+
+    fn drop_views(&self, view_names: &[String], tx: Arc<Mutex<Transaction>>) -> Result<()> {
+        for view_name in view_names {
+            // NOTE: e.g. through a cycle of views
+            if self.vm.view_def(view_name, tx.clone()).is_ok() {
+                self.drop_view(view_name, true, tx.clone())?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn add_field(
+        &self,
+        table_name: &str,
+        field_name: &str,
+        field_type: SqlType,
+        field_length: usize,
+        collation: Collation,
+        tx: Arc<Mutex<Transaction>>,
+    ) -> Result<Layout> {
+        self.change_layout(table_name, &tx);
+        self.tm.add_field(
+            table_name,
+            field_name,
+            field_type,
+            field_length,
+            collation,
+            tx,
+        )
+    }
+
+    pub fn drop_field(
+        &self,
+        table_name: &str,
+        field_name: &str,
+        tx: Arc<Mutex<Transaction>>,
+    ) -> Result<Layout> {
+        if let Some(ii) = self.im.index_info(table_name, tx.clone())?.get(field_name) {
+            return Err(MetadataError::FieldIndexed(
+                table_name.into(),
+                field_name.into(),
+                ii.index_name().into(),
+            ));
+        }
+        self.change_layout(table_name, &tx);
+        self.tm.drop_field(table_name, field_name, tx)
     }
 
     pub fn create_view(
         &self,
         view_name: &str,
         view_def: &str,
-        tx: Rc<RefCell<Transaction>>,
+        referenced: &[String],
+        tx: Arc<Mutex<Transaction>>,
+    ) -> Result<()> {
+        self.vm.create_view(view_name, view_def, referenced, tx)
+    }
+
+    pub fn drop_view(
+        &self,
+        view_name: &str,
+        cascade: bool,
+        tx: Arc<Mutex<Transaction>>,
     ) -> Result<()> {
-        self.vm.create_view(view_name, view_def, tx)
+        self.vm.view_def(view_name, tx.clone())?;
+        let dependents = self.dependent_views(view_name, cascade, tx.clone())?;
+        self.vm.drop_view(view_name, tx.clone())?;
+        self.am.drop_privileges(view_name, tx.clone())?;
+        self.drop_views(&dependents, tx)
     }
 
-    pub fn view_def(&self, view_name: &str, tx: Rc<RefCell<Transaction>>) -> Result<String> {
+    pub fn view_def(&self, view_name: &str, tx: Arc<Mutex<Transaction>>) -> Result<String> {
         self.vm.view_def(view_name, tx)
     }
 
@@ -79,34 +370,229 @@ impl MetadataMgr {
         index_name: &str,
         table_name: &str,
         field_name: &str,
-        tx: Rc<RefCell<Transaction>>,
+        index_type: IndexType,
+        key_order: KeyOrder,
+        tx: Arc<Mutex<Transaction>>,
     ) -> Result<()> {
-        self.im.create_index(index_name, table_name, field_name, tx)
+        self.create_index_in(
+            index_name, table_name, field_name, index_type, key_order, None, None, tx,
+        )
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn create_index_in(
+        &self,
+        index_name: &str,
+        table_name: &str,
+        field_name: &str,
+        index_type: IndexType,
+        key_order: KeyOrder,
+        buckets: Option<usize>,
+        tablespace_name: Option<&str>,
+        tx: Arc<Mutex<Transaction>>,
+    ) -> Result<()> {
+        if let Some(tsname) = tablespace_name {
+            self.tsm.check_exists(tsname, tx.clone())?;
+        }
+        let buckets = match index_type {
+            IndexType::BTree => 0,
+            IndexType::Hash => buckets.unwrap_or(DEFAULT_BUCKETS),
+        };
+        self.im.create_index(
+            index_name,
+            table_name,
+            field_name,
+            index_type,
+            key_order,
+            buckets,
+            tx.clone(),
+        )?;
+        if let Some(tsname) = tablespace_name {
+            self.tsm
+                .place(&index_type.filenames(index_name, buckets), tsname, tx)?;
+        }
+        Ok(())
+    }
+
+    pub fn create_tablespace(
+        &self,
+        tablespace_name: &str,
+        path: &str,
+        tx: Arc<Mutex<Transaction>>,
+    ) -> Result<()> {
+        self.tsm.create_tablespace(tablespace_name, path, tx)
+    }
+
+    pub fn create_sequence(&self, sequence_name: &str, tx: Arc<Mutex<Transaction>>) -> Result<()> {
+        self.seqm.create_sequence(sequence_name, tx)
+    }
+
+    pub fn next_sequence_value(
+        &self,
+        sequence_name: &str,
+        tx: Arc<Mutex<Transaction>>,
+    ) -> Result<i32> {
+        self.seqm.next_value(sequence_name, tx)
+    }
+
+    pub fn sequence_names(&self, tx: Arc<Mutex<Transaction>>) -> Result<Vec<String>> {
+        self.seqm.sequence_names(tx)
+    }
+
+    pub fn create_user(
+        &self,
+        user_name: &str,
+        password: &str,
+        tx: Arc<Mutex<Transaction>>,
+    ) -> Result<()> {
+        self.am.create_user(user_name, password, tx)
+    }
+
+    pub fn alter_user_password(
+        &self,
+        user_name: &str,
+        password: &str,
+        tx: Arc<Mutex<Transaction>>,
+    ) -> Result<()> {
+        self.am.alter_password(user_name, password, tx)
+    }
+
+    pub fn authenticate(
+        &self,
+        user_name: &str,
+        password: &str,
+        tx: Arc<Mutex<Transaction>>,
+    ) -> Result<bool> {
+        self.am.authenticate(user_name, password, tx)
+    }
+
+    pub fn user_names(&self, tx: Arc<Mutex<Transaction>>) -> Result<Vec<String>> {
+        self.am.user_names(tx)
+    }
+
+    pub fn grant(
+        &self,
+        user_name: &str,
+        table_name: &str,
+        privileges: &[Privilege],
+        tx: Arc<Mutex<Transaction>>,
+    ) -> Result<()> {
+        if self.vm.view_def(table_name, tx.clone()).is_err() {
+            self.tm.layout(table_name, tx.clone())?;
+        }
+        self.am.grant(user_name, table_name, privileges, tx)
+    }
+
+    pub fn revoke(
+        &self,
+        user_name: &str,
+        table_name: &str,
+        privileges: &[Privilege],
+        tx: Arc<Mutex<Transaction>>,
+    ) -> Result<()> {
+        self.am.revoke(user_name, table_name, privileges, tx)
+    }
+
+    pub fn has_privilege(
+        &self,
+        user_name: &str,
+        table_name: &str,
+        privilege: Privilege,
+        tx: Arc<Mutex<Transaction>>,
+    ) -> Result<bool> {
+        self.am.has_privilege(user_name, table_name, privilege, tx)
+    }
+
+    pub fn privileges(
+        &self,
+        user_name: &str,
+        tx: Arc<Mutex<Transaction>>,
+    ) -> Result<Vec<(String, Privilege)>> {
+        self.am.privileges(user_name, tx)
     }
 
     pub fn table_index_info(
         &self,
         table_name: &str,
-        tx: Rc<RefCell<Transaction>>,
+        tx: Arc<Mutex<Transaction>>,
     ) -> Result<HashMap<String, IndexInfo>> {
         self.im.index_info(table_name, tx)
     }
 
+    pub fn refresh_index_stats(
+        &self,
+        index_info: &IndexInfo,
+        tx: Arc<Mutex<Transaction>>,
+    ) -> Result<bool> {
+        match index_info.calc_index_stats(tx.clone())? {
+            Some(stats) if index_info.index_stat_info() != Some(stats) => {
+                self.im
+                    .update_index_stats(index_info.index_name(), stats, tx)?;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
     pub fn table_stat_info(
         &self,
         table_name: &str,
         layout: Layout,
-        tx: Rc<RefCell<Transaction>>,
-    ) -> StatInfo {
+        tx: Arc<Mutex<Transaction>>,
+    ) -> Result<StatInfo> {
         self.sm.table_stat_info(table_name, layout, tx)
     }
+
+    pub fn refresh_table_stats(
+        &self,
+        table_name: &str,
+        tx: Arc<Mutex<Transaction>>,
+    ) -> Result<StatInfo> {
+        let layout = self.tm.layout(table_name, tx.clone())?;
+        self.sm.refresh_table_stats(table_name, layout, tx)
+    }
+
+    pub fn analyze(&self, table_name: &str, tx: Arc<Mutex<Transaction>>) -> Result<StatInfo> {
+        let layout = self.tm.layout(table_name, tx.clone())?;
+        self.sm.analyze(table_name, layout, tx)
+    }
+
+    pub fn record_insert(
+        &self,
+        table_name: &str,
+        block_number: usize,
+        tx: Arc<Mutex<Transaction>>,
+    ) -> Result<()> {
+        self.sm.record_insert(table_name, block_number, tx)
+    }
+
+    pub fn record_inserts(
+        &self,
+        table_name: &str,
+        num_blocks: usize,
+        count: usize,
+        tx: Arc<Mutex<Transaction>>,
+    ) -> Result<()> {
+        self.sm.record_inserts(table_name, num_blocks, count, tx)
+    }
+
+    pub fn record_deletes(
+        &self,
+        table_name: &str,
+        count: usize,
+        tx: Arc<Mutex<Transaction>>,
+    ) -> Result<()> {
+        self.sm.record_deletes(table_name, count, tx)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use crate::{
+        index::{IndexType, KeyOrder},
+        metadata::common::MetadataError,
         record::{
-            schema::{Schema, SqlType},
+            schema::{Collation, Schema, SqlType},
             table_scan::TableScan,
         },
         server::simple_db::SimpleDB,
@@ -121,7 +607,7 @@ mod tests {
                 dir.path().join("testdb").as_path(),
                 "metadata_mgr_test.log",
             );
-            db.init();
+            db.init().unwrap();
 
             let mm = db.metadata_mgr();
             {
@@ -148,17 +634,20 @@ mod tests {
                         let layout = mm.table_layout("MyTable", tx.clone()).unwrap();
                         {
                             let mut ts =
-                                TableScan::new(tx.clone(), "MyTable".into(), layout.clone());
+                                TableScan::new(tx.clone(), "MyTable".into(), layout.clone())
+                                    .unwrap();
                             for i in 0..50 {
                                 ts.insert().unwrap();
                                 ts.set_i32("A", i).unwrap();
                                 ts.set_string("B", format!("rec{i}")).unwrap();
                             }
                         }
-                        let stat = mm.table_stat_info("MyTable", layout.clone(), tx.clone());
+                        let stat = mm
+                            .table_stat_info("MyTable", layout.clone(), tx.clone())
+                            .unwrap();
                         assert_eq!(
                             stat.blocks_accessed(),
-                            layout.slotsize() * 50 / tx.borrow().block_size() + 1
+                            layout.slotsize() * 50 / tx.lock().unwrap().block_size() + 1
                         );
                         assert_eq!(stat.records_output(), 50);
                         assert_eq!(stat.distinct_values("A"), 50 / 3 + 1);
@@ -167,23 +656,39 @@ mod tests {
                     {
                         // part 3: view metadata
                         let view_def = "SELECT b FROM MyTable WHERE A = 1";
-                        mm.create_view("viewA", view_def, tx.clone()).unwrap();
+                        mm.create_view("viewA", view_def, &["MyTable".into()], tx.clone())
+                            .unwrap();
                         let result = mm.view_def("viewA", tx.clone()).unwrap();
                         assert_eq!(result, view_def);
                     }
                     {
                         // part 4: index metadata
                         let layout = mm.table_layout("MyTable", tx.clone()).unwrap();
-                        let stat = mm.table_stat_info("MyTable", layout, tx.clone());
+                        let stat = mm.table_stat_info("MyTable", layout, tx.clone()).unwrap();
 
-                        mm.create_index("indexA", "MyTable", "A", tx.clone())
-                            .unwrap();
-                        mm.create_index("indexB", "MyTable", "B", tx.clone())
-                            .unwrap();
+                        mm.create_index(
+                            "indexA",
+                            "MyTable",
+                            "A",
+                            IndexType::BTree,
+                            KeyOrder::Ascending,
+                            tx.clone(),
+                        )
+                        .unwrap();
+                        mm.create_index(
+                            "indexB",
+                            "MyTable",
+                            "B",
+                            IndexType::Hash,
+                            KeyOrder::Ascending,
+                            tx.clone(),
+                        )
+                        .unwrap();
                         let indexes = mm.table_index_info("MyTable", tx.clone()).unwrap();
                         assert_eq!(indexes.len(), 2);
                         {
                             let index_a = indexes.get("A").unwrap();
+                            assert_eq!(index_a.index_type(), IndexType::BTree);
                             // TODO: assert_eq!(index_a.blocks_accessed(), )
                             assert_eq!(
                                 index_a.records_output(),
@@ -194,6 +699,7 @@ mod tests {
                         }
                         {
                             let index_b = indexes.get("B").unwrap();
+                            assert_eq!(index_b.index_type(), IndexType::Hash);
                             // TODO: assert_eq!(index_b.blocks_accessed(), )
                             assert_eq!(
                                 index_b.records_output(),
@@ -204,8 +710,60 @@ mod tests {
                         }
                     }
                 }
-                tx.borrow_mut().commit().unwrap();
+                tx.lock().unwrap().commit().unwrap();
             }
         }
     }
+
+    #[test]
+    fn test_layout_cache() {
+        let dir = tempdir().unwrap();
+        {
+            let mut db = SimpleDB::new_for_test(dir.path(), "metadata_mgr_test_layout_cache.log");
+            db.init().unwrap();
+
+            let mm = db.metadata_mgr();
+            let is_cached = |table_name: &str| mm.layouts.lock().unwrap().get(table_name).is_some();
+
+            let tx = db.new_tx();
+            let mut schema = Schema::new();
+            schema.add_i32_field("A");
+            mm.create_table("T", schema, tx.clone()).unwrap();
+            mm.table_layout("T", tx.clone()).unwrap();
+            assert!(!is_cached("T"));
+            tx.lock().unwrap().commit().unwrap();
+
+            let tx = db.new_tx();
+            mm.table_layout("T", tx.clone()).unwrap();
+            assert!(is_cached("T"));
+
+            // NOTE: not cached until the transaction ends
+            mm.add_field("T", "B", SqlType::Integer, 0, Collation::Binary, tx.clone())
+                .unwrap();
+            assert!(!is_cached("T"));
+            let layout = mm.table_layout("T", tx.clone()).unwrap();
+            assert!(layout.schema().has_field("B"));
+            assert!(!is_cached("T"));
+            tx.lock().unwrap().rollback().unwrap();
+
+            let tx = db.new_tx();
+            let layout = mm.table_layout("T", tx.clone()).unwrap();
+            assert!(!layout.schema().has_field("B"));
+            assert!(is_cached("T"));
+            mm.add_field("T", "B", SqlType::Integer, 0, Collation::Binary, tx.clone())
+                .unwrap();
+            tx.lock().unwrap().commit().unwrap();
+
+            let tx = db.new_tx();
+            let layout = mm.table_layout("T", tx.clone()).unwrap();
+            assert!(layout.schema().has_field("B"));
+            mm.drop_table("T", false, tx.clone()).unwrap();
+            assert!(matches!(
+                mm.table_layout("T", tx.clone()),
+                Err(MetadataError::TableNotFound(_))
+            ));
+            tx.lock().unwrap().commit().unwrap();
+        }
+        dir.close().unwrap();
+    }
 }
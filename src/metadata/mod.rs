@@ -3,9 +3,12 @@
 // This software is released under the MIT License.
 // https://opensource.org/licenses/MIT
 
+pub mod auth;
 pub(crate) mod common;
 pub mod index_mgr;
 pub mod metadata_mgr;
+pub mod sequence_mgr;
 pub mod stat_mgr;
 pub mod table_mgr;
+pub mod tablespace_mgr;
 pub mod view_mgr;
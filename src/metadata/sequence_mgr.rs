@@ -0,0 +1,169 @@
+// Copyright (c) 2023 Sho Kuroda <krdlab@gmail.com>
+//
+// This software is released under the MIT License.
+// https://opensource.org/licenses/MIT
+
+use super::common::{MetadataError, Result};
+use super::table_mgr::TableMgr;
+use crate::{
+    record::{schema::Schema, table_scan::TableScan},
+    tx::transaction::Transaction,
+};
+use std::sync::{Arc, Mutex};
+
+pub struct SequenceMgr {
+    tm: Arc<TableMgr>,
+}
+
+pub(super) const SEQUENCE_CATALOG_TABLE_NAME: &str = "seqcat";
+const START_VALUE: i32 = 1;
+
+impl SequenceMgr {
+    pub fn new(tm: Arc<TableMgr>) -> Self {
+        Self { tm }
+    }
+
+    pub fn init(&self, tx: Arc<Mutex<Transaction>>) {
+        self.create_catalog(tx).unwrap();
+    }
+
+    fn create_catalog(&self, tx: Arc<Mutex<Transaction>>) -> Result<()> {
+        for (tblname, schema) in Self::catalog_schemas(self.tm.max_name_length()) {
+            self.tm.create_table(tblname, schema, tx.clone())?;
+        }
+        Ok(())
+    }
+
+    pub(super) fn catalog_schemas(max_name_length: usize) -> Vec<(&'static str, Schema)> {
+        // NOTE: sequence -> next value
+        let mut schema = Schema::new();
+        schema.add_string_field("seqname", max_name_length);
+        schema.add_i32_field("nextval");
+        vec![(SEQUENCE_CATALOG_TABLE_NAME, schema)]
+    }
+
+    pub fn create_sequence(&self, seqname: &str, tx: Arc<Mutex<Transaction>>) -> Result<()> {
+        self.tm.check_name(seqname)?;
+        let mut ts = match self.open_catalog(tx.clone())? {
+            Some(mut ts) => {
+                while ts.next()? {
+                    if ts.get_string("seqname")? == seqname {
+                        return Err(MetadataError::SequenceAlreadyExists(seqname.into()));
+                    }
+                }
+                ts
+            }
+            // NOTE: created with the first sequence
+            None => {
+                self.create_catalog(tx.clone())?;
+                self.open_catalog(tx)?.unwrap()
+            }
+        };
+        ts.insert()?;
+        ts.set_string("seqname", seqname.into())?;
+        ts.set_i32("nextval", START_VALUE)?;
+        Ok(())
+    }
+
+    pub fn next_value(&self, seqname: &str, tx: Arc<Mutex<Transaction>>) -> Result<i32> {
+        if let Some(mut ts) = self.open_catalog(tx)? {
+            while ts.next()? {
+                if ts.get_string("seqname")? == seqname {
+                    let val = ts.get_i32("nextval")?;
+                    ts.set_i32("nextval", val + 1)?;
+                    return Ok(val);
+                }
+            }
+        }
+        Err(MetadataError::SequenceNotFound(seqname.into()))
+    }
+
+    pub fn sequence_names(&self, tx: Arc<Mutex<Transaction>>) -> Result<Vec<String>> {
+        let mut names = Vec::new();
+        if let Some(mut ts) = self.read_catalog(tx)? {
+            while ts.next()? {
+                names.push(ts.get_string("seqname")?);
+            }
+        }
+        Ok(names)
+    }
+
+    fn read_catalog(&self, tx: Arc<Mutex<Transaction>>) -> Result<Option<TableScan>> {
+        if tx
+            .lock()
+            .unwrap()
+            .size(&TableScan::filename(SEQUENCE_CATALOG_TABLE_NAME))?
+            == 0
+        {
+            return Ok(None);
+        }
+        self.open_catalog(tx)
+    }
+
+    fn open_catalog(&self, tx: Arc<Mutex<Transaction>>) -> Result<Option<TableScan>> {
+        match self.tm.layout(SEQUENCE_CATALOG_TABLE_NAME, tx.clone()) {
+            Ok(layout) => Ok(Some(TableScan::new(
+                tx,
+                SEQUENCE_CATALOG_TABLE_NAME.into(),
+                layout,
+            )?)),
+            Err(MetadataError::TableNotFound(_)) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SequenceMgr;
+    use crate::{
+        metadata::{
+            common::MetadataError,
+            table_mgr::{TableMgr, MAX_NAME_LENGTH},
+        },
+        server::simple_db::SimpleDB,
+    };
+    use std::sync::Arc;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test() {
+        let dir = tempdir().unwrap();
+        {
+            let db = SimpleDB::new_for_test(dir.path(), "sequence_mgr_test.log");
+            let tm = Arc::new(TableMgr::new(MAX_NAME_LENGTH));
+            let sm = SequenceMgr::new(tm.clone());
+            {
+                let tx = db.new_tx();
+                tm.init(tx.clone());
+                assert!(sm.sequence_names(tx.clone()).unwrap().is_empty());
+                sm.create_sequence("s1", tx.clone()).unwrap();
+                sm.create_sequence("s2", tx.clone()).unwrap();
+                assert!(matches!(
+                    sm.create_sequence("s1", tx.clone()),
+                    Err(MetadataError::SequenceAlreadyExists(s)) if s == "s1"
+                ));
+
+                assert_eq!(sm.next_value("s1", tx.clone()).unwrap(), 1);
+                assert_eq!(sm.next_value("s1", tx.clone()).unwrap(), 2);
+                assert_eq!(sm.next_value("s2", tx.clone()).unwrap(), 1);
+                assert!(matches!(
+                    sm.next_value("s3", tx.clone()),
+                    Err(MetadataError::SequenceNotFound(s)) if s == "s3"
+                ));
+                tx.lock().unwrap().commit().unwrap();
+            }
+            {
+                let tx = db.new_tx();
+                assert_eq!(sm.next_value("s1", tx.clone()).unwrap(), 3);
+                tx.lock().unwrap().rollback().unwrap();
+
+                let tx = db.new_tx();
+                assert_eq!(sm.next_value("s1", tx.clone()).unwrap(), 3);
+                assert_eq!(sm.sequence_names(tx.clone()).unwrap(), ["s1", "s2"]);
+                tx.lock().unwrap().commit().unwrap();
+            }
+        }
+        dir.close().unwrap();
+    }
+}
@@ -3,16 +3,18 @@
 // This software is released under the MIT License.
 // https://opensource.org/licenses/MIT
 
-use super::common::Result;
-use super::table_mgr::{TableMgr, TABLE_CATALOG_TABLE_NAME, TABLE_NAME_FIELD};
+use super::common::{MetadataError, Result};
+use super::table_mgr::{TableMgr, TABLE_NAME_FIELD};
 use crate::{
-    record::{schema::Layout, table_scan::TableScan},
+    query::scan::Scan,
+    record::{
+        schema::{Layout, Schema, SqlType},
+        table_scan::TableScan,
+    },
     tx::transaction::Transaction,
 };
 use std::{
-    cell::RefCell,
-    collections::{hash_map::Entry, HashMap},
-    rc::Rc,
+    collections::{HashMap, HashSet},
     sync::{Arc, Mutex},
 };
 
@@ -20,6 +22,52 @@ use std::{
 pub struct StatInfo {
     num_blocks: usize,
     num_records: usize,
+    columns: HashMap<String, ColumnStats>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ColumnStats {
+    distinct_values: usize,
+    histogram: Option<Histogram>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Histogram {
+    bounds: Vec<i32>,
+}
+
+impl Histogram {
+    const MAX_BUCKETS: usize = 8;
+
+    fn from_sorted(values: &[i32]) -> Option<Self> {
+        if values.is_empty() {
+            return None;
+        }
+        let num_buckets = Self::MAX_BUCKETS.min(values.len());
+        let bounds = (1..=num_buckets)
+            .map(|i| values[i * values.len() / num_buckets - 1])
+            .collect();
+        Some(Self { bounds })
+    }
+
+    pub fn bounds(&self) -> &[i32] {
+        &self.bounds
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        self.bounds.iter().flat_map(|b| b.to_be_bytes()).collect()
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.is_empty() {
+            return None;
+        }
+        let bounds = bytes
+            .chunks_exact(4)
+            .map(|c| i32::from_be_bytes(c.try_into().unwrap()))
+            .collect();
+        Some(Self { bounds })
+    }
 }
 
 impl StatInfo {
@@ -27,6 +75,7 @@ impl StatInfo {
         Self {
             num_blocks,
             num_records,
+            columns: HashMap::new(),
         }
     }
 
@@ -38,158 +87,544 @@ impl StatInfo {
         self.num_records
     }
 
-    pub fn distinct_values(&self, _field_name: &str) -> usize {
-        1 + self.num_records / 3 // NOTE: this is widely inaccurate
+    pub fn distinct_values(&self, field_name: &str) -> usize {
+        match self.columns.get(field_name) {
+            Some(cs) => cs.distinct_values,
+            None => 1 + self.num_records / 3, // NOTE: this is widely inaccurate
+        }
+    }
+
+    pub fn histogram(&self, field_name: &str) -> Option<&Histogram> {
+        self.columns.get(field_name)?.histogram.as_ref()
     }
 }
 
-pub struct StatMgrData {
-    tm: Arc<TableMgr>,
-    table_stats: HashMap<String, StatInfo>,
-    num_calls: usize,
+#[derive(Debug, Default, Clone, Copy)]
+struct Changes {
+    inserted: usize,
+    deleted: usize,
 }
+
+impl Changes {
+    fn are_significant(&self, num_records: usize) -> bool {
+        let counted = (num_records + self.deleted).saturating_sub(self.inserted);
+        self.inserted + self.deleted > StatMgr::MIN_CHANGES + counted / StatMgr::CHANGE_DIVISOR
+    }
+}
+
 pub struct StatMgr {
-    data: Mutex<StatMgrData>,
+    tm: Arc<TableMgr>,
 }
 
-impl StatMgrData {
-    pub(crate) fn refresh_statistics(&mut self, tx: Rc<RefCell<Transaction>>) -> Result<()> {
-        self.table_stats.clear();
-        self.num_calls = 0;
+pub(super) const STAT_CATALOG_TABLE_NAME: &str = "statcat";
+pub(super) const COLUMN_STAT_CATALOG_TABLE_NAME: &str = "colstatcat";
 
-        let mut table_names: Vec<String> = Vec::new();
-        {
-            let layout = self.tm.layout(TABLE_CATALOG_TABLE_NAME, tx.clone())?;
-            let mut tcat = TableScan::new(tx.clone(), TABLE_CATALOG_TABLE_NAME.into(), layout);
-            while tcat.next()? {
-                let table_name = tcat.get_string(TABLE_NAME_FIELD).unwrap();
-                table_names.push(table_name);
-            }
+impl StatMgr {
+    const MIN_CHANGES: usize = 100;
+    const CHANGE_DIVISOR: usize = 5;
+
+    pub fn new(tm: Arc<TableMgr>) -> Self {
+        Self { tm }
+    }
+
+    pub fn init(&self, tx: Arc<Mutex<Transaction>>) {
+        for (tblname, schema) in Self::catalog_schemas(self.tm.max_name_length()) {
+            self.tm.create_table(tblname, schema, tx.clone()).unwrap();
         }
-        for table_name in table_names {
-            if let Ok(layout) = self.tm.layout(&table_name, tx.clone()) {
-                let stats = StatMgrData::calc_table_stats(&table_name, layout, tx.clone())?;
-                self.table_stats.insert(table_name, stats);
+    }
+
+    pub(super) fn catalog_schemas(max_name_length: usize) -> Vec<(&'static str, Schema)> {
+        let mut scat_schema = Schema::new();
+        scat_schema.add_string_field(TABLE_NAME_FIELD, max_name_length);
+        scat_schema.add_i32_field("numblocks");
+        scat_schema.add_i32_field("numrecords");
+        scat_schema.add_i32_field("inserted");
+        scat_schema.add_i32_field("deleted");
+
+        let mut cscat_schema = Schema::new();
+        cscat_schema.add_string_field(TABLE_NAME_FIELD, max_name_length);
+        cscat_schema.add_string_field("fldname", max_name_length);
+        cscat_schema.add_i32_field("distinct");
+        // NOTE: a character takes up to four bytes
+        cscat_schema.add_string_field("histogram", Histogram::MAX_BUCKETS);
+        vec![
+            (STAT_CATALOG_TABLE_NAME, scat_schema),
+            (COLUMN_STAT_CATALOG_TABLE_NAME, cscat_schema),
+        ]
+    }
+
+    pub fn table_stat_info(
+        &self,
+        table_name: &str,
+        layout: Layout,
+        tx: Arc<Mutex<Transaction>>,
+    ) -> Result<StatInfo> {
+        self.get_or_create_table_stat_info(table_name, layout, tx)
+    }
+
+    fn get_or_create_table_stat_info(
+        &self,
+        table_name: &str,
+        layout: Layout,
+        tx: Arc<Mutex<Transaction>>,
+    ) -> Result<StatInfo> {
+        let Some(mut ts) = self.open_catalog(STAT_CATALOG_TABLE_NAME, tx.clone())? else {
+            // NOTE: a database older than the catalog
+            return Self::calc_table_stats(table_name, layout, tx);
+        };
+        if Self::find(&mut ts, table_name)? {
+            let mut stats = StatInfo::new(
+                ts.get_i32("numblocks")? as usize,
+                ts.get_i32("numrecords")? as usize,
+            );
+            let changes = Self::changes(&mut ts)?;
+            ts.close();
+            // NOTE: left until the next ANALYZE
+            if changes.are_significant(stats.num_records) && !tx.lock().unwrap().is_read_only() {
+                stats = self.refresh_table_stats(table_name, layout, tx.clone())?;
             }
+            stats.columns = self.column_stats(table_name, tx)?;
+            return Ok(stats);
         }
-        Ok(())
+        ts.close();
+
+        let stats = Self::calc_table_stats(table_name, layout, tx.clone())?;
+        if !tx.lock().unwrap().is_read_only() {
+            self.store(table_name, &stats, tx)?;
+        }
+        Ok(stats)
     }
 
-    pub(crate) fn get_or_create_table_stat_info(
-        &mut self,
+    pub fn refresh_table_stats(
+        &self,
         table_name: &str,
         layout: Layout,
-        tx: Rc<RefCell<Transaction>>,
+        tx: Arc<Mutex<Transaction>>,
     ) -> Result<StatInfo> {
-        let si = match self.table_stats.entry(table_name.into()) {
-            Entry::Occupied(e) => e.into_mut(),
-            Entry::Vacant(ve) => {
-                let si = StatMgrData::calc_table_stats(table_name, layout, tx)?;
-                ve.insert(si)
-            }
+        let stats = Self::calc_table_stats(table_name, layout, tx.clone())?;
+        self.store(table_name, &stats, tx)?;
+        Ok(stats)
+    }
+
+    pub fn record_insert(
+        &self,
+        table_name: &str,
+        block_number: usize,
+        tx: Arc<Mutex<Transaction>>,
+    ) -> Result<()> {
+        self.update(table_name, tx, |stats, changes| {
+            stats.num_blocks = stats.num_blocks.max(block_number + 1);
+            stats.num_records += 1;
+            changes.inserted += 1;
+        })
+    }
+
+    pub fn record_inserts(
+        &self,
+        table_name: &str,
+        num_blocks: usize,
+        count: usize,
+        tx: Arc<Mutex<Transaction>>,
+    ) -> Result<()> {
+        self.update(table_name, tx, |stats, changes| {
+            stats.num_blocks = stats.num_blocks.max(num_blocks);
+            stats.num_records += count;
+            changes.inserted += count;
+        })
+    }
+
+    pub fn record_deletes(
+        &self,
+        table_name: &str,
+        count: usize,
+        tx: Arc<Mutex<Transaction>>,
+    ) -> Result<()> {
+        self.update(table_name, tx, |stats, changes| {
+            stats.num_records = stats.num_records.saturating_sub(count);
+            changes.deleted += count;
+        })
+    }
+
+    fn update(
+        &self,
+        table_name: &str,
+        tx: Arc<Mutex<Transaction>>,
+        f: impl FnOnce(&mut StatInfo, &mut Changes),
+    ) -> Result<()> {
+        let Some(mut ts) = self.open_catalog(STAT_CATALOG_TABLE_NAME, tx)? else {
+            return Ok(());
         };
-        Ok(si.clone())
+        if Self::find(&mut ts, table_name)? {
+            let mut stats = StatInfo::new(
+                ts.get_i32("numblocks")? as usize,
+                ts.get_i32("numrecords")? as usize,
+            );
+            let mut changes = Self::changes(&mut ts)?;
+            f(&mut stats, &mut changes);
+            Self::set_stats(&mut ts, &stats, changes)?;
+        }
+        ts.close();
+        Ok(())
+    }
+
+    fn store(&self, table_name: &str, stats: &StatInfo, tx: Arc<Mutex<Transaction>>) -> Result<()> {
+        let Some(mut ts) = self.open_catalog(STAT_CATALOG_TABLE_NAME, tx)? else {
+            return Ok(());
+        };
+        if !Self::find(&mut ts, table_name)? {
+            ts.insert()?;
+            ts.set_string(TABLE_NAME_FIELD, table_name.into())?;
+        }
+        Self::set_stats(&mut ts, stats, Changes::default())?;
+        ts.close();
+        Ok(())
     }
 
-    pub(crate) fn calc_table_stats(
+    pub fn analyze(
+        &self,
         table_name: &str,
         layout: Layout,
-        tx: Rc<RefCell<Transaction>>,
+        tx: Arc<Mutex<Transaction>>,
     ) -> Result<StatInfo> {
-        let mut num_records = 0;
-        let mut num_blocks = 0;
+        let mut stats = Self::calc_table_stats(table_name, layout.clone(), tx.clone())?;
+        stats.columns = Self::calc_column_stats(table_name, layout, tx.clone())?;
+        self.store(table_name, &stats, tx.clone())?;
+
+        let Some(mut ts) = self.open_catalog(COLUMN_STAT_CATALOG_TABLE_NAME, tx)? else {
+            return Ok(stats);
+        };
+        while Self::find(&mut ts, table_name)? {
+            ts.delete()?;
+        }
+        for (fldname, cs) in stats.columns.iter() {
+            ts.insert()?;
+            ts.set_string(TABLE_NAME_FIELD, table_name.into())?;
+            ts.set_string("fldname", fldname.clone())?;
+            ts.set_i32("distinct", cs.distinct_values.try_into().unwrap())?;
+            let histogram = cs.histogram.as_ref().map(Histogram::to_bytes);
+            ts.set_bytes("histogram", &histogram.unwrap_or_default())?;
+        }
+        ts.close();
+        Ok(stats)
+    }
+
+    pub fn drop_table_stats(&self, table_name: &str, tx: Arc<Mutex<Transaction>>) -> Result<()> {
+        for catalog in [STAT_CATALOG_TABLE_NAME, COLUMN_STAT_CATALOG_TABLE_NAME] {
+            if let Some(mut ts) = self.open_catalog(catalog, tx.clone())? {
+                while Self::find(&mut ts, table_name)? {
+                    ts.delete()?;
+                }
+                ts.close();
+            }
+        }
+        Ok(())
+    }
+
+    pub fn rename_table_stats(
+        &self,
+        table_name: &str,
+        new_name: &str,
+        tx: Arc<Mutex<Transaction>>,
+    ) -> Result<()> {
+        for catalog in [STAT_CATALOG_TABLE_NAME, COLUMN_STAT_CATALOG_TABLE_NAME] {
+            if let Some(mut ts) = self.open_catalog(catalog, tx.clone())? {
+                while Self::find(&mut ts, table_name)? {
+                    ts.set_string(TABLE_NAME_FIELD, new_name.into())?;
+                }
+                ts.close();
+            }
+        }
+        Ok(())
+    }
 
-        let mut ts = TableScan::new(tx, table_name.into(), layout);
+    fn column_stats(
+        &self,
+        table_name: &str,
+        tx: Arc<Mutex<Transaction>>,
+    ) -> Result<HashMap<String, ColumnStats>> {
+        let mut columns = HashMap::new();
+        let Some(mut ts) = self.open_catalog(COLUMN_STAT_CATALOG_TABLE_NAME, tx)? else {
+            return Ok(columns);
+        };
+        while Self::find(&mut ts, table_name)? {
+            let cs = ColumnStats {
+                distinct_values: ts.get_i32("distinct")? as usize,
+                histogram: Histogram::from_bytes(&ts.get_bytes("histogram")?),
+            };
+            columns.insert(ts.get_string("fldname")?, cs);
+        }
+        ts.close();
+        Ok(columns)
+    }
+
+    fn open_catalog(
+        &self,
+        catalog: &str,
+        tx: Arc<Mutex<Transaction>>,
+    ) -> Result<Option<TableScan>> {
+        match self.tm.layout(catalog, tx.clone()) {
+            Ok(layout) => Ok(Some(TableScan::new(tx, catalog.into(), layout)?)),
+            Err(MetadataError::TableNotFound(_)) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn find(ts: &mut TableScan, table_name: &str) -> Result<bool> {
         while ts.next()? {
-            num_records += 1;
-            num_blocks = ts.current_rid().block_number() + 1;
+            if ts.get_string(TABLE_NAME_FIELD)? == table_name {
+                return Ok(true);
+            }
         }
+        Ok(false)
+    }
 
-        Ok(StatInfo {
-            num_blocks: num_blocks.try_into().unwrap(),
-            num_records,
+    fn set_stats(ts: &mut TableScan, stats: &StatInfo, changes: Changes) -> Result<()> {
+        ts.set_i32("numblocks", stats.num_blocks.try_into().unwrap())?;
+        ts.set_i32("numrecords", stats.num_records.try_into().unwrap())?;
+        // NOTE: an older catalog doesn't count the changes
+        if ts.has_field("inserted") {
+            ts.set_i32("inserted", changes.inserted.try_into().unwrap())?;
+            ts.set_i32("deleted", changes.deleted.try_into().unwrap())?;
+        }
+        Ok(())
+    }
+
+    fn changes(ts: &mut TableScan) -> Result<Changes> {
+        if !ts.has_field("inserted") {
+            return Ok(Changes::default());
+        }
+        Ok(Changes {
+            inserted: ts.get_i32("inserted")? as usize,
+            deleted: ts.get_i32("deleted")? as usize,
         })
     }
-}
 
-const STATS_REFRESH_THRESHOLD: usize = 100;
+    fn calc_table_stats(
+        table_name: &str,
+        layout: Layout,
+        tx: Arc<Mutex<Transaction>>,
+    ) -> Result<StatInfo> {
+        let mut num_records = 0;
+        let mut num_blocks = 0;
 
-impl StatMgr {
-    pub fn new(tm: Arc<TableMgr>) -> Self {
-        Self {
-            data: Mutex::new(StatMgrData {
-                tm,
-                table_stats: HashMap::new(),
-                num_calls: 0,
-            }),
+        let mut ts = TableScan::new(tx, table_name.into(), layout)?;
+        while ts.next()? {
+            num_records += 1;
+            num_blocks = ts.current_rid().block_number() + 1;
         }
-    }
 
-    pub fn init(&self, tx: Rc<RefCell<Transaction>>) {
-        let mut data = self.data.lock().unwrap();
-        data.refresh_statistics(tx).unwrap();
+        Ok(StatInfo::new(num_blocks.try_into().unwrap(), num_records))
     }
 
-    pub fn table_stat_info(
-        &self,
+    fn calc_column_stats(
         table_name: &str,
         layout: Layout,
-        tx: Rc<RefCell<Transaction>>,
-    ) -> StatInfo {
-        let mut data = self.data.lock().unwrap();
+        tx: Arc<Mutex<Transaction>>,
+    ) -> Result<HashMap<String, ColumnStats>> {
+        let schema = layout.schema().clone();
+        let mut ints: HashMap<&str, Vec<i32>> = HashMap::new();
+        let mut strings: HashMap<&str, HashSet<String>> = HashMap::new();
 
-        data.num_calls += 1;
-        if data.num_calls > STATS_REFRESH_THRESHOLD {
-            data.refresh_statistics(tx.clone()).unwrap();
+        let mut ts = TableScan::new(tx, table_name.into(), layout)?;
+        while ts.next()? {
+            for fldname in schema.fields_iter() {
+                if schema.field_type(fldname) == Some(SqlType::Integer) {
+                    let val = ts.get_i32(fldname)?;
+                    ints.entry(fldname).or_default().push(val);
+                } else {
+                    let val = ts.get_string(fldname)?;
+                    strings.entry(fldname).or_default().insert(val);
+                }
+            }
         }
+        ts.close();
 
-        // FIXME: If TableMgr has the specified table records, StatMgr should create a StatInfo. If not, should not create it.
-        data.get_or_create_table_stat_info(table_name, layout, tx.clone())
-            .unwrap() // TODO
+        let mut columns = HashMap::new();
+        for (fldname, mut values) in ints {
+            values.sort_unstable();
+            let histogram = Histogram::from_sorted(&values);
+            values.dedup();
+            let cs = ColumnStats {
+                distinct_values: values.len(),
+                histogram,
+            };
+            columns.insert(fldname.to_string(), cs);
+        }
+        for (fldname, values) in strings {
+            let cs = ColumnStats {
+                distinct_values: values.len(),
+                histogram: None,
+            };
+            columns.insert(fldname.to_string(), cs);
+        }
+        Ok(columns)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::StatMgr;
+    use super::{Histogram, StatMgr};
     use crate::{
-        metadata::{
-            stat_mgr::STATS_REFRESH_THRESHOLD,
-            table_mgr::{TableMgr, TABLE_CATALOG_TABLE_NAME, TABLE_NAME_FIELD},
-        },
+        metadata::table_mgr::{TABLE_CATALOG_TABLE_NAME, TABLE_NAME_FIELD},
+        query::scan::Scan,
+        record::table_scan::TableScan,
         server::simple_db::SimpleDB,
     };
-    use std::sync::Arc;
     use tempfile::tempdir;
 
     #[test]
     fn test() {
         let dir = tempdir().unwrap();
         {
-            let db = SimpleDB::new_for_test(dir.path(), "stat_mgr_test.log");
+            let mut db = SimpleDB::new_for_test(dir.path(), "stat_mgr_test.log");
+            db.init().unwrap();
+            let mm = db.metadata_mgr();
+            let planner = db.planner();
+            let tx = db.new_tx();
+
+            let layout = mm
+                .table_layout(TABLE_CATALOG_TABLE_NAME, tx.clone())
+                .unwrap();
+            let stats = mm
+                .table_stat_info(TABLE_CATALOG_TABLE_NAME, layout, tx.clone())
+                .unwrap();
+            assert_eq!(stats.blocks_accessed(), 1);
+            assert_eq!(
+                stats.distinct_values(TABLE_NAME_FIELD),
+                1 + stats.records_output() / 3
+            );
+
+            planner
+                .execute_update("create table T(A int)", tx.clone())
+                .unwrap();
+            let layout = mm.table_layout("t", tx.clone()).unwrap();
+            let stats = mm.table_stat_info("t", layout.clone(), tx.clone()).unwrap();
+            assert_eq!((stats.blocks_accessed(), stats.records_output()), (0, 0));
+
+            for i in 0..10 {
+                let cmd = format!("insert into T(A) values ({})", i);
+                planner.execute_update(&cmd, tx.clone()).unwrap();
+            }
+            planner
+                .execute_update("delete from T where A = 3", tx.clone())
+                .unwrap();
+            let stats = mm.table_stat_info("t", layout, tx.clone()).unwrap();
+            assert_eq!((stats.blocks_accessed(), stats.records_output()), (1, 9));
+            tx.lock().unwrap().commit().unwrap();
+        }
+        {
+            let mut db = SimpleDB::new_for_test(dir.path(), "stat_mgr_test.log");
+            db.init().unwrap();
+            let mm = db.metadata_mgr();
             let tx = db.new_tx();
+            let layout = mm.table_layout("t", tx.clone()).unwrap();
             {
-                let tm = Arc::new(TableMgr::new());
-                tm.init(tx.clone());
-                let sm = StatMgr::new(tm.clone());
-                sm.init(tx.clone());
-
-                let layout = tm.layout(TABLE_CATALOG_TABLE_NAME, tx.clone()).unwrap();
-                let stats1 =
-                    sm.table_stat_info(TABLE_CATALOG_TABLE_NAME, layout.clone(), tx.clone());
-                assert_eq!(stats1.blocks_accessed(), 1);
-                assert_eq!(stats1.records_output(), 2);
-                assert_eq!(stats1.distinct_values(TABLE_NAME_FIELD), 1);
-
-                for _ in 0..(STATS_REFRESH_THRESHOLD + 1) {
-                    let _ =
-                        sm.table_stat_info(TABLE_CATALOG_TABLE_NAME, layout.clone(), tx.clone());
-                }
+                let mut ts = TableScan::new(tx.clone(), "t".into(), layout.clone()).unwrap();
+                ts.insert().unwrap();
+                ts.set_i32("a", 10).unwrap();
+                ts.close();
+            }
+            let stats = mm.table_stat_info("t", layout.clone(), tx.clone()).unwrap();
+            assert_eq!(stats.records_output(), 9);
+            assert_eq!(stats.distinct_values("a"), 1 + 9 / 3);
+            assert_eq!(stats.histogram("a"), None);
+
+            db.planner()
+                .execute_update("analyze T", tx.clone())
+                .unwrap();
+            let stats = mm.table_stat_info("t", layout.clone(), tx.clone()).unwrap();
+            assert_eq!(stats.records_output(), 10);
+            assert_eq!(stats.distinct_values("a"), 10);
+            assert_eq!(
+                stats.histogram("a").unwrap().bounds(),
+                [0, 1, 2, 5, 6, 7, 8, 10]
+            );
+            assert_eq!(mm.analyze("t", tx.clone()).unwrap(), stats);
 
-                let stats2 = sm.table_stat_info(TABLE_CATALOG_TABLE_NAME, layout, tx.clone());
-                assert_eq!(stats2, stats1);
+            {
+                let mut ts = TableScan::new(tx.clone(), "t".into(), layout.clone()).unwrap();
+                ts.insert().unwrap();
+                ts.set_i32("a", 11).unwrap();
+                ts.close();
+            }
+            let planner = db.planner();
+            for i in 0..(StatMgr::MIN_CHANGES + 10 / StatMgr::CHANGE_DIVISOR) {
+                let cmd = format!("insert into T(A) values ({})", 100 + i);
+                planner.execute_update(&cmd, tx.clone()).unwrap();
             }
-            tx.borrow_mut().commit().unwrap();
+            let stats = mm.table_stat_info("t", layout.clone(), tx.clone()).unwrap();
+            assert_eq!(stats.records_output(), 10 + 102);
+            planner
+                .execute_update("insert into T(A) values (0)", tx.clone())
+                .unwrap();
+            let stats = mm.table_stat_info("t", layout, tx.clone()).unwrap();
+            assert_eq!(stats.records_output(), 10 + 103 + 1);
+            assert_eq!(stats.distinct_values("a"), 10);
+            tx.lock().unwrap().commit().unwrap();
         }
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_analyze() {
+        let dir = tempdir().unwrap();
+        {
+            let mut db = SimpleDB::new_for_test(dir.path(), "stat_mgr_analyze_test.log");
+            db.init().unwrap();
+            let planner = db.planner();
+            let tx = db.new_tx();
+            planner
+                .execute_update("create table T(A int, B varchar(10))", tx.clone())
+                .unwrap();
+            for i in 0..20 {
+                let cmd = format!("insert into T(A, B) values ({}, 'b{}')", i % 4, i % 5);
+                planner.execute_update(&cmd, tx.clone()).unwrap();
+            }
+            planner.execute_update("analyze T", tx.clone()).unwrap();
+            tx.lock().unwrap().commit().unwrap();
+        }
+        {
+            let mut db = SimpleDB::new_for_test(dir.path(), "stat_mgr_analyze_test.log");
+            db.init().unwrap();
+            let mm = db.metadata_mgr();
+            let tx = db.new_tx();
+            let layout = mm.table_layout("t", tx.clone()).unwrap();
+            let stats = mm.table_stat_info("t", layout, tx.clone()).unwrap();
+            assert_eq!(stats.records_output(), 20);
+            assert_eq!(stats.distinct_values("a"), 4);
+            assert_eq!(stats.distinct_values("b"), 5);
+            assert_eq!(
+                stats.histogram("a").unwrap().bounds(),
+                [0, 0, 1, 1, 2, 2, 3, 3]
+            );
+            assert_eq!(stats.histogram("b"), None);
+
+            let planner = db.planner();
+            planner.execute_update("drop table T", tx.clone()).unwrap();
+            planner
+                .execute_update("create table T(A int, B varchar(10))", tx.clone())
+                .unwrap();
+            let layout = mm.table_layout("t", tx.clone()).unwrap();
+            let stats = mm.table_stat_info("t", layout, tx.clone()).unwrap();
+            assert_eq!(stats.histogram("a"), None);
+            tx.lock().unwrap().commit().unwrap();
+        }
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_histogram() {
+        assert_eq!(Histogram::from_sorted(&[]), None);
+        let histogram = Histogram::from_sorted(&[3, 5, 9]).unwrap();
+        assert_eq!(histogram.bounds(), [3, 5, 9]);
+        let values: Vec<i32> = (1..=100).collect();
+        let histogram = Histogram::from_sorted(&values).unwrap();
+        assert_eq!(histogram.bounds(), [12, 25, 37, 50, 62, 75, 87, 100]);
+
+        assert_eq!(
+            Histogram::from_bytes(&histogram.to_bytes()).unwrap(),
+            histogram
+        );
+        assert_eq!(Histogram::from_bytes(&[]), None);
     }
 }
@@ -5,46 +5,80 @@
 
 use super::common::{MetadataError, Result};
 use crate::{
+    buffer_mgr::PinPriority,
     record::{
-        schema::{Layout, Schema},
+        schema::{Collation, Layout, Schema, SqlType},
         table_scan::TableScan,
     },
     tx::transaction::Transaction,
 };
-use std::{cell::RefCell, collections::HashMap, rc::Rc};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
 
 pub struct TableMgr {
     tcat_layout: Layout,
     fcat_layout: Layout,
+    max_name_length: usize,
 }
 
-pub const MAX_NAME_LENGTH: usize = 16;
+pub const MAX_NAME_LENGTH: usize = 64;
 pub const TABLE_CATALOG_TABLE_NAME: &str = "tblcat";
 pub const TABLE_NAME_FIELD: &str = "tblname";
-const FIELD_CATALOG_TABLE_NAME: &str = "fldcat";
+pub(super) const FIELD_CATALOG_TABLE_NAME: &str = "fldcat";
 
 impl TableMgr {
-    pub fn new() -> Self {
-        let mut tcat_schema = Schema::new();
-        tcat_schema.add_string_field(TABLE_NAME_FIELD, MAX_NAME_LENGTH);
-        tcat_schema.add_i32_field("slotsize");
-        let tcat_layout = Layout::new(tcat_schema);
-
-        let mut fcat_schema = Schema::new();
-        fcat_schema.add_string_field(TABLE_NAME_FIELD, MAX_NAME_LENGTH);
-        fcat_schema.add_string_field("fldname", MAX_NAME_LENGTH);
-        fcat_schema.add_i32_field("type");
-        fcat_schema.add_i32_field("length");
-        fcat_schema.add_i32_field("offset");
-        let fcat_layout = Layout::new(fcat_schema);
-
+    pub fn new(max_name_length: usize) -> Self {
         Self {
-            tcat_layout,
-            fcat_layout,
+            tcat_layout: Layout::new(Self::tcat_schema(max_name_length)),
+            fcat_layout: Layout::new(Self::fcat_schema(max_name_length)),
+            max_name_length,
         }
     }
 
-    pub fn init(&self, tx: Rc<RefCell<Transaction>>) {
+    fn tcat_schema(max_name_length: usize) -> Schema {
+        let mut schema = Schema::new();
+        schema.add_string_field(TABLE_NAME_FIELD, max_name_length);
+        schema.add_i32_field("slotsize");
+        schema.add_i32_field("version");
+        schema
+    }
+
+    fn fcat_schema(max_name_length: usize) -> Schema {
+        let mut schema = Schema::new();
+        schema.add_string_field(TABLE_NAME_FIELD, max_name_length);
+        schema.add_string_field("fldname", max_name_length);
+        schema.add_i32_field("type");
+        schema.add_i32_field("length");
+        schema.add_i32_field("offset");
+        schema.add_i32_field("version");
+        schema.add_i32_field("collation");
+        schema
+    }
+
+    pub(super) fn catalog_schemas(max_name_length: usize) -> Vec<(&'static str, Schema)> {
+        vec![
+            (TABLE_CATALOG_TABLE_NAME, Self::tcat_schema(max_name_length)),
+            (FIELD_CATALOG_TABLE_NAME, Self::fcat_schema(max_name_length)),
+        ]
+    }
+
+    pub fn max_name_length(&self) -> usize {
+        self.max_name_length
+    }
+
+    pub fn check_name(&self, name: &str) -> Result<()> {
+        if name.chars().count() > self.max_name_length {
+            return Err(MetadataError::NameTooLong(
+                name.into(),
+                self.max_name_length,
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn init(&self, tx: Arc<Mutex<Transaction>>) {
         self.create_table(
             TABLE_CATALOG_TABLE_NAME,
             self.tcat_layout.schema().clone(),
@@ -63,15 +97,20 @@ impl TableMgr {
         &self,
         tblname: &str,
         schema: Schema,
-        tx: Rc<RefCell<Transaction>>,
+        tx: Arc<Mutex<Transaction>>,
     ) -> Result<()> {
-        let layout = Layout::new(schema.clone());
+        self.check_name(tblname)?;
+        for fldname in schema.fields_iter() {
+            self.check_name(fldname)?;
+        }
+        let layout = Self::check_layout(tblname, schema, tx.clone())?;
         {
-            let mut tcat = TableScan::new(
+            let mut tcat = TableScan::new_with_priority(
                 tx.clone(),
                 TABLE_CATALOG_TABLE_NAME.into(),
                 self.tcat_layout.clone(),
-            );
+                PinPriority::Keep,
+            )?;
             while tcat.next()? {
                 let n = tcat.get_string(TABLE_NAME_FIELD)?;
                 if n == tblname {
@@ -82,55 +121,247 @@ impl TableMgr {
             tcat.insert()?;
             tcat.set_string(TABLE_NAME_FIELD, tblname.into())?;
             tcat.set_i32("slotsize", layout.slotsize().try_into().unwrap())?;
+            tcat.set_i32("version", 0)?;
+        }
+        self.insert_fields(tblname, &layout, 0, tx)
+    }
+
+    pub fn add_field(
+        &self,
+        tblname: &str,
+        fname: &str,
+        ftype: SqlType,
+        flength: usize,
+        collation: Collation,
+        tx: Arc<Mutex<Transaction>>,
+    ) -> Result<Layout> {
+        self.check_name(fname)?;
+        let layout = self.layout(tblname, tx.clone())?;
+        if layout.schema().has_field(fname) {
+            return Err(MetadataError::FieldAlreadyExists(
+                tblname.into(),
+                fname.into(),
+            ));
+        }
+        let mut schema = layout.schema().clone();
+        schema.add_field(fname, ftype, flength);
+        schema.set_collation(fname, collation);
+        self.create_version(tblname, schema, layout.version() + 1, tx)
+    }
+
+    pub fn drop_field(
+        &self,
+        tblname: &str,
+        fname: &str,
+        tx: Arc<Mutex<Transaction>>,
+    ) -> Result<Layout> {
+        let layout = self.layout(tblname, tx.clone())?;
+        if !layout.schema().has_field(fname) {
+            return Err(MetadataError::FieldNotFound(tblname.into(), fname.into()));
+        }
+        let mut schema = Schema::new();
+        for f in layout.schema().fields_iter().filter(|f| *f != fname) {
+            schema.add_field_from(f, layout.schema());
+        }
+        self.create_version(tblname, schema, layout.version() + 1, tx)
+    }
+
+    pub fn drop_table(&self, tblname: &str, tx: Arc<Mutex<Transaction>>) -> Result<()> {
+        {
+            let mut tcat = TableScan::new_with_priority(
+                tx.clone(),
+                TABLE_CATALOG_TABLE_NAME.into(),
+                self.tcat_layout.clone(),
+                PinPriority::Keep,
+            )?;
+            let mut found = false;
+            while tcat.next()? {
+                if tcat.get_string(TABLE_NAME_FIELD)? == tblname {
+                    tcat.delete()?;
+                    found = true;
+                    break;
+                }
+            }
+            if !found {
+                return Err(MetadataError::TableNotFound(tblname.into()));
+            }
+        }
+        let mut fcat = TableScan::new_with_priority(
+            tx,
+            FIELD_CATALOG_TABLE_NAME.into(),
+            self.fcat_layout.clone(),
+            PinPriority::Keep,
+        )?;
+        while fcat.next()? {
+            if fcat.get_string(TABLE_NAME_FIELD)? == tblname {
+                fcat.delete()?;
+            }
         }
+        Ok(())
+    }
+
+    pub fn rename_table(
+        &self,
+        tblname: &str,
+        newname: &str,
+        tx: Arc<Mutex<Transaction>>,
+    ) -> Result<()> {
+        self.check_name(newname)?;
         {
-            let mut fcat = TableScan::new(
+            let mut tcat = TableScan::new_with_priority(
                 tx.clone(),
-                FIELD_CATALOG_TABLE_NAME.into(),
-                self.fcat_layout.clone(),
-            );
-            for fldname in schema.fields_iter() {
-                let ftype = schema.field_type(fldname).unwrap(); // NOTE: If the returned value is None, it's a bug.
-                let flength = schema.field_length(fldname).unwrap(); // NOTE: same as above
-                let foffset = layout.field_offset(fldname).unwrap();
-                fcat.insert()?;
-                fcat.set_string(TABLE_NAME_FIELD, tblname.into())?;
-                fcat.set_string("fldname", fldname.into())?;
-                fcat.set_i32("type", ftype.into())?;
-                fcat.set_i32("length", flength.try_into().unwrap())?;
-                fcat.set_i32("offset", foffset.try_into().unwrap())?;
+                TABLE_CATALOG_TABLE_NAME.into(),
+                self.tcat_layout.clone(),
+                PinPriority::Keep,
+            )?;
+            while tcat.next()? {
+                if tcat.get_string(TABLE_NAME_FIELD)? == newname {
+                    return Err(MetadataError::TableAlreadyExists(newname.into()));
+                }
+            }
+            tcat.before_first()?;
+            let mut found = false;
+            while tcat.next()? {
+                if tcat.get_string(TABLE_NAME_FIELD)? == tblname {
+                    tcat.set_string(TABLE_NAME_FIELD, newname.into())?;
+                    found = true;
+                    break;
+                }
+            }
+            if !found {
+                return Err(MetadataError::TableNotFound(tblname.into()));
+            }
+        }
+        let mut fcat = TableScan::new_with_priority(
+            tx,
+            FIELD_CATALOG_TABLE_NAME.into(),
+            self.fcat_layout.clone(),
+            PinPriority::Keep,
+        )?;
+        while fcat.next()? {
+            if fcat.get_string(TABLE_NAME_FIELD)? == tblname {
+                fcat.set_string(TABLE_NAME_FIELD, newname.into())?;
             }
         }
         Ok(())
     }
 
-    fn table_slotsize(&self, tblname: &str, tx: Rc<RefCell<Transaction>>) -> Result<usize> {
-        let mut tcat = TableScan::new(
+    pub fn table_names(&self, tx: Arc<Mutex<Transaction>>) -> Result<Vec<String>> {
+        let mut tcat = TableScan::new_with_priority(
             tx,
             TABLE_CATALOG_TABLE_NAME.into(),
             self.tcat_layout.clone(),
-        );
+            PinPriority::Keep,
+        )?;
+        let mut names = Vec::new();
+        while tcat.next()? {
+            names.push(tcat.get_string(TABLE_NAME_FIELD)?);
+        }
+        Ok(names)
+    }
+
+    fn check_layout(tblname: &str, schema: Schema, tx: Arc<Mutex<Transaction>>) -> Result<Layout> {
+        let layout = Layout::new(schema);
+        // NOTE: records cannot span blocks
+        let space = tx.lock().unwrap().block_size() - Layout::PAGE_HEADER_SIZE;
+        if layout.slotsize() > space {
+            return Err(MetadataError::RecordTooLarge {
+                table: tblname.into(),
+                slotsize: layout.slotsize(),
+                max: space - Layout::SLOT_HEADER_SIZE,
+            });
+        }
+        Ok(layout)
+    }
+
+    fn create_version(
+        &self,
+        tblname: &str,
+        schema: Schema,
+        version: i32,
+        tx: Arc<Mutex<Transaction>>,
+    ) -> Result<Layout> {
+        let layout = Self::check_layout(tblname, schema, tx.clone())?;
+        {
+            let mut tcat = TableScan::new_with_priority(
+                tx.clone(),
+                TABLE_CATALOG_TABLE_NAME.into(),
+                self.tcat_layout.clone(),
+                PinPriority::Keep,
+            )?;
+            while tcat.next()? {
+                if tcat.get_string(TABLE_NAME_FIELD)? == tblname {
+                    tcat.set_i32("slotsize", layout.slotsize().try_into().unwrap())?;
+                    tcat.set_i32("version", version)?;
+                    break;
+                }
+            }
+        }
+        self.insert_fields(tblname, &layout, version, tx.clone())?;
+        self.layout(tblname, tx)
+    }
+
+    fn insert_fields(
+        &self,
+        tblname: &str,
+        layout: &Layout,
+        version: i32,
+        tx: Arc<Mutex<Transaction>>,
+    ) -> Result<()> {
+        let mut fcat = TableScan::new_with_priority(
+            tx,
+            FIELD_CATALOG_TABLE_NAME.into(),
+            self.fcat_layout.clone(),
+            PinPriority::Keep,
+        )?;
+        let schema = layout.schema();
+        for fldname in schema.fields_iter() {
+            let ftype = schema.field_type(fldname).unwrap(); // NOTE: If the returned value is None, it's a bug.
+            let flength = schema.field_length(fldname).unwrap(); // NOTE: same as above
+            let foffset = layout.field_offset(fldname).unwrap();
+            fcat.insert()?;
+            fcat.set_string(TABLE_NAME_FIELD, tblname.into())?;
+            fcat.set_string("fldname", fldname.into())?;
+            fcat.set_i32("type", ftype.into())?;
+            fcat.set_i32("length", flength.try_into().unwrap())?;
+            fcat.set_i32("offset", foffset.try_into().unwrap())?;
+            fcat.set_i32("version", version)?;
+            fcat.set_i32("collation", schema.field_collation(fldname).unwrap().into())?;
+        }
+        Ok(())
+    }
+
+    fn table_slotsize(&self, tblname: &str, tx: Arc<Mutex<Transaction>>) -> Result<(usize, i32)> {
+        let mut tcat = TableScan::new_with_priority(
+            tx,
+            TABLE_CATALOG_TABLE_NAME.into(),
+            self.tcat_layout.clone(),
+            PinPriority::Keep,
+        )?;
         while tcat.next()? {
             if let Ok(tn) = tcat.get_string(TABLE_NAME_FIELD) {
                 if tn == tblname {
                     let size = tcat.get_i32("slotsize").unwrap().try_into().unwrap();
-                    return Ok(size);
+                    let version = tcat.get_i32("version").unwrap();
+                    return Ok((size, version));
                 }
             }
         }
         Err(MetadataError::TableNotFound(tblname.into()))
     }
 
-    pub fn layout(&self, tblname: &str, tx: Rc<RefCell<Transaction>>) -> Result<Layout> {
-        let size = self.table_slotsize(tblname, tx.clone())?;
-        let mut schema = Schema::new();
-        let mut offsets = HashMap::new();
+    pub fn layout(&self, tblname: &str, tx: Arc<Mutex<Transaction>>) -> Result<Layout> {
+        let (size, version) = self.table_slotsize(tblname, tx.clone())?;
+        let num_versions = (version + 1) as usize;
+        let mut schemas = vec![Schema::new(); num_versions];
+        let mut offsets = vec![HashMap::new(); num_versions];
 
-        let mut fcat = TableScan::new(
+        let mut fcat = TableScan::new_with_priority(
             tx,
             FIELD_CATALOG_TABLE_NAME.into(),
             self.fcat_layout.clone(),
-        );
+            PinPriority::Keep,
+        )?;
         while fcat.next()? {
             if let Ok(tn) = fcat.get_string(TABLE_NAME_FIELD) {
                 if tn == tblname {
@@ -138,26 +369,44 @@ impl TableMgr {
                     let ftype = fcat.get_i32("type").unwrap();
                     let flength = fcat.get_i32("length").unwrap();
                     let foffset = fcat.get_i32("offset").unwrap();
-                    offsets.insert(fname.clone(), foffset.try_into().unwrap());
-                    schema.add_field(
+                    let v = fcat.get_i32("version").unwrap() as usize;
+                    let collation = fcat.get_i32("collation").unwrap();
+                    offsets[v].insert(fname.clone(), foffset.try_into().unwrap());
+                    schemas[v].add_field(
                         &fname,
                         ftype.try_into().unwrap(),
                         flength.try_into().unwrap(),
                     ); // TODO
+                    schemas[v].set_collation(&fname, collation.try_into().unwrap());
                 }
             }
         }
-        Ok(Layout::from_metadata(schema, offsets, size))
+        let current = Layout::from_metadata(schemas.pop().unwrap(), offsets.pop().unwrap(), size);
+        let older = schemas
+            .into_iter()
+            .zip(offsets)
+            .map(|(schema, offsets)| {
+                // NOTE: the slot of an older version ends at its last field
+                let size = offsets
+                    .iter()
+                    .map(|(f, o)| o + Layout::length_in_bytes(&schema, f).unwrap())
+                    .max()
+                    .unwrap_or(Layout::SLOT_HEADER_SIZE);
+                Layout::from_metadata(schema, offsets, size)
+            })
+            .collect();
+        Ok(current.with_older_versions(older))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::{TableMgr, FIELD_CATALOG_TABLE_NAME, TABLE_CATALOG_TABLE_NAME};
+    use crate::metadata::common::MetadataError;
     use crate::{
         metadata::table_mgr::{MAX_NAME_LENGTH, TABLE_NAME_FIELD},
         record::{
-            schema::{Schema, SqlType},
+            schema::{Collation, Schema, SqlType},
             table_scan::TableScan,
         },
         server::simple_db::SimpleDB,
@@ -171,7 +420,7 @@ mod tests {
             let db = SimpleDB::new_for_test(dir.path(), "table_mgr_test.log");
             let tx = db.new_tx();
 
-            let tm = TableMgr::new();
+            let tm = TableMgr::new(MAX_NAME_LENGTH);
             tm.init(tx.clone());
             {
                 let mut schema = Schema::new();
@@ -198,7 +447,84 @@ mod tests {
                 assert_eq!(field_iter.next(), None);
             }
 
-            tx.borrow_mut().commit().unwrap();
+            tx.lock().unwrap().commit().unwrap();
+        }
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_name_length() {
+        let dir = tempdir().unwrap();
+        {
+            let db = SimpleDB::new_for_test(dir.path(), "table_mgr_test_name_length.log");
+            let tx = db.new_tx();
+
+            let tm = TableMgr::new(MAX_NAME_LENGTH);
+            tm.init(tx.clone());
+            let longest = "t".repeat(MAX_NAME_LENGTH);
+            let mut schema = Schema::new();
+            schema.add_i32_field(&"f".repeat(MAX_NAME_LENGTH));
+            tm.create_table(&longest, schema.clone(), tx.clone())
+                .unwrap();
+            let layout = tm.layout(&longest, tx.clone()).unwrap();
+            assert!(layout.schema().has_field(&"f".repeat(MAX_NAME_LENGTH)));
+
+            let too_long = "t".repeat(MAX_NAME_LENGTH + 1);
+            assert!(matches!(
+                tm.create_table(&too_long, schema.clone(), tx.clone()),
+                Err(MetadataError::NameTooLong(n, MAX_NAME_LENGTH)) if n == too_long
+            ));
+            let mut schema = Schema::new();
+            schema.add_i32_field(&too_long);
+            assert!(matches!(
+                tm.create_table("T", schema, tx.clone()),
+                Err(MetadataError::NameTooLong(..))
+            ));
+            assert!(matches!(
+                tm.add_field(
+                    &longest,
+                    &too_long,
+                    SqlType::Integer,
+                    0,
+                    Collation::Binary,
+                    tx.clone()
+                ),
+                Err(MetadataError::NameTooLong(..))
+            ));
+
+            let old = TableMgr::new(16);
+            assert!(matches!(
+                old.create_table(&"t".repeat(17), Schema::new(), tx.clone()),
+                Err(MetadataError::NameTooLong(_, 16))
+            ));
+            tx.lock().unwrap().commit().unwrap();
+        }
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_record_too_large() {
+        let dir = tempdir().unwrap();
+        {
+            let db = SimpleDB::new_for_test(dir.path(), "table_mgr_test_too_large.log");
+            let tx = db.new_tx();
+
+            let tm = TableMgr::new(MAX_NAME_LENGTH);
+            tm.init(tx.clone());
+            // NOTE: page header, slot header, string length
+            let max_chars = (tx.lock().unwrap().block_size() - 4 - 4 - 4) / 4;
+            let mut schema = Schema::new();
+            schema.add_string_field("B", max_chars + 1);
+            assert!(matches!(
+                tm.create_table("TooLarge", schema, tx.clone()),
+                Err(MetadataError::RecordTooLarge { max, .. }) if max == 4 + max_chars * 4
+            ));
+            assert!(tm.layout("TooLarge", tx.clone()).is_err());
+
+            let mut schema = Schema::new();
+            schema.add_string_field("B", max_chars);
+            tm.create_table("JustFits", schema, tx.clone()).unwrap();
+            tx.lock().unwrap().commit().unwrap();
         }
         dir.close().unwrap();
     }
@@ -210,7 +536,7 @@ mod tests {
             let db = SimpleDB::new_for_test(dir.path(), "table_mgr_test_catalog.log");
             let tx = db.new_tx();
             {
-                let tm = TableMgr::new();
+                let tm = TableMgr::new(MAX_NAME_LENGTH);
                 tm.init(tx.clone());
                 let tcat_layout = tm.layout(TABLE_CATALOG_TABLE_NAME, tx.clone()).unwrap();
                 let fcat_layout = tm.layout(FIELD_CATALOG_TABLE_NAME, tx.clone()).unwrap();
@@ -219,7 +545,8 @@ mod tests {
                         tx.clone(),
                         TABLE_CATALOG_TABLE_NAME.into(),
                         tcat_layout.clone(),
-                    );
+                    )
+                    .unwrap();
                     assert_eq!(ts.next().unwrap(), true);
                     assert_eq!(
                         ts.get_string(TABLE_NAME_FIELD).unwrap(),
@@ -245,7 +572,8 @@ mod tests {
                         tx.clone(),
                         FIELD_CATALOG_TABLE_NAME.into(),
                         fcat_layout.clone(),
-                    );
+                    )
+                    .unwrap();
 
                     // NOTE: table catalog's fields
                     assert_eq!(ts.next().unwrap(), true);
@@ -272,6 +600,14 @@ mod tests {
                         ts.get_i32("offset").unwrap() as usize,
                         tcat_layout.field_offset("slotsize").unwrap()
                     );
+                    assert_eq!(ts.next().unwrap(), true);
+                    assert_eq!(
+                        ts.get_string(TABLE_NAME_FIELD).unwrap(),
+                        TABLE_CATALOG_TABLE_NAME
+                    );
+                    assert_eq!(ts.get_string("fldname").unwrap(), "version");
+                    assert_eq!(ts.get_i32("type").unwrap(), SqlType::Integer.into());
+                    assert_eq!(ts.get_i32("version").unwrap(), 0);
 
                     // NOTE: field catalog's fields
                     assert_eq!(ts.next().unwrap(), true);
@@ -334,11 +670,30 @@ mod tests {
                         ts.get_i32("offset").unwrap() as usize,
                         fcat_layout.field_offset("offset").unwrap()
                     );
+                    assert_eq!(ts.next().unwrap(), true);
+                    assert_eq!(
+                        ts.get_string(TABLE_NAME_FIELD).unwrap(),
+                        FIELD_CATALOG_TABLE_NAME
+                    );
+                    assert_eq!(ts.get_string("fldname").unwrap(), "version");
+                    assert_eq!(ts.get_i32("type").unwrap(), SqlType::Integer.into());
+                    assert_eq!(
+                        ts.get_i32("offset").unwrap() as usize,
+                        fcat_layout.field_offset("version").unwrap()
+                    );
+                    assert_eq!(ts.next().unwrap(), true);
+                    assert_eq!(
+                        ts.get_string(TABLE_NAME_FIELD).unwrap(),
+                        FIELD_CATALOG_TABLE_NAME
+                    );
+                    assert_eq!(ts.get_string("fldname").unwrap(), "collation");
+                    assert_eq!(ts.get_i32("type").unwrap(), SqlType::Integer.into());
+                    assert_eq!(ts.get_i32("collation").unwrap(), 0);
 
                     assert_eq!(ts.next().unwrap(), false);
                 }
             }
-            tx.borrow_mut().commit().unwrap();
+            tx.lock().unwrap().commit().unwrap();
         }
         dir.close().unwrap();
     }
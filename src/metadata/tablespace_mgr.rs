@@ -0,0 +1,282 @@
+// Copyright (c) 2023 Sho Kuroda <krdlab@gmail.com>
+//
+// This software is released under the MIT License.
+// https://opensource.org/licenses/MIT
+
+use super::common::{MetadataError, Result};
+use super::table_mgr::TableMgr;
+use crate::{
+    query::scan::Scan,
+    record::{
+        schema::{Layout, Schema},
+        table_scan::TableScan,
+    },
+    tx::transaction::Transaction,
+};
+use std::{
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+pub struct TablespaceMgr {
+    tm: Arc<TableMgr>,
+    tscat_layout: Layout,
+    tsfcat_layout: Layout,
+}
+
+pub(super) const TABLESPACE_CATALOG_TABLE_NAME: &str = "tscat";
+pub(super) const TABLESPACE_FILE_CATALOG_TABLE_NAME: &str = "tsfilecat";
+const MAX_PATH_LENGTH: usize = 100; // CAVEAT: The DB BLOCK_SIZE must be sufficiently larger than four times this value.
+
+impl TablespaceMgr {
+    pub fn new(tm: Arc<TableMgr>) -> Self {
+        let max_name_length = tm.max_name_length();
+        Self {
+            tm,
+            tscat_layout: Layout::new(Self::tscat_schema(max_name_length)),
+            tsfcat_layout: Layout::new(Self::tsfcat_schema(max_name_length)),
+        }
+    }
+
+    fn tscat_schema(max_name_length: usize) -> Schema {
+        let mut schema = Schema::new();
+        schema.add_string_field("tsname", max_name_length);
+        schema.add_string_field("path", MAX_PATH_LENGTH);
+        schema
+    }
+
+    fn tsfcat_schema(max_name_length: usize) -> Schema {
+        // NOTE: e.g. "{index name}leaf.tbl", "{index name}{bucket}.tbl"
+        let max_filename_length = max_name_length + 8;
+        let mut schema = Schema::new();
+        schema.add_string_field("filename", max_filename_length);
+        schema.add_string_field("tsname", max_name_length);
+        schema
+    }
+
+    pub(super) fn catalog_schemas(max_name_length: usize) -> Vec<(&'static str, Schema)> {
+        vec![
+            (
+                TABLESPACE_CATALOG_TABLE_NAME,
+                Self::tscat_schema(max_name_length),
+            ),
+            (
+                TABLESPACE_FILE_CATALOG_TABLE_NAME,
+                Self::tsfcat_schema(max_name_length),
+            ),
+        ]
+    }
+
+    pub fn init(&self, tx: Arc<Mutex<Transaction>>) {
+        for (tblname, schema) in Self::catalog_schemas(self.tm.max_name_length()) {
+            self.tm.create_table(tblname, schema, tx.clone()).unwrap();
+        }
+    }
+
+    pub fn create_tablespace(
+        &self,
+        tsname: &str,
+        path: &str,
+        tx: Arc<Mutex<Transaction>>,
+    ) -> Result<()> {
+        self.tm.check_name(tsname)?;
+        if self.tablespace_path(tsname, tx.clone())?.is_some() {
+            return Err(MetadataError::TablespaceAlreadyExists(tsname.into()));
+        }
+        {
+            let mut ts = TableScan::new(
+                tx.clone(),
+                TABLESPACE_CATALOG_TABLE_NAME.into(),
+                self.tscat_layout.clone(),
+            )?;
+            ts.insert()?;
+            ts.set_string("tsname", tsname.into())?;
+            ts.set_string("path", path.into())?;
+            ts.close();
+        }
+        let mut tx = tx.lock().unwrap();
+        tx.add_tablespace(tsname, Path::new(path))?;
+        tx.flush()?;
+        Ok(())
+    }
+
+    pub fn place(
+        &self,
+        filenames: &[String],
+        tsname: &str,
+        tx: Arc<Mutex<Transaction>>,
+    ) -> Result<()> {
+        self.check_exists(tsname, tx.clone())?;
+        {
+            let mut ts = TableScan::new(
+                tx.clone(),
+                TABLESPACE_FILE_CATALOG_TABLE_NAME.into(),
+                self.tsfcat_layout.clone(),
+            )?;
+            for filename in filenames {
+                ts.insert()?;
+                ts.set_string("filename", filename.clone())?;
+                ts.set_string("tsname", tsname.into())?;
+            }
+            ts.close();
+        }
+        let mut tx = tx.lock().unwrap();
+        for filename in filenames {
+            tx.place_file(filename, tsname)?;
+        }
+        tx.flush()?;
+        Ok(())
+    }
+
+    pub fn rename_file(&self, from: &str, to: &str, tx: Arc<Mutex<Transaction>>) -> Result<()> {
+        if Self::is_empty(TABLESPACE_FILE_CATALOG_TABLE_NAME, tx.clone())? {
+            return Ok(());
+        }
+        {
+            let mut ts = TableScan::new(
+                tx.clone(),
+                TABLESPACE_FILE_CATALOG_TABLE_NAME.into(),
+                self.tsfcat_layout.clone(),
+            )?;
+            while ts.next()? {
+                if ts.get_string("filename")? == from {
+                    ts.set_string("filename", to.into())?;
+                }
+            }
+            ts.close();
+        }
+        // NOTE: loaded before recovery
+        tx.lock().unwrap().flush()?;
+        Ok(())
+    }
+
+    pub fn check_exists(&self, tsname: &str, tx: Arc<Mutex<Transaction>>) -> Result<()> {
+        match self.tablespace_path(tsname, tx)? {
+            Some(_) => Ok(()),
+            None => Err(MetadataError::TablespaceNotFound(tsname.into())),
+        }
+    }
+
+    pub fn tablespace_path(
+        &self,
+        tsname: &str,
+        tx: Arc<Mutex<Transaction>>,
+    ) -> Result<Option<String>> {
+        let mut ts = TableScan::new(
+            tx,
+            TABLESPACE_CATALOG_TABLE_NAME.into(),
+            self.tscat_layout.clone(),
+        )?;
+        let mut path = None;
+        while ts.next()? {
+            if ts.get_string("tsname")? == tsname {
+                path = Some(ts.get_string("path")?);
+                break;
+            }
+        }
+        ts.close();
+        Ok(path)
+    }
+
+    pub fn load(&self, tx: Arc<Mutex<Transaction>>) -> Result<()> {
+        // NOTE: must not create the catalog, which can be before recovery
+        if Self::is_empty(TABLESPACE_CATALOG_TABLE_NAME, tx.clone())? {
+            return Ok(());
+        }
+        {
+            let mut ts = TableScan::new(
+                tx.clone(),
+                TABLESPACE_CATALOG_TABLE_NAME.into(),
+                self.tscat_layout.clone(),
+            )?;
+            while ts.next()? {
+                let tsname = ts.get_string("tsname")?;
+                let path = ts.get_string("path")?;
+                tx.lock()
+                    .unwrap()
+                    .add_tablespace(&tsname, Path::new(&path))?;
+            }
+            ts.close();
+        }
+        if Self::is_empty(TABLESPACE_FILE_CATALOG_TABLE_NAME, tx.clone())? {
+            return Ok(());
+        }
+        let mut ts = TableScan::new(
+            tx.clone(),
+            TABLESPACE_FILE_CATALOG_TABLE_NAME.into(),
+            self.tsfcat_layout.clone(),
+        )?;
+        while ts.next()? {
+            let filename = ts.get_string("filename")?;
+            let tsname = ts.get_string("tsname")?;
+            tx.lock().unwrap().place_file(&filename, &tsname)?;
+        }
+        ts.close();
+        Ok(())
+    }
+
+    fn is_empty(tblname: &str, tx: Arc<Mutex<Transaction>>) -> Result<bool> {
+        let size = tx.lock().unwrap().size(&TableScan::filename(tblname))?;
+        Ok(size == 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::server::simple_db::SimpleDB;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_tablespace() {
+        let dir = tempdir().unwrap();
+        let db_dir = dir.path().join("db");
+        let ts_dir = dir.path().join("disk2");
+        {
+            let mut db = SimpleDB::new_for_test(&db_dir, "tablespace_mgr_test.log");
+            db.init().unwrap();
+            let planner = db.planner();
+            let tx = db.new_tx();
+            let cmd = format!("create tablespace big location '{}'", ts_dir.display());
+            planner.execute_update(&cmd, tx.clone()).unwrap();
+            assert!(planner.execute_update(&cmd, tx.clone()).is_err());
+            assert!(planner
+                .execute_update("create table t (a int) tablespace nowhere", tx.clone())
+                .is_err());
+            planner
+                .execute_update("create table t (a int) tablespace big", tx.clone())
+                .unwrap();
+            for i in 0..10 {
+                let cmd = format!("insert into t (a) values ({})", i);
+                planner.execute_update(&cmd, tx.clone()).unwrap();
+            }
+            planner
+                .execute_update("create index t_a on t (a) tablespace big", tx.clone())
+                .unwrap();
+            tx.lock().unwrap().commit().unwrap();
+
+            assert!(ts_dir.join("t.tbl").exists());
+            assert!(ts_dir.join("t_aleaf.tbl").exists());
+            assert!(!db_dir.join("t.tbl").exists());
+            assert!(!db_dir.join("t_aleaf.tbl").exists());
+        }
+        {
+            let mut db = SimpleDB::new_for_test(&db_dir, "tablespace_mgr_test.log");
+            db.init().unwrap();
+            assert_eq!(db.file_mgr().path_of("t.tbl"), ts_dir.join("t.tbl"));
+            let planner = db.planner();
+            let tx = db.new_tx();
+            {
+                let plan = planner
+                    .create_query_plan("select a from t where a = 7", tx.clone())
+                    .unwrap();
+                let mut scan = plan.open(tx.clone()).unwrap();
+                assert!(scan.next().unwrap());
+                assert_eq!(scan.get_i32("a").unwrap(), 7);
+                assert!(!scan.next().unwrap());
+                scan.close();
+            }
+            tx.lock().unwrap().commit().unwrap();
+        }
+        dir.close().unwrap();
+    }
+}
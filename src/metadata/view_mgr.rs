@@ -4,18 +4,19 @@
 // https://opensource.org/licenses/MIT
 
 use super::common::{MetadataError, Result};
-use super::table_mgr::{TableMgr, MAX_NAME_LENGTH};
+use super::table_mgr::TableMgr;
 use crate::{
     record::{schema::Schema, table_scan::TableScan},
     tx::transaction::Transaction,
 };
-use std::{cell::RefCell, rc::Rc, sync::Arc};
+use std::sync::{Arc, Mutex};
 
 pub struct ViewMgr {
     tm: Arc<TableMgr>,
 }
 
-const VIEW_CATALOG_TABLE_NAME: &str = "viewcat";
+pub(super) const VIEW_CATALOG_TABLE_NAME: &str = "viewcat";
+pub(super) const VIEW_DEPENDENCY_CATALOG_TABLE_NAME: &str = "viewdepcat";
 const MAX_VIEW_DEF: usize = 100; // CAVEAT: The DB BLOCK_SIZE must be sufficiently larger than four times this value.
 
 impl ViewMgr {
@@ -23,27 +24,55 @@ impl ViewMgr {
         Self { tm }
     }
 
-    pub fn init(&self, tx: Rc<RefCell<Transaction>>) {
-        let mut schema = Schema::new();
-        schema.add_string_field("viewname", MAX_NAME_LENGTH);
-        schema.add_string_field("viewdef", MAX_VIEW_DEF);
-        self.tm
-            .create_table(VIEW_CATALOG_TABLE_NAME, schema, tx)
-            .unwrap();
+    pub fn init(&self, tx: Arc<Mutex<Transaction>>) {
+        for (tblname, schema) in Self::catalog_schemas(self.tm.max_name_length()) {
+            self.tm.create_table(tblname, schema, tx.clone()).unwrap();
+        }
     }
 
-    pub fn create_view(&self, vname: &str, vdef: &str, tx: Rc<RefCell<Transaction>>) -> Result<()> {
-        let layout = self.tm.layout(VIEW_CATALOG_TABLE_NAME, tx.clone())?;
-        let mut ts = TableScan::new(tx, VIEW_CATALOG_TABLE_NAME.into(), layout);
-        ts.insert()?;
-        ts.set_string("viewname", vname.into())?;
-        ts.set_string("viewdef", vdef.into())?;
+    pub(super) fn catalog_schemas(max_name_length: usize) -> Vec<(&'static str, Schema)> {
+        let mut vcat_schema = Schema::new();
+        vcat_schema.add_string_field("viewname", max_name_length);
+        vcat_schema.add_string_field("viewdef", MAX_VIEW_DEF);
+
+        // NOTE: view -> the tables and views it references
+        let mut vdep_schema = Schema::new();
+        vdep_schema.add_string_field("viewname", max_name_length);
+        vdep_schema.add_string_field("objname", max_name_length);
+        vec![
+            (VIEW_CATALOG_TABLE_NAME, vcat_schema),
+            (VIEW_DEPENDENCY_CATALOG_TABLE_NAME, vdep_schema),
+        ]
+    }
+
+    pub fn create_view(
+        &self,
+        vname: &str,
+        vdef: &str,
+        referenced: &[String],
+        tx: Arc<Mutex<Transaction>>,
+    ) -> Result<()> {
+        self.tm.check_name(vname)?;
+        {
+            let layout = self.tm.layout(VIEW_CATALOG_TABLE_NAME, tx.clone())?;
+            let mut ts = TableScan::new(tx.clone(), VIEW_CATALOG_TABLE_NAME.into(), layout)?;
+            ts.insert()?;
+            ts.set_string("viewname", vname.into())?;
+            ts.set_string("viewdef", vdef.into())?;
+        }
+        if let Some(mut ts) = self.open_dependency_catalog(tx)? {
+            for objname in referenced {
+                ts.insert()?;
+                ts.set_string("viewname", vname.into())?;
+                ts.set_string("objname", objname.clone())?;
+            }
+        }
         Ok(())
     }
 
-    pub fn view_def(&self, vname: &str, tx: Rc<RefCell<Transaction>>) -> Result<String> {
+    pub fn view_def(&self, vname: &str, tx: Arc<Mutex<Transaction>>) -> Result<String> {
         let layout = self.tm.layout(VIEW_CATALOG_TABLE_NAME, tx.clone())?;
-        let mut ts = TableScan::new(tx, VIEW_CATALOG_TABLE_NAME.into(), layout);
+        let mut ts = TableScan::new(tx, VIEW_CATALOG_TABLE_NAME.into(), layout)?;
         while ts.next()? {
             if let Ok(vn) = ts.get_string("viewname") {
                 if vn == vname {
@@ -53,12 +82,82 @@ impl ViewMgr {
         }
         Err(MetadataError::ViewNotFound(vname.into()))
     }
+
+    pub fn view_names(&self, tx: Arc<Mutex<Transaction>>) -> Result<Vec<String>> {
+        let layout = self.tm.layout(VIEW_CATALOG_TABLE_NAME, tx.clone())?;
+        let mut ts = TableScan::new(tx, VIEW_CATALOG_TABLE_NAME.into(), layout)?;
+        let mut names = Vec::new();
+        while ts.next()? {
+            names.push(ts.get_string("viewname")?);
+        }
+        Ok(names)
+    }
+
+    pub fn dependent_views(
+        &self,
+        objname: &str,
+        tx: Arc<Mutex<Transaction>>,
+    ) -> Result<Vec<String>> {
+        let mut views = Vec::new();
+        if let Some(mut ts) = self.open_dependency_catalog(tx)? {
+            while ts.next()? {
+                if ts.get_string("objname")? == objname {
+                    views.push(ts.get_string("viewname")?);
+                }
+            }
+        }
+        Ok(views)
+    }
+
+    pub fn drop_view(&self, vname: &str, tx: Arc<Mutex<Transaction>>) -> Result<()> {
+        {
+            let layout = self.tm.layout(VIEW_CATALOG_TABLE_NAME, tx.clone())?;
+            let mut ts = TableScan::new(tx.clone(), VIEW_CATALOG_TABLE_NAME.into(), layout)?;
+            let mut found = false;
+            while ts.next()? {
+                if ts.get_string("viewname")? == vname {
+                    ts.delete()?;
+                    found = true;
+                    break;
+                }
+            }
+            if !found {
+                return Err(MetadataError::ViewNotFound(vname.into()));
+            }
+        }
+        if let Some(mut ts) = self.open_dependency_catalog(tx)? {
+            while ts.next()? {
+                if ts.get_string("viewname")? == vname {
+                    ts.delete()?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn open_dependency_catalog(&self, tx: Arc<Mutex<Transaction>>) -> Result<Option<TableScan>> {
+        match self
+            .tm
+            .layout(VIEW_DEPENDENCY_CATALOG_TABLE_NAME, tx.clone())
+        {
+            Ok(layout) => Ok(Some(TableScan::new(
+                tx,
+                VIEW_DEPENDENCY_CATALOG_TABLE_NAME.into(),
+                layout,
+            )?)),
+            Err(MetadataError::TableNotFound(_)) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::ViewMgr;
-    use crate::{metadata::table_mgr::TableMgr, server::simple_db::SimpleDB};
+    use crate::{
+        metadata::table_mgr::{TableMgr, MAX_NAME_LENGTH},
+        server::simple_db::SimpleDB,
+    };
     use std::sync::Arc;
     use tempfile::tempdir;
 
@@ -69,24 +168,32 @@ mod tests {
             let db = SimpleDB::new_for_test(dir.path(), "view_mgr_test.log");
             let tx = db.new_tx();
             {
-                let tm = Arc::new(TableMgr::new());
+                let tm = Arc::new(TableMgr::new(MAX_NAME_LENGTH));
                 tm.init(tx.clone());
                 let vm = ViewMgr::new(tm.clone());
                 vm.init(tx.clone());
 
-                vm.create_view("FirstView", "SELECT * FROM t", tx.clone())
+                vm.create_view("FirstView", "SELECT * FROM t", &["t".into()], tx.clone())
                     .unwrap();
                 vm.create_view(
                     "MyView",
                     "SELECT qty, price, qty*price AS value FROM t",
+                    &["t".into()],
                     tx.clone(),
                 )
                 .unwrap();
 
                 let viewdef = vm.view_def("MyView", tx.clone()).unwrap();
                 assert_eq!(viewdef, "SELECT qty, price, qty*price AS value FROM t");
+
+                let mut views = vm.dependent_views("t", tx.clone()).unwrap();
+                views.sort();
+                assert_eq!(views, ["FirstView", "MyView"]);
+                vm.drop_view("FirstView", tx.clone()).unwrap();
+                assert!(vm.view_def("FirstView", tx.clone()).is_err());
+                assert_eq!(vm.dependent_views("t", tx.clone()).unwrap(), ["MyView"]);
             }
-            tx.borrow_mut().commit().unwrap();
+            tx.lock().unwrap().commit().unwrap();
         }
     }
 }
@@ -6,22 +6,106 @@
 use std::fmt::Display;
 
 use crate::{
+    index::{IndexType, KeyOrder},
+    metadata::auth::Privilege,
     query::predicate::{Constant, Predicate, Term},
     record::schema::Schema,
 };
 
+#[derive(Debug, Clone, PartialEq)]
+pub struct SelectItem {
+    term: Term,
+    name: String,
+}
+
+impl SelectItem {
+    pub fn new(term: Term, name: String) -> Self {
+        Self { term, name }
+    }
+
+    pub fn field(name: String) -> Self {
+        Self::new(Term::FieldName(name.clone()), name)
+    }
+
+    pub fn term(&self) -> &Term {
+        &self.term
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn is_field(&self) -> bool {
+        matches!(&self.term, Term::FieldName(f) if *f == self.name)
+    }
+}
+
+impl Display for SelectItem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let term = self.term.to_string();
+        if term == self.name {
+            write!(f, "{}", term)
+        } else {
+            write!(f, "{} as {}", term, self.name)
+        }
+    }
+}
+
+pub struct ExistsData {
+    query: QueryData,
+    negated: bool,
+}
+
+impl ExistsData {
+    pub fn new(query: QueryData, negated: bool) -> Self {
+        Self { query, negated }
+    }
+
+    pub fn query(&self) -> &QueryData {
+        &self.query
+    }
+
+    pub fn is_negated(&self) -> bool {
+        self.negated
+    }
+}
+
+impl Display for ExistsData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.negated {
+            write!(f, "not exists ({})", self.query)
+        } else {
+            write!(f, "exists ({})", self.query)
+        }
+    }
+}
+
 pub struct QueryData {
+    items: Vec<SelectItem>,
     fields: Vec<String>,
     tables: Vec<String>,
     pred: Predicate,
+    exists: Vec<ExistsData>,
 }
 
 impl Display for QueryData {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let cols = self.fields.join(", ");
+        let items: Vec<String> = self.items.iter().map(|i| i.to_string()).collect();
+        let cols = items.join(", ");
         let tbls = self.tables.join(", ");
+        let mut conds: Vec<String> = Vec::new();
         if !self.pred.is_empty() {
-            write!(f, "select {} from {} where {}", cols, tbls, self.pred)
+            conds.push(self.pred.to_string());
+        }
+        conds.extend(self.exists.iter().map(|e| e.to_string()));
+        if !conds.is_empty() {
+            write!(
+                f,
+                "select {} from {} where {}",
+                cols,
+                tbls,
+                conds.join(" and ")
+            )
         } else {
             write!(f, "select {} from {}", cols, tbls)
         }
@@ -29,14 +113,25 @@ impl Display for QueryData {
 }
 
 impl QueryData {
-    pub fn new(fields: Vec<String>, tables: Vec<String>, pred: Predicate) -> Self {
+    pub fn new(items: Vec<SelectItem>, tables: Vec<String>, pred: Predicate) -> Self {
+        let fields = items.iter().map(|i| i.name().to_string()).collect();
         Self {
+            items,
             fields,
             tables,
             pred,
+            exists: Vec::new(),
         }
     }
 
+    pub fn with_exists(self, exists: Vec<ExistsData>) -> Self {
+        Self { exists, ..self }
+    }
+
+    pub fn items(&self) -> &[SelectItem] {
+        &self.items
+    }
+
     pub fn fields(&self) -> &Vec<String> {
         &self.fields
     }
@@ -48,6 +143,37 @@ impl QueryData {
     pub fn pred(&self) -> &Predicate {
         &self.pred
     }
+
+    pub fn exists(&self) -> &[ExistsData] {
+        &self.exists
+    }
+
+    pub fn referenced_tables(&self) -> Vec<String> {
+        let mut tables = self.tables.clone();
+        for e in self.exists.iter() {
+            for t in e.query.referenced_tables() {
+                if !tables.contains(&t) {
+                    tables.push(t);
+                }
+            }
+        }
+        tables
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum InsertValue {
+    Constant(Constant),
+    NextVal(String),
+}
+
+impl Display for InsertValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Constant(c) => write!(f, "{}", c),
+            Self::NextVal(seqname) => write!(f, "nextval('{}')", seqname),
+        }
+    }
 }
 
 pub enum UpdateCmd {
@@ -58,7 +184,7 @@ pub enum UpdateCmd {
     InsertData {
         table_name: String,
         fields: Vec<String>,
-        values: Vec<Constant>,
+        values: Vec<InsertValue>,
     },
     ModifyData {
         table_name: String,
@@ -69,6 +195,7 @@ pub enum UpdateCmd {
     CreateTableData {
         table_name: String,
         schema: Schema,
+        tablespace: Option<String>,
     },
     CreateViewData {
         view_name: String,
@@ -78,5 +205,66 @@ pub enum UpdateCmd {
         index_name: String,
         table_name: String,
         field: String,
+        order: KeyOrder,
+        index_type: IndexType,
+        buckets: Option<usize>, // NOTE: of a hash index, if given
+        tablespace: Option<String>,
+    },
+    CreateSequenceData {
+        sequence_name: String,
+    },
+    CreateTablespaceData {
+        tablespace_name: String,
+        path: String,
+    },
+    CreateUserData {
+        user_name: String,
+        password: String,
+    },
+    AlterUserData {
+        user_name: String,
+        password: String,
+    },
+    GrantData {
+        privileges: Vec<Privilege>,
+        table_name: String,
+        user_name: String,
+    },
+    RevokeData {
+        privileges: Vec<Privilege>,
+        table_name: String,
+        user_name: String,
+    },
+    VacuumData {
+        table_name: String,
+    },
+    AnalyzeData {
+        table_name: String,
+    },
+    DropTableData {
+        table_name: String,
+        cascade: bool,
+    },
+    DropViewData {
+        view_name: String,
+        cascade: bool,
+    },
+    RenameTableData {
+        table_name: String,
+        new_name: String,
+    },
+    AddFieldData {
+        table_name: String,
+        schema: Schema, // NOTE: holds the added field only
+    },
+    DropFieldData {
+        table_name: String,
+        field: String,
+    },
+    CopyData {
+        table_name: String,
+        path: String,
+        delimiter: char,
+        header: bool,
     },
 }
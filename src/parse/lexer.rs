@@ -4,12 +4,16 @@
 // https://opensource.org/licenses/MIT
 
 use super::stream_tokenizer::{StreamTokenizer, TT};
+use crate::metadata::table_mgr::MAX_NAME_LENGTH;
 use thiserror::Error;
 
 #[derive(Debug, Error, PartialEq)]
 pub enum LexerError {
     #[error("Bad syntax")]
     BadSyntax,
+
+    #[error("name too long: {0} (at most {MAX_NAME_LENGTH} characters)")]
+    NameTooLong(String),
 }
 
 pub type Result<T> = core::result::Result<T, LexerError>;
@@ -23,11 +27,16 @@ impl<'s> Lexer<'s> {
     pub fn new(input: &'s str) -> Result<Self> {
         let keywords = vec![
             "select", "from", "where", "and", "insert", "into", "values", "delete", "update",
-            "set", "create", "table", "int", "varchar", "view", "as", "index", "on",
+            "set", "create", "table", "int", "varchar", "view", "as", "index", "on", "asc", "desc",
+            "using", "tablespace", "location", "vacuum", "alter", "add", "drop", "analyze",
+            "cascade", "restrict", "rename", "to", "copy", "collate",
+            "exists", "not", "sequence", "nextval",
         ];
 
         let mut tokenizer = StreamTokenizer::new(input);
         tokenizer.ordinary_char('.');
+        // NOTE: a sign is the unary minus of a term
+        tokenizer.ordinary_char('-');
         tokenizer.word_chars('_', '_');
         tokenizer.lower_case_mode(true);
 
@@ -93,12 +102,25 @@ impl<'s> Lexer<'s> {
     }
 
     pub fn eat_int_constant(&mut self) -> Result<i32> {
+        self.eat_int(false)
+    }
+
+    pub fn eat_negative_int_constant(&mut self) -> Result<i32> {
+        self.eat_int(true)
+    }
+
+    fn eat_int(&mut self, negative: bool) -> Result<i32> {
         if !self.match_int_constant() {
             return Err(LexerError::BadSyntax);
         }
-        let i = self.tokenizer.nval().ok_or(LexerError::BadSyntax)?;
+        let n = self.tokenizer.nval().ok_or(LexerError::BadSyntax)?;
+        let n = if negative { -n } else { n };
+        // NOTE: the tokenizer reads fractions too
+        if n.fract() != 0.0 || n < i32::MIN as f64 || n > i32::MAX as f64 {
+            return Err(LexerError::BadSyntax);
+        }
         self.next_token()?;
-        Ok(i.round() as i32) // ! FIXME
+        Ok(n as i32)
     }
 
     pub fn eat_string_constant(&mut self) -> Result<String> {
@@ -123,6 +145,9 @@ impl<'s> Lexer<'s> {
             return Err(LexerError::BadSyntax);
         }
         let s: String = self.tokenizer.sval().ok_or(LexerError::BadSyntax)?.into();
+        if s.chars().count() > MAX_NAME_LENGTH {
+            return Err(LexerError::NameTooLong(s));
+        }
         self.next_token()?;
         Ok(s)
     }
@@ -135,7 +160,7 @@ impl<'s> Lexer<'s> {
 
 #[cfg(test)]
 mod tests {
-    use super::Lexer;
+    use super::{Lexer, LexerError, MAX_NAME_LENGTH};
 
     #[test]
     fn test_query() {
@@ -211,4 +236,37 @@ mod tests {
         assert!(l.match_delim(')'));
         l.eat_delim(')').unwrap();
     }
+
+    #[test]
+    fn test_int_constant() {
+        let mut l = Lexer::new("5 -5 - 2147483648 -2147483649").unwrap();
+        assert_eq!(l.eat_int_constant().unwrap(), 5);
+        l.eat_delim('-').unwrap();
+        assert_eq!(l.eat_negative_int_constant().unwrap(), -5);
+        l.eat_delim('-').unwrap();
+        assert_eq!(l.eat_negative_int_constant().unwrap(), i32::MIN);
+        l.eat_delim('-').unwrap();
+        assert_eq!(l.eat_negative_int_constant(), Err(LexerError::BadSyntax));
+        assert_eq!(l.eat_int_constant(), Err(LexerError::BadSyntax));
+
+        for input in ["2147483648", "1.5"] {
+            let mut l = Lexer::new(input).unwrap();
+            assert_eq!(l.eat_int_constant(), Err(LexerError::BadSyntax));
+        }
+    }
+
+    #[test]
+    fn test_long_name() {
+        let longest = "n".repeat(MAX_NAME_LENGTH);
+        let input = format!("select {} from {}n", longest, longest);
+        let mut l = Lexer::new(&input).unwrap();
+
+        l.eat_keyword("select").unwrap();
+        assert_eq!(l.eat_id().unwrap(), longest);
+        l.eat_keyword("from").unwrap();
+        assert_eq!(
+            l.eat_id(),
+            Err(LexerError::NameTooLong(format!("{}n", longest)))
+        );
+    }
 }
@@ -4,13 +4,15 @@
 // https://opensource.org/licenses/MIT
 
 use crate::{
+    index::{IndexType, KeyOrder},
+    metadata::auth::Privilege,
     query::predicate::{Constant, Expression, Predicate, Term},
-    record::schema::Schema,
+    record::schema::{Collation, Schema},
 };
 
 use super::{
-    data::{QueryData, UpdateCmd},
-    lexer::{Lexer, Result},
+    data::{ExistsData, InsertValue, QueryData, SelectItem, UpdateCmd},
+    lexer::{Lexer, LexerError, Result},
 };
 
 struct PredParser<'s> {
@@ -50,6 +52,10 @@ impl<'s> PredParser<'s> {
         self.term()?;
         self.lex.eat_delim('=')?;
         self.term()?;
+        if self.lex.match_keyword("collate") {
+            self.lex.eat_keyword("collate")?;
+            self.lex.eat_id()?;
+        }
         Ok(())
     }
 
@@ -81,12 +87,38 @@ impl<'s> Parser<'s> {
     pub fn constant(&mut self) -> Result<Constant> {
         if self.lex.match_string_constant() {
             Ok(Constant::String(self.lex.eat_string_constant()?))
+        } else if self.lex.match_delim('-') {
+            self.lex.eat_delim('-')?;
+            Ok(Constant::Int(self.lex.eat_negative_int_constant()?))
         } else {
             Ok(Constant::Int(self.lex.eat_int_constant()?))
         }
     }
 
     pub fn term(&mut self) -> Result<Term> {
+        let first = self.operand()?;
+        if !self.lex.match_delim('|') {
+            return Ok(first);
+        }
+        let mut terms = vec![first];
+        while self.lex.match_delim('|') {
+            self.lex.eat_delim('|')?;
+            self.lex.eat_delim('|')?;
+            terms.push(self.operand()?);
+        }
+        Ok(Term::Concat(terms))
+    }
+
+    fn operand(&mut self) -> Result<Term> {
+        if self.lex.match_delim('-') {
+            self.lex.eat_delim('-')?;
+            if self.lex.match_int_constant() {
+                return Ok(Term::Constant(Constant::Int(
+                    self.lex.eat_negative_int_constant()?,
+                )));
+            }
+            return Ok(Term::Negate(Box::new(self.operand()?)).folded());
+        }
         if self.lex.match_id() {
             Ok(Term::FieldName(self.field()?))
         } else {
@@ -98,7 +130,22 @@ impl<'s> Parser<'s> {
         let lhs = self.term()?;
         self.lex.eat_delim('=')?;
         let rhs = self.term()?;
-        Ok(Expression::new(lhs, rhs))
+        let expr = Expression::new(lhs, rhs);
+        match self.collate_clause()? {
+            Some(collation) => Ok(expr.with_collation(collation)),
+            None => Ok(expr),
+        }
+    }
+
+    fn collate_clause(&mut self) -> Result<Option<Collation>> {
+        if !self.lex.match_keyword("collate") {
+            return Ok(None);
+        }
+        self.lex.eat_keyword("collate")?;
+        match Collation::parse(&self.lex.eat_id()?) {
+            Some(collation) => Ok(Some(collation)),
+            None => Err(LexerError::BadSyntax),
+        }
     }
 
     pub fn predicate(&mut self) -> Result<Predicate> {
@@ -116,17 +163,40 @@ impl<'s> Parser<'s> {
         self.lex.eat_keyword("from")?;
         let tables = self.table_list()?;
         let mut pred = Predicate::empty();
+        let mut exists = Vec::new();
 
         if self.lex.match_keyword("where") {
             self.lex.eat_keyword("where")?;
-            pred = self.predicate()?;
+            loop {
+                if self.lex.match_keyword("exists") || self.lex.match_keyword("not") {
+                    exists.push(self.exists()?);
+                } else {
+                    pred.conjoin_with(Predicate::new(self.expression()?));
+                }
+                if !self.lex.match_keyword("and") {
+                    break;
+                }
+                self.lex.eat_keyword("and")?;
+            }
         }
-        Ok(QueryData::new(fields, tables, pred))
+        Ok(QueryData::new(fields, tables, pred).with_exists(exists))
     }
 
-    pub fn select_list(&mut self) -> Result<Vec<String>> {
-        let mut l: Vec<String> = Vec::new();
-        l.push(self.field()?);
+    fn exists(&mut self) -> Result<ExistsData> {
+        let negated = self.lex.match_keyword("not");
+        if negated {
+            self.lex.eat_keyword("not")?;
+        }
+        self.lex.eat_keyword("exists")?;
+        self.lex.eat_delim('(')?;
+        let query = self.query()?;
+        self.lex.eat_delim(')')?;
+        Ok(ExistsData::new(query, negated))
+    }
+
+    pub fn select_list(&mut self) -> Result<Vec<SelectItem>> {
+        let mut l: Vec<SelectItem> = Vec::new();
+        l.push(self.select_item()?);
         if self.lex.match_delim(',') {
             self.lex.eat_delim(',')?;
             l.extend(self.select_list()?);
@@ -134,6 +204,22 @@ impl<'s> Parser<'s> {
         Ok(l)
     }
 
+    fn select_item(&mut self) -> Result<SelectItem> {
+        let term = self.term()?;
+        if self.lex.match_keyword("as") {
+            self.lex.eat_keyword("as")?;
+            let name = self.field()?;
+            return Ok(SelectItem::new(term, name));
+        }
+        match term {
+            Term::FieldName(name) => Ok(SelectItem::field(name)),
+            term => {
+                let name = term.to_string();
+                Ok(SelectItem::new(term, name))
+            }
+        }
+    }
+
     pub fn table_list(&mut self) -> Result<Vec<String>> {
         let mut l: Vec<String> = Vec::new();
         l.push(self.lex.eat_id()?);
@@ -151,6 +237,22 @@ impl<'s> Parser<'s> {
             self.delete()
         } else if self.lex.match_keyword("update") {
             self.modify()
+        } else if self.lex.match_keyword("vacuum") {
+            self.vacuum()
+        } else if self.lex.match_keyword("analyze") {
+            self.analyze()
+        } else if self.lex.match_keyword("alter") {
+            self.alter()
+        } else if self.lex.match_keyword("drop") {
+            self.drop()
+        } else if self.lex.match_keyword("rename") {
+            self.rename_table()
+        } else if self.lex.match_keyword("copy") {
+            self.copy()
+        } else if self.lex.match_keyword("grant") {
+            self.grant()
+        } else if self.lex.match_keyword("revoke") {
+            self.revoke()
         } else {
             self.create()
         }
@@ -162,6 +264,12 @@ impl<'s> Parser<'s> {
             self.create_table()
         } else if self.lex.match_keyword("view") {
             self.create_view()
+        } else if self.lex.match_keyword("tablespace") {
+            self.create_tablespace()
+        } else if self.lex.match_keyword("sequence") {
+            self.create_sequence()
+        } else if self.lex.match_keyword("user") {
+            self.create_user()
         } else {
             self.create_index()
         }
@@ -188,7 +296,7 @@ impl<'s> Parser<'s> {
         self.lex.eat_delim(')')?;
         self.lex.eat_keyword("values")?;
         self.lex.eat_delim('(')?;
-        let values = self.constant_list()?;
+        let values = self.insert_value_list()?;
         self.lex.eat_delim(')')?;
         Ok(UpdateCmd::InsertData {
             table_name,
@@ -197,6 +305,129 @@ impl<'s> Parser<'s> {
         })
     }
 
+    pub fn vacuum(&mut self) -> Result<UpdateCmd> {
+        self.lex.eat_keyword("vacuum")?;
+        let table_name = self.lex.eat_id()?;
+        Ok(UpdateCmd::VacuumData { table_name })
+    }
+
+    pub fn analyze(&mut self) -> Result<UpdateCmd> {
+        self.lex.eat_keyword("analyze")?;
+        if self.lex.match_keyword("table") {
+            self.lex.eat_keyword("table")?;
+        }
+        let table_name = self.lex.eat_id()?;
+        Ok(UpdateCmd::AnalyzeData { table_name })
+    }
+
+    pub fn drop(&mut self) -> Result<UpdateCmd> {
+        self.lex.eat_keyword("drop")?;
+        if self.lex.match_keyword("table") {
+            self.lex.eat_keyword("table")?;
+            let table_name = self.lex.eat_id()?;
+            let cascade = self.drop_behavior()?;
+            Ok(UpdateCmd::DropTableData {
+                table_name,
+                cascade,
+            })
+        } else {
+            self.lex.eat_keyword("view")?;
+            let view_name = self.lex.eat_id()?;
+            let cascade = self.drop_behavior()?;
+            Ok(UpdateCmd::DropViewData { view_name, cascade })
+        }
+    }
+
+    fn drop_behavior(&mut self) -> Result<bool> {
+        if self.lex.match_keyword("cascade") {
+            self.lex.eat_keyword("cascade")?;
+            Ok(true)
+        } else {
+            if self.lex.match_keyword("restrict") {
+                self.lex.eat_keyword("restrict")?;
+            }
+            Ok(false)
+        }
+    }
+
+    pub fn rename_table(&mut self) -> Result<UpdateCmd> {
+        self.lex.eat_keyword("rename")?;
+        self.lex.eat_keyword("table")?;
+        let table_name = self.lex.eat_id()?;
+        self.lex.eat_keyword("to")?;
+        let new_name = self.lex.eat_id()?;
+        Ok(UpdateCmd::RenameTableData {
+            table_name,
+            new_name,
+        })
+    }
+
+    fn alter(&mut self) -> Result<UpdateCmd> {
+        self.lex.eat_keyword("alter")?;
+        if self.lex.match_keyword("user") {
+            self.alter_user()
+        } else {
+            self.alter_table()
+        }
+    }
+
+    pub fn alter_table(&mut self) -> Result<UpdateCmd> {
+        self.lex.eat_keyword("table")?;
+        let table_name = self.lex.eat_id()?;
+        if self.lex.match_keyword("drop") {
+            self.lex.eat_keyword("drop")?;
+            let field = self.field()?;
+            Ok(UpdateCmd::DropFieldData { table_name, field })
+        } else {
+            self.lex.eat_keyword("add")?;
+            let schema = self.field_def()?;
+            Ok(UpdateCmd::AddFieldData { table_name, schema })
+        }
+    }
+
+    pub fn copy(&mut self) -> Result<UpdateCmd> {
+        self.lex.eat_keyword("copy")?;
+        let table_name = self.lex.eat_id()?;
+        self.lex.eat_keyword("from")?;
+        let path = self.lex.eat_string_constant()?;
+        let mut delimiter = ',';
+        let mut header = false;
+        if self.lex.match_delim('(') {
+            self.lex.eat_delim('(')?;
+            loop {
+                match self.lex.eat_id()?.as_str() {
+                    "delimiter" => {
+                        let s = self.lex.eat_string_constant()?;
+                        let mut chars = s.chars();
+                        delimiter = match (chars.next(), chars.next()) {
+                            (Some(c), None) => c,
+                            _ => return Err(LexerError::BadSyntax),
+                        };
+                    }
+                    "header" => {
+                        header = match self.lex.eat_id()?.as_str() {
+                            "true" => true,
+                            "false" => false,
+                            _ => return Err(LexerError::BadSyntax),
+                        };
+                    }
+                    _ => return Err(LexerError::BadSyntax),
+                }
+                if !self.lex.match_delim(',') {
+                    break;
+                }
+                self.lex.eat_delim(',')?;
+            }
+            self.lex.eat_delim(')')?;
+        }
+        Ok(UpdateCmd::CopyData {
+            table_name,
+            path,
+            delimiter,
+            header,
+        })
+    }
+
     fn field_list(&mut self) -> Result<Vec<String>> {
         let mut l = Vec::new();
         l.push(self.field()?);
@@ -207,16 +438,28 @@ impl<'s> Parser<'s> {
         Ok(l)
     }
 
-    fn constant_list(&mut self) -> Result<Vec<Constant>> {
+    fn insert_value_list(&mut self) -> Result<Vec<InsertValue>> {
         let mut l = Vec::new();
-        l.push(self.constant()?);
+        l.push(self.insert_value()?);
         if self.lex.match_delim(',') {
             self.lex.eat_delim(',')?;
-            l.extend(self.constant_list()?);
+            l.extend(self.insert_value_list()?);
         }
         Ok(l)
     }
 
+    fn insert_value(&mut self) -> Result<InsertValue> {
+        if self.lex.match_keyword("nextval") {
+            self.lex.eat_keyword("nextval")?;
+            self.lex.eat_delim('(')?;
+            let sequence_name = self.lex.eat_string_constant()?;
+            self.lex.eat_delim(')')?;
+            Ok(InsertValue::NextVal(sequence_name))
+        } else {
+            Ok(InsertValue::Constant(self.constant()?))
+        }
+    }
+
     pub fn modify(&mut self) -> Result<UpdateCmd> {
         self.lex.eat_keyword("update")?;
         let table_name = self.lex.eat_id()?;
@@ -243,7 +486,21 @@ impl<'s> Parser<'s> {
         self.lex.eat_delim('(')?;
         let schema = self.field_defs()?;
         self.lex.eat_delim(')')?;
-        Ok(UpdateCmd::CreateTableData { table_name, schema })
+        let tablespace = self.tablespace_clause()?;
+        Ok(UpdateCmd::CreateTableData {
+            table_name,
+            schema,
+            tablespace,
+        })
+    }
+
+    fn tablespace_clause(&mut self) -> Result<Option<String>> {
+        if self.lex.match_keyword("tablespace") {
+            self.lex.eat_keyword("tablespace")?;
+            Ok(Some(self.lex.eat_id()?))
+        } else {
+            Ok(None)
+        }
     }
 
     fn field_defs(&mut self) -> Result<Schema> {
@@ -272,6 +529,9 @@ impl<'s> Parser<'s> {
             let len = self.lex.eat_int_constant()?;
             self.lex.eat_delim(')')?;
             schema.add_string_field(&name, len.try_into().unwrap());
+            if let Some(collation) = self.collate_clause()? {
+                schema.set_collation(&name, collation);
+            }
         }
         Ok(schema)
     }
@@ -291,22 +551,161 @@ impl<'s> Parser<'s> {
         let table_name = self.lex.eat_id()?;
         self.lex.eat_delim('(')?;
         let field = self.lex.eat_id()?;
+        let order = if self.lex.match_keyword("desc") {
+            self.lex.eat_keyword("desc")?;
+            KeyOrder::Descending
+        } else {
+            if self.lex.match_keyword("asc") {
+                self.lex.eat_keyword("asc")?;
+            }
+            KeyOrder::Ascending
+        };
         self.lex.eat_delim(')')?;
+        let index_type = if self.lex.match_keyword("using") {
+            self.lex.eat_keyword("using")?;
+            match self.lex.eat_id()?.as_str() {
+                "btree" => IndexType::BTree,
+                "hash" => IndexType::Hash,
+                _ => return Err(LexerError::BadSyntax),
+            }
+        } else {
+            IndexType::BTree
+        };
+        // NOTE: e.g. `using hash(16)`
+        let buckets = if index_type == IndexType::Hash && self.lex.match_delim('(') {
+            self.lex.eat_delim('(')?;
+            let buckets = self.lex.eat_int_constant()?;
+            self.lex.eat_delim(')')?;
+            if buckets <= 0 {
+                return Err(LexerError::BadSyntax);
+            }
+            Some(buckets.try_into().unwrap())
+        } else {
+            None
+        };
+        if index_type == IndexType::Hash && order == KeyOrder::Descending {
+            return Err(LexerError::BadSyntax);
+        }
+        let tablespace = self.tablespace_clause()?;
         Ok(UpdateCmd::CreateIndexData {
             index_name,
             table_name,
             field,
+            order,
+            index_type,
+            buckets,
+            tablespace,
+        })
+    }
+
+    pub fn create_tablespace(&mut self) -> Result<UpdateCmd> {
+        self.lex.eat_keyword("tablespace")?;
+        let tablespace_name = self.lex.eat_id()?;
+        self.lex.eat_keyword("location")?;
+        let path = self.lex.eat_string_constant()?;
+        Ok(UpdateCmd::CreateTablespaceData {
+            tablespace_name,
+            path,
         })
     }
+
+    pub fn create_sequence(&mut self) -> Result<UpdateCmd> {
+        self.lex.eat_keyword("sequence")?;
+        let sequence_name = self.lex.eat_id()?;
+        Ok(UpdateCmd::CreateSequenceData { sequence_name })
+    }
+
+    // NOTE: "user" and "password" are not reserved
+    pub fn create_user(&mut self) -> Result<UpdateCmd> {
+        self.lex.eat_keyword("user")?;
+        let user_name = self.lex.eat_id()?;
+        let password = self.password()?;
+        Ok(UpdateCmd::CreateUserData {
+            user_name,
+            password,
+        })
+    }
+
+    pub fn alter_user(&mut self) -> Result<UpdateCmd> {
+        self.lex.eat_keyword("user")?;
+        let user_name = self.lex.eat_id()?;
+        let password = self.password()?;
+        Ok(UpdateCmd::AlterUserData {
+            user_name,
+            password,
+        })
+    }
+
+    // NOTE: nor "grant", "revoke" and "all"
+    pub fn grant(&mut self) -> Result<UpdateCmd> {
+        self.lex.eat_keyword("grant")?;
+        let privileges = self.privilege_list()?;
+        self.lex.eat_keyword("on")?;
+        let table_name = self.lex.eat_id()?;
+        self.lex.eat_keyword("to")?;
+        let user_name = self.lex.eat_id()?;
+        Ok(UpdateCmd::GrantData {
+            privileges,
+            table_name,
+            user_name,
+        })
+    }
+
+    pub fn revoke(&mut self) -> Result<UpdateCmd> {
+        self.lex.eat_keyword("revoke")?;
+        let privileges = self.privilege_list()?;
+        self.lex.eat_keyword("on")?;
+        let table_name = self.lex.eat_id()?;
+        self.lex.eat_keyword("from")?;
+        let user_name = self.lex.eat_id()?;
+        Ok(UpdateCmd::RevokeData {
+            privileges,
+            table_name,
+            user_name,
+        })
+    }
+
+    fn privilege_list(&mut self) -> Result<Vec<Privilege>> {
+        if self.lex.match_keyword("all") {
+            self.lex.eat_keyword("all")?;
+            return Ok(Privilege::ALL.to_vec());
+        }
+        let mut privileges = Vec::new();
+        loop {
+            let p = Privilege::ALL
+                .into_iter()
+                .find(|p| self.lex.match_keyword(p.name()))
+                .ok_or(LexerError::BadSyntax)?;
+            self.lex.eat_keyword(p.name())?;
+            privileges.push(p);
+            if !self.lex.match_delim(',') {
+                return Ok(privileges);
+            }
+            self.lex.eat_delim(',')?;
+        }
+    }
+
+    fn password(&mut self) -> Result<String> {
+        if self.lex.match_keyword("with") {
+            self.lex.eat_keyword("with")?;
+        }
+        self.lex.eat_keyword("password")?;
+        self.lex.eat_string_constant()
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::{Parser, PredParser};
     use crate::{
-        parse::{data::UpdateCmd, lexer::LexerError},
+        index::{IndexType, KeyOrder},
+        metadata::auth::Privilege,
+        parse::{
+            data::{InsertValue, UpdateCmd},
+            lexer::LexerError,
+        },
         query::predicate::{Constant, Expression, Predicate, Term},
-        record::schema::SqlType,
+        record::schema::{Collation, SqlType},
     };
 
     #[test]
@@ -336,6 +735,90 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parser_when_select_concat() {
+        let input = "select a || '-' || b as c, a, a as d, b || 1 from t where a || b = 'x1'";
+        let mut p = Parser::new(input).unwrap();
+        let query = p.query().unwrap();
+        assert_eq!(*query.fields(), vec!["c", "a", "d", "b || 1"]);
+        let concat = Term::Concat(vec![
+            Term::FieldName("a".into()),
+            Term::Constant(Constant::String("-".into())),
+            Term::FieldName("b".into()),
+        ]);
+        assert_eq!(*query.items()[0].term(), concat);
+        assert!(query.items()[1].is_field());
+        assert!(!query.items()[2].is_field());
+        assert_eq!(
+            *query.pred(),
+            Predicate::new(Expression::new(
+                Term::Concat(vec![
+                    Term::FieldName("a".into()),
+                    Term::FieldName("b".into())
+                ]),
+                Term::Constant(Constant::String("x1".into()))
+            ))
+        );
+        assert_eq!(query.to_string(), input);
+
+        let mut p = Parser::new("select a || from t").unwrap();
+        assert_eq!(p.query().err().unwrap(), LexerError::BadSyntax);
+    }
+
+    #[test]
+    fn test_parser_when_negative() {
+        let input =
+            "select -a as b, -5 as c from t where a = -5 and -a = b and - - a = - 2147483648";
+        let mut p = Parser::new(input).unwrap();
+        let query = p.query().unwrap();
+        let negate = |f: &str| Term::Negate(Box::new(Term::FieldName(f.into())));
+        assert_eq!(*query.items()[0].term(), negate("a"));
+        assert_eq!(*query.items()[1].term(), Term::Constant(Constant::Int(-5)));
+        let mut pred = Predicate::new(Expression::new(
+            Term::FieldName("a".into()),
+            Term::Constant(Constant::Int(-5)),
+        ));
+        pred.conjoin_with(Predicate::new(Expression::new(
+            negate("a"),
+            Term::FieldName("b".into()),
+        )));
+        pred.conjoin_with(Predicate::new(Expression::new(
+            Term::FieldName("a".into()),
+            Term::Constant(Constant::Int(i32::MIN)),
+        )));
+        assert_eq!(*query.pred(), pred);
+        assert_eq!(
+            query.to_string(),
+            "select -a as b, -5 as c from t where a = -5 and -a = b and a = -2147483648"
+        );
+
+        let mut p = Parser::new("insert into t(a, b) values (-1, - 2)").unwrap();
+        let UpdateCmd::InsertData { values, .. } = p.update_cmd().unwrap() else {
+            panic!("not an insert");
+        };
+        assert_eq!(
+            values,
+            vec![
+                InsertValue::Constant(Constant::Int(-1)),
+                InsertValue::Constant(Constant::Int(-2))
+            ]
+        );
+
+        for input in [
+            "insert into t(a) values (-'x')",
+            "create table t(a varchar(-1))",
+            "select a from t where a = -",
+        ] {
+            let mut p = Parser::new(input).unwrap();
+            let result = if input.starts_with("select") {
+                p.query().map(|_| ())
+            } else {
+                p.update_cmd().map(|_| ())
+            };
+            assert_eq!(result, Err(LexerError::BadSyntax), "{}", input);
+        }
+    }
+
     #[test]
     fn test_parser_when_insert() {
         let mut p = Parser::new("insert into users (id, name) values (1, 'krdlab')").unwrap();
@@ -349,13 +832,93 @@ mod tests {
             assert_eq!(fields, vec!["id", "name"]);
             assert_eq!(
                 values,
-                vec![Constant::Int(1), Constant::String("krdlab".into())]
+                vec![
+                    InsertValue::Constant(Constant::Int(1)),
+                    InsertValue::Constant(Constant::String("krdlab".into()))
+                ]
             );
         } else {
             assert!(false);
         }
     }
 
+    #[test]
+    fn test_parser_when_user() {
+        let mut p = Parser::new("create user krdlab with password 'it\\'s secret'").unwrap();
+        assert!(matches!(
+            p.update_cmd().unwrap(),
+            UpdateCmd::CreateUserData { user_name, password }
+                if user_name == "krdlab" && password == "it's secret"
+        ));
+
+        let mut p = Parser::new("alter user krdlab password 'changed'").unwrap();
+        assert!(matches!(
+            p.update_cmd().unwrap(),
+            UpdateCmd::AlterUserData { user_name, password }
+                if user_name == "krdlab" && password == "changed"
+        ));
+
+        let mut p = Parser::new("create table user(password varchar(8))").unwrap();
+        assert!(matches!(
+            p.update_cmd().unwrap(),
+            UpdateCmd::CreateTableData { table_name, .. } if table_name == "user"
+        ));
+
+        let mut p = Parser::new("create user krdlab").unwrap();
+        assert!(matches!(p.update_cmd(), Err(LexerError::BadSyntax)));
+    }
+
+    #[test]
+    fn test_parser_when_grant() {
+        let mut p = Parser::new("grant select, insert on users to krdlab").unwrap();
+        assert!(matches!(
+            p.update_cmd().unwrap(),
+            UpdateCmd::GrantData { privileges, table_name, user_name }
+                if privileges == [Privilege::Select, Privilege::Insert]
+                    && table_name == "users"
+                    && user_name == "krdlab"
+        ));
+
+        let mut p = Parser::new("revoke all on users from krdlab").unwrap();
+        assert!(matches!(
+            p.update_cmd().unwrap(),
+            UpdateCmd::RevokeData { privileges, .. } if privileges == Privilege::ALL
+        ));
+
+        let mut p = Parser::new("grant drop on users to krdlab").unwrap();
+        assert!(matches!(p.update_cmd(), Err(LexerError::BadSyntax)));
+        let mut p = Parser::new("revoke select on users to krdlab").unwrap();
+        assert!(matches!(p.update_cmd(), Err(LexerError::BadSyntax)));
+    }
+
+    #[test]
+    fn test_parser_when_sequence() {
+        let mut p = Parser::new("create sequence user_ids").unwrap();
+        assert!(matches!(
+            p.update_cmd().unwrap(),
+            UpdateCmd::CreateSequenceData { sequence_name } if sequence_name == "user_ids"
+        ));
+
+        let mut p =
+            Parser::new("insert into users (id, name) values (nextval('user_ids'), 'krdlab')")
+                .unwrap();
+        if let UpdateCmd::InsertData { values, .. } = p.update_cmd().unwrap() {
+            assert_eq!(
+                values,
+                vec![
+                    InsertValue::NextVal("user_ids".into()),
+                    InsertValue::Constant(Constant::String("krdlab".into()))
+                ]
+            );
+            assert_eq!(values[0].to_string(), "nextval('user_ids')");
+        } else {
+            assert!(false);
+        }
+
+        let mut p = Parser::new("insert into users (id) values (nextval(user_ids))").unwrap();
+        assert_eq!(p.update_cmd().err().unwrap(), LexerError::BadSyntax);
+    }
+
     #[test]
     fn test_parser_when_delete() {
         let mut p = Parser::new("delete from users where id = 1").unwrap();
@@ -404,7 +967,12 @@ mod tests {
     #[test]
     fn test_parser_when_create_table() {
         let mut p = Parser::new("create table users (id int, name varchar(32))").unwrap();
-        if let UpdateCmd::CreateTableData { table_name, schema } = p.update_cmd().unwrap() {
+        if let UpdateCmd::CreateTableData {
+            table_name,
+            schema,
+            tablespace,
+        } = p.update_cmd().unwrap()
+        {
             assert_eq!(table_name, "users");
 
             let mut f_iter = schema.fields_iter();
@@ -416,11 +984,47 @@ mod tests {
             assert_eq!(schema.field_length("name").unwrap(), 32);
 
             assert!(f_iter.next().is_none());
+            assert_eq!(tablespace, None);
         } else {
             assert!(false);
         }
     }
 
+    #[test]
+    fn test_parser_when_collate() {
+        let mut p =
+            Parser::new("create table T (A varchar(8) collate nocase, B varchar(8))").unwrap();
+        if let UpdateCmd::CreateTableData { schema, .. } = p.update_cmd().unwrap() {
+            assert_eq!(schema.field_collation("a"), Some(Collation::NoCase));
+            assert_eq!(schema.field_collation("b"), Some(Collation::Binary));
+        } else {
+            assert!(false);
+        }
+
+        let mut p = Parser::new("select A from T where A = 'x' collate nocase").unwrap();
+        let q = p.query().unwrap();
+        assert_eq!(q.pred().to_string(), "a = 'x' collate nocase");
+
+        let mut p = Parser::new("create table T (A varchar(8) collate rtrim)").unwrap();
+        assert_eq!(p.update_cmd().err().unwrap(), LexerError::BadSyntax);
+    }
+
+    #[test]
+    fn test_parser_when_exists() {
+        let query = "select a from t where b = 1 and not exists (select c from u where c = a and exists (select d from v where d = c))";
+        let mut p = Parser::new(query).unwrap();
+        let q = p.query().unwrap();
+        assert_eq!(q.pred().to_string(), "b = 1");
+        assert_eq!(q.exists().len(), 1);
+        assert!(q.exists()[0].is_negated());
+        assert_eq!(q.exists()[0].query().tables(), &vec!["u".to_string()]);
+        assert_eq!(q.exists()[0].query().exists().len(), 1);
+        assert_eq!(q.to_string(), query);
+
+        let mut p = Parser::new("select a from t where exists select c from u").unwrap();
+        assert_eq!(p.query().err().unwrap(), LexerError::BadSyntax);
+    }
+
     #[test]
     fn test_parser_when_create_view() {
         let mut p = Parser::new("create view test as select name from users").unwrap();
@@ -441,13 +1045,226 @@ mod tests {
             index_name,
             table_name,
             field,
+            order,
+            index_type,
+            ..
         } = p.update_cmd().unwrap()
         {
             assert_eq!(index_name, "name_idx");
             assert_eq!(table_name, "users");
             assert_eq!(field, "name");
+            assert_eq!(order, KeyOrder::Ascending);
+            assert_eq!(index_type, IndexType::BTree);
+        } else {
+            assert!(false);
+        }
+    }
+
+    #[test]
+    fn test_parser_when_create_index_with_order() {
+        let mut p = Parser::new("create index age_idx on users (age desc)").unwrap();
+        if let UpdateCmd::CreateIndexData { field, order, .. } = p.update_cmd().unwrap() {
+            assert_eq!(field, "age");
+            assert_eq!(order, KeyOrder::Descending);
+        } else {
+            assert!(false);
+        }
+
+        let mut p = Parser::new("create index age_idx on users (age asc)").unwrap();
+        if let UpdateCmd::CreateIndexData { order, .. } = p.update_cmd().unwrap() {
+            assert_eq!(order, KeyOrder::Ascending);
+        } else {
+            assert!(false);
+        }
+    }
+
+    #[test]
+    fn test_parser_when_create_index_using() {
+        let mut p = Parser::new("create index name_idx on users (name) using hash").unwrap();
+        if let UpdateCmd::CreateIndexData {
+            index_type,
+            buckets,
+            ..
+        } = p.update_cmd().unwrap()
+        {
+            assert_eq!(index_type, IndexType::Hash);
+            assert_eq!(buckets, None);
         } else {
             assert!(false);
         }
+
+        let mut p = Parser::new("create index name_idx on users (name) using hash(16)").unwrap();
+        if let UpdateCmd::CreateIndexData { buckets, .. } = p.update_cmd().unwrap() {
+            assert_eq!(buckets, Some(16));
+        } else {
+            assert!(false);
+        }
+
+        let mut p = Parser::new("create index name_idx on users (name) using hash(0)").unwrap();
+        assert!(matches!(p.update_cmd(), Err(LexerError::BadSyntax)));
+
+        let mut p = Parser::new("create index age_idx on users (age desc) using btree").unwrap();
+        if let UpdateCmd::CreateIndexData {
+            order, index_type, ..
+        } = p.update_cmd().unwrap()
+        {
+            assert_eq!(order, KeyOrder::Descending);
+            assert_eq!(index_type, IndexType::BTree);
+        } else {
+            assert!(false);
+        }
+
+        let mut p = Parser::new("create index name_idx on users (name) using bitmap").unwrap();
+        assert!(matches!(p.update_cmd(), Err(LexerError::BadSyntax)));
+
+        let mut p = Parser::new("create index age_idx on users (age desc) using hash").unwrap();
+        assert!(matches!(p.update_cmd(), Err(LexerError::BadSyntax)));
+    }
+
+    #[test]
+    fn test_parser_when_vacuum() {
+        let mut p = Parser::new("vacuum users").unwrap();
+        if let UpdateCmd::VacuumData { table_name } = p.update_cmd().unwrap() {
+            assert_eq!(table_name, "users");
+        } else {
+            assert!(false);
+        }
+    }
+
+    #[test]
+    fn test_parser_when_analyze() {
+        let mut p = Parser::new("analyze users").unwrap();
+        if let UpdateCmd::AnalyzeData { table_name } = p.update_cmd().unwrap() {
+            assert_eq!(table_name, "users");
+        } else {
+            assert!(false);
+        }
+
+        let mut p = Parser::new("analyze table users").unwrap();
+        if let UpdateCmd::AnalyzeData { table_name } = p.update_cmd().unwrap() {
+            assert_eq!(table_name, "users");
+        } else {
+            assert!(false);
+        }
+    }
+
+    #[test]
+    fn test_parser_when_drop() {
+        let mut p = Parser::new("drop table users").unwrap();
+        assert!(matches!(
+            p.update_cmd().unwrap(),
+            UpdateCmd::DropTableData { table_name, cascade: false } if table_name == "users"
+        ));
+
+        let mut p = Parser::new("drop table users cascade").unwrap();
+        assert!(matches!(
+            p.update_cmd().unwrap(),
+            UpdateCmd::DropTableData { table_name, cascade: true } if table_name == "users"
+        ));
+
+        let mut p = Parser::new("drop view adults restrict").unwrap();
+        assert!(matches!(
+            p.update_cmd().unwrap(),
+            UpdateCmd::DropViewData { view_name, cascade: false } if view_name == "adults"
+        ));
+
+        let mut p = Parser::new("drop index age_idx").unwrap();
+        assert!(matches!(p.update_cmd(), Err(LexerError::BadSyntax)));
+    }
+
+    #[test]
+    fn test_parser_when_rename_table() {
+        let mut p = Parser::new("rename table users to members").unwrap();
+        assert!(matches!(
+            p.update_cmd().unwrap(),
+            UpdateCmd::RenameTableData { table_name, new_name }
+                if table_name == "users" && new_name == "members"
+        ));
+
+        let mut p = Parser::new("rename table users members").unwrap();
+        assert!(matches!(p.update_cmd(), Err(LexerError::BadSyntax)));
+    }
+
+    #[test]
+    fn test_parser_when_alter_table() {
+        let mut p = Parser::new("alter table users add nickname varchar(8)").unwrap();
+        if let UpdateCmd::AddFieldData { table_name, schema } = p.update_cmd().unwrap() {
+            assert_eq!(table_name, "users");
+            assert_eq!(schema.field_name(0).unwrap(), "nickname");
+            assert_eq!(schema.field_type("nickname"), Some(SqlType::VarChar));
+            assert_eq!(schema.field_length("nickname"), Some(8));
+        } else {
+            assert!(false);
+        }
+
+        let mut p = Parser::new("alter table users drop age").unwrap();
+        if let UpdateCmd::DropFieldData { table_name, field } = p.update_cmd().unwrap() {
+            assert_eq!(table_name, "users");
+            assert_eq!(field, "age");
+        } else {
+            assert!(false);
+        }
+
+        let mut p = Parser::new("alter table users rename age").unwrap();
+        assert!(matches!(p.update_cmd(), Err(LexerError::BadSyntax)));
+    }
+
+    #[test]
+    fn test_parser_when_tablespace() {
+        let mut p = Parser::new("create tablespace fast location '/mnt/SSD'").unwrap();
+        if let UpdateCmd::CreateTablespaceData {
+            tablespace_name,
+            path,
+        } = p.update_cmd().unwrap()
+        {
+            assert_eq!(tablespace_name, "fast");
+            assert_eq!(path, "/mnt/SSD");
+        } else {
+            assert!(false);
+        }
+
+        let mut p = Parser::new("create table logs (id int) tablespace fast").unwrap();
+        if let UpdateCmd::CreateTableData { tablespace, .. } = p.update_cmd().unwrap() {
+            assert_eq!(tablespace, Some("fast".to_string()));
+        } else {
+            assert!(false);
+        }
+
+        let mut p =
+            Parser::new("create index id_idx on logs (id) using hash tablespace fast").unwrap();
+        if let UpdateCmd::CreateIndexData {
+            index_type,
+            tablespace,
+            ..
+        } = p.update_cmd().unwrap()
+        {
+            assert_eq!(index_type, IndexType::Hash);
+            assert_eq!(tablespace, Some("fast".to_string()));
+        } else {
+            assert!(false);
+        }
+    }
+
+    #[test]
+    fn test_parser_when_copy() {
+        let mut p = Parser::new("copy users from 'users.csv'").unwrap();
+        assert!(matches!(
+            p.update_cmd().unwrap(),
+            UpdateCmd::CopyData { table_name, path, delimiter: ',', header: false }
+                if table_name == "users" && path == "users.csv"
+        ));
+
+        let mut p =
+            Parser::new("copy users from '/tmp/users.tsv' (header true, delimiter '|')").unwrap();
+        assert!(matches!(
+            p.update_cmd().unwrap(),
+            UpdateCmd::CopyData { path, delimiter: '|', header: true, .. } if path == "/tmp/users.tsv"
+        ));
+
+        let mut p = Parser::new("copy users from 'users.csv' (delimiter ';;')").unwrap();
+        assert!(matches!(p.update_cmd(), Err(LexerError::BadSyntax)));
+
+        let mut p = Parser::new("copy users from 'users.csv' (header yes)").unwrap();
+        assert!(matches!(p.update_cmd(), Err(LexerError::BadSyntax)));
     }
 }
@@ -0,0 +1,442 @@
+// Copyright (c) 2023 Sho Kuroda <krdlab@gmail.com>
+//
+// This software is released under the MIT License.
+// https://opensource.org/licenses/MIT
+
+use crate::{
+    index::{Index, IndexError},
+    metadata::{common::MetadataError, index_mgr::IndexInfo, metadata_mgr::MetadataMgr},
+    query::{
+        predicate::Constant,
+        scan::{Scan, ScanError},
+    },
+    record::{
+        schema::{Schema, SqlType},
+        table_scan::TableScan,
+    },
+    tx::transaction::Transaction,
+};
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader},
+    mem,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum CopyError {
+    #[error("{0}")]
+    Io(#[from] io::Error),
+
+    #[error("line {0}: {1}")]
+    InvalidRow(usize, String),
+
+    #[error("field not found: {0}")]
+    FieldNotFound(String),
+
+    #[error("invalid delimiter: {0:?}")]
+    InvalidDelimiter(char),
+
+    #[error("{0}")]
+    Metadata(#[from] MetadataError),
+
+    #[error("{0}")]
+    Scan(#[from] ScanError),
+
+    #[error("{0}")]
+    Index(#[from] IndexError),
+}
+
+pub type Result<T> = core::result::Result<T, CopyError>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CopyOptions {
+    pub delimiter: char,
+    pub header: bool,
+    pub batch_size: usize,
+}
+
+impl Default for CopyOptions {
+    fn default() -> Self {
+        Self {
+            delimiter: ',',
+            header: false,
+            batch_size: 1000,
+        }
+    }
+}
+
+pub struct CsvReader<R> {
+    reader: R,
+    delimiter: char,
+    lines_read: usize,
+    line: usize,
+}
+
+impl<R: BufRead> CsvReader<R> {
+    pub fn new(reader: R, delimiter: char) -> Self {
+        Self {
+            reader,
+            delimiter,
+            lines_read: 0,
+            line: 0,
+        }
+    }
+
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    pub fn read_row(&mut self) -> Result<Option<Vec<String>>> {
+        let mut text = String::new();
+        loop {
+            text.clear();
+            if self.reader.read_line(&mut text)? == 0 {
+                return Ok(None);
+            }
+            self.lines_read += 1;
+            if !text.trim_end_matches(['\r', '\n']).is_empty() {
+                break;
+            }
+        }
+        self.line = self.lines_read;
+
+        let mut values = Vec::new();
+        let mut value = String::new();
+        let mut quoted = false;
+        loop {
+            let mut chars = text.chars().peekable();
+            while let Some(c) = chars.next() {
+                if quoted {
+                    if c != '"' {
+                        value.push(c);
+                    } else if chars.peek() == Some(&'"') {
+                        chars.next();
+                        value.push('"');
+                    } else {
+                        quoted = false;
+                    }
+                } else if c == '"' {
+                    quoted = true;
+                } else if c == self.delimiter {
+                    values.push(mem::take(&mut value));
+                } else if c == '\n' || (c == '\r' && matches!(chars.peek(), Some('\n') | None)) {
+                    break;
+                } else {
+                    value.push(c);
+                }
+            }
+            if !quoted {
+                break;
+            }
+            // NOTE: a quoted value goes on in the next line
+            text.clear();
+            if self.reader.read_line(&mut text)? == 0 {
+                return Err(CopyError::InvalidRow(
+                    self.line,
+                    "unterminated quoted value".into(),
+                ));
+            }
+            self.lines_read += 1;
+        }
+        values.push(value);
+        Ok(Some(values))
+    }
+}
+
+pub struct CsvRows<R> {
+    csv: CsvReader<R>,
+    fields: Vec<String>,
+    schema: Schema,
+}
+
+impl<R: BufRead> CsvRows<R> {
+    pub fn new(reader: R, schema: &Schema, options: &CopyOptions) -> Result<Self> {
+        if matches!(options.delimiter, '"' | '\r' | '\n') {
+            return Err(CopyError::InvalidDelimiter(options.delimiter));
+        }
+        let mut csv = CsvReader::new(reader, options.delimiter);
+        let fields: Vec<String> = if options.header {
+            let names = csv.read_row()?.unwrap_or_default();
+            names.iter().map(|n| n.trim().to_lowercase()).collect()
+        } else {
+            schema.fields_iter().cloned().collect()
+        };
+        if let Some(field) = fields.iter().find(|f| !schema.has_field(f)) {
+            return Err(CopyError::FieldNotFound(field.clone()));
+        }
+        Ok(Self {
+            csv,
+            fields,
+            schema: schema.clone(),
+        })
+    }
+
+    pub fn fields(&self) -> &[String] {
+        &self.fields
+    }
+
+    pub fn next_row(&mut self) -> Result<Option<Vec<Constant>>> {
+        let Some(row) = self.csv.read_row()? else {
+            return Ok(None);
+        };
+        let line = self.csv.line();
+        if row.len() != self.fields.len() {
+            return Err(CopyError::InvalidRow(
+                line,
+                format!("{} values for {} fields", row.len(), self.fields.len()),
+            ));
+        }
+        let mut values = Vec::with_capacity(row.len());
+        for (field, value) in self.fields.iter().zip(row) {
+            match self.schema.field_type(field).unwrap() {
+                SqlType::Integer => match value.trim().parse() {
+                    Ok(n) => values.push(Constant::Int(n)),
+                    Err(_) => {
+                        return Err(CopyError::InvalidRow(
+                            line,
+                            format!("invalid integer for {}: {:?}", field, value),
+                        ))
+                    }
+                },
+                SqlType::VarChar => {
+                    let length = self.schema.field_length(field).unwrap();
+                    if value.chars().count() > length {
+                        return Err(CopyError::InvalidRow(
+                            line,
+                            format!("too long for {} (at most {} characters)", field, length),
+                        ));
+                    }
+                    values.push(Constant::String(value));
+                }
+            }
+        }
+        Ok(Some(values))
+    }
+}
+
+const LOAD_BATCH_ROWS: usize = 256;
+
+pub struct BulkLoader {
+    mdm: Arc<MetadataMgr>,
+    table_name: String,
+    tx: Arc<Mutex<Transaction>>,
+    schema: Schema,
+    scan: TableScan,
+    indexes: Vec<(String, IndexInfo, Box<dyn Index>)>,
+    count: usize,
+    num_blocks: usize,
+}
+
+impl BulkLoader {
+    pub fn new(
+        mdm: Arc<MetadataMgr>,
+        table_name: &str,
+        tx: Arc<Mutex<Transaction>>,
+    ) -> Result<Self> {
+        let layout = mdm.table_layout(table_name, tx.clone())?;
+        let schema = layout.schema().clone();
+        let scan = TableScan::new(tx.clone(), table_name.into(), layout)?;
+        let mut indexes = Vec::new();
+        for (field, ii) in mdm.table_index_info(table_name, tx.clone())? {
+            let idx = ii.open(tx.clone())?;
+            indexes.push((field, ii, idx));
+        }
+        Ok(Self {
+            mdm,
+            table_name: table_name.into(),
+            tx,
+            schema,
+            scan,
+            indexes,
+            count: 0,
+            num_blocks: 0,
+        })
+    }
+
+    pub fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    pub fn insert_rows(&mut self, fields: &[String], rows: Vec<Vec<Constant>>) -> Result<()> {
+        let rows = rows
+            .into_iter()
+            .map(|values| {
+                let mut row = self
+                    .schema
+                    .fields_iter()
+                    .map(|f| match self.schema.field_type(f).unwrap() {
+                        SqlType::Integer => Constant::Int(0),
+                        SqlType::VarChar => Constant::String(String::new()),
+                    })
+                    .collect::<Vec<_>>();
+                for (field, val) in fields.iter().zip(values) {
+                    let pos = self.schema.fields_iter().position(|f| f == field);
+                    row[pos.ok_or_else(|| CopyError::FieldNotFound(field.clone()))?] = val;
+                }
+                Ok(row)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let rids = self.scan.insert_rows(rows.iter().cloned())?;
+        for (field, _, idx) in self.indexes.iter_mut() {
+            let pos = self.schema.fields_iter().position(|f| f == field).unwrap();
+            for (row, rid) in rows.iter().zip(&rids) {
+                idx.insert(&row[pos], rid)?;
+            }
+        }
+        self.count += rids.len();
+        for rid in rids.iter() {
+            self.num_blocks = self.num_blocks.max(rid.block_number() as usize + 1);
+        }
+        Ok(())
+    }
+
+    pub fn load<R: BufRead>(&mut self, rows: &mut CsvRows<R>, limit: usize) -> Result<usize> {
+        let mut n = 0;
+        let mut batch = Vec::new();
+        while n < limit {
+            let Some(values) = rows.next_row()? else {
+                break;
+            };
+            batch.push(values);
+            n += 1;
+            if batch.len() == LOAD_BATCH_ROWS {
+                self.insert_rows(rows.fields(), mem::take(&mut batch))?;
+            }
+        }
+        self.insert_rows(rows.fields(), batch)?;
+        Ok(n)
+    }
+
+    pub fn finish(mut self) -> Result<u64> {
+        self.scan.close();
+        for (_, ii, idx) in self.indexes.iter_mut() {
+            idx.close();
+            if self.count > 0 {
+                self.mdm.refresh_index_stats(ii, self.tx.clone())?;
+            }
+        }
+        if self.count > 0 {
+            self.mdm.record_inserts(
+                &self.table_name,
+                self.num_blocks,
+                self.count,
+                self.tx.clone(),
+            )?;
+        }
+        Ok(self.count as u64)
+    }
+}
+
+pub fn copy_file(
+    mdm: Arc<MetadataMgr>,
+    table_name: &str,
+    path: &Path,
+    options: &CopyOptions,
+    tx: Arc<Mutex<Transaction>>,
+) -> Result<u64> {
+    let file = BufReader::new(File::open(path)?);
+    let mut loader = BulkLoader::new(mdm, table_name, tx)?;
+    let mut rows = CsvRows::new(file, loader.schema(), options)?;
+    loader.load(&mut rows, usize::MAX)?;
+    loader.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CopyError, CsvReader};
+    use crate::{plan::planner::PlannerError, server::simple_db::SimpleDB};
+    use serde::Deserialize;
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_csv_reader() {
+        let input = "1,joe,\"a, b\"\r\n\n2,\"say \"\"hi\"\"\",\"two\nlines\"\n3;x";
+        let mut csv = CsvReader::new(input.as_bytes(), ',');
+        assert_eq!(csv.read_row().unwrap().unwrap(), vec!["1", "joe", "a, b"]);
+        assert_eq!(csv.line(), 1);
+        assert_eq!(
+            csv.read_row().unwrap().unwrap(),
+            vec!["2", "say \"hi\"", "two\nlines"]
+        );
+        assert_eq!(csv.line(), 3);
+        assert_eq!(csv.read_row().unwrap().unwrap(), vec!["3;x"]);
+        assert_eq!(csv.line(), 5);
+        assert!(csv.read_row().unwrap().is_none());
+
+        let mut csv = CsvReader::new("1;\"x\n".as_bytes(), ';');
+        assert!(matches!(
+            csv.read_row(),
+            Err(CopyError::InvalidRow(1, message)) if message == "unterminated quoted value"
+        ));
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Row {
+        a: i32,
+        b: String,
+    }
+
+    #[test]
+    fn test_copy() {
+        let dir = tempdir().unwrap();
+        let mut db = SimpleDB::new_for_test(dir.path(), "copy_test.log");
+        db.init().unwrap();
+        let planner = db.planner();
+        let tx = db.new_tx();
+        planner
+            .execute_update("create table T(A int, B varchar(5))", tx.clone())
+            .unwrap();
+        planner
+            .execute_update("create index a_idx on T(A)", tx.clone())
+            .unwrap();
+
+        let csv = dir.path().join("t.csv");
+        let rows = (1..=300).map(|a| format!("\"b{}\";{}\n", a % 7, a));
+        fs::write(&csv, format!("B;A\n{}", rows.collect::<String>())).unwrap();
+        let sql = format!(
+            "copy T from '{}' (delimiter ';', header true)",
+            csv.display()
+        );
+        assert_eq!(planner.execute_update(&sql, tx.clone()).unwrap(), 300);
+        let rows: Vec<Row> = db
+            .query_as("select A, B from T where A = 150", tx.clone())
+            .unwrap();
+        assert_eq!(
+            rows,
+            vec![Row {
+                a: 150,
+                b: "b3".into()
+            }]
+        );
+        let mdm = db.metadata_mgr();
+        let layout = mdm.table_layout("t", tx.clone()).unwrap();
+        let stats = mdm.table_stat_info("t", layout, tx.clone()).unwrap();
+        assert_eq!(stats.records_output(), 300);
+        tx.lock().unwrap().commit().unwrap();
+
+        let tx = db.new_tx();
+        fs::write(&csv, "301,x\n302,\"too long\"\n").unwrap();
+        let sql = format!("copy T from '{}'", csv.display());
+        let err = planner.execute_update(&sql, tx.clone()).unwrap_err();
+        assert!(matches!(
+            err,
+            PlannerError::Copy(CopyError::InvalidRow(2, ref message))
+                if message == "too long for b (at most 5 characters)"
+        ));
+        fs::write(&csv, "C\n1\n").unwrap();
+        let sql = format!("copy T from '{}' (header true)", csv.display());
+        let err = planner.execute_update(&sql, tx.clone()).unwrap_err();
+        assert!(matches!(err, PlannerError::Copy(CopyError::FieldNotFound(f)) if f == "c"));
+        tx.lock().unwrap().rollback().unwrap();
+
+        let tx = db.new_tx();
+        let rows: Vec<Row> = db.query_as("select A, B from T", tx.clone()).unwrap();
+        assert_eq!(rows.len(), 300);
+        assert_eq!(rows.iter().map(|r| r.a).max(), Some(300));
+        tx.lock().unwrap().commit().unwrap();
+    }
+}
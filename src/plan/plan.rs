@@ -4,11 +4,11 @@
 // https://opensource.org/licenses/MIT
 
 use crate::{
-    metadata::{metadata_mgr::MetadataMgr, stat_mgr::StatInfo},
+    metadata::{common::MetadataError, metadata_mgr::MetadataMgr, stat_mgr::StatInfo},
     query::{
-        operators::{ProductScan, ProjectScan, SelectScan},
-        predicate::Predicate,
-        scan::UpdateScan,
+        operators::{EmptyScan, ExtendScan, ProductScan, ProjectScan, SelectScan, SemiJoinScan},
+        predicate::{Predicate, Term},
+        scan::{Result, UpdateScan},
     },
     record::{
         schema::{Layout, Schema},
@@ -16,18 +16,39 @@ use crate::{
     },
     tx::transaction::Transaction,
 };
-use std::{cell::RefCell, cmp::min, rc::Rc, sync::Arc};
-
-pub trait Plan {
-    fn open<'lm, 'bm, 'scan>(
-        &self,
-        tx: Rc<RefCell<Transaction<'lm, 'bm>>>,
-    ) -> Box<dyn UpdateScan + 'scan>
-    where
-        'lm: 'scan,
-        'bm: 'scan;
-    fn blocks_accessed(&self) -> usize;
-    fn records_output(&self) -> usize;
+use std::{
+    cmp::min,
+    fmt::Display,
+    sync::{Arc, Mutex},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PlanCost {
+    pub blocks: usize,
+    pub records: usize,
+    pub cpu: usize,
+    pub buffers: usize,
+}
+
+impl Display for PlanCost {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "blocks={} records={} cpu={} buffers={}",
+            self.blocks, self.records, self.cpu, self.buffers
+        )
+    }
+}
+
+pub trait Plan: Send {
+    fn open(&self, tx: Arc<Mutex<Transaction>>) -> Result<Box<dyn UpdateScan>>;
+    fn cost(&self) -> PlanCost;
+    fn blocks_accessed(&self) -> usize {
+        self.cost().blocks
+    }
+    fn records_output(&self) -> usize {
+        self.cost().records
+    }
     fn distinct_values(&self, field_name: &str) -> usize;
     fn schema(&self) -> Schema;
 }
@@ -38,44 +59,39 @@ pub struct TablePlan {
     stat_info: StatInfo,
 }
 
-impl<'lm, 'bm> TablePlan {
+impl TablePlan {
     pub fn new(
-        tx: Rc<RefCell<Transaction<'lm, 'bm>>>,
+        tx: Arc<Mutex<Transaction>>,
         table_name: &str,
         meta_mgr: Arc<MetadataMgr>,
-    ) -> Self {
-        let layout = meta_mgr.table_layout(table_name, tx.clone()).unwrap(); // FIXME:
-        let stat_info = meta_mgr.table_stat_info(table_name, layout.clone(), tx.clone());
-        Self {
+    ) -> core::result::Result<Self, MetadataError> {
+        let layout = meta_mgr.table_layout(table_name, tx.clone())?;
+        let stat_info = meta_mgr.table_stat_info(table_name, layout.clone(), tx.clone())?;
+        Ok(Self {
             table_name: table_name.into(),
             layout,
             stat_info,
-        }
+        })
     }
 }
 
 impl Plan for TablePlan {
-    fn open<'lm, 'bm, 'scan>(
-        &self,
-        tx: Rc<RefCell<Transaction<'lm, 'bm>>>,
-    ) -> Box<dyn UpdateScan + 'scan>
-    where
-        'lm: 'scan,
-        'bm: 'scan,
-    {
-        Box::new(TableScan::new(
+    fn open(&self, tx: Arc<Mutex<Transaction>>) -> Result<Box<dyn UpdateScan>> {
+        Ok(Box::new(TableScan::new(
             tx.clone(),
             self.table_name.clone(),
             self.layout.clone(),
-        ))
+        )?))
     }
 
-    fn blocks_accessed(&self) -> usize {
-        self.stat_info.blocks_accessed()
-    }
-
-    fn records_output(&self) -> usize {
-        self.stat_info.records_output()
+    fn cost(&self) -> PlanCost {
+        let records = self.stat_info.records_output();
+        PlanCost {
+            blocks: self.stat_info.blocks_accessed(),
+            records,
+            cpu: records,
+            buffers: 1,
+        }
     }
 
     fn distinct_values(&self, field_name: &str) -> usize {
@@ -87,37 +103,31 @@ impl Plan for TablePlan {
     }
 }
 
-pub struct SelectPlan<'p> {
-    plan: Box<dyn Plan + 'p>,
+pub struct SelectPlan {
+    plan: Box<dyn Plan>,
     pred: Predicate,
 }
 
-impl<'p> SelectPlan<'p> {
-    pub fn new(plan: Box<dyn Plan + 'p>, pred: Predicate) -> Self {
+impl SelectPlan {
+    pub fn new(plan: Box<dyn Plan>, pred: Predicate) -> Self {
+        let pred = pred.with_collations_of(&plan.schema());
         Self { plan, pred }
     }
 }
 
-impl<'p> Plan for SelectPlan<'p> {
-    fn open<'lm, 'bm, 'scan>(
-        &self,
-        tx: Rc<RefCell<Transaction<'lm, 'bm>>>,
-    ) -> Box<dyn UpdateScan + 'scan>
-    where
-        'lm: 'scan,
-        'bm: 'scan,
-    {
-        let s = self.plan.open(tx);
-        Box::new(SelectScan::new(s, self.pred.clone()))
+impl Plan for SelectPlan {
+    fn open(&self, tx: Arc<Mutex<Transaction>>) -> Result<Box<dyn UpdateScan>> {
+        let s = self.plan.open(tx)?;
+        Ok(Box::new(SelectScan::new(s, self.pred.clone())))
     }
 
-    fn blocks_accessed(&self) -> usize {
-        self.plan.blocks_accessed()
-    }
-
-    fn records_output(&self) -> usize {
-        let reduction_factor = self.pred.reduction_factor(&self.plan);
-        self.plan.records_output() / reduction_factor
+    fn cost(&self) -> PlanCost {
+        let cost = self.plan.cost();
+        PlanCost {
+            records: cost.records / self.pred.reduction_factor(self.plan.as_ref()),
+            cpu: cost.cpu + cost.records,
+            ..cost
+        }
     }
 
     fn distinct_values(&self, field_name: &str) -> usize {
@@ -138,13 +148,13 @@ impl<'p> Plan for SelectPlan<'p> {
     }
 }
 
-pub struct ProjectPlan<'p> {
-    plan: Box<dyn Plan + 'p>,
+pub struct ProjectPlan {
+    plan: Box<dyn Plan>,
     schema: Schema,
 }
 
-impl<'p> ProjectPlan<'p> {
-    pub fn new(plan: Box<dyn Plan + 'p>, fields: Vec<&str>) -> Self {
+impl ProjectPlan {
+    pub fn new(plan: Box<dyn Plan>, fields: Vec<&str>) -> Self {
         let ps = plan.schema();
         let mut schema = Schema::new();
         for f in fields {
@@ -154,30 +164,68 @@ impl<'p> ProjectPlan<'p> {
     }
 }
 
-impl<'p> Plan for ProjectPlan<'p> {
-    fn open<'lm, 'bm, 'scan>(
-        &self,
-        tx: Rc<RefCell<Transaction<'lm, 'bm>>>,
-    ) -> Box<dyn UpdateScan + 'scan>
-    where
-        'lm: 'scan,
-        'bm: 'scan,
-    {
-        let scan = self.plan.open(tx);
+impl Plan for ProjectPlan {
+    fn open(&self, tx: Arc<Mutex<Transaction>>) -> Result<Box<dyn UpdateScan>> {
+        let scan = self.plan.open(tx)?;
         let fields: Vec<String> = self.schema.fields_iter().map(|f| f.into()).collect();
-        Box::new(ProjectScan::new(scan, fields))
+        Ok(Box::new(ProjectScan::new(scan, fields)))
     }
 
-    fn blocks_accessed(&self) -> usize {
-        self.plan.blocks_accessed()
+    fn cost(&self) -> PlanCost {
+        self.plan.cost()
     }
 
-    fn records_output(&self) -> usize {
-        self.plan.records_output()
+    fn distinct_values(&self, field_name: &str) -> usize {
+        self.plan.distinct_values(field_name)
+    }
+
+    fn schema(&self) -> Schema {
+        self.schema.clone()
+    }
+}
+
+pub struct ExtendPlan {
+    plan: Box<dyn Plan>,
+    fields: Vec<(String, Term)>,
+    schema: Schema,
+}
+
+impl ExtendPlan {
+    pub fn new(plan: Box<dyn Plan>, fields: Vec<(String, Term)>) -> Self {
+        let mut schema = plan.schema();
+        for (f, t) in fields.iter() {
+            let (ftype, flength) = t.field_type(&schema).unwrap(); // TODO
+            schema.add_field(f, ftype, flength);
+        }
+        Self {
+            plan,
+            fields,
+            schema,
+        }
+    }
+}
+
+impl Plan for ExtendPlan {
+    fn open(&self, tx: Arc<Mutex<Transaction>>) -> Result<Box<dyn UpdateScan>> {
+        let scan = self.plan.open(tx)?;
+        Ok(Box::new(ExtendScan::new(scan, self.fields.clone())))
+    }
+
+    fn cost(&self) -> PlanCost {
+        let cost = self.plan.cost();
+        PlanCost {
+            cpu: cost.cpu + cost.records * self.fields.len(),
+            ..cost
+        }
     }
 
     fn distinct_values(&self, field_name: &str) -> usize {
-        self.plan.distinct_values(field_name)
+        match self.fields.iter().find(|(f, _)| f == field_name) {
+            Some((_, Term::FieldName(f))) => self.plan.distinct_values(f),
+            Some((_, Term::Constant(_))) => 1,
+            Some(_) => self.plan.records_output(),
+            None => self.plan.distinct_values(field_name),
+        }
     }
 
     fn schema(&self) -> Schema {
@@ -185,14 +233,95 @@ impl<'p> Plan for ProjectPlan<'p> {
     }
 }
 
-pub struct ProductPlan<'p> {
-    plan1: Box<dyn Plan + 'p>,
-    plan2: Box<dyn Plan + 'p>,
+pub struct SemiJoinPlan {
+    outer: Box<dyn Plan>,
+    inner: Box<dyn Plan>,
+    pred: Predicate,
+    negated: bool,
+}
+
+impl SemiJoinPlan {
+    pub fn new(outer: Box<dyn Plan>, inner: Box<dyn Plan>, pred: Predicate, negated: bool) -> Self {
+        let mut schema = outer.schema();
+        schema.add_all(&inner.schema());
+        let pred = pred.with_collations_of(&schema);
+        Self {
+            outer,
+            inner,
+            pred,
+            negated,
+        }
+    }
+}
+
+impl Plan for SemiJoinPlan {
+    fn open(&self, tx: Arc<Mutex<Transaction>>) -> Result<Box<dyn UpdateScan>> {
+        let outer = self.outer.open(tx.clone())?;
+        let inner = self.inner.open(tx)?;
+        Ok(Box::new(SemiJoinScan::new(
+            outer,
+            inner,
+            self.pred.clone(),
+            self.negated,
+        )))
+    }
+
+    // NOTE: the worst case
+    fn cost(&self) -> PlanCost {
+        let (outer, inner) = (self.outer.cost(), self.inner.cost());
+        PlanCost {
+            blocks: outer.blocks + outer.records * inner.blocks,
+            records: outer.records,
+            cpu: outer.cpu + outer.records * inner.cpu,
+            buffers: outer.buffers + inner.buffers,
+        }
+    }
+
+    fn distinct_values(&self, field_name: &str) -> usize {
+        self.outer.distinct_values(field_name)
+    }
+
+    fn schema(&self) -> Schema {
+        self.outer.schema()
+    }
+}
+
+pub struct EmptyPlan {
+    schema: Schema,
+}
+
+impl EmptyPlan {
+    pub fn new(schema: Schema) -> Self {
+        Self { schema }
+    }
+}
+
+impl Plan for EmptyPlan {
+    fn open(&self, _tx: Arc<Mutex<Transaction>>) -> Result<Box<dyn UpdateScan>> {
+        Ok(Box::new(EmptyScan::new(self.schema.clone())))
+    }
+
+    fn cost(&self) -> PlanCost {
+        PlanCost::default()
+    }
+
+    fn distinct_values(&self, _field_name: &str) -> usize {
+        0
+    }
+
+    fn schema(&self) -> Schema {
+        self.schema.clone()
+    }
+}
+
+pub struct ProductPlan {
+    plan1: Box<dyn Plan>,
+    plan2: Box<dyn Plan>,
     schema: Schema,
 }
 
-impl<'p> ProductPlan<'p> {
-    pub fn new(plan1: Box<dyn Plan + 'p>, plan2: Box<dyn Plan + 'p>) -> Self {
+impl ProductPlan {
+    pub fn new(plan1: Box<dyn Plan>, plan2: Box<dyn Plan>) -> Self {
         let mut schema = Schema::new();
         schema.add_all(&plan1.schema());
         schema.add_all(&plan2.schema());
@@ -204,26 +333,22 @@ impl<'p> ProductPlan<'p> {
     }
 }
 
-impl<'p> Plan for ProductPlan<'p> {
-    fn open<'lm, 'bm, 'scan>(
-        &self,
-        tx: Rc<RefCell<Transaction<'lm, 'bm>>>,
-    ) -> Box<dyn UpdateScan + 'scan>
-    where
-        'lm: 'scan,
-        'bm: 'scan,
-    {
-        let s1 = self.plan1.open(tx.clone());
-        let s2 = self.plan2.open(tx.clone());
-        Box::new(ProductScan::new(s1, s2))
-    }
-
-    fn blocks_accessed(&self) -> usize {
-        self.plan1.blocks_accessed() + self.plan1.records_output() * self.plan2.blocks_accessed()
+impl Plan for ProductPlan {
+    fn open(&self, tx: Arc<Mutex<Transaction>>) -> Result<Box<dyn UpdateScan>> {
+        let s1 = self.plan1.open(tx.clone())?;
+        let s2 = self.plan2.open(tx.clone())?;
+        Ok(Box::new(ProductScan::new(s1, s2)))
     }
 
-    fn records_output(&self) -> usize {
-        self.plan1.records_output() * self.plan2.records_output()
+    // NOTE: `plan2` is read again for each record of `plan1`
+    fn cost(&self) -> PlanCost {
+        let (cost1, cost2) = (self.plan1.cost(), self.plan2.cost());
+        PlanCost {
+            blocks: cost1.blocks + cost1.records * cost2.blocks,
+            records: cost1.records * cost2.records,
+            cpu: cost1.cpu + cost1.records * cost2.cpu,
+            buffers: cost1.buffers + cost2.buffers,
+        }
     }
 
     fn distinct_values(&self, field_name: &str) -> usize {
@@ -241,21 +366,125 @@ impl<'p> Plan for ProductPlan<'p> {
 
 #[cfg(test)]
 mod tests {
-    use super::{Plan, ProductPlan, SelectPlan, TablePlan};
+    use super::{
+        EmptyPlan, ExtendPlan, Plan, PlanCost, ProductPlan, ProjectPlan, SelectPlan, SemiJoinPlan,
+        TablePlan,
+    };
     use crate::{
-        query::predicate::{Expression, Predicate, Term},
+        query::{
+            predicate::{Constant, Expression, Predicate, Term},
+            scan::{Result, UpdateScan},
+        },
         record::{schema::Schema, table_scan::TableScan},
         server::simple_db::SimpleDB,
+        tx::transaction::Transaction,
     };
+    use std::sync::{Arc, Mutex};
     use tempfile::tempdir;
 
+    // NOTE: 5 distinct values per field
+    struct FixedPlan {
+        cost: PlanCost,
+        schema: Schema,
+    }
+
+    impl FixedPlan {
+        fn new(field_name: &str, blocks: usize, records: usize, buffers: usize) -> Box<dyn Plan> {
+            let mut schema = Schema::new();
+            schema.add_i32_field(field_name);
+            let cost = PlanCost {
+                blocks,
+                records,
+                cpu: records,
+                buffers,
+            };
+            Box::new(Self { cost, schema })
+        }
+    }
+
+    impl Plan for FixedPlan {
+        fn open(&self, _tx: Arc<Mutex<Transaction>>) -> Result<Box<dyn UpdateScan>> {
+            unreachable!()
+        }
+
+        fn cost(&self) -> PlanCost {
+            self.cost
+        }
+
+        fn distinct_values(&self, _field_name: &str) -> usize {
+            5
+        }
+
+        fn schema(&self) -> Schema {
+            self.schema.clone()
+        }
+    }
+
+    #[test]
+    fn test_cost() {
+        let p = ProductPlan::new(FixedPlan::new("a", 3, 20, 1), FixedPlan::new("b", 2, 10, 1));
+        let cost = PlanCost {
+            blocks: 3 + 20 * 2,
+            records: 20 * 10,
+            cpu: 20 + 20 * 10,
+            buffers: 2,
+        };
+        assert_eq!(p.cost(), cost);
+
+        let pred = Predicate::new(Expression::new(
+            Term::FieldName("a".into()),
+            Term::FieldName("b".into()),
+        ));
+        for negated in [false, true] {
+            let p = SemiJoinPlan::new(
+                FixedPlan::new("a", 3, 20, 1),
+                FixedPlan::new("b", 2, 10, 2),
+                pred.clone(),
+                negated,
+            );
+            let cost = PlanCost {
+                blocks: 3 + 20 * 2,
+                records: 20,
+                cpu: 20 + 20 * 10,
+                buffers: 1 + 2,
+            };
+            assert_eq!(p.cost(), cost);
+        }
+
+        let pred = Predicate::new(Expression::new(
+            Term::FieldName("a".into()),
+            Term::Constant(Constant::Int(1)),
+        ));
+        let p = SelectPlan::new(FixedPlan::new("a", 3, 20, 1), pred);
+        let cost = PlanCost {
+            blocks: 3,
+            records: 20 / 5,
+            cpu: 20 + 20,
+            buffers: 1,
+        };
+        assert_eq!(p.cost(), cost);
+
+        let p = ExtendPlan::new(
+            FixedPlan::new("a", 3, 20, 1),
+            vec![
+                ("x".into(), Term::FieldName("a".into())),
+                ("y".into(), Term::Constant(Constant::Int(1))),
+            ],
+        );
+        assert_eq!(p.cost().cpu, 20 + 20 * 2);
+        let p = ProjectPlan::new(Box::new(p), vec!["x"]);
+        assert_eq!(p.cost().cpu, 20 + 20 * 2);
+
+        assert_eq!(EmptyPlan::new(Schema::new()).cost(), PlanCost::default());
+    }
+
     #[test]
     fn test() {
         let dir = tempdir().unwrap();
         println!("{:?}", dir);
         {
             let mut db = SimpleDB::new_for_test(dir.path(), "plan_test.log");
-            db.init();
+            db.init().unwrap();
 
             let mdm = db.metadata_mgr();
             let tx = db.new_tx();
@@ -279,7 +508,7 @@ mod tests {
                 {
                     // dept
                     let layout = mdm.table_layout("dept", tx.clone()).unwrap();
-                    let mut ts = TableScan::new(tx.clone(), "dept".into(), layout);
+                    let mut ts = TableScan::new(tx.clone(), "dept".into(), layout).unwrap();
                     ts.insert().unwrap();
                     ts.set_i32("did", 1).unwrap();
                     ts.set_string("dname", "Math".into()).unwrap();
@@ -290,7 +519,7 @@ mod tests {
                 {
                     // student
                     let layout = mdm.table_layout("student", tx.clone()).unwrap();
-                    let mut ts = TableScan::new(tx.clone(), "student".into(), layout);
+                    let mut ts = TableScan::new(tx.clone(), "student".into(), layout).unwrap();
                     ts.insert().unwrap();
                     ts.set_i32("sid", 1).unwrap();
                     ts.set_string("sname", "Tom".into()).unwrap();
@@ -304,8 +533,8 @@ mod tests {
                 }
 
                 // 3. test
-                let p1 = Box::new(TablePlan::new(tx.clone(), "student", mdm.clone()));
-                let p2 = Box::new(TablePlan::new(tx.clone(), "dept", mdm.clone()));
+                let p1 = Box::new(TablePlan::new(tx.clone(), "student", mdm.clone()).unwrap());
+                let p2 = Box::new(TablePlan::new(tx.clone(), "dept", mdm.clone()).unwrap());
                 let p3 = Box::new(ProductPlan::new(p1, p2));
 
                 let expr = Expression::new(
@@ -315,7 +544,7 @@ mod tests {
                 let pred = Predicate::new(expr);
                 let p4 = SelectPlan::new(p3, pred);
                 {
-                    let mut s = p4.open(tx.clone());
+                    let mut s = p4.open(tx.clone()).unwrap();
                     assert!(s.before_first().is_ok());
                     assert!(s.next().unwrap());
                     assert_eq!(s.get_i32("sid").unwrap(), 1);
@@ -330,8 +559,27 @@ mod tests {
                     assert!(!s.next().unwrap());
                     // s.close();
                 }
+
+                let cost = PlanCost {
+                    blocks: 1 + 2 * 1,
+                    records: 2 * 2,
+                    cpu: 2 + 2 * 2 + 4,
+                    buffers: 2,
+                };
+                assert_eq!(p4.cost(), cost);
+                assert_eq!(p4.cost().to_string(), "blocks=3 records=4 cpu=10 buffers=2");
+                assert_eq!((p4.blocks_accessed(), p4.records_output()), (3, 4));
+
+                let p5 = SelectPlan::new(
+                    Box::new(TablePlan::new(tx.clone(), "student", mdm.clone()).unwrap()),
+                    Predicate::new(Expression::new(
+                        Term::Constant(Constant::Int(1)),
+                        Term::Constant(Constant::Int(2)),
+                    )),
+                );
+                assert_eq!(p5.records_output(), 0);
             }
-            tx.borrow_mut().commit().unwrap();
+            tx.lock().unwrap().commit().unwrap();
         }
         dir.close().unwrap();
     }
@@ -3,35 +3,74 @@
 // This software is released under the MIT License.
 // https://opensource.org/licenses/MIT
 
-use super::plan::{Plan, SelectPlan, TablePlan};
+use super::{
+    copy::{self, CopyError, CopyOptions},
+    plan::{EmptyPlan, Plan, SelectPlan, TablePlan},
+};
 use crate::{
-    metadata::{common::MetadataError, metadata_mgr::MetadataMgr},
+    index::{hash_index::HashIndex, IndexError, IndexType, KeyOrder},
+    materialize::{
+        sort::{RecordComparator, SortScan},
+        temp_table::TempTable,
+    },
+    metadata::{auth::Privilege, common::MetadataError, metadata_mgr::MetadataMgr},
     parse::{
-        data::{QueryData, UpdateCmd},
+        data::{ExistsData, InsertValue, QueryData, UpdateCmd},
         lexer::LexerError,
         parser::Parser,
     },
-    plan::plan::{ProductPlan, ProjectPlan},
+    plan::plan::{ExtendPlan, ProductPlan, ProjectPlan, SemiJoinPlan},
     query::{
         predicate::{Constant, Predicate, Term},
-        scan::ScanError,
+        scan::{Scan, ScanError, RID},
+    },
+    record::{
+        schema::{Schema, SqlType},
+        table_scan::{self, TableScan},
     },
-    record::schema::Schema,
     tx::transaction::Transaction,
 };
-use std::{cell::RefCell, rc::Rc, sync::Arc};
+use std::{
+    path::Path,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant},
+};
 use thiserror::Error;
 
 #[derive(Debug, Error)]
 pub enum PlannerError {
-    #[error("{0:?}")]
+    #[error("{0}")]
     Metadata(#[from] MetadataError),
 
-    #[error("{0:?}")]
+    #[error("{0}")]
     Scan(#[from] ScanError),
 
-    #[error("{0:?}")]
+    #[error("{0}")]
     Lexer(#[from] LexerError),
+
+    #[error("{0}")]
+    Index(#[from] IndexError),
+
+    #[error("{0}")]
+    Copy(#[from] CopyError),
+
+    #[error("permission denied: {0} has no {1} privilege on {2}")]
+    PermissionDenied(String, Privilege, String),
+
+    #[error("permission denied: {0} can only query and change records")]
+    AdministratorOnly(String),
+
+    #[error("type mismatch: {0} compares {1} with {2}")]
+    TypeMismatch(String, SqlType, SqlType),
+
+    #[error("type mismatch: {1} field {0} is set to {2}")]
+    AssignmentMismatch(String, SqlType, String),
+
+    #[error("type mismatch: {0} negates a varchar")]
+    NegationMismatch(String),
 }
 
 pub type Result<T> = core::result::Result<T, PlannerError>;
@@ -39,23 +78,23 @@ pub type Result<T> = core::result::Result<T, PlannerError>;
 // traits
 
 pub trait QueryPlanner {
-    fn create_plan<'s, 'lm: 's, 'bm: 's>(
-        &'s self,
-        data: QueryData,
-        tx: Rc<RefCell<Transaction<'lm, 'bm>>>,
-    ) -> Box<dyn Plan + '_>;
+    fn create_plan(&self, data: QueryData, tx: Arc<Mutex<Transaction>>) -> Result<Box<dyn Plan>>;
 }
 
 pub trait UpdatePlanner {
-    fn execute<'lm, 'bm>(
-        &self,
-        data: UpdateCmd,
-        tx: Rc<RefCell<Transaction<'lm, 'bm>>>,
-    ) -> Result<u64>;
+    fn execute(&self, data: UpdateCmd, tx: Arc<Mutex<Transaction>>) -> Result<u64>;
 }
 
 // query impl
 
+fn select_plan(plan: Box<dyn Plan>, pred: &Predicate) -> Box<dyn Plan> {
+    match pred.simplified() {
+        None => Box::new(EmptyPlan::new(plan.schema())),
+        Some(pred) if pred.is_empty() => plan,
+        Some(pred) => Box::new(SelectPlan::new(plan, pred)),
+    }
+}
+
 pub struct BasicQueryPlanner {
     mdm: Arc<MetadataMgr>,
 }
@@ -64,45 +103,174 @@ impl BasicQueryPlanner {
     pub fn new(mdm: Arc<MetadataMgr>) -> Self {
         Self { mdm }
     }
-}
 
-impl QueryPlanner for BasicQueryPlanner {
-    fn create_plan<'s, 'lm: 's, 'bm: 's>(
-        &'s self,
-        data: QueryData,
-        tx: Rc<RefCell<Transaction<'lm, 'bm>>>,
-    ) -> Box<dyn Plan + '_> {
+    fn product_plan(
+        &self,
+        tables: &[String],
+        tx: &Arc<Mutex<Transaction>>,
+    ) -> Result<Box<dyn Plan>> {
         let mut plans = Vec::new();
-        for table_name in data.tables() {
+        for table_name in tables {
             if let Ok(viewdef) = self.mdm.view_def(table_name, tx.clone()) {
-                let mut parser = Parser::new(&viewdef).unwrap(); // TODO
-                let viewdata = parser.query().unwrap(); // TODO
-                plans.push(self.create_plan(viewdata, tx.clone()));
+                let mut parser = Parser::new(&viewdef)?;
+                let viewdata = parser.query()?;
+                plans.push(self.create_plan(viewdata, tx.clone())?);
             } else {
                 plans.push(Box::new(TablePlan::new(
                     tx.clone(),
-                    &table_name,
+                    table_name,
                     self.mdm.clone(),
-                )));
+                )?));
             }
         }
 
-        let mut plan = plans
+        Ok(plans
             .into_iter()
             .reduce(|acc, p| Box::new(ProductPlan::new(acc, p)))
-            .unwrap();
-        plan = Box::new(SelectPlan::new(plan, data.pred().clone()));
+            .unwrap())
+    }
+
+    fn semi_join_plan(
+        &self,
+        mut plan: Box<dyn Plan>,
+        exists: &[ExistsData],
+        tx: &Arc<Mutex<Transaction>>,
+    ) -> Result<Box<dyn Plan>> {
+        for e in exists {
+            let query = e.query();
+            let inner = self.product_plan(query.tables(), tx)?;
+            let (local, correlated) = query.pred().partition(&inner.schema());
+            let inner = select_plan(inner, &local);
+            let inner = self.semi_join_plan(inner, query.exists(), tx)?;
+            plan = Box::new(SemiJoinPlan::new(plan, inner, correlated, e.is_negated()));
+        }
+        Ok(plan)
+    }
+}
+
+impl QueryPlanner for BasicQueryPlanner {
+    fn create_plan(&self, data: QueryData, tx: Arc<Mutex<Transaction>>) -> Result<Box<dyn Plan>> {
+        let mut plan = self.product_plan(data.tables(), &tx)?;
+        plan = select_plan(plan, data.pred());
+        plan = self.semi_join_plan(plan, data.exists(), &tx)?;
+        let derived: Vec<(String, Term)> = data
+            .items()
+            .iter()
+            .filter(|i| !i.is_field())
+            .map(|i| (i.name().to_string(), i.term().clone()))
+            .collect();
+        if !derived.is_empty() {
+            plan = Box::new(ExtendPlan::new(plan, derived));
+        }
         let fields = data.fields().iter().map(|f| &**f).collect();
-        Box::new(ProjectPlan::new(plan, fields))
+        Ok(Box::new(ProjectPlan::new(plan, fields)))
     }
 }
 
 // update impl
 
+fn vacuum(mdm: &MetadataMgr, table_name: &str, tx: &Arc<Mutex<Transaction>>) -> Result<u64> {
+    let layout = mdm.table_layout(table_name, tx.clone())?;
+    let indexes = mdm.table_index_info(table_name, tx.clone())?;
+    let position = |rid: &RID| (rid.block_number(), rid.slot().unwrap());
+
+    let mut src = TableScan::new(tx.clone(), table_name.into(), layout.clone())?;
+    let mut rids = Vec::new();
+    while src.next()? {
+        rids.push(src.current_rid());
+    }
+
+    // NOTE: move records from the back into empty slots at the front
+    let mut dest = TableScan::new(tx.clone(), table_name.into(), layout.clone())?;
+    let mut moved = 0;
+    let mut last_block = None;
+    while let Some(rid) = rids.pop() {
+        dest.insert()?;
+        let new_rid = dest.current_rid();
+        if position(&new_rid) > position(&rid) {
+            dest.delete()?;
+            last_block = last_block.max(Some(rid.block_number()));
+            break;
+        }
+        last_block = last_block.max(Some(new_rid.block_number()));
+        src.move_to_rid(rid.clone())?;
+        for field in layout.schema().fields_iter() {
+            let val = src.get_val(field)?;
+            dest.set_val(field, val.clone())?;
+            if let Some(ii) = indexes.get(field) {
+                let mut idx = ii.open(tx.clone())?;
+                idx.delete(&val, &rid)?;
+                idx.insert(&val, &new_rid)?;
+                idx.close();
+            }
+        }
+        src.delete()?;
+        moved += 1;
+    }
+    src.close();
+    dest.close();
+
+    let num_blocks = last_block.map_or(0, |b| b + 1);
+    tx.lock()
+        .unwrap()
+        .truncate_on_commit(&TableScan::filename(table_name), num_blocks as u64)
+        .map_err(ScanError::from)?;
+    mdm.refresh_table_stats(table_name, tx.clone())?;
+    Ok(moved)
+}
+
+fn insert_values(
+    mdm: &MetadataMgr,
+    schema: &Schema,
+    fields: &[String],
+    values: &[InsertValue],
+    tx: &Arc<Mutex<Transaction>>,
+) -> Result<Vec<Constant>> {
+    fields
+        .iter()
+        .zip(values)
+        .map(|(field, v)| match v {
+            InsertValue::Constant(Constant::String(s)) => {
+                // NOTE: before the record is inserted
+                table_scan::check_length(schema, field, s)?;
+                Ok(Constant::String(s.clone()))
+            }
+            InsertValue::Constant(c) => Ok(c.clone()),
+            InsertValue::NextVal(seqname) => {
+                Ok(Constant::Int(mdm.next_sequence_value(seqname, tx.clone())?))
+            }
+        })
+        .collect()
+}
+
+fn execute_copy(
+    mdm: &Arc<MetadataMgr>,
+    table_name: &str,
+    path: &str,
+    delimiter: char,
+    header: bool,
+    tx: Arc<Mutex<Transaction>>,
+) -> Result<u64> {
+    let options = CopyOptions {
+        delimiter,
+        header,
+        ..CopyOptions::default()
+    };
+    Ok(copy::copy_file(
+        mdm.clone(),
+        table_name,
+        Path::new(path),
+        &options,
+        tx,
+    )?)
+}
+
+#[allow(dead_code)] // NOTE: SimpleDB uses IndexUpdatePlanner
 pub struct BasicUpdatePlanner {
     mdm: Arc<MetadataMgr>,
 }
 
+#[allow(dead_code)]
 impl BasicUpdatePlanner {
     pub fn new(mdm: Arc<MetadataMgr>) -> Self {
         Self { mdm }
@@ -110,11 +278,7 @@ impl BasicUpdatePlanner {
 }
 
 impl UpdatePlanner for BasicUpdatePlanner {
-    fn execute<'lm, 'bm>(
-        &self,
-        data: UpdateCmd,
-        tx: Rc<RefCell<Transaction<'lm, 'bm>>>,
-    ) -> Result<u64> {
+    fn execute(&self, data: UpdateCmd, tx: Arc<Mutex<Transaction>>) -> Result<u64> {
         match data {
             UpdateCmd::DeleteData { table_name, pred } => {
                 self.execute_delete(table_name, pred, &tx)
@@ -130,9 +294,11 @@ impl UpdatePlanner for BasicUpdatePlanner {
                 fields,
                 values,
             } => self.execute_insert(&table_name, &fields, &values, &tx),
-            UpdateCmd::CreateTableData { table_name, schema } => {
-                self.execute_create_table(&table_name, schema, &tx)
-            }
+            UpdateCmd::CreateTableData {
+                table_name,
+                schema,
+                tablespace,
+            } => self.execute_create_table(&table_name, schema, tablespace.as_deref(), &tx),
             UpdateCmd::CreateViewData { view_name, query } => {
                 self.execute_create_view(&view_name, &query, &tx)
             }
@@ -140,191 +306,926 @@ impl UpdatePlanner for BasicUpdatePlanner {
                 index_name,
                 table_name,
                 field,
-            } => self.execute_create_index(&index_name, &table_name, &field, &tx),
+                order,
+                index_type,
+                buckets,
+                tablespace,
+            } => self.execute_create_index(
+                &index_name,
+                &table_name,
+                &field,
+                index_type,
+                order,
+                buckets,
+                tablespace.as_deref(),
+                &tx,
+            ),
+            UpdateCmd::CreateSequenceData { sequence_name } => {
+                self.mdm.create_sequence(&sequence_name, tx)?;
+                Ok(0)
+            }
+            UpdateCmd::CreateUserData {
+                user_name,
+                password,
+            } => {
+                self.mdm.create_user(&user_name, &password, tx)?;
+                Ok(0)
+            }
+            UpdateCmd::AlterUserData {
+                user_name,
+                password,
+            } => {
+                self.mdm.alter_user_password(&user_name, &password, tx)?;
+                Ok(0)
+            }
+            UpdateCmd::GrantData {
+                privileges,
+                table_name,
+                user_name,
+            } => {
+                self.mdm.grant(&user_name, &table_name, &privileges, tx)?;
+                Ok(0)
+            }
+            UpdateCmd::RevokeData {
+                privileges,
+                table_name,
+                user_name,
+            } => {
+                self.mdm.revoke(&user_name, &table_name, &privileges, tx)?;
+                Ok(0)
+            }
+            UpdateCmd::CreateTablespaceData {
+                tablespace_name,
+                path,
+            } => {
+                self.mdm.create_tablespace(&tablespace_name, &path, tx)?;
+                Ok(0)
+            }
+            UpdateCmd::VacuumData { table_name } => vacuum(&self.mdm, &table_name, &tx),
+            UpdateCmd::AnalyzeData { table_name } => {
+                self.mdm.analyze(&table_name, tx)?;
+                Ok(0)
+            }
+            UpdateCmd::DropTableData {
+                table_name,
+                cascade,
+            } => {
+                self.mdm.drop_table(&table_name, cascade, tx)?;
+                Ok(0)
+            }
+            UpdateCmd::DropViewData { view_name, cascade } => {
+                self.mdm.drop_view(&view_name, cascade, tx)?;
+                Ok(0)
+            }
+            UpdateCmd::RenameTableData {
+                table_name,
+                new_name,
+            } => {
+                self.mdm.rename_table(&table_name, &new_name, tx)?;
+                Ok(0)
+            }
+            UpdateCmd::AddFieldData { table_name, schema } => {
+                let field = schema.field_name(0).unwrap(); // NOTE: the parser puts exactly one field
+                self.mdm.add_field(
+                    &table_name,
+                    field,
+                    schema.field_type(field).unwrap(),
+                    schema.field_length(field).unwrap(),
+                    schema.field_collation(field).unwrap(),
+                    tx,
+                )?;
+                Ok(0)
+            }
+            UpdateCmd::DropFieldData { table_name, field } => {
+                self.mdm.drop_field(&table_name, &field, tx)?;
+                Ok(0)
+            }
+            UpdateCmd::CopyData {
+                table_name,
+                path,
+                delimiter,
+                header,
+            } => execute_copy(&self.mdm, &table_name, &path, delimiter, header, tx),
         }
     }
 }
 
+#[allow(dead_code)]
 impl BasicUpdatePlanner {
-    fn execute_delete<'lm, 'bm>(
+    fn execute_delete(
         &self,
         table_name: String,
         pred: Predicate,
-        tx: &Rc<RefCell<Transaction<'lm, 'bm>>>,
+        tx: &Arc<Mutex<Transaction>>,
     ) -> Result<u64> {
-        let tp = Box::new(TablePlan::new(tx.clone(), &table_name, self.mdm.clone()));
-        let sp = SelectPlan::new(tp, pred);
-        let mut s = sp.open(tx.clone());
+        let tp = Box::new(TablePlan::new(tx.clone(), &table_name, self.mdm.clone())?);
+        let sp = select_plan(tp, &pred);
+        let mut s = sp.open(tx.clone())?;
         let mut count = 0;
         while s.next()? {
             s.delete()?;
             count += 1;
         }
+        if count > 0 {
+            self.mdm
+                .record_deletes(&table_name, count as usize, tx.clone())?;
+        }
         Ok(count)
     }
 
-    fn execute_modify<'lm, 'bm>(
+    fn execute_modify(
         &self,
         table_name: &str,
         field: &str,
         value: &Term,
         pred: Predicate,
-        tx: &Rc<RefCell<Transaction<'lm, 'bm>>>,
+        tx: &Arc<Mutex<Transaction>>,
     ) -> Result<u64> {
-        let tp = Box::new(TablePlan::new(tx.clone(), table_name, self.mdm.clone()));
-        let sp = SelectPlan::new(tp, pred);
-        let mut s = sp.open(tx.clone());
+        let tp = Box::new(TablePlan::new(tx.clone(), table_name, self.mdm.clone())?);
+        let sp = select_plan(tp, &pred);
+        let mut s = sp.open(tx.clone())?;
         let mut count = 0;
         while s.next()? {
-            let new_value = value.evaluate(&s);
+            let new_value = value.evaluate(s.as_ref());
             s.set_val(field, new_value)?;
             count += 1;
         }
         Ok(count)
     }
 
-    fn execute_insert<'lm, 'bm>(
+    fn execute_insert(
         &self,
         table_name: &str,
         fields: &Vec<String>,
-        values: &Vec<Constant>,
-        tx: &Rc<RefCell<Transaction<'lm, 'bm>>>,
+        values: &[InsertValue],
+        tx: &Arc<Mutex<Transaction>>,
     ) -> Result<u64> {
-        let p = TablePlan::new(tx.clone(), table_name, self.mdm.clone());
-        let mut s = p.open(tx.clone());
+        let p = TablePlan::new(tx.clone(), table_name, self.mdm.clone())?;
+        let values = insert_values(&self.mdm, &p.schema(), fields, values, tx)?;
+        let mut s = p.open(tx.clone())?;
         s.insert()?;
         let mut v = values.iter();
         for f in fields {
             let val = v.next().unwrap();
             s.set_val(f, val.clone()).unwrap();
         }
+        let block_number = s.get_rid()?.block_number() as usize;
+        self.mdm
+            .record_insert(table_name, block_number, tx.clone())?;
         Ok(1)
     }
 
-    fn execute_create_table<'lm, 'bm>(
+    fn execute_create_table(
         &self,
         table_name: &str,
         schema: Schema,
-        tx: &Rc<RefCell<Transaction<'lm, 'bm>>>,
+        tablespace: Option<&str>,
+        tx: &Arc<Mutex<Transaction>>,
     ) -> Result<u64> {
-        self.mdm.create_table(table_name, schema, tx.clone())?;
+        self.mdm
+            .create_table_in(table_name, schema, tablespace, tx.clone())?;
         Ok(0)
     }
 
-    fn execute_create_view<'lm, 'bm>(
+    fn execute_create_view(
         &self,
         view_name: &str,
         query: &QueryData,
-        tx: &Rc<RefCell<Transaction<'lm, 'bm>>>,
+        tx: &Arc<Mutex<Transaction>>,
     ) -> Result<u64> {
-        self.mdm
-            .create_view(view_name, &query.to_string(), tx.clone())?;
+        self.mdm.create_view(
+            view_name,
+            &query.to_string(),
+            &query.referenced_tables(),
+            tx.clone(),
+        )?;
         Ok(0)
     }
 
-    fn execute_create_index<'lm, 'bm>(
+    #[allow(clippy::too_many_arguments)]
+    fn execute_create_index(
         &self,
         index_name: &str,
         table_name: &str,
         field: &str,
-        tx: &Rc<RefCell<Transaction<'lm, 'bm>>>,
+        index_type: IndexType,
+        order: KeyOrder,
+        buckets: Option<usize>,
+        tablespace: Option<&str>,
+        tx: &Arc<Mutex<Transaction>>,
+    ) -> Result<u64> {
+        if let Some(buckets) = buckets {
+            HashIndex::check_buckets(buckets)?;
+        }
+        self.mdm.create_index_in(
+            index_name,
+            table_name,
+            field,
+            index_type,
+            order,
+            buckets,
+            tablespace,
+            tx.clone(),
+        )?;
+        Ok(0)
+    }
+}
+
+// index update impl
+
+pub struct IndexUpdatePlanner {
+    mdm: Arc<MetadataMgr>,
+}
+
+impl IndexUpdatePlanner {
+    pub fn new(mdm: Arc<MetadataMgr>) -> Self {
+        Self { mdm }
+    }
+}
+
+impl UpdatePlanner for IndexUpdatePlanner {
+    fn execute(&self, data: UpdateCmd, tx: Arc<Mutex<Transaction>>) -> Result<u64> {
+        match data {
+            UpdateCmd::DeleteData { table_name, pred } => {
+                self.execute_delete(&table_name, pred, &tx)
+            }
+            UpdateCmd::ModifyData {
+                table_name,
+                field,
+                value,
+                pred,
+            } => self.execute_modify(&table_name, &field, &value, pred, &tx),
+            UpdateCmd::InsertData {
+                table_name,
+                fields,
+                values,
+            } => self.execute_insert(&table_name, &fields, &values, &tx),
+            UpdateCmd::CreateTableData {
+                table_name,
+                schema,
+                tablespace,
+            } => {
+                self.mdm
+                    .create_table_in(&table_name, schema, tablespace.as_deref(), tx)?;
+                Ok(0)
+            }
+            UpdateCmd::CreateViewData { view_name, query } => {
+                self.mdm.create_view(
+                    &view_name,
+                    &query.to_string(),
+                    &query.referenced_tables(),
+                    tx,
+                )?;
+                Ok(0)
+            }
+            UpdateCmd::CreateIndexData {
+                index_name,
+                table_name,
+                field,
+                order,
+                index_type,
+                buckets,
+                tablespace,
+            } => self.execute_create_index(
+                &index_name,
+                &table_name,
+                &field,
+                index_type,
+                order,
+                buckets,
+                tablespace.as_deref(),
+                &tx,
+            ),
+            UpdateCmd::CreateSequenceData { sequence_name } => {
+                self.mdm.create_sequence(&sequence_name, tx)?;
+                Ok(0)
+            }
+            UpdateCmd::CreateUserData {
+                user_name,
+                password,
+            } => {
+                self.mdm.create_user(&user_name, &password, tx)?;
+                Ok(0)
+            }
+            UpdateCmd::AlterUserData {
+                user_name,
+                password,
+            } => {
+                self.mdm.alter_user_password(&user_name, &password, tx)?;
+                Ok(0)
+            }
+            UpdateCmd::GrantData {
+                privileges,
+                table_name,
+                user_name,
+            } => {
+                self.mdm.grant(&user_name, &table_name, &privileges, tx)?;
+                Ok(0)
+            }
+            UpdateCmd::RevokeData {
+                privileges,
+                table_name,
+                user_name,
+            } => {
+                self.mdm.revoke(&user_name, &table_name, &privileges, tx)?;
+                Ok(0)
+            }
+            UpdateCmd::CreateTablespaceData {
+                tablespace_name,
+                path,
+            } => {
+                self.mdm.create_tablespace(&tablespace_name, &path, tx)?;
+                Ok(0)
+            }
+            UpdateCmd::VacuumData { table_name } => vacuum(&self.mdm, &table_name, &tx),
+            UpdateCmd::AnalyzeData { table_name } => {
+                self.mdm.analyze(&table_name, tx)?;
+                Ok(0)
+            }
+            UpdateCmd::DropTableData {
+                table_name,
+                cascade,
+            } => {
+                self.mdm.drop_table(&table_name, cascade, tx)?;
+                Ok(0)
+            }
+            UpdateCmd::DropViewData { view_name, cascade } => {
+                self.mdm.drop_view(&view_name, cascade, tx)?;
+                Ok(0)
+            }
+            UpdateCmd::RenameTableData {
+                table_name,
+                new_name,
+            } => {
+                self.mdm.rename_table(&table_name, &new_name, tx)?;
+                Ok(0)
+            }
+            UpdateCmd::AddFieldData { table_name, schema } => {
+                let field = schema.field_name(0).unwrap(); // NOTE: the parser puts exactly one field
+                self.mdm.add_field(
+                    &table_name,
+                    field,
+                    schema.field_type(field).unwrap(),
+                    schema.field_length(field).unwrap(),
+                    schema.field_collation(field).unwrap(),
+                    tx,
+                )?;
+                Ok(0)
+            }
+            UpdateCmd::DropFieldData { table_name, field } => {
+                self.mdm.drop_field(&table_name, &field, tx)?;
+                Ok(0)
+            }
+            UpdateCmd::CopyData {
+                table_name,
+                path,
+                delimiter,
+                header,
+            } => execute_copy(&self.mdm, &table_name, &path, delimiter, header, tx),
+        }
+    }
+}
+
+impl IndexUpdatePlanner {
+    fn execute_insert(
+        &self,
+        table_name: &str,
+        fields: &[String],
+        values: &[InsertValue],
+        tx: &Arc<Mutex<Transaction>>,
     ) -> Result<u64> {
+        let p = TablePlan::new(tx.clone(), table_name, self.mdm.clone())?;
+        let values = insert_values(&self.mdm, &p.schema(), fields, values, tx)?;
+        let mut s = p.open(tx.clone())?;
+        s.insert()?;
+        let rid = s.get_rid()?;
+
+        let indexes = self.mdm.table_index_info(table_name, tx.clone())?;
+        for (field, val) in fields.iter().zip(values.iter()) {
+            s.set_val(field, val.clone())?;
+            if let Some(ii) = indexes.get(field) {
+                let mut idx = ii.open(tx.clone())?;
+                idx.insert(val, &rid)?;
+                idx.close();
+                self.mdm.refresh_index_stats(ii, tx.clone())?;
+            }
+        }
         self.mdm
-            .create_index(index_name, table_name, field, tx.clone())?;
+            .record_insert(table_name, rid.block_number() as usize, tx.clone())?;
+        Ok(1)
+    }
+
+    fn execute_delete(
+        &self,
+        table_name: &str,
+        pred: Predicate,
+        tx: &Arc<Mutex<Transaction>>,
+    ) -> Result<u64> {
+        let tp = Box::new(TablePlan::new(tx.clone(), table_name, self.mdm.clone())?);
+        let sp = select_plan(tp, &pred);
+        let indexes = self.mdm.table_index_info(table_name, tx.clone())?;
+
+        let mut s = sp.open(tx.clone())?;
+        let mut count = 0;
+        while s.next()? {
+            let rid = s.get_rid()?;
+            for (field, ii) in indexes.iter() {
+                let val = s.get_val(field)?;
+                let mut idx = ii.open(tx.clone())?;
+                idx.delete(&val, &rid)?;
+                idx.close();
+            }
+            s.delete()?;
+            count += 1;
+        }
+        if count > 0 {
+            for ii in indexes.values() {
+                self.mdm.refresh_index_stats(ii, tx.clone())?;
+            }
+            self.mdm
+                .record_deletes(table_name, count as usize, tx.clone())?;
+        }
+        Ok(count)
+    }
+
+    fn execute_modify(
+        &self,
+        table_name: &str,
+        field: &str,
+        value: &Term,
+        pred: Predicate,
+        tx: &Arc<Mutex<Transaction>>,
+    ) -> Result<u64> {
+        let tp = Box::new(TablePlan::new(tx.clone(), table_name, self.mdm.clone())?);
+        let sp = select_plan(tp, &pred);
+        let indexes = self.mdm.table_index_info(table_name, tx.clone())?;
+        let mut idx = indexes
+            .get(field)
+            .map(|ii| ii.open(tx.clone()))
+            .transpose()?;
+
+        let mut s = sp.open(tx.clone())?;
+        let mut count = 0;
+        while s.next()? {
+            let new_value = value.evaluate(s.as_ref());
+            let old_value = s.get_val(field)?;
+            let old_rid = s.get_rid()?;
+            s.set_val(field, new_value.clone())?;
+            let rid = s.get_rid()?;
+            if let Some(idx) = idx.as_mut() {
+                idx.delete(&old_value, &old_rid)?;
+                idx.insert(&new_value, &rid)?;
+            }
+            // NOTE: moved to the current layout version
+            if rid != old_rid {
+                for (f, ii) in indexes.iter().filter(|(f, _)| *f != field) {
+                    let val = s.get_val(f)?;
+                    let mut other = ii.open(tx.clone())?;
+                    other.delete(&val, &old_rid)?;
+                    other.insert(&val, &rid)?;
+                    other.close();
+                }
+            }
+            count += 1;
+        }
+        if let Some(idx) = idx.as_mut() {
+            idx.close();
+        }
+        if let Some(ii) = indexes.get(field).filter(|_| count > 0) {
+            self.mdm.refresh_index_stats(ii, tx.clone())?;
+        }
+        Ok(count)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn execute_create_index(
+        &self,
+        index_name: &str,
+        table_name: &str,
+        field: &str,
+        index_type: IndexType,
+        order: KeyOrder,
+        buckets: Option<usize>,
+        tablespace: Option<&str>,
+        tx: &Arc<Mutex<Transaction>>,
+    ) -> Result<u64> {
+        if let Some(buckets) = buckets {
+            HashIndex::check_buckets(buckets)?;
+        }
+        self.mdm.create_index_in(
+            index_name,
+            table_name,
+            field,
+            index_type,
+            order,
+            buckets,
+            tablespace,
+            tx.clone(),
+        )?;
+
+        // NOTE: sort the existing records and load them at once
+        let indexes = self.mdm.table_index_info(table_name, tx.clone())?;
+        if let Some(ii) = indexes.get(field) {
+            let schema = ii.index_layout().schema().clone();
+            let entries = TempTable::new(tx.clone(), schema.clone());
+            {
+                let p = TablePlan::new(tx.clone(), table_name, self.mdm.clone())?;
+                let mut s = p.open(tx.clone())?;
+                let mut dest = entries.open()?;
+                while s.next()? {
+                    let rid = s.get_rid()?;
+                    let block = rid.block_number_i32()?;
+                    dest.insert()?;
+                    dest.set_val("dataval", s.get_val(field)?)?;
+                    dest.set_i32("block", block)?;
+                    dest.set_i32("id", rid.slot().unwrap_or(-1))?;
+                }
+            }
+
+            let comp = RecordComparator::new(vec!["dataval".into()]).with_collations_of(&schema);
+            let comp = match ii.key_order() {
+                KeyOrder::Ascending => comp,
+                KeyOrder::Descending => comp.reversed(),
+            };
+            let mut sorted = SortScan::sort(tx.clone(), Box::new(entries.open()?), &schema, &comp)?;
+            let mut idx = ii.open(tx.clone())?;
+            idx.bulk_load(&mut sorted)?;
+            idx.close();
+            sorted.close();
+            self.mdm.refresh_index_stats(ii, tx.clone())?;
+        }
         Ok(0)
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PlannerStats {
+    pub queries: usize,
+    pub updates: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PlannerTiming {
+    pub parse: Duration,
+    pub plan: Duration,
+}
+
 pub struct Planner {
+    mdm: Arc<MetadataMgr>,
     qp: Box<dyn QueryPlanner + Send + Sync>,
     up: Box<dyn UpdatePlanner + Send + Sync>,
+    queries: AtomicUsize,
+    updates: AtomicUsize,
 }
 
-impl<'s> Planner {
+impl Planner {
     pub fn new(
+        mdm: Arc<MetadataMgr>,
         qp: impl QueryPlanner + Send + Sync + 'static,
         up: impl UpdatePlanner + Send + Sync + 'static,
     ) -> Self {
         Self {
+            mdm,
             qp: Box::new(qp),
             up: Box::new(up),
+            queries: AtomicUsize::new(0),
+            updates: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn stats(&self) -> PlannerStats {
+        PlannerStats {
+            queries: self.queries.load(Ordering::Relaxed),
+            updates: self.updates.load(Ordering::Relaxed),
         }
     }
 
-    pub fn create_query_plan<'lm: 's, 'bm: 's>(
-        &'s self,
+    pub fn create_query_plan(
+        &self,
         query: &str,
-        tx: Rc<RefCell<Transaction<'lm, 'bm>>>,
-    ) -> Result<Box<dyn Plan + '_>> {
-        let mut parser = Parser::new(query)?;
-        let qry = parser.query()?;
-        self.verify_query(&qry);
-        let plan = self.qp.create_plan(qry, tx);
-        Ok(plan)
+        tx: Arc<Mutex<Transaction>>,
+    ) -> Result<Box<dyn Plan>> {
+        self.create_query_plan_timed(query, tx)
+            .map(|(plan, _)| plan)
     }
 
-    fn verify_query(&self, data: &QueryData) {
-        // TODO
+    pub fn create_query_plan_timed(
+        &self,
+        query: &str,
+        tx: Arc<Mutex<Transaction>>,
+    ) -> Result<(Box<dyn Plan>, PlannerTiming)> {
+        self.plan_query(query, tx)
+            .map(|(plan, _, timing)| (plan, timing))
     }
 
-    pub fn execute_update<'lm, 'bm>(
+    pub fn create_query_plan_with_tables(
         &self,
-        command: &str,
-        tx: Rc<RefCell<Transaction<'lm, 'bm>>>,
-    ) -> Result<u64> {
-        let mut parser = Parser::new(command)?;
-        let cmd = parser.update_cmd()?;
-        self.verify_update(&cmd);
+        query: &str,
+        tx: Arc<Mutex<Transaction>>,
+    ) -> Result<(Box<dyn Plan>, Vec<String>)> {
+        self.plan_query(query, tx)
+            .map(|(plan, tables, _)| (plan, tables))
+    }
 
-        self.up.execute(cmd, tx)
+    fn plan_query(
+        &self,
+        query: &str,
+        tx: Arc<Mutex<Transaction>>,
+    ) -> Result<(Box<dyn Plan>, Vec<String>, PlannerTiming)> {
+        self.queries.fetch_add(1, Ordering::Relaxed);
+        let start = Instant::now();
+        let mut parser = Parser::new(query)?;
+        let qry = parser.query()?;
+        self.verify_query(&qry, &tx)?;
+        self.check_query_types(&qry, &Schema::new(), &tx)?;
+        let tables = qry.tables().clone();
+        let parsed = Instant::now();
+        let plan = self.qp.create_plan(qry, tx)?;
+        let timing = PlannerTiming {
+            parse: parsed - start,
+            plan: parsed.elapsed(),
+        };
+        Ok((plan, tables, timing))
     }
 
-    fn verify_update(&self, data: &UpdateCmd) {
-        // TODO
+    fn verify_query(&self, data: &QueryData, tx: &Arc<Mutex<Transaction>>) -> Result<()> {
+        match Self::user(tx) {
+            Some(user) => self.check_query(&user, data, tx),
+            None => Ok(()),
+        }
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use crate::server::simple_db::SimpleDB;
-    use tempfile::tempdir;
+    fn check_query(
+        &self,
+        user: &str,
+        data: &QueryData,
+        tx: &Arc<Mutex<Transaction>>,
+    ) -> Result<()> {
+        for table_name in data.tables() {
+            self.check_privilege(user, table_name, Privilege::Select, tx)?;
+        }
+        for e in data.exists() {
+            self.check_query(user, e.query(), tx)?;
+        }
+        Ok(())
+    }
 
-    #[test]
-    fn test1() {
-        let dir = tempdir().unwrap();
-        {
-            let mut db = SimpleDB::new_for_test(dir.path(), "planner_test1.log");
-            db.init();
+    pub fn execute_update(&self, command: &str, tx: Arc<Mutex<Transaction>>) -> Result<u64> {
+        self.execute_update_timed(command, tx)
+            .map(|(count, _)| count)
+    }
 
-            let planner = db.planner();
-            let tx = db.new_tx();
-            {
-                planner
-                    .execute_update("create table T1(A int, B varchar(9))", tx.clone())
-                    .unwrap();
+    pub fn execute_update_timed(
+        &self,
+        command: &str,
+        tx: Arc<Mutex<Transaction>>,
+    ) -> Result<(u64, PlannerTiming)> {
+        self.updates.fetch_add(1, Ordering::Relaxed);
+        let start = Instant::now();
+        let mut parser = Parser::new(command)?;
+        let cmd = parser.update_cmd()?;
+        self.verify_update(&cmd, &tx)?;
+        self.check_update_types(&cmd, &tx)?;
+        let parsed = Instant::now();
 
-                for i in 0..200 {
-                    let cmd = format!("insert into T1(A, B) values ({}, 'rec{}')", i, i);
-                    planner.execute_update(&cmd, tx.clone()).unwrap();
-                }
+        let count = self.up.execute(cmd, tx)?;
+        let timing = PlannerTiming {
+            parse: parsed - start,
+            plan: parsed.elapsed(),
+        };
+        Ok((count, timing))
+    }
 
-                let query = "select B from T1 where A = 10";
-                let plan = planner.create_query_plan(query, tx.clone()).unwrap();
-                let mut scan = plan.open(tx.clone());
-                assert!(scan.next().unwrap());
-                assert_eq!(scan.get_string("b").unwrap(), "rec10"); // TODO: case insensitive
-                assert!(!scan.next().unwrap());
+    fn verify_update(&self, data: &UpdateCmd, tx: &Arc<Mutex<Transaction>>) -> Result<()> {
+        let Some(user) = Self::user(tx) else {
+            return Ok(());
+        };
+        match data {
+            UpdateCmd::InsertData { table_name, .. } => {
+                self.check_privilege(&user, table_name, Privilege::Insert, tx)
+            }
+            UpdateCmd::ModifyData { table_name, .. } => {
+                self.check_privilege(&user, table_name, Privilege::Update, tx)
             }
-            tx.borrow_mut().commit().unwrap();
+            UpdateCmd::DeleteData { table_name, .. } => {
+                self.check_privilege(&user, table_name, Privilege::Delete, tx)
+            }
+            UpdateCmd::AlterUserData { user_name, .. } if *user_name == user => Ok(()),
+            // NOTE: copy reads a file of the server
+            _ => Err(PlannerError::AdministratorOnly(user)),
         }
     }
 
-    #[test]
-    fn test2() {
-        let dir = tempdir().unwrap();
+    fn check_privilege(
+        &self,
+        user: &str,
+        table_name: &str,
+        privilege: Privilege,
+        tx: &Arc<Mutex<Transaction>>,
+    ) -> Result<()> {
+        if self
+            .mdm
+            .has_privilege(user, table_name, privilege, tx.clone())?
+        {
+            Ok(())
+        } else {
+            Err(PlannerError::PermissionDenied(
+                user.into(),
+                privilege,
+                table_name.into(),
+            ))
+        }
+    }
+
+    fn check_query_types(
+        &self,
+        data: &QueryData,
+        outer: &Schema,
+        tx: &Arc<Mutex<Transaction>>,
+    ) -> Result<()> {
+        let mut schema = outer.clone();
+        for table_name in data.tables() {
+            schema.add_all(&self.table_schema(table_name, tx)?);
+        }
+        Self::check_fields(data.items().iter().map(|i| i.term()), data.pred(), &schema)?;
+        for item in data.items() {
+            Self::check_term_types(item.term(), &schema)?;
+        }
+        Self::check_pred_types(data.pred(), &schema)?;
+        for e in data.exists() {
+            self.check_query_types(e.query(), &schema, tx)?;
+        }
+        Ok(())
+    }
+
+    fn check_update_types(&self, data: &UpdateCmd, tx: &Arc<Mutex<Transaction>>) -> Result<()> {
+        match data {
+            UpdateCmd::InsertData {
+                table_name,
+                fields,
+                values,
+            } => {
+                let schema = self.table_schema(table_name, tx)?;
+                if let Some(field) = fields.iter().find(|f| !schema.has_field(f)) {
+                    return Err(ScanError::FieldNotFound(field.clone()).into());
+                }
+                for (field, value) in fields.iter().zip(values) {
+                    let vtype = match value {
+                        InsertValue::Constant(Constant::String(_)) => SqlType::VarChar,
+                        InsertValue::Constant(Constant::Int(_)) | InsertValue::NextVal(_) => {
+                            SqlType::Integer
+                        }
+                    };
+                    if let Some(ftype) = schema.field_type(field).filter(|t| *t != vtype) {
+                        return Err(PlannerError::AssignmentMismatch(
+                            field.clone(),
+                            ftype,
+                            value.to_string(),
+                        ));
+                    }
+                }
+                Ok(())
+            }
+            UpdateCmd::ModifyData {
+                table_name,
+                field,
+                value,
+                pred,
+            } => {
+                let schema = self.table_schema(table_name, tx)?;
+                if !schema.has_field(field) {
+                    return Err(ScanError::FieldNotFound(field.clone()).into());
+                }
+                Self::check_fields([value], pred, &schema)?;
+                Self::check_term_types(value, &schema)?;
+                if let (Some(ftype), Some((vtype, _))) =
+                    (schema.field_type(field), value.field_type(&schema))
+                {
+                    if ftype != vtype {
+                        return Err(PlannerError::AssignmentMismatch(
+                            field.clone(),
+                            ftype,
+                            value.to_string(),
+                        ));
+                    }
+                }
+                Self::check_pred_types(pred, &schema)
+            }
+            UpdateCmd::DeleteData { table_name, pred } => {
+                let schema = self.table_schema(table_name, tx)?;
+                Self::check_fields([], pred, &schema)?;
+                Self::check_pred_types(pred, &schema)
+            }
+            UpdateCmd::CreateViewData { query, .. } => {
+                self.check_query_types(query, &Schema::new(), tx)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn check_fields<'a>(
+        terms: impl IntoIterator<Item = &'a Term>,
+        pred: &Predicate,
+        schema: &Schema,
+    ) -> Result<()> {
+        let mut names = pred.field_names();
+        for term in terms {
+            term.collect_field_names(&mut names);
+        }
+        match names.into_iter().find(|f| !schema.has_field(f)) {
+            Some(f) => Err(ScanError::FieldNotFound(f.clone()).into()),
+            None => Ok(()),
+        }
+    }
+
+    fn check_term_types(term: &Term, schema: &Schema) -> Result<()> {
+        match term.string_negation(schema) {
+            Some(t) => Err(PlannerError::NegationMismatch(t.to_string())),
+            None => Ok(()),
+        }
+    }
+
+    fn check_pred_types(pred: &Predicate, schema: &Schema) -> Result<()> {
+        if let Some(t) = pred.string_negation(schema) {
+            return Err(PlannerError::NegationMismatch(t.to_string()));
+        }
+        match pred.type_mismatch(schema) {
+            Some((e, ltype, rtype)) => Err(PlannerError::TypeMismatch(e.to_string(), ltype, rtype)),
+            None => Ok(()),
+        }
+    }
+
+    fn table_schema(&self, table_name: &str, tx: &Arc<Mutex<Transaction>>) -> Result<Schema> {
+        if let Ok(viewdef) = self.mdm.view_def(table_name, tx.clone()) {
+            let viewdata = Parser::new(&viewdef)?.query()?;
+            return Ok(self.qp.create_plan(viewdata, tx.clone())?.schema());
+        }
+        Ok(self
+            .mdm
+            .table_layout(table_name, tx.clone())?
+            .schema()
+            .clone())
+    }
+
+    fn user(tx: &Arc<Mutex<Transaction>>) -> Option<String> {
+        tx.lock().unwrap().user().map(String::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PlannerError;
+    use crate::{
+        index::{hash_index::DEFAULT_BUCKETS, IndexType, KeyOrder},
+        metadata::{auth::Privilege, common::MetadataError, index_mgr::IndexStatInfo},
+        query::{
+            predicate::Constant,
+            scan::{Scan, ScanError},
+        },
+        record::{schema::SqlType, table_scan::TableScan},
+        server::simple_db::SimpleDB,
+        tx::transaction::Transaction,
+    };
+    use std::sync::{Arc, Mutex};
+    use std::time::Instant;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test1() {
+        let dir = tempdir().unwrap();
+        {
+            let mut db = SimpleDB::new_for_test(dir.path(), "planner_test1.log");
+            db.init().unwrap();
+
+            let planner = db.planner();
+            let tx = db.new_tx();
+            {
+                planner
+                    .execute_update("create table T1(A int, B varchar(9))", tx.clone())
+                    .unwrap();
+
+                for i in 0..200 {
+                    let cmd = format!("insert into T1(A, B) values ({}, 'rec{}')", i, i);
+                    planner.execute_update(&cmd, tx.clone()).unwrap();
+                }
+
+                let query = "select B from T1 where A = 10";
+                let plan = planner.create_query_plan(query, tx.clone()).unwrap();
+                let mut scan = plan.open(tx.clone()).unwrap();
+                assert!(scan.next().unwrap());
+                assert_eq!(scan.get_string("b").unwrap(), "rec10"); // TODO: case insensitive
+                assert!(!scan.next().unwrap());
+            }
+            tx.lock().unwrap().commit().unwrap();
+        }
+    }
+
+    #[test]
+    fn test2() {
+        let dir = tempdir().unwrap();
         {
             let mut db = SimpleDB::new_for_test(dir.path(), "planner_test2.log");
-            db.init();
+            db.init().unwrap();
 
             let planner = db.planner();
             let tx = db.new_tx();
@@ -348,13 +1249,1233 @@ mod tests {
 
                 let query = "select B, D from T1, T2 where A = C";
                 let plan = planner.create_query_plan(query, tx.clone()).unwrap();
-                let mut scan = plan.open(tx.clone());
+                let mut scan = plan.open(tx.clone()).unwrap();
                 while scan.next().unwrap() {
                     assert_eq!(scan.get_string("b").unwrap(), scan.get_string("d").unwrap());
                     // TODO: case insensitive
                 }
             }
-            tx.borrow_mut().commit().unwrap();
+            tx.lock().unwrap().commit().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_concat() {
+        let dir = tempdir().unwrap();
+        {
+            let mut db = SimpleDB::new_for_test(dir.path(), "planner_test_concat.log");
+            db.init().unwrap();
+
+            let planner = db.planner();
+            let tx = db.new_tx();
+            {
+                planner
+                    .execute_update("create table T(A int, B varchar(9))", tx.clone())
+                    .unwrap();
+                for i in 0..10 {
+                    let cmd = format!("insert into T(A, B) values ({}, 'rec{}')", i, i % 3);
+                    planner.execute_update(&cmd, tx.clone()).unwrap();
+                }
+
+                let query = "select A, B || '-' || A as C from T where B || A = 'rec14'";
+                let plan = planner.create_query_plan(query, tx.clone()).unwrap();
+                assert_eq!(plan.schema().field_length("c"), Some(9 + 1 + 11));
+                let mut scan = plan.open(tx.clone()).unwrap();
+                assert!(scan.next().unwrap());
+                assert_eq!(scan.get_i32("a").unwrap(), 4);
+                assert_eq!(scan.get_string("c").unwrap(), "rec1-4");
+                assert!(!scan.next().unwrap());
+                scan.close();
+
+                planner
+                    .execute_update("update T set B = B || '!' where A = 4", tx.clone())
+                    .unwrap();
+                planner
+                    .execute_update("create view V as select A || B as C from T", tx.clone())
+                    .unwrap();
+                let plan = planner
+                    .create_query_plan("select C from V where C = '4rec1!'", tx.clone())
+                    .unwrap();
+                let mut scan = plan.open(tx.clone()).unwrap();
+                assert!(scan.next().unwrap());
+                assert!(!scan.next().unwrap());
+            }
+            tx.lock().unwrap().commit().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_privileges() {
+        let dir = tempdir().unwrap();
+        {
+            let mut db = SimpleDB::new_for_test(dir.path(), "planner_test_privileges.log");
+            db.init().unwrap();
+
+            let planner = db.planner();
+            let tx = db.new_tx();
+            for cmd in [
+                "create table T(A int, B varchar(9))",
+                "create view V as select A from T",
+                "create user alice password 'secret'",
+                "create user bob password 'secret'",
+                "grant select on V to alice",
+            ] {
+                planner.execute_update(cmd, tx.clone()).unwrap();
+            }
+            tx.lock().unwrap().commit().unwrap();
+
+            let tx = db.new_tx();
+            tx.lock().unwrap().set_user(Some("alice".into()));
+            let denied = |err: Option<PlannerError>, privilege: Privilege, table: &str| {
+                matches!(err, Some(PlannerError::PermissionDenied(u, p, t))
+                    if u == "alice" && p == privilege && t == table)
+            };
+            assert!(planner
+                .create_query_plan("select A from V", tx.clone())
+                .is_ok());
+            assert!(denied(
+                planner
+                    .create_query_plan("select A from T", tx.clone())
+                    .err(),
+                Privilege::Select,
+                "t"
+            ));
+            assert!(denied(
+                planner
+                    .create_query_plan("select A from V where exists (select B from T)", tx.clone())
+                    .err(),
+                Privilege::Select,
+                "t"
+            ));
+            assert!(denied(
+                planner
+                    .execute_update("insert into T(A, B) values (1, 'a')", tx.clone())
+                    .err(),
+                Privilege::Insert,
+                "t"
+            ));
+            assert!(matches!(
+                planner.execute_update("create table U(A int)", tx.clone()),
+                Err(PlannerError::AdministratorOnly(u)) if u == "alice"
+            ));
+            assert!(matches!(
+                planner.execute_update("grant all on T to alice", tx.clone()),
+                Err(PlannerError::AdministratorOnly(_))
+            ));
+            assert!(matches!(
+                planner.execute_update("alter user bob password 'mine'", tx.clone()),
+                Err(PlannerError::AdministratorOnly(_))
+            ));
+            planner
+                .execute_update("alter user alice password 'changed'", tx.clone())
+                .unwrap();
+
+            tx.lock().unwrap().set_user(None);
+            planner
+                .execute_update("grant insert, update, delete on T to alice", tx.clone())
+                .unwrap();
+            tx.lock().unwrap().set_user(Some("alice".into()));
+            for cmd in [
+                "insert into T(A, B) values (1, 'a')",
+                "update T set A = 2 where A = 1",
+                "delete from T where A = 2",
+            ] {
+                assert_eq!(planner.execute_update(cmd, tx.clone()).unwrap(), 1);
+            }
+            assert!(denied(
+                planner
+                    .create_query_plan("select B from T", tx.clone())
+                    .err(),
+                Privilege::Select,
+                "t"
+            ));
+
+            tx.lock().unwrap().set_user(None);
+            assert!(matches!(
+                planner.execute_update("grant select on U to alice", tx.clone()),
+                Err(PlannerError::Metadata(MetadataError::TableNotFound(_)))
+            ));
+            planner
+                .execute_update("drop table T cascade", tx.clone())
+                .unwrap();
+            planner
+                .execute_update("create table T(A int)", tx.clone())
+                .unwrap();
+            tx.lock().unwrap().set_user(Some("alice".into()));
+            assert!(denied(
+                planner
+                    .execute_update("insert into T(A) values (1)", tx.clone())
+                    .err(),
+                Privilege::Insert,
+                "t"
+            ));
+            tx.lock().unwrap().commit().unwrap();
+            assert!(db.authenticate("alice", "changed").unwrap());
+        }
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_sequence() {
+        let dir = tempdir().unwrap();
+        {
+            let mut db = SimpleDB::new_for_test(dir.path(), "planner_test_sequence.log");
+            db.init().unwrap();
+
+            let planner = db.planner();
+            let tx = db.new_tx();
+            {
+                for cmd in [
+                    "create table T(A int, B varchar(9))",
+                    "create index TA on T(A)",
+                    "create sequence S",
+                ] {
+                    planner.execute_update(cmd, tx.clone()).unwrap();
+                }
+                for i in 0..3 {
+                    let cmd = format!("insert into T(A, B) values (nextval('s'), 'rec{}')", i);
+                    planner.execute_update(&cmd, tx.clone()).unwrap();
+                }
+                assert!(matches!(
+                    planner.execute_update("insert into T(A) values (nextval('u'))", tx.clone()),
+                    Err(PlannerError::Metadata(MetadataError::SequenceNotFound(s))) if s == "u"
+                ));
+
+                let plan = planner
+                    .create_query_plan("select B from T where A = 3", tx.clone())
+                    .unwrap();
+                let mut scan = plan.open(tx.clone()).unwrap();
+                assert!(scan.next().unwrap());
+                assert_eq!(scan.get_string("b").unwrap(), "rec2");
+                assert!(!scan.next().unwrap());
+                scan.close();
+            }
+            tx.lock().unwrap().commit().unwrap();
+        }
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_varchar_length() {
+        let dir = tempdir().unwrap();
+        {
+            let mut db = SimpleDB::new_for_test(dir.path(), "planner_test_varchar_length.log");
+            db.init().unwrap();
+
+            let planner = db.planner();
+            let tx = db.new_tx();
+            {
+                for cmd in [
+                    "create table T(A int, B varchar(9), C varchar(3))",
+                    "create index TB on T(B)",
+                    "insert into T(A, B, C) values (1, 'ninechars', 'abc')",
+                ] {
+                    planner.execute_update(cmd, tx.clone()).unwrap();
+                }
+                planner
+                    .execute_update("insert into T(A, B, C) values (2, 'b', 'äöü')", tx.clone())
+                    .unwrap();
+                assert!(matches!(
+                    planner.execute_update(
+                        "insert into T(A, B, C) values (3, 'much longer than nine', 'c')",
+                        tx.clone()
+                    ),
+                    Err(PlannerError::Scan(ScanError::ValueTooLong(f, 9))) if f == "b"
+                ));
+                assert!(matches!(
+                    planner.execute_update("update T set C = 'abcd' where A = 1", tx.clone()),
+                    Err(PlannerError::Scan(ScanError::ValueTooLong(f, 3))) if f == "c"
+                ));
+
+                let plan = planner
+                    .create_query_plan("select A, B, C from T", tx.clone())
+                    .unwrap();
+                let mut scan = plan.open(tx.clone()).unwrap();
+                let mut rows = Vec::new();
+                while scan.next().unwrap() {
+                    rows.push((
+                        scan.get_i32("a").unwrap(),
+                        scan.get_string("b").unwrap(),
+                        scan.get_string("c").unwrap(),
+                    ));
+                }
+                scan.close();
+                assert_eq!(
+                    rows,
+                    vec![
+                        (1, "ninechars".to_string(), "abc".to_string()),
+                        (2, "b".to_string(), "äöü".to_string())
+                    ]
+                );
+            }
+            tx.lock().unwrap().commit().unwrap();
+        }
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_type_check() {
+        let dir = tempdir().unwrap();
+        {
+            let mut db = SimpleDB::new_for_test(dir.path(), "planner_test_type_check.log");
+            db.init().unwrap();
+
+            let planner = db.planner();
+            let tx = db.new_tx();
+            {
+                for cmd in [
+                    "create table T(A int, B varchar(9))",
+                    "create table U(C int)",
+                    "create index TA on T(A)",
+                    "create view V as select B from T",
+                    "insert into T(A, B) values (1, 'rec1')",
+                ] {
+                    planner.execute_update(cmd, tx.clone()).unwrap();
+                }
+                let type_mismatch = |sql: &str| match planner.create_query_plan(sql, tx.clone()) {
+                    Err(PlannerError::TypeMismatch(e, ltype, rtype)) => (e, ltype, rtype),
+                    Err(e) => panic!("{}: {:?}", sql, e),
+                    Ok(_) => panic!("{}: no error", sql),
+                };
+                assert_eq!(
+                    type_mismatch("select A from T where B = 5"),
+                    ("b = 5".into(), SqlType::VarChar, SqlType::Integer)
+                );
+                assert_eq!(
+                    type_mismatch("select B from T where A = 'x'"),
+                    ("a = 'x'".into(), SqlType::Integer, SqlType::VarChar)
+                );
+                assert_eq!(
+                    type_mismatch("select B from V where B = 1"),
+                    ("b = 1".into(), SqlType::VarChar, SqlType::Integer)
+                );
+                assert_eq!(
+                    type_mismatch("select A from T where exists (select C from U where C = B)"),
+                    ("c = b".into(), SqlType::Integer, SqlType::VarChar)
+                );
+                for sql in [
+                    "select A from T where B = 'x' || A",
+                    "select A from T where exists (select C from U where C = A)",
+                ] {
+                    planner.create_query_plan(sql, tx.clone()).unwrap();
+                }
+
+                assert!(matches!(
+                    planner.execute_update("insert into T(A, B) values ('1', 'rec')", tx.clone()),
+                    Err(PlannerError::AssignmentMismatch(f, SqlType::Integer, v)) if f == "a" && v == "'1'"
+                ));
+                assert!(matches!(
+                    planner.execute_update("update T set B = 5 where A = 1", tx.clone()),
+                    Err(PlannerError::AssignmentMismatch(f, SqlType::VarChar, v)) if f == "b" && v == "5"
+                ));
+                assert!(matches!(
+                    planner.execute_update("update T set B = 'x' where A = 'y'", tx.clone()),
+                    Err(PlannerError::TypeMismatch(..))
+                ));
+                assert!(matches!(
+                    planner.execute_update("delete from T where B = 0", tx.clone()),
+                    Err(PlannerError::TypeMismatch(..))
+                ));
+                assert!(matches!(
+                    planner.execute_update(
+                        "create view W as select A from T where A = 'a'",
+                        tx.clone()
+                    ),
+                    Err(PlannerError::TypeMismatch(..))
+                ));
+                assert_eq!(
+                    planner
+                        .execute_update("update T set B = B || A where A = 1", tx.clone())
+                        .unwrap(),
+                    1
+                );
+
+                for sql in [
+                    "select Z from T",
+                    "select A from T where Z = 1",
+                    "select A from V",
+                    "select A from T where exists (select C from U where Z = A)",
+                ] {
+                    assert!(
+                        matches!(
+                            planner.create_query_plan(sql, tx.clone()),
+                            Err(PlannerError::Scan(ScanError::FieldNotFound(f))) if f == "z" || f == "a"
+                        ),
+                        "{sql}"
+                    );
+                }
+                for sql in [
+                    "select A from X",
+                    "select A from T where exists (select C from X)",
+                ] {
+                    assert!(
+                        matches!(
+                            planner.create_query_plan(sql, tx.clone()),
+                            Err(PlannerError::Metadata(MetadataError::TableNotFound(t))) if t == "x"
+                        ),
+                        "{sql}"
+                    );
+                }
+                for cmd in [
+                    "insert into T(A, Z) values (1, 2)",
+                    "update T set Z = 1",
+                    "update T set A = Z",
+                    "update T set A = 1 where Z = 1",
+                    "delete from T where Z = 1",
+                    "create view W as select Z from T",
+                ] {
+                    assert!(
+                        matches!(
+                            planner.execute_update(cmd, tx.clone()),
+                            Err(PlannerError::Scan(ScanError::FieldNotFound(f))) if f == "z"
+                        ),
+                        "{cmd}"
+                    );
+                }
+                for cmd in [
+                    "insert into X(A) values (1)",
+                    "update X set A = 1",
+                    "delete from X",
+                ] {
+                    assert!(
+                        matches!(
+                            planner.execute_update(cmd, tx.clone()),
+                            Err(PlannerError::Metadata(MetadataError::TableNotFound(t))) if t == "x"
+                        ),
+                        "{cmd}"
+                    );
+                }
+            }
+            tx.lock().unwrap().commit().unwrap();
+        }
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_negative() {
+        let dir = tempdir().unwrap();
+        {
+            let mut db = SimpleDB::new_for_test(dir.path(), "planner_test_negative.log");
+            db.init().unwrap();
+
+            let planner = db.planner();
+            let tx = db.new_tx();
+            {
+                for cmd in [
+                    "create table T(A int, B varchar(9))",
+                    "create index TA on T(A)",
+                    "insert into T(A, B) values (-5, 'neg')",
+                    "insert into T(A, B) values (5, 'pos')",
+                    "insert into T(A, B) values (-2147483648, 'min')",
+                ] {
+                    planner.execute_update(cmd, tx.clone()).unwrap();
+                }
+                let query = |sql: &str| {
+                    let plan = planner.create_query_plan(sql, tx.clone()).unwrap();
+                    let mut scan = plan.open(tx.clone()).unwrap();
+                    let mut rows = Vec::new();
+                    while scan.next().unwrap() {
+                        rows.push((scan.get_i32("c").unwrap(), scan.get_string("b").unwrap()));
+                    }
+                    scan.close();
+                    rows
+                };
+                assert_eq!(
+                    query("select -A as C, B from T where A = -5"),
+                    vec![(5, "neg".to_string())]
+                );
+                assert_eq!(
+                    query("select A as C, B from T where -A = 5"),
+                    vec![(-5, "neg".to_string())]
+                );
+                assert_eq!(
+                    query("select A as C, B from T where A = - -2147483648"),
+                    vec![(i32::MIN, "min".to_string())]
+                );
+
+                assert_eq!(
+                    planner
+                        .execute_update("update T set A = -A where B = 'pos'", tx.clone())
+                        .unwrap(),
+                    1
+                );
+                assert_eq!(
+                    query("select A as C, B from T where A = -5")
+                        .into_iter()
+                        .map(|(_, b)| b)
+                        .collect::<Vec<_>>(),
+                    vec!["neg", "pos"]
+                );
+
+                assert!(matches!(
+                    planner.create_query_plan("select -B as C from T", tx.clone()),
+                    Err(PlannerError::NegationMismatch(t)) if t == "-b"
+                ));
+                assert!(matches!(
+                    planner.execute_update("update T set A = 1 where B = -B", tx.clone()),
+                    Err(PlannerError::NegationMismatch(_))
+                ));
+            }
+            tx.lock().unwrap().commit().unwrap();
+        }
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_collation() {
+        let dir = tempdir().unwrap();
+        {
+            let mut db = SimpleDB::new_for_test(dir.path(), "planner_test_collation.log");
+            db.init().unwrap();
+
+            let planner = db.planner();
+            let mdm = db.metadata_mgr();
+            let tx = db.new_tx();
+            {
+                planner
+                    .execute_update(
+                        "create table T(A int, B varchar(9) collate nocase, C varchar(9))",
+                        tx.clone(),
+                    )
+                    .unwrap();
+                for (i, s) in ["rec", "Rec", "REC", "rec!"].iter().enumerate() {
+                    let cmd = format!("insert into T(A, B, C) values ({i}, '{s}', '{s}')");
+                    planner.execute_update(&cmd, tx.clone()).unwrap();
+                }
+                planner
+                    .execute_update("create index idx_b on T(B)", tx.clone())
+                    .unwrap();
+                planner
+                    .execute_update(
+                        "insert into T(A, B, C) values (4, 'rEc', 'rEc')",
+                        tx.clone(),
+                    )
+                    .unwrap();
+
+                let count_of = |query: &str| {
+                    let plan = planner.create_query_plan(query, tx.clone()).unwrap();
+                    let mut scan = plan.open(tx.clone()).unwrap();
+                    let mut count = 0;
+                    while scan.next().unwrap() {
+                        count += 1;
+                    }
+                    count
+                };
+                assert_eq!(count_of("select A from T where B = 'rec'"), 4);
+                assert_eq!(count_of("select A from T where C = 'rec'"), 1);
+                assert_eq!(
+                    count_of("select A from T where C = 'REC' collate nocase"),
+                    4
+                );
+                assert_eq!(
+                    count_of("select A from T where B = 'REC' collate binary"),
+                    1
+                );
+                assert_eq!(count_of("select A from T where C = B"), 5);
+
+                let indexes = mdm.table_index_info("t", tx.clone()).unwrap();
+                let mut idx = indexes.get("b").unwrap().open(tx.clone()).unwrap();
+                let mut count = 0;
+                idx.before_first(&Constant::String("ReC".into())).unwrap();
+                while idx.next().unwrap() {
+                    count += 1;
+                }
+                assert_eq!(count, 4);
+                idx.close();
+            }
+            tx.lock().unwrap().commit().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_constant_predicate() {
+        let dir = tempdir().unwrap();
+        {
+            let mut db = SimpleDB::new_for_test(dir.path(), "planner_test_constant_predicate.log");
+            db.init().unwrap();
+
+            let planner = db.planner();
+            let bm = db.buffer_mgr();
+            let tx = db.new_tx();
+            {
+                planner
+                    .execute_update("create table T(A int, B varchar(9))", tx.clone())
+                    .unwrap();
+                for i in 0..10 {
+                    let cmd = format!("insert into T(A, B) values ({}, 'rec{}')", i, i % 3);
+                    planner.execute_update(&cmd, tx.clone()).unwrap();
+                }
+
+                let query = "select A from T where 'rec' || 1 = B and A = A and 1 = 1";
+                let plan = planner.create_query_plan(query, tx.clone()).unwrap();
+                let mut scan = plan.open(tx.clone()).unwrap();
+                let mut count = 0;
+                while scan.next().unwrap() {
+                    count += 1;
+                }
+                assert_eq!(count, 3);
+                scan.close();
+
+                let query = "select A from T where A = 1 and 'a' = 'b'";
+                let plan = planner.create_query_plan(query, tx.clone()).unwrap();
+                assert_eq!(plan.blocks_accessed(), 0);
+                let before = bm.stats();
+                let mut scan = plan.open(tx.clone()).unwrap();
+                assert!(!scan.next().unwrap());
+                assert!(scan.has_field("a"));
+                scan.close();
+                let after = bm.stats();
+                assert_eq!(after.hits + after.misses, before.hits + before.misses);
+
+                let n = planner
+                    .execute_update("delete from T where 1 = 2", tx.clone())
+                    .unwrap();
+                assert_eq!(n, 0);
+                let n = planner
+                    .execute_update("update T set B = 'x' where 'a' = 'a'", tx.clone())
+                    .unwrap();
+                assert_eq!(n, 10);
+            }
+            tx.lock().unwrap().commit().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_exists() {
+        let dir = tempdir().unwrap();
+        {
+            let mut db = SimpleDB::new_for_test(dir.path(), "planner_test_exists.log");
+            db.init().unwrap();
+
+            let planner = db.planner();
+            let tx = db.new_tx();
+            {
+                planner
+                    .execute_update("create table P(Id int, Name varchar(9))", tx.clone())
+                    .unwrap();
+                planner
+                    .execute_update("create table C(PId int, Kind varchar(9))", tx.clone())
+                    .unwrap();
+                for i in 0..6 {
+                    let cmd = format!("insert into P(Id, Name) values ({i}, 'p{i}')");
+                    planner.execute_update(&cmd, tx.clone()).unwrap();
+                }
+                // NOTE: parents 0, 2 and 4 have children, and 2 has a 'y'
+                for (pid, kind) in [(0, "x"), (0, "x"), (2, "x"), (2, "y"), (4, "x")] {
+                    let cmd = format!("insert into C(PId, Kind) values ({pid}, '{kind}')");
+                    planner.execute_update(&cmd, tx.clone()).unwrap();
+                }
+
+                let ids_of = |query: &str| {
+                    let plan = planner.create_query_plan(query, tx.clone()).unwrap();
+                    let mut scan = plan.open(tx.clone()).unwrap();
+                    let mut ids = Vec::new();
+                    while scan.next().unwrap() {
+                        ids.push(scan.get_i32("id").unwrap());
+                    }
+                    scan.close();
+                    ids
+                };
+                assert_eq!(
+                    ids_of("select Id from P where exists (select PId from C where PId = Id)"),
+                    [0, 2, 4]
+                );
+                assert_eq!(
+                    ids_of("select Id from P where not exists (select PId from C where PId = Id)"),
+                    [1, 3, 5]
+                );
+                assert_eq!(
+                    ids_of(
+                        "select Id from P where Name = 'p2' and exists (select PId from C where Id = PId and Kind = 'y')"
+                    ),
+                    [2]
+                );
+                assert_eq!(
+                    ids_of("select Id from P where exists (select PId from C where Kind = 'z')"),
+                    []
+                );
+                let (_, tables) = planner
+                    .create_query_plan_with_tables(
+                        "select Id from P where exists (select PId from C where PId = Id)",
+                        tx.clone(),
+                    )
+                    .unwrap();
+                assert_eq!(tables, ["p"]);
+
+                planner
+                    .execute_update(
+                        "create view V as select Id from P where not exists (select PId from C where PId = Id and Kind = 'x')",
+                        tx.clone(),
+                    )
+                    .unwrap();
+                assert_eq!(ids_of("select Id from V"), [1, 3, 5]);
+                assert!(matches!(
+                    planner.execute_update("drop table C restrict", tx.clone()),
+                    Err(PlannerError::Metadata(MetadataError::DependentView(v, t))) if v == "v" && t == "c"
+                ));
+            }
+            tx.lock().unwrap().commit().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_index_update_planner() {
+        let dir = tempdir().unwrap();
+        {
+            let mut db = SimpleDB::new_for_test(dir.path(), "planner_test_index.log");
+            db.init().unwrap();
+
+            let planner = db.planner();
+            let mdm = db.metadata_mgr();
+            let tx = db.new_tx();
+            {
+                planner
+                    .execute_update("create table T1(A int, B varchar(9))", tx.clone())
+                    .unwrap();
+                for i in 0..10 {
+                    let cmd = format!("insert into T1(A, B) values ({}, 'rec{}')", i, i % 3);
+                    planner.execute_update(&cmd, tx.clone()).unwrap();
+                }
+                planner
+                    .execute_update("create index idx_b on T1(B)", tx.clone())
+                    .unwrap();
+                {
+                    let indexes = mdm.table_index_info("t1", tx.clone()).unwrap();
+                    let stats = indexes.get("b").unwrap().index_stat_info().unwrap();
+                    assert_eq!((stats.height(), stats.num_leaves()), (2, 1));
+                }
+                planner
+                    .execute_update("insert into T1(A, B) values (10, 'rec1')", tx.clone())
+                    .unwrap();
+                {
+                    let indexes = mdm.table_index_info("t1", tx.clone()).unwrap();
+                    let ii = indexes.get("b").unwrap();
+                    assert_eq!(ii.index_stat_info(), Some(IndexStatInfo::new(2, 1)));
+                    assert!(!mdm.refresh_index_stats(ii, tx.clone()).unwrap());
+                }
+
+                let count_of = |key: &str| {
+                    let indexes = mdm.table_index_info("t1", tx.clone()).unwrap();
+                    let mut idx = indexes.get("b").unwrap().open(tx.clone()).unwrap();
+                    let mut count = 0;
+                    idx.before_first(&Constant::String(key.into())).unwrap();
+                    while idx.next().unwrap() {
+                        count += 1;
+                    }
+                    idx.close();
+                    count
+                };
+                assert_eq!(count_of("rec1"), 4); // NOTE: A = 1, 4, 7 and 10
+                assert_eq!(count_of("rec2"), 3);
+
+                planner
+                    .execute_update("delete from T1 where A = 4", tx.clone())
+                    .unwrap();
+                assert_eq!(count_of("rec1"), 3);
+
+                planner
+                    .execute_update("update T1 set B = 'rec2' where A = 7", tx.clone())
+                    .unwrap();
+                assert_eq!(count_of("rec1"), 2);
+                assert_eq!(count_of("rec2"), 4);
+
+                planner
+                    .execute_update("create index idx_a on T1(A desc)", tx.clone())
+                    .unwrap();
+                planner
+                    .execute_update("insert into T1(A, B) values (11, 'rec2')", tx.clone())
+                    .unwrap();
+                let indexes = mdm.table_index_info("t1", tx.clone()).unwrap();
+                let ii = indexes.get("a").unwrap();
+                assert_eq!(ii.key_order(), KeyOrder::Descending);
+                let mut idx = ii.open(tx.clone()).unwrap();
+                for key in [0, 7, 10, 11] {
+                    idx.before_first(&Constant::Int(key)).unwrap();
+                    assert!(idx.next().unwrap(), "key={key}");
+                    assert!(!idx.next().unwrap());
+                }
+                idx.before_first(&Constant::Int(4)).unwrap();
+                assert!(!idx.next().unwrap());
+                idx.close();
+            }
+            tx.lock().unwrap().commit().unwrap();
+        }
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_vacuum() {
+        let dir = tempdir().unwrap();
+        {
+            let mut db = SimpleDB::new_for_test(dir.path(), "planner_test_vacuum.log");
+            db.init().unwrap();
+
+            let planner = db.planner();
+            let mdm = db.metadata_mgr();
+            let tx = db.new_tx();
+            planner
+                .execute_update("create table T1(A int, B varchar(9))", tx.clone())
+                .unwrap();
+            planner
+                .execute_update("create index idx_a on T1(A)", tx.clone())
+                .unwrap();
+            for i in 0..300 {
+                let cmd = format!("insert into T1(A, B) values ({}, 'rec{}')", i, i % 3);
+                planner.execute_update(&cmd, tx.clone()).unwrap();
+            }
+            {
+                let indexes = mdm.table_index_info("t1", tx.clone()).unwrap();
+                let ii = indexes.get("a").unwrap();
+                assert_eq!(ii.index_stat_info(), Some(IndexStatInfo::new(2, 2)));
+                assert_eq!(
+                    ii.calc_index_stats(tx.clone()).unwrap(),
+                    ii.index_stat_info()
+                );
+            }
+            for cmd in [
+                "delete from T1 where B = 'rec0'",
+                "delete from T1 where B = 'rec1'",
+            ] {
+                planner.execute_update(cmd, tx.clone()).unwrap();
+            }
+            tx.lock().unwrap().commit().unwrap();
+            assert_eq!(db.file_mgr().length("t1.tbl").unwrap(), 4);
+
+            let tx = db.new_tx();
+            let moved = planner.execute_update("vacuum T1", tx.clone()).unwrap();
+            assert!(moved > 0);
+            tx.lock().unwrap().commit().unwrap();
+            // NOTE: 2 blocks
+            assert_eq!(db.file_mgr().length("t1.tbl").unwrap(), 2);
+
+            let tx = db.new_tx();
+            {
+                let plan = planner
+                    .create_query_plan("select A, B from T1", tx.clone())
+                    .unwrap();
+                let mut scan = plan.open(tx.clone()).unwrap();
+                let mut count = 0;
+                while scan.next().unwrap() {
+                    assert_eq!(scan.get_i32("a").unwrap() % 3, 2);
+                    count += 1;
+                }
+                scan.close();
+                assert_eq!(count, 100);
+            }
+            {
+                let indexes = mdm.table_index_info("t1", tx.clone()).unwrap();
+                let mut idx = indexes.get("a").unwrap().open(tx.clone()).unwrap();
+                let layout = mdm.table_layout("t1", tx.clone()).unwrap();
+                let mut scan = TableScan::new(tx.clone(), "t1".into(), layout).unwrap();
+                for key in [2, 152, 299] {
+                    idx.before_first(&Constant::Int(key)).unwrap();
+                    assert!(idx.next().unwrap());
+                    scan.move_to_rid(idx.get_data_rid().unwrap()).unwrap();
+                    assert_eq!(scan.get_i32("a").unwrap(), key);
+                }
+                idx.close();
+                scan.close();
+            }
+            tx.lock().unwrap().commit().unwrap();
+        }
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_alter_table() {
+        let dir = tempdir().unwrap();
+        {
+            let mut db = SimpleDB::new_for_test(dir.path(), "planner_test_alter_table.log");
+            db.init().unwrap();
+
+            let planner = db.planner();
+            let mdm = db.metadata_mgr();
+            let tx = db.new_tx();
+            planner
+                .execute_update("create table T2(A int, B varchar(9))", tx.clone())
+                .unwrap();
+            planner
+                .execute_update("create index idx_a2 on T2(A)", tx.clone())
+                .unwrap();
+            for i in 0..30 {
+                let cmd = format!("insert into T2(A, B) values ({}, 'rec{}')", i, i);
+                planner.execute_update(&cmd, tx.clone()).unwrap();
+            }
+            tx.lock().unwrap().commit().unwrap();
+            let num_blocks = db.file_mgr().length("t2.tbl").unwrap();
+
+            let tx = db.new_tx();
+            planner
+                .execute_update("alter table T2 add C int", tx.clone())
+                .unwrap();
+            planner
+                .execute_update("insert into T2(A, B, C) values (100, 'new', 7)", tx.clone())
+                .unwrap();
+            {
+                let plan = planner
+                    .create_query_plan("select A, C from T2 where A = 100", tx.clone())
+                    .unwrap();
+                let mut scan = plan.open(tx.clone()).unwrap();
+                assert!(scan.next().unwrap());
+                assert_eq!(scan.get_i32("c").unwrap(), 7);
+                scan.close();
+            }
+            // NOTE: the old records are updated only once
+            let count = planner
+                .execute_update("update T2 set C = A where B = 'rec3'", tx.clone())
+                .unwrap();
+            assert_eq!(count, 1);
+            let count = planner
+                .execute_update("update T2 set C = A", tx.clone())
+                .unwrap();
+            assert_eq!(count, 31);
+            assert!(db.file_mgr().length("t2.tbl").unwrap() > num_blocks);
+            {
+                let plan = planner
+                    .create_query_plan("select A, B, C from T2", tx.clone())
+                    .unwrap();
+                let mut scan = plan.open(tx.clone()).unwrap();
+                let mut count = 0;
+                while scan.next().unwrap() {
+                    let a = scan.get_i32("a").unwrap();
+                    assert_eq!(scan.get_i32("c").unwrap(), a);
+                    if a != 100 {
+                        assert_eq!(scan.get_string("b").unwrap(), format!("rec{}", a));
+                    }
+                    count += 1;
+                }
+                scan.close();
+                assert_eq!(count, 31);
+            }
+            {
+                let indexes = mdm.table_index_info("t2", tx.clone()).unwrap();
+                let mut idx = indexes.get("a").unwrap().open(tx.clone()).unwrap();
+                let layout = mdm.table_layout("t2", tx.clone()).unwrap();
+                let mut scan = TableScan::new(tx.clone(), "t2".into(), layout).unwrap();
+                idx.before_first(&Constant::Int(3)).unwrap();
+                assert!(idx.next().unwrap());
+                scan.move_to_rid(idx.get_data_rid().unwrap()).unwrap();
+                assert_eq!(scan.get_i32("c").unwrap(), 3);
+                assert!(!idx.next().unwrap());
+                idx.close();
+                scan.close();
+            }
+            tx.lock().unwrap().commit().unwrap();
+
+            let tx = db.new_tx();
+            assert!(matches!(
+                planner.execute_update("alter table T2 drop A", tx.clone()),
+                Err(PlannerError::Metadata(MetadataError::FieldIndexed(..)))
+            ));
+            assert!(matches!(
+                planner.execute_update("alter table T2 add C int", tx.clone()),
+                Err(PlannerError::Metadata(MetadataError::FieldAlreadyExists(
+                    ..
+                )))
+            ));
+            planner
+                .execute_update("alter table T2 drop B", tx.clone())
+                .unwrap();
+            planner
+                .execute_update("alter table T2 add B int", tx.clone())
+                .unwrap();
+            let layout = mdm.table_layout("t2", tx.clone()).unwrap();
+            assert_eq!(layout.version(), 3);
+            {
+                let plan = planner
+                    .create_query_plan("select A, B from T2 where A = 10", tx.clone())
+                    .unwrap();
+                let mut scan = plan.open(tx.clone()).unwrap();
+                assert!(scan.next().unwrap());
+                assert_eq!(scan.get_i32("b").unwrap(), 0);
+                assert!(!scan.next().unwrap());
+                scan.close();
+            }
+            tx.lock().unwrap().commit().unwrap();
+        }
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_drop_table() {
+        let dir = tempdir().unwrap();
+        {
+            let mut db = SimpleDB::new_for_test(dir.path(), "planner_test_drop_table.log");
+            db.init().unwrap();
+
+            let planner = db.planner();
+            let mdm = db.metadata_mgr();
+            let tx = db.new_tx();
+            for cmd in [
+                "create table T3(A int, B varchar(9))",
+                "create index idx_t3_a on T3(A)",
+                "insert into T3(A, B) values (1, 'one')",
+                "create view V3 as select A from T3",
+                "create view W3 as select A from V3",
+            ] {
+                planner.execute_update(cmd, tx.clone()).unwrap();
+            }
+            tx.lock().unwrap().commit().unwrap();
+
+            let tx = db.new_tx();
+            assert!(matches!(
+                planner.execute_update("drop table T3", tx.clone()),
+                Err(PlannerError::Metadata(MetadataError::DependentView(v, t))) if v == "v3" && t == "t3"
+            ));
+            assert!(matches!(
+                planner.execute_update("drop view V3 restrict", tx.clone()),
+                Err(PlannerError::Metadata(MetadataError::DependentView(v, t))) if v == "w3" && t == "v3"
+            ));
+            assert!(mdm.table_layout("t3", tx.clone()).is_ok());
+            assert!(mdm.view_def("v3", tx.clone()).is_ok());
+
+            planner
+                .execute_update("drop table T3 cascade", tx.clone())
+                .unwrap();
+            assert!(matches!(
+                mdm.table_layout("t3", tx.clone()),
+                Err(MetadataError::TableNotFound(_))
+            ));
+            assert!(mdm.view_def("v3", tx.clone()).is_err());
+            assert!(mdm.view_def("w3", tx.clone()).is_err());
+            assert!(mdm.table_index_info("t3", tx.clone()).unwrap().is_empty());
+            tx.lock().unwrap().commit().unwrap();
+            assert_eq!(db.file_mgr().length("t3.tbl").unwrap(), 0);
+            assert_eq!(db.file_mgr().length("idx_t3_aleaf.tbl").unwrap(), 0);
+
+            let tx = db.new_tx();
+            planner
+                .execute_update("create table T3(C int)", tx.clone())
+                .unwrap();
+            {
+                let plan = planner
+                    .create_query_plan("select C from T3", tx.clone())
+                    .unwrap();
+                let mut scan = plan.open(tx.clone()).unwrap();
+                assert!(!scan.next().unwrap());
+                scan.close();
+            }
+            planner
+                .execute_update("create view V3 as select C from T3", tx.clone())
+                .unwrap();
+            planner.execute_update("drop view V3", tx.clone()).unwrap();
+            assert!(matches!(
+                planner.execute_update("drop view V3", tx.clone()),
+                Err(PlannerError::Metadata(MetadataError::ViewNotFound(_)))
+            ));
+            tx.lock().unwrap().commit().unwrap();
+        }
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_rename_table() {
+        let dir = tempdir().unwrap();
+        {
+            let mut db = SimpleDB::new_for_test(dir.path(), "planner_test_rename_table.log");
+            db.init().unwrap();
+
+            let planner = db.planner();
+            let mdm = db.metadata_mgr();
+            let tx = db.new_tx();
+            for cmd in [
+                "create table T4(A int, B varchar(9))",
+                "create index idx_t4_a on T4(A)",
+                "insert into T4(A, B) values (1, 'one')",
+                "insert into T4(A, B) values (2, 'two')",
+                "create table T6(C int)",
+            ] {
+                planner.execute_update(cmd, tx.clone()).unwrap();
+            }
+            tx.lock().unwrap().commit().unwrap();
+
+            let select_two = |table: &str, tx: Arc<Mutex<Transaction>>| {
+                let query = format!("select B from {} where A = 2", table);
+                let plan = planner.create_query_plan(&query, tx.clone()).unwrap();
+                let mut scan = plan.open(tx).unwrap();
+                let mut values = Vec::new();
+                while scan.next().unwrap() {
+                    values.push(scan.get_string("b").unwrap());
+                }
+                scan.close();
+                values
+            };
+
+            let tx = db.new_tx();
+            planner
+                .execute_update("create view V4 as select A from T4", tx.clone())
+                .unwrap();
+            assert!(matches!(
+                planner.execute_update("rename table T4 to T5", tx.clone()),
+                Err(PlannerError::Metadata(MetadataError::DependentView(v, t))) if v == "v4" && t == "t4"
+            ));
+            tx.lock().unwrap().rollback().unwrap();
+
+            let tx = db.new_tx();
+            assert!(matches!(
+                planner.execute_update("rename table T4 to T6", tx.clone()),
+                Err(PlannerError::Metadata(MetadataError::TableAlreadyExists(t))) if t == "t6"
+            ));
+            planner
+                .execute_update("rename table T4 to T5", tx.clone())
+                .unwrap();
+            assert!(matches!(
+                mdm.table_layout("t4", tx.clone()),
+                Err(MetadataError::TableNotFound(_))
+            ));
+            assert!(mdm
+                .table_index_info("t5", tx.clone())
+                .unwrap()
+                .contains_key("a"));
+            assert_eq!(select_two("T5", tx.clone()), vec!["two"]);
+            tx.lock().unwrap().rollback().unwrap();
+
+            assert_eq!(db.file_mgr().length("t5.tbl").unwrap(), 0);
+            let tx = db.new_tx();
+            assert_eq!(select_two("T4", tx.clone()), vec!["two"]);
+            planner
+                .execute_update("rename table T4 to T5", tx.clone())
+                .unwrap();
+            tx.lock().unwrap().commit().unwrap();
+
+            let tx = db.new_tx();
+            assert_eq!(select_two("T5", tx.clone()), vec!["two"]);
+            planner
+                .execute_update("insert into T5(A, B) values (2, 'deux')", tx.clone())
+                .unwrap();
+            assert_eq!(select_two("T5", tx.clone()), vec!["two", "deux"]);
+            let layout = mdm.table_layout("t5", tx.clone()).unwrap();
+            let si = mdm.table_stat_info("t5", layout, tx.clone()).unwrap();
+            assert_eq!(si.records_output(), 3);
+            tx.lock().unwrap().commit().unwrap();
+        }
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_index_update_planner_with_hash_index() {
+        let dir = tempdir().unwrap();
+        {
+            let mut db = SimpleDB::new_for_test(dir.path(), "planner_test_hash_index.log");
+            db.init().unwrap();
+
+            let planner = db.planner();
+            let mdm = db.metadata_mgr();
+            let tx = db.new_tx();
+            {
+                planner
+                    .execute_update("create table T1(A int, B varchar(9))", tx.clone())
+                    .unwrap();
+                for i in 0..10 {
+                    let cmd = format!("insert into T1(A, B) values ({}, 'rec{}')", i, i % 3);
+                    planner.execute_update(&cmd, tx.clone()).unwrap();
+                }
+                planner
+                    .execute_update("create index idx_b on T1(B) using hash", tx.clone())
+                    .unwrap();
+                planner
+                    .execute_update("insert into T1(A, B) values (10, 'rec1')", tx.clone())
+                    .unwrap();
+
+                let indexes = mdm.table_index_info("t1", tx.clone()).unwrap();
+                let ii = indexes.get("b").unwrap();
+                assert_eq!(ii.index_type(), IndexType::Hash);
+                assert_eq!(ii.index_stat_info(), None);
+                assert_eq!(ii.buckets(), Some(DEFAULT_BUCKETS));
+
+                let mut idx = ii.open(tx.clone()).unwrap();
+                let mut count = 0;
+                idx.before_first(&Constant::String("rec1".into())).unwrap();
+                while idx.next().unwrap() {
+                    count += 1;
+                }
+                idx.close();
+                assert_eq!(count, 4); // NOTE: A = 1, 4, 7 and 10
+
+                planner
+                    .execute_update("create index idx_a on T1(A) using hash(4)", tx.clone())
+                    .unwrap();
+                planner
+                    .execute_update("insert into T1(A, B) values (11, 'rec2')", tx.clone())
+                    .unwrap();
+                let indexes = mdm.table_index_info("t1", tx.clone()).unwrap();
+                let ii = indexes.get("a").unwrap();
+                assert_eq!(ii.buckets(), Some(4));
+                assert_eq!(ii.filenames().len(), 4);
+                let mut idx = ii.open(tx.clone()).unwrap();
+                for a in 0..12 {
+                    idx.before_first(&Constant::Int(a)).unwrap();
+                    assert!(idx.next().unwrap());
+                    assert!(!idx.next().unwrap());
+                }
+                idx.close();
+                assert_eq!(indexes.get("b").unwrap().buckets(), Some(DEFAULT_BUCKETS));
+            }
+            tx.lock().unwrap().commit().unwrap();
+        }
+        {
+            let mut db = SimpleDB::new_for_test(dir.path(), "planner_test_hash_index.log");
+            db.init().unwrap();
+            let tx = db.new_tx();
+            let indexes = db
+                .metadata_mgr()
+                .table_index_info("t1", tx.clone())
+                .unwrap();
+            let ii = indexes.get("a").unwrap();
+            assert_eq!(ii.buckets(), Some(4));
+            let mut idx = ii.open(tx.clone()).unwrap();
+            for a in 0..12 {
+                idx.before_first(&Constant::Int(a)).unwrap();
+                assert!(idx.next().unwrap());
+            }
+            idx.close();
+            tx.lock().unwrap().commit().unwrap();
+        }
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_timed() {
+        let dir = tempdir().unwrap();
+        {
+            let mut db = SimpleDB::new_for_test(dir.path(), "planner_test_timed.log");
+            db.init().unwrap();
+
+            let planner = db.planner();
+            let tx = db.new_tx();
+            {
+                planner
+                    .execute_update("create table T(A int)", tx.clone())
+                    .unwrap();
+                let start = Instant::now();
+                let (count, timing) = planner
+                    .execute_update_timed("insert into T(A) values (1)", tx.clone())
+                    .unwrap();
+                assert_eq!(count, 1);
+                assert!(timing.parse + timing.plan <= start.elapsed());
+
+                let start = Instant::now();
+                let (plan, timing) = planner
+                    .create_query_plan_timed("select A from T", tx.clone())
+                    .unwrap();
+                assert!(timing.parse + timing.plan <= start.elapsed());
+                let mut scan = plan.open(tx.clone()).unwrap();
+                assert!(scan.next().unwrap());
+                assert_eq!(scan.get_i32("a").unwrap(), 1);
+                scan.close();
+
+                let stats = planner.stats();
+                assert!(planner
+                    .create_query_plan_timed("select A from", tx.clone())
+                    .is_err());
+                assert!(planner
+                    .execute_update_timed("insert into T(A)", tx.clone())
+                    .is_err());
+                assert_eq!(planner.stats().queries, stats.queries + 1);
+                assert_eq!(planner.stats().updates, stats.updates + 1);
+            }
+            tx.lock().unwrap().commit().unwrap();
         }
+        dir.close().unwrap();
     }
 }
@@ -0,0 +1,117 @@
+// Copyright (c) 2023 Sho Kuroda <krdlab@gmail.com>
+//
+// This software is released under the MIT License.
+// https://opensource.org/licenses/MIT
+
+use super::{predicate::Constant, scan::Result};
+
+#[derive(Debug, Clone)]
+pub struct RowBatch {
+    fields: Vec<String>,
+    columns: Vec<Vec<Constant>>,
+    capacity: usize,
+}
+
+impl RowBatch {
+    pub fn new(fields: Vec<String>, capacity: usize) -> Self {
+        assert!(capacity > 0);
+        let columns = fields
+            .iter()
+            .map(|_| Vec::with_capacity(capacity))
+            .collect();
+        Self {
+            fields,
+            columns,
+            capacity,
+        }
+    }
+
+    pub fn fields(&self) -> &[String] {
+        &self.fields
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn set_capacity(&mut self, capacity: usize) {
+        assert!(capacity > 0);
+        self.capacity = capacity;
+    }
+
+    pub fn len(&self) -> usize {
+        self.columns.first().map_or(0, Vec::len)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len() >= self.capacity
+    }
+
+    pub fn clear(&mut self) {
+        self.columns.iter_mut().for_each(Vec::clear);
+    }
+
+    pub fn column(&self, field_name: &str) -> Option<&[Constant]> {
+        let i = self.fields.iter().position(|f| f == field_name)?;
+        Some(&self.columns[i])
+    }
+
+    pub fn value(&self, row: usize, field_name: &str) -> Option<&Constant> {
+        self.column(field_name)?.get(row)
+    }
+
+    pub fn push_with<F: FnMut(&str) -> Result<Constant>>(&mut self, mut get: F) -> Result<()> {
+        let mut row = Vec::with_capacity(self.fields.len());
+        for f in self.fields.iter() {
+            row.push(get(f)?);
+        }
+        for (column, val) in self.columns.iter_mut().zip(row) {
+            column.push(val);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RowBatch;
+    use crate::query::{predicate::Constant, scan::ScanError};
+
+    #[test]
+    fn test_push_and_clear() {
+        let mut batch = RowBatch::new(vec!["A".into(), "B".into()], 2);
+        assert!(batch.is_empty());
+
+        for i in 0..2 {
+            batch
+                .push_with(|f| match f {
+                    "A" => Ok(Constant::Int(i)),
+                    _ => Ok(Constant::String(format!("rec{i}"))),
+                })
+                .unwrap();
+        }
+        assert!(batch.is_full());
+        assert_eq!(
+            batch.column("A").unwrap(),
+            [Constant::Int(0), Constant::Int(1)]
+        );
+        assert_eq!(batch.value(1, "B"), Some(&Constant::String("rec1".into())));
+        assert_eq!(batch.value(2, "B"), None);
+        assert_eq!(batch.column("C"), None);
+
+        let err = batch.push_with(|f| match f {
+            "A" => Ok(Constant::Int(2)),
+            _ => Err(ScanError::FieldNotFound(f.into())),
+        });
+        assert!(matches!(err, Err(ScanError::FieldNotFound(f)) if f == "B"));
+        assert_eq!(batch.len(), 2);
+
+        batch.clear();
+        assert!(batch.is_empty());
+        assert_eq!(batch.fields(), ["A", "B"]);
+    }
+}
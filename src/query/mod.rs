@@ -3,6 +3,8 @@
 // This software is released under the MIT License.
 // https://opensource.org/licenses/MIT
 
+pub mod batch;
 pub mod operators;
 pub mod predicate;
+pub mod row;
 pub mod scan;
@@ -4,31 +4,39 @@
 // https://opensource.org/licenses/MIT
 
 use super::{
-    predicate::{Constant, Predicate},
+    batch::RowBatch,
+    predicate::{Constant, Predicate, Term},
     scan::{Result, Scan, ScanError, UpdateScan, RID},
 };
+use crate::record::schema::{Schema, SqlType};
 
 // select operator
 
-pub struct SelectScan<'s> {
-    scan: Box<dyn UpdateScan + 's>,
+pub struct SelectScan {
+    scan: Box<dyn UpdateScan>,
     pred: Predicate,
+    // NOTE: with the fields of the predicate
+    batch: Option<RowBatch>,
 }
 
-impl<'s> SelectScan<'s> {
-    pub fn new(scan: Box<dyn UpdateScan + 's>, pred: Predicate) -> Self {
-        Self { scan, pred }
+impl SelectScan {
+    pub fn new(scan: Box<dyn UpdateScan>, pred: Predicate) -> Self {
+        Self {
+            scan,
+            pred,
+            batch: None,
+        }
     }
 }
 
-impl<'s> Scan for SelectScan<'s> {
+impl Scan for SelectScan {
     fn before_first(&mut self) -> Result<()> {
         self.scan.before_first()
     }
 
     fn next(&mut self) -> Result<bool> {
         while self.scan.next()? {
-            if self.pred.is_satisfied(&self.scan) {
+            if self.pred.is_satisfied(self.scan.as_ref()) {
                 return Ok(true);
             }
         }
@@ -43,20 +51,62 @@ impl<'s> Scan for SelectScan<'s> {
         self.scan.get_string(field_name)
     }
 
+    fn read_string(&self, field_name: &str, buf: &mut String) -> super::scan::Result<()> {
+        self.scan.read_string(field_name, buf)
+    }
+
     fn get_val(&self, field_name: &str) -> super::scan::Result<Constant> {
         self.scan.get_val(field_name)
     }
 
+    fn next_batch(&mut self, batch: &mut RowBatch) -> Result<bool> {
+        batch.clear();
+        let mut inner = match self.batch.take() {
+            Some(inner) if inner.fields().starts_with(batch.fields()) => inner,
+            _ => {
+                let mut fields = batch.fields().to_vec();
+                for f in self.pred.field_names() {
+                    if !fields.contains(f) {
+                        fields.push(f.clone());
+                    }
+                }
+                RowBatch::new(fields, batch.capacity())
+            }
+        };
+        // NOTE: so that no row is skipped
+        while !batch.is_full() {
+            inner.set_capacity(batch.capacity() - batch.len());
+            if !self.scan.next_batch(&mut inner)? {
+                break;
+            }
+            for i in 0..inner.len() {
+                if self.pred.is_satisfied_with(&|f| inner.value(i, f)) {
+                    batch.push_with(|f| Ok(inner.value(i, f).unwrap().clone()))?;
+                }
+            }
+        }
+        self.batch = Some(inner);
+        Ok(!batch.is_empty())
+    }
+
     fn has_field(&self, field_name: &str) -> bool {
         self.scan.has_field(field_name)
     }
 
+    fn fields(&self) -> Vec<String> {
+        self.scan.fields()
+    }
+
+    fn field_type(&self, field_name: &str) -> Option<SqlType> {
+        self.scan.field_type(field_name)
+    }
+
     fn close(&mut self) {
         self.scan.close();
     }
 }
 
-impl<'s> UpdateScan for SelectScan<'s> {
+impl UpdateScan for SelectScan {
     fn set_val(&mut self, field_name: &str, value: Constant) -> super::scan::Result<()> {
         self.scan.set_val(field_name, value)
     }
@@ -88,18 +138,18 @@ impl<'s> UpdateScan for SelectScan<'s> {
 
 // project operator
 
-pub struct ProjectScan<'s> {
-    scan: Box<dyn UpdateScan + 's>,
+pub struct ProjectScan {
+    scan: Box<dyn UpdateScan>,
     fields: Vec<String>,
 }
 
-impl<'s> ProjectScan<'s> {
-    pub fn new(scan: Box<dyn UpdateScan + 's>, fields: Vec<String>) -> Self {
+impl ProjectScan {
+    pub fn new(scan: Box<dyn UpdateScan>, fields: Vec<String>) -> Self {
         Self { scan, fields }
     }
 }
 
-impl<'s> Scan for ProjectScan<'s> {
+impl Scan for ProjectScan {
     fn before_first(&mut self) -> Result<()> {
         self.scan.before_first()
     }
@@ -124,6 +174,14 @@ impl<'s> Scan for ProjectScan<'s> {
         }
     }
 
+    fn read_string(&self, field_name: &str, buf: &mut String) -> super::scan::Result<()> {
+        if self.has_field(field_name) {
+            self.scan.read_string(field_name, buf)
+        } else {
+            Err(ScanError::FieldNotFound(field_name.into()))
+        }
+    }
+
     fn get_val(&self, field_name: &str) -> super::scan::Result<Constant> {
         if self.has_field(field_name) {
             self.scan.get_val(field_name)
@@ -136,70 +194,105 @@ impl<'s> Scan for ProjectScan<'s> {
         self.fields.contains(&field_name.into())
     }
 
+    fn fields(&self) -> Vec<String> {
+        self.fields.clone()
+    }
+
+    fn field_type(&self, field_name: &str) -> Option<SqlType> {
+        if self.has_field(field_name) {
+            self.scan.field_type(field_name)
+        } else {
+            None
+        }
+    }
+
     fn close(&mut self) {
         self.scan.close()
     }
 }
 
-impl<'s> UpdateScan for ProjectScan<'s> {
-    fn set_val(&mut self, _field_name: &str, _value: Constant) -> Result<()> {
-        Err(ScanError::UnsupportedOperation("set_val".into()))
+// NOTE: only the projected fields can be set
+impl UpdateScan for ProjectScan {
+    fn set_val(&mut self, field_name: &str, value: Constant) -> Result<()> {
+        if self.has_field(field_name) {
+            self.scan.set_val(field_name, value)
+        } else {
+            Err(ScanError::FieldNotFound(field_name.into()))
+        }
     }
 
-    fn set_i32(&mut self, _field_name: &str, _value: i32) -> Result<()> {
-        Err(ScanError::UnsupportedOperation("set_i32".into()))
+    fn set_i32(&mut self, field_name: &str, value: i32) -> Result<()> {
+        if self.has_field(field_name) {
+            self.scan.set_i32(field_name, value)
+        } else {
+            Err(ScanError::FieldNotFound(field_name.into()))
+        }
     }
 
-    fn set_string(&mut self, _field_name: &str, _value: String) -> Result<()> {
-        Err(ScanError::UnsupportedOperation("set_string".into()))
+    fn set_string(&mut self, field_name: &str, value: String) -> Result<()> {
+        if self.has_field(field_name) {
+            self.scan.set_string(field_name, value)
+        } else {
+            Err(ScanError::FieldNotFound(field_name.into()))
+        }
     }
 
     fn insert(&mut self) -> Result<()> {
-        Err(ScanError::UnsupportedOperation("insert".into()))
+        self.scan.insert()
     }
 
     fn delete(&mut self) -> Result<()> {
-        Err(ScanError::UnsupportedOperation("delete".into()))
+        self.scan.delete()
     }
 
     fn get_rid(&self) -> Result<RID> {
-        Err(ScanError::UnsupportedOperation("get_rid".into()))
+        self.scan.get_rid()
     }
 
-    fn move_to_rid(&mut self, _rid: super::scan::RID) -> Result<()> {
-        Err(ScanError::UnsupportedOperation("move_to_rid".into()))
+    fn move_to_rid(&mut self, rid: super::scan::RID) -> Result<()> {
+        self.scan.move_to_rid(rid)
     }
 }
 
 // product operator
 
-pub struct ProductScan<'s> {
-    scan1: Box<dyn UpdateScan + 's>,
-    scan2: Box<dyn UpdateScan + 's>,
+pub struct ProductScan {
+    scan1: Box<dyn UpdateScan>,
+    scan2: Box<dyn UpdateScan>,
+    // NOTE: false if `scan1` is empty
+    has_more1: bool,
 }
 
-impl<'s> ProductScan<'s> {
-    pub fn new(scan1: Box<dyn UpdateScan + 's>, scan2: Box<dyn UpdateScan + 's>) -> Self {
-        let mut this = Self { scan1, scan2 };
+impl ProductScan {
+    pub fn new(scan1: Box<dyn UpdateScan>, scan2: Box<dyn UpdateScan>) -> Self {
+        let mut this = Self {
+            scan1,
+            scan2,
+            has_more1: false,
+        };
         this.before_first().unwrap(); // TODO
         this
     }
 }
 
-impl<'s> Scan for ProductScan<'s> {
+impl Scan for ProductScan {
     fn before_first(&mut self) -> Result<()> {
         self.scan1.before_first()?;
-        self.scan1.next()?;
+        self.has_more1 = self.scan1.next()?;
         self.scan2.before_first()?;
         Ok(())
     }
 
     fn next(&mut self) -> Result<bool> {
+        if !self.has_more1 {
+            return Ok(false);
+        }
         if self.scan2.next()? {
             Ok(true)
         } else {
             self.scan2.before_first()?;
-            Ok(self.scan2.next()? && self.scan1.next()?)
+            self.has_more1 = self.scan2.next()? && self.scan1.next()?;
+            Ok(self.has_more1)
         }
     }
 
@@ -219,6 +312,14 @@ impl<'s> Scan for ProductScan<'s> {
         }
     }
 
+    fn read_string(&self, field_name: &str, buf: &mut String) -> super::scan::Result<()> {
+        if self.scan1.has_field(field_name) {
+            self.scan1.read_string(field_name, buf)
+        } else {
+            self.scan2.read_string(field_name, buf)
+        }
+    }
+
     fn get_val(&self, field_name: &str) -> super::scan::Result<Constant> {
         if self.scan1.has_field(field_name) {
             self.scan1.get_val(field_name)
@@ -231,13 +332,31 @@ impl<'s> Scan for ProductScan<'s> {
         self.scan1.has_field(field_name) || self.scan2.has_field(field_name)
     }
 
+    fn fields(&self) -> Vec<String> {
+        let mut fields = self.scan1.fields();
+        for f in self.scan2.fields() {
+            if !fields.contains(&f) {
+                fields.push(f);
+            }
+        }
+        fields
+    }
+
+    fn field_type(&self, field_name: &str) -> Option<SqlType> {
+        if self.scan1.has_field(field_name) {
+            self.scan1.field_type(field_name)
+        } else {
+            self.scan2.field_type(field_name)
+        }
+    }
+
     fn close(&mut self) {
         self.scan1.close();
         self.scan2.close();
     }
 }
 
-impl<'s> UpdateScan for ProductScan<'s> {
+impl UpdateScan for ProductScan {
     fn set_val(&mut self, _field_name: &str, _value: Constant) -> Result<()> {
         Err(ScanError::UnsupportedOperation("set_val".into()))
     }
@@ -267,16 +386,332 @@ impl<'s> UpdateScan for ProductScan<'s> {
     }
 }
 
+// extend operator
+
+pub struct ExtendScan {
+    scan: Box<dyn UpdateScan>,
+    fields: Vec<(String, Term)>,
+}
+
+impl ExtendScan {
+    pub fn new(scan: Box<dyn UpdateScan>, fields: Vec<(String, Term)>) -> Self {
+        Self { scan, fields }
+    }
+
+    fn term(&self, field_name: &str) -> Option<&Term> {
+        self.fields
+            .iter()
+            .find(|(f, _)| f == field_name)
+            .map(|(_, t)| t)
+    }
+}
+
+impl Scan for ExtendScan {
+    fn before_first(&mut self) -> Result<()> {
+        self.scan.before_first()
+    }
+
+    fn next(&mut self) -> Result<bool> {
+        self.scan.next()
+    }
+
+    fn get_i32(&self, field_name: &str) -> super::scan::Result<i32> {
+        match self.get_val(field_name)? {
+            Constant::Int(n) => Ok(n),
+            val => Err(ScanError::TypeMismatch(field_name.into(), val)),
+        }
+    }
+
+    fn get_string(&self, field_name: &str) -> super::scan::Result<String> {
+        match self.get_val(field_name)? {
+            Constant::String(s) => Ok(s),
+            val => Err(ScanError::TypeMismatch(field_name.into(), val)),
+        }
+    }
+
+    fn get_val(&self, field_name: &str) -> super::scan::Result<Constant> {
+        match self.term(field_name) {
+            Some(t) => Ok(t.evaluate(self.scan.as_ref())),
+            None => self.scan.get_val(field_name),
+        }
+    }
+
+    fn has_field(&self, field_name: &str) -> bool {
+        self.term(field_name).is_some() || self.scan.has_field(field_name)
+    }
+
+    fn fields(&self) -> Vec<String> {
+        let mut fields = self.scan.fields();
+        for (f, _) in self.fields.iter() {
+            if !fields.contains(f) {
+                fields.push(f.clone());
+            }
+        }
+        fields
+    }
+
+    fn field_type(&self, field_name: &str) -> Option<SqlType> {
+        match self.term(field_name) {
+            Some(Term::Constant(Constant::Int(_))) | Some(Term::Negate(_)) => {
+                Some(SqlType::Integer)
+            }
+            Some(Term::Constant(Constant::String(_))) | Some(Term::Concat(_)) => {
+                Some(SqlType::VarChar)
+            }
+            Some(Term::FieldName(f)) => self.scan.field_type(f),
+            None => self.scan.field_type(field_name),
+        }
+    }
+
+    fn close(&mut self) {
+        self.scan.close()
+    }
+}
+
+// NOTE: the added fields can't be set
+impl UpdateScan for ExtendScan {
+    fn set_val(&mut self, field_name: &str, value: Constant) -> Result<()> {
+        match self.term(field_name) {
+            Some(_) => Err(ScanError::UnsupportedOperation(format!(
+                "set {}",
+                field_name
+            ))),
+            None => self.scan.set_val(field_name, value),
+        }
+    }
+
+    fn set_i32(&mut self, field_name: &str, value: i32) -> Result<()> {
+        self.set_val(field_name, Constant::Int(value))
+    }
+
+    fn set_string(&mut self, field_name: &str, value: String) -> Result<()> {
+        self.set_val(field_name, Constant::String(value))
+    }
+
+    fn insert(&mut self) -> Result<()> {
+        self.scan.insert()
+    }
+
+    fn delete(&mut self) -> Result<()> {
+        self.scan.delete()
+    }
+
+    fn get_rid(&self) -> Result<RID> {
+        self.scan.get_rid()
+    }
+
+    fn move_to_rid(&mut self, rid: RID) -> Result<()> {
+        self.scan.move_to_rid(rid)
+    }
+}
+
+// semi-join operator
+
+pub struct SemiJoinScan {
+    outer: Box<dyn UpdateScan>,
+    inner: Box<dyn UpdateScan>,
+    pred: Predicate,
+    negated: bool,
+}
+
+impl SemiJoinScan {
+    pub fn new(
+        outer: Box<dyn UpdateScan>,
+        inner: Box<dyn UpdateScan>,
+        pred: Predicate,
+        negated: bool,
+    ) -> Self {
+        Self {
+            outer,
+            inner,
+            pred,
+            negated,
+        }
+    }
+
+    fn has_match(&mut self) -> Result<bool> {
+        let (outer, inner) = (&self.outer, &self.inner);
+        let pred = self.pred.bound_with(&|f| match inner.has_field(f) {
+            true => None,
+            false => outer.get_val(f).ok(),
+        });
+        self.inner.before_first()?;
+        while self.inner.next()? {
+            if pred.is_satisfied(self.inner.as_ref()) {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+}
+
+impl Scan for SemiJoinScan {
+    fn before_first(&mut self) -> Result<()> {
+        self.outer.before_first()
+    }
+
+    fn next(&mut self) -> Result<bool> {
+        while self.outer.next()? {
+            if self.has_match()? != self.negated {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    fn get_i32(&self, field_name: &str) -> Result<i32> {
+        self.outer.get_i32(field_name)
+    }
+
+    fn get_string(&self, field_name: &str) -> Result<String> {
+        self.outer.get_string(field_name)
+    }
+
+    fn read_string(&self, field_name: &str, buf: &mut String) -> Result<()> {
+        self.outer.read_string(field_name, buf)
+    }
+
+    fn get_val(&self, field_name: &str) -> Result<Constant> {
+        self.outer.get_val(field_name)
+    }
+
+    fn has_field(&self, field_name: &str) -> bool {
+        self.outer.has_field(field_name)
+    }
+
+    fn fields(&self) -> Vec<String> {
+        self.outer.fields()
+    }
+
+    fn field_type(&self, field_name: &str) -> Option<SqlType> {
+        self.outer.field_type(field_name)
+    }
+
+    fn close(&mut self) {
+        self.outer.close();
+        self.inner.close();
+    }
+}
+
+impl UpdateScan for SemiJoinScan {
+    fn set_val(&mut self, field_name: &str, value: Constant) -> Result<()> {
+        self.outer.set_val(field_name, value)
+    }
+
+    fn set_i32(&mut self, field_name: &str, value: i32) -> Result<()> {
+        self.outer.set_i32(field_name, value)
+    }
+
+    fn set_string(&mut self, field_name: &str, value: String) -> Result<()> {
+        self.outer.set_string(field_name, value)
+    }
+
+    fn insert(&mut self) -> Result<()> {
+        self.outer.insert()
+    }
+
+    fn delete(&mut self) -> Result<()> {
+        self.outer.delete()
+    }
+
+    fn get_rid(&self) -> Result<RID> {
+        self.outer.get_rid()
+    }
+
+    fn move_to_rid(&mut self, rid: RID) -> Result<()> {
+        self.outer.move_to_rid(rid)
+    }
+}
+
+// empty operator
+
+pub struct EmptyScan {
+    schema: Schema,
+}
+
+impl EmptyScan {
+    pub fn new(schema: Schema) -> Self {
+        Self { schema }
+    }
+}
+
+impl Scan for EmptyScan {
+    fn before_first(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<bool> {
+        Ok(false)
+    }
+
+    fn get_i32(&self, _field_name: &str) -> Result<i32> {
+        Err(ScanError::NoCurrentRecord)
+    }
+
+    fn get_string(&self, _field_name: &str) -> Result<String> {
+        Err(ScanError::NoCurrentRecord)
+    }
+
+    fn get_val(&self, _field_name: &str) -> Result<Constant> {
+        Err(ScanError::NoCurrentRecord)
+    }
+
+    fn has_field(&self, field_name: &str) -> bool {
+        self.schema.has_field(field_name)
+    }
+
+    fn fields(&self) -> Vec<String> {
+        self.schema.fields_iter().cloned().collect()
+    }
+
+    fn field_type(&self, field_name: &str) -> Option<SqlType> {
+        self.schema.field_type(field_name)
+    }
+
+    fn close(&mut self) {}
+}
+
+impl UpdateScan for EmptyScan {
+    fn set_val(&mut self, _field_name: &str, _value: Constant) -> Result<()> {
+        Err(ScanError::NoCurrentRecord)
+    }
+
+    fn set_i32(&mut self, _field_name: &str, _value: i32) -> Result<()> {
+        Err(ScanError::NoCurrentRecord)
+    }
+
+    fn set_string(&mut self, _field_name: &str, _value: String) -> Result<()> {
+        Err(ScanError::NoCurrentRecord)
+    }
+
+    fn insert(&mut self) -> Result<()> {
+        Err(ScanError::UnsupportedOperation("insert".into()))
+    }
+
+    fn delete(&mut self) -> Result<()> {
+        Err(ScanError::NoCurrentRecord)
+    }
+
+    fn get_rid(&self) -> Result<RID> {
+        Err(ScanError::NoCurrentRecord)
+    }
+
+    fn move_to_rid(&mut self, _rid: RID) -> Result<()> {
+        Err(ScanError::UnsupportedOperation("move_to_rid".into()))
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{ProductScan, ProjectScan, SelectScan};
+    use super::{ExtendScan, ProductScan, ProjectScan, SelectScan};
     use crate::{
         query::{
+            batch::RowBatch,
             predicate::{Constant, Expression, Predicate, Term},
-            scan::Scan,
+            scan::{Scan, ScanError},
         },
         record::{
-            schema::{Layout, Schema},
+            schema::{Layout, Schema, SqlType},
             table_scan::TableScan,
         },
         server::simple_db::SimpleDB,
@@ -297,7 +732,7 @@ mod tests {
 
             let tx = db.new_tx();
             {
-                let mut s1 = TableScan::new(tx.clone(), "T".into(), layout.clone());
+                let mut s1 = TableScan::new(tx.clone(), "T".into(), layout.clone()).unwrap();
                 s1.before_first().unwrap();
                 for i in 0..200 {
                     s1.insert().unwrap();
@@ -306,7 +741,7 @@ mod tests {
                 }
             }
             {
-                let s2 = Box::new(TableScan::new(tx.clone(), "T".into(), layout.clone()));
+                let s2 = Box::new(TableScan::new(tx.clone(), "T".into(), layout.clone()).unwrap());
                 let pred = {
                     let c = Constant::Int(10);
                     let t = Expression::new(Term::FieldName("A".into()), Term::Constant(c));
@@ -321,7 +756,144 @@ mod tests {
                 assert_eq!(s4.get_string("B").unwrap(), "rec10");
                 assert!(!s4.next().unwrap());
             }
-            tx.borrow_mut().commit().unwrap();
+            tx.lock().unwrap().commit().unwrap();
+        }
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_next_batch() {
+        let dir = tempdir().unwrap();
+        {
+            let db = SimpleDB::new_for_test(dir.path(), "operators_test_next_batch.log");
+            let layout = {
+                let mut schema = Schema::new();
+                schema.add_i32_field("A");
+                schema.add_string_field("B", 9);
+                Layout::new(schema)
+            };
+
+            let tx = db.new_tx();
+            {
+                let mut s1 = TableScan::new(tx.clone(), "T".into(), layout.clone()).unwrap();
+                s1.insert_rows(
+                    (0..200)
+                        .map(|i| vec![Constant::Int(i % 10), Constant::String(format!("rec{i}"))]),
+                )
+                .unwrap();
+            }
+            {
+                let s2 = Box::new(TableScan::new(tx.clone(), "T".into(), layout.clone()).unwrap());
+                let pred = {
+                    let c = Constant::Int(3);
+                    let t = Expression::new(Term::FieldName("A".into()), Term::Constant(c));
+                    Predicate::new(t)
+                };
+                let mut s3 = SelectScan::new(s2, pred);
+                s3.before_first().unwrap();
+
+                let mut batch = RowBatch::new(vec!["B".into()], 6);
+                let mut found = Vec::new();
+                while s3.next_batch(&mut batch).unwrap() {
+                    assert!(batch.len() == 6 || found.len() + batch.len() == 20);
+                    found.extend(batch.column("B").unwrap().iter().cloned());
+                }
+                let expected = (0..20)
+                    .map(|i| Constant::String(format!("rec{}", i * 10 + 3)))
+                    .collect::<Vec<_>>();
+                assert_eq!(found, expected);
+
+                let mut s4 = ProjectScan::new(Box::new(s3), vec!["B".into()]);
+                s4.before_first().unwrap();
+                assert!(s4.next_batch(&mut batch).unwrap());
+                assert_eq!(batch.column("B").unwrap(), &expected[..6]);
+            }
+            tx.lock().unwrap().commit().unwrap();
+        }
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_fields() {
+        let dir = tempdir().unwrap();
+        {
+            let db = SimpleDB::new_for_test(dir.path(), "operators_test_fields.log");
+            let layout1 = {
+                let mut schema = Schema::new();
+                schema.add_i32_field("A");
+                schema.add_string_field("B", 9);
+                Layout::new(schema)
+            };
+            let layout2 = {
+                let mut schema = Schema::new();
+                schema.add_i32_field("B");
+                schema.add_i32_field("C");
+                Layout::new(schema)
+            };
+
+            let tx = db.new_tx();
+            {
+                let s1 = Box::new(TableScan::new(tx.clone(), "T1".into(), layout1).unwrap());
+                let s2 = Box::new(TableScan::new(tx.clone(), "T2".into(), layout2).unwrap());
+                // NOTE: B of T2 is hidden by that of T1
+                let s3 = Box::new(ProductScan::new(s1, s2));
+                assert_eq!(s3.fields(), ["A", "B", "C"]);
+                assert_eq!(s3.field_type("B"), Some(SqlType::VarChar));
+                assert_eq!(s3.field_type("C"), Some(SqlType::Integer));
+
+                let s4 = Box::new(ExtendScan::new(
+                    s3,
+                    vec![
+                        ("D".into(), Term::Concat(vec![Term::FieldName("A".into())])),
+                        ("E".into(), Term::FieldName("C".into())),
+                    ],
+                ));
+                assert_eq!(s4.fields(), ["A", "B", "C", "D", "E"]);
+                assert_eq!(s4.field_type("D"), Some(SqlType::VarChar));
+                assert_eq!(s4.field_type("E"), Some(SqlType::Integer));
+
+                let s5 = ProjectScan::new(s4, vec!["E".into(), "B".into()]);
+                assert_eq!(s5.fields(), ["E", "B"]);
+                assert_eq!(s5.field_type("B"), Some(SqlType::VarChar));
+                assert_eq!(s5.field_type("A"), None);
+            }
+            tx.lock().unwrap().commit().unwrap();
+        }
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_product_of_empty() {
+        let dir = tempdir().unwrap();
+        {
+            let db = SimpleDB::new_for_test(dir.path(), "operators_test_product_of_empty.log");
+            let tx = db.new_tx();
+            {
+                let layout = |field: &str| {
+                    let mut schema = Schema::new();
+                    schema.add_i32_field(field);
+                    Layout::new(schema)
+                };
+                {
+                    let mut ts = TableScan::new(tx.clone(), "T2".into(), layout("B")).unwrap();
+                    for i in 0..3 {
+                        ts.insert().unwrap();
+                        ts.set_i32("B", i).unwrap();
+                    }
+                }
+
+                let s1 = Box::new(TableScan::new(tx.clone(), "T1".into(), layout("A")).unwrap());
+                let s2 = Box::new(TableScan::new(tx.clone(), "T2".into(), layout("B")).unwrap());
+                let mut s3 = ProductScan::new(s1, s2);
+                assert!(!s3.next().unwrap());
+                assert!(matches!(s3.get_i32("A"), Err(ScanError::NoCurrentRecord)));
+
+                let s1 = Box::new(TableScan::new(tx.clone(), "T2".into(), layout("B")).unwrap());
+                let s2 = Box::new(TableScan::new(tx.clone(), "T1".into(), layout("A")).unwrap());
+                let mut s3 = ProductScan::new(s1, s2);
+                assert!(!s3.next().unwrap());
+            }
+            tx.lock().unwrap().commit().unwrap();
         }
         dir.close().unwrap();
     }
@@ -339,7 +911,7 @@ mod tests {
                 schema1.add_string_field("B", 9);
                 let layout1 = Layout::new(schema1);
                 {
-                    let mut us1 = TableScan::new(tx.clone(), "T1".into(), layout1.clone());
+                    let mut us1 = TableScan::new(tx.clone(), "T1".into(), layout1.clone()).unwrap();
                     us1.before_first().unwrap();
 
                     for i in 0..200 {
@@ -354,7 +926,7 @@ mod tests {
                 schema2.add_string_field("D", 9);
                 let layout2 = Layout::new(schema2);
                 {
-                    let mut us2 = TableScan::new(tx.clone(), "T2".into(), layout2.clone());
+                    let mut us2 = TableScan::new(tx.clone(), "T2".into(), layout2.clone()).unwrap();
                     us2.before_first().unwrap();
                     for i in 0..200 {
                         us2.insert().unwrap();
@@ -365,8 +937,10 @@ mod tests {
                 }
 
                 {
-                    let s1 = Box::new(TableScan::new(tx.clone(), "T1".into(), layout1.clone()));
-                    let s2 = Box::new(TableScan::new(tx.clone(), "T2".into(), layout2.clone()));
+                    let s1 =
+                        Box::new(TableScan::new(tx.clone(), "T1".into(), layout1.clone()).unwrap());
+                    let s2 =
+                        Box::new(TableScan::new(tx.clone(), "T2".into(), layout2.clone()).unwrap());
                     let s3 = Box::new(ProductScan::new(s1, s2));
 
                     let t =
@@ -381,7 +955,7 @@ mod tests {
                     }
                 }
             }
-            tx.borrow_mut().commit().unwrap();
+            tx.lock().unwrap().commit().unwrap();
         }
         dir.close().unwrap();
     }
@@ -3,9 +3,12 @@
 // This software is released under the MIT License.
 // https://opensource.org/licenses/MIT
 
-use std::fmt::Display;
+use std::{cmp::max, fmt::Display};
 
-use crate::{plan::plan::Plan, record::schema::Schema};
+use crate::{
+    plan::plan::Plan,
+    record::schema::{Collation, Schema, SqlType},
+};
 
 use super::scan::UpdateScan;
 
@@ -15,24 +18,111 @@ pub enum Constant {
     String(String),
 }
 
+impl Constant {
+    fn to_text(&self) -> String {
+        match self {
+            Self::Int(n) => n.to_string(),
+            Self::String(s) => s.clone(),
+        }
+    }
+
+    fn negated(self) -> Constant {
+        match self {
+            Self::Int(n) => Self::Int(n.wrapping_neg()),
+            Self::String(_) => self,
+        }
+    }
+}
+
+impl Display for Constant {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Int(n) => write!(f, "{}", n),
+            Self::String(s) => write!(f, "'{}'", s),
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Term {
     Constant(Constant),
     FieldName(String),
+    Concat(Vec<Term>),
+    Negate(Box<Term>),
 }
 
 impl Term {
-    pub fn evaluate<'s>(&self, s: &Box<dyn UpdateScan + 's>) -> Constant {
+    pub fn evaluate(&self, s: &dyn UpdateScan) -> Constant {
         match self {
             Self::Constant(val) => val.clone(),
             Self::FieldName(fname) => s.get_val(fname.as_str()).unwrap(),
+            Self::Concat(terms) => {
+                Constant::String(terms.iter().map(|t| t.evaluate(s).to_text()).collect())
+            }
+            Self::Negate(t) => t.evaluate(s).negated(),
+        }
+    }
+
+    pub fn evaluate_with<'a>(
+        &self,
+        get: &impl Fn(&str) -> Option<&'a Constant>,
+    ) -> Option<Constant> {
+        match self {
+            Self::Constant(val) => Some(val.clone()),
+            Self::FieldName(fname) => get(fname).cloned(),
+            Self::Concat(terms) => {
+                let mut s = String::new();
+                for t in terms {
+                    s.push_str(&t.evaluate_with(get)?.to_text());
+                }
+                Some(Constant::String(s))
+            }
+            Self::Negate(t) => Some(t.evaluate_with(get)?.negated()),
+        }
+    }
+
+    pub fn folded(&self) -> Term {
+        match self {
+            Self::Constant(_) | Self::FieldName(_) => self.clone(),
+            Self::Negate(t) => match t.folded() {
+                Term::Constant(val @ Constant::Int(_)) => Term::Constant(val.negated()),
+                Term::Negate(t) => *t,
+                t => Term::Negate(Box::new(t)),
+            },
+            Self::Concat(terms) => {
+                let mut folded: Vec<Term> = Vec::with_capacity(terms.len());
+                for t in terms.iter().map(Term::folded) {
+                    match (folded.last_mut(), t) {
+                        (Some(Term::Constant(prev)), Term::Constant(val)) => {
+                            *prev = Constant::String(prev.to_text() + &val.to_text());
+                        }
+                        (_, t) => folded.push(t),
+                    }
+                }
+                match folded.as_slice() {
+                    [Term::Constant(val)] => Term::Constant(Constant::String(val.to_text())),
+                    _ => Term::Concat(folded),
+                }
+            }
+        }
+    }
+
+    pub fn bound_with(&self, get: &impl Fn(&str) -> Option<Constant>) -> Term {
+        match self {
+            Self::Constant(_) => self.clone(),
+            Self::FieldName(fname) => match get(fname) {
+                Some(val) => Self::Constant(val),
+                None => self.clone(),
+            },
+            Self::Concat(terms) => Self::Concat(terms.iter().map(|t| t.bound_with(get)).collect()),
+            Self::Negate(t) => Self::Negate(Box::new(t.bound_with(get))),
         }
     }
 
     pub fn is_field_name(&self) -> bool {
         match self {
-            Self::Constant(_) => false,
             Self::FieldName(_) => true,
+            Self::Constant(_) | Self::Concat(_) | Self::Negate(_) => false,
         }
     }
 
@@ -40,6 +130,69 @@ impl Term {
         match self {
             Self::Constant(_) => true,
             Self::FieldName(fname) => schema.has_field(fname),
+            Self::Concat(terms) => terms.iter().all(|t| t.apply_to(schema)),
+            Self::Negate(t) => t.apply_to(schema),
+        }
+    }
+
+    pub fn collect_field_names<'a>(&'a self, names: &mut Vec<&'a String>) {
+        match self {
+            Self::Constant(_) => {}
+            Self::FieldName(fname) => {
+                if !names.contains(&fname) {
+                    names.push(fname);
+                }
+            }
+            Self::Concat(terms) => terms.iter().for_each(|t| t.collect_field_names(names)),
+            Self::Negate(t) => t.collect_field_names(names),
+        }
+    }
+
+    pub fn field_type(&self, schema: &Schema) -> Option<(SqlType, usize)> {
+        match self {
+            Self::Constant(Constant::Int(_)) => Some((SqlType::Integer, 0)),
+            Self::Constant(Constant::String(s)) => Some((SqlType::VarChar, s.chars().count())),
+            Self::FieldName(fname) => {
+                Some((schema.field_type(fname)?, schema.field_length(fname)?))
+            }
+            Self::Concat(terms) => {
+                let mut length = 0;
+                for t in terms {
+                    length += match t.field_type(schema)? {
+                        (SqlType::Integer, _) => INT_TEXT_LENGTH,
+                        (SqlType::VarChar, n) => n,
+                    };
+                }
+                Some((SqlType::VarChar, length))
+            }
+            Self::Negate(t) => t.field_type(schema),
+        }
+    }
+
+    pub fn string_negation(&self, schema: &Schema) -> Option<&Term> {
+        match self {
+            Self::Constant(_) | Self::FieldName(_) => None,
+            Self::Concat(terms) => terms.iter().find_map(|t| t.string_negation(schema)),
+            Self::Negate(t) => match t.field_type(schema) {
+                Some((SqlType::VarChar, _)) => Some(self),
+                _ => t.string_negation(schema),
+            },
+        }
+    }
+}
+
+const INT_TEXT_LENGTH: usize = 11;
+
+impl Display for Term {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Constant(val) => write!(f, "{}", val),
+            Self::FieldName(fname) => write!(f, "{}", fname),
+            Self::Concat(terms) => {
+                let s: Vec<String> = terms.iter().map(|t| t.to_string()).collect();
+                write!(f, "{}", s.join(" || "))
+            }
+            Self::Negate(t) => write!(f, "-{}", t),
         }
     }
 }
@@ -48,21 +201,79 @@ impl Term {
 pub struct Expression {
     lhs: Term,
     rhs: Term,
+    collation: Option<Collation>,
 }
 
 impl Expression {
     pub fn new(lhs: Term, rhs: Term) -> Self {
-        Self { lhs, rhs }
+        Self {
+            lhs,
+            rhs,
+            collation: None,
+        }
+    }
+
+    pub fn with_collation(self, collation: Collation) -> Self {
+        Self {
+            collation: Some(collation),
+            ..self
+        }
     }
 
-    pub fn is_satisfied<'s>(&self, s: &Box<dyn UpdateScan + 's>) -> bool {
+    fn resolve_collation(&self, schema: &Schema) -> Self {
+        let field_collation = |t: &Term| match t {
+            Term::FieldName(fname) => schema.field_collation(fname),
+            Term::Constant(_) | Term::Concat(_) | Term::Negate(_) => None,
+        };
+        let collation = self.collation.or_else(|| {
+            field_collation(&self.lhs)
+                .filter(|c| !c.is_binary())
+                .or_else(|| field_collation(&self.rhs))
+        });
+        Self {
+            collation,
+            ..self.clone()
+        }
+    }
+
+    fn truth(&self) -> Option<bool> {
+        match (&self.lhs, &self.rhs) {
+            (Term::Constant(lval), Term::Constant(rval)) => {
+                Some(self.collation.unwrap_or_default().equals(lval, rval))
+            }
+            (lhs, rhs) if lhs == rhs => Some(true),
+            _ => None,
+        }
+    }
+
+    pub fn is_satisfied(&self, s: &dyn UpdateScan) -> bool {
         let lval = self.lhs.evaluate(s);
         let rval = self.rhs.evaluate(s);
-        lval == rval
+        self.collation.unwrap_or_default().equals(&lval, &rval)
+    }
+
+    pub fn is_satisfied_with<'a>(&self, get: &impl Fn(&str) -> Option<&'a Constant>) -> bool {
+        match (self.lhs.evaluate_with(get), self.rhs.evaluate_with(get)) {
+            (Some(lval), Some(rval)) => self.collation.unwrap_or_default().equals(&lval, &rval),
+            _ => false,
+        }
     }
 
-    pub fn reduction_factor<'p>(&self, p: &Box<dyn Plan + 'p>) -> usize {
-        todo!()
+    pub fn reduction_factor(&self, p: &dyn Plan) -> usize {
+        let factor = match (&self.lhs, &self.rhs) {
+            (Term::FieldName(lhs), Term::FieldName(rhs)) => {
+                max(p.distinct_values(lhs), p.distinct_values(rhs))
+            }
+            (Term::FieldName(fname), _) | (_, Term::FieldName(fname)) => p.distinct_values(fname),
+            (Term::Constant(lval), Term::Constant(rval)) => {
+                match self.collation.unwrap_or_default().equals(lval, rval) {
+                    true => 1,
+                    false => usize::MAX,
+                }
+            }
+            _ => 1,
+        };
+        factor.max(1)
     }
 
     // F = c
@@ -105,11 +316,21 @@ impl Expression {
     pub fn apply_to(&self, schema: &Schema) -> bool {
         self.lhs.apply_to(schema) && self.rhs.apply_to(schema)
     }
+
+    pub fn type_mismatch(&self, schema: &Schema) -> Option<(SqlType, SqlType)> {
+        let (ltype, _) = self.lhs.field_type(schema)?;
+        let (rtype, _) = self.rhs.field_type(schema)?;
+        (ltype != rtype).then_some((ltype, rtype))
+    }
 }
 
 impl Display for Expression {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{:?} = {:?}", self.lhs, self.rhs)
+        write!(f, "{} = {}", self.lhs, self.rhs)?;
+        if let Some(collation) = self.collation {
+            write!(f, " collate {}", collation)?;
+        }
+        Ok(())
     }
 }
 
@@ -135,7 +356,35 @@ impl Predicate {
         self.exprs.append(&mut pred.exprs);
     }
 
-    pub fn is_satisfied<'s>(&self, scan: &Box<dyn UpdateScan + 's>) -> bool {
+    pub fn simplified(&self) -> Option<Predicate> {
+        let mut exprs: Vec<Expression> = Vec::new();
+        for e in self.exprs.iter() {
+            let e = Expression {
+                lhs: e.lhs.folded(),
+                rhs: e.rhs.folded(),
+                collation: e.collation,
+            };
+            match e.truth() {
+                Some(true) => {}
+                Some(false) => return None,
+                None if exprs.contains(&e) => {}
+                None => exprs.push(e),
+            }
+        }
+        Some(Predicate { exprs })
+    }
+
+    pub fn with_collations_of(&self, schema: &Schema) -> Predicate {
+        Predicate {
+            exprs: self
+                .exprs
+                .iter()
+                .map(|e| e.resolve_collation(schema))
+                .collect(),
+        }
+    }
+
+    pub fn is_satisfied(&self, scan: &dyn UpdateScan) -> bool {
         for t in self.exprs.iter() {
             if !t.is_satisfied(scan) {
                 return false;
@@ -144,10 +393,23 @@ impl Predicate {
         true
     }
 
-    pub fn reduction_factor<'p>(&self, p: &Box<dyn Plan + 'p>) -> usize {
-        let mut factor = 1;
+    pub fn is_satisfied_with<'a>(&self, get: &impl Fn(&str) -> Option<&'a Constant>) -> bool {
+        self.exprs.iter().all(|e| e.is_satisfied_with(get))
+    }
+
+    pub fn field_names(&self) -> Vec<&String> {
+        let mut names = Vec::new();
         for e in self.exprs.iter() {
-            factor *= e.reduction_factor(p);
+            e.lhs.collect_field_names(&mut names);
+            e.rhs.collect_field_names(&mut names);
+        }
+        names
+    }
+
+    pub fn reduction_factor(&self, p: &dyn Plan) -> usize {
+        let mut factor: usize = 1;
+        for e in self.exprs.iter() {
+            factor = factor.saturating_mul(e.reduction_factor(p));
         }
         factor
     }
@@ -166,6 +428,24 @@ impl Predicate {
         }
     }
 
+    pub fn partition(&self, schema: &Schema) -> (Predicate, Predicate) {
+        let (applied, rest) = self.exprs.iter().cloned().partition(|e| e.apply_to(schema));
+        (Predicate { exprs: applied }, Predicate { exprs: rest })
+    }
+
+    pub fn bound_with(&self, get: &impl Fn(&str) -> Option<Constant>) -> Predicate {
+        let exprs = self
+            .exprs
+            .iter()
+            .map(|e| Expression {
+                lhs: e.lhs.bound_with(get),
+                rhs: e.rhs.bound_with(get),
+                collation: e.collation,
+            })
+            .collect();
+        Predicate { exprs }
+    }
+
     pub fn join_sub_pred(&self, schema1: &Schema, schema2: &Schema) -> Option<Predicate> {
         let mut new_schema = Schema::new();
         new_schema.add_all(&schema1);
@@ -185,6 +465,21 @@ impl Predicate {
         }
     }
 
+    pub fn string_negation(&self, schema: &Schema) -> Option<&Term> {
+        self.exprs.iter().find_map(|e| {
+            e.lhs
+                .string_negation(schema)
+                .or_else(|| e.rhs.string_negation(schema))
+        })
+    }
+
+    pub fn type_mismatch(&self, schema: &Schema) -> Option<(&Expression, SqlType, SqlType)> {
+        self.exprs.iter().find_map(|e| {
+            e.type_mismatch(schema)
+                .map(|(ltype, rtype)| (e, ltype, rtype))
+        })
+    }
+
     pub fn equates_with_constant(&self, field_name: &str) -> Option<Constant> {
         for t in self.exprs.iter() {
             if let Some(c) = t.equates_with_constant(field_name) {
@@ -214,7 +509,10 @@ impl Display for Predicate {
 #[cfg(test)]
 mod tests {
     use super::Term;
-    use crate::{query::predicate::Expression, record::schema::Schema};
+    use crate::{
+        query::predicate::{Expression, Predicate},
+        record::schema::{Collation, Schema, SqlType},
+    };
 
     #[test]
     fn test_constant_partialeq() {
@@ -255,6 +553,58 @@ mod tests {
             let t = Term::FieldName("B".into());
             assert!(!t.apply_to(&schema));
         }
+        {
+            schema.add_string_field("S", 5);
+            let t = Term::Concat(vec![
+                Term::FieldName("S".into()),
+                Term::Constant(String("-".into())),
+                Term::FieldName("A".into()),
+            ]);
+            assert!(!t.is_field_name());
+            assert!(t.apply_to(&schema));
+            assert_eq!(t.field_type(&schema), Some((SqlType::VarChar, 5 + 1 + 11)));
+            assert_eq!(t.to_string(), "S || '-' || A");
+
+            let a = Int(-12);
+            let s = String("abc".into());
+            let get = |f: &str| match f {
+                "A" => Some(&a),
+                "S" => Some(&s),
+                _ => None,
+            };
+            assert_eq!(t.evaluate_with(&get), Some(String("abc--12".into())));
+            let t = Term::Concat(vec![
+                Term::FieldName("S".into()),
+                Term::FieldName("B".into()),
+            ]);
+            assert_eq!(t.evaluate_with(&get), None);
+            assert!(!t.apply_to(&schema));
+        }
+        {
+            let negate = |t: Term| Term::Negate(Box::new(t));
+            let t = negate(Term::FieldName("A".into()));
+            assert!(!t.is_field_name());
+            assert!(t.apply_to(&schema));
+            assert_eq!(t.field_type(&schema), Some((SqlType::Integer, 0)));
+            assert_eq!(t.to_string(), "-A");
+            assert_eq!(t.string_negation(&schema), None);
+            let a = Int(-12);
+            assert_eq!(t.evaluate_with(&|_| Some(&a)), Some(Int(12)));
+            let min = Int(i32::MIN);
+            assert_eq!(t.evaluate_with(&|_| Some(&min)), Some(min.clone()));
+
+            assert_eq!(negate(t.clone()).folded(), Term::FieldName("A".into()));
+            assert_eq!(
+                negate(Term::Constant(Int(5))).folded(),
+                Term::Constant(Int(-5))
+            );
+
+            let t = Term::Concat(vec![
+                Term::Constant(String("x".into())),
+                negate(Term::FieldName("S".into())),
+            ]);
+            assert_eq!(t.string_negation(&schema).unwrap().to_string(), "-S");
+        }
     }
 
     #[test]
@@ -278,6 +628,127 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_type_mismatch() {
+        use super::Constant::*;
+        let mut schema = Schema::new();
+        schema.add_i32_field("A");
+        schema.add_string_field("B", 9);
+        let field = |name: &str| Term::FieldName(name.into());
+
+        let expr = Expression::new(field("B"), Term::Constant(Int(5)));
+        assert_eq!(
+            expr.type_mismatch(&schema),
+            Some((SqlType::VarChar, SqlType::Integer))
+        );
+        let expr = Expression::new(field("A"), field("B"));
+        assert_eq!(
+            expr.type_mismatch(&schema),
+            Some((SqlType::Integer, SqlType::VarChar))
+        );
+        let expr = Expression::new(field("B"), Term::Concat(vec![field("A"), field("A")]));
+        assert_eq!(expr.type_mismatch(&schema), None);
+        let expr = Expression::new(field("A"), Term::Constant(Int(5)));
+        assert_eq!(expr.type_mismatch(&schema), None);
+        let expr = Expression::new(field("Z"), Term::Constant(Int(5)));
+        assert_eq!(expr.type_mismatch(&schema), None);
+
+        let mut pred = Predicate::new(Expression::new(field("A"), Term::Constant(Int(1))));
+        assert!(pred.type_mismatch(&schema).is_none());
+        pred.conjoin_with(Predicate::new(Expression::new(
+            field("B"),
+            Term::Constant(Int(2)),
+        )));
+        let (expr, ltype, rtype) = pred.type_mismatch(&schema).unwrap();
+        assert_eq!(
+            (expr.to_string(), ltype, rtype),
+            ("B = 2".into(), SqlType::VarChar, SqlType::Integer)
+        );
+    }
+
+    #[test]
+    fn test_expression_collation() {
+        use super::Constant::*;
+
+        let mut schema = Schema::new();
+        schema.add_string_field("A", 5);
+        schema.add_string_field("B", 5);
+        schema.set_collation("B", Collation::NoCase);
+
+        let a = String("abc".into());
+        let b = String("ABC".into());
+        let get = |f: &str| match f {
+            "A" => Some(&a),
+            "B" => Some(&b),
+            _ => None,
+        };
+        let expr = Expression::new(Term::FieldName("A".into()), Term::FieldName("B".into()));
+        assert!(!expr.is_satisfied_with(&get));
+
+        // NOTE: A is binary
+        let pred = Predicate::new(expr.clone()).with_collations_of(&schema);
+        assert!(pred.is_satisfied_with(&get));
+        assert_eq!(pred.to_string(), "A = B collate nocase");
+
+        let expr = expr.with_collation(Collation::Binary);
+        let pred = Predicate::new(expr.clone()).with_collations_of(&schema);
+        assert_eq!(pred.exprs[0].collation, Some(Collation::Binary));
+        assert!(!pred.is_satisfied_with(&get));
+
+        let expr = Expression::new(
+            Term::FieldName("A".into()),
+            Term::Constant(String("ABC".into())),
+        )
+        .with_collation(Collation::NoCase);
+        assert!(expr.is_satisfied_with(&get));
+    }
+
+    #[test]
+    fn test_simplified() {
+        use super::Constant::*;
+
+        let field = |f: &str| Term::FieldName(f.into());
+        let constant = |s: &str| Term::Constant(String(s.into()));
+        let pred_of = |exprs: Vec<Expression>| {
+            let mut pred = Predicate::empty();
+            for e in exprs {
+                pred.conjoin_with(Predicate::new(e));
+            }
+            pred
+        };
+
+        let concat = Term::Concat(vec![constant("a"), Term::Constant(Int(1)), field("F")]);
+        assert_eq!(
+            concat.folded(),
+            Term::Concat(vec![constant("a1"), field("F")])
+        );
+        let concat = Term::Concat(vec![Term::Constant(Int(1)), Term::Constant(Int(2))]);
+        assert_eq!(concat.folded(), constant("12"));
+
+        let pred = pred_of(vec![
+            Expression::new(Term::Constant(Int(1)), Term::Constant(Int(1))),
+            Expression::new(field("A"), field("A")),
+            Expression::new(field("A"), Term::Constant(Int(3))),
+            Expression::new(
+                Term::Concat(vec![constant("a"), constant("b")]),
+                constant("ab"),
+            ),
+            Expression::new(field("A"), Term::Constant(Int(3))),
+        ]);
+        assert_eq!(pred.simplified().unwrap().to_string(), "A = 3");
+
+        let pred = pred_of(vec![
+            Expression::new(field("A"), Term::Constant(Int(3))),
+            Expression::new(constant("x"), constant("X")),
+        ]);
+        assert_eq!(pred.simplified(), None);
+
+        let pred = Predicate::new(
+            Expression::new(constant("x"), constant("X")).with_collation(Collation::NoCase),
+        );
+        assert!(pred.simplified().unwrap().is_empty());
+    }
+
     #[test]
     fn test_predicate() {
         // NOTE: see: operators::tests
@@ -0,0 +1,105 @@
+// Copyright (c) 2023 Sho Kuroda <krdlab@gmail.com>
+//
+// This software is released under the MIT License.
+// https://opensource.org/licenses/MIT
+
+use super::predicate::Constant;
+use serde::de::{self, value::MapDeserializer, DeserializeOwned, IntoDeserializer, Visitor};
+use std::fmt::Display;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum RowError {
+    #[error("{0}")]
+    Deserialize(String),
+}
+
+impl de::Error for RowError {
+    fn custom<T: Display>(msg: T) -> Self {
+        RowError::Deserialize(msg.to_string())
+    }
+}
+
+pub type Result<T> = core::result::Result<T, RowError>;
+
+pub fn from_row<T: DeserializeOwned>(
+    row: impl IntoIterator<Item = (String, Constant)>,
+) -> Result<T> {
+    let map = MapDeserializer::new(
+        row.into_iter()
+            .map(|(name, val)| (name, ValueDeserializer(val))),
+    );
+    T::deserialize(map)
+}
+
+struct ValueDeserializer(Constant);
+
+impl<'de> de::Deserializer<'de> for ValueDeserializer {
+    type Error = RowError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.0 {
+            Constant::Int(n) => visitor.visit_i32(n),
+            Constant::String(s) => visitor.visit_string(s),
+        }
+    }
+
+    // NOTE: values are never null
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_some(self)
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+impl<'de> IntoDeserializer<'de, RowError> for ValueDeserializer {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{from_row, Constant};
+    use serde::Deserialize;
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Student {
+        sid: i32,
+        sname: String,
+        grad_year: Option<i64>,
+        major: Option<String>,
+    }
+
+    #[test]
+    fn test_from_row() {
+        let row = vec![
+            ("sname".to_string(), Constant::String("joe".into())),
+            ("sid".to_string(), Constant::Int(1)),
+            ("grad_year".to_string(), Constant::Int(2021)),
+            ("dept".to_string(), Constant::Int(10)),
+        ];
+        assert_eq!(
+            from_row::<Student>(row).unwrap(),
+            Student {
+                sid: 1,
+                sname: "joe".into(),
+                grad_year: Some(2021),
+                major: None,
+            }
+        );
+
+        let row = vec![("sid".to_string(), Constant::String("one".into()))];
+        let err = from_row::<Student>(row).unwrap_err().to_string();
+        assert!(err.contains("invalid type: string \"one\", expected i32"));
+        let row = vec![("sid".to_string(), Constant::Int(1))];
+        let err = from_row::<Student>(row).unwrap_err().to_string();
+        assert!(err.contains("missing field `sname`"));
+    }
+}
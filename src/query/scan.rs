@@ -3,8 +3,11 @@
 // This software is released under the MIT License.
 // https://opensource.org/licenses/MIT
 
-use super::predicate::Constant;
-use crate::{record::record_page::RecordPageError, tx::transaction::TransactionError};
+use super::{batch::RowBatch, predicate::Constant};
+use crate::{
+    record::{record_page::RecordPageError, schema::SqlType},
+    tx::transaction::TransactionError,
+};
 use std::fmt::Display;
 use thiserror::Error;
 
@@ -13,25 +16,56 @@ pub enum ScanError {
     #[error("field not found: {0}")]
     FieldNotFound(String),
 
-    #[error("{0:?}")]
+    #[error("{0}")]
     Transaction(#[from] TransactionError),
 
-    #[error("{0:?}")]
+    #[error("{0}")]
     RecordPage(#[from] RecordPageError),
 
-    #[error("{0:?}")]
+    #[error("{0}")]
     UnsupportedOperation(String),
+
+    #[error("no current record")]
+    NoCurrentRecord,
+
+    #[error("type mismatch: field={0}, value={1}")]
+    TypeMismatch(String, Constant),
+
+    #[error("wrong number of values: expected={0}, actual={1}")]
+    ArityMismatch(usize, usize),
+
+    #[error("value too long for {0} (at most {1} characters)")]
+    ValueTooLong(String, usize),
+
+    #[error("a record of {0} bytes doesn't fit in a block of {1} bytes")]
+    RecordTooLarge(usize, usize),
+
+    #[error("block number out of range: {0}")]
+    BlockNumberOverflow(i64),
 }
 
 pub type Result<T> = core::result::Result<T, ScanError>;
 
-pub trait Scan {
+pub trait Scan: Send {
     fn before_first(&mut self) -> Result<()>;
     fn next(&mut self) -> Result<bool>;
     fn get_i32(&self, field_name: &str) -> Result<i32>;
     fn get_string(&self, field_name: &str) -> Result<String>;
+    fn read_string(&self, field_name: &str, buf: &mut String) -> Result<()> {
+        *buf = self.get_string(field_name)?;
+        Ok(())
+    }
     fn get_val(&self, field_name: &str) -> Result<Constant>;
+    fn next_batch(&mut self, batch: &mut RowBatch) -> Result<bool> {
+        batch.clear();
+        while !batch.is_full() && self.next()? {
+            batch.push_with(|f| self.get_val(f))?;
+        }
+        Ok(!batch.is_empty())
+    }
     fn has_field(&self, field_name: &str) -> bool;
+    fn fields(&self) -> Vec<String>;
+    fn field_type(&self, field_name: &str) -> Option<SqlType>;
     fn close(&mut self);
 }
 
@@ -46,7 +80,7 @@ pub trait UpdateScan: Scan {
     fn move_to_rid(&mut self, rid: RID) -> Result<()>;
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct RID {
     blknum: i64,
     slot: Option<i32>,
@@ -64,6 +98,11 @@ impl RID {
     pub fn slot(&self) -> Option<i32> {
         self.slot
     }
+
+    // NOTE: index records store the block number as an i32
+    pub fn block_number_i32(&self) -> Result<i32> {
+        i32::try_from(self.blknum).map_err(|_| ScanError::BlockNumberOverflow(self.blknum))
+    }
 }
 
 impl Display for RID {
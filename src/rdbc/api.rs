@@ -1,8 +1,14 @@
 // This module is written based on https://github.com/tokio-rs/rdbc/blob/dcaa6fff9445123d3d829aedbf9e8fdcc473d504/rdbc/src/lib.rs
 
 use anyhow;
+use std::time::Duration;
 use thiserror;
 
+pub use crate::index::{IndexType, KeyOrder};
+pub use crate::query::predicate::Constant;
+use crate::query::row::from_row;
+use serde::de::DeserializeOwned;
+
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error("{0}")]
@@ -10,6 +16,12 @@ pub enum Error {
 
     #[error("{0}")]
     Internal(#[from] anyhow::Error),
+
+    #[error("timed out: {0}")]
+    Timeout(anyhow::Error),
+
+    #[error("no current row")]
+    NoCurrentRow,
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -20,12 +32,30 @@ pub enum DataType {
     Utf8,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Value {
     Int32(i32),
     String(String),
 }
 
+impl From<Value> for Constant {
+    fn from(value: Value) -> Self {
+        match value {
+            Value::Int32(n) => Constant::Int(n),
+            Value::String(s) => Constant::String(s),
+        }
+    }
+}
+
+impl From<Constant> for Value {
+    fn from(value: Constant) -> Self {
+        match value {
+            Constant::Int(n) => Value::Int32(n),
+            Constant::String(s) => Value::String(s),
+        }
+    }
+}
+
 impl ToString for Value {
     fn to_string(&self) -> String {
         match self {
@@ -44,11 +74,61 @@ pub trait Connection {
     fn prepare_statement(&mut self, sql: &str) -> Result<Box<dyn PreparedStatement + '_>>;
     fn commit(&mut self) -> Result<()>;
     fn rollback(&mut self) -> Result<()>;
+    fn set_autocommit(&mut self, autocommit: bool) -> Result<()>;
+    fn autocommit(&self) -> bool;
+    fn meta_data(&mut self) -> Result<Box<dyn DatabaseMetaData + '_>>;
+}
+
+pub trait DatabaseMetaData {
+    fn tables(&mut self) -> Result<Vec<TableDesc>>;
+    fn columns(&mut self, table: &str) -> Result<Vec<Column>>;
+    fn indexes(&mut self, table: &str) -> Result<Vec<IndexDesc>>;
+    fn primary_keys(&mut self, table: &str) -> Result<Vec<String>>;
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TableType {
+    Table,
+    View,
+    SystemTable,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableDesc {
+    pub name: String,
+    pub table_type: TableType,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexDesc {
+    pub name: String,
+    pub field_name: String,
+    pub index_type: IndexType,
+    pub key_order: KeyOrder,
 }
 
 pub trait Statement {
     fn execute_query(&mut self, sql: &str) -> Result<Box<dyn ResultSet + '_>>;
     fn execute_update(&mut self, sql: &str) -> Result<u64>;
+    fn set_query_timeout(&mut self, timeout: Duration);
+}
+
+impl dyn Statement + '_ {
+    pub fn query_as<T: DeserializeOwned>(&mut self, sql: &str) -> Result<Vec<T>> {
+        let mut rs = self.execute_query(sql)?;
+        let meta = rs.meta_data()?;
+        let mut rows = Vec::new();
+        while rs.next()? {
+            let mut row = Vec::new();
+            for i in 0..meta.num_columns() {
+                if let Some(val) = rs.get_val(i)? {
+                    row.push((meta.column_name(i), val));
+                }
+            }
+            rows.push(from_row(row).map_err(|e| Error::General(e.to_string()))?);
+        }
+        Ok(rows)
+    }
 }
 
 pub trait PreparedStatement {
@@ -59,8 +139,16 @@ pub trait PreparedStatement {
 pub trait ResultSet {
     fn meta_data(&self) -> Result<Box<dyn ResultSetMetaData>>;
     fn next(&mut self) -> Result<bool>;
+    fn before_first(&mut self) -> Result<()>;
+    fn absolute(&mut self, n: usize) -> Result<bool>;
     fn get_i32(&mut self, i: usize) -> Result<Option<i32>>;
     fn get_string(&mut self, i: usize) -> Result<Option<String>>;
+    fn get_val(&mut self, i: usize) -> Result<Option<Constant>>;
+    fn was_null(&self) -> bool;
+    fn update_i32(&mut self, i: usize, value: i32) -> Result<()>;
+    fn update_string(&mut self, i: usize, value: &str) -> Result<()>;
+    fn delete_row(&mut self) -> Result<()>;
+    fn insert_row(&mut self, values: &[Value]) -> Result<()>;
 }
 
 pub trait ResultSetMetaData {
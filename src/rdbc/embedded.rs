@@ -3,44 +3,134 @@
 // This software is released under the MIT License.
 // https://opensource.org/licenses/MIT
 
+use crate::metadata::{common::MetadataError, index_mgr::IndexInfo, metadata_mgr::MetadataMgr};
 use crate::plan::planner::Planner;
-use crate::query::scan::UpdateScan;
+use crate::query::scan::{self, Scan, ScanError, UpdateScan, RID};
 use crate::rdbc;
-use crate::rdbc::api::{Connection, ResultSet, ResultSetMetaData};
-use crate::record::schema::{Schema, SqlType};
+use crate::rdbc::api::{
+    Column, Connection, Constant, DatabaseMetaData, IndexDesc, ResultSet, ResultSetMetaData,
+    TableDesc, TableType, Value,
+};
+use crate::record::schema::{Layout, Schema, SqlType};
+use crate::record::table_scan::TableScan;
 use crate::server::simple_db::SimpleDB;
-use crate::tx::transaction::Transaction;
-use std::cell::RefCell;
-use std::path::Path;
-use std::rc::Rc;
-use std::sync::Arc;
+use crate::tx::transaction::{Transaction, TransactionError};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 pub struct EmbeddedDriver {}
 
 impl rdbc::api::Driver for EmbeddedDriver {
     fn connect(&self, url: &str) -> rdbc::api::Result<Box<dyn Connection + 'static>> {
-        let path = url
-            .split(":")
-            .last()
-            .ok_or(rdbc::api::Error::General(format!("invalid url: {}", url)))?;
-        let mut db = SimpleDB::new(Path::new(path), 4096, 16);
-        db.init();
+        let options = ConnectionOptions::parse(url)?;
+        let exists = options
+            .path
+            .read_dir()
+            .is_ok_and(|mut d| d.next().is_some());
+        if !options.create && !exists {
+            return Err(rdbc::api::Error::General(format!(
+                "database not found: {}",
+                options.path.display()
+            )));
+        }
+        let mut db = SimpleDB::builder()
+            .block_size(options.blocksize)
+            .buffers(options.buffers)
+            .open(&options.path)?;
+        db.init()?;
         Ok(Box::new(EmbeddedConnection::new(db)))
     }
 }
 
-struct EmbeddedConnection<'lm, 'bm> {
-    db: SimpleDB<'lm, 'bm>,
-    tx: Rc<RefCell<Transaction<'lm, 'bm>>>,
+#[derive(Debug, PartialEq)]
+struct ConnectionOptions {
+    path: PathBuf,
+    blocksize: usize,
+    buffers: usize,
+    create: bool,
 }
 
-impl<'lm, 'bm> EmbeddedConnection<'lm, 'bm> {
-    pub fn new(db: SimpleDB<'lm, 'bm>) -> Self {
+impl ConnectionOptions {
+    const MIN_BLOCKSIZE: usize = 512;
+    // NOTE: e.g. a product with an index pins a few blocks
+    const MIN_BUFFERS: usize = 8;
+
+    fn parse(url: &str) -> rdbc::api::Result<Self> {
+        let invalid =
+            |reason: String| rdbc::api::Error::General(format!("invalid url: {}: {}", url, reason));
+        let rest = url.strip_prefix("jdbc:").unwrap_or(url);
+        let rest = rest
+            .strip_prefix("simpledb:")
+            .ok_or_else(|| invalid("expected simpledb:<path>[?<options>]".into()))?;
+        let (path, query) = rest.split_once('?').unwrap_or((rest, ""));
+        if path.is_empty() {
+            return Err(invalid("the path is empty".into()));
+        }
+
+        let mut options = Self {
+            path: PathBuf::from(path),
+            blocksize: 4096,
+            buffers: 16,
+            create: true,
+        };
+        let mut seen = HashSet::new();
+        for option in query.split('&').filter(|option| !option.is_empty()) {
+            let (key, value) = option
+                .split_once('=')
+                .ok_or_else(|| invalid(format!("expected <name>=<value>: {}", option)))?;
+            if !seen.insert(key) {
+                return Err(invalid(format!("duplicate option: {}", key)));
+            }
+            let bad_value =
+                |expected: &str| invalid(format!("{} must be {}: {}", key, expected, value));
+            match key {
+                "blocksize" => {
+                    options.blocksize = value
+                        .parse()
+                        .ok()
+                        .filter(|n| *n >= Self::MIN_BLOCKSIZE)
+                        .ok_or_else(|| {
+                            bad_value(&format!("an integer of at least {}", Self::MIN_BLOCKSIZE))
+                        })?
+                }
+                "buffers" => {
+                    options.buffers = value
+                        .parse()
+                        .ok()
+                        .filter(|n| *n >= Self::MIN_BUFFERS)
+                        .ok_or_else(|| {
+                            bad_value(&format!("an integer of at least {}", Self::MIN_BUFFERS))
+                        })?
+                }
+                "create" => {
+                    options.create = value.parse().map_err(|_| bad_value("true or false"))?
+                }
+                _ => return Err(invalid(format!("unknown option: {}", key))),
+            }
+        }
+        Ok(options)
+    }
+}
+
+struct EmbeddedConnection {
+    db: SimpleDB,
+    tx: Arc<Mutex<Transaction>>,
+    autocommit: bool,
+}
+
+impl EmbeddedConnection {
+    pub fn new(db: SimpleDB) -> Self {
         let tx = db.new_tx();
-        Self { db, tx }
+        Self {
+            db,
+            tx,
+            autocommit: true,
+        }
     }
 
-    pub(crate) fn transaction(&self) -> Rc<RefCell<Transaction<'lm, 'bm>>> {
+    pub(crate) fn transaction(&self) -> Arc<Mutex<Transaction>> {
         self.tx.clone()
     }
 
@@ -48,18 +138,36 @@ impl<'lm, 'bm> EmbeddedConnection<'lm, 'bm> {
         self.db.planner()
     }
 
+    fn abort(&mut self, e: impl Into<anyhow::Error>) -> rdbc::api::Error {
+        let e = e.into();
+        let timeout = TransactionError::is_timeout(&e);
+        let e = match self.rollback() {
+            Ok(()) => e,
+            Err(re) => e.context(re),
+        };
+        if timeout {
+            rdbc::api::Error::Timeout(e)
+        } else {
+            rdbc::api::Error::Internal(e)
+        }
+    }
+
     fn close(&self) -> rdbc::api::Result<()> {
         self.tx
-            .borrow_mut()
+            .lock()
+            .unwrap()
             .commit()
             .map_err(|e| e.into())
             .map_err(rdbc::api::Error::Internal)
     }
 }
 
-impl Connection for EmbeddedConnection<'_, '_> {
+impl Connection for EmbeddedConnection {
     fn create_statement(&mut self) -> rdbc::api::Result<Box<dyn rdbc::api::Statement + '_>> {
-        Ok(Box::new(EmbeddedStatement { conn: self }))
+        Ok(Box::new(EmbeddedStatement {
+            conn: self,
+            timeout: Duration::ZERO,
+        }))
     }
 
     fn prepare_statement(
@@ -71,7 +179,8 @@ impl Connection for EmbeddedConnection<'_, '_> {
 
     fn commit(&mut self) -> rdbc::api::Result<()> {
         self.tx
-            .borrow_mut()
+            .lock()
+            .unwrap()
             .commit()
             .map_err(|e| e.into())
             .map_err(rdbc::api::Error::Internal)?;
@@ -81,43 +190,158 @@ impl Connection for EmbeddedConnection<'_, '_> {
 
     fn rollback(&mut self) -> rdbc::api::Result<()> {
         self.tx
-            .borrow_mut()
+            .lock()
+            .unwrap()
             .rollback()
             .map_err(|e| e.into())
             .map_err(rdbc::api::Error::Internal)?;
         self.tx = self.db.new_tx();
         Ok(())
     }
+
+    fn set_autocommit(&mut self, autocommit: bool) -> rdbc::api::Result<()> {
+        if autocommit && !self.autocommit {
+            self.commit()?;
+        }
+        self.autocommit = autocommit;
+        Ok(())
+    }
+
+    fn autocommit(&self) -> bool {
+        self.autocommit
+    }
+
+    fn meta_data(&mut self) -> rdbc::api::Result<Box<dyn DatabaseMetaData + '_>> {
+        Ok(Box::new(EmbeddedDatabaseMetaData { conn: self }))
+    }
 }
 
-struct EmbeddedStatement<'lm, 'bm, 'c> {
-    conn: &'c mut EmbeddedConnection<'lm, 'bm>,
+struct EmbeddedDatabaseMetaData<'c> {
+    conn: &'c mut EmbeddedConnection,
+}
+
+impl EmbeddedDatabaseMetaData<'_> {
+    fn schema(&mut self, table: &str) -> anyhow::Result<Schema> {
+        let mm = self.conn.db.metadata_mgr();
+        let tx = self.conn.transaction();
+        match mm.table_layout(table, tx.clone()) {
+            Ok(layout) => Ok(layout.schema().clone()),
+            Err(MetadataError::TableNotFound(_)) => {
+                let view_def = mm.view_def(table, tx.clone())?;
+                let planner = self.conn.planner();
+                let plan = planner.create_query_plan(&view_def, tx)?;
+                Ok(plan.schema())
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn check_table(&mut self, table: &str) -> anyhow::Result<()> {
+        let mm = self.conn.db.metadata_mgr();
+        mm.table_layout(table, self.conn.transaction())?;
+        Ok(())
+    }
 }
 
-impl<'lm, 'bm, 'c> EmbeddedStatement<'lm, 'bm, 'c> {
+impl DatabaseMetaData for EmbeddedDatabaseMetaData<'_> {
+    fn tables(&mut self) -> rdbc::api::Result<Vec<TableDesc>> {
+        let mm = self.conn.db.metadata_mgr();
+        let tx = self.conn.transaction();
+        let tables = mm
+            .table_names(tx.clone())
+            .and_then(|tables| Ok((tables, mm.view_names(tx)?)));
+        let (tables, views) = tables.map_err(|e| self.conn.abort(e))?;
+        let tables = tables.into_iter().map(|name| {
+            let table_type = if MetadataMgr::is_catalog(&name) {
+                TableType::SystemTable
+            } else {
+                TableType::Table
+            };
+            TableDesc { name, table_type }
+        });
+        let views = views.into_iter().map(|name| TableDesc {
+            name,
+            table_type: TableType::View,
+        });
+        Ok(tables.chain(views).collect())
+    }
+
+    fn columns(&mut self, table: &str) -> rdbc::api::Result<Vec<Column>> {
+        let schema = self.schema(table).map_err(|e| self.conn.abort(e))?;
+        Ok(schema
+            .fields_iter()
+            .map(|name| {
+                Column::new(
+                    name,
+                    EmbeddedResultSet::to_rdbc_datatype(schema.field_type(name).unwrap()),
+                    schema.field_length(name).unwrap_or(11),
+                )
+            })
+            .collect())
+    }
+
+    fn indexes(&mut self, table: &str) -> rdbc::api::Result<Vec<IndexDesc>> {
+        self.check_table(table).map_err(|e| self.conn.abort(e))?;
+        let mm = self.conn.db.metadata_mgr();
+        let indexes = mm
+            .table_index_info(table, self.conn.transaction())
+            .map_err(|e| self.conn.abort(e))?;
+        let mut indexes: Vec<IndexDesc> = indexes
+            .into_iter()
+            .map(|(field_name, ii)| IndexDesc {
+                name: ii.index_name().into(),
+                field_name,
+                index_type: ii.index_type(),
+                key_order: ii.key_order(),
+            })
+            .collect();
+        indexes.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(indexes)
+    }
+
+    fn primary_keys(&mut self, table: &str) -> rdbc::api::Result<Vec<String>> {
+        self.check_table(table).map_err(|e| self.conn.abort(e))?;
+        Ok(Vec::new())
+    }
+}
+
+struct EmbeddedStatement<'c> {
+    conn: &'c mut EmbeddedConnection,
+    timeout: Duration,
+}
+
+impl<'c> EmbeddedStatement<'c> {
+    fn deadline(&self) -> Option<Instant> {
+        (!self.timeout.is_zero()).then(|| Instant::now() + self.timeout)
+    }
+
     fn close(&self) -> rdbc::api::Result<()> {
         Ok(())
     }
 }
 
-impl<'lm, 'bm, 'c> rdbc::api::Statement for EmbeddedStatement<'lm, 'bm, 'c> {
+impl<'c> rdbc::api::Statement for EmbeddedStatement<'c> {
     fn execute_query(&mut self, sql: &str) -> rdbc::api::Result<Box<dyn ResultSet + '_>> {
         let tx = self.conn.transaction();
-        match self.conn.planner().create_query_plan(sql, tx.clone()) {
-            Ok(plan) => {
-                let scan = plan.open(tx.clone());
+        let planner = self.conn.planner();
+        let (plan, tables) = match planner.create_query_plan_with_tables(sql, tx.clone()) {
+            Ok(planned) => planned,
+            Err(pe) => return Err(self.conn.abort(pe)),
+        };
+        // NOTE: the deadline doesn't cover planning
+        tx.lock().unwrap().set_deadline(self.deadline());
+        match plan.open(tx) {
+            Ok(scan) => {
                 let schema = plan.schema();
-                Ok(Box::new(EmbeddedResultSet::new(self.conn, scan, schema)))
-            }
-            Err(pe) => {
-                let e = if let Err(re) = self.conn.rollback() {
-                    let ae: anyhow::Error = pe.into();
-                    ae.context(re)
-                } else {
-                    pe.into()
+                let table = match &tables[..] {
+                    [table] => Some(table.clone()),
+                    _ => None,
                 };
-                Err(rdbc::api::Error::Internal(e))
+                Ok(Box::new(EmbeddedResultSet::new(
+                    self.conn, scan, schema, table,
+                )))
             }
+            Err(se) => Err(self.conn.abort(se)),
         }
     }
 
@@ -125,35 +349,145 @@ impl<'lm, 'bm, 'c> rdbc::api::Statement for EmbeddedStatement<'lm, 'bm, 'c> {
         let tx = self.conn.transaction();
         match self.conn.planner().execute_update(sql, tx) {
             Ok(num) => {
-                self.conn.commit()?;
+                if self.conn.autocommit {
+                    self.conn.commit()?;
+                }
                 Ok(num)
             }
-            Err(pe) => {
-                let e = if let Err(re) = self.conn.rollback() {
-                    let ae: anyhow::Error = pe.into();
-                    ae.context(re)
-                } else {
-                    pe.into()
-                };
-                Err(rdbc::api::Error::Internal(e))
-            }
+            Err(pe) => Err(self.conn.abort(pe)),
         }
     }
+
+    fn set_query_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
 }
 
-struct EmbeddedResultSet<'lm, 'bm, 'c, 'scan> {
-    conn: &'c mut EmbeddedConnection<'lm, 'bm>,
-    scan: Box<dyn UpdateScan + 'scan>,
+struct EmbeddedResultSet<'c> {
+    conn: &'c mut EmbeddedConnection,
+    scan: Box<dyn UpdateScan>,
     schema: Schema,
+    was_null: bool,
+    // NOTE: the only table the query reads, if not a view
+    table: Option<String>,
+    target: Option<UpdateTarget>,
+}
+
+struct UpdateTarget {
+    table: String,
+    layout: Layout,
+    indexes: HashMap<String, IndexInfo>,
+}
+
+impl UpdateTarget {
+    fn index_keys(
+        &self,
+        rid: &RID,
+        tx: &Arc<Mutex<Transaction>>,
+    ) -> anyhow::Result<HashMap<String, Constant>> {
+        let mut keys = HashMap::new();
+        if self.indexes.is_empty() {
+            return Ok(keys);
+        }
+        let mut ts = TableScan::new(tx.clone(), self.table.clone(), self.layout.clone())?;
+        ts.move_to_rid(rid.clone())?;
+        for field in self.indexes.keys() {
+            keys.insert(field.clone(), ts.get_val(field)?);
+        }
+        ts.close();
+        Ok(keys)
+    }
+
+    fn reindex(
+        &self,
+        old: Option<(&RID, &HashMap<String, Constant>)>,
+        new: Option<(&RID, &HashMap<String, Constant>)>,
+        mm: &MetadataMgr,
+        tx: &Arc<Mutex<Transaction>>,
+    ) -> anyhow::Result<()> {
+        for (field, ii) in self.indexes.iter() {
+            let old = old.map(|(rid, keys)| (rid, &keys[field]));
+            let new = new.map(|(rid, keys)| (rid, &keys[field]));
+            if old == new {
+                continue;
+            }
+            let mut idx = ii.open(tx.clone())?;
+            if let Some((rid, key)) = old {
+                idx.delete(key, rid)?;
+            }
+            if let Some((rid, key)) = new {
+                idx.insert(key, rid)?;
+            }
+            idx.close();
+            mm.refresh_index_stats(ii, tx.clone())?;
+        }
+        Ok(())
+    }
 }
 
-impl<'lm, 'bm, 'c, 'scan> EmbeddedResultSet<'lm, 'bm, 'c, 'scan> {
+impl<'c> EmbeddedResultSet<'c> {
     pub fn new(
-        conn: &'c mut EmbeddedConnection<'lm, 'bm>,
-        scan: Box<dyn UpdateScan + 'scan>,
+        conn: &'c mut EmbeddedConnection,
+        scan: Box<dyn UpdateScan>,
         schema: Schema,
+        table: Option<String>,
     ) -> Self {
-        Self { conn, scan, schema }
+        Self {
+            conn,
+            scan,
+            schema,
+            was_null: false,
+            table,
+            target: None,
+        }
+    }
+
+    fn target(&mut self) -> rdbc::api::Result<&UpdateTarget> {
+        if self.target.is_none() {
+            let not_updatable =
+                || rdbc::api::Error::General("the result set is not updatable".into());
+            let table = self.table.clone().ok_or_else(not_updatable)?;
+            let mm = self.conn.db.metadata_mgr();
+            let tx = self.conn.transaction();
+            if mm.view_def(&table, tx.clone()).is_ok() {
+                return Err(not_updatable());
+            }
+            let target = mm
+                .table_layout(&table, tx.clone())
+                .and_then(|layout| {
+                    let indexes = mm.table_index_info(&table, tx)?;
+                    Ok(UpdateTarget {
+                        table,
+                        layout,
+                        indexes,
+                    })
+                })
+                .map_err(|e| self.abort(e))?;
+            self.target = Some(target);
+        }
+        Ok(self.target.as_ref().unwrap())
+    }
+
+    fn update(&mut self, i: usize, value: Constant) -> rdbc::api::Result<()> {
+        let field = self
+            .schema
+            .field_name(i)
+            .cloned()
+            .ok_or_else(|| rdbc::api::Error::General(format!("no column {}", i)))?;
+        self.target()?;
+        let target = self.target.as_ref().unwrap();
+        let mm = self.conn.db.metadata_mgr();
+        let tx = self.conn.transaction();
+        let result = (|| {
+            let old_rid = self.scan.get_rid()?;
+            let old_keys = target.index_keys(&old_rid, &tx)?;
+            self.scan.set_val(&field, value)?;
+            // NOTE: moved by TableScan::set_val
+            let rid = self.scan.get_rid()?;
+            let keys = target.index_keys(&rid, &tx)?;
+            target.reindex(Some((&old_rid, &old_keys)), Some((&rid, &keys)), &mm, &tx)
+        })();
+        result.map_err(|e| self.abort(e))
     }
 
     fn to_rdbc_datatype(sql_type: SqlType) -> rdbc::api::DataType {
@@ -163,14 +497,41 @@ impl<'lm, 'bm, 'c, 'scan> EmbeddedResultSet<'lm, 'bm, 'c, 'scan> {
         }
     }
 
+    fn get<T>(
+        &mut self,
+        i: usize,
+        f: impl FnOnce(&dyn UpdateScan, &str) -> scan::Result<T>,
+    ) -> rdbc::api::Result<Option<T>> {
+        let Some(name) = self.schema.field_name(i) else {
+            self.was_null = true;
+            return Ok(None);
+        };
+        match f(self.scan.as_ref(), name) {
+            Ok(value) => {
+                self.was_null = false;
+                Ok(Some(value))
+            }
+            Err(ScanError::NoCurrentRecord) => Err(rdbc::api::Error::NoCurrentRow),
+            Err(se) => Err(self.abort(se)),
+        }
+    }
+
+    fn abort(&mut self, e: impl Into<anyhow::Error>) -> rdbc::api::Error {
+        self.scan.close();
+        self.conn.abort(e)
+    }
+
     fn close(&mut self) -> rdbc::api::Result<()> {
-        // self.scan.close();
-        // self.conn.commit()?;
+        self.scan.close();
+        self.conn.tx.lock().unwrap().set_deadline(None);
+        if self.conn.autocommit {
+            self.conn.commit()?;
+        }
         Ok(())
     }
 }
 
-impl<'lm, 'bm, 'c, 'scan> ResultSet for EmbeddedResultSet<'lm, 'bm, 'c, 'scan> {
+impl<'c> ResultSet for EmbeddedResultSet<'c> {
     fn meta_data(&self) -> rdbc::api::Result<Box<dyn ResultSetMetaData>> {
         let meta: Vec<rdbc::api::Column> = self
             .schema
@@ -193,75 +554,452 @@ impl<'lm, 'bm, 'c, 'scan> ResultSet for EmbeddedResultSet<'lm, 'bm, 'c, 'scan> {
     }
 
     fn next(&mut self) -> rdbc::api::Result<bool> {
-        match self.scan.next() {
-            Ok(has) => Ok(has),
-            Err(se) => {
-                let e = if let Err(re) = self.conn.rollback() {
-                    let ae: anyhow::Error = se.into();
-                    ae.context(re)
-                } else {
-                    se.into()
-                };
-                Err(rdbc::api::Error::Internal(e))
+        self.scan.next().map_err(|se| self.abort(se))
+    }
+
+    fn before_first(&mut self) -> rdbc::api::Result<()> {
+        self.scan.before_first().map_err(|se| self.abort(se))
+    }
+
+    fn absolute(&mut self, n: usize) -> rdbc::api::Result<bool> {
+        self.before_first()?;
+        for _ in 0..n {
+            if !self.next()? {
+                return Ok(false);
             }
         }
+        Ok(n > 0)
     }
 
     fn get_i32(&mut self, i: usize) -> rdbc::api::Result<Option<i32>> {
-        let name = self.schema.field_name(i);
-        if let Some(name) = name {
-            match self.scan.get_i32(name) {
-                Ok(value) => Ok(Some(value)),
-                Err(se) => {
-                    let e = if let Err(re) = self.conn.rollback() {
-                        let ae: anyhow::Error = se.into();
-                        ae.context(re)
-                    } else {
-                        se.into()
-                    };
-                    Err(rdbc::api::Error::Internal(e))
-                }
-            }
-        } else {
-            return Ok(None);
-        }
+        self.get(i, |scan, name| scan.get_i32(name))
     }
 
     fn get_string(&mut self, i: usize) -> rdbc::api::Result<Option<String>> {
-        let name = self.schema.field_name(i);
-        if let Some(name) = name {
-            match self.scan.get_string(name) {
-                Ok(value) => Ok(Some(value)),
-                Err(se) => {
-                    let e = if let Err(re) = self.conn.rollback() {
-                        let ae: anyhow::Error = se.into();
-                        ae.context(re)
-                    } else {
-                        se.into()
-                    };
-                    Err(rdbc::api::Error::Internal(e))
-                }
-            }
-        } else {
-            return Ok(None);
+        self.get(i, |scan, name| scan.get_string(name))
+    }
+
+    fn get_val(&mut self, i: usize) -> rdbc::api::Result<Option<Constant>> {
+        self.get(i, |scan, name| scan.get_val(name))
+    }
+
+    fn was_null(&self) -> bool {
+        self.was_null
+    }
+
+    fn update_i32(&mut self, i: usize, value: i32) -> rdbc::api::Result<()> {
+        self.update(i, Constant::Int(value))
+    }
+
+    fn update_string(&mut self, i: usize, value: &str) -> rdbc::api::Result<()> {
+        self.update(i, Constant::String(value.into()))
+    }
+
+    fn delete_row(&mut self) -> rdbc::api::Result<()> {
+        self.target()?;
+        let target = self.target.as_ref().unwrap();
+        let mm = self.conn.db.metadata_mgr();
+        let tx = self.conn.transaction();
+        let result = (|| {
+            let rid = self.scan.get_rid()?;
+            let keys = target.index_keys(&rid, &tx)?;
+            self.scan.delete()?;
+            target.reindex(Some((&rid, &keys)), None, &mm, &tx)?;
+            mm.record_deletes(&target.table, 1, tx.clone())?;
+            Ok(())
+        })();
+        result.map_err(|e: anyhow::Error| self.abort(e))
+    }
+
+    fn insert_row(&mut self, values: &[Value]) -> rdbc::api::Result<()> {
+        if values.len() != self.schema.fields_iter().count() {
+            return Err(rdbc::api::Error::General(format!(
+                "expected {} values, but got {}",
+                self.schema.fields_iter().count(),
+                values.len()
+            )));
         }
+        self.target()?;
+        let target = self.target.as_ref().unwrap();
+        let mm = self.conn.db.metadata_mgr();
+        let tx = self.conn.transaction();
+        let result = (|| {
+            self.scan.insert()?;
+            for (field, value) in self.schema.fields_iter().zip(values) {
+                self.scan.set_val(field, value.clone().into())?;
+            }
+            let rid = self.scan.get_rid()?;
+            let keys = target.index_keys(&rid, &tx)?;
+            target.reindex(None, Some((&rid, &keys)), &mm, &tx)?;
+            mm.record_insert(&target.table, rid.block_number() as usize, tx.clone())?;
+            Ok(())
+        })();
+        result.map_err(|e: anyhow::Error| self.abort(e))
     }
 }
 
-impl Drop for EmbeddedConnection<'_, '_> {
+impl Drop for EmbeddedConnection {
     fn drop(&mut self) {
         self.close().unwrap(); // TODO
     }
 }
 
-impl Drop for EmbeddedStatement<'_, '_, '_> {
+impl Drop for EmbeddedStatement<'_> {
     fn drop(&mut self) {
         self.close().unwrap(); // TODO
     }
 }
 
-impl Drop for EmbeddedResultSet<'_, '_, '_, '_> {
+impl Drop for EmbeddedResultSet<'_> {
     fn drop(&mut self) {
         self.close().unwrap(); // TODO
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{ConnectionOptions, EmbeddedConnection, EmbeddedDriver};
+    use crate::rdbc::api::{
+        Connection, Constant, DataType, Driver, Error, IndexType, KeyOrder, ResultSetMetaData,
+        TableDesc, TableType, Value,
+    };
+    use crate::server::simple_db::SimpleDB;
+    use serde::Deserialize;
+    use std::{path::PathBuf, thread, time::Duration};
+    use tempfile::tempdir;
+
+    fn count(conn: &mut Box<dyn Connection>) -> usize {
+        let mut stmt = conn.create_statement().unwrap();
+        let mut rs = stmt.execute_query("select A from T").unwrap();
+        let mut n = 0;
+        while rs.next().unwrap() {
+            n += 1;
+        }
+        n
+    }
+
+    #[test]
+    fn test_autocommit() {
+        let dir = tempdir().unwrap();
+        let url = format!("jdbc:simpledb:{}", dir.path().display());
+        let mut conn = EmbeddedDriver {}.connect(&url).unwrap();
+        assert!(conn.autocommit());
+        conn.create_statement()
+            .unwrap()
+            .execute_update("create table T(A int)")
+            .unwrap();
+
+        conn.set_autocommit(false).unwrap();
+        assert!(!conn.autocommit());
+        {
+            let mut stmt = conn.create_statement().unwrap();
+            stmt.execute_update("insert into T(A) values (1)").unwrap();
+            stmt.execute_update("insert into T(A) values (2)").unwrap();
+        }
+        assert_eq!(count(&mut conn), 2);
+        conn.rollback().unwrap();
+        assert_eq!(count(&mut conn), 0);
+
+        conn.create_statement()
+            .unwrap()
+            .execute_update("insert into T(A) values (3)")
+            .unwrap();
+        conn.set_autocommit(true).unwrap();
+        conn.rollback().unwrap();
+        assert_eq!(count(&mut conn), 1);
+        drop(conn);
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_get_val() {
+        let dir = tempdir().unwrap();
+        let url = format!("jdbc:simpledb:{}", dir.path().display());
+        let mut conn = EmbeddedDriver {}.connect(&url).unwrap();
+        {
+            let mut stmt = conn.create_statement().unwrap();
+            stmt.execute_update("create table T(A int, B varchar(5))")
+                .unwrap();
+            stmt.execute_update("insert into T(A, B) values (1, 'one')")
+                .unwrap();
+            let mut rs = stmt.execute_query("select A, B from T").unwrap();
+            assert!(rs.next().unwrap());
+            assert_eq!(rs.get_val(0).unwrap(), Some(Constant::Int(1)));
+            assert!(!rs.was_null());
+            assert_eq!(rs.get_val(1).unwrap(), Some(Constant::String("one".into())));
+            assert!(!rs.was_null());
+            assert_eq!(rs.get_val(2).unwrap(), None);
+            assert!(rs.was_null());
+            assert_eq!(rs.get_i32(0).unwrap(), Some(1));
+            assert!(!rs.was_null());
+        }
+        drop(conn);
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_query_as() {
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct Row {
+            a: i32,
+            b: Option<String>,
+        }
+
+        let dir = tempdir().unwrap();
+        let url = format!("jdbc:simpledb:{}", dir.path().display());
+        let mut conn = EmbeddedDriver {}.connect(&url).unwrap();
+        {
+            let mut stmt = conn.create_statement().unwrap();
+            stmt.execute_update("create table T(A int, B varchar(5))")
+                .unwrap();
+            stmt.execute_update("insert into T(A, B) values (1, 'one')")
+                .unwrap();
+            let rows: Vec<Row> = stmt.query_as("select A from T").unwrap();
+            assert_eq!(rows, vec![Row { a: 1, b: None }]);
+            let Err(err) = stmt.query_as::<Row>("select B from T") else {
+                panic!("no field a");
+            };
+            assert!(err.to_string().contains("missing field `a`"));
+        }
+        drop(conn);
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_before_first() {
+        let dir = tempdir().unwrap();
+        let url = format!("jdbc:simpledb:{}", dir.path().display());
+        let mut conn = EmbeddedDriver {}.connect(&url).unwrap();
+        {
+            let mut stmt = conn.create_statement().unwrap();
+            stmt.execute_update("create table T(A int)").unwrap();
+            for a in 1..=3 {
+                let sql = format!("insert into T(A) values ({})", a);
+                stmt.execute_update(&sql).unwrap();
+            }
+            let mut rs = stmt.execute_query("select A from T").unwrap();
+            for _ in 0..2 {
+                let mut values = Vec::new();
+                while rs.next().unwrap() {
+                    values.push(rs.get_i32(0).unwrap().unwrap());
+                }
+                assert_eq!(values, vec![1, 2, 3]);
+                rs.before_first().unwrap();
+            }
+
+            assert!(rs.absolute(2).unwrap());
+            assert_eq!(rs.get_i32(0).unwrap(), Some(2));
+            assert!(rs.next().unwrap());
+            assert_eq!(rs.get_i32(0).unwrap(), Some(3));
+            assert!(!rs.absolute(4).unwrap());
+            assert!(matches!(rs.get_i32(0), Err(Error::NoCurrentRow)));
+            assert!(!rs.absolute(0).unwrap());
+            assert!(matches!(rs.get_i32(0), Err(Error::NoCurrentRow)));
+            assert!(rs.next().unwrap());
+            assert_eq!(rs.get_i32(0).unwrap(), Some(1));
+        }
+        drop(conn);
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_meta_data() {
+        let dir = tempdir().unwrap();
+        let url = format!("jdbc:simpledb:{}", dir.path().display());
+        let mut conn = EmbeddedDriver {}.connect(&url).unwrap();
+        {
+            let mut stmt = conn.create_statement().unwrap();
+            for sql in [
+                "create table T(A int, B varchar(9))",
+                "create index idx_b on T(B desc)",
+                "create view V as select B from T",
+            ] {
+                stmt.execute_update(sql).unwrap();
+            }
+        }
+
+        let mut meta = conn.meta_data().unwrap();
+        let tables = meta.tables().unwrap();
+        let table = |name: &str, table_type| TableDesc {
+            name: name.into(),
+            table_type,
+        };
+        assert!(tables.contains(&table("t", TableType::Table)));
+        assert!(tables.contains(&table("v", TableType::View)));
+        assert!(tables.contains(&table("tblcat", TableType::SystemTable)));
+
+        let columns = meta.columns("t").unwrap();
+        assert_eq!(columns.num_columns(), 2);
+        assert_eq!(columns.column_name(1), "b");
+        assert_eq!(columns.column_type(1), DataType::Utf8);
+        assert_eq!(columns.column_display_size(1), 9);
+        let columns = meta.columns("v").unwrap();
+        assert_eq!(columns.num_columns(), 1);
+        assert_eq!(columns.column_name(0), "b");
+
+        let indexes = meta.indexes("t").unwrap();
+        assert_eq!(indexes.len(), 1);
+        assert_eq!(indexes[0].name, "idx_b");
+        assert_eq!(indexes[0].field_name, "b");
+        assert_eq!(indexes[0].index_type, IndexType::BTree);
+        assert_eq!(indexes[0].key_order, KeyOrder::Descending);
+
+        assert!(meta.primary_keys("t").unwrap().is_empty());
+        assert!(meta.primary_keys("x").is_err());
+        assert!(meta.columns("x").is_err());
+        drop(meta);
+        drop(conn);
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_query_timeout() {
+        let dir = tempdir().unwrap();
+        let url = format!("jdbc:simpledb:{}", dir.path().display());
+        let mut conn = EmbeddedDriver {}.connect(&url).unwrap();
+        conn.create_statement()
+            .unwrap()
+            .execute_update("create table T(A int)")
+            .unwrap();
+        conn.set_autocommit(false).unwrap();
+        {
+            let mut stmt = conn.create_statement().unwrap();
+            stmt.execute_update("insert into T(A) values (1)").unwrap();
+            stmt.set_query_timeout(Duration::from_millis(1));
+            let mut rs = stmt.execute_query("select A from T").unwrap();
+            thread::sleep(Duration::from_millis(10));
+            assert!(matches!(rs.next(), Err(Error::Timeout(_))));
+        }
+        assert_eq!(count(&mut conn), 0);
+        drop(conn);
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_updatable_result_set() {
+        let dir = tempdir().unwrap();
+        let mut db = SimpleDB::new_for_test(dir.path(), "updatable_result_set_test.log");
+        db.init().unwrap();
+        let mut conn = EmbeddedConnection::new(db);
+        {
+            let mut stmt = conn.create_statement().unwrap();
+            stmt.execute_update("create table T(A int, B varchar(5))")
+                .unwrap();
+            stmt.execute_update("create index T_B on T(B)").unwrap();
+            for (a, b) in [(1, "one"), (2, "two"), (3, "three")] {
+                let sql = format!("insert into T(A, B) values ({}, '{}')", a, b);
+                stmt.execute_update(&sql).unwrap();
+            }
+            let mut rs = stmt.execute_query("select A from T").unwrap();
+            while rs.next().unwrap() {
+                match rs.get_i32(0).unwrap() {
+                    Some(1) => rs.update_i32(0, 10).unwrap(),
+                    Some(2) => rs.delete_row().unwrap(),
+                    _ => {}
+                }
+            }
+        }
+        {
+            let mut stmt = conn.create_statement().unwrap();
+            let mut rs = stmt.execute_query("select A, B from T").unwrap();
+            assert!(rs.next().unwrap());
+            rs.update_string(1, "uno").unwrap();
+            rs.insert_row(&[Value::Int32(4), Value::String("four".into())])
+                .unwrap();
+            assert_eq!(rs.get_i32(0).unwrap(), Some(4));
+            assert!(rs
+                .insert_row(&[Value::Int32(5)])
+                .unwrap_err()
+                .to_string()
+                .contains("expected 2 values"));
+        }
+        let count_of = |conn: &EmbeddedConnection, key: &str| {
+            let tx = conn.transaction();
+            let indexes = conn.db.metadata_mgr().table_index_info("t", tx.clone());
+            let mut idx = indexes.unwrap().get("b").unwrap().open(tx).unwrap();
+            let mut count = 0;
+            idx.before_first(&Constant::String(key.into())).unwrap();
+            while idx.next().unwrap() {
+                count += 1;
+            }
+            idx.close();
+            count
+        };
+        assert_eq!(count_of(&conn, "one"), 0);
+        assert_eq!(count_of(&conn, "uno"), 1);
+        assert_eq!(count_of(&conn, "two"), 0);
+        assert_eq!(count_of(&conn, "three"), 1);
+        assert_eq!(count_of(&conn, "four"), 1);
+        let mut stmt = conn.create_statement().unwrap();
+        let mut rs = stmt
+            .execute_query("select A from T where B = 'uno'")
+            .unwrap();
+        assert!(rs.next().unwrap());
+        assert_eq!(rs.get_i32(0).unwrap(), Some(10));
+        assert!(!rs.next().unwrap());
+        drop(rs);
+
+        stmt.execute_update("create table U(C int)").unwrap();
+        stmt.execute_update("insert into U(C) values (1)").unwrap();
+        let mut rs = stmt.execute_query("select A, C from T, U").unwrap();
+        assert!(rs.next().unwrap());
+        assert!(rs
+            .update_i32(0, 1)
+            .unwrap_err()
+            .to_string()
+            .contains("not updatable"));
+        drop(rs);
+        drop(stmt);
+        drop(conn);
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_parse_url() {
+        assert_eq!(
+            ConnectionOptions::parse("jdbc:simpledb:/tmp/db").unwrap(),
+            ConnectionOptions {
+                path: PathBuf::from("/tmp/db"),
+                blocksize: 4096,
+                buffers: 16,
+                create: true,
+            }
+        );
+        assert_eq!(
+            ConnectionOptions::parse("simpledb:db?blocksize=8192&buffers=256&create=false")
+                .unwrap(),
+            ConnectionOptions {
+                path: PathBuf::from("db"),
+                blocksize: 8192,
+                buffers: 256,
+                create: false,
+            }
+        );
+
+        let message = |url: &str| ConnectionOptions::parse(url).unwrap_err().to_string();
+        assert!(message("postgres://localhost/db").contains("expected simpledb:<path>"));
+        assert!(message("jdbc:simpledb:?buffers=32").contains("the path is empty"));
+        assert!(message("simpledb:db?blocksize=100").contains("blocksize must be"));
+        assert!(message("simpledb:db?buffers=many").contains("buffers must be"));
+        assert!(message("simpledb:db?create=yes").contains("create must be true or false"));
+        assert!(message("simpledb:db?create").contains("expected <name>=<value>"));
+        assert!(message("simpledb:db?cache=1").contains("unknown option: cache"));
+        assert!(message("simpledb:db?buffers=32&buffers=64").contains("duplicate option"));
+    }
+
+    #[test]
+    fn test_connect_without_create() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("db");
+        let url = format!("jdbc:simpledb:{}?create=false", path.display());
+        let Err(err) = EmbeddedDriver {}.connect(&url) else {
+            panic!("connected to no database");
+        };
+        assert!(err.to_string().contains("database not found"));
+
+        let created = format!("jdbc:simpledb:{}?blocksize=1024&buffers=32", path.display());
+        drop(EmbeddedDriver {}.connect(&created).unwrap());
+        drop(EmbeddedDriver {}.connect(&url).unwrap());
+        dir.close().unwrap();
+    }
+}
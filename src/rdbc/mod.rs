@@ -5,3 +5,6 @@
 
 pub mod api;
 pub mod embedded;
+pub mod network;
+pub(crate) mod protocol;
+pub mod sync;
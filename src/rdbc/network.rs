@@ -0,0 +1,328 @@
+// Copyright (c) 2023 Sho Kuroda <krdlab@gmail.com>
+//
+// This software is released under the MIT License.
+// https://opensource.org/licenses/MIT
+
+use super::protocol::{Request, Response};
+use crate::rdbc;
+use crate::rdbc::api::{Column, Connection, Constant, ResultSet, ResultSetMetaData, Value};
+use std::net::TcpStream;
+use std::time::Duration;
+
+pub struct NetworkDriver {}
+
+impl rdbc::api::Driver for NetworkDriver {
+    fn connect(&self, url: &str) -> rdbc::api::Result<Box<dyn Connection + 'static>> {
+        let authority = url
+            .split("//")
+            .nth(1)
+            .ok_or(rdbc::api::Error::General(format!("invalid url: {}", url)))?;
+        // NOTE: the password may contain '@'
+        let (user, password, addr) = match authority.rsplit_once('@') {
+            Some((userinfo, addr)) => {
+                let (user, password) = userinfo.split_once(':').unwrap_or((userinfo, ""));
+                (user, password, addr)
+            }
+            None => ("", "", authority),
+        };
+        let stream = TcpStream::connect(addr)
+            .map_err(|e| e.into())
+            .map_err(rdbc::api::Error::Internal)?;
+        let mut conn = NetworkConnection {
+            stream,
+            autocommit: true,
+        };
+        match conn.call(Request::Authenticate(user.into(), password.into()))? {
+            Response::Done => Ok(Box::new(conn)),
+            resp => Err(NetworkConnection::unexpected(resp)),
+        }
+    }
+}
+
+struct NetworkConnection {
+    stream: TcpStream,
+    autocommit: bool,
+}
+
+impl NetworkConnection {
+    fn call(&mut self, req: Request) -> rdbc::api::Result<Response> {
+        req.write_to(&mut self.stream)
+            .map_err(|e| e.into())
+            .map_err(rdbc::api::Error::Internal)?;
+        self.receive()
+    }
+
+    fn receive(&mut self) -> rdbc::api::Result<Response> {
+        let resp = Response::read_from(&mut self.stream)
+            .map_err(|e| e.into())
+            .map_err(rdbc::api::Error::Internal)?;
+        match resp {
+            Response::Error(message) => Err(rdbc::api::Error::General(message)),
+            Response::Timeout(message) => Err(rdbc::api::Error::Timeout(anyhow::anyhow!(message))),
+            resp => Ok(resp),
+        }
+    }
+
+    fn unexpected(resp: Response) -> rdbc::api::Error {
+        rdbc::api::Error::General(format!("unexpected response: {:?}", resp))
+    }
+}
+
+impl Connection for NetworkConnection {
+    fn create_statement(&mut self) -> rdbc::api::Result<Box<dyn rdbc::api::Statement + '_>> {
+        Ok(Box::new(NetworkStatement {
+            conn: self,
+            timeout: Duration::ZERO,
+        }))
+    }
+
+    fn prepare_statement(
+        &mut self,
+        sql: &str,
+    ) -> rdbc::api::Result<Box<dyn rdbc::api::PreparedStatement + '_>> {
+        match self.call(Request::Prepare(sql.into()))? {
+            Response::Prepared(id) => Ok(Box::new(NetworkPreparedStatement { conn: self, id })),
+            resp => Err(Self::unexpected(resp)),
+        }
+    }
+
+    fn commit(&mut self) -> rdbc::api::Result<()> {
+        match self.call(Request::Commit)? {
+            Response::Done => Ok(()),
+            resp => Err(Self::unexpected(resp)),
+        }
+    }
+
+    fn rollback(&mut self) -> rdbc::api::Result<()> {
+        match self.call(Request::Rollback)? {
+            Response::Done => Ok(()),
+            resp => Err(Self::unexpected(resp)),
+        }
+    }
+
+    fn set_autocommit(&mut self, autocommit: bool) -> rdbc::api::Result<()> {
+        match self.call(Request::SetAutocommit(autocommit))? {
+            Response::Done => {
+                self.autocommit = autocommit;
+                Ok(())
+            }
+            resp => Err(Self::unexpected(resp)),
+        }
+    }
+
+    fn autocommit(&self) -> bool {
+        self.autocommit
+    }
+
+    fn meta_data(&mut self) -> rdbc::api::Result<Box<dyn rdbc::api::DatabaseMetaData + '_>> {
+        Err(rdbc::api::Error::General(
+            "database metadata is not supported".into(),
+        ))
+    }
+}
+
+struct NetworkStatement<'c> {
+    conn: &'c mut NetworkConnection,
+    timeout: Duration,
+}
+
+impl rdbc::api::Statement for NetworkStatement<'_> {
+    fn execute_query(&mut self, sql: &str) -> rdbc::api::Result<Box<dyn ResultSet + '_>> {
+        match self.conn.call(Request::Query(sql.into(), self.timeout))? {
+            Response::Columns(columns) => Ok(Box::new(NetworkResultSet {
+                columns,
+                rows: Vec::new(),
+                position: 0,
+                was_null: false,
+                conn: Some(self.conn),
+            })),
+            resp => Err(NetworkConnection::unexpected(resp)),
+        }
+    }
+
+    fn execute_update(&mut self, sql: &str) -> rdbc::api::Result<u64> {
+        match self.conn.call(Request::Update(sql.into()))? {
+            Response::Count(count) => Ok(count),
+            resp => Err(NetworkConnection::unexpected(resp)),
+        }
+    }
+
+    fn set_query_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+}
+
+struct NetworkPreparedStatement<'c> {
+    conn: &'c mut NetworkConnection,
+    id: u64,
+}
+
+impl rdbc::api::PreparedStatement for NetworkPreparedStatement<'_> {
+    fn execute_query(&mut self, params: &[Value]) -> rdbc::api::Result<Box<dyn ResultSet + '_>> {
+        let req = Request::QueryPrepared(self.id, params.to_vec(), Duration::ZERO);
+        match self.conn.call(req)? {
+            Response::Columns(columns) => Ok(Box::new(NetworkResultSet {
+                columns,
+                rows: Vec::new(),
+                position: 0,
+                was_null: false,
+                conn: Some(self.conn),
+            })),
+            resp => Err(NetworkConnection::unexpected(resp)),
+        }
+    }
+
+    fn execute_update(&mut self, params: &[Value]) -> rdbc::api::Result<u64> {
+        match self
+            .conn
+            .call(Request::UpdatePrepared(self.id, params.to_vec()))?
+        {
+            Response::Count(count) => Ok(count),
+            resp => Err(NetworkConnection::unexpected(resp)),
+        }
+    }
+}
+
+impl Drop for NetworkPreparedStatement<'_> {
+    fn drop(&mut self) {
+        // NOTE: a broken connection has no statements to forget
+        let _ = self.conn.call(Request::ClosePrepared(self.id));
+    }
+}
+
+pub(super) struct NetworkResultSet<'c> {
+    columns: Vec<Column>,
+    rows: Vec<Vec<Value>>,
+    // NOTE: 1-based; 0 is before the first row
+    position: usize,
+    was_null: bool,
+    // NOTE: None once all the rows are received
+    conn: Option<&'c mut NetworkConnection>,
+}
+
+impl NetworkResultSet<'_> {
+    pub(super) fn new(columns: Vec<Column>, rows: Vec<Vec<Value>>) -> Self {
+        Self {
+            columns,
+            rows,
+            position: 0,
+            was_null: false,
+            conn: None,
+        }
+    }
+
+    fn fetch(&mut self) -> rdbc::api::Result<bool> {
+        let Some(conn) = self.conn.as_mut() else {
+            return Ok(false);
+        };
+        match conn.receive() {
+            Ok(Response::Batch(rows)) => {
+                self.rows.extend(rows);
+                Ok(true)
+            }
+            Ok(Response::Done) => {
+                self.conn = None;
+                Ok(false)
+            }
+            // NOTE: the transaction is rolled back
+            Err(e) => {
+                self.conn = None;
+                Err(e)
+            }
+            Ok(resp) => {
+                self.conn = None;
+                Err(NetworkConnection::unexpected(resp))
+            }
+        }
+    }
+
+    fn not_updatable() -> rdbc::api::Error {
+        rdbc::api::Error::General("the result set is not updatable".into())
+    }
+
+    fn value(&mut self, i: usize) -> rdbc::api::Result<Option<&Value>> {
+        match self.position.checked_sub(1).and_then(|i| self.rows.get(i)) {
+            Some(row) => {
+                let value = row.get(i);
+                self.was_null = value.is_none();
+                Ok(value)
+            }
+            None => Err(rdbc::api::Error::NoCurrentRow),
+        }
+    }
+}
+
+impl ResultSet for NetworkResultSet<'_> {
+    fn meta_data(&self) -> rdbc::api::Result<Box<dyn ResultSetMetaData>> {
+        Ok(Box::new(self.columns.clone()))
+    }
+
+    fn next(&mut self) -> rdbc::api::Result<bool> {
+        let n = self.position + 1;
+        self.absolute(n)
+    }
+
+    fn before_first(&mut self) -> rdbc::api::Result<()> {
+        self.position = 0;
+        Ok(())
+    }
+
+    fn absolute(&mut self, n: usize) -> rdbc::api::Result<bool> {
+        while self.rows.len() < n && self.fetch()? {}
+        self.position = n.min(self.rows.len() + 1);
+        Ok(0 < n && n <= self.rows.len())
+    }
+
+    fn get_i32(&mut self, i: usize) -> rdbc::api::Result<Option<i32>> {
+        match self.value(i)? {
+            Some(Value::Int32(n)) => Ok(Some(*n)),
+            Some(Value::String(_)) => Err(rdbc::api::Error::General(format!(
+                "column {} is not an integer",
+                self.columns.column_name(i)
+            ))),
+            None => Ok(None),
+        }
+    }
+
+    fn get_string(&mut self, i: usize) -> rdbc::api::Result<Option<String>> {
+        match self.value(i)? {
+            Some(Value::String(s)) => Ok(Some(s.clone())),
+            Some(Value::Int32(_)) => Err(rdbc::api::Error::General(format!(
+                "column {} is not a string",
+                self.columns.column_name(i)
+            ))),
+            None => Ok(None),
+        }
+    }
+
+    fn get_val(&mut self, i: usize) -> rdbc::api::Result<Option<Constant>> {
+        Ok(self.value(i)?.cloned().map(Constant::from))
+    }
+
+    fn was_null(&self) -> bool {
+        self.was_null
+    }
+
+    fn update_i32(&mut self, _i: usize, _value: i32) -> rdbc::api::Result<()> {
+        Err(Self::not_updatable())
+    }
+
+    fn update_string(&mut self, _i: usize, _value: &str) -> rdbc::api::Result<()> {
+        Err(Self::not_updatable())
+    }
+
+    fn delete_row(&mut self) -> rdbc::api::Result<()> {
+        Err(Self::not_updatable())
+    }
+
+    fn insert_row(&mut self, _values: &[Value]) -> rdbc::api::Result<()> {
+        Err(Self::not_updatable())
+    }
+}
+
+impl Drop for NetworkResultSet<'_> {
+    fn drop(&mut self) {
+        // NOTE: so that the next response can be read
+        while let Ok(true) = self.fetch() {}
+    }
+}
@@ -0,0 +1,482 @@
+// Copyright (c) 2023 Sho Kuroda <krdlab@gmail.com>
+//
+// This software is released under the MIT License.
+// https://opensource.org/licenses/MIT
+
+use super::api::{Column, DataType, ResultSetMetaData, Value};
+use std::io::{self, Read, Write};
+use std::time::Duration;
+
+// NOTE: [length (u32)] [tag] [body]; integers are big-endian, strings are [length (u32)] [UTF-8]
+
+const MAX_MESSAGE_LENGTH: usize = 64 << 20;
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Request {
+    // NOTE: a timeout of 0 means no limit
+    Query(String, Duration),
+    Update(String),
+    Commit,
+    Rollback,
+    SetAutocommit(bool),
+    // NOTE: answered with the id of the statement
+    Prepare(String),
+    QueryPrepared(u64, Vec<Value>, Duration),
+    UpdatePrepared(u64, Vec<Value>),
+    ClosePrepared(u64),
+    // NOTE: the first request of a connection
+    Authenticate(String, String),
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum Response {
+    Error(String),
+    Timeout(String),
+    Count(u64),
+    // NOTE: never sent over the network (see rdbc::sync)
+    Rows {
+        columns: Vec<Column>,
+        rows: Vec<Vec<Value>>,
+    },
+    Done,
+    Columns(Vec<Column>),
+    Batch(Vec<Vec<Value>>),
+    Prepared(u64),
+}
+
+impl Request {
+    pub(crate) fn write_to(&self, w: &mut impl Write) -> io::Result<()> {
+        let mut e = Encoder::default();
+        match self {
+            Request::Query(sql, timeout) => {
+                e.put_u8(0);
+                e.put_string(sql);
+                e.put_duration(timeout);
+            }
+            Request::Update(sql) => {
+                e.put_u8(1);
+                e.put_string(sql);
+            }
+            Request::Commit => e.put_u8(2),
+            Request::Rollback => e.put_u8(3),
+            Request::SetAutocommit(autocommit) => {
+                e.put_u8(4);
+                e.put_u8(*autocommit as u8);
+            }
+            Request::Prepare(sql) => {
+                e.put_u8(5);
+                e.put_string(sql);
+            }
+            Request::QueryPrepared(id, params, timeout) => {
+                e.put_u8(6);
+                e.put_u64(*id);
+                e.put_values(params);
+                e.put_duration(timeout);
+            }
+            Request::UpdatePrepared(id, params) => {
+                e.put_u8(7);
+                e.put_u64(*id);
+                e.put_values(params);
+            }
+            Request::ClosePrepared(id) => {
+                e.put_u8(8);
+                e.put_u64(*id);
+            }
+            Request::Authenticate(user, password) => {
+                e.put_u8(9);
+                e.put_string(user);
+                e.put_string(password);
+            }
+        }
+        e.write_to(w)
+    }
+
+    pub(crate) fn read_from(r: &mut impl Read) -> io::Result<Option<Self>> {
+        let Some(bytes) = read_message(r)? else {
+            return Ok(None);
+        };
+        let mut d = Decoder::new(&bytes);
+        let req = match d.get_u8()? {
+            0 => Request::Query(d.get_string()?, d.get_duration()?),
+            1 => Request::Update(d.get_string()?),
+            2 => Request::Commit,
+            3 => Request::Rollback,
+            4 => Request::SetAutocommit(d.get_u8()? != 0),
+            5 => Request::Prepare(d.get_string()?),
+            6 => Request::QueryPrepared(d.get_u64()?, d.get_values()?, d.get_duration()?),
+            7 => Request::UpdatePrepared(d.get_u64()?, d.get_values()?),
+            8 => Request::ClosePrepared(d.get_u64()?),
+            9 => Request::Authenticate(d.get_string()?, d.get_string()?),
+            tag => return Err(invalid_data(format!("unknown request: {}", tag))),
+        };
+        Ok(Some(req))
+    }
+}
+
+impl Response {
+    pub(crate) fn write_to(&self, w: &mut impl Write) -> io::Result<()> {
+        let mut e = Encoder::default();
+        match self {
+            Response::Error(message) => {
+                e.put_u8(0);
+                e.put_string(message);
+            }
+            Response::Count(count) => {
+                e.put_u8(1);
+                e.put_u64(*count);
+            }
+            Response::Rows { columns, rows } => {
+                e.put_u8(2);
+                e.put_columns(columns);
+                e.put_rows(rows);
+            }
+            Response::Done => e.put_u8(3),
+            Response::Timeout(message) => {
+                e.put_u8(4);
+                e.put_string(message);
+            }
+            Response::Columns(columns) => {
+                e.put_u8(5);
+                e.put_columns(columns);
+            }
+            Response::Batch(rows) => {
+                e.put_u8(6);
+                e.put_rows(rows);
+            }
+            Response::Prepared(id) => {
+                e.put_u8(7);
+                e.put_u64(*id);
+            }
+        }
+        e.write_to(w)
+    }
+
+    pub(crate) fn read_from(r: &mut impl Read) -> io::Result<Self> {
+        let bytes = read_message(r)?.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "the server closed the connection",
+            )
+        })?;
+        let mut d = Decoder::new(&bytes);
+        let resp = match d.get_u8()? {
+            0 => Response::Error(d.get_string()?),
+            1 => Response::Count(d.get_u64()?),
+            2 => {
+                let columns = d.get_columns()?;
+                let rows = d.get_rows()?;
+                Response::Rows { columns, rows }
+            }
+            3 => Response::Done,
+            4 => Response::Timeout(d.get_string()?),
+            5 => Response::Columns(d.get_columns()?),
+            6 => Response::Batch(d.get_rows()?),
+            7 => Response::Prepared(d.get_u64()?),
+            tag => return Err(invalid_data(format!("unknown response: {}", tag))),
+        };
+        Ok(resp)
+    }
+}
+
+pub(crate) fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+pub(crate) fn read_message(r: &mut impl Read) -> io::Result<Option<Vec<u8>>> {
+    let mut len = [0u8; 4];
+    match r.read_exact(&mut len) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    let len = u32::from_be_bytes(len) as usize;
+    if len > MAX_MESSAGE_LENGTH {
+        return Err(invalid_data(format!("message too long: {} bytes", len)));
+    }
+    let mut bytes = vec![0u8; len];
+    r.read_exact(&mut bytes)?;
+    Ok(Some(bytes))
+}
+
+#[derive(Default)]
+pub(crate) struct Encoder {
+    bytes: Vec<u8>,
+}
+
+impl Encoder {
+    pub(crate) fn put_u8(&mut self, n: u8) {
+        self.bytes.push(n);
+    }
+
+    fn put_u32(&mut self, n: usize) {
+        let n = u32::try_from(n).unwrap();
+        self.bytes.extend(n.to_be_bytes());
+    }
+
+    pub(crate) fn put_u64(&mut self, n: u64) {
+        self.bytes.extend(n.to_be_bytes());
+    }
+
+    pub(crate) fn put_i64(&mut self, n: i64) {
+        self.bytes.extend(n.to_be_bytes());
+    }
+
+    fn put_i32(&mut self, n: i32) {
+        self.bytes.extend(n.to_be_bytes());
+    }
+
+    fn put_duration(&mut self, d: &Duration) {
+        self.put_u64(u64::try_from(d.as_millis()).unwrap_or(u64::MAX));
+    }
+
+    pub(crate) fn put_string(&mut self, s: &str) {
+        self.put_u32(s.len());
+        self.bytes.extend(s.as_bytes());
+    }
+
+    pub(crate) fn put_bytes(&mut self, bytes: &[u8]) {
+        self.put_u32(bytes.len());
+        self.bytes.extend(bytes);
+    }
+
+    fn put_columns(&mut self, columns: &dyn ResultSetMetaData) {
+        self.put_u32(columns.num_columns());
+        for i in 0..columns.num_columns() {
+            self.put_string(&columns.column_name(i));
+            self.put_u8(match columns.column_type(i) {
+                DataType::Integer => 0,
+                DataType::Utf8 => 1,
+            });
+            self.put_u32(columns.column_display_size(i));
+        }
+    }
+
+    fn put_rows(&mut self, rows: &[Vec<Value>]) {
+        self.put_u32(rows.len());
+        self.put_u32(rows.first().map_or(0, |row| row.len()));
+        for value in rows.iter().flatten() {
+            self.put_value(value);
+        }
+    }
+
+    fn put_values(&mut self, values: &[Value]) {
+        self.put_u32(values.len());
+        for value in values {
+            self.put_value(value);
+        }
+    }
+
+    fn put_value(&mut self, value: &Value) {
+        match value {
+            Value::Int32(n) => {
+                self.put_u8(0);
+                self.put_i32(*n);
+            }
+            Value::String(s) => {
+                self.put_u8(1);
+                self.put_string(s);
+            }
+        }
+    }
+
+    pub(crate) fn write_to(self, w: &mut impl Write) -> io::Result<()> {
+        if self.bytes.len() > MAX_MESSAGE_LENGTH {
+            return Err(invalid_data(format!(
+                "message too long: {} bytes",
+                self.bytes.len()
+            )));
+        }
+        // NOTE: written at once to avoid Nagle's delay
+        let mut message = u32::try_from(self.bytes.len())
+            .unwrap()
+            .to_be_bytes()
+            .to_vec();
+        message.extend(self.bytes);
+        w.write_all(&message)?;
+        w.flush()
+    }
+}
+
+pub(crate) struct Decoder<'b> {
+    bytes: &'b [u8],
+    pos: usize,
+}
+
+impl<'b> Decoder<'b> {
+    pub(crate) fn new(bytes: &'b [u8]) -> Self {
+        Self { bytes, pos: 0 }
+    }
+
+    fn take<const N: usize>(&mut self) -> io::Result<[u8; N]> {
+        let bytes = self.take_slice(N)?;
+        Ok(bytes.try_into().unwrap())
+    }
+
+    fn take_slice(&mut self, len: usize) -> io::Result<&'b [u8]> {
+        let end = self.pos + len;
+        if end > self.bytes.len() {
+            return Err(invalid_data("message truncated".into()));
+        }
+        let bytes = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    pub(crate) fn get_u8(&mut self) -> io::Result<u8> {
+        Ok(self.take::<1>()?[0])
+    }
+
+    fn get_u32(&mut self) -> io::Result<usize> {
+        Ok(u32::from_be_bytes(self.take()?) as usize)
+    }
+
+    pub(crate) fn get_u64(&mut self) -> io::Result<u64> {
+        Ok(u64::from_be_bytes(self.take()?))
+    }
+
+    pub(crate) fn get_i64(&mut self) -> io::Result<i64> {
+        Ok(i64::from_be_bytes(self.take()?))
+    }
+
+    fn get_i32(&mut self) -> io::Result<i32> {
+        Ok(i32::from_be_bytes(self.take()?))
+    }
+
+    fn get_duration(&mut self) -> io::Result<Duration> {
+        Ok(Duration::from_millis(self.get_u64()?))
+    }
+
+    pub(crate) fn get_string(&mut self) -> io::Result<String> {
+        let len = self.get_u32()?;
+        let bytes = self.take_slice(len)?;
+        String::from_utf8(bytes.to_vec()).map_err(|e| invalid_data(e.to_string()))
+    }
+
+    pub(crate) fn get_bytes(&mut self) -> io::Result<Vec<u8>> {
+        let len = self.get_u32()?;
+        Ok(self.take_slice(len)?.to_vec())
+    }
+
+    fn get_columns(&mut self) -> io::Result<Vec<Column>> {
+        let num_columns = self.get_u32()?;
+        let mut columns = Vec::new();
+        for _ in 0..num_columns {
+            let name = self.get_string()?;
+            let data_type = match self.get_u8()? {
+                0 => DataType::Integer,
+                1 => DataType::Utf8,
+                tag => return Err(invalid_data(format!("unknown data type: {}", tag))),
+            };
+            columns.push(Column::new(&name, data_type, self.get_u32()?));
+        }
+        Ok(columns)
+    }
+
+    fn get_rows(&mut self) -> io::Result<Vec<Vec<Value>>> {
+        let num_rows = self.get_u32()?;
+        let num_columns = self.get_u32()?;
+        let mut rows = Vec::new();
+        for _ in 0..num_rows {
+            let mut row = Vec::with_capacity(num_columns);
+            for _ in 0..num_columns {
+                row.push(self.get_value()?);
+            }
+            rows.push(row);
+        }
+        Ok(rows)
+    }
+
+    fn get_values(&mut self) -> io::Result<Vec<Value>> {
+        let len = self.get_u32()?;
+        (0..len).map(|_| self.get_value()).collect()
+    }
+
+    fn get_value(&mut self) -> io::Result<Value> {
+        match self.get_u8()? {
+            0 => Ok(Value::Int32(self.get_i32()?)),
+            1 => Ok(Value::String(self.get_string()?)),
+            tag => Err(invalid_data(format!("unknown value: {}", tag))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() -> io::Result<()> {
+        let mut buf = Vec::new();
+        Request::Query("select a from t".into(), Duration::from_secs(3)).write_to(&mut buf)?;
+        Request::Commit.write_to(&mut buf)?;
+        Request::SetAutocommit(false).write_to(&mut buf)?;
+        Request::UpdatePrepared(7, vec![Value::Int32(1), Value::String("a".into())])
+            .write_to(&mut buf)?;
+        Request::Authenticate("alice".into(), "secret".into()).write_to(&mut buf)?;
+        let mut r = buf.as_slice();
+        assert_eq!(
+            Request::read_from(&mut r)?,
+            Some(Request::Query(
+                "select a from t".into(),
+                Duration::from_secs(3)
+            ))
+        );
+        assert_eq!(Request::read_from(&mut r)?, Some(Request::Commit));
+        assert_eq!(
+            Request::read_from(&mut r)?,
+            Some(Request::SetAutocommit(false))
+        );
+        assert_eq!(
+            Request::read_from(&mut r)?,
+            Some(Request::UpdatePrepared(
+                7,
+                vec![Value::Int32(1), Value::String("a".into())]
+            ))
+        );
+        assert_eq!(
+            Request::read_from(&mut r)?,
+            Some(Request::Authenticate("alice".into(), "secret".into()))
+        );
+        assert_eq!(Request::read_from(&mut r)?, None);
+
+        let mut buf = Vec::new();
+        Response::Rows {
+            columns: vec![
+                Column::new("a", DataType::Integer, 11),
+                Column::new("b", DataType::Utf8, 9),
+            ],
+            rows: vec![vec![Value::Int32(-1), Value::String("ö".into())]],
+        }
+        .write_to(&mut buf)?;
+        let Response::Rows { columns, rows } = Response::read_from(&mut buf.as_slice())? else {
+            panic!("not rows");
+        };
+        assert_eq!(columns.column_name(1), "b");
+        assert_eq!(columns.column_type(1), DataType::Utf8);
+        assert_eq!(columns.column_display_size(1), 9);
+        assert!(matches!(rows[0][0], Value::Int32(-1)));
+        assert!(matches!(&rows[0][1], Value::String(s) if s == "ö"));
+
+        let mut buf = Vec::new();
+        Response::Columns(vec![Column::new("a", DataType::Integer, 11)]).write_to(&mut buf)?;
+        Response::Batch(vec![vec![Value::Int32(1)], vec![Value::Int32(2)]]).write_to(&mut buf)?;
+        Response::Batch(vec![]).write_to(&mut buf)?;
+        let mut r = buf.as_slice();
+        let Response::Columns(columns) = Response::read_from(&mut r)? else {
+            panic!("not columns");
+        };
+        assert_eq!(columns.column_name(0), "a");
+        let Response::Batch(rows) = Response::read_from(&mut r)? else {
+            panic!("not a batch");
+        };
+        assert_eq!(rows.len(), 2);
+        assert!(matches!(rows[1][..], [Value::Int32(2)]));
+        assert!(matches!(Response::read_from(&mut r)?, Response::Batch(rows) if rows.is_empty()));
+
+        let mut buf = Vec::new();
+        Response::Count(3).write_to(&mut buf)?;
+        buf.truncate(buf.len() - 1);
+        assert!(Response::read_from(&mut buf.as_slice()).is_err());
+        Ok(())
+    }
+}
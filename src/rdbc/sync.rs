@@ -0,0 +1,295 @@
+// Copyright (c) 2023 Sho Kuroda <krdlab@gmail.com>
+//
+// This software is released under the MIT License.
+// https://opensource.org/licenses/MIT
+
+use super::network::NetworkResultSet;
+use super::protocol::{Request, Response};
+use crate::rdbc;
+use crate::rdbc::api::{Column, Connection, Driver, ResultSet, Value};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+type Call = (Request, Sender<rdbc::api::Result<Response>>);
+
+pub struct SyncConnection {
+    calls: Option<Sender<Call>>,
+    worker: Option<JoinHandle<()>>,
+    autocommit: bool,
+}
+
+impl SyncConnection {
+    pub fn connect(driver: impl Driver + 'static, url: &str) -> rdbc::api::Result<Self> {
+        let url = url.to_owned();
+        let (calls, requests) = mpsc::channel::<Call>();
+        let (ready, connected) = mpsc::channel();
+        let worker = thread::spawn(move || match driver.connect(&url) {
+            Ok(conn) => {
+                ready.send(Ok(())).unwrap();
+                Self::work(conn, requests);
+            }
+            Err(e) => ready.send(Err(e)).unwrap(),
+        });
+        if let Err(e) = connected.recv().unwrap() {
+            worker.join().unwrap();
+            return Err(e);
+        }
+        Ok(Self {
+            calls: Some(calls),
+            worker: Some(worker),
+            autocommit: true,
+        })
+    }
+
+    fn work(mut conn: Box<dyn Connection>, requests: Receiver<Call>) {
+        for (req, reply) in requests {
+            let result = Self::handle(conn.as_mut(), req);
+            reply.send(result).unwrap();
+        }
+    }
+
+    fn handle(conn: &mut dyn Connection, req: Request) -> rdbc::api::Result<Response> {
+        match req {
+            Request::Query(sql, timeout) => {
+                let mut stmt = conn.create_statement()?;
+                stmt.set_query_timeout(timeout);
+                let mut rs = stmt.execute_query(&sql)?;
+                let meta = rs.meta_data()?;
+                let columns = (0..meta.num_columns())
+                    .map(|i| {
+                        Column::new(
+                            &meta.column_name(i),
+                            meta.column_type(i),
+                            meta.column_display_size(i),
+                        )
+                    })
+                    .collect();
+                let mut rows = Vec::new();
+                while rs.next()? {
+                    let mut row = Vec::with_capacity(meta.num_columns());
+                    for i in 0..meta.num_columns() {
+                        let value = rs
+                            .get_val(i)?
+                            .ok_or_else(|| rdbc::api::Error::General(format!("no column {}", i)))?;
+                        row.push(Value::from(value));
+                    }
+                    rows.push(row);
+                }
+                Ok(Response::Rows { columns, rows })
+            }
+            Request::Update(sql) => Ok(Response::Count(
+                conn.create_statement()?.execute_update(&sql)?,
+            )),
+            Request::Commit => conn.commit().map(|_| Response::Done),
+            Request::Rollback => conn.rollback().map(|_| Response::Done),
+            Request::SetAutocommit(autocommit) => {
+                conn.set_autocommit(autocommit).map(|_| Response::Done)
+            }
+            // NOTE: SyncConnection doesn't prepare statements
+            Request::Prepare(_)
+            | Request::QueryPrepared(..)
+            | Request::UpdatePrepared(..)
+            | Request::ClosePrepared(_) => Err(rdbc::api::Error::General(
+                "prepared statements are not supported".into(),
+            )),
+            Request::Authenticate(..) => Err(rdbc::api::Error::General(
+                "the connection is authenticated already".into(),
+            )),
+        }
+    }
+
+    fn call(&mut self, req: Request) -> rdbc::api::Result<Response> {
+        let (reply, response) = mpsc::channel();
+        let stopped = || rdbc::api::Error::General("the worker thread has stopped".into());
+        self.calls
+            .as_ref()
+            .unwrap()
+            .send((req, reply))
+            .map_err(|_| stopped())?;
+        response.recv().map_err(|_| stopped())?
+    }
+
+    fn unexpected(resp: Response) -> rdbc::api::Error {
+        rdbc::api::Error::General(format!("unexpected response: {:?}", resp))
+    }
+}
+
+impl Connection for SyncConnection {
+    fn create_statement(&mut self) -> rdbc::api::Result<Box<dyn rdbc::api::Statement + '_>> {
+        Ok(Box::new(SyncStatement {
+            conn: self,
+            timeout: Duration::ZERO,
+        }))
+    }
+
+    fn prepare_statement(
+        &mut self,
+        _sql: &str,
+    ) -> rdbc::api::Result<Box<dyn rdbc::api::PreparedStatement + '_>> {
+        Err(rdbc::api::Error::General(
+            "prepared statements are not supported".into(),
+        ))
+    }
+
+    fn commit(&mut self) -> rdbc::api::Result<()> {
+        match self.call(Request::Commit)? {
+            Response::Done => Ok(()),
+            resp => Err(Self::unexpected(resp)),
+        }
+    }
+
+    fn rollback(&mut self) -> rdbc::api::Result<()> {
+        match self.call(Request::Rollback)? {
+            Response::Done => Ok(()),
+            resp => Err(Self::unexpected(resp)),
+        }
+    }
+
+    fn set_autocommit(&mut self, autocommit: bool) -> rdbc::api::Result<()> {
+        match self.call(Request::SetAutocommit(autocommit))? {
+            Response::Done => {
+                self.autocommit = autocommit;
+                Ok(())
+            }
+            resp => Err(Self::unexpected(resp)),
+        }
+    }
+
+    fn autocommit(&self) -> bool {
+        self.autocommit
+    }
+
+    fn meta_data(&mut self) -> rdbc::api::Result<Box<dyn rdbc::api::DatabaseMetaData + '_>> {
+        Err(rdbc::api::Error::General(
+            "database metadata is not supported".into(),
+        ))
+    }
+}
+
+impl Drop for SyncConnection {
+    fn drop(&mut self) {
+        // NOTE: stops the worker
+        self.calls.take();
+        if let Some(worker) = self.worker.take() {
+            worker.join().unwrap(); // TODO
+        }
+    }
+}
+
+struct SyncStatement<'c> {
+    conn: &'c mut SyncConnection,
+    timeout: Duration,
+}
+
+impl rdbc::api::Statement for SyncStatement<'_> {
+    fn execute_query(&mut self, sql: &str) -> rdbc::api::Result<Box<dyn ResultSet + '_>> {
+        match self.conn.call(Request::Query(sql.into(), self.timeout))? {
+            Response::Rows { columns, rows } => Ok(Box::new(NetworkResultSet::new(columns, rows))),
+            resp => Err(SyncConnection::unexpected(resp)),
+        }
+    }
+
+    fn execute_update(&mut self, sql: &str) -> rdbc::api::Result<u64> {
+        match self.conn.call(Request::Update(sql.into()))? {
+            Response::Count(count) => Ok(count),
+            resp => Err(SyncConnection::unexpected(resp)),
+        }
+    }
+
+    fn set_query_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SyncConnection;
+    use crate::rdbc::api::{Connection, Error};
+    use crate::rdbc::embedded::EmbeddedDriver;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use tempfile::tempdir;
+
+    fn count(conn: &mut SyncConnection) -> usize {
+        let mut stmt = conn.create_statement().unwrap();
+        let mut rs = stmt.execute_query("select A from T").unwrap();
+        let mut n = 0;
+        while rs.next().unwrap() {
+            n += 1;
+        }
+        n
+    }
+
+    #[test]
+    fn test_sync_connection() {
+        let dir = tempdir().unwrap();
+        let url = format!("jdbc:simpledb:{}", dir.path().display());
+        let conn = Arc::new(Mutex::new(
+            SyncConnection::connect(EmbeddedDriver {}, &url).unwrap(),
+        ));
+        conn.lock()
+            .unwrap()
+            .create_statement()
+            .unwrap()
+            .execute_update("create table T(A int, B varchar(5))")
+            .unwrap();
+        thread::scope(|s| {
+            for i in 0..4 {
+                let conn = conn.clone();
+                s.spawn(move || {
+                    let mut conn = conn.lock().unwrap();
+                    let mut stmt = conn.create_statement().unwrap();
+                    let sql = format!("insert into T(A, B) values ({}, 'b{}')", i, i);
+                    assert_eq!(stmt.execute_update(&sql).unwrap(), 1);
+                });
+            }
+        });
+        let mut conn = Arc::into_inner(conn).unwrap().into_inner().unwrap();
+        assert_eq!(count(&mut conn), 4);
+        {
+            let mut stmt = conn.create_statement().unwrap();
+            let mut rs = stmt.execute_query("select B from T where A = 2").unwrap();
+            assert_eq!(rs.meta_data().unwrap().column_name(0), "b");
+            assert!(rs.next().unwrap());
+            assert_eq!(rs.get_string(0).unwrap(), Some("b2".into()));
+            assert!(!rs.next().unwrap());
+        }
+
+        conn.set_autocommit(false).unwrap();
+        assert!(!conn.autocommit());
+        conn.create_statement()
+            .unwrap()
+            .execute_update("delete from T")
+            .unwrap();
+        assert_eq!(count(&mut conn), 0);
+        conn.rollback().unwrap();
+        assert_eq!(count(&mut conn), 4);
+
+        let mut stmt = conn.create_statement().unwrap();
+        let err = stmt.execute_update("create table T(C int)").unwrap_err();
+        assert!(matches!(err, Error::Internal(e) if e.to_string() == "table already exists: t"));
+        let Err(err) = stmt.execute_query("select X from T") else {
+            panic!("unknown field");
+        };
+        assert!(err.to_string().contains("field not found: x"));
+        drop(stmt);
+        assert_eq!(count(&mut conn), 4);
+        drop(conn);
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_connect_error() {
+        let dir = tempdir().unwrap();
+        let url = format!(
+            "jdbc:simpledb:{}?create=false",
+            dir.path().join("db").display()
+        );
+        let Err(err) = SyncConnection::connect(EmbeddedDriver {}, &url) else {
+            panic!("connected to no database");
+        };
+        assert!(err.to_string().contains("database not found"));
+        dir.close().unwrap();
+    }
+}
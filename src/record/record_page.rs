@@ -5,22 +5,39 @@
 
 use super::schema::{Layout, SqlType};
 use crate::{
-    file::block_id::BlockId,
+    file::{
+        block_id::BlockId,
+        page::{Page, PageError},
+    },
+    query::predicate::Constant,
     tx::transaction::{Transaction, TransactionError},
 };
-use std::convert::Into;
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    convert::Into,
+};
 use thiserror::Error;
 
 #[derive(Debug, Error)]
 pub enum RecordPageError {
-    #[error("illegal slot: {0:?}")]
+    #[error("illegal slot: {0}")]
     IllegalSlot(i32),
 
     #[error("field not found: {0}")]
     FieldNotFound(String),
 
-    #[error("{0:?}")]
+    #[error("field {0} is not stored in the records written under version {1} of the layout")]
+    FieldNotStored(String, i32),
+
+    #[error("unknown layout version: {0}")]
+    UnknownVersion(i32),
+
+    #[error("{0}")]
     Transaction(#[from] TransactionError),
+
+    #[error("{0}")]
+    Page(#[from] PageError),
 }
 
 pub type Result<T> = core::result::Result<T, RecordPageError>;
@@ -40,93 +57,197 @@ impl Into<i32> for SlotFlag {
 pub struct RecordPage {
     block: BlockId,
     layout: Layout,
+    // NOTE: read lazily
+    version: Cell<Option<i32>>,
+    // NOTE: version -> field offsets, None if missing in the version
+    offsets: RefCell<HashMap<i32, HashMap<String, Option<usize>>>>,
 }
 
-impl<'tx, 'lm, 'bm, 'lt> RecordPage {
+impl<'tx> RecordPage {
     pub fn new(block: BlockId, layout: Layout) -> Self {
-        Self { block, layout }
+        Self {
+            block,
+            layout,
+            version: Cell::new(None),
+            offsets: RefCell::new(HashMap::new()),
+        }
+    }
+
+    pub fn move_to(&mut self, block: BlockId) {
+        self.block = block;
+        self.version.set(None);
+    }
+
+    pub fn version(&self, tx: &'tx Transaction) -> Result<i32> {
+        if let Some(version) = self.version.get() {
+            return Ok(version);
+        }
+        let version = tx.peek_i32(&self.block, 0)?;
+        self.version.set(Some(version));
+        Ok(version)
+    }
+
+    fn page_layout(&self, tx: &'tx Transaction) -> Result<&Layout> {
+        let version = self.version(tx)?;
+        self.layout
+            .of_version(version)
+            .ok_or(RecordPageError::UnknownVersion(version))
     }
 
-    fn slot_offset(&self, slot: i32) -> Result<usize> {
+    fn slot_offset(&self, tx: &'tx Transaction, slot: i32) -> Result<usize> {
         // assert!(slot >= 0);
         if slot >= 0 {
-            Ok((slot as usize) * self.layout.slotsize())
+            let slotsize = self.page_layout(tx)?.slotsize();
+            Ok(Layout::PAGE_HEADER_SIZE + (slot as usize) * slotsize)
         } else {
             Err(RecordPageError::IllegalSlot(slot))
         }
     }
 
-    fn is_valid_slot(&self, tx: &'tx Transaction<'lm, 'bm>, slot: i32) -> bool {
-        self.slot_offset(slot + 1)
-            .map_or(false, |o| o <= tx.block_size())
+    fn is_valid_slot(&self, tx: &'tx Transaction, slot: i32) -> bool {
+        self.slot_offset(tx, slot + 1)
+            .is_ok_and(|o| o <= tx.block_size())
     }
 
     pub fn block(&self) -> &BlockId {
         &self.block
     }
 
-    fn field_offset(&self, slot: i32, fname: &str) -> Result<usize> {
-        let fpos = self.slot_offset(slot)?
-            + self
-                .layout
-                .field_offset(fname)
-                .ok_or(RecordPageError::FieldNotFound(fname.into()))?;
-        Ok(fpos)
+    pub fn is_missing(&self, tx: &'tx Transaction, fname: &str) -> Result<bool> {
+        Ok(self.layout.schema().has_field(fname)
+            && !self.layout.is_stored_in(fname, self.version(tx)?))
+    }
+
+    fn field_offset(&self, tx: &'tx Transaction, slot: i32, fname: &str) -> Result<Option<usize>> {
+        let version = self.version(tx)?;
+        let cached = self
+            .offsets
+            .borrow()
+            .get(&version)
+            .and_then(|offsets| offsets.get(fname).copied());
+        let offset = match cached {
+            Some(offset) => offset,
+            None => {
+                if !self.layout.schema().has_field(fname) {
+                    return Err(RecordPageError::FieldNotFound(fname.into()));
+                }
+                let offset = if self.is_missing(tx, fname)? {
+                    None
+                } else {
+                    self.page_layout(tx)?.field_offset(fname) // NOTE: is_missing checked it is Some
+                };
+                self.offsets
+                    .borrow_mut()
+                    .entry(version)
+                    .or_default()
+                    .insert(fname.into(), offset);
+                offset
+            }
+        };
+        match offset {
+            Some(offset) => Ok(Some(self.slot_offset(tx, slot)? + offset)),
+            None => Ok(None),
+        }
+    }
+
+    fn stored_field_offset(&self, tx: &'tx Transaction, slot: i32, fname: &str) -> Result<usize> {
+        self.field_offset(tx, slot, fname)?.ok_or_else(|| {
+            RecordPageError::FieldNotStored(fname.into(), self.version.get().unwrap())
+        })
     }
 
-    pub fn get_i32(&self, tx: &'tx Transaction<'lm, 'bm>, slot: i32, fname: &str) -> Result<i32> {
-        let foffset = self.field_offset(slot, fname)?;
-        Ok(tx.get_i32(&self.block, foffset)?)
+    pub fn get_i32(&self, tx: &'tx Transaction, slot: i32, fname: &str) -> Result<i32> {
+        match self.field_offset(tx, slot, fname)? {
+            Some(foffset) => Ok(tx.get_i32_in_slot(&self.block, slot, foffset)?),
+            None => Ok(0),
+        }
     }
 
     pub fn set_i32(
         &self,
-        tx: &'tx mut Transaction<'lm, 'bm>,
+        tx: &'tx mut Transaction,
         slot: i32,
         fname: &str,
         value: i32,
     ) -> Result<()> {
-        let foffset = self.field_offset(slot, fname)?;
-        Ok(tx.set_i32(&self.block, foffset, value, true)?)
+        let foffset = self.stored_field_offset(tx, slot, fname)?;
+        Ok(tx.set_i32_in_slot(&self.block, slot, foffset, value, true)?)
     }
 
-    pub fn get_string(
+    pub fn get_string(&self, tx: &'tx Transaction, slot: i32, fname: &str) -> Result<String> {
+        match self.field_offset(tx, slot, fname)? {
+            Some(foffset) => Ok(tx.get_string_in_slot(&self.block, slot, foffset)?),
+            None => Ok(String::new()),
+        }
+    }
+
+    pub fn read_string(
         &self,
-        tx: &'tx Transaction<'lm, 'bm>,
+        tx: &'tx Transaction,
         slot: i32,
         fname: &str,
-    ) -> Result<String> {
-        let foffset = self.field_offset(slot, fname)?;
-        Ok(tx.get_string(&self.block, foffset)?)
+        buf: &mut String,
+    ) -> Result<()> {
+        match self.field_offset(tx, slot, fname)? {
+            Some(foffset) => Ok(tx.read_string_in_slot(&self.block, slot, foffset, buf)?),
+            None => {
+                buf.clear();
+                Ok(())
+            }
+        }
     }
 
     pub fn set_string(
         &self,
-        tx: &'tx mut Transaction<'lm, 'bm>,
+        tx: &'tx mut Transaction,
         slot: i32,
         fname: &str,
         value: String,
     ) -> Result<()> {
-        let foffset = self.field_offset(slot, fname)?;
-        Ok(tx.set_string(&self.block, foffset, &value, true)?)
+        let foffset = self.stored_field_offset(tx, slot, fname)?;
+        Ok(tx.set_string_in_slot(&self.block, slot, foffset, &value, true)?)
+    }
+
+    pub fn get_bytes(&self, tx: &'tx Transaction, slot: i32, fname: &str) -> Result<Vec<u8>> {
+        match self.field_offset(tx, slot, fname)? {
+            Some(foffset) => Ok(tx.get_bytes_in_slot(&self.block, slot, foffset)?),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    pub fn set_bytes(
+        &self,
+        tx: &'tx mut Transaction,
+        slot: i32,
+        fname: &str,
+        value: &[u8],
+    ) -> Result<()> {
+        let foffset = self.stored_field_offset(tx, slot, fname)?;
+        Ok(tx.set_bytes_in_slot(&self.block, slot, foffset, value, true)?)
     }
 
-    pub fn delete(&self, tx: &'tx mut Transaction<'lm, 'bm>, slot: i32) -> Result<()> {
+    pub fn delete(&self, tx: &'tx mut Transaction, slot: i32) -> Result<()> {
         Ok(self.set_flag(tx, slot, SlotFlag::Empty)?)
     }
 
-    pub fn format(&self, tx: &'tx mut Transaction<'lm, 'bm>) -> Result<()> {
+    pub fn format(&self, tx: &'tx mut Transaction) -> Result<()> {
+        let version = self.layout.version();
+        // NOTE: a zeroed page is of version 0, so other versions must be logged
+        if version != 0 {
+            tx.set_i32(&self.block, 0, version, true)?;
+        }
+        self.version.set(Some(version));
         let mut slot = 0;
         while self.is_valid_slot(tx, slot) {
             tx.set_i32(
                 &self.block,
-                self.slot_offset(slot)?,
+                self.slot_offset(tx, slot)?,
                 SlotFlag::Empty.into(),
                 false,
             )?;
             let schema = self.layout.schema();
             for fname in schema.fields_iter() {
-                let foffset = self.field_offset(slot, fname)?;
+                let foffset = self.stored_field_offset(tx, slot, fname)?;
                 let ftype = schema.field_type(fname).unwrap();
                 if ftype == SqlType::Integer {
                     tx.set_i32(&self.block, foffset, 0, false)?;
@@ -139,51 +260,123 @@ impl<'tx, 'lm, 'bm, 'lt> RecordPage {
         Ok(())
     }
 
-    pub fn next_after(&self, tx: &'tx Transaction<'lm, 'bm>, slot: Option<i32>) -> Option<i32> {
+    pub fn next_after(&self, tx: &'tx Transaction, slot: Option<i32>) -> Result<Option<i32>> {
         self.search_after(tx, slot, SlotFlag::Used)
     }
 
-    pub fn insert_after(
+    pub fn insert_after(&self, tx: &'tx mut Transaction, slot: Option<i32>) -> Result<Option<i32>> {
+        // NOTE: the current version only
+        if self.version(tx)? != self.layout.version() {
+            return Ok(None);
+        }
+        let mut current = slot;
+        // NOTE: searched without locks, so checked again under the xlock
+        while let Some(newslot) = self.search_after(tx, current, SlotFlag::Empty)? {
+            tx.xlock_slot(&self.block, newslot)?;
+            let flag = tx.get_i32_in_slot(&self.block, newslot, self.slot_offset(tx, newslot)?)?;
+            if flag == SlotFlag::Empty.into() {
+                self.set_flag(tx, newslot, SlotFlag::Used)?;
+                return Ok(Some(newslot));
+            }
+            current = Some(newslot);
+        }
+        Ok(None)
+    }
+
+    pub fn insert_rows(
         &self,
-        tx: &'tx mut Transaction<'lm, 'bm>,
+        tx: &'tx mut Transaction,
         slot: Option<i32>,
-    ) -> Option<i32> {
-        if let Some(newslot) = self.search_after(tx, slot, SlotFlag::Empty) {
-            self.set_flag(tx, newslot, SlotFlag::Used).unwrap(); // TODO
-            Some(newslot)
-        } else {
-            None
+        rows: &[Vec<Constant>],
+    ) -> Result<Vec<i32>> {
+        if rows.is_empty() || self.version(tx)? != self.layout.version() {
+            return Ok(Vec::new());
+        }
+        tx.xlock(&self.block)?;
+        let mut slots = Vec::with_capacity(rows.len());
+        let mut current = slot;
+        while slots.len() < rows.len() {
+            match self.search_after(tx, current, SlotFlag::Empty)? {
+                Some(s) => slots.push(s),
+                None => break,
+            }
+            current = slots.last().copied();
         }
+        // NOTE: in runs of slots that fit in a log record
+        let max = tx.max_raw_bytes(&self.block);
+        let schema = self.layout.schema();
+        let mut i = 0;
+        while i < slots.len() {
+            let start = self.slot_offset(tx, slots[i])?;
+            if self.layout.slotsize() > max {
+                self.set_flag(tx, slots[i], SlotFlag::Used)?;
+                for (fname, val) in schema.fields_iter().zip(&rows[i]) {
+                    let foffset = start + self.layout.field_offset(fname).unwrap();
+                    match val {
+                        Constant::Int(v) => {
+                            tx.set_i32_in_slot(&self.block, slots[i], foffset, *v, true)?
+                        }
+                        Constant::String(v) => {
+                            tx.set_string_in_slot(&self.block, slots[i], foffset, v, true)?
+                        }
+                    }
+                }
+                i += 1;
+                continue;
+            }
+            let mut j = i + 1;
+            while j < slots.len() && self.slot_offset(tx, slots[j] + 1)? - start <= max {
+                j += 1;
+            }
+            let end = self.slot_offset(tx, slots[j - 1] + 1)?;
+            let mut bytes = tx.get_raw_bytes(&self.block, start, end - start)?;
+            {
+                let mut p = Page::for_log(&mut bytes);
+                for (&s, row) in slots[i..j].iter().zip(&rows[i..j]) {
+                    let offset = self.slot_offset(tx, s)? - start;
+                    p.set_i32(offset, SlotFlag::Used.into())?;
+                    for (fname, val) in schema.fields_iter().zip(row) {
+                        let foffset = offset + self.layout.field_offset(fname).unwrap();
+                        match val {
+                            Constant::Int(v) => p.set_i32(foffset, *v)?,
+                            Constant::String(v) => p.set_string(foffset, v)?,
+                        }
+                    }
+                }
+            }
+            tx.set_raw_bytes(&self.block, start, &bytes, true)?;
+            i = j;
+        }
+        Ok(slots)
     }
 
-    fn set_flag(
-        &self,
-        tx: &'tx mut Transaction<'lm, 'bm>,
-        slot: i32,
-        flag: SlotFlag,
-    ) -> Result<()> {
-        Ok(tx.set_i32(&self.block, self.slot_offset(slot)?, flag.into(), true)?)
+    fn set_flag(&self, tx: &'tx mut Transaction, slot: i32, flag: SlotFlag) -> Result<()> {
+        let offset = self.slot_offset(tx, slot)?;
+        Ok(tx.set_i32_in_slot(&self.block, slot, offset, flag.into(), true)?)
     }
 
     fn search_after(
         &self,
-        tx: &'tx Transaction<'lm, 'bm>,
+        tx: &'tx Transaction,
         slot: Option<i32>,
         flag: SlotFlag,
-    ) -> Option<i32> {
+    ) -> Result<Option<i32>> {
         let mut next = slot.map(|s| s + 1).unwrap_or(0);
+        let lock = flag == SlotFlag::Used;
         let flag_i32: i32 = flag.into();
         while self.is_valid_slot(tx, next) {
-            if tx
-                .get_i32(&self.block, self.slot_offset(next).unwrap())
-                .unwrap()
-                == flag_i32
-            {
-                return Some(next);
+            let offset = self.slot_offset(tx, next)?;
+            let current = if lock {
+                tx.get_i32_in_slot(&self.block, next, offset)?
+            } else {
+                tx.peek_i32(&self.block, offset)?
+            };
+            if current == flag_i32 {
+                return Ok(Some(next));
             }
             next += 1;
         }
-        None
+        Ok(None)
     }
 }
 
@@ -212,43 +405,109 @@ mod tests {
 
             let tx = db.new_tx();
             {
-                let block = tx.borrow_mut().append("record_page_text").unwrap();
-                tx.borrow_mut().pin(&block).unwrap();
+                let block = tx.lock().unwrap().append("record_page_text").unwrap();
+                tx.lock().unwrap().pin(&block).unwrap();
 
                 let rp = RecordPage::new(block.clone(), layout.clone());
-                rp.format(&mut tx.borrow_mut()).unwrap();
+                rp.format(&mut tx.lock().unwrap()).unwrap();
 
-                let mut slot = rp.insert_after(&mut tx.borrow_mut(), None);
+                let mut slot = rp.insert_after(&mut tx.lock().unwrap(), None).unwrap();
                 while slot.is_some() {
                     let n = slot.unwrap();
-                    rp.set_i32(&mut tx.borrow_mut(), n, "A", n).unwrap();
-                    rp.set_string(&mut tx.borrow_mut(), n, "B", format!("rec{}", n))
+                    rp.set_i32(&mut tx.lock().unwrap(), n, "A", n).unwrap();
+                    rp.set_string(&mut tx.lock().unwrap(), n, "B", format!("rec{}", n))
                         .unwrap();
-                    slot = rp.insert_after(&mut tx.borrow_mut(), slot);
+                    slot = rp.insert_after(&mut tx.lock().unwrap(), slot).unwrap();
                 }
 
                 let mut prev_slot = None;
-                while let Some(slot) = rp.next_after(&tx.borrow(), prev_slot) {
-                    let a = rp.get_i32(&tx.borrow(), slot, "A").unwrap();
-                    assert_eq!(a, slot);
-                    let b = rp.get_string(&tx.borrow(), slot, "B").unwrap();
-                    assert_eq!(b, format!("rec{}", slot));
-                    prev_slot = Some(slot);
+                {
+                    let tx = tx.lock().unwrap();
+                    while let Some(slot) = rp.next_after(&tx, prev_slot).unwrap() {
+                        let a = rp.get_i32(&tx, slot, "A").unwrap();
+                        assert_eq!(a, slot);
+                        let b = rp.get_string(&tx, slot, "B").unwrap();
+                        assert_eq!(b, format!("rec{}", slot));
+                        prev_slot = Some(slot);
+                    }
                 }
 
                 let slot_num = db.file_mgr().blocksize() / layout.slotsize();
                 let target_slot = (slot_num / 2) as i32;
-                rp.delete(&mut tx.borrow_mut(), target_slot).unwrap();
+                rp.delete(&mut tx.lock().unwrap(), target_slot).unwrap();
 
-                let prev_slot_a = rp.get_i32(&tx.borrow(), target_slot - 1, "A").unwrap();
+                let prev_slot_a = rp
+                    .get_i32(&tx.lock().unwrap(), target_slot - 1, "A")
+                    .unwrap();
                 assert_eq!(prev_slot_a, target_slot - 1);
-                let next_slot =
-                    rp.search_after(&tx.borrow(), Some(target_slot - 1), SlotFlag::Used);
+                let next_slot = rp
+                    .search_after(&tx.lock().unwrap(), Some(target_slot - 1), SlotFlag::Used)
+                    .unwrap();
                 assert_eq!(next_slot, Some(target_slot + 1));
 
-                tx.borrow_mut().unpin(&block);
+                tx.lock().unwrap().unpin(&block);
+            }
+            tx.lock().unwrap().commit().unwrap();
+        }
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_slot_locks() {
+        let dir = tempdir().unwrap();
+        {
+            let db = SimpleDB::new_for_test(dir.path(), "record_page_slot_locks_test.log");
+
+            let mut schema = Schema::new();
+            schema.add_i32_field("A");
+            let layout = Layout::new(schema);
+
+            let tx = db.new_tx();
+            let block = tx.lock().unwrap().append("record_page_slot_locks").unwrap();
+            let rp = RecordPage::new(block.clone(), layout.clone());
+            tx.lock().unwrap().pin(&block).unwrap();
+            rp.format(&mut tx.lock().unwrap()).unwrap();
+            for _ in 0..2 {
+                let slot = rp
+                    .insert_after(&mut tx.lock().unwrap(), None)
+                    .unwrap()
+                    .unwrap();
+                rp.set_i32(&mut tx.lock().unwrap(), slot, "A", slot)
+                    .unwrap();
             }
-            tx.borrow_mut().commit().unwrap();
+            tx.lock().unwrap().unpin(&block);
+            tx.lock().unwrap().commit().unwrap();
+
+            let tx1 = db.new_tx();
+            let tx2 = db.new_tx();
+            let tx3 = db.new_tx();
+            tx1.lock().unwrap().pin(&block).unwrap();
+            tx2.lock().unwrap().pin(&block).unwrap();
+            tx3.lock().unwrap().pin(&block).unwrap();
+            rp.set_i32(&mut tx1.lock().unwrap(), 0, "A", 10).unwrap();
+            rp.set_i32(&mut tx2.lock().unwrap(), 1, "A", 11).unwrap();
+            assert_eq!(
+                rp.insert_after(&mut tx3.lock().unwrap(), None).unwrap(),
+                Some(2)
+            );
+            assert_eq!(rp.get_i32(&tx2.lock().unwrap(), 1, "A").unwrap(), 11);
+            tx1.lock().unwrap().unpin(&block);
+            tx1.lock().unwrap().commit().unwrap();
+            tx2.lock().unwrap().unpin(&block);
+            tx2.lock().unwrap().rollback().unwrap();
+            tx3.lock().unwrap().unpin(&block);
+            tx3.lock().unwrap().commit().unwrap();
+
+            let tx4 = db.new_tx();
+            tx4.lock().unwrap().pin(&block).unwrap();
+            assert_eq!(rp.get_i32(&tx4.lock().unwrap(), 0, "A").unwrap(), 10);
+            assert_eq!(rp.get_i32(&tx4.lock().unwrap(), 1, "A").unwrap(), 1);
+            assert_eq!(
+                rp.next_after(&tx4.lock().unwrap(), Some(1)).unwrap(),
+                Some(2)
+            );
+            tx4.lock().unwrap().unpin(&block);
+            tx4.lock().unwrap().commit().unwrap();
         }
         dir.close().unwrap();
     }
@@ -3,12 +3,19 @@
 // This software is released under the MIT License.
 // https://opensource.org/licenses/MIT
 
-use crate::{constants::I32_BYTE_SIZE, file::page::Page};
-use std::{collections::HashMap, convert::Into};
+use crate::{constants::I32_BYTE_SIZE, file::page::Page, query::predicate::Constant};
+use serde::{Deserialize, Serialize};
+use std::{
+    borrow::Cow,
+    cmp::Ordering,
+    collections::{BTreeMap, HashMap},
+    convert::Into,
+    fmt::Display,
+};
 use thiserror::Error;
 
 // NOTE: java.sql.Types
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum SqlType {
     Integer = 4,
     VarChar = 12,
@@ -20,10 +27,22 @@ impl Into<i32> for SqlType {
     }
 }
 
+impl Display for SqlType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SqlType::Integer => write!(f, "int"),
+            SqlType::VarChar => write!(f, "varchar"),
+        }
+    }
+}
+
 #[derive(Debug, Error)]
 pub enum SqlTypeError {
     #[error("unknown SQL type: {0}")]
     UnknownNumber(i32),
+
+    #[error("unknown collation: {0}")]
+    UnknownCollation(i32),
 }
 
 impl TryFrom<i32> for SqlType {
@@ -38,13 +57,104 @@ impl TryFrom<i32> for SqlType {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Collation {
+    #[default]
+    Binary = 0,
+    NoCase = 1,
+}
+
+impl Collation {
+    pub fn is_binary(&self) -> bool {
+        *self == Collation::Binary
+    }
+
+    pub fn key<'a>(&self, val: &'a Constant) -> Cow<'a, Constant> {
+        match (self, val) {
+            (Collation::NoCase, Constant::String(s))
+                if s.bytes().any(|b| b.is_ascii_uppercase()) =>
+            {
+                Cow::Owned(Constant::String(s.to_ascii_lowercase()))
+            }
+            _ => Cow::Borrowed(val),
+        }
+    }
+
+    pub fn compare(&self, lhs: &Constant, rhs: &Constant) -> Option<Ordering> {
+        self.key(lhs).partial_cmp(&self.key(rhs))
+    }
+
+    pub fn equals(&self, lhs: &Constant, rhs: &Constant) -> bool {
+        self.compare(lhs, rhs) == Some(Ordering::Equal)
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "binary" => Some(Collation::Binary),
+            "nocase" => Some(Collation::NoCase),
+            _ => None,
+        }
+    }
+}
+
+impl Display for Collation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Collation::Binary => write!(f, "binary"),
+            Collation::NoCase => write!(f, "nocase"),
+        }
+    }
+}
+
+impl From<Collation> for i32 {
+    fn from(collation: Collation) -> Self {
+        collation as i32
+    }
+}
+
+impl TryFrom<i32> for Collation {
+    type Error = SqlTypeError;
+
+    fn try_from(value: i32) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Collation::Binary),
+            1 => Ok(Collation::NoCase),
+            _ => Err(SqlTypeError::UnknownCollation(value)),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum SchemaError {
+    #[error("duplicate field: {0}")]
+    DuplicateField(String),
+
+    #[error("no offset for field: {0}")]
+    MissingOffset(String),
+
+    #[error("offset {1} of field {0} is out of the slot")]
+    InvalidOffset(String, usize),
+}
+
 #[derive(Debug, Clone)]
 struct FieldInfo {
     ftype: SqlType,
     flength: usize,
+    collation: Collation,
 }
 
-#[derive(Clone)]
+#[derive(Serialize, Deserialize)]
+struct FieldDef {
+    name: String,
+    #[serde(rename = "type")]
+    ftype: SqlType,
+    length: usize,
+    #[serde(default, skip_serializing_if = "Collation::is_binary")]
+    collation: Collation,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(into = "Vec<FieldDef>", try_from = "Vec<FieldDef>")]
 pub struct Schema {
     fields: Vec<String>,
     info: HashMap<String, FieldInfo>,
@@ -80,9 +190,28 @@ impl Schema {
             .and_then(|fi| Some(fi.flength.try_into().unwrap()))
     }
 
+    pub fn field_collation(&self, fname: &str) -> Option<Collation> {
+        self.info.get(fname).map(|fi| fi.collation)
+    }
+
     pub fn add_field(&mut self, fname: &str, ftype: SqlType, flength: usize) {
         self.fields.push(fname.into());
-        self.info.insert(fname.into(), FieldInfo { ftype, flength });
+        self.info.insert(
+            fname.into(),
+            FieldInfo {
+                ftype,
+                flength,
+                collation: Collation::Binary,
+            },
+        );
+    }
+
+    pub fn set_collation(&mut self, fname: &str, collation: Collation) {
+        if let Some(fi) = self.info.get_mut(fname) {
+            if fi.ftype == SqlType::VarChar {
+                fi.collation = collation;
+            }
+        }
     }
 
     pub fn add_i32_field(&mut self, fname: &str) {
@@ -97,6 +226,7 @@ impl Schema {
         let ft = schema.field_type(fname).unwrap(); // TODO
         let fl = schema.field_length(fname).unwrap(); // TODO
         self.add_field(fname, ft, fl);
+        self.set_collation(fname, schema.field_collation(fname).unwrap());
     }
 
     pub fn add_all(&mut self, schema: &Schema) {
@@ -106,17 +236,59 @@ impl Schema {
     }
 }
 
-#[derive(Clone)]
+impl From<Schema> for Vec<FieldDef> {
+    fn from(schema: Schema) -> Self {
+        let Schema { fields, mut info } = schema;
+        fields
+            .into_iter()
+            .map(|name| {
+                let fi = info.remove(&name).unwrap(); // NOTE: every field has its info
+                FieldDef {
+                    name,
+                    ftype: fi.ftype,
+                    length: fi.flength,
+                    collation: fi.collation,
+                }
+            })
+            .collect()
+    }
+}
+
+impl TryFrom<Vec<FieldDef>> for Schema {
+    type Error = SchemaError;
+
+    fn try_from(defs: Vec<FieldDef>) -> Result<Self, Self::Error> {
+        let mut schema = Schema::new();
+        for def in defs {
+            if schema.has_field(&def.name) {
+                return Err(SchemaError::DuplicateField(def.name));
+            }
+            schema.add_field(&def.name, def.ftype, def.length);
+            schema.set_collation(&def.name, def.collation);
+        }
+        Ok(schema)
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(into = "LayoutDef", try_from = "LayoutDef")]
 pub struct Layout {
     schema: Schema,
     offsets: HashMap<String, usize>,
     slotsize: usize,
+    version: i32,
+    // NOTE: still used by the record pages written under them
+    older: Vec<Layout>,
 }
 
 impl Layout {
+    pub const PAGE_HEADER_SIZE: usize = I32_BYTE_SIZE as usize;
+
+    pub const SLOT_HEADER_SIZE: usize = I32_BYTE_SIZE as usize;
+
     pub fn new(schema: Schema) -> Self {
         let mut offsets: HashMap<String, usize> = HashMap::new();
-        let mut pos: usize = I32_BYTE_SIZE as usize;
+        let mut pos: usize = Self::SLOT_HEADER_SIZE;
         for fname in schema.fields_iter() {
             offsets.insert(fname.into(), pos);
             pos += Self::length_in_bytes(&schema, fname).unwrap(); // TODO
@@ -127,6 +299,8 @@ impl Layout {
             schema,
             offsets,
             slotsize,
+            version: 0,
+            older: Vec::new(),
         }
     }
 
@@ -135,9 +309,38 @@ impl Layout {
             schema,
             offsets,
             slotsize,
+            version: 0,
+            older: Vec::new(),
         }
     }
 
+    pub fn with_older_versions(mut self, older: Vec<Layout>) -> Self {
+        self.version = older.len().try_into().unwrap();
+        self.older = older;
+        self
+    }
+
+    pub fn version(&self) -> i32 {
+        self.version
+    }
+
+    pub fn of_version(&self, version: i32) -> Option<&Layout> {
+        if version == self.version {
+            Some(self)
+        } else {
+            usize::try_from(version)
+                .ok()
+                .and_then(|v| self.older.get(v))
+        }
+    }
+
+    pub fn is_stored_in(&self, fname: &str, version: i32) -> bool {
+        (version..=self.version).all(|v| {
+            self.of_version(v)
+                .is_some_and(|layout| layout.schema.has_field(fname))
+        })
+    }
+
     pub fn schema(&self) -> &Schema {
         &self.schema
     }
@@ -150,7 +353,7 @@ impl Layout {
         self.slotsize
     }
 
-    fn length_in_bytes(schema: &Schema, fname: &str) -> Option<usize> {
+    pub(crate) fn length_in_bytes(schema: &Schema, fname: &str) -> Option<usize> {
         if let Some(ftype) = schema.field_type(fname) {
             if ftype == SqlType::Integer {
                 Some(I32_BYTE_SIZE as usize)
@@ -163,9 +366,53 @@ impl Layout {
     }
 }
 
+#[derive(Serialize, Deserialize)]
+struct LayoutDef {
+    schema: Schema,
+    offsets: BTreeMap<String, usize>,
+    slotsize: usize,
+    older: Vec<Layout>,
+}
+
+impl From<Layout> for LayoutDef {
+    fn from(layout: Layout) -> Self {
+        Self {
+            schema: layout.schema,
+            offsets: layout.offsets.into_iter().collect(),
+            slotsize: layout.slotsize,
+            older: layout.older,
+        }
+    }
+}
+
+impl TryFrom<LayoutDef> for Layout {
+    type Error = SchemaError;
+
+    fn try_from(def: LayoutDef) -> Result<Self, Self::Error> {
+        for fname in def.schema.fields_iter() {
+            let offset = *def
+                .offsets
+                .get(fname)
+                .ok_or_else(|| SchemaError::MissingOffset(fname.clone()))?;
+            let length = Layout::length_in_bytes(&def.schema, fname).unwrap();
+            if offset < Layout::SLOT_HEADER_SIZE || offset + length > def.slotsize {
+                return Err(SchemaError::InvalidOffset(fname.clone(), offset));
+            }
+        }
+        let offsets = def
+            .offsets
+            .into_iter()
+            .filter(|(fname, _)| def.schema.has_field(fname))
+            .collect();
+        Ok(Layout::from_metadata(def.schema, offsets, def.slotsize).with_older_versions(def.older))
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{Layout, Schema};
+    use super::{Collation, Layout, Schema};
+    use crate::query::predicate::Constant;
+    use std::cmp::Ordering;
 
     #[test]
     fn test() {
@@ -178,4 +425,64 @@ mod tests {
         assert_eq!(layout.field_offset("B"), Some(8));
         assert_eq!(layout.slotsize(), 48); // NOTE: 4 + 4 + 4 (area of string bytes length) + (9 (field length) * 4 (bytes/char))
     }
+
+    #[test]
+    fn test_collation() {
+        let s = |s: &str| Constant::String(s.into());
+        assert_eq!(
+            Collation::Binary.compare(&s("a"), &s("B")),
+            Some(Ordering::Greater)
+        );
+        assert_eq!(
+            Collation::NoCase.compare(&s("a"), &s("B")),
+            Some(Ordering::Less)
+        );
+        assert!(Collation::NoCase.equals(&s("Straße"), &s("STRAßE")));
+        assert!(!Collation::Binary.equals(&s("abc"), &s("ABC")));
+        assert_eq!(*Collation::NoCase.key(&s("AbC")), s("abc"));
+        assert_eq!(*Collation::NoCase.key(&Constant::Int(1)), Constant::Int(1));
+
+        let mut schema = Schema::new();
+        schema.add_i32_field("A");
+        schema.add_string_field("B", 9);
+        schema.set_collation("A", Collation::NoCase);
+        schema.set_collation("B", Collation::NoCase);
+        assert_eq!(schema.field_collation("A"), Some(Collation::Binary));
+        assert_eq!(schema.field_collation("B"), Some(Collation::NoCase));
+        assert_eq!(schema.field_collation("C"), None);
+
+        let mut copy = Schema::new();
+        copy.add_all(&schema);
+        assert_eq!(copy.field_collation("B"), Some(Collation::NoCase));
+        assert_eq!(
+            Collation::try_from(i32::from(Collation::NoCase)).unwrap(),
+            Collation::NoCase
+        );
+    }
+
+    #[test]
+    fn test_versions() {
+        let mut schema = Schema::new();
+        schema.add_i32_field("A");
+        schema.add_i32_field("B");
+        let v0 = Layout::new(schema);
+
+        let mut schema = Schema::new();
+        schema.add_i32_field("A");
+        let v1 = Layout::new(schema.clone());
+
+        schema.add_string_field("B", 9);
+        let v2 = Layout::new(schema).with_older_versions(vec![v0, v1]);
+
+        assert_eq!(v2.version(), 2);
+        assert_eq!(v2.of_version(0).unwrap().slotsize(), 12);
+        assert_eq!(v2.of_version(2).unwrap().slotsize(), 48);
+        assert!(v2.of_version(3).is_none());
+        assert!(v2.of_version(-1).is_none());
+
+        assert!(v2.is_stored_in("A", 0));
+        assert!(v2.is_stored_in("B", 2));
+        assert!(!v2.is_stored_in("B", 1));
+        assert!(!v2.is_stored_in("B", 0));
+    }
 }
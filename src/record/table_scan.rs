@@ -5,117 +5,268 @@
 
 use super::{
     record_page::RecordPage,
-    schema::{Layout, SqlType},
+    schema::{Layout, Schema, SqlType},
 };
 use crate::{
+    buffer_mgr::PinPriority,
     file::block_id::BlockId,
     query::{
+        batch::RowBatch,
         predicate::Constant,
-        scan::{Result, Scan, UpdateScan, RID},
+        scan::{Result, Scan, ScanError, UpdateScan, RID},
     },
     tx::transaction::Transaction,
 };
-use std::{cell::RefCell, rc::Rc};
+use std::{
+    cmp::min,
+    collections::HashSet,
+    sync::{Arc, Mutex},
+};
+
+const PREFETCH_BLOCKS: i64 = 4;
+
+pub(crate) fn check_length(schema: &Schema, fname: &str, val: &str) -> Result<()> {
+    if schema.field_type(fname) != Some(SqlType::VarChar) {
+        return Ok(());
+    }
+    let length = schema.field_length(fname).unwrap();
+    if val.chars().count() > length {
+        return Err(ScanError::ValueTooLong(fname.into(), length));
+    }
+    Ok(())
+}
 
-pub struct TableScan<'lm, 'bm> {
-    tx: Rc<RefCell<Transaction<'lm, 'bm>>>,
+pub struct TableScan {
+    tx: Arc<Mutex<Transaction>>,
     layout: Layout,
     filename: String,
     rp: RecordPage,
     current_slot: Option<i32>,
     closed: bool,
+    priority: PinPriority,
+    // NOTE: moved by `upgrade`, and already visited by `next`
+    moved: HashSet<RID>,
+    resume_at: Option<RID>,
+    // NOTE: so that `next_batch` doesn't read the last block again
+    at_end: bool,
 }
 
-impl<'tx, 'lm, 'bm> TableScan<'lm, 'bm> {
-    pub fn new(tx: Rc<RefCell<Transaction<'lm, 'bm>>>, tblname: String, layout: Layout) -> Self {
-        let filename = format!("{tblname}.tbl");
+impl TableScan {
+    pub fn new(tx: Arc<Mutex<Transaction>>, tblname: String, layout: Layout) -> Result<Self> {
+        TableScan::new_with_priority(tx, tblname, layout, PinPriority::Normal)
+    }
+
+    pub fn filename(tblname: &str) -> String {
+        format!("{tblname}.tbl")
+    }
+
+    pub fn new_with_priority(
+        tx: Arc<Mutex<Transaction>>,
+        tblname: String,
+        layout: Layout,
+        priority: PinPriority,
+    ) -> Result<Self> {
+        let filename = TableScan::filename(&tblname);
         let rp = {
-            let mut tx = tx.borrow_mut();
-            if tx.size(&filename).unwrap() == 0 {
-                let block = tx.append(&filename).unwrap(); // TODO
-                tx.pin(&block).unwrap(); // TODO
+            let mut tx = tx.lock().unwrap();
+            if tx.size(&filename)? == 0 {
+                let block = tx.append(&filename)?;
+                tx.pin_with_priority(&block, priority)?;
                 let rp = RecordPage::new(block, layout.clone());
-                rp.format(&mut *tx).unwrap(); // TODO
+                rp.format(&mut tx)?;
                 rp
             } else {
                 let block = BlockId::new(&filename, 0);
-                tx.pin(&block).unwrap(); // TODO
+                tx.pin_with_priority(&block, priority)?;
                 RecordPage::new(block, layout.clone())
             }
         };
 
-        Self {
+        Ok(Self {
             tx,
             layout,
             filename,
             rp,
             current_slot: None,
             closed: false,
-        }
+            priority,
+            moved: HashSet::new(),
+            resume_at: None,
+            at_end: false,
+        })
     }
 
     fn close(&mut self) {
         if !self.closed {
-            self.tx.borrow_mut().unpin(self.rp.block());
+            self.tx.lock().unwrap().unpin(self.rp.block());
             self.closed = true;
         }
     }
 
     fn move_to_block(&mut self, blknum: i64) -> Result<()> {
+        self.pin_block(blknum)?;
+        self.current_slot = None;
+        Ok(())
+    }
+
+    fn pin_block(&mut self, blknum: i64) -> Result<()> {
+        if !self.closed && self.rp.block().number() == blknum {
+            return Ok(());
+        }
         self.close();
         let block = BlockId::new(&self.filename, blknum);
-        self.tx.borrow_mut().pin(&block)?;
-        self.rp = RecordPage::new(block, self.layout.clone());
-        self.current_slot = None;
+        self.tx
+            .lock()
+            .unwrap()
+            .pin_with_priority(&block, self.priority)?;
+        self.rp.move_to(block);
+        self.closed = false;
         Ok(())
     }
 
     fn move_to_new_block(&mut self) -> Result<()> {
         self.close();
         {
-            let mut tx = self.tx.borrow_mut();
+            let mut tx = self.tx.lock().unwrap();
             let block = tx.append(&self.filename)?;
-            tx.pin(&block)?;
-            self.rp = RecordPage::new(block, self.layout.clone());
+            tx.pin_with_priority(&block, self.priority)?;
+            self.rp.move_to(block);
             self.rp.format(&mut *tx)?;
         }
+        self.closed = false;
         self.current_slot = None;
         Ok(())
     }
 
     pub fn before_first(&mut self) -> Result<()> {
+        self.tx.lock().unwrap().slock_table(&self.filename)?;
+        self.moved.clear();
+        self.resume_at = None;
+        self.at_end = false;
         self.move_to_block(0)
     }
 
-    fn as_last_block(&self) -> bool {
-        self.rp.block().number() as u64 == self.tx.borrow().size(&self.filename).unwrap() - 1
+    fn as_last_block(&self) -> Result<bool> {
+        let size = self.tx.lock().unwrap().size(&self.filename)?;
+        Ok(self.rp.block().number() as u64 + 1 == size)
+    }
+
+    fn prefetch_after(&self, blknum: i64) -> Result<()> {
+        let tx = self.tx.lock().unwrap();
+        let size = tx.size(&self.filename)? as i64;
+        let blocks = (blknum + 1..min(blknum + 1 + PREFETCH_BLOCKS, size))
+            .map(|n| BlockId::new(&self.filename, n))
+            .collect::<Vec<_>>();
+        tx.prefetch(&blocks);
+        Ok(())
+    }
+
+    fn slot(&self) -> Result<i32> {
+        self.current_slot.ok_or(ScanError::NoCurrentRecord)
     }
 
     pub fn next(&mut self) -> Result<bool> {
-        // let tx = self.tx.borrow();
-        self.current_slot = self.rp.next_after(&self.tx.borrow(), self.current_slot);
+        // NOTE: every scan reaches here, so it stops at the deadline
+        self.tx.lock().unwrap().check_deadline()?;
+        if let Some(rid) = self.resume_at.take() {
+            self.move_to_rid(rid)?;
+        }
+        while self.next_slot()? {
+            if !self.moved.contains(&self.current_rid()) {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    pub fn next_batch(&mut self, batch: &mut RowBatch) -> Result<bool> {
+        batch.clear();
+        if self.at_end {
+            return Ok(false);
+        }
+        self.tx.lock().unwrap().check_deadline()?;
+        if let Some(rid) = self.resume_at.take() {
+            self.move_to_rid(rid)?;
+        }
+        while !batch.is_full() {
+            {
+                let schema = self.layout.schema();
+                let tx = self.tx.lock().unwrap();
+                while !batch.is_full() {
+                    self.current_slot = self.rp.next_after(&tx, self.current_slot)?;
+                    let Some(slot) = self.current_slot else {
+                        break;
+                    };
+                    if self.moved.contains(&self.current_rid()) {
+                        continue;
+                    }
+                    batch.push_with(|f| match schema.field_type(f) {
+                        Some(SqlType::Integer) => Ok(Constant::Int(self.rp.get_i32(&tx, slot, f)?)),
+                        Some(SqlType::VarChar) => {
+                            Ok(Constant::String(self.rp.get_string(&tx, slot, f)?))
+                        }
+                        None => Err(ScanError::FieldNotFound(f.into())),
+                    })?;
+                }
+            }
+            if self.current_slot.is_none() {
+                if self.as_last_block()? {
+                    self.at_end = true;
+                    break;
+                }
+                self.move_to_block(self.rp.block().number() + 1)?;
+                self.prefetch_after(self.rp.block().number())?;
+            }
+        }
+        Ok(!batch.is_empty())
+    }
+
+    fn next_slot(&mut self) -> Result<bool> {
+        self.current_slot = self
+            .rp
+            .next_after(&self.tx.lock().unwrap(), self.current_slot)?;
         while self.current_slot.is_none() {
-            if self.as_last_block() {
+            if self.as_last_block()? {
                 return Ok(false);
             }
             self.move_to_block(self.rp.block().number() + 1)?;
-            self.current_slot = self.rp.next_after(&self.tx.borrow(), self.current_slot);
+            self.prefetch_after(self.rp.block().number())?;
+            self.current_slot = self
+                .rp
+                .next_after(&self.tx.lock().unwrap(), self.current_slot)?;
         }
         Ok(true)
     }
 
     pub fn get_i32(&self, fname: &str) -> Result<i32> {
-        let slot = self.current_slot.as_ref().unwrap();
-        Ok(self.rp.get_i32(&*self.tx.borrow(), *slot, fname)?)
+        let slot = self.slot()?;
+        Ok(self.rp.get_i32(&self.tx.lock().unwrap(), slot, fname)?)
     }
 
     pub fn get_string(&self, fname: &str) -> Result<String> {
-        let slot = self.current_slot.as_ref().unwrap();
-        Ok(self.rp.get_string(&*self.tx.borrow(), *slot, fname)?)
+        let slot = self.slot()?;
+        Ok(self.rp.get_string(&self.tx.lock().unwrap(), slot, fname)?)
+    }
+
+    pub fn read_string(&self, fname: &str, buf: &mut String) -> Result<()> {
+        let slot = self.slot()?;
+        Ok(self
+            .rp
+            .read_string(&self.tx.lock().unwrap(), slot, fname, buf)?)
+    }
+
+    pub fn get_bytes(&self, fname: &str) -> Result<Vec<u8>> {
+        let slot = self.slot()?;
+        Ok(self.rp.get_bytes(&self.tx.lock().unwrap(), slot, fname)?)
     }
 
     pub fn get_val(&self, fname: &str) -> Result<Constant> {
-        if self.layout.schema().field_type(fname).unwrap() == SqlType::Integer {
+        let ftype = self
+            .layout
+            .schema()
+            .field_type(fname)
+            .ok_or_else(|| ScanError::FieldNotFound(fname.into()))?;
+        if ftype == SqlType::Integer {
             self.get_i32(fname).map(Constant::Int)
         } else {
             self.get_string(fname).map(Constant::String)
@@ -127,17 +278,28 @@ impl<'tx, 'lm, 'bm> TableScan<'lm, 'bm> {
     }
 
     pub fn set_i32(&mut self, fname: &str, val: i32) -> Result<()> {
-        let slot = self.current_slot.as_ref().unwrap();
+        self.upgrade_if_missing(fname)?;
+        let slot = self.slot()?;
         Ok(self
             .rp
-            .set_i32(&mut *self.tx.borrow_mut(), *slot, fname, val)?)
+            .set_i32(&mut self.tx.lock().unwrap(), slot, fname, val)?)
     }
 
     pub fn set_string(&mut self, fname: &str, val: String) -> Result<()> {
-        let slot = self.current_slot.as_ref().unwrap();
+        check_length(self.layout.schema(), fname, &val)?;
+        self.upgrade_if_missing(fname)?;
+        let slot = self.slot()?;
+        Ok(self
+            .rp
+            .set_string(&mut self.tx.lock().unwrap(), slot, fname, val)?)
+    }
+
+    pub fn set_bytes(&mut self, fname: &str, val: &[u8]) -> Result<()> {
+        self.upgrade_if_missing(fname)?;
+        let slot = self.slot()?;
         Ok(self
             .rp
-            .set_string(&mut *self.tx.borrow_mut(), *slot, fname, val)?)
+            .set_bytes(&mut self.tx.lock().unwrap(), slot, fname, val)?)
     }
 
     pub fn set_val(&mut self, fname: &str, val: Constant) -> Result<()> {
@@ -145,40 +307,126 @@ impl<'tx, 'lm, 'bm> TableScan<'lm, 'bm> {
         match val {
             Constant::Int(v) if ftype == Some(SqlType::Integer) => self.set_i32(fname, v),
             Constant::String(v) if ftype == Some(SqlType::VarChar) => self.set_string(fname, v),
-            _ => panic!("mismatched type: fname={fname}, val={val:?}"),
+            _ if ftype.is_none() => Err(ScanError::FieldNotFound(fname.into())),
+            _ => Err(ScanError::TypeMismatch(fname.into(), val)),
         }
     }
 
     pub fn insert(&mut self) -> Result<()> {
         self.current_slot = self
             .rp
-            .insert_after(&mut self.tx.borrow_mut(), self.current_slot);
+            .insert_after(&mut self.tx.lock().unwrap(), self.current_slot)?;
         while self.current_slot.is_none() {
-            if self.as_last_block() {
+            self.records_per_block()?;
+            if self.as_last_block()? {
                 self.move_to_new_block()?;
             } else {
                 self.move_to_block(self.rp.block().number() + 1)?;
             }
             self.current_slot = self
                 .rp
-                .insert_after(&mut self.tx.borrow_mut(), self.current_slot);
+                .insert_after(&mut self.tx.lock().unwrap(), self.current_slot)?;
         }
         Ok(())
     }
 
+    fn records_per_block(&self) -> Result<usize> {
+        let space = self.tx.lock().unwrap().block_size() - Layout::PAGE_HEADER_SIZE;
+        match space / self.layout.slotsize() {
+            0 => Err(ScanError::RecordTooLarge(self.layout.slotsize(), space)),
+            n => Ok(n),
+        }
+    }
+
+    pub fn insert_rows<I: IntoIterator<Item = Vec<Constant>>>(
+        &mut self,
+        rows: I,
+    ) -> Result<Vec<RID>> {
+        let schema = self.layout.schema().clone();
+        let per_block = self.records_per_block()?;
+        let mut rows = rows.into_iter();
+        let mut pending: Vec<Vec<Constant>> = Vec::with_capacity(per_block);
+        let mut rids = Vec::new();
+        loop {
+            for row in rows.by_ref().take(per_block - pending.len()) {
+                if row.len() != schema.fields_iter().len() {
+                    return Err(ScanError::ArityMismatch(
+                        schema.fields_iter().len(),
+                        row.len(),
+                    ));
+                }
+                for (fname, val) in schema.fields_iter().zip(&row) {
+                    let ftype = schema.field_type(fname);
+                    match val {
+                        Constant::Int(_) if ftype == Some(SqlType::Integer) => {}
+                        Constant::String(s) if ftype == Some(SqlType::VarChar) => {
+                            check_length(&schema, fname, s)?
+                        }
+                        _ => return Err(ScanError::TypeMismatch(fname.clone(), val.clone())),
+                    }
+                }
+                pending.push(row);
+            }
+            if pending.is_empty() {
+                return Ok(rids);
+            }
+            let slots =
+                self.rp
+                    .insert_rows(&mut self.tx.lock().unwrap(), self.current_slot, &pending)?;
+            if let Some(&last) = slots.last() {
+                let blknum = self.rp.block().number();
+                rids.extend(slots.iter().map(|&s| RID::new(blknum, Some(s))));
+                pending.drain(..slots.len());
+                self.current_slot = Some(last);
+            }
+            if !pending.is_empty() {
+                if self.as_last_block()? {
+                    self.move_to_new_block()?;
+                } else {
+                    self.move_to_block(self.rp.block().number() + 1)?;
+                }
+            }
+        }
+    }
+
     pub fn delete(&mut self) -> Result<()> {
-        if let Some(slot) = self.current_slot.as_ref() {
-            Ok(self.rp.delete(&mut *self.tx.borrow_mut(), *slot)?)
-        } else {
-            Ok(())
+        let slot = self.slot()?;
+        Ok(self.rp.delete(&mut self.tx.lock().unwrap(), slot)?)
+    }
+
+    fn upgrade_if_missing(&mut self, fname: &str) -> Result<()> {
+        let slot = self.slot()?;
+        if !self.rp.is_missing(&self.tx.lock().unwrap(), fname)? {
+            return Ok(());
+        }
+        let schema = self.layout.schema().clone();
+        let mut ints = Vec::new();
+        let mut bytes = Vec::new();
+        for f in schema.fields_iter() {
+            if schema.field_type(f) == Some(SqlType::Integer) {
+                ints.push((f, self.get_i32(f)?));
+            } else {
+                bytes.push((f, self.get_bytes(f)?));
+            }
+        }
+        let old_rid = RID::new(self.rp.block().number(), Some(slot));
+        self.delete()?;
+        self.insert()?;
+        for (f, val) in ints {
+            self.set_i32(f, val)?;
         }
+        for (f, val) in bytes {
+            self.set_bytes(f, &val)?;
+        }
+        self.moved.insert(self.current_rid());
+        self.resume_at = Some(old_rid);
+        Ok(())
     }
 
     pub fn move_to_rid(&mut self, rid: RID) -> Result<()> {
-        self.close();
-        let block = BlockId::new(&self.filename, rid.block_number());
-        self.tx.borrow_mut().pin(&block)?;
-        self.rp = RecordPage::new(block, self.layout.clone());
+        self.resume_at = None;
+        self.at_end = false;
+        self.pin_block(rid.block_number())?;
         self.current_slot = rid.slot();
         Ok(())
     }
@@ -188,7 +436,7 @@ impl<'tx, 'lm, 'bm> TableScan<'lm, 'bm> {
     }
 }
 
-impl<'tx, 'lm, 'bm> Scan for TableScan<'lm, 'bm> {
+impl Scan for TableScan {
     fn before_first(&mut self) -> Result<()> {
         TableScan::before_first(self)
     }
@@ -205,6 +453,14 @@ impl<'tx, 'lm, 'bm> Scan for TableScan<'lm, 'bm> {
         TableScan::get_string(self, field_name)
     }
 
+    fn read_string(&self, field_name: &str, buf: &mut String) -> crate::query::scan::Result<()> {
+        TableScan::read_string(self, field_name, buf)
+    }
+
+    fn next_batch(&mut self, batch: &mut RowBatch) -> crate::query::scan::Result<bool> {
+        TableScan::next_batch(self, batch)
+    }
+
     fn get_val(&self, field_name: &str) -> crate::query::scan::Result<Constant> {
         TableScan::get_val(self, field_name)
     }
@@ -213,12 +469,20 @@ impl<'tx, 'lm, 'bm> Scan for TableScan<'lm, 'bm> {
         TableScan::has_field(self, field_name)
     }
 
+    fn fields(&self) -> Vec<String> {
+        self.layout.schema().fields_iter().cloned().collect()
+    }
+
+    fn field_type(&self, field_name: &str) -> Option<SqlType> {
+        self.layout.schema().field_type(field_name)
+    }
+
     fn close(&mut self) {
         TableScan::close(self);
     }
 }
 
-impl<'tx, 'lm, 'bm> UpdateScan for TableScan<'lm, 'bm> {
+impl UpdateScan for TableScan {
     fn set_val(&mut self, field_name: &str, value: Constant) -> crate::query::scan::Result<()> {
         TableScan::set_val(self, field_name, value)
     }
@@ -240,6 +504,7 @@ impl<'tx, 'lm, 'bm> UpdateScan for TableScan<'lm, 'bm> {
     }
 
     fn get_rid(&self) -> Result<RID> {
+        self.slot()?;
         Ok(TableScan::current_rid(self))
     }
 
@@ -248,7 +513,7 @@ impl<'tx, 'lm, 'bm> UpdateScan for TableScan<'lm, 'bm> {
     }
 }
 
-impl<'tx, 'lm, 'bm> Drop for TableScan<'lm, 'bm> {
+impl Drop for TableScan {
     fn drop(&mut self) {
         self.close();
     }
@@ -258,9 +523,15 @@ impl<'tx, 'lm, 'bm> Drop for TableScan<'lm, 'bm> {
 mod tests {
     use super::TableScan;
     use crate::{
+        query::{
+            batch::RowBatch,
+            predicate::Constant,
+            scan::{ScanError, UpdateScan},
+        },
         record::schema::{Layout, Schema},
         server::simple_db::SimpleDB,
     };
+    use std::time::Instant;
     use tempfile::tempdir;
 
     #[test]
@@ -276,7 +547,7 @@ mod tests {
 
             let tx = db.new_tx();
             {
-                let mut ts = TableScan::new(tx.clone(), "T".into(), layout.clone());
+                let mut ts = TableScan::new(tx.clone(), "T".into(), layout.clone()).unwrap();
                 for i in 0..50 {
                     ts.insert().unwrap();
                     ts.set_i32("A", i).unwrap();
@@ -284,10 +555,13 @@ mod tests {
                 }
 
                 let mut i = 0;
+                let mut buf = String::new();
                 ts.before_first().unwrap();
                 while ts.next().unwrap() {
                     assert_eq!(ts.get_i32("A").unwrap(), i);
                     assert_eq!(ts.get_string("B").unwrap(), format!("rec{i}"));
+                    ts.read_string("B", &mut buf).unwrap();
+                    assert_eq!(buf, format!("rec{i}"));
                     i += 1;
                 }
                 assert_eq!(i, 50);
@@ -307,7 +581,368 @@ mod tests {
                     i += 2;
                 }
             }
-            tx.borrow_mut().commit().unwrap();
+            tx.lock().unwrap().commit().unwrap();
+        }
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_insert_rows() {
+        let dir = tempdir().unwrap();
+        {
+            let db = SimpleDB::new_for_test(dir.path(), "table_scan_insert_rows_test.log");
+
+            let mut schema = Schema::new();
+            schema.add_i32_field("A");
+            schema.add_string_field("B", 9);
+            let layout = Layout::new(schema);
+            let rows = |range: std::ops::Range<i32>| {
+                range.map(|i| vec![Constant::Int(i), Constant::String(format!("rec{i}"))])
+            };
+
+            let tx = db.new_tx();
+            {
+                let mut ts = TableScan::new(tx.clone(), "T".into(), layout.clone()).unwrap();
+                let lsn = db.log_mgr().latest_lsn();
+                for i in 0..200 {
+                    ts.insert().unwrap();
+                    ts.set_i32("A", i).unwrap();
+                    ts.set_string("B", format!("rec{i}")).unwrap();
+                }
+                let per_field = db.log_mgr().latest_lsn() - lsn;
+
+                let lsn = db.log_mgr().latest_lsn();
+                let rids = ts.insert_rows(rows(200..400)).unwrap();
+                let bulk = db.log_mgr().latest_lsn() - lsn;
+                assert_eq!(rids.len(), 200);
+                assert_eq!(ts.current_rid(), rids[199]);
+                assert!(bulk * 10 < per_field, "bulk={bulk}, per_field={per_field}");
+
+                let mut i = 0;
+                ts.before_first().unwrap();
+                while ts.next().unwrap() {
+                    assert_eq!(ts.get_i32("A").unwrap(), i);
+                    assert_eq!(ts.get_string("B").unwrap(), format!("rec{i}"));
+                    i += 1;
+                }
+                assert_eq!(i, 400);
+
+                assert!(matches!(
+                    ts.insert_rows(vec![vec![Constant::Int(0)]]),
+                    Err(ScanError::ArityMismatch(2, 1))
+                ));
+                assert!(matches!(
+                    ts.insert_rows(vec![vec![Constant::Int(0), Constant::Int(1)]]),
+                    Err(ScanError::TypeMismatch(f, _)) if f == "B"
+                ));
+                assert!(matches!(
+                    ts.insert_rows(vec![vec![Constant::Int(0), Constant::String("x".repeat(10))]]),
+                    Err(ScanError::ValueTooLong(f, 9)) if f == "B"
+                ));
+            }
+            tx.lock().unwrap().commit().unwrap();
+
+            let tx = db.new_tx();
+            {
+                let mut ts = TableScan::new(tx.clone(), "T".into(), layout.clone()).unwrap();
+                ts.before_first().unwrap();
+                ts.insert_rows(rows(400..500)).unwrap();
+            }
+            tx.lock().unwrap().rollback().unwrap();
+
+            let tx = db.new_tx();
+            {
+                let mut ts = TableScan::new(tx.clone(), "T".into(), layout.clone()).unwrap();
+                let mut count = 0;
+                ts.before_first().unwrap();
+                while ts.next().unwrap() {
+                    assert!(ts.get_i32("A").unwrap() < 400);
+                    count += 1;
+                }
+                assert_eq!(count, 400);
+            }
+            tx.lock().unwrap().commit().unwrap();
+        }
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_next_batch() {
+        let dir = tempdir().unwrap();
+        {
+            let db = SimpleDB::new_for_test(dir.path(), "table_scan_next_batch_test.log");
+
+            let mut schema = Schema::new();
+            schema.add_i32_field("A");
+            schema.add_string_field("B", 9);
+            let layout = Layout::new(schema);
+
+            let tx = db.new_tx();
+            {
+                let mut ts = TableScan::new(tx.clone(), "T".into(), layout.clone()).unwrap();
+                ts.insert_rows(
+                    (0..50).map(|i| vec![Constant::Int(i), Constant::String(format!("rec{i}"))]),
+                )
+                .unwrap();
+
+                let mut batch = RowBatch::new(vec!["B".into(), "A".into()], 7);
+                let mut i = 0usize;
+                ts.before_first().unwrap();
+                while ts.next_batch(&mut batch).unwrap() {
+                    assert!(batch.len() == 7 || i + batch.len() == 50);
+                    for row in 0..batch.len() {
+                        assert_eq!(batch.value(row, "A"), Some(&Constant::Int(i as i32)));
+                        assert_eq!(
+                            batch.value(row, "B"),
+                            Some(&Constant::String(format!("rec{i}")))
+                        );
+                        i += 1;
+                    }
+                }
+                assert_eq!(i, 50);
+                assert!(!ts.next_batch(&mut batch).unwrap());
+                assert!(batch.is_empty());
+
+                ts.before_first().unwrap();
+                assert!(ts.next_batch(&mut batch).unwrap());
+                assert_eq!(batch.value(0, "A"), Some(&Constant::Int(0)));
+                assert_eq!(ts.get_i32("A").unwrap(), 6);
+                assert!(ts.next().unwrap());
+                assert_eq!(ts.get_i32("A").unwrap(), 7);
+
+                let mut batch = RowBatch::new(vec!["Z".into()], 7);
+                assert!(matches!(
+                    ts.next_batch(&mut batch),
+                    Err(ScanError::FieldNotFound(f)) if f == "Z"
+                ));
+            }
+            tx.lock().unwrap().commit().unwrap();
+        }
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_move_to_rid() {
+        let dir = tempdir().unwrap();
+        {
+            let db = SimpleDB::new_for_test(dir.path(), "table_scan_move_to_rid_test.log");
+
+            let mut schema = Schema::new();
+            schema.add_i32_field("A");
+            schema.add_string_field("B", 9);
+            let layout = Layout::new(schema);
+
+            let tx = db.new_tx();
+            {
+                let mut ts = TableScan::new(tx.clone(), "T".into(), layout.clone()).unwrap();
+                let rids = ts
+                    .insert_rows(
+                        (0..50)
+                            .map(|i| vec![Constant::Int(i), Constant::String(format!("rec{i}"))]),
+                    )
+                    .unwrap();
+
+                let available = db.buffer_mgr().available();
+                for (i, rid) in rids.iter().enumerate().rev().chain(rids.iter().enumerate()) {
+                    ts.move_to_rid(rid.clone()).unwrap();
+                    assert_eq!(ts.get_i32("A").unwrap(), i as i32);
+                    ts.read_string("B", &mut String::new()).unwrap();
+                    assert_eq!(db.buffer_mgr().available(), available);
+                }
+            }
+            tx.lock().unwrap().commit().unwrap();
+        }
+        dir.close().unwrap();
+    }
+
+    #[test]
+    #[ignore]
+    fn bench_scan() {
+        let dir = tempdir().unwrap();
+        {
+            let db = SimpleDB::new_for_test(dir.path(), "table_scan_bench.log");
+
+            let mut schema = Schema::new();
+            schema.add_i32_field("A");
+            schema.add_string_field("B", 9);
+            let layout = Layout::new(schema);
+
+            let rows = 100_000;
+            let tx = db.new_tx();
+            {
+                let mut ts = TableScan::new(tx.clone(), "T".into(), layout.clone()).unwrap();
+                ts.insert_rows(
+                    (0..rows).map(|i| vec![Constant::Int(i), Constant::String(format!("rec{i}"))]),
+                )
+                .unwrap();
+            }
+            tx.lock().unwrap().commit().unwrap();
+
+            let tx = db.new_read_tx();
+            {
+                let mut ts = TableScan::new(tx.clone(), "T".into(), layout.clone()).unwrap();
+                let mut buf = String::new();
+                for _ in 0..3 {
+                    let begin = Instant::now();
+                    let mut n = 0;
+                    ts.before_first().unwrap();
+                    while ts.next().unwrap() {
+                        n += ts.get_i32("A").unwrap();
+                        ts.read_string("B", &mut buf).unwrap();
+                    }
+                    let elapsed = begin.elapsed();
+                    assert_eq!(n, (0..rows).sum::<i32>());
+                    println!(
+                        "scanned {rows} records in {elapsed:?} ({:.0} records/s)",
+                        rows as f64 / elapsed.as_secs_f64()
+                    );
+                }
+
+                let mut batch = RowBatch::new(vec!["A".into(), "B".into()], 256);
+                let begin = Instant::now();
+                let mut count = 0;
+                ts.before_first().unwrap();
+                while ts.next_batch(&mut batch).unwrap() {
+                    count += batch.len();
+                }
+                let elapsed = begin.elapsed();
+                assert_eq!(count, rows as usize);
+                println!("scanned {rows} records in batches in {elapsed:?}");
+            }
+            tx.lock().unwrap().commit().unwrap();
+        }
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_bytes() {
+        let dir = tempdir().unwrap();
+        {
+            let db = SimpleDB::new_for_test(dir.path(), "table_scan_bytes_test.log");
+
+            let mut schema = Schema::new();
+            schema.add_string_field("B", 4);
+            let layout = Layout::new(schema);
+
+            let tx = db.new_tx();
+            {
+                let mut ts = TableScan::new(tx.clone(), "T".into(), layout.clone()).unwrap();
+                ts.insert().unwrap();
+                ts.set_bytes("B", &[0x00, 0xff, 0x10]).unwrap();
+                assert_eq!(ts.get_bytes("B").unwrap(), vec![0x00, 0xff, 0x10]);
+                ts.insert().unwrap();
+                ts.set_string("B", "abc".into()).unwrap();
+                assert_eq!(ts.get_bytes("B").unwrap(), b"abc".to_vec());
+            }
+            tx.lock().unwrap().commit().unwrap();
+
+            let tx = db.new_tx();
+            {
+                let mut ts = TableScan::new(tx.clone(), "T".into(), layout.clone()).unwrap();
+                assert!(ts.next().unwrap());
+                ts.set_bytes("B", &[1; 16]).unwrap();
+                assert_eq!(ts.get_bytes("B").unwrap(), vec![1; 16]);
+            }
+            tx.lock().unwrap().rollback().unwrap();
+
+            let tx = db.new_tx();
+            {
+                let mut ts = TableScan::new(tx.clone(), "T".into(), layout).unwrap();
+                assert!(ts.next().unwrap());
+                assert_eq!(ts.get_bytes("B").unwrap(), vec![0x00, 0xff, 0x10]);
+                assert!(matches!(ts.get_bytes("Z"), Err(ScanError::RecordPage(_))));
+            }
+            tx.lock().unwrap().commit().unwrap();
+        }
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_errors() {
+        let dir = tempdir().unwrap();
+        {
+            let db = SimpleDB::new_for_test(dir.path(), "table_scan_errors_test.log");
+
+            let mut schema = Schema::new();
+            schema.add_i32_field("A");
+            let layout = Layout::new(schema);
+
+            let tx = db.new_tx();
+            {
+                let mut ts = TableScan::new(tx.clone(), "T".into(), layout).unwrap();
+                assert!(matches!(ts.get_i32("A"), Err(ScanError::NoCurrentRecord)));
+                assert!(matches!(
+                    ts.set_i32("A", 1),
+                    Err(ScanError::NoCurrentRecord)
+                ));
+                assert!(matches!(ts.get_rid(), Err(ScanError::NoCurrentRecord)));
+                assert!(matches!(ts.delete(), Err(ScanError::NoCurrentRecord)));
+
+                ts.insert().unwrap();
+                assert!(matches!(
+                    ts.set_val("A", Constant::String("x".into())),
+                    Err(ScanError::TypeMismatch(f, _)) if f == "A"
+                ));
+                assert!(matches!(
+                    ts.set_val("Z", Constant::Int(1)),
+                    Err(ScanError::FieldNotFound(f)) if f == "Z"
+                ));
+                assert!(matches!(ts.get_val("Z"), Err(ScanError::FieldNotFound(_))));
+                ts.set_val("A", Constant::Int(1)).unwrap();
+                assert_eq!(ts.get_i32("A").unwrap(), 1);
+            }
+            {
+                let mut schema = Schema::new();
+                schema.add_string_field("B", 2000);
+                let mut ts = TableScan::new(tx.clone(), "BIG".into(), Layout::new(schema)).unwrap();
+                assert!(matches!(ts.insert(), Err(ScanError::RecordTooLarge(_, _))));
+                assert!(matches!(
+                    ts.insert_rows(vec![vec![Constant::String("x".into())]]),
+                    Err(ScanError::RecordTooLarge(_, _))
+                ));
+                assert!(tx.lock().unwrap().size("BIG.tbl").unwrap() <= 1);
+            }
+            tx.lock().unwrap().commit().unwrap();
+        }
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_unpin_on_block_move() {
+        let dir = tempdir().unwrap();
+        {
+            let db = SimpleDB::builder()
+                .block_size(1024)
+                .buffers(8)
+                .open(dir.path())
+                .unwrap();
+
+            let mut schema = Schema::new();
+            schema.add_i32_field("A");
+            schema.add_string_field("B", 9);
+            let layout = Layout::new(schema);
+
+            let tx = db.new_tx();
+            {
+                let mut ts = TableScan::new(tx.clone(), "T".into(), layout.clone()).unwrap();
+                let mut n = 0;
+                while ts.current_rid().block_number() < 4 {
+                    ts.insert().unwrap();
+                    ts.set_i32("A", n).unwrap();
+                    n += 1;
+                }
+                assert_eq!(tx.lock().unwrap().available_buffs(), 7);
+
+                let mut count = 0;
+                ts.before_first().unwrap();
+                while ts.next().unwrap() {
+                    count += 1;
+                }
+                assert_eq!(count, n);
+                assert_eq!(tx.lock().unwrap().available_buffs(), 7);
+            }
+            assert_eq!(tx.lock().unwrap().available_buffs(), 8);
+            tx.lock().unwrap().commit().unwrap();
         }
         dir.close().unwrap();
     }
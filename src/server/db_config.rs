@@ -0,0 +1,257 @@
+// Copyright (c) 2022 Sho Kuroda <krdlab@gmail.com>
+//
+// This software is released under the MIT License.
+// https://opensource.org/licenses/MIT
+
+use crate::{
+    file::file_mgr::PAGE_CHECKSUM_SIZE,
+    metadata::{metadata_mgr::MetadataMgr, table_mgr::MAX_NAME_LENGTH},
+};
+use std::{fs, path::Path};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum DbConfigError {
+    #[error("{0}")]
+    IO(#[from] std::io::Error),
+
+    #[error("invalid database config: {0}")]
+    Invalid(String),
+
+    #[error(
+        "unsupported database version: {0} (expected {})",
+        DbConfig::CURRENT_VERSION
+    )]
+    UnsupportedVersion(u32),
+
+    #[error(
+        "the database has no {}, e.g. it was created by an older version",
+        DbConfig::CONFIG_FILE
+    )]
+    Missing,
+}
+
+pub type Result<T> = core::result::Result<T, DbConfigError>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DbConfig {
+    pub version: u32,
+    pub blocksize: usize,
+    pub log_segment_blocks: i64,
+}
+
+impl DbConfig {
+    pub const CONFIG_FILE: &'static str = "simpledb.conf";
+    pub const CURRENT_VERSION: u32 = 2;
+    const V1_MAX_NAME_LENGTH: usize = 16;
+
+    pub fn new(blocksize: usize, log_segment_blocks: i64) -> Self {
+        Self {
+            version: Self::CURRENT_VERSION,
+            blocksize,
+            log_segment_blocks,
+        }
+    }
+
+    pub fn load(dir: &Path) -> Result<Option<Self>> {
+        let path = dir.join(Self::CONFIG_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let (mut version, mut blocksize, mut log_segment_blocks) = (None, None, None);
+        for line in fs::read_to_string(path)?.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| DbConfigError::Invalid(line.to_string()))?;
+            let value = value.trim();
+            let invalid = || DbConfigError::Invalid(line.to_string());
+            match key.trim() {
+                "version" => version = Some(value.parse().map_err(|_| invalid())?),
+                "blocksize" => blocksize = Some(value.parse().map_err(|_| invalid())?),
+                "log_segment_blocks" => {
+                    log_segment_blocks = Some(value.parse().map_err(|_| invalid())?)
+                }
+                _ => return Err(invalid()),
+            }
+        }
+
+        let missing = |key: &str| DbConfigError::Invalid(format!("{} is missing", key));
+        let version = version.ok_or_else(|| missing("version"))?;
+        if version == 0 || version > Self::CURRENT_VERSION {
+            return Err(DbConfigError::UnsupportedVersion(version));
+        }
+        let config = Self {
+            version,
+            blocksize: blocksize.ok_or_else(|| missing("blocksize"))?,
+            log_segment_blocks: log_segment_blocks.ok_or_else(|| missing("log_segment_blocks"))?,
+        };
+        config.validate()?;
+        Ok(Some(config))
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        let min_blocksize = self.min_blocksize();
+        if self.blocksize < min_blocksize {
+            return Err(DbConfigError::Invalid(format!(
+                "blocksize must be at least {}, not {}",
+                min_blocksize, self.blocksize
+            )));
+        }
+        if self.log_segment_blocks <= 0 {
+            return Err(DbConfigError::Invalid(format!(
+                "log_segment_blocks must be positive, not {}",
+                self.log_segment_blocks
+            )));
+        }
+        Ok(())
+    }
+
+    pub fn min_blocksize(&self) -> usize {
+        MetadataMgr::min_page_size(self.max_name_length()) + PAGE_CHECKSUM_SIZE
+    }
+
+    pub fn max_name_length(&self) -> usize {
+        if self.version < 2 {
+            Self::V1_MAX_NAME_LENGTH
+        } else {
+            MAX_NAME_LENGTH
+        }
+    }
+
+    pub fn save(&self, dir: &Path) -> Result<()> {
+        let contents = format!(
+            "version = {}\nblocksize = {}\nlog_segment_blocks = {}\n",
+            self.version, self.blocksize, self.log_segment_blocks
+        );
+        // NOTE: atomic rename
+        let tmp = dir.join(format!("{}.new", Self::CONFIG_FILE));
+        fs::write(&tmp, contents)?;
+        fs::rename(tmp, dir.join(Self::CONFIG_FILE))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DbConfig, DbConfigError};
+    use crate::{file::file_mgr::PAGE_CHECKSUM_SIZE, server::simple_db::SimpleDB};
+    use std::fs;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_save_and_load() {
+        let dir = tempdir().unwrap();
+        assert_eq!(DbConfig::load(dir.path()).unwrap(), None);
+
+        let config = DbConfig::new(1024, 16);
+        config.save(dir.path()).unwrap();
+        assert_eq!(DbConfig::load(dir.path()).unwrap(), Some(config));
+
+        let path = dir.path().join(DbConfig::CONFIG_FILE);
+        fs::write(
+            &path,
+            "version = 3\nblocksize = 1024\nlog_segment_blocks = 16\n",
+        )
+        .unwrap();
+        assert!(matches!(
+            DbConfig::load(dir.path()),
+            Err(DbConfigError::UnsupportedVersion(3))
+        ));
+        fs::write(&path, "version = 2\nblocksize = 1024\n").unwrap();
+        assert!(matches!(
+            DbConfig::load(dir.path()),
+            Err(DbConfigError::Invalid(_))
+        ));
+        fs::write(
+            &path,
+            "version = 2\nblocksize = 1024\nlog_segment_blocks = 0\n",
+        )
+        .unwrap();
+        assert!(matches!(
+            DbConfig::load(dir.path()),
+            Err(DbConfigError::Invalid(_))
+        ));
+        assert!(DbConfig::new(1024, -1).validate().is_err());
+
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_open_with_another_blocksize() {
+        let dir = tempdir().unwrap();
+        {
+            let mut db = SimpleDB::builder()
+                .block_size(1024)
+                .open(dir.path())
+                .unwrap();
+            db.init().unwrap();
+            let tx = db.new_tx();
+            let planner = db.planner();
+            planner
+                .execute_update("create table T(A int)", tx.clone())
+                .unwrap();
+            planner
+                .execute_update("insert into T(A) values (42)", tx.clone())
+                .unwrap();
+            tx.lock().unwrap().commit().unwrap();
+        }
+
+        let mut db = SimpleDB::builder()
+            .block_size(4096)
+            .open(dir.path())
+            .unwrap();
+        db.init().unwrap();
+        assert_eq!(db.file_mgr().blocksize(), 1024 - PAGE_CHECKSUM_SIZE);
+        let tx = db.new_tx();
+        let planner = db.planner();
+        let plan = planner
+            .create_query_plan("select A from T", tx.clone())
+            .unwrap();
+        let mut scan = plan.open(tx.clone()).unwrap();
+        assert!(scan.next().unwrap());
+        assert_eq!(scan.get_i32("a").unwrap(), 42);
+        scan.close();
+        tx.lock().unwrap().commit().unwrap();
+
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_min_blocksize() {
+        let config = DbConfig::new(400, 16);
+        let min_blocksize = config.min_blocksize();
+        assert!(min_blocksize > 400 && min_blocksize <= 1024);
+        let err = config.validate().unwrap_err();
+        assert!(err.to_string().contains("blocksize must be at least"));
+        assert!(DbConfig::new(min_blocksize - 1, 16).validate().is_err());
+        let v1 = DbConfig {
+            version: 1,
+            ..DbConfig::new(min_blocksize, 16)
+        };
+        assert!(v1.min_blocksize() < min_blocksize);
+
+        let dir = tempdir().unwrap();
+        assert!(SimpleDB::builder()
+            .block_size(400)
+            .open(dir.path())
+            .is_err());
+        assert!(!dir.path().join(DbConfig::CONFIG_FILE).exists());
+
+        let mut db = SimpleDB::builder()
+            .block_size(min_blocksize)
+            .open(dir.path())
+            .unwrap();
+        db.init().unwrap();
+        let tx = db.new_tx();
+        db.planner()
+            .execute_update("create table T(A int)", tx.clone())
+            .unwrap();
+        tx.lock().unwrap().commit().unwrap();
+        drop(db);
+        dir.close().unwrap();
+    }
+}
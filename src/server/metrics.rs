@@ -0,0 +1,40 @@
+// Copyright (c) 2023 Sho Kuroda <krdlab@gmail.com>
+//
+// This software is released under the MIT License.
+// https://opensource.org/licenses/MIT
+
+use crate::{
+    buffer_mgr::BufferStats, log_mgr::LogStats, plan::planner::PlannerStats,
+    tx::lock_table::LockStats,
+};
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Metrics {
+    pub uptime: Duration,
+    pub buffer: BufferStats,
+    pub log: LogStats,
+    pub lock: LockStats,
+    pub planner: PlannerStats,
+    pub active_txs: usize,
+    pub failed_truncations: usize,
+}
+
+impl Metrics {
+    pub fn buffer_hit_ratio(&self) -> f64 {
+        self.buffer.hit_ratio()
+    }
+
+    pub fn log_bytes_per_sec(&self) -> f64 {
+        let secs = self.uptime.as_secs_f64();
+        if secs == 0.0 {
+            0.0
+        } else {
+            self.log.bytes_written as f64 / secs
+        }
+    }
+
+    pub fn statements(&self) -> usize {
+        self.planner.queries + self.planner.updates
+    }
+}
@@ -3,4 +3,9 @@
 // This software is released under the MIT License.
 // https://opensource.org/licenses/MIT
 
+pub mod db_config;
+pub mod metrics;
+pub mod network_server;
+pub mod replication;
+pub mod session;
 pub mod simple_db;
@@ -0,0 +1,433 @@
+// Copyright (c) 2023 Sho Kuroda <krdlab@gmail.com>
+//
+// This software is released under the MIT License.
+// https://opensource.org/licenses/MIT
+
+use super::session::{Session, SessionInfo, SessionRegistry};
+use super::simple_db::SimpleDB;
+use std::io;
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+
+pub struct NetworkServer {
+    listener: TcpListener,
+    stopped: AtomicBool,
+    sessions: SessionRegistry,
+    on_error: Option<Box<dyn Fn(anyhow::Error) + Send + Sync>>,
+}
+
+impl NetworkServer {
+    pub fn bind(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        Ok(Self {
+            listener: TcpListener::bind(addr)?,
+            stopped: AtomicBool::new(false),
+            sessions: SessionRegistry::default(),
+            on_error: None,
+        })
+    }
+
+    pub fn on_error(self, f: impl Fn(anyhow::Error) + Send + Sync + 'static) -> Self {
+        Self {
+            on_error: Some(Box::new(f)),
+            ..self
+        }
+    }
+
+    fn report(&self, e: anyhow::Error) {
+        if let Some(f) = &self.on_error {
+            f(e);
+        }
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    pub fn sessions(&self) -> Vec<SessionInfo> {
+        self.sessions.list()
+    }
+
+    pub fn serve(&self, db: &SimpleDB) -> io::Result<()> {
+        thread::scope(|s| {
+            for stream in self.listener.incoming() {
+                if self.stopped.load(Ordering::SeqCst) {
+                    break;
+                }
+                match stream {
+                    Ok(stream) => {
+                        let sessions = &self.sessions;
+                        s.spawn(move || {
+                            if let Err(e) = Session::accept(db, sessions, stream) {
+                                self.report(e.context("connection closed by an error"));
+                            }
+                        });
+                    }
+                    Err(e) => {
+                        self.report(anyhow::Error::new(e).context("failed to accept a connection"))
+                    }
+                }
+            }
+        });
+        Ok(())
+    }
+
+    pub fn stop(&self) -> io::Result<()> {
+        self.stopped.store(true, Ordering::SeqCst);
+        TcpStream::connect(self.local_addr()?)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NetworkServer;
+    use crate::rdbc::api::{Constant, DataType, Driver, Value};
+    use crate::rdbc::network::NetworkDriver;
+    use crate::rdbc::protocol::{Encoder, Request, Response};
+    use crate::server::simple_db::SimpleDB;
+    use std::{net::TcpStream, sync::mpsc, sync::Arc, thread, time::Duration};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_network_server() {
+        let dir = tempdir().unwrap();
+        let mut db = SimpleDB::new_for_test(dir.path(), "network_server_test.log");
+        db.init().unwrap();
+
+        let server = NetworkServer::bind("127.0.0.1:0").unwrap();
+        let url = format!("jdbc:simpledb://{}", server.local_addr().unwrap());
+        let server = Arc::new(server);
+        let handle = {
+            let server = server.clone();
+            let db = Arc::new(db);
+            thread::spawn(move || server.serve(&db))
+        };
+        {
+            let mut conn = NetworkDriver {}.connect(&url).unwrap();
+            let mut stmt = conn.create_statement().unwrap();
+            stmt.execute_update("create table T(A int, B varchar(9))")
+                .unwrap();
+            for (a, b) in [(1, "one"), (2, "two")] {
+                let sql = format!("insert into T(A, B) values ({}, '{}')", a, b);
+                assert_eq!(stmt.execute_update(&sql).unwrap(), 1);
+            }
+            let err = stmt.execute_update("create table T(C int)").unwrap_err();
+            assert!(err.to_string().contains("table already exists: t"));
+
+            let mut rs = stmt.execute_query("select B, A from T").unwrap();
+            let meta = rs.meta_data().unwrap();
+            assert_eq!(meta.num_columns(), 2);
+            assert_eq!(meta.column_name(0), "b");
+            assert_eq!(meta.column_type(0), DataType::Utf8);
+            assert_eq!(meta.column_display_size(0), 9);
+            let mut rows = Vec::new();
+            while rs.next().unwrap() {
+                rows.push((rs.get_string(0).unwrap(), rs.get_i32(1).unwrap()));
+            }
+            assert_eq!(
+                rows,
+                vec![(Some("one".into()), Some(1)), (Some("two".into()), Some(2))]
+            );
+            assert!(rs.get_i32(0).is_err());
+            rs.before_first().unwrap();
+            assert!(rs.next().unwrap());
+            assert_eq!(rs.get_i32(1).unwrap(), Some(1));
+            assert!(rs.absolute(2).unwrap());
+            assert_eq!(rs.get_i32(1).unwrap(), Some(2));
+            assert!(!rs.absolute(3).unwrap());
+            assert!(!rs.next().unwrap());
+            assert!(!rs.absolute(0).unwrap());
+            assert!(rs.get_i32(1).is_err());
+        }
+        {
+            let mut conn = NetworkDriver {}.connect(&url).unwrap();
+            let mut stmt = conn.create_statement().unwrap();
+            let mut rs = stmt.execute_query("select A from T where A = 2").unwrap();
+            assert!(rs.next().unwrap());
+            assert_eq!(rs.get_i32(0).unwrap(), Some(2));
+            assert_eq!(rs.get_val(0).unwrap(), Some(Constant::Int(2)));
+            assert!(!rs.was_null());
+            assert_eq!(rs.get_val(1).unwrap(), None);
+            assert!(rs.was_null());
+            assert!(!rs.next().unwrap());
+            drop(rs);
+            let Err(err) = stmt.execute_query("select X from T") else {
+                panic!("unknown field");
+            };
+            assert!(err.to_string().contains("field not found: x"));
+            assert!(stmt.execute_query("select A from T").is_ok());
+            drop(stmt);
+            conn.commit().unwrap();
+        }
+        {
+            let mut conn = NetworkDriver {}.connect(&url).unwrap();
+            conn.set_autocommit(false).unwrap();
+            let mut stmt = conn.create_statement().unwrap();
+            for a in 3..=250 {
+                let sql = format!("insert into T(A, B) values ({}, 'many')", a);
+                stmt.execute_update(&sql).unwrap();
+            }
+            let mut rs = stmt.execute_query("select A from T").unwrap();
+            assert!(rs.absolute(150).unwrap());
+            assert_eq!(rs.get_i32(0).unwrap(), Some(150));
+            drop(rs);
+            let mut rs = stmt.execute_query("select A from T").unwrap();
+            let mut n = 0;
+            while rs.next().unwrap() {
+                n += 1;
+            }
+            assert_eq!(n, 250);
+            assert!(rs.absolute(1).unwrap());
+            assert_eq!(rs.get_i32(0).unwrap(), Some(1));
+            drop(rs);
+            drop(stmt);
+            conn.rollback().unwrap();
+        }
+        server.stop().unwrap();
+        handle.join().unwrap().unwrap();
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_authentication() {
+        let dir = tempdir().unwrap();
+        let mut db = SimpleDB::new_for_test(dir.path(), "network_server_auth_test.log");
+        db.init().unwrap();
+
+        let server = Arc::new(NetworkServer::bind("127.0.0.1:0").unwrap());
+        let addr = server.local_addr().unwrap();
+        let handle = {
+            let server = server.clone();
+            let db = Arc::new(db);
+            thread::spawn(move || server.serve(&db))
+        };
+        let connect = |userinfo: &str| {
+            let url = format!("jdbc:simpledb://{}{}", userinfo, addr);
+            NetworkDriver {}.connect(&url)
+        };
+        let mut admin = connect("").unwrap();
+        let mut admin_stmt = admin.create_statement().unwrap();
+        for sql in [
+            "create table T(A int)",
+            "insert into T(A) values (1)",
+            "create user alice password 'p@ss:word'",
+        ] {
+            admin_stmt.execute_update(sql).unwrap();
+        }
+        let Err(err) = connect("") else {
+            panic!("no credentials");
+        };
+        assert!(err.to_string().contains("authentication failed"));
+        assert!(connect("alice:secret@").is_err());
+        assert!(connect("bob:p@ss:word@").is_err());
+        {
+            let mut conn = connect("alice:p@ss:word@").unwrap();
+            let mut stmt = conn.create_statement().unwrap();
+            let mut rs = stmt.execute_query("show sessions").unwrap();
+            let mut users = Vec::new();
+            while rs.next().unwrap() {
+                users.push(rs.get_string(2).unwrap().unwrap());
+            }
+            assert_eq!(users, ["", "alice"]);
+            drop(rs);
+
+            let Err(err) = stmt.execute_query("select A from T") else {
+                panic!("no privilege");
+            };
+            assert!(err.to_string().contains("permission denied"));
+            admin_stmt
+                .execute_update("grant select on T to alice")
+                .unwrap();
+            let mut rs = stmt.execute_query("select A from T").unwrap();
+            assert!(rs.next().unwrap());
+            drop(rs);
+            assert!(stmt.execute_update("create table U(A int)").is_err());
+            assert!(stmt.execute_update("insert into T(A) values (2)").is_err());
+            stmt.execute_update("alter user alice password 'changed'")
+                .unwrap();
+        }
+        drop(admin_stmt);
+        drop(admin);
+        assert!(connect("alice:p@ss:word@").is_err());
+        assert!(connect("alice:changed@").is_ok());
+        server.stop().unwrap();
+        handle.join().unwrap().unwrap();
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_sessions() {
+        let dir = tempdir().unwrap();
+        let mut db = SimpleDB::new_for_test(dir.path(), "network_server_sessions_test.log");
+        db.init().unwrap();
+
+        let server = Arc::new(NetworkServer::bind("127.0.0.1:0").unwrap());
+        let url = format!("jdbc:simpledb://{}", server.local_addr().unwrap());
+        let handle = {
+            let server = server.clone();
+            let db = Arc::new(db);
+            thread::spawn(move || server.serve(&db))
+        };
+        {
+            let mut conn = NetworkDriver {}.connect(&url).unwrap();
+            {
+                let mut stmt = conn.create_statement().unwrap();
+                stmt.execute_update("create table T(A int, B varchar(9))")
+                    .unwrap();
+            }
+            {
+                let mut stmt = conn
+                    .prepare_statement("insert into T(A, B) values (?, ?)")
+                    .unwrap();
+                for (a, b) in [(1, "one"), (2, "it's"), (3, "a?b")] {
+                    let params = [Value::Int32(a), Value::String(b.into())];
+                    assert_eq!(stmt.execute_update(&params).unwrap(), 1);
+                }
+                assert!(stmt.execute_update(&[Value::Int32(4)]).is_err());
+            }
+            {
+                let mut stmt = conn
+                    .prepare_statement("select A from T where B = ?")
+                    .unwrap();
+                let mut rs = stmt.execute_query(&[Value::String("it's".into())]).unwrap();
+                assert!(rs.next().unwrap());
+                assert_eq!(rs.get_i32(0).unwrap(), Some(2));
+                assert!(!rs.next().unwrap());
+            }
+
+            let mut other = NetworkDriver {}.connect(&url).unwrap();
+            other.set_autocommit(false).unwrap();
+            let mut stmt = conn.create_statement().unwrap();
+            assert_eq!(stmt.execute_update("set limit = 2").unwrap(), 0);
+            let mut rs = stmt.execute_query("show limit").unwrap();
+            assert!(rs.next().unwrap());
+            assert_eq!(rs.get_i32(0).unwrap(), Some(2));
+            drop(rs);
+            let mut rs = stmt.execute_query("select A from T").unwrap();
+            let mut n = 0;
+            while rs.next().unwrap() {
+                n += 1;
+            }
+            assert_eq!(n, 2);
+            drop(rs);
+            let mut other_stmt = other.create_statement().unwrap();
+            let mut rs = other_stmt.execute_query("show limit").unwrap();
+            assert!(rs.next().unwrap());
+            assert_eq!(rs.get_i32(0).unwrap(), Some(0));
+            drop(rs);
+            assert!(stmt.execute_update("set width = 1").is_err());
+
+            assert_eq!(stmt.execute_update("set limit = 0").unwrap(), 0);
+            let mut rs = stmt.execute_query("show sessions").unwrap();
+            let meta = rs.meta_data().unwrap();
+            assert_eq!(meta.column_name(5), "sql");
+            let mut rows = Vec::new();
+            while rs.next().unwrap() {
+                rows.push((rs.get_string(4).unwrap(), rs.get_string(5).unwrap()));
+            }
+            assert_eq!(
+                rows,
+                vec![
+                    (Some("on".into()), Some("show sessions".into())),
+                    (Some("off".into()), Some("".into())),
+                ]
+            );
+            drop(rs);
+            let sessions = server.sessions();
+            assert_eq!(sessions.len(), 2);
+            assert!(sessions.iter().all(|info| info.sql.is_none()));
+            assert!(!sessions[1].autocommit);
+            drop(other_stmt);
+            drop(other);
+        }
+        let start = std::time::Instant::now();
+        while !server.sessions().is_empty() {
+            assert!(start.elapsed() < std::time::Duration::from_secs(10));
+            thread::sleep(std::time::Duration::from_millis(10));
+        }
+        server.stop().unwrap();
+        handle.join().unwrap().unwrap();
+        dir.close().unwrap();
+    }
+
+    fn call(stream: &mut TcpStream, req: Request) -> Response {
+        req.write_to(stream).unwrap();
+        Response::read_from(stream).unwrap()
+    }
+
+    fn batches(stream: &mut TcpStream, sql: &str) -> Vec<usize> {
+        let query = Request::Query(sql.into(), Duration::ZERO);
+        assert!(matches!(call(stream, query), Response::Columns(_)));
+        let mut sizes = Vec::new();
+        loop {
+            match Response::read_from(stream).unwrap() {
+                Response::Batch(rows) => sizes.push(rows.len()),
+                Response::Done => return sizes,
+                resp => panic!("unexpected response: {:?}", resp),
+            }
+        }
+    }
+
+    #[test]
+    fn test_batches() {
+        let dir = tempdir().unwrap();
+        let mut db = SimpleDB::new_for_test(dir.path(), "network_server_batch_test.log");
+        db.init().unwrap();
+
+        let server = NetworkServer::bind("127.0.0.1:0").unwrap();
+        let addr = server.local_addr().unwrap();
+        thread::scope(|s| {
+            let serving = s.spawn(|| server.serve(&db));
+            let mut stream = TcpStream::connect(addr).unwrap();
+            let auth = Request::Authenticate("".into(), "".into());
+            assert!(matches!(call(&mut stream, auth), Response::Done));
+            call(&mut stream, Request::Update("create table T(A int)".into()));
+            for a in 1..=250 {
+                let sql = format!("insert into T(A) values ({})", a);
+                assert!(matches!(
+                    call(&mut stream, Request::Update(sql)),
+                    Response::Count(1)
+                ));
+            }
+
+            assert_eq!(batches(&mut stream, "select A from T"), [100, 100, 50]);
+            assert!(batches(&mut stream, "select A from T where A = 0").is_empty());
+
+            let set = Request::Update("set limit=150".into());
+            assert!(matches!(call(&mut stream, set), Response::Count(0)));
+            assert_eq!(batches(&mut stream, "select A from T"), [100, 50]);
+
+            drop(stream);
+            server.stop().unwrap();
+            serving.join().unwrap().unwrap();
+        });
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_connection_error() {
+        let dir = tempdir().unwrap();
+        let mut db = SimpleDB::new_for_test(dir.path(), "network_server_error_test.log");
+        db.init().unwrap();
+
+        let (sender, receiver) = mpsc::channel();
+        let server = NetworkServer::bind("127.0.0.1:0")
+            .unwrap()
+            .on_error(move |e| sender.send(format!("{:#}", e)).unwrap());
+        let addr = server.local_addr().unwrap();
+        thread::scope(|s| {
+            let serving = s.spawn(|| server.serve(&db));
+            // NOTE: the error is handed over
+            let mut stream = TcpStream::connect(addr).unwrap();
+            let mut e = Encoder::default();
+            e.put_u8(99);
+            e.write_to(&mut stream).unwrap();
+            let err = receiver.recv_timeout(Duration::from_secs(10)).unwrap();
+            assert_eq!(err, "connection closed by an error: unknown request: 99");
+            server.stop().unwrap();
+            serving.join().unwrap().unwrap();
+        });
+        dir.close().unwrap();
+    }
+}
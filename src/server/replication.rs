@@ -0,0 +1,484 @@
+// Copyright (c) 2023 Sho Kuroda <krdlab@gmail.com>
+//
+// This software is released under the MIT License.
+// https://opensource.org/licenses/MIT
+
+use super::simple_db::SimpleDB;
+use crate::log_mgr::LSN;
+use crate::rdbc::protocol::{invalid_data, read_message, Decoder, Encoder};
+use crate::tx::recovery_mgr::LogReplayer;
+use crate::tx::transaction::TransactionError;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
+use std::thread;
+use std::time::Duration;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+const POSITION_FILE: &str = "replication.pos";
+
+// NOTE: the messages are framed like those of rdbc::protocol
+#[derive(Debug, PartialEq)]
+enum Message {
+    // NOTE: from a replica; epoch and replayed LSN, or 0s if new
+    Follow { epoch: u64, lsn: LSN },
+    // NOTE: from the primary
+    Accepted { epoch: u64 },
+    Record { lsn: LSN, bytes: Vec<u8> },
+    Error(String),
+}
+
+impl Message {
+    fn write_to(&self, w: &mut impl Write) -> io::Result<()> {
+        let mut e = Encoder::default();
+        match self {
+            Message::Follow { epoch, lsn } => {
+                e.put_u8(0);
+                e.put_u64(*epoch);
+                e.put_i64(*lsn);
+            }
+            Message::Accepted { epoch } => {
+                e.put_u8(1);
+                e.put_u64(*epoch);
+            }
+            Message::Record { lsn, bytes } => {
+                e.put_u8(2);
+                e.put_i64(*lsn);
+                e.put_bytes(bytes);
+            }
+            Message::Error(message) => {
+                e.put_u8(3);
+                e.put_string(message);
+            }
+        }
+        e.write_to(w)
+    }
+
+    fn read_from(r: &mut impl Read) -> io::Result<Option<Self>> {
+        let Some(bytes) = read_message(r)? else {
+            return Ok(None);
+        };
+        let mut d = Decoder::new(&bytes);
+        let msg = match d.get_u8()? {
+            0 => Message::Follow {
+                epoch: d.get_u64()?,
+                lsn: d.get_i64()?,
+            },
+            1 => Message::Accepted {
+                epoch: d.get_u64()?,
+            },
+            2 => Message::Record {
+                lsn: d.get_i64()?,
+                bytes: d.get_bytes()?,
+            },
+            3 => Message::Error(d.get_string()?),
+            tag => return Err(invalid_data(format!("unknown message: {}", tag))),
+        };
+        Ok(Some(msg))
+    }
+}
+
+pub struct LogShipper {
+    listener: TcpListener,
+    stopped: AtomicBool,
+    on_error: Option<Box<dyn Fn(anyhow::Error) + Send + Sync>>,
+}
+
+impl LogShipper {
+    pub fn bind(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        Ok(Self {
+            listener: TcpListener::bind(addr)?,
+            stopped: AtomicBool::new(false),
+            on_error: None,
+        })
+    }
+
+    pub fn on_error(self, f: impl Fn(anyhow::Error) + Send + Sync + 'static) -> Self {
+        Self {
+            on_error: Some(Box::new(f)),
+            ..self
+        }
+    }
+
+    fn report(&self, e: anyhow::Error) {
+        if let Some(f) = &self.on_error {
+            f(e);
+        }
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    pub fn serve(&self, db: &SimpleDB) -> io::Result<()> {
+        thread::scope(|s| {
+            for stream in self.listener.incoming() {
+                if self.stopped.load(Ordering::SeqCst) {
+                    break;
+                }
+                match stream {
+                    Ok(stream) => {
+                        s.spawn(move || {
+                            if let Err(e) = self.ship(db, stream) {
+                                self.report(e.context("replication stopped by an error"));
+                            }
+                        });
+                    }
+                    Err(e) => {
+                        self.report(anyhow::Error::new(e).context("failed to accept a replica"))
+                    }
+                }
+            }
+        });
+        Ok(())
+    }
+
+    pub fn stop(&self) -> io::Result<()> {
+        self.stopped.store(true, Ordering::SeqCst);
+        TcpStream::connect(self.local_addr()?)?;
+        Ok(())
+    }
+
+    fn ship(&self, db: &SimpleDB, mut stream: TcpStream) -> anyhow::Result<()> {
+        let lm = db.log_mgr();
+        let Some(Message::Follow { epoch, lsn }) = Message::read_from(&mut stream)? else {
+            // NOTE: e.g. the connection made by `stop`
+            return Ok(());
+        };
+        let mut next = if epoch == lm.epoch() {
+            lsn + 1
+        } else if epoch == 0 && lsn == 0 {
+            1
+        } else {
+            let message = "the replica follows a previous startup of the primary, and must be copied from it again";
+            Message::Error(message.into()).write_to(&mut stream)?;
+            return Ok(());
+        };
+        Message::Accepted { epoch: lm.epoch() }.write_to(&mut stream)?;
+
+        while !self.stopped.load(Ordering::SeqCst) {
+            if lm.latest_lsn() < next {
+                thread::sleep(POLL_INTERVAL);
+                continue;
+            }
+            for (lsn, bytes) in lm.forward_iter(next)? {
+                if lsn != next {
+                    let message =
+                        format!("the log records from LSN {} are no longer available", next);
+                    Message::Error(message).write_to(&mut stream)?;
+                    return Ok(());
+                }
+                Message::Record { lsn, bytes }.write_to(&mut stream)?;
+                next += 1;
+            }
+        }
+        Ok(())
+    }
+}
+
+pub struct Replica {
+    stream: TcpStream,
+    stopped: AtomicBool,
+    replayed_lsn: AtomicI64,
+}
+
+impl Replica {
+    pub fn connect(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        Ok(Self {
+            stream: TcpStream::connect(addr)?,
+            stopped: AtomicBool::new(false),
+            replayed_lsn: AtomicI64::new(0),
+        })
+    }
+
+    pub fn follow(&self, db: &SimpleDB) -> anyhow::Result<()> {
+        let path = db.file_mgr().path_of(POSITION_FILE);
+        let (epoch, lsn) = read_position(&path)?;
+        let mut stream = &self.stream;
+        Message::Follow { epoch, lsn }.write_to(&mut stream)?;
+        let epoch = match Message::read_from(&mut stream)? {
+            Some(Message::Accepted { epoch }) => epoch,
+            Some(Message::Error(message)) => anyhow::bail!(message),
+            msg => anyhow::bail!("unexpected message: {:?}", msg),
+        };
+
+        let mut replayer = LogReplayer::default();
+        loop {
+            let msg = match Message::read_from(&mut stream) {
+                Ok(Some(msg)) => msg,
+                Ok(None) => break,
+                Err(_) if self.stopped.load(Ordering::SeqCst) => break,
+                Err(e) => return Err(e.into()),
+            };
+            match msg {
+                Message::Record { lsn, bytes } => {
+                    if let Some(rtx) = replayer.add(lsn, bytes)? {
+                        let tx = loop {
+                            let tx = db.new_tx();
+                            let result = tx.lock().unwrap().replay(&rtx);
+                            match result {
+                                Ok(()) => break tx,
+                                // NOTE: retried once the queries are out of the way
+                                Err(TransactionError::LockTableError(_)) => {
+                                    tx.lock().unwrap().rollback()?
+                                }
+                                Err(e) => return Err(e.into()),
+                            }
+                        };
+                        tx.lock().unwrap().commit()?;
+                        // NOTE: uncommitted transactions are replayed from their start
+                        let resume = replayer.oldest_pending_lsn().map_or(lsn, |first| first - 1);
+                        write_position(&path, epoch, resume)?;
+                    }
+                    self.replayed_lsn.store(lsn, Ordering::SeqCst);
+                }
+                Message::Error(message) => anyhow::bail!(message),
+                msg => anyhow::bail!("unexpected message: {:?}", msg),
+            }
+        }
+        Ok(())
+    }
+
+    pub fn replayed_lsn(&self) -> LSN {
+        self.replayed_lsn.load(Ordering::SeqCst)
+    }
+
+    pub fn stop(&self) -> io::Result<()> {
+        self.stopped.store(true, Ordering::SeqCst);
+        self.stream.shutdown(Shutdown::Both)
+    }
+}
+
+fn read_position(path: &Path) -> anyhow::Result<(u64, LSN)> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok((0, 0)),
+        Err(e) => return Err(e.into()),
+    };
+    let invalid = || anyhow::anyhow!("invalid replication position: {:?}", text);
+    let (epoch, lsn) = text.trim().split_once(' ').ok_or_else(invalid)?;
+    Ok((
+        epoch.parse().map_err(|_| invalid())?,
+        lsn.parse().map_err(|_| invalid())?,
+    ))
+}
+
+fn write_position(path: &Path, epoch: u64, lsn: LSN) -> io::Result<()> {
+    // NOTE: atomic rename
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, format!("{} {}\n", epoch, lsn))?;
+    fs::rename(tmp, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LogShipper, Replica};
+    use crate::query::predicate::Constant;
+    use crate::rdbc::protocol::Encoder;
+    use crate::server::simple_db::SimpleDB;
+    use std::fs;
+    use std::net::TcpStream;
+    use std::path::Path;
+    use std::sync::{mpsc, Arc};
+    use std::thread;
+    use std::time::{Duration, Instant};
+    use tempfile::tempdir;
+
+    fn update(db: &SimpleDB, sqls: &[&str]) {
+        let planner = db.planner();
+        let tx = db.new_tx();
+        for sql in sqls {
+            planner.execute_update(sql, tx.clone()).unwrap();
+        }
+        tx.lock().unwrap().commit().unwrap();
+    }
+
+    fn values(db: &SimpleDB, sql: &str) -> Option<Vec<String>> {
+        let planner = db.planner();
+        let tx = db.new_tx();
+        let read = || -> anyhow::Result<Vec<String>> {
+            let plan = planner.create_query_plan(sql, tx.clone())?;
+            let field = plan.schema().fields_iter().next().unwrap().clone();
+            let mut scan = plan.open(tx.clone())?;
+            let mut values = Vec::new();
+            while scan.next()? {
+                values.push(match scan.get_val(&field)? {
+                    Constant::Int(n) => n.to_string(),
+                    Constant::String(s) => s,
+                });
+            }
+            scan.close();
+            Ok(values)
+        };
+        let values = read().ok();
+        if values.is_some() {
+            tx.lock().unwrap().commit().unwrap();
+        } else {
+            tx.lock().unwrap().rollback().unwrap();
+        }
+        values
+    }
+
+    fn wait_until(mut done: impl FnMut() -> bool) {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !done() {
+            assert!(Instant::now() < deadline, "the replica has not caught up");
+            thread::sleep(Duration::from_millis(20));
+        }
+    }
+
+    fn wait_for(db: &SimpleDB, sql: &str, expected: &[&str]) {
+        wait_until(|| values(db, sql).is_some_and(|values| values == expected));
+    }
+
+    fn copy_dir(from: &Path, to: &Path) {
+        for entry in fs::read_dir(from).unwrap() {
+            let path = entry.unwrap().path();
+            fs::copy(&path, to.join(path.file_name().unwrap())).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_replication() {
+        let primary_dir = tempdir().unwrap();
+        let replica_dir = tempdir().unwrap();
+        let logfile = "replication_test.log";
+        {
+            let mut db = SimpleDB::new_for_test(primary_dir.path(), logfile);
+            db.init().unwrap();
+            update(
+                &db,
+                &["create table T(A int)", "insert into T(A) values (1)"],
+            );
+            db.close().unwrap();
+        }
+        copy_dir(primary_dir.path(), replica_dir.path());
+
+        let mut primary = SimpleDB::new_for_test(primary_dir.path(), logfile);
+        primary.init().unwrap();
+        let primary = Arc::new(primary);
+        let mut replica = SimpleDB::new_for_test(replica_dir.path(), logfile);
+        replica.init().unwrap();
+        let replica = Arc::new(replica);
+        let shipper = Arc::new(LogShipper::bind("127.0.0.1:0").unwrap());
+        let addr = shipper.local_addr().unwrap();
+        let shipping = {
+            let (shipper, primary) = (shipper.clone(), primary.clone());
+            thread::spawn(move || shipper.serve(&primary))
+        };
+        let follow = || {
+            let follower = Arc::new(Replica::connect(addr).unwrap());
+            let following = {
+                let (follower, replica) = (follower.clone(), replica.clone());
+                thread::spawn(move || follower.follow(&replica))
+            };
+            (follower, following)
+        };
+
+        let (follower, following) = follow();
+        update(&primary, &["insert into T(A) values (2)"]);
+        let tx = primary.new_tx();
+        primary
+            .planner()
+            .execute_update("insert into T(A) values (99)", tx.clone())
+            .unwrap();
+        tx.lock().unwrap().rollback().unwrap();
+        update(
+            &primary,
+            &[
+                "create table U(B varchar(5))",
+                "insert into U(B) values ('x')",
+                "insert into T(A) values (3)",
+            ],
+        );
+        wait_for(&replica, "select A from T", &["1", "2", "3"]);
+        wait_for(&replica, "select B from U", &["x"]);
+        assert!(follower.replayed_lsn() > 0);
+
+        let pending = primary.new_tx();
+        primary
+            .planner()
+            .execute_update("insert into T(A) values (4)", pending.clone())
+            .unwrap();
+        let blk = {
+            let tx = primary.new_tx();
+            let blk = tx.lock().unwrap().append("replication_test.dat").unwrap();
+            tx.lock().unwrap().pin(&blk).unwrap();
+            tx.lock().unwrap().set_i32(&blk, 0, 42, true).unwrap();
+            tx.lock().unwrap().unpin(&blk);
+            tx.lock().unwrap().commit().unwrap();
+            blk
+        };
+        wait_until(|| {
+            let fm = replica.file_mgr();
+            if fm.length(blk.filename()).unwrap() == 0 {
+                return false;
+            }
+            let tx = replica.new_tx();
+            tx.lock().unwrap().pin(&blk).unwrap();
+            let val = tx.lock().unwrap().get_i32(&blk, 0).unwrap();
+            tx.lock().unwrap().unpin(&blk);
+            tx.lock().unwrap().commit().unwrap();
+            val == 42
+        });
+        follower.stop().unwrap();
+        following.join().unwrap().unwrap();
+        pending.lock().unwrap().commit().unwrap();
+        let (follower, following) = follow();
+        wait_for(&replica, "select A from T", &["1", "2", "3", "4"]);
+        follower.stop().unwrap();
+        following.join().unwrap().unwrap();
+
+        shipper.stop().unwrap();
+        shipping.join().unwrap().unwrap();
+        Arc::into_inner(primary).unwrap().close().unwrap();
+
+        let mut primary = SimpleDB::new_for_test(primary_dir.path(), logfile);
+        primary.init().unwrap();
+        let shipper = Arc::new(LogShipper::bind("127.0.0.1:0").unwrap());
+        let addr = shipper.local_addr().unwrap();
+        thread::scope(|s| {
+            let shipping = s.spawn(|| shipper.serve(&primary));
+            let err = Replica::connect(addr)
+                .unwrap()
+                .follow(&replica)
+                .unwrap_err();
+            assert!(err.to_string().contains("must be copied"));
+            shipper.stop().unwrap();
+            shipping.join().unwrap().unwrap();
+        });
+        primary.close().unwrap();
+        Arc::into_inner(replica).unwrap().close().unwrap();
+        primary_dir.close().unwrap();
+        replica_dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_shipping_error() {
+        let dir = tempdir().unwrap();
+        let mut db = SimpleDB::new_for_test(dir.path(), "replication_error_test.log");
+        db.init().unwrap();
+        let (sender, receiver) = mpsc::channel();
+        let shipper = LogShipper::bind("127.0.0.1:0")
+            .unwrap()
+            .on_error(move |e| sender.send(format!("{:#}", e)).unwrap());
+        let addr = shipper.local_addr().unwrap();
+        thread::scope(|s| {
+            let shipping = s.spawn(|| shipper.serve(&db));
+            // NOTE: the error is handed over
+            let mut stream = TcpStream::connect(addr).unwrap();
+            let mut e = Encoder::default();
+            e.put_u8(9);
+            e.write_to(&mut stream).unwrap();
+            let err = receiver.recv_timeout(Duration::from_secs(10)).unwrap();
+            assert_eq!(err, "replication stopped by an error: unknown message: 9");
+            shipper.stop().unwrap();
+            shipping.join().unwrap().unwrap();
+        });
+        db.close().unwrap();
+        dir.close().unwrap();
+    }
+}
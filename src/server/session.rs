@@ -0,0 +1,582 @@
+// Copyright (c) 2023 Sho Kuroda <krdlab@gmail.com>
+//
+// This software is released under the MIT License.
+// https://opensource.org/licenses/MIT
+
+use super::simple_db::SimpleDB;
+use crate::rdbc::api::{Column, DataType, Value};
+use crate::rdbc::protocol::{Request, Response};
+use crate::record::schema::SqlType;
+use crate::tx::transaction::{Transaction, TransactionError};
+use std::collections::HashMap;
+use std::mem;
+use std::net::{SocketAddr, TcpStream};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+const BATCH_SIZE: usize = 100;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionInfo {
+    pub id: u64,
+    pub addr: Option<SocketAddr>,
+    pub user: Option<String>,
+    pub connected_at: SystemTime,
+    pub txnum: i32,
+    pub autocommit: bool,
+    pub sql: Option<String>,
+}
+
+#[derive(Default)]
+pub(super) struct SessionRegistry {
+    sessions: Mutex<HashMap<u64, SessionInfo>>,
+    next_id: AtomicU64,
+}
+
+impl SessionRegistry {
+    fn open(&self, addr: Option<SocketAddr>, user: Option<String>, txnum: i32) -> u64 {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst) + 1;
+        let info = SessionInfo {
+            id,
+            addr,
+            user,
+            connected_at: SystemTime::now(),
+            txnum,
+            autocommit: true,
+            sql: None,
+        };
+        self.sessions.lock().unwrap().insert(id, info);
+        id
+    }
+
+    fn update(&self, id: u64, f: impl FnOnce(&mut SessionInfo)) {
+        if let Some(info) = self.sessions.lock().unwrap().get_mut(&id) {
+            f(info);
+        }
+    }
+
+    fn close(&self, id: u64) {
+        self.sessions.lock().unwrap().remove(&id);
+    }
+
+    pub(super) fn list(&self) -> Vec<SessionInfo> {
+        let mut sessions: Vec<_> = self.sessions.lock().unwrap().values().cloned().collect();
+        sessions.sort_by_key(|info| info.id);
+        sessions
+    }
+}
+
+struct SessionVars {
+    // NOTE: 0 means no limit
+    limit: usize,
+    // NOTE: zero means no limit
+    timeout: Duration,
+}
+
+impl Default for SessionVars {
+    fn default() -> Self {
+        Self {
+            limit: 0,
+            timeout: Duration::ZERO,
+        }
+    }
+}
+
+impl SessionVars {
+    fn get(&self, name: &str) -> Option<i32> {
+        let value = match name {
+            "limit" => self.limit as u128,
+            "timeout" => self.timeout.as_millis(),
+            _ => return None,
+        };
+        Some(value.try_into().unwrap_or(i32::MAX))
+    }
+
+    fn set(&mut self, name: &str, value: &str) -> anyhow::Result<()> {
+        let n: u32 = value
+            .parse()
+            .map_err(|_| anyhow::anyhow!("invalid value of {}: {}", name, value))?;
+        match name {
+            "limit" => self.limit = n as usize,
+            "timeout" => self.timeout = Duration::from_millis(n.into()),
+            _ => anyhow::bail!("unknown variable: {}", name),
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum SessionCommand {
+    Set(String, String),
+    Show(String),
+}
+
+impl SessionCommand {
+    fn parse(sql: &str) -> anyhow::Result<Option<Self>> {
+        let words: Vec<String> = sql
+            .replace('=', " = ")
+            .split_whitespace()
+            .map(str::to_lowercase)
+            .collect();
+        match words.first().map(String::as_str) {
+            Some("set") => match &words[1..] {
+                [name, op, value] if op == "=" || op == "to" => {
+                    Ok(Some(Self::Set(name.clone(), value.clone())))
+                }
+                _ => anyhow::bail!("bad syntax: expected set NAME = VALUE"),
+            },
+            Some("show") => match &words[1..] {
+                [name] => Ok(Some(Self::Show(name.clone()))),
+                _ => anyhow::bail!("bad syntax: expected show NAME"),
+            },
+            _ => Ok(None),
+        }
+    }
+}
+
+struct PreparedSql {
+    parts: Vec<String>,
+}
+
+impl PreparedSql {
+    fn new(sql: &str) -> Self {
+        let mut parts = vec![String::new()];
+        let mut quoted = false;
+        let mut escaped = false;
+        for c in sql.chars() {
+            match c {
+                '?' if !quoted => {
+                    parts.push(String::new());
+                    continue;
+                }
+                '\\' if quoted && !escaped => escaped = true,
+                '\'' if !escaped => quoted = !quoted,
+                _ => escaped = false,
+            }
+            if c != '\\' {
+                escaped = false;
+            }
+            parts.last_mut().unwrap().push(c);
+        }
+        Self { parts }
+    }
+
+    fn num_params(&self) -> usize {
+        self.parts.len() - 1
+    }
+
+    fn bind(&self, params: &[Value]) -> anyhow::Result<String> {
+        if params.len() != self.num_params() {
+            anyhow::bail!(
+                "wrong number of parameters: expected={}, actual={}",
+                self.num_params(),
+                params.len()
+            );
+        }
+        let mut sql = self.parts[0].clone();
+        for (param, part) in params.iter().zip(&self.parts[1..]) {
+            sql.push_str(&Self::literal(param));
+            sql.push_str(part);
+        }
+        Ok(sql)
+    }
+
+    fn literal(value: &Value) -> String {
+        match value {
+            Value::Int32(n) => n.to_string(),
+            Value::String(s) => {
+                let mut literal = String::from('\'');
+                for c in s.chars() {
+                    match c {
+                        '\\' => literal.push_str("\\\\"),
+                        '\'' => literal.push_str("\\'"),
+                        '\n' => literal.push_str("\\n"),
+                        '\r' => literal.push_str("\\r"),
+                        c => literal.push(c),
+                    }
+                }
+                literal.push('\'');
+                literal
+            }
+        }
+    }
+}
+
+enum Handshake {
+    Accepted(Option<String>),
+    Rejected,
+}
+
+pub(super) struct Session<'s> {
+    id: u64,
+    db: &'s SimpleDB,
+    registry: &'s SessionRegistry,
+    user: Option<String>,
+    tx: Arc<Mutex<Transaction>>,
+    autocommit: bool,
+    vars: SessionVars,
+    prepared: HashMap<u64, PreparedSql>,
+    next_prepared_id: u64,
+}
+
+impl<'s> Session<'s> {
+    pub(super) fn accept(
+        db: &'s SimpleDB,
+        registry: &'s SessionRegistry,
+        mut stream: TcpStream,
+    ) -> anyhow::Result<()> {
+        match Self::authenticate(db, &mut stream)? {
+            Handshake::Accepted(user) => {
+                Self::new(db, registry, stream.peer_addr().ok(), user).run(stream)
+            }
+            Handshake::Rejected => Ok(()),
+        }
+    }
+
+    fn authenticate(db: &SimpleDB, stream: &mut TcpStream) -> anyhow::Result<Handshake> {
+        let resp = match Request::read_from(stream)? {
+            None => return Ok(Handshake::Rejected),
+            Some(Request::Authenticate(user, password)) => {
+                let accepted = if db.user_names()?.is_empty() {
+                    Some(None)
+                } else if db.authenticate(&user, &password)? {
+                    Some(Some(user))
+                } else {
+                    None
+                };
+                if let Some(user) = accepted {
+                    Response::Done.write_to(stream)?;
+                    return Ok(Handshake::Accepted(user));
+                }
+                Response::Error("authentication failed".into())
+            }
+            Some(_) => Response::Error("authentication required".into()),
+        };
+        resp.write_to(stream)?;
+        Ok(Handshake::Rejected)
+    }
+
+    fn new(
+        db: &'s SimpleDB,
+        registry: &'s SessionRegistry,
+        addr: Option<SocketAddr>,
+        user: Option<String>,
+    ) -> Self {
+        let tx = db.new_tx();
+        tx.lock().unwrap().set_user(user.clone());
+        let id = registry.open(addr, user.clone(), tx.lock().unwrap().txnum());
+        Self {
+            id,
+            db,
+            registry,
+            user,
+            tx,
+            autocommit: true,
+            vars: SessionVars::default(),
+            prepared: HashMap::new(),
+            next_prepared_id: 0,
+        }
+    }
+
+    fn run(mut self, mut stream: TcpStream) -> anyhow::Result<()> {
+        while let Some(req) = Request::read_from(&mut stream)? {
+            let resp = self.handle(req, &mut stream).unwrap_or_else(|e| {
+                let message = match self.rollback() {
+                    Ok(()) => e.to_string(),
+                    Err(re) => format!("{} (and failed to roll back: {})", e, re),
+                };
+                if TransactionError::is_timeout(&e) {
+                    Response::Timeout(message)
+                } else {
+                    Response::Error(message)
+                }
+            });
+            self.publish(None);
+            resp.write_to(&mut stream)?;
+        }
+        self.tx.lock().unwrap().commit()?;
+        Ok(())
+    }
+
+    fn publish(&self, sql: Option<&str>) {
+        let txnum = self.tx.lock().unwrap().txnum();
+        let autocommit = self.autocommit;
+        self.registry.update(self.id, |info| {
+            info.txnum = txnum;
+            info.autocommit = autocommit;
+            info.sql = sql.map(String::from);
+        });
+    }
+
+    fn handle(&mut self, req: Request, stream: &mut TcpStream) -> anyhow::Result<Response> {
+        match req {
+            Request::Query(sql, timeout) => self.query(&sql, timeout, stream),
+            Request::Update(sql) => self.update(&sql),
+            Request::Commit => {
+                self.commit()?;
+                Ok(Response::Done)
+            }
+            Request::Rollback => {
+                self.rollback()?;
+                Ok(Response::Done)
+            }
+            Request::SetAutocommit(autocommit) => {
+                if autocommit && !self.autocommit {
+                    self.commit()?;
+                }
+                self.autocommit = autocommit;
+                Ok(Response::Done)
+            }
+            Request::Prepare(sql) => {
+                self.next_prepared_id += 1;
+                self.prepared
+                    .insert(self.next_prepared_id, PreparedSql::new(&sql));
+                Ok(Response::Prepared(self.next_prepared_id))
+            }
+            Request::QueryPrepared(id, params, timeout) => {
+                let sql = self.bind(id, &params)?;
+                self.query(&sql, timeout, stream)
+            }
+            Request::UpdatePrepared(id, params) => {
+                let sql = self.bind(id, &params)?;
+                self.update(&sql)
+            }
+            Request::ClosePrepared(id) => {
+                self.prepared.remove(&id);
+                Ok(Response::Done)
+            }
+            Request::Authenticate(..) => anyhow::bail!("the session is authenticated already"),
+        }
+    }
+
+    fn bind(&self, id: u64, params: &[Value]) -> anyhow::Result<String> {
+        let prepared = self
+            .prepared
+            .get(&id)
+            .ok_or_else(|| anyhow::anyhow!("unknown prepared statement: {}", id))?;
+        prepared.bind(params)
+    }
+
+    fn query(
+        &mut self,
+        sql: &str,
+        timeout: Duration,
+        stream: &mut TcpStream,
+    ) -> anyhow::Result<Response> {
+        self.publish(Some(sql));
+        match SessionCommand::parse(sql)? {
+            Some(SessionCommand::Show(name)) => return self.show(&name, stream),
+            Some(SessionCommand::Set(..)) => anyhow::bail!("not a query: {}", sql),
+            None => {}
+        }
+        let timeout = if timeout.is_zero() {
+            self.vars.timeout
+        } else {
+            timeout
+        };
+        let resp = self.run_query(sql, timeout, stream)?;
+        if self.autocommit {
+            self.commit()?;
+        }
+        Ok(resp)
+    }
+
+    fn update(&mut self, sql: &str) -> anyhow::Result<Response> {
+        self.publish(Some(sql));
+        match SessionCommand::parse(sql)? {
+            Some(SessionCommand::Set(name, value)) => {
+                self.vars.set(&name, &value)?;
+                return Ok(Response::Count(0));
+            }
+            Some(SessionCommand::Show(_)) => anyhow::bail!("not an update: {}", sql),
+            None => {}
+        }
+        let count = self.db.planner().execute_update(sql, self.tx.clone())?;
+        if self.autocommit {
+            self.commit()?;
+        }
+        Ok(Response::Count(count))
+    }
+
+    fn run_query(
+        &mut self,
+        sql: &str,
+        timeout: Duration,
+        stream: &mut TcpStream,
+    ) -> anyhow::Result<Response> {
+        let planner = self.db.planner();
+        let plan = planner.create_query_plan(sql, self.tx.clone())?;
+        let schema = plan.schema();
+        let columns = schema
+            .fields_iter()
+            .map(|name| {
+                let data_type = match schema.field_type(name).unwrap() {
+                    SqlType::Integer => DataType::Integer,
+                    SqlType::VarChar => DataType::Utf8,
+                };
+                let display_size = schema.field_length(name).unwrap_or(11);
+                Column::new(name, data_type, display_size)
+            })
+            .collect();
+
+        // NOTE: not the planning, like EmbeddedStatement
+        let deadline = (!timeout.is_zero()).then(|| Instant::now() + timeout);
+        self.tx.lock().unwrap().set_deadline(deadline);
+        let mut scan = plan.open(self.tx.clone())?;
+        let fields = scan.fields();
+        let limit = self.vars.limit;
+        Response::Columns(columns).write_to(stream)?;
+        let mut send_rows = || -> anyhow::Result<()> {
+            let mut rows = Vec::new();
+            let mut sent = 0;
+            while (limit == 0 || sent < limit) && scan.next()? {
+                let mut row = Vec::new();
+                for name in fields.iter() {
+                    row.push(match scan.field_type(name).unwrap() {
+                        SqlType::Integer => Value::Int32(scan.get_i32(name)?),
+                        SqlType::VarChar => Value::String(scan.get_string(name)?),
+                    });
+                }
+                rows.push(row);
+                sent += 1;
+                if rows.len() == BATCH_SIZE {
+                    Response::Batch(mem::take(&mut rows)).write_to(stream)?;
+                }
+            }
+            if !rows.is_empty() {
+                Response::Batch(rows).write_to(stream)?;
+            }
+            Ok(())
+        };
+        let result = send_rows();
+        // NOTE: unpin before the rollback
+        scan.close();
+        self.tx.lock().unwrap().set_deadline(None);
+        result.map(|_| Response::Done)
+    }
+
+    fn show(&self, name: &str, stream: &mut TcpStream) -> anyhow::Result<Response> {
+        let (columns, rows) = if name == "sessions" {
+            let rows: Vec<Vec<Value>> = self
+                .registry
+                .list()
+                .into_iter()
+                .map(|info| {
+                    vec![
+                        Value::Int32(info.id.try_into().unwrap_or(i32::MAX)),
+                        Value::String(info.addr.map_or(String::new(), |a| a.to_string())),
+                        Value::String(info.user.unwrap_or_default()),
+                        Value::Int32(info.txnum),
+                        Value::String(if info.autocommit { "on" } else { "off" }.into()),
+                        Value::String(info.sql.unwrap_or_default()),
+                    ]
+                })
+                .collect();
+            let width = |i: usize| {
+                rows.iter()
+                    .map(|row| match &row[i] {
+                        Value::String(s) => s.chars().count(),
+                        Value::Int32(_) => 11,
+                    })
+                    .max()
+                    .unwrap_or(0)
+            };
+            let columns = vec![
+                Column::new("id", DataType::Integer, 11),
+                Column::new("addr", DataType::Utf8, width(1)),
+                Column::new("user", DataType::Utf8, width(2)),
+                Column::new("txnum", DataType::Integer, 11),
+                Column::new("autocommit", DataType::Utf8, 3),
+                Column::new("sql", DataType::Utf8, width(5)),
+            ];
+            (columns, rows)
+        } else {
+            let value = self
+                .vars
+                .get(name)
+                .ok_or_else(|| anyhow::anyhow!("unknown variable: {}", name))?;
+            let columns = vec![Column::new(name, DataType::Integer, 11)];
+            (columns, vec![vec![Value::Int32(value)]])
+        };
+        Response::Columns(columns).write_to(stream)?;
+        Response::Batch(rows).write_to(stream)?;
+        Ok(Response::Done)
+    }
+
+    fn commit(&mut self) -> anyhow::Result<()> {
+        self.tx.lock().unwrap().commit()?;
+        self.tx = self.new_tx();
+        Ok(())
+    }
+
+    fn rollback(&mut self) -> anyhow::Result<()> {
+        self.tx.lock().unwrap().rollback()?;
+        self.tx = self.new_tx();
+        Ok(())
+    }
+
+    fn new_tx(&self) -> Arc<Mutex<Transaction>> {
+        let tx = self.db.new_tx();
+        tx.lock().unwrap().set_user(self.user.clone());
+        tx
+    }
+}
+
+impl Drop for Session<'_> {
+    fn drop(&mut self) {
+        self.registry.close(self.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PreparedSql, SessionCommand, SessionVars};
+    use crate::rdbc::api::Value;
+
+    #[test]
+    fn test_prepared_sql() {
+        let p = PreparedSql::new("insert into T(A, B) values (?, ?)");
+        assert_eq!(p.num_params(), 2);
+        assert_eq!(
+            p.bind(&[Value::Int32(1), Value::String("it's \\ ok".into())])
+                .unwrap(),
+            "insert into T(A, B) values (1, 'it\\'s \\\\ ok')"
+        );
+        assert!(p.bind(&[Value::Int32(1)]).is_err());
+
+        let p = PreparedSql::new("select A from T where B = '?\\'?' and A = ?");
+        assert_eq!(p.num_params(), 1);
+        assert_eq!(
+            p.bind(&[Value::Int32(3)]).unwrap(),
+            "select A from T where B = '?\\'?' and A = 3"
+        );
+    }
+
+    #[test]
+    fn test_session_command() {
+        assert_eq!(
+            SessionCommand::parse("SET limit=10").unwrap(),
+            Some(SessionCommand::Set("limit".into(), "10".into()))
+        );
+        assert_eq!(
+            SessionCommand::parse("set timeout to 500").unwrap(),
+            Some(SessionCommand::Set("timeout".into(), "500".into()))
+        );
+        assert_eq!(
+            SessionCommand::parse("show sessions").unwrap(),
+            Some(SessionCommand::Show("sessions".into()))
+        );
+        assert_eq!(SessionCommand::parse("select A from T").unwrap(), None);
+        assert!(SessionCommand::parse("set limit").is_err());
+
+        let mut vars = SessionVars::default();
+        vars.set("limit", "10").unwrap();
+        vars.set("timeout", "500").unwrap();
+        assert_eq!(vars.get("limit"), Some(10));
+        assert_eq!(vars.get("timeout"), Some(500));
+        assert!(vars.set("limit", "-1").is_err());
+        assert!(vars.set("width", "1").is_err());
+        assert_eq!(vars.get("width"), None);
+    }
+}
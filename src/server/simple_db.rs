@@ -4,91 +4,489 @@
 // https://opensource.org/licenses/MIT
 
 use crate::{
-    buffer_mgr::BufferMgr,
+    buffer_mgr::{BufferMgr, ReplacementPolicy},
     file::file_mgr::FileMgr,
-    log_mgr::LogMgr,
-    metadata::metadata_mgr::MetadataMgr,
-    plan::planner::{BasicQueryPlanner, BasicUpdatePlanner, Planner},
+    log_mgr::{LogMgr, DEFAULT_CHECKPOINT_BLOCKS, DEFAULT_SEGMENT_BLOCKS},
+    metadata::{
+        self, metadata_mgr::MetadataMgr, table_mgr::TableMgr, tablespace_mgr::TablespaceMgr,
+    },
+    plan::{
+        copy::{BulkLoader, CsvRows},
+        planner::{BasicQueryPlanner, IndexUpdatePlanner, Planner},
+    },
+    query::row,
+    record::schema::Schema,
     tx::{
-        lock_table::LockTable,
-        transaction::{Transaction, TxNumber},
+        concurrency_mgr::IsolationLevel,
+        lock_table::{self, LockTable},
+        recovery_mgr::{self, RecoveryMgr},
+        transaction::TxNumber,
     },
 };
-use std::{cell::RefCell, path::Path, rc::Rc, sync::Arc};
+use serde::de::DeserializeOwned;
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::BufReader,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use super::{
+    db_config::{DbConfig, DbConfigError},
+    metrics::Metrics,
+};
+
+pub use crate::file::file_mgr::StorageBackend;
+pub use crate::index::{IndexType, KeyOrder};
+pub use crate::plan::copy::CopyOptions;
+pub use crate::tx::lock_table::DeadlockPolicy;
+pub use crate::tx::recovery_mgr::{LogEntry, RecoveryReport, RestoreReport, RestoreTarget};
+pub use crate::tx::transaction::{Durability, Transaction};
 
-pub struct SimpleDB<'lm, 'bm> {
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexDescription {
+    pub name: String,
+    pub field: String,
+    pub index_type: IndexType,
+    pub key_order: KeyOrder,
+    pub buckets: Option<usize>,
+}
+
+static OPEN_DIRS: Mutex<BTreeMap<PathBuf, usize>> = Mutex::new(BTreeMap::new());
+
+pub struct SimpleDB {
+    dir: PathBuf,
     fm: Arc<FileMgr>,
-    lm: Arc<LogMgr<'lm>>,
-    bm: Arc<BufferMgr<'bm, 'lm>>,
-    tn: TxNumber,
+    lm: Arc<LogMgr>,
+    bm: Arc<BufferMgr>,
+    tn: Arc<TxNumber>,
     lt: Arc<LockTable>,
+    config: DbConfig,
     mm: Option<Arc<MetadataMgr>>,
     planner: Option<Arc<Planner>>,
+    opened_at: Instant,
+    ignored_config: Option<DbConfig>,
+    recovery: Option<RecoveryReport>,
 }
 
-impl<'lm, 'bm> SimpleDB<'lm, 'bm> {
-    const BLOCK_SIZE: usize = 4096;
-    const LOG_FILE: &str = "simpledb.log";
-    const BUFFER_SIZE: usize = 8;
-
-    pub fn new(db_dir_path: &Path, blocksize: usize, buffersize: usize) -> Self {
-        let fm = Arc::new(FileMgr::new(db_dir_path, blocksize));
-        let lm = Arc::new(LogMgr::new(fm.clone(), SimpleDB::LOG_FILE));
-        let bm = Arc::new(BufferMgr::new(fm.clone(), lm.clone(), buffersize));
-        let tn = TxNumber::new();
-        let lt = Arc::new(LockTable::new());
+#[derive(Debug, Clone)]
+pub struct SimpleDBBuilder {
+    block_size: usize,
+    log_segment_blocks: i64,
+    buffers: usize,
+    log_file: String,
+    lock_timeout: Duration,
+    deadlock_policy: DeadlockPolicy,
+    replacement_policy: ReplacementPolicy,
+    storage_backend: StorageBackend,
+    log_archive: Option<PathBuf>,
+    checkpoint_interval: Option<i64>,
+}
+
+impl Default for SimpleDBBuilder {
+    fn default() -> Self {
         Self {
-            fm,
-            lm,
-            bm,
-            tn,
-            lt,
-            mm: None,
-            planner: None,
+            block_size: 4096,
+            log_segment_blocks: DEFAULT_SEGMENT_BLOCKS,
+            buffers: 8,
+            log_file: "simpledb.log".into(),
+            lock_timeout: lock_table::MAX_TIME,
+            deadlock_policy: DeadlockPolicy::default(),
+            replacement_policy: ReplacementPolicy::default(),
+            storage_backend: StorageBackend::default(),
+            log_archive: None,
+            checkpoint_interval: Some(DEFAULT_CHECKPOINT_BLOCKS),
         }
     }
+}
 
-    pub fn new_for_test(db_dir_path: &Path, logfile: &str) -> Self {
-        let fm = Arc::new(FileMgr::new(db_dir_path, SimpleDB::BLOCK_SIZE));
-        let lm = Arc::new(LogMgr::new(fm.clone(), logfile));
+impl SimpleDBBuilder {
+    pub fn block_size(mut self, block_size: usize) -> Self {
+        self.block_size = block_size;
+        self
+    }
+
+    pub fn log_segment_blocks(mut self, log_segment_blocks: i64) -> Self {
+        self.log_segment_blocks = log_segment_blocks;
+        self
+    }
+
+    pub fn buffers(mut self, buffers: usize) -> Self {
+        self.buffers = buffers;
+        self
+    }
+
+    pub fn log_file(mut self, log_file: &str) -> Self {
+        self.log_file = log_file.into();
+        self
+    }
+
+    pub fn lock_timeout(mut self, lock_timeout: Duration) -> Self {
+        self.lock_timeout = lock_timeout;
+        self
+    }
+
+    pub fn deadlock_policy(mut self, deadlock_policy: DeadlockPolicy) -> Self {
+        self.deadlock_policy = deadlock_policy;
+        self
+    }
+
+    pub fn replacement_policy(mut self, replacement_policy: ReplacementPolicy) -> Self {
+        self.replacement_policy = replacement_policy;
+        self
+    }
+
+    pub fn storage_backend(mut self, storage_backend: StorageBackend) -> Self {
+        self.storage_backend = storage_backend;
+        self
+    }
+
+    pub fn log_archive(mut self, path: &Path) -> Self {
+        self.log_archive = Some(path.to_path_buf());
+        self
+    }
+
+    pub fn checkpoint_interval(mut self, blocks: Option<i64>) -> Self {
+        self.checkpoint_interval = blocks;
+        self
+    }
+
+    pub fn open(self, db_dir_path: &Path) -> anyhow::Result<SimpleDB> {
+        let requested = DbConfig::new(self.block_size, self.log_segment_blocks);
+        let (config, stored) = SimpleDB::load_config(db_dir_path, requested)?;
+        let ignored_config = (stored && config != requested).then_some(requested);
+        let fm = Arc::new(FileMgr::new_with_backend(
+            db_dir_path,
+            config.blocksize,
+            self.storage_backend,
+        ));
+        if !stored {
+            if !fm.is_new() {
+                return Err(DbConfigError::Missing.into());
+            }
+            config.save(db_dir_path)?;
+        }
+        let mut lm =
+            LogMgr::new_with_segment_size(fm.clone(), &self.log_file, config.log_segment_blocks)?
+                .with_checkpoint_interval(self.checkpoint_interval);
+        if let Some(archive) = &self.log_archive {
+            lm = lm.with_archive(&db_dir_path.join(archive))?;
+        }
+        let lm = Arc::new(lm);
         let bm = Arc::new(BufferMgr::new(
             fm.clone(),
             lm.clone(),
-            SimpleDB::BUFFER_SIZE,
+            self.buffers,
+            self.replacement_policy,
         ));
-        let tn = TxNumber::new();
-        let lt = Arc::new(LockTable::new());
-        Self {
+        let tn = Arc::new(TxNumber::new());
+        let lt = Arc::new(
+            LockTable::with_timeout(self.lock_timeout).with_deadlock_policy(self.deadlock_policy),
+        );
+        let dir = fs::canonicalize(db_dir_path)?;
+        *OPEN_DIRS.lock().unwrap().entry(dir.clone()).or_default() += 1;
+        Ok(SimpleDB {
+            dir,
             fm,
             lm,
             bm,
             tn,
             lt,
+            config,
             mm: None,
             planner: None,
+            opened_at: Instant::now(),
+            ignored_config,
+            recovery: None,
+        })
+    }
+}
+
+impl SimpleDB {
+    pub fn builder() -> SimpleDBBuilder {
+        SimpleDBBuilder::default()
+    }
+
+    pub fn new_for_test(db_dir_path: &Path, logfile: &str) -> Self {
+        SimpleDB::builder()
+            .log_file(logfile)
+            .open(db_dir_path)
+            .unwrap()
+    }
+
+    fn load_config(db_dir_path: &Path, requested: DbConfig) -> anyhow::Result<(DbConfig, bool)> {
+        match DbConfig::load(db_dir_path)? {
+            Some(stored) => Ok((stored, true)),
+            None => {
+                requested.validate()?;
+                Ok((requested, false))
+            }
         }
     }
 
-    pub fn init(&mut self) {
+    pub fn init(&mut self) -> anyhow::Result<()> {
         let is_new = self.fm.is_new();
+        // NOTE: a clean shutdown ends the log with a checkpoint
+        let clean = is_new || RecoveryMgr::ends_with_checkpoint(&self.lm)?;
 
         let tx = self.new_tx();
+        if !is_new {
+            // NOTE: recovery writes to files in tablespaces
+            let tm = TableMgr::new(self.config.max_name_length());
+            let tsm = TablespaceMgr::new(Arc::new(tm));
+            tsm.load(tx.clone())?;
+            if !clean {
+                let report = tx.lock().unwrap().recover()?;
+                self.lm.truncate_before(report.checkpoint)?;
+                // NOTE: drop the placements of the rolled back transactions
+                self.fm.clear_placements();
+                tsm.load(tx.clone())?;
+                self.recovery = Some(report);
+            }
+        }
 
-        let mm = Arc::new(MetadataMgr::new(is_new, tx.clone()));
+        let mm = Arc::new(MetadataMgr::new(
+            is_new,
+            self.config.max_name_length(),
+            tx.clone(),
+        ));
 
         let qp = BasicQueryPlanner::new(mm.clone());
-        let up = BasicUpdatePlanner::new(mm.clone());
-        let planner = Arc::new(Planner::new(qp, up));
+        let up = IndexUpdatePlanner::new(mm.clone());
+        let planner = Arc::new(Planner::new(mm.clone(), qp, up));
 
-        tx.borrow_mut().commit().unwrap();
+        tx.lock().unwrap().commit()?;
 
         self.mm = Some(mm);
         self.planner = Some(planner);
+        Ok(())
+    }
+
+    pub fn close(self) -> anyhow::Result<()> {
+        // NOTE: the rolled back transactions hold no locks
+        for txnum in self.tn.active_txs() {
+            self.lt.unlock_all(txnum);
+        }
+        let tx = self.new_tx();
+        tx.lock().unwrap().recover()?;
+        tx.lock().unwrap().commit()?;
+        let checkpoint = RecoveryMgr::write_checkpoint(self.lm.clone())?;
+        self.lm.truncate_before(checkpoint)?;
+        Ok(())
+    }
+
+    pub fn restore(self, target: RestoreTarget) -> anyhow::Result<RestoreReport> {
+        for txnum in self.tn.active_txs() {
+            self.lt.unlock_all(txnum);
+        }
+        self.lm.restore_archived()?;
+        let tx = self.new_tx();
+        let recovery = tx.lock().unwrap().recover()?;
+        let mut report = tx.lock().unwrap().rewind(target)?;
+        report.corrupted |= recovery.corrupted;
+        tx.lock().unwrap().commit()?;
+        let checkpoint = RecoveryMgr::write_checkpoint(self.lm.clone())?;
+        self.lm.truncate_before(checkpoint)?;
+        Ok(report)
+    }
+
+    pub fn destroy(self) -> anyhow::Result<()> {
+        // NOTE: held until the directory is gone
+        let open_dirs = OPEN_DIRS.lock().unwrap();
+        let others = open_dirs[&self.dir] - 1;
+        if others > 0 {
+            anyhow::bail!(
+                "the database {} is open by {} other handle(s)",
+                self.dir.display(),
+                others
+            );
+        }
+        let active = self.tn.active_txs().len();
+        if active > 0 {
+            anyhow::bail!("{} transaction(s) of the database are still active", active);
+        }
+        self.fm.destroy()?;
+        Ok(())
+    }
+
+    pub fn config(&self) -> DbConfig {
+        self.config
+    }
+
+    pub fn ignored_config(&self) -> Option<DbConfig> {
+        self.ignored_config
+    }
+
+    pub fn recovered(&self) -> bool {
+        self.recovery.is_some()
+    }
+
+    pub fn recovery_report(&self) -> Option<RecoveryReport> {
+        self.recovery
+    }
+
+    pub fn log_dump(&self) -> anyhow::Result<impl Iterator<Item = anyhow::Result<LogEntry>>> {
+        let entries = recovery_mgr::log_dump(&self.lm)?;
+        Ok(entries.map(|entry| entry.map_err(Into::into)))
+    }
+
+    pub fn query_as<T: DeserializeOwned>(
+        &self,
+        sql: &str,
+        tx: Arc<Mutex<Transaction>>,
+    ) -> anyhow::Result<Vec<T>> {
+        let planner = self.planner();
+        let plan = planner.create_query_plan(sql, tx.clone())?;
+        let schema = plan.schema();
+        let mut scan = plan.open(tx)?;
+        let mut rows = Vec::new();
+        let mut read_rows = || -> anyhow::Result<()> {
+            while scan.next()? {
+                let mut row = Vec::new();
+                for name in schema.fields_iter() {
+                    row.push((name.clone(), scan.get_val(name)?));
+                }
+                rows.push(row::from_row(row)?);
+            }
+            Ok(())
+        };
+        let result = read_rows();
+        scan.close();
+        result.map(|_| rows)
+    }
+
+    pub fn copy_from(
+        &self,
+        table_name: &str,
+        path: &Path,
+        options: &CopyOptions,
+    ) -> anyhow::Result<u64> {
+        let file = BufReader::new(File::open(path)?);
+        let mm = self.metadata_mgr();
+        let tx = self.new_read_tx();
+        let layout = mm.table_layout(table_name, tx.clone())?;
+        tx.lock().unwrap().commit()?;
+        let mut rows = CsvRows::new(file, layout.schema(), options)?;
+        let batch_size = options.batch_size.max(1);
+
+        let mut count = 0;
+        loop {
+            let tx = self.new_tx();
+            let mut loader = BulkLoader::new(mm.clone(), table_name, tx.clone())?;
+            let n = loader.load(&mut rows, batch_size)?;
+            count += loader.finish()?;
+            tx.lock().unwrap().commit()?;
+            if n < batch_size {
+                return Ok(count);
+            }
+        }
+    }
+
+    pub fn table_names(&self) -> anyhow::Result<Vec<String>> {
+        self.read_catalog(|mm, tx| {
+            let mut names = mm.table_names(tx)?;
+            names.retain(|name| !MetadataMgr::is_catalog(name));
+            names.sort();
+            Ok(names)
+        })
+    }
+
+    pub fn view_names(&self) -> anyhow::Result<Vec<String>> {
+        self.read_catalog(|mm, tx| {
+            let mut names = mm.view_names(tx)?;
+            names.sort();
+            Ok(names)
+        })
+    }
+
+    pub fn sequence_names(&self) -> anyhow::Result<Vec<String>> {
+        self.read_catalog(|mm, tx| {
+            let mut names = mm.sequence_names(tx)?;
+            names.sort();
+            Ok(names)
+        })
+    }
+
+    pub fn user_names(&self) -> anyhow::Result<Vec<String>> {
+        self.read_catalog(|mm, tx| {
+            let mut names = mm.user_names(tx)?;
+            names.sort();
+            Ok(names)
+        })
+    }
+
+    pub fn authenticate(&self, user_name: &str, password: &str) -> anyhow::Result<bool> {
+        self.read_catalog(|mm, tx| mm.authenticate(user_name, password, tx))
+    }
+
+    pub fn view_def(&self, view_name: &str) -> anyhow::Result<String> {
+        self.read_catalog(|mm, tx| mm.view_def(view_name, tx))
+    }
+
+    pub fn table_schema(&self, table_name: &str) -> anyhow::Result<Schema> {
+        self.read_catalog(|mm, tx| Ok(mm.table_layout(table_name, tx)?.schema().clone()))
     }
 
-    pub fn new_tx(&self) -> Rc<RefCell<Transaction<'lm, 'bm>>> {
-        Rc::new(RefCell::new(Transaction::new(
-            self.tn.next(),
+    pub fn table_indexes(&self, table_name: &str) -> anyhow::Result<Vec<IndexDescription>> {
+        self.read_catalog(|mm, tx| {
+            mm.table_layout(table_name, tx.clone())?;
+            let mut indexes = mm
+                .table_index_info(table_name, tx)?
+                .into_iter()
+                .map(|(field, ii)| IndexDescription {
+                    name: ii.index_name().into(),
+                    field,
+                    index_type: ii.index_type(),
+                    key_order: ii.key_order(),
+                    buckets: ii.buckets(),
+                })
+                .collect::<Vec<_>>();
+            indexes.sort_by(|a, b| a.name.cmp(&b.name));
+            Ok(indexes)
+        })
+    }
+
+    fn read_catalog<T>(
+        &self,
+        read: impl FnOnce(&MetadataMgr, Arc<Mutex<Transaction>>) -> metadata::common::Result<T>,
+    ) -> anyhow::Result<T> {
+        let tx = self.new_read_tx();
+        let result = read(&self.metadata_mgr(), tx.clone());
+        tx.lock().unwrap().commit()?;
+        Ok(result?)
+    }
+
+    pub fn new_tx(&self) -> Arc<Mutex<Transaction>> {
+        self.new_tx_with_isolation(IsolationLevel::default())
+    }
+
+    pub fn new_tx_with_isolation(&self, isolation: IsolationLevel) -> Arc<Mutex<Transaction>> {
+        Arc::new(Mutex::new(Transaction::new(
+            self.tn.clone(),
+            isolation,
+            self.fm.clone(),
+            self.lm.clone(),
+            self.bm.clone(),
+            self.lt.clone(),
+        )))
+    }
+
+    pub fn new_read_tx(&self) -> Arc<Mutex<Transaction>> {
+        Arc::new(Mutex::new(Transaction::new_read_only(
+            self.tn.clone(),
+            IsolationLevel::default(),
+            self.fm.clone(),
+            self.lm.clone(),
+            self.bm.clone(),
+            self.lt.clone(),
+        )))
+    }
+
+    pub fn new_snapshot_tx(&self) -> Arc<Mutex<Transaction>> {
+        Arc::new(Mutex::new(Transaction::new_snapshot(
+            self.tn.clone(),
             self.fm.clone(),
             self.lm.clone(),
             self.bm.clone(),
@@ -100,11 +498,27 @@ impl<'lm, 'bm> SimpleDB<'lm, 'bm> {
         self.fm.clone()
     }
 
-    pub fn log_mgr(&self) -> Arc<LogMgr<'lm>> {
+    pub fn metrics(&self) -> Metrics {
+        Metrics {
+            uptime: self.opened_at.elapsed(),
+            buffer: self.bm.stats(),
+            log: self.lm.stats(),
+            lock: self.lt.stats(),
+            planner: self
+                .planner
+                .as_ref()
+                .map(|planner| planner.stats())
+                .unwrap_or_default(),
+            active_txs: self.tn.active_txs().len(),
+            failed_truncations: self.tn.failed_truncations(),
+        }
+    }
+
+    pub fn log_mgr(&self) -> Arc<LogMgr> {
         self.lm.clone()
     }
 
-    pub fn buffer_mgr(&self) -> Arc<BufferMgr<'bm, 'lm>> {
+    pub fn buffer_mgr(&self) -> Arc<BufferMgr> {
         self.bm.clone()
     }
 
@@ -116,3 +530,696 @@ impl<'lm, 'bm> SimpleDB<'lm, 'bm> {
         self.planner.as_ref().unwrap().clone()
     }
 }
+
+impl Drop for SimpleDB {
+    fn drop(&mut self) {
+        let mut open_dirs = OPEN_DIRS.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(count) = open_dirs.get_mut(&self.dir) {
+            *count -= 1;
+            if *count == 0 {
+                open_dirs.remove(&self.dir);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        CopyOptions, DbConfig, DeadlockPolicy, IndexDescription, IndexType, KeyOrder,
+        RestoreTarget, SimpleDB,
+    };
+    use crate::{buffer_mgr::ReplacementPolicy, file::file_mgr::PAGE_CHECKSUM_SIZE};
+    use serde::Deserialize;
+    use std::{
+        fs,
+        path::Path,
+        thread,
+        time::{Duration, Instant, SystemTime},
+    };
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_close() {
+        let dir = tempdir().unwrap();
+        {
+            let mut db = SimpleDB::new_for_test(dir.path(), "close_test.log");
+            db.init().unwrap();
+            let planner = db.planner();
+            let tx = db.new_tx();
+            planner
+                .execute_update("create table T(A int)", tx.clone())
+                .unwrap();
+            planner
+                .execute_update("insert into T(A) values (1)", tx.clone())
+                .unwrap();
+            tx.lock().unwrap().commit().unwrap();
+            let active = db.new_tx();
+            planner
+                .execute_update("insert into T(A) values (2)", active.clone())
+                .unwrap();
+            db.close().unwrap();
+            drop(active);
+        }
+
+        for entry in fs::read_dir(dir.path()).unwrap() {
+            let path = entry.unwrap().path();
+            if path
+                .file_name()
+                .unwrap()
+                .to_string_lossy()
+                .starts_with("close_test.log")
+            {
+                fs::remove_file(path).unwrap();
+            }
+        }
+        let mut db = SimpleDB::new_for_test(dir.path(), "close_test.log");
+        db.init().unwrap();
+        let planner = db.planner();
+        let tx = db.new_tx();
+        let plan = planner
+            .create_query_plan("select A from T", tx.clone())
+            .unwrap();
+        let mut scan = plan.open(tx.clone()).unwrap();
+        let mut values = Vec::new();
+        while scan.next().unwrap() {
+            values.push(scan.get_i32("a").unwrap());
+        }
+        scan.close();
+        assert_eq!(values, vec![1]);
+        tx.lock().unwrap().commit().unwrap();
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_restore() {
+        #[derive(Deserialize)]
+        struct Row {
+            a: i32,
+        }
+
+        let dir = tempdir().unwrap();
+        let open = || {
+            // NOTE: a segment per block
+            let mut db = SimpleDB::builder()
+                .log_file("restore_test.log")
+                .log_segment_blocks(1)
+                .log_archive(Path::new("archive"))
+                .open(dir.path())
+                .unwrap();
+            db.init().unwrap();
+            db
+        };
+        let update = |db: &SimpleDB, sql: &str| {
+            let tx = db.new_tx();
+            db.planner().execute_update(sql, tx.clone()).unwrap();
+            tx.lock().unwrap().commit().unwrap();
+        };
+        let values = |db: &SimpleDB| {
+            let tx = db.new_tx();
+            let rows = db.query_as::<Row>("select A from T", tx.clone());
+            tx.lock().unwrap().commit().unwrap();
+            rows.unwrap()
+                .into_iter()
+                .map(|row| row.a)
+                .collect::<Vec<_>>()
+        };
+
+        let db = open();
+        update(&db, "create table T(A int)");
+        update(&db, "insert into T(A) values (1)");
+        update(&db, "insert into T(A) values (2)");
+        db.close().unwrap();
+
+        let db = open();
+        thread::sleep(Duration::from_millis(10));
+        let before_delete = SystemTime::now();
+        thread::sleep(Duration::from_millis(10));
+        update(&db, "delete from T");
+        update(&db, "insert into T(A) values (3)");
+        let lsn = db.log_mgr().latest_lsn() + 1;
+        update(&db, "insert into T(A) values (4)");
+        let tx = db.new_tx();
+        db.planner()
+            .execute_update("insert into T(A) values (5)", tx.clone())
+            .unwrap();
+        let report = db.restore(RestoreTarget::Lsn(lsn)).unwrap();
+        assert_eq!(report.undone, 2);
+        assert!(!report.corrupted);
+        drop(tx);
+
+        let db = open();
+        assert_eq!(values(&db), vec![3]);
+        update(&db, "create table U(B int)");
+        let tx = db.new_tx();
+        for b in 0..100 {
+            let sql = format!("insert into U(B) values ({})", b);
+            db.planner().execute_update(&sql, tx.clone()).unwrap();
+        }
+        tx.lock().unwrap().commit().unwrap();
+        db.close().unwrap();
+        assert!(dir.path().join("archive").read_dir().unwrap().count() > 0);
+
+        let db = open();
+        assert!(
+            db.restore(RestoreTarget::Time(before_delete))
+                .unwrap()
+                .undone
+                >= 3
+        );
+        let db = open();
+        assert_eq!(values(&db), vec![1, 2]);
+        db.close().unwrap();
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_recovered() {
+        let dir = tempdir().unwrap();
+        let values = |db: &SimpleDB| {
+            let planner = db.planner();
+            let tx = db.new_tx();
+            let plan = planner
+                .create_query_plan("select A from T", tx.clone())
+                .unwrap();
+            let mut scan = plan.open(tx.clone()).unwrap();
+            let mut values = Vec::new();
+            while scan.next().unwrap() {
+                values.push(scan.get_i32("a").unwrap());
+            }
+            scan.close();
+            tx.lock().unwrap().commit().unwrap();
+            values
+        };
+        {
+            let mut db = SimpleDB::new_for_test(dir.path(), "recovered_test.log");
+            db.init().unwrap();
+            assert!(!db.recovered());
+            let tx = db.new_tx();
+            db.planner()
+                .execute_update("create table T(A int)", tx.clone())
+                .unwrap();
+            db.planner()
+                .execute_update("insert into T(A) values (1)", tx.clone())
+                .unwrap();
+            tx.lock().unwrap().commit().unwrap();
+            db.close().unwrap();
+        }
+        {
+            let mut db = SimpleDB::new_for_test(dir.path(), "recovered_test.log");
+            db.init().unwrap();
+            assert!(!db.recovered());
+            assert_eq!(values(&db), vec![1]);
+            let tx = db.new_tx();
+            db.planner()
+                .execute_update("insert into T(A) values (2)", tx.clone())
+                .unwrap();
+            tx.lock().unwrap().commit().unwrap();
+            let active = db.new_tx();
+            db.planner()
+                .execute_update("insert into T(A) values (3)", active.clone())
+                .unwrap();
+            // NOTE: simulate a crash
+            std::mem::forget(active);
+        }
+        let mut db = SimpleDB::new_for_test(dir.path(), "recovered_test.log");
+        db.init().unwrap();
+        assert!(db.recovered());
+        assert!(!db.recovery_report().unwrap().corrupted);
+        assert_eq!(values(&db), vec![1, 2]);
+        db.close().unwrap();
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_log_dump() {
+        let dir = tempdir().unwrap();
+        {
+            let mut db = SimpleDB::new_for_test(dir.path(), "log_dump_test.log");
+            db.init().unwrap();
+            let tx = db.new_tx();
+            db.planner()
+                .execute_update("create table T(A int)", tx.clone())
+                .unwrap();
+            tx.lock().unwrap().commit().unwrap();
+            db.close().unwrap();
+        }
+        let mut db = SimpleDB::new_for_test(dir.path(), "log_dump_test.log");
+        db.init().unwrap();
+        let tx = db.new_tx();
+        db.planner()
+            .execute_update("insert into T(A) values (1)", tx.clone())
+            .unwrap();
+        tx.lock().unwrap().commit().unwrap();
+        let txnum = tx.lock().unwrap().txnum();
+
+        let entries = db.log_dump().unwrap().collect::<Vec<_>>();
+        let entries = entries.into_iter().map(Result::unwrap).collect::<Vec<_>>();
+        // NOTE: the records of the previous run have no LSNs
+        let first = entries.iter().position(|e| e.lsn().is_some()).unwrap();
+        assert!(first > 0);
+        assert!(entries[first..].iter().all(|e| e.lsn().is_some()));
+        assert_eq!(entries[first].lsn(), Some(1));
+        assert_eq!(
+            entries.last().unwrap().lsn(),
+            Some(db.log_mgr().latest_lsn())
+        );
+        assert_eq!(entries[first - 1].tx_number(), None);
+        assert_eq!(entries[first - 1].to_string(), "- <CHECKPOINT>");
+
+        let written = entries
+            .iter()
+            .filter(|e| e.tx_number() == Some(txnum))
+            .map(|e| e.to_string())
+            .collect::<Vec<_>>();
+        assert!(written
+            .first()
+            .unwrap()
+            .ends_with(&format!("<START {}>", txnum)));
+        assert!(written
+            .last()
+            .unwrap()
+            .contains(&format!("<COMMIT {}", txnum)));
+        assert!(written.iter().any(|rec| rec.contains("<SETINT")));
+        db.close().unwrap();
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_checkpoint_interval() {
+        let dir = tempdir().unwrap();
+        let insert = |db: &SimpleDB, a: i32| {
+            let tx = db.new_tx();
+            db.planner()
+                .execute_update(&format!("insert into T(A) values ({})", a), tx.clone())
+                .unwrap();
+            tx
+        };
+        let open = || {
+            let mut db = SimpleDB::builder()
+                .block_size(1024)
+                .log_file("checkpoint_test.log")
+                .checkpoint_interval(Some(2))
+                .open(dir.path())
+                .unwrap();
+            db.init().unwrap();
+            db
+        };
+        {
+            let db = open();
+            let tx = db.new_tx();
+            db.planner()
+                .execute_update("create table T(A int)", tx.clone())
+                .unwrap();
+            tx.lock().unwrap().commit().unwrap();
+
+            let checkpoints = db.metrics().log.checkpoints;
+            let active = db.new_tx();
+            for a in 1..20 {
+                insert(&db, a).lock().unwrap().commit().unwrap();
+            }
+            assert!(db.log_mgr().checkpoint_due());
+            assert_eq!(db.metrics().log.checkpoints, checkpoints);
+            active.lock().unwrap().rollback().unwrap();
+            assert_eq!(db.metrics().log.checkpoints, checkpoints + 1);
+            assert!(!db.log_mgr().checkpoint_due());
+
+            for a in 20..40 {
+                insert(&db, a).lock().unwrap().commit().unwrap();
+            }
+            assert!(db.metrics().log.checkpoints > checkpoints + 1);
+            // NOTE: simulate a crash
+            let tx = insert(&db, 40);
+            db.log_mgr().flush(db.log_mgr().latest_lsn()).unwrap();
+            std::mem::forget(tx);
+        }
+        let db = open();
+        assert!(db.recovered());
+        let tx = db.new_tx();
+        let plan = db
+            .planner()
+            .create_query_plan("select A from T", tx.clone())
+            .unwrap();
+        let mut scan = plan.open(tx.clone()).unwrap();
+        let mut values = Vec::new();
+        while scan.next().unwrap() {
+            values.push(scan.get_i32("a").unwrap());
+        }
+        scan.close();
+        tx.lock().unwrap().commit().unwrap();
+        values.sort();
+        assert_eq!(values, (1..40).collect::<Vec<_>>());
+        db.close().unwrap();
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_query_as() {
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct Row {
+            a: i32,
+            b: String,
+        }
+
+        let dir = tempdir().unwrap();
+        let mut db = SimpleDB::new_for_test(dir.path(), "query_as_test.log");
+        db.init().unwrap();
+        let planner = db.planner();
+        let tx = db.new_tx();
+        planner
+            .execute_update("create table T(A int, B varchar(5))", tx.clone())
+            .unwrap();
+        planner
+            .execute_update("insert into T(A, B) values (1, 'one')", tx.clone())
+            .unwrap();
+        let rows: Vec<Row> = db.query_as("select B, A from T", tx.clone()).unwrap();
+        assert_eq!(
+            rows,
+            vec![Row {
+                a: 1,
+                b: "one".into()
+            }]
+        );
+        let err = db
+            .query_as::<Row>("select A from T", tx.clone())
+            .unwrap_err();
+        assert!(err.to_string().contains("missing field `b`"));
+        tx.lock().unwrap().commit().unwrap();
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_copy_from() {
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct Row {
+            a: i32,
+        }
+
+        let dir = tempdir().unwrap();
+        let mut db = SimpleDB::new_for_test(dir.path(), "copy_from_test.log");
+        db.init().unwrap();
+        let tx = db.new_tx();
+        db.planner()
+            .execute_update("create table T(A int)", tx.clone())
+            .unwrap();
+        tx.lock().unwrap().commit().unwrap();
+
+        let csv = dir.path().join("t.csv");
+        let options = CopyOptions {
+            batch_size: 10,
+            ..CopyOptions::default()
+        };
+        let text: String = (1..=25).map(|a| format!("{}\n", a)).collect();
+        fs::write(&csv, text.replace("23\n", "x\n")).unwrap();
+        let err = db.copy_from("t", &csv, &options).unwrap_err();
+        assert_eq!(err.to_string(), "line 23: invalid integer for a: \"x\"");
+        let tx = db.new_tx();
+        let rows: Vec<Row> = db.query_as("select A from T", tx.clone()).unwrap();
+        assert_eq!(rows.len(), 20);
+        tx.lock().unwrap().commit().unwrap();
+
+        let text: String = (21..=30).map(|a| format!("{}\n", a)).collect();
+        fs::write(&csv, text).unwrap();
+        assert_eq!(db.copy_from("t", &csv, &options).unwrap(), 10);
+        let tx = db.new_tx();
+        let rows: Vec<Row> = db.query_as("select A from T", tx.clone()).unwrap();
+        assert_eq!(
+            rows.iter().map(|r| r.a).collect::<Vec<_>>(),
+            (1..=30).collect::<Vec<_>>()
+        );
+        tx.lock().unwrap().commit().unwrap();
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_catalog() {
+        let dir = tempdir().unwrap();
+        let mut db = SimpleDB::new_for_test(dir.path(), "catalog_test.log");
+        db.init().unwrap();
+        assert!(db.sequence_names().unwrap().is_empty());
+        assert!(db.user_names().unwrap().is_empty());
+        let planner = db.planner();
+        let tx = db.new_tx();
+        for sql in [
+            "create table U(B varchar(9))",
+            "create table T(A int, B varchar(5))",
+            "create index TB on T(B) using hash(16)",
+            "create index TA on T(A desc)",
+            "create view V as select A from T",
+            "create sequence S",
+            "create user Alice password 'secret'",
+            "alter user Alice with password 'changed'",
+        ] {
+            planner.execute_update(sql, tx.clone()).unwrap();
+        }
+        tx.lock().unwrap().commit().unwrap();
+
+        assert_eq!(db.table_names().unwrap(), vec!["t", "u"]);
+        assert_eq!(db.view_names().unwrap(), vec!["v"]);
+        assert_eq!(db.view_def("v").unwrap(), "select a from t");
+        assert_eq!(db.sequence_names().unwrap(), vec!["s"]);
+        assert_eq!(db.user_names().unwrap(), vec!["alice"]);
+        assert!(db.authenticate("alice", "changed").unwrap());
+        assert!(!db.authenticate("alice", "secret").unwrap());
+        let schema = db.table_schema("t").unwrap();
+        assert_eq!(schema.fields_iter().collect::<Vec<_>>(), vec!["a", "b"]);
+        assert_eq!(schema.field_length("b"), Some(5));
+        assert_eq!(
+            db.table_indexes("t").unwrap(),
+            vec![
+                IndexDescription {
+                    name: "ta".into(),
+                    field: "a".into(),
+                    index_type: IndexType::BTree,
+                    key_order: KeyOrder::Descending,
+                    buckets: None,
+                },
+                IndexDescription {
+                    name: "tb".into(),
+                    field: "b".into(),
+                    index_type: IndexType::Hash,
+                    key_order: KeyOrder::Ascending,
+                    buckets: Some(16),
+                },
+            ]
+        );
+        assert!(db.table_indexes("u").unwrap().is_empty());
+        let err = db.table_indexes("x").unwrap_err();
+        assert!(err.to_string().contains("table not found: x"));
+        assert!(db.table_schema("x").is_err());
+        db.close().unwrap();
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_destroy() {
+        let dir = tempdir().unwrap();
+        let db_dir = dir.path().join("db");
+        let ts_dir = dir.path().join("big");
+        {
+            let mut db = SimpleDB::new_for_test(&db_dir, "destroy_test.log");
+            db.init().unwrap();
+            let planner = db.planner();
+            let tx = db.new_tx();
+            let cmd = format!("create tablespace big location '{}'", ts_dir.display());
+            planner.execute_update(&cmd, tx.clone()).unwrap();
+            planner
+                .execute_update("create table T(A int) tablespace big", tx.clone())
+                .unwrap();
+            planner
+                .execute_update("insert into T(A) values (1)", tx.clone())
+                .unwrap();
+            tx.lock().unwrap().commit().unwrap();
+            assert!(ts_dir.join("t.tbl").exists());
+
+            let other = SimpleDB::new_for_test(&db_dir.join("."), "destroy_test.log");
+            let err = other.destroy().unwrap_err();
+            assert!(err.to_string().contains("open by 1 other handle"));
+            let _tx = db.new_tx();
+            let err = db.destroy().unwrap_err();
+            assert!(err.to_string().contains("1 transaction(s)"));
+        }
+        assert!(db_dir.exists());
+
+        let mut db = SimpleDB::new_for_test(&db_dir, "destroy_test.log");
+        db.init().unwrap();
+        db.destroy().unwrap();
+        assert!(!db_dir.exists());
+        assert!(!ts_dir.exists());
+        let mut db = SimpleDB::new_for_test(&db_dir, "destroy_test.log");
+        db.init().unwrap();
+        assert!(!db.recovered());
+        db.close().unwrap();
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_metrics() {
+        let dir = tempdir().unwrap();
+        let mut db = SimpleDB::new_for_test(dir.path(), "metrics_test.log");
+        db.init().unwrap();
+        let planner = db.planner();
+        let tx = db.new_tx();
+        planner
+            .execute_update("create table T(A int)", tx.clone())
+            .unwrap();
+        planner
+            .execute_update("insert into T(A) values (1)", tx.clone())
+            .unwrap();
+        planner
+            .create_query_plan("select A from T", tx.clone())
+            .unwrap();
+        let metrics = db.metrics();
+        assert_eq!(metrics.statements(), 3);
+        assert_eq!(metrics.active_txs, 1);
+        assert!(metrics.buffer.hits > 0);
+        assert!(metrics.log_bytes_per_sec() > 0.0);
+
+        tx.lock().unwrap().commit().unwrap();
+        let metrics = db.metrics();
+        assert_eq!(metrics.active_txs, 0);
+        assert_eq!(metrics.lock.waits, 0);
+        assert_eq!(metrics.failed_truncations, 0);
+
+        // NOTE: e.g. a directory can't be truncated
+        fs::create_dir(dir.path().join("metrics_test_dir")).unwrap();
+        let tx = db.new_tx();
+        tx.lock()
+            .unwrap()
+            .truncate_on_commit("metrics_test_dir", 0)
+            .unwrap();
+        tx.lock().unwrap().commit().unwrap();
+        assert_eq!(db.metrics().failed_truncations, 1);
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_builder() {
+        let dir = tempdir().unwrap();
+        let mut db = SimpleDB::builder()
+            .block_size(1024)
+            .buffers(12)
+            .log_file("builder_test.log")
+            .lock_timeout(Duration::from_millis(100))
+            .replacement_policy(ReplacementPolicy::Lru)
+            .open(dir.path())
+            .unwrap();
+        db.init().unwrap();
+        assert_eq!(db.file_mgr().blocksize(), 1024 - PAGE_CHECKSUM_SIZE);
+        assert_eq!(db.buffer_mgr().available(), 12);
+        assert!(db
+            .log_mgr()
+            .segment_files()
+            .iter()
+            .all(|f| f.starts_with("builder_test.log")));
+
+        let tx1 = db.new_tx();
+        let blk = tx1.lock().unwrap().append("builder_test_file").unwrap();
+        tx1.lock().unwrap().pin(&blk).unwrap();
+        tx1.lock().unwrap().set_i32(&blk, 0, 1, false).unwrap();
+        let tx2 = db.new_tx();
+        tx2.lock().unwrap().pin(&blk).unwrap();
+        let start = Instant::now();
+        assert!(tx2.lock().unwrap().get_i32(&blk, 0).is_err());
+        assert!(start.elapsed() < Duration::from_secs(5));
+        tx2.lock().unwrap().unpin(&blk);
+        tx2.lock().unwrap().rollback().unwrap();
+        tx1.lock().unwrap().unpin(&blk);
+        tx1.lock().unwrap().rollback().unwrap();
+        db.close().unwrap();
+
+        let db = SimpleDB::builder()
+            .block_size(1024)
+            .log_file("builder_test.log")
+            .deadlock_policy(DeadlockPolicy::WaitDie)
+            .open(dir.path())
+            .unwrap();
+        let tx1 = db.new_tx();
+        tx1.lock().unwrap().pin(&blk).unwrap();
+        tx1.lock().unwrap().set_i32(&blk, 0, 1, false).unwrap();
+        let tx2 = db.new_tx();
+        tx2.lock().unwrap().pin(&blk).unwrap();
+        let start = Instant::now();
+        assert!(tx2.lock().unwrap().get_i32(&blk, 0).is_err());
+        assert!(start.elapsed() < Duration::from_secs(1));
+        tx2.lock().unwrap().unpin(&blk);
+        tx2.lock().unwrap().rollback().unwrap();
+        tx1.lock().unwrap().unpin(&blk);
+        tx1.lock().unwrap().rollback().unwrap();
+        assert_eq!(db.ignored_config(), None);
+        drop(db);
+
+        let db = SimpleDB::builder()
+            .block_size(400)
+            .log_file("builder_test.log")
+            .open(dir.path())
+            .unwrap();
+        assert_eq!(db.config().blocksize, 1024);
+        assert_eq!(db.ignored_config().unwrap().blocksize, 400);
+        drop(db);
+
+        fs::write(
+            dir.path().join(DbConfig::CONFIG_FILE),
+            "version = 99\nblocksize = 1024\nlog_segment_blocks = 16\n",
+        )
+        .unwrap();
+        let err = SimpleDB::builder().open(dir.path()).err().unwrap();
+        assert!(err.to_string().contains("unsupported database version: 99"));
+        fs::remove_file(dir.path().join(DbConfig::CONFIG_FILE)).unwrap();
+        let err = SimpleDB::builder().open(dir.path()).err().unwrap();
+        assert!(err.to_string().contains("has no simpledb.conf"));
+        assert!(!dir.path().join(DbConfig::CONFIG_FILE).exists());
+        let new_dir = tempdir().unwrap();
+        assert!(SimpleDB::builder()
+            .log_segment_blocks(0)
+            .open(new_dir.path())
+            .is_err());
+        new_dir.close().unwrap();
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_owned_handles() {
+        use crate::{query::scan::UpdateScan, tx::transaction::Transaction};
+        use std::sync::{Arc, Mutex};
+
+        struct Cursor {
+            scan: Box<dyn UpdateScan>,
+            tx: Arc<Mutex<Transaction>>,
+            _db: SimpleDB,
+        }
+
+        let dir = tempdir().unwrap();
+        let mut db = SimpleDB::new_for_test(dir.path(), "owned_handles_test.log");
+        db.init().unwrap();
+        let planner = db.planner();
+        let tx = db.new_tx();
+        planner
+            .execute_update("create table T(A int)", tx.clone())
+            .unwrap();
+        for i in 0..3 {
+            let sql = format!("insert into T(A) values ({})", i);
+            planner.execute_update(&sql, tx.clone()).unwrap();
+        }
+        let plan = planner
+            .create_query_plan("select A from T", tx.clone())
+            .unwrap();
+        let scan = plan.open(tx.clone()).unwrap();
+        let cursor = Cursor { scan, tx, _db: db };
+
+        let values = thread::spawn(move || {
+            let mut cursor = cursor;
+            let mut values = Vec::new();
+            while cursor.scan.next().unwrap() {
+                values.push(cursor.scan.get_i32("a").unwrap());
+            }
+            cursor.scan.close();
+            cursor.tx.lock().unwrap().commit().unwrap();
+            values
+        })
+        .join()
+        .unwrap();
+        assert_eq!(values, vec![0, 1, 2]);
+        dir.close().unwrap();
+    }
+}
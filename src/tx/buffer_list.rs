@@ -4,7 +4,7 @@
 // https://opensource.org/licenses/MIT
 
 use crate::{
-    buffer_mgr::{Buffer, BufferMgr, Result},
+    buffer_mgr::{Buffer, BufferMgr, PinPriority, Result},
     file::block_id::BlockId,
 };
 use std::{
@@ -12,27 +12,29 @@ use std::{
     sync::{Arc, Mutex},
 };
 
-pub(crate) struct BufferList<'b, 'lm> {
-    buffers: HashMap<BlockId, Arc<Mutex<Buffer<'b, 'lm>>>>,
+pub(crate) struct BufferList {
+    buffers: HashMap<BlockId, Arc<Mutex<Buffer>>>,
     pins: Vec<BlockId>,
-    bm: Arc<BufferMgr<'b, 'lm>>,
+    bm: Arc<BufferMgr>,
+    txnum: i32,
 }
 
-impl<'b, 'lm> BufferList<'b, 'lm> {
-    pub(crate) fn new(bm: Arc<BufferMgr<'b, 'lm>>) -> Self {
+impl BufferList {
+    pub(crate) fn new(bm: Arc<BufferMgr>, txnum: i32) -> Self {
         Self {
             buffers: HashMap::new(),
             pins: Vec::new(),
             bm,
+            txnum,
         }
     }
 
-    pub(crate) fn get_buffer(&self, block: &BlockId) -> Option<&Arc<Mutex<Buffer<'b, 'lm>>>> {
+    pub(crate) fn get_buffer(&self, block: &BlockId) -> Option<&Arc<Mutex<Buffer>>> {
         self.buffers.get(block)
     }
 
-    pub(crate) fn pin(&mut self, block: &BlockId) -> Result<()> {
-        let buff = self.bm.pin(block)?;
+    pub(crate) fn pin(&mut self, block: &BlockId, priority: PinPriority) -> Result<()> {
+        let buff = self.bm.pin_with_priority(self.txnum, block, priority)?;
         self.buffers.insert(block.clone(), buff);
         self.pins.push(block.clone());
         Ok(())
@@ -47,19 +49,24 @@ impl<'b, 'lm> BufferList<'b, 'lm> {
 
     pub(crate) fn unpin(&mut self, block: &BlockId) {
         let buff = self.buffers.get(block).unwrap();
-        self.bm.unpin(buff.clone());
+        self.bm.unpin(self.txnum, buff.clone());
         self.remove_from_pins(block);
         if !self.pins.contains(block) {
             self.buffers.remove(block);
         }
     }
 
-    pub(crate) fn unpin_all(&mut self) {
-        for block in self.pins.iter() {
-            let buff = self.buffers.get(block).unwrap();
-            self.bm.unpin(buff.clone());
-        }
+    pub(crate) fn discard(&self, block: &BlockId) {
+        self.bm.discard(block);
+    }
+
+    pub(crate) fn flush_file(&self, filename: &str) -> Result<()> {
+        self.bm.flush_file(filename)
+    }
+
+    pub(crate) fn unpin_all(&mut self) -> Vec<BlockId> {
         self.buffers.clear();
         self.pins.clear();
+        self.bm.release_pins(self.txnum)
     }
 }
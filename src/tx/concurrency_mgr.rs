@@ -3,64 +3,194 @@
 // This software is released under the MIT License.
 // https://opensource.org/licenses/MIT
 
-use super::lock_table::{LockTable, Result};
+use super::lock_table::{LockMode, LockTable, LockTarget, Result};
 use crate::file::block_id::BlockId;
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
 
-#[derive(Debug, PartialEq, Eq)]
-enum LockType {
-    S,
-    X,
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IsolationLevel {
+    ReadCommitted,
+    #[default]
+    Serializable,
 }
 
 pub struct ConcurrencyMgr {
+    txnum: i32,
+    isolation: IsolationLevel,
     lock_table: Arc<LockTable>,
-    locks: HashMap<BlockId, LockType>,
+    // NOTE: file -> modes, including the intention ones
+    table_locks: HashMap<String, HashSet<LockMode>>,
+    locks: HashMap<BlockId, HashSet<LockMode>>,
+    slot_locks: HashMap<BlockId, HashMap<i32, LockMode>>,
 }
 
 impl ConcurrencyMgr {
-    pub fn new(lock_table: Arc<LockTable>) -> Self {
+    pub fn new(txnum: i32, isolation: IsolationLevel, lock_table: Arc<LockTable>) -> Self {
         Self {
+            txnum,
+            isolation,
             lock_table,
+            table_locks: HashMap::new(),
             locks: HashMap::new(),
+            slot_locks: HashMap::new(),
         }
     }
 
     pub fn slock(&mut self, blk: &BlockId) -> Result<()> {
-        if !self.locks.contains_key(blk) {
-            self.lock_table.slock(blk)?;
-            self.locks.insert(blk.clone(), LockType::S);
-        }
-        Ok(())
+        self.lock_block(blk, LockMode::S)
     }
 
     pub fn xlock(&mut self, blk: &BlockId) -> Result<()> {
-        if !self.has_xlock(blk) {
+        if !self.has_block_lock(blk, LockMode::X) {
             self.slock(blk)?;
-            self.lock_table.xlock(blk)?;
-            self.locks.insert(blk.clone(), LockType::X);
+            self.lock_block(blk, LockMode::X)?;
+        }
+        Ok(())
+    }
+
+    pub fn slock_table(&mut self, filename: &str) -> Result<()> {
+        if self.isolation == IsolationLevel::ReadCommitted {
+            return Ok(());
+        }
+        self.lock_file(filename, LockMode::S)
+    }
+
+    pub fn xlock_table(&mut self, filename: &str) -> Result<()> {
+        self.lock_file(filename, LockMode::X)
+    }
+
+    pub fn slock_slot(&mut self, blk: &BlockId, slot: i32) -> Result<()> {
+        self.lock_slot(blk, slot, LockMode::IS, LockMode::S)
+    }
+
+    pub fn xlock_slot(&mut self, blk: &BlockId, slot: i32) -> Result<()> {
+        self.lock_slot(blk, slot, LockMode::IX, LockMode::X)
+    }
+
+    pub fn end_read(&mut self, blk: &BlockId) {
+        if self.isolation == IsolationLevel::ReadCommitted
+            && self.locks.get(blk) == Some(&HashSet::from([LockMode::S]))
+        {
+            self.lock_table
+                .unlock(self.txnum, &LockTarget::Block(blk.clone()));
+            self.locks.remove(blk);
+        }
+    }
+
+    pub fn end_read_slot(&mut self, blk: &BlockId, slot: i32) {
+        if self.isolation != IsolationLevel::ReadCommitted {
+            return;
+        }
+        if let Some(slots) = self.slot_locks.get_mut(blk) {
+            if slots.get(&slot) == Some(&LockMode::S) {
+                self.lock_table
+                    .unlock(self.txnum, &LockTarget::Slot(blk.clone(), slot));
+                slots.remove(&slot);
+            }
+        }
+    }
+
+    fn has_table_lock(&self, filename: &str, mode: LockMode) -> bool {
+        self.table_locks
+            .get(filename)
+            .is_some_and(|modes| modes.iter().any(|m| m.covers(mode)))
+    }
+
+    fn has_block_lock(&self, blk: &BlockId, mode: LockMode) -> bool {
+        let covered = self
+            .table_locks
+            .get(blk.filename())
+            .is_some_and(|modes| modes.iter().any(|m| m.covers_children(mode)));
+        covered
+            || self
+                .locks
+                .get(blk)
+                .is_some_and(|modes| modes.iter().any(|m| m.covers(mode)))
+    }
+
+    fn has_slot_lock(&self, blk: &BlockId, slot: i32, mode: LockMode) -> bool {
+        self.has_block_lock(blk, mode)
+            || self
+                .slot_locks
+                .get(blk)
+                .and_then(|slots| slots.get(&slot))
+                .is_some_and(|m| m.covers(mode))
+    }
+
+    fn lock_file(&mut self, filename: &str, mode: LockMode) -> Result<()> {
+        if !self.has_table_lock(filename, mode) {
+            self.lock_table
+                .lock(self.txnum, &LockTarget::Table(filename.into()), mode)?;
+            self.table_locks
+                .entry(filename.into())
+                .or_default()
+                .insert(mode);
         }
         Ok(())
     }
 
-    fn has_xlock(&self, blk: &BlockId) -> bool {
-        match self.locks.get(blk) {
-            Some(locktype) => *locktype == LockType::X,
-            None => false,
+    fn lock_block(&mut self, blk: &BlockId, mode: LockMode) -> Result<()> {
+        if !self.has_block_lock(blk, mode) {
+            self.lock_file(blk.filename(), mode.intention())?;
+            self.lock_table
+                .lock(self.txnum, &LockTarget::Block(blk.clone()), mode)?;
+            self.locks.entry(blk.clone()).or_default().insert(mode);
         }
+        Ok(())
+    }
+
+    fn lock_slot(
+        &mut self,
+        blk: &BlockId,
+        slot: i32,
+        intention: LockMode,
+        mode: LockMode,
+    ) -> Result<()> {
+        if !self.has_slot_lock(blk, slot, mode) {
+            self.lock_block(blk, intention)?;
+            self.lock_table
+                .lock(self.txnum, &LockTarget::Slot(blk.clone(), slot), mode)?;
+            self.slot_locks
+                .entry(blk.clone())
+                .or_default()
+                .insert(slot, mode);
+        }
+        Ok(())
     }
 
     pub fn release(&mut self) {
+        for (blk, slots) in self.slot_locks.iter() {
+            for slot in slots.keys() {
+                self.lock_table
+                    .unlock(self.txnum, &LockTarget::Slot(blk.clone(), *slot));
+            }
+        }
         for blk in self.locks.keys() {
-            self.lock_table.unlock(blk);
+            self.lock_table
+                .unlock(self.txnum, &LockTarget::Block(blk.clone()));
+        }
+        for filename in self.table_locks.keys() {
+            self.lock_table
+                .unlock(self.txnum, &LockTarget::Table(filename.clone()));
         }
+        self.slot_locks.clear();
         self.locks.clear();
+        self.table_locks.clear();
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{file::block_id::BlockId, server::simple_db::SimpleDB};
+    use super::{ConcurrencyMgr, IsolationLevel};
+    use crate::{
+        file::block_id::BlockId,
+        server::simple_db::SimpleDB,
+        tx::lock_table::{LockMode, LockTable},
+    };
+    use std::collections::HashSet;
     use std::{
         sync::{Arc, Mutex},
         thread,
@@ -78,7 +208,7 @@ mod tests {
             path.as_path(),
             "test_concurrency_mgr.log",
         )));
-        db.lock().unwrap().init();
+        db.lock().unwrap().init().unwrap();
         {
             let db1 = db.clone();
             let th1 = thread::spawn(move || {
@@ -86,20 +216,22 @@ mod tests {
 
                 let block1 = BlockId::new(FILE_NAME, 1);
                 let block2 = BlockId::new(FILE_NAME, 2);
-                tx.borrow_mut().pin(&block1).unwrap();
-                tx.borrow_mut().pin(&block2).unwrap();
+                tx.lock().unwrap().pin(&block1).unwrap();
+                tx.lock().unwrap().pin(&block2).unwrap();
 
                 println!("tx1: request slock 1");
-                tx.borrow().get_i32(&block1, 0).unwrap();
+                tx.lock().unwrap().get_i32(&block1, 0).unwrap();
                 println!("tx1: receive slock 1");
 
                 thread::sleep(Duration::from_millis(1000));
 
                 println!("tx1: request slock 2");
-                tx.borrow().get_i32(&block2, 0).unwrap();
+                tx.lock().unwrap().get_i32(&block2, 0).unwrap();
                 println!("tx1: receive slock 2");
 
-                tx.borrow_mut().commit().unwrap();
+                tx.lock().unwrap().unpin(&block1);
+                tx.lock().unwrap().unpin(&block2);
+                tx.lock().unwrap().commit().unwrap();
                 println!("tx1: commit");
             });
 
@@ -109,20 +241,22 @@ mod tests {
 
                 let block1 = BlockId::new(FILE_NAME, 1);
                 let block2 = BlockId::new(FILE_NAME, 2);
-                tx.borrow_mut().pin(&block1).unwrap();
-                tx.borrow_mut().pin(&block2).unwrap();
+                tx.lock().unwrap().pin(&block1).unwrap();
+                tx.lock().unwrap().pin(&block2).unwrap();
 
                 println!("tx2: request xlock 2");
-                tx.borrow_mut().set_i32(&block2, 0, 0, false).unwrap();
+                tx.lock().unwrap().set_i32(&block2, 0, 0, false).unwrap();
                 println!("tx2: receive xlock 2");
 
                 thread::sleep(Duration::from_millis(1000));
 
                 println!("tx2: request slock 1");
-                tx.borrow().get_i32(&block1, 0).unwrap();
+                tx.lock().unwrap().get_i32(&block1, 0).unwrap();
                 println!("tx2: receive slock 1");
 
-                tx.borrow_mut().commit().unwrap();
+                tx.lock().unwrap().unpin(&block1);
+                tx.lock().unwrap().unpin(&block2);
+                tx.lock().unwrap().commit().unwrap();
                 println!("tx2: commit");
             });
 
@@ -132,20 +266,22 @@ mod tests {
 
                 let block1 = BlockId::new(FILE_NAME, 1);
                 let block2 = BlockId::new(FILE_NAME, 2);
-                tx.borrow_mut().pin(&block1).unwrap();
-                tx.borrow_mut().pin(&block2).unwrap();
+                tx.lock().unwrap().pin(&block1).unwrap();
+                tx.lock().unwrap().pin(&block2).unwrap();
 
                 thread::sleep(Duration::from_millis(500));
                 println!("tx3: request xlock 1");
-                tx.borrow_mut().set_i32(&block1, 0, 0, false).unwrap();
+                tx.lock().unwrap().set_i32(&block1, 0, 0, false).unwrap();
                 println!("tx3: receive xlock 1");
 
                 thread::sleep(Duration::from_millis(1000));
                 println!("tx3: request slock 2");
-                tx.borrow().get_i32(&block2, 0).unwrap();
+                tx.lock().unwrap().get_i32(&block2, 0).unwrap();
                 println!("tx3: receive slock 2");
 
-                tx.borrow_mut().commit().unwrap();
+                tx.lock().unwrap().unpin(&block1);
+                tx.lock().unwrap().unpin(&block2);
+                tx.lock().unwrap().commit().unwrap();
                 println!("tx3: commit");
             });
 
@@ -155,4 +291,66 @@ mod tests {
         }
         dir.close().unwrap();
     }
+
+    #[test]
+    fn test_read_committed() {
+        let dir = tempdir().unwrap();
+        let db = SimpleDB::new_for_test(dir.path(), "test_read_committed.log");
+        let block = BlockId::new(FILE_NAME, 1);
+
+        let tx1 = db.new_tx_with_isolation(IsolationLevel::ReadCommitted);
+        assert_eq!(
+            tx1.lock().unwrap().isolation(),
+            IsolationLevel::ReadCommitted
+        );
+        tx1.lock().unwrap().pin(&block).unwrap();
+        assert_eq!(tx1.lock().unwrap().get_i32(&block, 0).unwrap(), 0);
+
+        let tx2 = db.new_tx();
+        tx2.lock().unwrap().pin(&block).unwrap();
+        tx2.lock().unwrap().set_i32(&block, 0, 1, true).unwrap();
+        tx2.lock().unwrap().unpin(&block);
+        tx2.lock().unwrap().commit().unwrap();
+
+        assert_eq!(tx1.lock().unwrap().get_i32(&block, 0).unwrap(), 1);
+        tx1.lock().unwrap().set_i32(&block, 0, 2, true).unwrap();
+        assert_eq!(tx1.lock().unwrap().get_i32(&block, 0).unwrap(), 2);
+        tx1.lock().unwrap().unpin(&block);
+        tx1.lock().unwrap().commit().unwrap();
+
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_table_locks() {
+        let lt = Arc::new(LockTable::new());
+        let mut cm1 = ConcurrencyMgr::new(1, IsolationLevel::Serializable, lt.clone());
+        let mut cm2 = ConcurrencyMgr::new(2, IsolationLevel::Serializable, lt.clone());
+
+        // NOTE: one lock for a full scan
+        cm1.slock_table(FILE_NAME).unwrap();
+        for n in 0..100 {
+            cm1.slock(&BlockId::new(FILE_NAME, n)).unwrap();
+        }
+        assert!(cm1.locks.is_empty());
+
+        cm2.xlock(&BlockId::new("test_table_locks_file", 0))
+            .unwrap();
+        assert_eq!(
+            cm2.table_locks["test_table_locks_file"],
+            HashSet::from([LockMode::IS, LockMode::IX])
+        );
+
+        let th = thread::spawn(move || {
+            cm2.xlock(&BlockId::new(FILE_NAME, 5)).unwrap();
+            cm2
+        });
+        thread::sleep(Duration::from_millis(200));
+        assert!(!th.is_finished());
+
+        cm1.xlock(&BlockId::new(FILE_NAME, 7)).unwrap();
+        cm1.release();
+        let mut cm2 = th.join().unwrap();
+        cm2.release();
+    }
 }
@@ -4,117 +4,529 @@
 // https://opensource.org/licenses/MIT
 
 use std::{
-    collections::HashMap,
-    sync::{Condvar, Mutex, MutexGuard},
+    collections::{HashMap, HashSet, VecDeque},
+    fmt::Display,
+    sync::{Condvar, Mutex},
     time::{Duration, SystemTime},
 };
 use thiserror::Error;
 
 use crate::file::block_id::BlockId;
 
-const MAX_TIME: u64 = 10000; // 10 sec
+pub(crate) const MAX_TIME: Duration = Duration::from_secs(10);
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum LockTarget {
+    Table(String),
+    Block(BlockId),
+    Slot(BlockId, i32),
+}
+
+impl Display for LockTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LockTarget::Table(filename) => write!(f, "[file {}]", filename),
+            LockTarget::Block(blk) => write!(f, "{}", blk),
+            LockTarget::Slot(blk, slot) => write!(f, "{} (slot {})", blk, slot),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum LockMode {
+    IS,
+    IX,
+    S,
+    X,
+}
+
+impl LockMode {
+    fn is_compatible_with(&self, other: LockMode) -> bool {
+        use LockMode::*;
+        matches!(
+            (self, other),
+            (IS, IS) | (IS, IX) | (IS, S) | (IX, IS) | (IX, IX) | (S, IS) | (S, S)
+        )
+    }
+
+    pub fn intention(&self) -> LockMode {
+        use LockMode::*;
+        match self {
+            IS | S => IS,
+            IX | X => IX,
+        }
+    }
+
+    pub fn covers_children(&self, other: LockMode) -> bool {
+        use LockMode::*;
+        matches!((self, other), (X, _) | (S, S) | (S, IS))
+    }
+
+    pub fn covers(&self, other: LockMode) -> bool {
+        use LockMode::*;
+        matches!(
+            (self, other),
+            (X, _) | (S, S) | (S, IS) | (IX, IX) | (IX, IS) | (IS, IS)
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DeadlockPolicy {
+    #[default]
+    Detection,
+    WaitDie,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LockStats {
+    pub waits: usize,
+    pub wait_time: Duration,
+    pub aborts: usize,
+}
+
+#[derive(Default)]
+struct LockState {
+    stats: LockStats,
+    // NOTE: target -> txnum -> modes
+    locks: HashMap<LockTarget, HashMap<i32, HashSet<LockMode>>>,
+    // NOTE: FIFO
+    queues: HashMap<LockTarget, VecDeque<(i32, LockMode)>>,
+    // NOTE: waits-for graph
+    waits_for: HashMap<i32, HashSet<i32>>,
+    // NOTE: the edges for the queue order
+    queued_for: HashMap<i32, HashSet<i32>>,
+    // NOTE: let go ahead of the queue to avoid a deadlock
+    overtaking: HashSet<i32>,
+}
+
+impl LockState {
+    fn conflicting_holders(&self, txnum: i32, target: &LockTarget, mode: LockMode) -> HashSet<i32> {
+        self.locks
+            .get(target)
+            .map(|holders| {
+                holders
+                    .iter()
+                    .filter(|(t, modes)| {
+                        **t != txnum && modes.iter().any(|m| !m.is_compatible_with(mode))
+                    })
+                    .map(|(t, _)| *t)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    fn conflicting_waiters(&self, txnum: i32, target: &LockTarget, mode: LockMode) -> HashSet<i32> {
+        // NOTE: a holder goes ahead of the queue, which may be waiting for it
+        let holds = self
+            .locks
+            .get(target)
+            .is_some_and(|holders| holders.contains_key(&txnum));
+        if holds || self.overtaking.contains(&txnum) {
+            return HashSet::new();
+        }
+        self.queues
+            .get(target)
+            .map(|queue| {
+                queue
+                    .iter()
+                    .take_while(|(t, _)| *t != txnum)
+                    .filter(|(_, m)| !m.is_compatible_with(mode))
+                    .map(|(t, _)| *t)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    fn enqueue(&mut self, txnum: i32, target: &LockTarget, mode: LockMode) {
+        self.queues
+            .entry(target.clone())
+            .or_default()
+            .push_back((txnum, mode));
+    }
+
+    fn dequeue(&mut self, txnum: i32, target: &LockTarget) {
+        if let Some(queue) = self.queues.get_mut(target) {
+            queue.retain(|(t, _)| *t != txnum);
+            if queue.is_empty() {
+                self.queues.remove(target);
+            }
+        }
+    }
+
+    fn register_wait(
+        &mut self,
+        txnum: i32,
+        holders: HashSet<i32>,
+        queued: HashSet<i32>,
+    ) -> Option<Vec<i32>> {
+        let queued = &queued - &holders;
+        self.waits_for.insert(txnum, &holders | &queued);
+        self.queued_for.insert(txnum, queued);
+
+        let mut parents = HashMap::new();
+        let mut stack = vec![txnum];
+        while let Some(t) = stack.pop() {
+            for next in self.waits_for.get(&t).into_iter().flatten() {
+                if *next == txnum {
+                    let mut cycle = vec![t];
+                    while let Some(parent) = parents.get(cycle.last().unwrap()) {
+                        cycle.push(*parent);
+                    }
+                    cycle.reverse();
+                    return Some(cycle);
+                }
+                if !parents.contains_key(next) {
+                    parents.insert(*next, t);
+                    stack.push(*next);
+                }
+            }
+        }
+        None
+    }
+
+    fn break_queued_wait(&mut self, cycle: &[i32]) -> bool {
+        let next = cycle.iter().cycle().skip(1);
+        let overtaking = cycle.iter().zip(next).find(|(t, next)| {
+            self.queued_for
+                .get(t)
+                .is_some_and(|queued| queued.contains(next))
+        });
+        let Some((&txnum, _)) = overtaking else {
+            return false;
+        };
+        self.overtaking.insert(txnum);
+        let queued = self.queued_for.remove(&txnum).unwrap_or_default();
+        if let Some(blockers) = self.waits_for.get_mut(&txnum) {
+            blockers.retain(|t| !queued.contains(t));
+        }
+        true
+    }
+
+    fn stop_waiting(&mut self, txnum: i32) {
+        self.waits_for.remove(&txnum);
+        self.queued_for.remove(&txnum);
+        self.overtaking.remove(&txnum);
+    }
+}
 
 pub struct LockTable {
-    locks: Mutex<HashMap<BlockId, i32>>,
+    state: Mutex<LockState>,
     waiting: Condvar,
+    timeout: Duration,
+    policy: DeadlockPolicy,
 }
 
 #[derive(Debug, Error)]
 pub enum LockTableError {
     #[error("aborted to acquire the lock of {0}")]
-    LockAborted(BlockId),
+    LockAborted(LockTarget),
+
+    #[error("deadlock detected while waiting for the lock of {0}")]
+    Deadlock(LockTarget),
+
+    #[error("aborted not to wait for an older transaction for the lock of {0}")]
+    Died(LockTarget),
 }
 
 pub type Result<T> = core::result::Result<T, LockTableError>;
 
 impl LockTable {
     pub fn new() -> Self {
+        Self::with_timeout(MAX_TIME)
+    }
+
+    pub fn with_timeout(timeout: Duration) -> Self {
         Self {
-            locks: Mutex::new(HashMap::new()),
+            state: Mutex::new(LockState::default()),
             waiting: Condvar::new(),
+            timeout,
+            policy: DeadlockPolicy::default(),
         }
     }
 
-    pub fn slock(&self, blk: &BlockId) -> Result<()> {
-        let mut locks = self.locks.lock().unwrap();
+    pub fn with_deadlock_policy(self, policy: DeadlockPolicy) -> Self {
+        Self { policy, ..self }
+    }
+
+    pub fn lock(&self, txnum: i32, target: &LockTarget, mode: LockMode) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
 
         let begintime = SystemTime::now();
-        while self.has_xlock(&locks, blk) && !self.waiting_too_long(begintime) {
-            let result = self
-                .waiting
-                .wait_timeout(locks, Duration::from_millis(MAX_TIME))
-                .unwrap();
+        let mut waited = false;
+        let result = loop {
+            let holders = state.conflicting_holders(txnum, target, mode);
+            let queued = state.conflicting_waiters(txnum, target, mode);
+            if holders.is_empty() && queued.is_empty() {
+                break Ok(());
+            }
+            if self.waiting_too_long(begintime) {
+                break Err(LockTableError::LockAborted(target.clone()));
+            }
+            // NOTE: a smaller txnum is older
+            if self.policy == DeadlockPolicy::WaitDie
+                && holders.iter().chain(&queued).any(|t| *t < txnum)
+            {
+                break Err(LockTableError::Died(target.clone()));
+            }
+            if let Some(cycle) = state.register_wait(txnum, holders, queued) {
+                // NOTE: give up the queue order rather than abort
+                if state.break_queued_wait(&cycle) {
+                    self.waiting.notify_all();
+                    continue;
+                }
+                break Err(LockTableError::Deadlock(target.clone()));
+            }
+            if !waited {
+                state.enqueue(txnum, target, mode);
+                waited = true;
+            }
+            let result = self.waiting.wait_timeout(state, self.timeout).unwrap();
+            state = result.0;
             if result.1.timed_out() {
-                return Err(LockTableError::LockAborted(blk.clone()));
-            } else {
-                locks = result.0;
+                break Err(LockTableError::LockAborted(target.clone()));
             }
+        };
+        state.stop_waiting(txnum);
+        if waited {
+            state.dequeue(txnum, target);
+            state.stats.waits += 1;
+            state.stats.wait_time += begintime.elapsed().unwrap_or_default();
         }
-
-        if self.has_xlock(&locks, blk) {
-            Err(LockTableError::LockAborted(blk.clone()))
-        } else {
-            let val = self.get_lock_val(&locks, blk);
-            locks.insert(blk.clone(), val + 1);
-            Ok(())
+        if result.is_err() {
+            state.stats.aborts += 1;
+            if waited {
+                self.waiting.notify_all();
+            }
         }
+        result?;
+
+        state
+            .locks
+            .entry(target.clone())
+            .or_default()
+            .entry(txnum)
+            .or_default()
+            .insert(mode);
+        Ok(())
     }
 
-    pub fn xlock(&self, blk: &BlockId) -> Result<()> {
-        let mut locks = self.locks.lock().unwrap();
+    pub fn stats(&self) -> LockStats {
+        self.state.lock().unwrap().stats
+    }
 
-        let begintime = SystemTime::now();
-        while self.has_other_slocks(&locks, blk) && !self.waiting_too_long(begintime) {
-            let result = self
-                .waiting
-                .wait_timeout(locks, Duration::from_millis(MAX_TIME))
-                .unwrap();
-            if result.1.timed_out() {
-                return Err(LockTableError::LockAborted(blk.clone()));
-            } else {
-                locks = result.0;
+    pub fn unlock(&self, txnum: i32, target: &LockTarget) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(holders) = state.locks.get_mut(target) {
+            holders.remove(&txnum);
+            if holders.is_empty() {
+                state.locks.remove(target);
             }
         }
-
-        if self.has_other_slocks(&locks, blk) {
-            Err(LockTableError::LockAborted(blk.clone()))
-        } else {
-            locks.insert(blk.clone(), -1);
-            Ok(())
-        }
+        self.waiting.notify_all();
     }
 
-    pub fn unlock(&self, blk: &BlockId) {
-        let mut locks = self.locks.lock().unwrap();
-        let val = self.get_lock_val(&locks, blk);
-        if val > 1 {
-            locks.insert(blk.clone(), val - 1);
-        } else {
-            locks.remove(blk);
-            self.waiting.notify_all();
-        }
+    pub fn unlock_all(&self, txnum: i32) {
+        let mut state = self.state.lock().unwrap();
+        state.locks.retain(|_, holders| {
+            holders.remove(&txnum);
+            !holders.is_empty()
+        });
+        self.waiting.notify_all();
     }
 
     fn waiting_too_long(&self, begintime: SystemTime) -> bool {
-        SystemTime::now()
-            .duration_since(begintime)
-            .unwrap()
-            .as_millis()
-            > MAX_TIME.into()
+        SystemTime::now().duration_since(begintime).unwrap() > self.timeout
     }
+}
 
-    fn has_xlock(&self, locks: &MutexGuard<HashMap<BlockId, i32>>, blk: &BlockId) -> bool {
-        self.get_lock_val(locks, blk) < 0
+#[cfg(test)]
+mod tests {
+    use super::{DeadlockPolicy, LockMode, LockTable, LockTableError, LockTarget};
+    use crate::file::block_id::BlockId;
+    use std::{sync::Arc, thread, time::Duration};
+
+    fn block(filename: &str, number: i64) -> LockTarget {
+        LockTarget::Block(BlockId::new(filename, number))
     }
 
-    fn has_other_slocks(&self, locks: &MutexGuard<HashMap<BlockId, i32>>, blk: &BlockId) -> bool {
-        self.get_lock_val(locks, blk) > 1
+    #[test]
+    fn test_deadlock() {
+        let lt = Arc::new(LockTable::new());
+        let block1 = block("test_deadlock_file", 1);
+        let block2 = block("test_deadlock_file", 2);
+
+        lt.lock(1, &block1, LockMode::X).unwrap();
+        lt.lock(2, &block2, LockMode::X).unwrap();
+
+        let th = {
+            let lt = lt.clone();
+            let block2 = block2.clone();
+            thread::spawn(move || lt.lock(1, &block2, LockMode::S))
+        };
+        thread::sleep(Duration::from_millis(200));
+
+        assert!(matches!(
+            lt.lock(2, &block1, LockMode::S),
+            Err(LockTableError::Deadlock(b)) if b == block1
+        ));
+        lt.unlock(2, &block2);
+        th.join().unwrap().unwrap();
     }
 
-    fn get_lock_val(&self, locks: &MutexGuard<HashMap<BlockId, i32>>, blk: &BlockId) -> i32 {
-        match locks.get(blk) {
-            Some(val) => *val,
-            None => 0,
-        }
+    #[test]
+    fn test_deadlock_on_upgrade() {
+        let lt = Arc::new(LockTable::new());
+        let block = block("test_deadlock_on_upgrade_file", 1);
+
+        lt.lock(1, &block, LockMode::S).unwrap();
+        lt.lock(2, &block, LockMode::S).unwrap();
+
+        let th = {
+            let lt = lt.clone();
+            let block = block.clone();
+            thread::spawn(move || lt.lock(1, &block, LockMode::X))
+        };
+        thread::sleep(Duration::from_millis(200));
+
+        assert!(matches!(
+            lt.lock(2, &block, LockMode::X),
+            Err(LockTableError::Deadlock(_))
+        ));
+        lt.unlock(2, &block);
+        th.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn test_fifo() {
+        let lt = Arc::new(LockTable::new());
+        let block = block("test_fifo_file", 1);
+        let lock = |txnum, mode| {
+            let lt = lt.clone();
+            let block = block.clone();
+            thread::spawn(move || lt.lock(txnum, &block, mode))
+        };
+
+        lt.lock(1, &block, LockMode::S).unwrap();
+        let writer = lock(2, LockMode::X);
+        thread::sleep(Duration::from_millis(200));
+
+        let reader = lock(3, LockMode::S);
+        thread::sleep(Duration::from_millis(200));
+        assert!(!writer.is_finished());
+        assert!(!reader.is_finished());
+
+        lt.lock(1, &block, LockMode::S).unwrap();
+
+        lt.unlock(1, &block);
+        writer.join().unwrap().unwrap();
+        thread::sleep(Duration::from_millis(200));
+        assert!(!reader.is_finished());
+
+        lt.unlock(2, &block);
+        reader.join().unwrap().unwrap();
+        lt.unlock(3, &block);
+    }
+
+    #[test]
+    fn test_overtaking() {
+        let lt = Arc::new(LockTable::new());
+        let block1 = block("test_overtaking_file", 1);
+        let block2 = block("test_overtaking_file", 2);
+        let lock = |txnum, block: &LockTarget, mode| {
+            let lt = lt.clone();
+            let block = block.clone();
+            thread::spawn(move || lt.lock(txnum, &block, mode))
+        };
+
+        lt.lock(1, &block1, LockMode::S).unwrap();
+        lt.lock(2, &block2, LockMode::X).unwrap();
+        lt.lock(3, &block1, LockMode::S).unwrap();
+        let upgrade = lock(3, &block1, LockMode::X);
+        thread::sleep(Duration::from_millis(200));
+        let reader = lock(2, &block1, LockMode::S);
+        thread::sleep(Duration::from_millis(200));
+        assert!(!reader.is_finished());
+
+        // NOTE: tx 2 goes ahead of tx 3 to avoid a deadlock
+        let th = lock(1, &block2, LockMode::S);
+        reader.join().unwrap().unwrap();
+        assert_eq!(lt.stats().aborts, 0);
+
+        lt.unlock_all(2);
+        th.join().unwrap().unwrap();
+        lt.unlock_all(1);
+        upgrade.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn test_slot_locks() {
+        let lt = Arc::new(LockTable::new());
+        let blk = BlockId::new("test_slot_locks_file", 1);
+        let block = LockTarget::Block(blk.clone());
+        let slot0 = LockTarget::Slot(blk.clone(), 0);
+        let slot1 = LockTarget::Slot(blk.clone(), 1);
+
+        lt.lock(1, &block, LockMode::IX).unwrap();
+        lt.lock(1, &slot0, LockMode::X).unwrap();
+        lt.lock(2, &block, LockMode::IX).unwrap();
+        lt.lock(2, &slot1, LockMode::X).unwrap();
+
+        let th = {
+            let lt = lt.clone();
+            let block = block.clone();
+            thread::spawn(move || lt.lock(3, &block, LockMode::X))
+        };
+        thread::sleep(Duration::from_millis(200));
+        assert!(!th.is_finished());
+
+        lt.unlock(1, &slot0);
+        lt.unlock(1, &block);
+        lt.unlock(2, &slot1);
+        lt.unlock(2, &block);
+        th.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn test_wait_die() {
+        let lt = Arc::new(LockTable::new().with_deadlock_policy(DeadlockPolicy::WaitDie));
+        let block1 = block("test_wait_die_file", 1);
+        let block2 = block("test_wait_die_file", 2);
+
+        lt.lock(1, &block1, LockMode::X).unwrap();
+        lt.lock(2, &block2, LockMode::X).unwrap();
+
+        let th = {
+            let lt = lt.clone();
+            let block2 = block2.clone();
+            thread::spawn(move || lt.lock(1, &block2, LockMode::S))
+        };
+        thread::sleep(Duration::from_millis(200));
+        assert!(!th.is_finished());
+
+        assert!(matches!(
+            lt.lock(2, &block1, LockMode::S),
+            Err(LockTableError::Died(b)) if b == block1
+        ));
+        let stats = lt.stats();
+        assert_eq!((stats.waits, stats.aborts), (0, 1));
+
+        lt.unlock(2, &block2);
+        th.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn test_timeout() {
+        let lt = LockTable::with_timeout(Duration::from_millis(100));
+        let block = block("test_timeout_file", 1);
+
+        lt.lock(1, &block, LockMode::X).unwrap();
+        assert!(matches!(
+            lt.lock(2, &block, LockMode::S),
+            Err(LockTableError::LockAborted(b)) if b == block
+        ));
+        let stats = lt.stats();
+        assert_eq!((stats.waits, stats.aborts), (1, 1));
+        assert!(stats.wait_time >= Duration::from_millis(100));
     }
 }
@@ -3,34 +3,45 @@
 // This software is released under the MIT License.
 // https://opensource.org/licenses/MIT
 
-use super::transaction::TxInner;
+use super::transaction::{self, Durability, Snapshot, TxInner, TxNumber};
 use crate::buffer_mgr::{Buffer, BufferError, BufferMgr};
 use crate::file::block_id::BlockId;
 use crate::file::page::{self, Page, PageError};
 use crate::log_mgr::{self, LogMgrError, LSN};
-use crate::{constants::I32_BYTE_SIZE, log_mgr::LogMgr};
+use crate::{
+    constants::{I32_BYTE_SIZE, I64_BYTE_SIZE},
+    log_mgr::LogMgr,
+};
 use std::sync::Arc;
-use std::{convert::TryInto, fmt::Display};
+use std::{
+    collections::{HashMap, HashSet},
+    convert::TryInto,
+    fmt::Display,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 use thiserror::Error;
 
 #[derive(Debug, Error)]
 pub enum RecoveryError {
-    #[error("{0:?}")]
+    #[error("{0}")]
     IO(#[from] std::io::Error),
 
-    #[error("{0:?}")]
+    #[error("{0}")]
     PageError(#[from] PageError),
 
-    #[error("{0:?}")]
+    #[error("{0}")]
     LogMgrError(#[from] LogMgrError),
 
-    #[error("{0:?}")]
+    #[error("{0}")]
     BufferMgrError(#[from] BufferError),
 
-    #[error("failed to undo: {0:?}")]
+    #[error("failed to undo: {0}")]
     UndoError(String),
 
-    #[error("unknown op: {0:?}")]
+    #[error("failed to redo: {0}")]
+    RedoError(String),
+
+    #[error("unknown op: {0}")]
     UnknownOp(i32),
 }
 
@@ -44,6 +55,12 @@ enum Op {
     Rollback,
     SetInt,
     SetString,
+    Append,
+    SetLong,
+    SetDouble,
+    SetBytes,
+    Rename,
+    SetRawBytes,
 }
 
 impl Op {
@@ -55,6 +72,12 @@ impl Op {
             3 => Some(Op::Rollback),
             4 => Some(Op::SetInt),
             5 => Some(Op::SetString),
+            6 => Some(Op::Append),
+            7 => Some(Op::SetLong),
+            8 => Some(Op::SetDouble),
+            9 => Some(Op::SetBytes),
+            10 => Some(Op::Rename),
+            11 => Some(Op::SetRawBytes),
             _ => None,
         }
     }
@@ -66,6 +89,12 @@ impl Op {
             Op::Rollback => 3,
             Op::SetInt => 4,
             Op::SetString => 5,
+            Op::Append => 6,
+            Op::SetLong => 7,
+            Op::SetDouble => 8,
+            Op::SetBytes => 9,
+            Op::Rename => 10,
+            Op::SetRawBytes => 11,
         }
     }
 }
@@ -74,6 +103,33 @@ trait LogRecord: Display {
     fn op(&self) -> Op;
     fn tx_number(&self) -> i32;
     fn undo<'t>(&self, tx: &'t mut TxInner) -> Result<()>;
+
+    fn redo(&self, _tx: &mut TxInner) -> Result<()> {
+        Ok(())
+    }
+
+    fn undo_page(&self, _blk: &BlockId, _page: &mut Page) -> Result<()> {
+        Ok(())
+    }
+
+    fn commit_time(&self) -> Option<SystemTime> {
+        None
+    }
+
+    fn block(&self) -> Option<&BlockId> {
+        None
+    }
+
+    fn lock_for_replay(&self, tx: &mut TxInner) -> transaction::Result<()> {
+        match self.block() {
+            Some(blk) => tx.xlock(blk),
+            None => Ok(()),
+        }
+    }
+
+    fn replay(&self, tx: &mut TxInner) -> Result<()> {
+        self.redo(tx)
+    }
 }
 
 fn create_log_record(mut bytes: Vec<u8>) -> Result<Box<dyn LogRecord>> {
@@ -86,6 +142,12 @@ fn create_log_record(mut bytes: Vec<u8>) -> Result<Box<dyn LogRecord>> {
         Some(Op::Rollback) => Ok(Box::new(RollbackRecord::new(&p)?)),
         Some(Op::SetInt) => Ok(Box::new(SetIntRecord::new(&mut p)?)),
         Some(Op::SetString) => Ok(Box::new(SetStringRecord::new(&mut p)?)),
+        Some(Op::Append) => Ok(Box::new(AppendRecord::new(&mut p)?)),
+        Some(Op::SetLong) => Ok(Box::new(SetLongRecord::new(&mut p)?)),
+        Some(Op::SetDouble) => Ok(Box::new(SetDoubleRecord::new(&mut p)?)),
+        Some(Op::SetBytes) => Ok(Box::new(SetBytesRecord::new(&mut p)?)),
+        Some(Op::Rename) => Ok(Box::new(RenameRecord::new(&mut p)?)),
+        Some(Op::SetRawBytes) => Ok(Box::new(SetRawBytesRecord::new(&mut p)?)),
         _ => Err(RecoveryError::UnknownOp(op)),
     }
 }
@@ -163,21 +225,33 @@ impl Display for StartRecord {
 
 struct CommitRecord {
     txnum: i32,
+    // NOTE: older records have none
+    time: Option<SystemTime>,
 }
 impl CommitRecord {
     pub fn new(p: &Page) -> page::Result<Self> {
-        let tpos = I32_BYTE_SIZE.try_into().unwrap();
-        let txnum = p.get_i32(tpos)?;
-        Ok(Self { txnum })
+        let i32_bytes: usize = I32_BYTE_SIZE.try_into().unwrap();
+        let txnum = p.get_i32(i32_bytes)?;
+        let time = p
+            .get_i64(2 * i32_bytes)
+            .ok()
+            .map(|millis| UNIX_EPOCH + Duration::from_millis(millis as u64));
+        Ok(Self { txnum, time })
     }
 
     pub fn write_to_log(lm: Arc<LogMgr>, txnum: i32) -> log_mgr::Result<i64> {
         let i32_bytes: usize = I32_BYTE_SIZE.try_into().unwrap();
-        let mut rec = vec![0u8; 2 * i32_bytes];
+        let i64_bytes: usize = I64_BYTE_SIZE.try_into().unwrap();
+        let millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as i64;
+        let mut rec = vec![0u8; 2 * i32_bytes + i64_bytes];
         {
             let mut p = Page::for_log(&mut rec);
             p.set_i32(0, Op::Commit.to_i32())?;
             p.set_i32(i32_bytes, txnum)?;
+            p.set_i64(2 * i32_bytes, millis)?;
         }
         lm.apppend(&rec)
     }
@@ -191,6 +265,10 @@ impl LogRecord for CommitRecord {
         self.txnum
     }
 
+    fn commit_time(&self) -> Option<SystemTime> {
+        self.time
+    }
+
     fn undo<'t>(&self, _tx: &'t mut TxInner) -> Result<()> {
         Ok(())
     }
@@ -245,7 +323,8 @@ struct SetIntRecord {
     txnum: i32,
     offset: usize,
     block: BlockId,
-    value: i32,
+    old_value: i32,
+    new_value: i32,
 }
 impl SetIntRecord {
     pub fn new(p: &mut Page) -> page::Result<Self> {
@@ -261,13 +340,16 @@ impl SetIntRecord {
         let opos = bpos + i32_bytes;
         let offset = p.get_i32(opos)?.try_into().unwrap();
         let vpos = opos + i32_bytes;
-        let value = p.get_i32(vpos)?;
+        let old_value = p.get_i32(vpos)?;
+        let nvpos = vpos + i32_bytes;
+        let new_value = p.get_i32(nvpos)?;
 
         Ok(Self {
             txnum,
             offset,
             block,
-            value,
+            old_value,
+            new_value,
         })
     }
 
@@ -276,7 +358,8 @@ impl SetIntRecord {
         txnum: i32,
         blk: &BlockId,
         offset: usize,
-        value: i32,
+        old_value: i32,
+        new_value: i32,
     ) -> log_mgr::Result<i64> {
         let i32_bytes = I32_BYTE_SIZE.try_into().unwrap();
         let tpos = i32_bytes;
@@ -284,8 +367,9 @@ impl SetIntRecord {
         let bpos = fpos + Page::max_length(blk.filename().len());
         let opos = bpos + i32_bytes;
         let vpos = opos + i32_bytes;
+        let nvpos = vpos + i32_bytes;
 
-        let mut rec = vec![0u8; vpos + i32_bytes];
+        let mut rec = vec![0u8; nvpos + i32_bytes];
         {
             let mut p = Page::for_log(&mut rec);
             p.set_i32(0, Op::SetInt.to_i32())?;
@@ -293,7 +377,8 @@ impl SetIntRecord {
             p.set_string(fpos, blk.filename())?;
             p.set_i32(bpos, blk.number().try_into().unwrap())?;
             p.set_i32(opos, offset.try_into().unwrap())?;
-            p.set_i32(vpos, value)?;
+            p.set_i32(vpos, old_value)?;
+            p.set_i32(nvpos, new_value)?;
         }
         lm.apppend(&rec)
     }
@@ -307,23 +392,45 @@ impl LogRecord for SetIntRecord {
         self.txnum
     }
 
+    fn block(&self) -> Option<&BlockId> {
+        Some(&self.block)
+    }
+
     fn undo<'t>(&self, tx: &'t mut TxInner) -> Result<()> {
         if let Err(e) = tx.pin(&self.block) {
             return Err(RecoveryError::UndoError(e.to_string()));
         }
-        if let Err(e) = tx.set_i32_for_recovery(&self.block, self.offset, self.value) {
+        if let Err(e) = tx.set_i32_for_recovery(&self.block, self.offset, self.old_value) {
             return Err(RecoveryError::UndoError(e.to_string()));
         }
         tx.unpin(&self.block);
         Ok(())
     }
+
+    fn redo(&self, tx: &mut TxInner) -> Result<()> {
+        if let Err(e) = tx.pin(&self.block) {
+            return Err(RecoveryError::RedoError(e.to_string()));
+        }
+        if let Err(e) = tx.set_i32_for_recovery(&self.block, self.offset, self.new_value) {
+            return Err(RecoveryError::RedoError(e.to_string()));
+        }
+        tx.unpin(&self.block);
+        Ok(())
+    }
+
+    fn undo_page(&self, blk: &BlockId, page: &mut Page) -> Result<()> {
+        if self.block == *blk {
+            page.set_i32(self.offset, self.old_value)?;
+        }
+        Ok(())
+    }
 }
 impl Display for SetIntRecord {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "<SETINT {} {} {} {}>",
-            self.txnum, self.block, self.offset, self.value
+            "<SETINT {} {} {} {} {}>",
+            self.txnum, self.block, self.offset, self.old_value, self.new_value
         )
     }
 }
@@ -332,7 +439,8 @@ struct SetStringRecord {
     txnum: i32,
     offset: usize,
     block: BlockId,
-    value: String,
+    old_value: String,
+    new_value: String,
 }
 
 impl SetStringRecord {
@@ -354,13 +462,17 @@ impl SetStringRecord {
         let offset: usize = p.get_i32(opos)?.try_into().unwrap();
 
         let vpos = opos + i32_bytes;
-        let value = p.get_string(vpos)?;
+        let old_value = p.get_string(vpos)?;
+
+        let nvpos = vpos + Page::max_length(old_value.len());
+        let new_value = p.get_string(nvpos)?;
 
         Ok(Self {
             txnum,
             offset,
             block,
-            value,
+            old_value,
+            new_value,
         })
     }
 
@@ -369,7 +481,8 @@ impl SetStringRecord {
         txnum: i32,
         blk: &BlockId,
         offset: usize,
-        val: String,
+        old_value: String,
+        new_value: &str,
     ) -> log_mgr::Result<i64> {
         let i32_bytes: usize = I32_BYTE_SIZE.try_into().unwrap();
         let tpos = i32_bytes;
@@ -377,7 +490,8 @@ impl SetStringRecord {
         let bpos = fpos + Page::max_length(blk.filename().len());
         let opos = bpos + i32_bytes;
         let vpos = opos + i32_bytes;
-        let reclen = vpos + Page::max_length(val.len());
+        let nvpos = vpos + Page::max_length(old_value.len());
+        let reclen = nvpos + Page::max_length(new_value.len());
 
         let mut rec = vec![0u8; reclen];
         {
@@ -387,7 +501,8 @@ impl SetStringRecord {
             p.set_string(fpos, blk.filename())?;
             p.set_i32(bpos, blk.number().try_into().unwrap())?;
             p.set_i32(opos, offset.try_into().unwrap())?;
-            p.set_string(vpos, val.as_str())?;
+            p.set_string(vpos, old_value.as_str())?;
+            p.set_string(nvpos, new_value)?;
         }
         lm.apppend(&rec)
     }
@@ -402,234 +517,1300 @@ impl LogRecord for SetStringRecord {
         self.txnum
     }
 
+    fn block(&self) -> Option<&BlockId> {
+        Some(&self.block)
+    }
+
     fn undo<'t>(&self, tx: &'t mut TxInner) -> Result<()> {
         if let Err(e) = tx.pin(&self.block) {
             return Err(RecoveryError::UndoError(e.to_string()));
         }
-        if let Err(e) = tx.set_string_for_recovery(&self.block, self.offset, &self.value) {
+        if let Err(e) = tx.set_string_for_recovery(&self.block, self.offset, &self.old_value) {
             return Err(RecoveryError::UndoError(e.to_string()));
         }
         tx.unpin(&self.block);
         Ok(())
     }
+
+    fn redo(&self, tx: &mut TxInner) -> Result<()> {
+        if let Err(e) = tx.pin(&self.block) {
+            return Err(RecoveryError::RedoError(e.to_string()));
+        }
+        if let Err(e) = tx.set_string_for_recovery(&self.block, self.offset, &self.new_value) {
+            return Err(RecoveryError::RedoError(e.to_string()));
+        }
+        tx.unpin(&self.block);
+        Ok(())
+    }
+
+    fn undo_page(&self, blk: &BlockId, page: &mut Page) -> Result<()> {
+        if self.block == *blk {
+            page.set_string(self.offset, &self.old_value)?;
+        }
+        Ok(())
+    }
 }
 
 impl Display for SetStringRecord {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "<SETSTRING {} {} {} {}>",
-            self.txnum, self.block, self.offset, self.value
+            "<SETSTRING {} {} {} {} {}>",
+            self.txnum, self.block, self.offset, self.old_value, self.new_value
         )
     }
 }
 
-pub struct RecoveryMgr<'lm, 'bm> {
-    lm: Arc<LogMgr<'lm>>,
-    bm: Arc<BufferMgr<'bm, 'lm>>,
+struct SetLongRecord {
     txnum: i32,
+    offset: usize,
+    block: BlockId,
+    old_value: i64,
+    new_value: i64,
 }
 
-impl<'lm, 'bm> RecoveryMgr<'lm, 'bm> {
-    pub fn new(txnum: i32, lm: Arc<LogMgr<'lm>>, bm: Arc<BufferMgr<'bm, 'lm>>) -> Self {
-        StartRecord::write_to_log(lm.clone(), txnum).unwrap();
-        Self { lm, bm, txnum }
-    }
+impl SetLongRecord {
+    pub fn new(p: &mut Page) -> page::Result<Self> {
+        let i32_bytes: usize = I32_BYTE_SIZE.try_into().unwrap();
 
-    pub fn commit(&mut self) -> Result<()> {
-        self.bm.flush_all(self.txnum)?;
-        let lsn = CommitRecord::write_to_log(self.lm.clone(), self.txnum)?;
-        self.lm.flush(lsn)?;
-        Ok(())
+        let tpos = i32_bytes;
+        let txnum = p.get_i32(tpos)?;
+
+        let fpos = tpos + i32_bytes;
+        let filename = p.get_string(fpos)?;
+
+        let bpos = fpos + Page::max_length(filename.len());
+        let blknum = p.get_i32(bpos)?;
+        let block = BlockId::new(&filename, blknum.into());
+
+        let opos = bpos + i32_bytes;
+        let offset: usize = p.get_i32(opos)?.try_into().unwrap();
+
+        let vpos = opos + i32_bytes;
+        let old_value = p.get_i64(vpos)?;
+
+        let nvpos = vpos + usize::try_from(I64_BYTE_SIZE).unwrap();
+        let new_value = p.get_i64(nvpos)?;
+
+        Ok(Self {
+            txnum,
+            offset,
+            block,
+            old_value,
+            new_value,
+        })
     }
 
-    pub(crate) fn rollback<'tx, 'lt>(&self, tx: &'tx mut TxInner<'lm, 'bm>) -> Result<()> {
-        self.do_rollback(tx)?;
-        self.bm.flush_all(self.txnum)?;
-        let lsn = RollbackRecord::write_to_log(self.lm.clone(), self.txnum)?;
-        self.lm.flush(lsn)?;
-        Ok(())
+    pub fn write_to_log(
+        lm: Arc<LogMgr>,
+        txnum: i32,
+        blk: &BlockId,
+        offset: usize,
+        old_value: i64,
+        new_value: i64,
+    ) -> log_mgr::Result<i64> {
+        let i32_bytes: usize = I32_BYTE_SIZE.try_into().unwrap();
+        let i64_bytes: usize = I64_BYTE_SIZE.try_into().unwrap();
+        let tpos = i32_bytes;
+        let fpos = tpos + i32_bytes;
+        let bpos = fpos + Page::max_length(blk.filename().len());
+        let opos = bpos + i32_bytes;
+        let vpos = opos + i32_bytes;
+        let nvpos = vpos + i64_bytes;
+
+        let mut rec = vec![0u8; nvpos + i64_bytes];
+        {
+            let mut p = Page::for_log(&mut rec);
+            p.set_i32(0, Op::SetLong.to_i32())?;
+            p.set_i32(tpos, txnum)?;
+            p.set_string(fpos, blk.filename())?;
+            p.set_i32(bpos, blk.number().try_into().unwrap())?;
+            p.set_i32(opos, offset.try_into().unwrap())?;
+            p.set_i64(vpos, old_value)?;
+            p.set_i64(nvpos, new_value)?;
+        }
+        lm.apppend(&rec)
     }
+}
 
-    pub(crate) fn recover<'tx, 'lt>(&self, tx: &'tx mut TxInner<'lm, 'bm>) -> Result<()> {
-        self.do_recover(tx)?;
-        self.bm.flush_all(self.txnum)?;
-        let lsn = CheckpointRecord::write_to_log(self.lm.clone())?;
-        self.lm.flush(lsn)?;
-        Ok(())
+impl LogRecord for SetLongRecord {
+    fn op(&self) -> Op {
+        Op::SetLong
     }
 
-    pub fn set_i32(&mut self, buff: &mut Buffer, offset: usize, _newval: i32) -> Result<LSN> {
-        let oldval = buff.contents_as_mut().get_i32(offset)?;
-        let blk = buff.block().as_ref().unwrap();
-        let lsn = SetIntRecord::write_to_log(self.lm.clone(), self.txnum, blk, offset, oldval)?;
-        Ok(lsn)
+    fn tx_number(&self) -> i32 {
+        self.txnum
     }
 
-    pub fn set_string(&self, buff: &mut Buffer, offset: usize, _newval: &str) -> Result<LSN> {
-        let oldval = buff.contents_as_mut().get_string(offset)?;
-        let blk = buff.block().as_ref().unwrap();
-        let lsn = SetStringRecord::write_to_log(self.lm.clone(), self.txnum, blk, offset, oldval)?;
-        Ok(lsn)
+    fn block(&self) -> Option<&BlockId> {
+        Some(&self.block)
     }
 
-    fn do_rollback<'tx, 'lt>(&self, tx: &'tx mut TxInner<'lm, 'bm>) -> Result<()> {
-        let mut iter = self.lm.reverse_iter()?;
-        while iter.has_next() {
-            let bytes = iter.next().unwrap();
-            let rec = create_log_record(bytes)?;
-            if rec.tx_number() == self.txnum {
-                if rec.op() == Op::Start {
-                    break;
-                }
-                rec.undo(tx)?;
-            }
+    fn undo(&self, tx: &mut TxInner) -> Result<()> {
+        if let Err(e) = tx.pin(&self.block) {
+            return Err(RecoveryError::UndoError(e.to_string()));
         }
+        if let Err(e) = tx.set_i64_for_recovery(&self.block, self.offset, self.old_value) {
+            return Err(RecoveryError::UndoError(e.to_string()));
+        }
+        tx.unpin(&self.block);
         Ok(())
     }
 
-    fn do_recover<'tx, 'lt>(&self, tx: &'tx mut TxInner<'lm, 'bm>) -> Result<()> {
-        let mut finished_txs: Vec<i32> = Vec::new();
-        let mut iter = self.lm.reverse_iter()?;
-        while iter.has_next() {
-            let bytes = iter.next().unwrap();
-            let rec = create_log_record(bytes)?;
-            if rec.op() == Op::Checkpoint {
-                break;
-            }
-            if rec.op() == Op::Commit || rec.op() == Op::Rollback {
-                finished_txs.push(rec.tx_number());
-            } else if !finished_txs.contains(&rec.tx_number()) {
-                rec.undo(tx)?;
-            }
+    fn redo(&self, tx: &mut TxInner) -> Result<()> {
+        if let Err(e) = tx.pin(&self.block) {
+            return Err(RecoveryError::RedoError(e.to_string()));
+        }
+        if let Err(e) = tx.set_i64_for_recovery(&self.block, self.offset, self.new_value) {
+            return Err(RecoveryError::RedoError(e.to_string()));
         }
+        tx.unpin(&self.block);
         Ok(())
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::{file::file_mgr::FileMgr, server::simple_db::SimpleDB};
-    use std::path::Path;
-    use tempfile::tempdir;
-
-    struct Context<'lm, 'bm> {
-        db: SimpleDB<'lm, 'bm>,
-        fm: Arc<FileMgr>,
-        bm: Arc<BufferMgr<'bm, 'lm>>,
-        block0: BlockId,
-        block1: BlockId,
-    }
-    impl Context<'_, '_> {
-        pub fn new(dir: &Path) -> Self {
-            let db = SimpleDB::new_for_test(dir, "test_recovery_mgr.log");
-            let fm = db.file_mgr();
-            let bm = db.buffer_mgr();
-            Context {
-                db,
-                fm: fm.clone(),
-                bm,
-                block0: BlockId::new("test_recovery_mgr_file", 0),
-                block1: BlockId::new("test_recovery_mgr_file", 1),
-            }
+    fn undo_page(&self, blk: &BlockId, page: &mut Page) -> Result<()> {
+        if self.block == *blk {
+            page.set_i64(self.offset, self.old_value)?;
         }
+        Ok(())
     }
+}
 
-    #[test]
-    fn test_recovery_mgr() {
-        let dir = tempdir().unwrap();
-        {
-            let mut ctx = Context::new(dir.path());
-            test_initialize(&mut ctx);
-            test_modify(&ctx);
-        }
-        {
-            let mut ctx = Context::new(dir.path());
-            test_recover(&mut ctx);
-        }
-        dir.close().unwrap();
+impl Display for SetLongRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "<SETLONG {} {} {} {} {}>",
+            self.txnum, self.block, self.offset, self.old_value, self.new_value
+        )
     }
+}
 
-    fn test_initialize(ctx: &mut Context) {
-        let tx1 = ctx.db.new_tx();
-        let tx2 = ctx.db.new_tx();
-        tx1.borrow_mut().pin(&ctx.block0).unwrap();
-        tx2.borrow_mut().pin(&ctx.block1).unwrap();
+struct SetDoubleRecord {
+    txnum: i32,
+    offset: usize,
+    block: BlockId,
+    old_value: f64,
+    new_value: f64,
+}
 
-        let mut pos = 0;
-        for _i in 0..6 {
-            tx1.borrow_mut()
-                .set_i32(&ctx.block0, pos, pos as i32, false)
-                .unwrap();
-            tx2.borrow_mut()
-                .set_i32(&ctx.block1, pos, pos as i32, false)
-                .unwrap();
-            pos += I32_BYTE_SIZE as usize;
-        }
+impl SetDoubleRecord {
+    pub fn new(p: &mut Page) -> page::Result<Self> {
+        let i32_bytes: usize = I32_BYTE_SIZE.try_into().unwrap();
 
-        tx1.borrow_mut()
-            .set_string(&ctx.block0, 30, "abc", false)
-            .unwrap();
-        tx2.borrow_mut()
-            .set_string(&ctx.block1, 30, "def", false)
-            .unwrap();
-        tx1.borrow_mut().commit().unwrap();
-        tx2.borrow_mut().commit().unwrap();
+        let tpos = i32_bytes;
+        let txnum = p.get_i32(tpos)?;
 
-        assert_fm_values(
-            &ctx,
-            [[0, 4, 8, 12, 16, 20], [0, 4, 8, 12, 16, 20]],
-            ["abc", "def"],
-        );
-    }
+        let fpos = tpos + i32_bytes;
+        let filename = p.get_string(fpos)?;
 
-    fn test_modify(ctx: &Context) {
-        let tx3 = ctx.db.new_tx();
-        let tx4 = ctx.db.new_tx();
+        let bpos = fpos + Page::max_length(filename.len());
+        let blknum = p.get_i32(bpos)?;
+        let block = BlockId::new(&filename, blknum.into());
 
-        tx3.borrow_mut().pin(&ctx.block0).unwrap();
-        tx4.borrow_mut().pin(&ctx.block1).unwrap();
+        let opos = bpos + i32_bytes;
+        let offset: usize = p.get_i32(opos)?.try_into().unwrap();
 
-        let mut pos = 0;
-        for _i in 0..6 {
-            tx3.borrow_mut()
-                .set_i32(&ctx.block0, pos, pos as i32 + 100, true)
-                .unwrap();
-            tx4.borrow_mut()
-                .set_i32(&ctx.block1, pos, pos as i32 + 100, true)
-                .unwrap();
-            pos += I32_BYTE_SIZE as usize;
-        }
-        tx3.borrow_mut()
-            .set_string(&ctx.block0, 30, "uvw", true)
-            .unwrap();
-        tx4.borrow_mut()
-            .set_string(&ctx.block1, 30, "xyz", true)
-            .unwrap();
-        ctx.bm.flush_all(tx3.borrow().txnum()).unwrap();
-        ctx.bm.flush_all(tx4.borrow().txnum()).unwrap();
-        assert_fm_values(
-            &ctx,
-            [
-                [100, 104, 108, 112, 116, 120],
-                [100, 104, 108, 112, 116, 120],
-            ],
-            ["uvw", "xyz"],
-        );
+        let vpos = opos + i32_bytes;
+        let old_value = p.get_f64(vpos)?;
 
-        tx3.borrow_mut().rollback().unwrap();
-        assert_fm_values(
-            &ctx,
-            [[0, 4, 8, 12, 16, 20], [100, 104, 108, 112, 116, 120]],
+        let nvpos = vpos + usize::try_from(I64_BYTE_SIZE).unwrap();
+        let new_value = p.get_f64(nvpos)?;
+
+        Ok(Self {
+            txnum,
+            offset,
+            block,
+            old_value,
+            new_value,
+        })
+    }
+
+    pub fn write_to_log(
+        lm: Arc<LogMgr>,
+        txnum: i32,
+        blk: &BlockId,
+        offset: usize,
+        old_value: f64,
+        new_value: f64,
+    ) -> log_mgr::Result<i64> {
+        let i32_bytes: usize = I32_BYTE_SIZE.try_into().unwrap();
+        let i64_bytes: usize = I64_BYTE_SIZE.try_into().unwrap();
+        let tpos = i32_bytes;
+        let fpos = tpos + i32_bytes;
+        let bpos = fpos + Page::max_length(blk.filename().len());
+        let opos = bpos + i32_bytes;
+        let vpos = opos + i32_bytes;
+        let nvpos = vpos + i64_bytes;
+
+        let mut rec = vec![0u8; nvpos + i64_bytes];
+        {
+            let mut p = Page::for_log(&mut rec);
+            p.set_i32(0, Op::SetDouble.to_i32())?;
+            p.set_i32(tpos, txnum)?;
+            p.set_string(fpos, blk.filename())?;
+            p.set_i32(bpos, blk.number().try_into().unwrap())?;
+            p.set_i32(opos, offset.try_into().unwrap())?;
+            p.set_f64(vpos, old_value)?;
+            p.set_f64(nvpos, new_value)?;
+        }
+        lm.apppend(&rec)
+    }
+}
+
+impl LogRecord for SetDoubleRecord {
+    fn op(&self) -> Op {
+        Op::SetDouble
+    }
+
+    fn tx_number(&self) -> i32 {
+        self.txnum
+    }
+
+    fn block(&self) -> Option<&BlockId> {
+        Some(&self.block)
+    }
+
+    fn undo(&self, tx: &mut TxInner) -> Result<()> {
+        if let Err(e) = tx.pin(&self.block) {
+            return Err(RecoveryError::UndoError(e.to_string()));
+        }
+        if let Err(e) = tx.set_f64_for_recovery(&self.block, self.offset, self.old_value) {
+            return Err(RecoveryError::UndoError(e.to_string()));
+        }
+        tx.unpin(&self.block);
+        Ok(())
+    }
+
+    fn redo(&self, tx: &mut TxInner) -> Result<()> {
+        if let Err(e) = tx.pin(&self.block) {
+            return Err(RecoveryError::RedoError(e.to_string()));
+        }
+        if let Err(e) = tx.set_f64_for_recovery(&self.block, self.offset, self.new_value) {
+            return Err(RecoveryError::RedoError(e.to_string()));
+        }
+        tx.unpin(&self.block);
+        Ok(())
+    }
+
+    fn undo_page(&self, blk: &BlockId, page: &mut Page) -> Result<()> {
+        if self.block == *blk {
+            page.set_f64(self.offset, self.old_value)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for SetDoubleRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "<SETDOUBLE {} {} {} {} {}>",
+            self.txnum, self.block, self.offset, self.old_value, self.new_value
+        )
+    }
+}
+
+struct SetBytesRecord {
+    txnum: i32,
+    offset: usize,
+    block: BlockId,
+    old_value: Vec<u8>,
+    new_value: Vec<u8>,
+}
+
+impl SetBytesRecord {
+    pub fn new(p: &mut Page) -> page::Result<Self> {
+        let i32_bytes: usize = I32_BYTE_SIZE.try_into().unwrap();
+
+        let tpos = i32_bytes;
+        let txnum = p.get_i32(tpos)?;
+
+        let fpos = tpos + i32_bytes;
+        let filename = p.get_string(fpos)?;
+
+        let bpos = fpos + Page::max_length(filename.len());
+        let blknum = p.get_i32(bpos)?;
+        let block = BlockId::new(&filename, blknum.into());
+
+        let opos = bpos + i32_bytes;
+        let offset: usize = p.get_i32(opos)?.try_into().unwrap();
+
+        let vpos = opos + i32_bytes;
+        let old_value = p.get_bytes(vpos)?;
+
+        let nvpos = vpos + i32_bytes + old_value.len();
+        let new_value = p.get_bytes(nvpos)?;
+
+        Ok(Self {
+            txnum,
+            offset,
+            block,
+            old_value,
+            new_value,
+        })
+    }
+
+    pub fn write_to_log(
+        lm: Arc<LogMgr>,
+        txnum: i32,
+        blk: &BlockId,
+        offset: usize,
+        old_value: Vec<u8>,
+        new_value: &[u8],
+    ) -> log_mgr::Result<i64> {
+        let i32_bytes: usize = I32_BYTE_SIZE.try_into().unwrap();
+        let tpos = i32_bytes;
+        let fpos = tpos + i32_bytes;
+        let bpos = fpos + Page::max_length(blk.filename().len());
+        let opos = bpos + i32_bytes;
+        let vpos = opos + i32_bytes;
+        let nvpos = vpos + i32_bytes + old_value.len();
+        let reclen = nvpos + i32_bytes + new_value.len();
+
+        let mut rec = vec![0u8; reclen];
+        {
+            let mut p = Page::for_log(&mut rec);
+            p.set_i32(0, Op::SetBytes.to_i32())?;
+            p.set_i32(tpos, txnum)?;
+            p.set_string(fpos, blk.filename())?;
+            p.set_i32(bpos, blk.number().try_into().unwrap())?;
+            p.set_i32(opos, offset.try_into().unwrap())?;
+            p.set_bytes(vpos, &old_value)?;
+            p.set_bytes(nvpos, new_value)?;
+        }
+        lm.apppend(&rec)
+    }
+}
+
+impl LogRecord for SetBytesRecord {
+    fn op(&self) -> Op {
+        Op::SetBytes
+    }
+
+    fn tx_number(&self) -> i32 {
+        self.txnum
+    }
+
+    fn block(&self) -> Option<&BlockId> {
+        Some(&self.block)
+    }
+
+    fn undo(&self, tx: &mut TxInner) -> Result<()> {
+        if let Err(e) = tx.pin(&self.block) {
+            return Err(RecoveryError::UndoError(e.to_string()));
+        }
+        if let Err(e) = tx.set_bytes_for_recovery(&self.block, self.offset, &self.old_value) {
+            return Err(RecoveryError::UndoError(e.to_string()));
+        }
+        tx.unpin(&self.block);
+        Ok(())
+    }
+
+    fn redo(&self, tx: &mut TxInner) -> Result<()> {
+        if let Err(e) = tx.pin(&self.block) {
+            return Err(RecoveryError::RedoError(e.to_string()));
+        }
+        if let Err(e) = tx.set_bytes_for_recovery(&self.block, self.offset, &self.new_value) {
+            return Err(RecoveryError::RedoError(e.to_string()));
+        }
+        tx.unpin(&self.block);
+        Ok(())
+    }
+
+    fn undo_page(&self, blk: &BlockId, page: &mut Page) -> Result<()> {
+        if self.block == *blk {
+            page.set_bytes(self.offset, &self.old_value)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for SetBytesRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "<SETBYTES {} {} {} {:?} {:?}>",
+            self.txnum, self.block, self.offset, self.old_value, self.new_value
+        )
+    }
+}
+
+struct SetRawBytesRecord {
+    txnum: i32,
+    offset: usize,
+    block: BlockId,
+    old_value: Vec<u8>,
+    new_value: Vec<u8>,
+}
+
+impl SetRawBytesRecord {
+    pub fn new(p: &mut Page) -> page::Result<Self> {
+        let i32_bytes: usize = I32_BYTE_SIZE.try_into().unwrap();
+
+        let tpos = i32_bytes;
+        let txnum = p.get_i32(tpos)?;
+
+        let fpos = tpos + i32_bytes;
+        let filename = p.get_string(fpos)?;
+
+        let bpos = fpos + Page::max_length(filename.len());
+        let blknum = p.get_i32(bpos)?;
+        let block = BlockId::new(&filename, blknum.into());
+
+        let opos = bpos + i32_bytes;
+        let offset: usize = p.get_i32(opos)?.try_into().unwrap();
+
+        let vpos = opos + i32_bytes;
+        let old_value = p.get_bytes(vpos)?;
+
+        let nvpos = vpos + i32_bytes + old_value.len();
+        let new_value = p.get_bytes(nvpos)?;
+
+        Ok(Self {
+            txnum,
+            offset,
+            block,
+            old_value,
+            new_value,
+        })
+    }
+
+    pub fn max_value_len(lm: &LogMgr, blk: &BlockId) -> usize {
+        let i32_bytes: usize = I32_BYTE_SIZE.try_into().unwrap();
+        let header = 6 * i32_bytes + Page::max_length(blk.filename().len());
+        lm.max_record_len().saturating_sub(header) / 2
+    }
+
+    pub fn write_to_log(
+        lm: Arc<LogMgr>,
+        txnum: i32,
+        blk: &BlockId,
+        offset: usize,
+        old_value: &[u8],
+        new_value: &[u8],
+    ) -> log_mgr::Result<i64> {
+        let i32_bytes: usize = I32_BYTE_SIZE.try_into().unwrap();
+        let tpos = i32_bytes;
+        let fpos = tpos + i32_bytes;
+        let bpos = fpos + Page::max_length(blk.filename().len());
+        let opos = bpos + i32_bytes;
+        let vpos = opos + i32_bytes;
+        let nvpos = vpos + i32_bytes + old_value.len();
+        let reclen = nvpos + i32_bytes + new_value.len();
+
+        let mut rec = vec![0u8; reclen];
+        {
+            let mut p = Page::for_log(&mut rec);
+            p.set_i32(0, Op::SetRawBytes.to_i32())?;
+            p.set_i32(tpos, txnum)?;
+            p.set_string(fpos, blk.filename())?;
+            p.set_i32(bpos, blk.number().try_into().unwrap())?;
+            p.set_i32(opos, offset.try_into().unwrap())?;
+            p.set_bytes(vpos, old_value)?;
+            p.set_bytes(nvpos, new_value)?;
+        }
+        lm.apppend(&rec)
+    }
+}
+
+impl LogRecord for SetRawBytesRecord {
+    fn op(&self) -> Op {
+        Op::SetRawBytes
+    }
+
+    fn tx_number(&self) -> i32 {
+        self.txnum
+    }
+
+    fn block(&self) -> Option<&BlockId> {
+        Some(&self.block)
+    }
+
+    fn undo(&self, tx: &mut TxInner) -> Result<()> {
+        if let Err(e) = tx.pin(&self.block) {
+            return Err(RecoveryError::UndoError(e.to_string()));
+        }
+        if let Err(e) = tx.set_raw_bytes_for_recovery(&self.block, self.offset, &self.old_value) {
+            return Err(RecoveryError::UndoError(e.to_string()));
+        }
+        tx.unpin(&self.block);
+        Ok(())
+    }
+
+    fn redo(&self, tx: &mut TxInner) -> Result<()> {
+        if let Err(e) = tx.pin(&self.block) {
+            return Err(RecoveryError::RedoError(e.to_string()));
+        }
+        if let Err(e) = tx.set_raw_bytes_for_recovery(&self.block, self.offset, &self.new_value) {
+            return Err(RecoveryError::RedoError(e.to_string()));
+        }
+        tx.unpin(&self.block);
+        Ok(())
+    }
+
+    fn undo_page(&self, blk: &BlockId, page: &mut Page) -> Result<()> {
+        if self.block == *blk {
+            page.set_raw_bytes(self.offset, &self.old_value)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for SetRawBytesRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "<SETRAWBYTES {} {} {} {:?} {:?}>",
+            self.txnum, self.block, self.offset, self.old_value, self.new_value
+        )
+    }
+}
+
+struct AppendRecord {
+    txnum: i32,
+    block: BlockId,
+}
+
+impl AppendRecord {
+    pub fn new(p: &mut Page) -> page::Result<Self> {
+        let i32_bytes: usize = I32_BYTE_SIZE.try_into().unwrap();
+
+        let tpos = i32_bytes;
+        let txnum = p.get_i32(tpos)?;
+
+        let fpos = tpos + i32_bytes;
+        let filename = p.get_string(fpos)?;
+
+        let bpos = fpos + Page::max_length(filename.len());
+        let blknum = p.get_i32(bpos)?;
+        let block = BlockId::new(&filename, i64::from(blknum));
+
+        Ok(Self { txnum, block })
+    }
+
+    pub fn write_to_log(lm: Arc<LogMgr>, txnum: i32, blk: &BlockId) -> log_mgr::Result<i64> {
+        let i32_bytes: usize = I32_BYTE_SIZE.try_into().unwrap();
+        let tpos = i32_bytes;
+        let fpos = tpos + i32_bytes;
+        let bpos = fpos + Page::max_length(blk.filename().len());
+
+        let mut rec = vec![0u8; bpos + i32_bytes];
+        {
+            let mut p = Page::for_log(&mut rec);
+            p.set_i32(0, Op::Append.to_i32())?;
+            p.set_i32(tpos, txnum)?;
+            p.set_string(fpos, blk.filename())?;
+            p.set_i32(bpos, blk.number().try_into().unwrap())?;
+        }
+        lm.apppend(&rec)
+    }
+}
+
+impl LogRecord for AppendRecord {
+    fn op(&self) -> Op {
+        Op::Append
+    }
+
+    fn tx_number(&self) -> i32 {
+        self.txnum
+    }
+
+    fn block(&self) -> Option<&BlockId> {
+        Some(&self.block)
+    }
+
+    // NOTE: no redo is needed because FileMgr::append extends the file immediately
+    fn undo(&self, tx: &mut TxInner) -> Result<()> {
+        if let Err(e) = tx.truncate_for_recovery(&self.block) {
+            return Err(RecoveryError::UndoError(e.to_string()));
+        }
+        Ok(())
+    }
+
+    fn lock_for_replay(&self, tx: &mut TxInner) -> transaction::Result<()> {
+        tx.lock_file(self.block.filename())
+    }
+
+    fn replay(&self, tx: &mut TxInner) -> Result<()> {
+        if let Err(e) = tx.extend_for_replay(&self.block) {
+            return Err(RecoveryError::RedoError(e.to_string()));
+        }
+        Ok(())
+    }
+}
+
+impl Display for AppendRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<APPEND {} {}>", self.txnum, self.block)
+    }
+}
+
+struct RenameRecord {
+    txnum: i32,
+    from: String,
+    to: String,
+}
+
+impl RenameRecord {
+    pub fn new(p: &mut Page) -> page::Result<Self> {
+        let i32_bytes: usize = I32_BYTE_SIZE.try_into().unwrap();
+
+        let tpos = i32_bytes;
+        let txnum = p.get_i32(tpos)?;
+
+        let fpos = tpos + i32_bytes;
+        let from = p.get_string(fpos)?;
+
+        let topos = fpos + Page::max_length(from.len());
+        let to = p.get_string(topos)?;
+
+        Ok(Self { txnum, from, to })
+    }
+
+    pub fn write_to_log(lm: Arc<LogMgr>, txnum: i32, from: &str, to: &str) -> log_mgr::Result<i64> {
+        let i32_bytes: usize = I32_BYTE_SIZE.try_into().unwrap();
+        let tpos = i32_bytes;
+        let fpos = tpos + i32_bytes;
+        let topos = fpos + Page::max_length(from.len());
+
+        let mut rec = vec![0u8; topos + Page::max_length(to.len())];
+        {
+            let mut p = Page::for_log(&mut rec);
+            p.set_i32(0, Op::Rename.to_i32())?;
+            p.set_i32(tpos, txnum)?;
+            p.set_string(fpos, from)?;
+            p.set_string(topos, to)?;
+        }
+        lm.apppend(&rec)
+    }
+}
+
+impl LogRecord for RenameRecord {
+    fn op(&self) -> Op {
+        Op::Rename
+    }
+
+    fn tx_number(&self) -> i32 {
+        self.txnum
+    }
+
+    fn undo(&self, tx: &mut TxInner) -> Result<()> {
+        if let Err(e) = tx.undo_rename(&self.from, &self.to) {
+            return Err(RecoveryError::UndoError(e.to_string()));
+        }
+        Ok(())
+    }
+
+    fn lock_for_replay(&self, tx: &mut TxInner) -> transaction::Result<()> {
+        tx.lock_file(&self.from)?;
+        tx.lock_file(&self.to)
+    }
+
+    // NOTE: earlier changes may be redone under the old name
+    fn redo(&self, tx: &mut TxInner) -> Result<()> {
+        if let Err(e) = tx.redo_rename(&self.from, &self.to) {
+            return Err(RecoveryError::RedoError(e.to_string()));
+        }
+        Ok(())
+    }
+}
+
+impl Display for RenameRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<RENAME {} {} {}>", self.txnum, self.from, self.to)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RestoreTarget {
+    Lsn(LSN),
+    Time(SystemTime),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecoveryReport {
+    pub checkpoint: LSN,
+    pub corrupted: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RestoreReport {
+    pub undone: usize,
+    pub corrupted: bool,
+}
+
+#[derive(Default)]
+pub struct LogReplayer {
+    // NOTE: txnum -> first LSN, records
+    pending: HashMap<i32, (LSN, Vec<Box<dyn LogRecord>>)>,
+}
+
+impl LogReplayer {
+    pub fn add(&mut self, lsn: LSN, bytes: Vec<u8>) -> Result<Option<ReplayedTx>> {
+        let rec = create_log_record(bytes)?;
+        let txnum = rec.tx_number();
+        match rec.op() {
+            Op::Checkpoint => {}
+            Op::Start => {
+                self.pending.insert(txnum, (lsn, Vec::new()));
+            }
+            Op::Commit => {
+                if let Some((_, records)) = self.pending.remove(&txnum) {
+                    return Ok(Some(ReplayedTx { txnum, records }));
+                }
+            }
+            Op::Rollback => {
+                self.pending.remove(&txnum);
+            }
+            _ => self
+                .pending
+                .entry(txnum)
+                .or_insert_with(|| (lsn, Vec::new()))
+                .1
+                .push(rec),
+        }
+        Ok(None)
+    }
+
+    pub fn oldest_pending_lsn(&self) -> Option<LSN> {
+        self.pending.values().map(|(lsn, _)| *lsn).min()
+    }
+}
+
+pub struct ReplayedTx {
+    txnum: i32,
+    records: Vec<Box<dyn LogRecord>>,
+}
+
+impl ReplayedTx {
+    pub fn txnum(&self) -> i32 {
+        self.txnum
+    }
+
+    pub(crate) fn lock(&self, tx: &mut TxInner) -> transaction::Result<()> {
+        for rec in &self.records {
+            rec.lock_for_replay(tx)?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn replay(&self, tx: &mut TxInner) -> Result<()> {
+        for rec in &self.records {
+            rec.replay(tx)?;
+        }
+        Ok(())
+    }
+}
+
+pub struct LogEntry {
+    lsn: Option<LSN>,
+    record: Box<dyn LogRecord>,
+}
+
+impl LogEntry {
+    pub fn lsn(&self) -> Option<LSN> {
+        self.lsn
+    }
+
+    pub fn tx_number(&self) -> Option<i32> {
+        match self.record.op() {
+            Op::Checkpoint => None,
+            _ => Some(self.record.tx_number()),
+        }
+    }
+}
+
+impl Display for LogEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.lsn {
+            Some(lsn) => write!(f, "{} {}", lsn, self.record),
+            None => write!(f, "- {}", self.record),
+        }
+    }
+}
+
+pub fn log_dump(lm: &LogMgr) -> Result<impl Iterator<Item = Result<LogEntry>>> {
+    let mut records = lm.reverse_iter()?.collect::<Vec<_>>();
+    records.reverse();
+    // NOTE: the records of the previous runs have no LSNs
+    let first_lsn = lm.latest_lsn() + 1 - records.len() as LSN;
+    Ok(records.into_iter().zip(first_lsn..).map(|(bytes, lsn)| {
+        Ok(LogEntry {
+            lsn: (lsn > 0).then_some(lsn),
+            record: create_log_record(bytes)?,
+        })
+    }))
+}
+
+#[derive(Default)]
+pub(crate) struct SnapshotUndo {
+    records: HashMap<BlockId, Vec<Vec<u8>>>,
+    collected: Option<LSN>,
+}
+
+pub struct RecoveryMgr {
+    lm: Arc<LogMgr>,
+    bm: Arc<BufferMgr>,
+    txnum: i32,
+}
+
+impl RecoveryMgr {
+    pub fn new(txnum: i32, lm: Arc<LogMgr>, bm: Arc<BufferMgr>) -> Self {
+        StartRecord::write_to_log(lm.clone(), txnum).unwrap();
+        Self { lm, bm, txnum }
+    }
+
+    pub fn write_checkpoint(lm: Arc<LogMgr>) -> Result<LSN> {
+        let lsn = CheckpointRecord::write_to_log(lm.clone())?;
+        lm.flush(lsn)?;
+        lm.mark_checkpoint();
+        Ok(lsn)
+    }
+
+    pub fn checkpoint_if_due(&self, tn: &TxNumber) -> Option<LSN> {
+        if !self.lm.checkpoint_due() {
+            return None;
+        }
+        let result = tn.when_idle(|| {
+            self.bm.flush_modified()?;
+            self.bm.sync()?;
+            RecoveryMgr::write_checkpoint(self.lm.clone())
+        });
+        match result {
+            Some(Ok(lsn)) => Some(lsn),
+            // NOTE: the transaction has ended, so only count it
+            Some(Err(_)) => {
+                self.lm.mark_failed_checkpoint();
+                None
+            }
+            None => None,
+        }
+    }
+
+    pub fn ends_with_checkpoint(lm: &LogMgr) -> Result<bool> {
+        match lm.reverse_iter()?.next() {
+            Some(bytes) => Ok(create_log_record(bytes)?.op() == Op::Checkpoint),
+            None => Ok(false),
+        }
+    }
+
+    pub fn new_read_only(txnum: i32, lm: Arc<LogMgr>, bm: Arc<BufferMgr>) -> Self {
+        Self { lm, bm, txnum }
+    }
+
+    pub fn commit(&mut self, durability: Durability) -> Result<()> {
+        // NOTE: recovery redoes the modified buffers
+        let lsn = CommitRecord::write_to_log(self.lm.clone(), self.txnum)?;
+        if durability == Durability::Strict {
+            self.lm.flush_for_commit(lsn)?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn rollback(&self, tx: &mut TxInner) -> Result<()> {
+        self.do_rollback(tx)?;
+        self.bm.flush_all(self.txnum)?;
+        let lsn = RollbackRecord::write_to_log(self.lm.clone(), self.txnum)?;
+        self.lm.flush(lsn)?;
+        Ok(())
+    }
+
+    pub(crate) fn recover(&self, tx: &mut TxInner) -> Result<RecoveryReport> {
+        let corrupted = self.do_recover(tx)?;
+        self.bm.flush_all(self.txnum)?;
+        self.bm.sync()?;
+        let checkpoint = RecoveryMgr::write_checkpoint(self.lm.clone())?;
+        Ok(RecoveryReport {
+            checkpoint,
+            corrupted,
+        })
+    }
+
+    pub(crate) fn rewind(&self, tx: &mut TxInner, target: RestoreTarget) -> Result<RestoreReport> {
+        let mut undoing: HashSet<i32> = HashSet::new();
+        let mut rolled_back: HashSet<i32> = HashSet::new();
+        let mut undone = 0;
+        let mut reached = false;
+        // NOTE: the records of the previous runs have no LSNs
+        let mut lsn = self.lm.latest_lsn() + 1;
+
+        let mut iter = self.lm.reverse_iter()?;
+        for bytes in iter.by_ref() {
+            let rec = create_log_record(bytes)?;
+            lsn -= 1;
+            reached = reached
+                || match target {
+                    RestoreTarget::Lsn(target) => lsn < target,
+                    RestoreTarget::Time(time) => rec.commit_time().is_some_and(|t| t <= time),
+                };
+            if reached && undoing.is_empty() {
+                break;
+            }
+            // NOTE: txnums restart on each run
+            let txnum = rec.tx_number();
+            match rec.op() {
+                Op::Checkpoint => {}
+                Op::Start => {
+                    if undoing.remove(&txnum) {
+                        undone += 1;
+                    }
+                    rolled_back.remove(&txnum);
+                }
+                Op::Commit if !reached => {
+                    undoing.insert(txnum);
+                }
+                Op::Commit => {}
+                Op::Rollback => {
+                    rolled_back.insert(txnum);
+                }
+                _ if undoing.contains(&txnum) => rec.undo(tx)?,
+                // NOTE: unfinished; undoing an undone one again is harmless
+                _ if !reached && !rolled_back.contains(&txnum) => {
+                    undoing.insert(txnum);
+                    rec.undo(tx)?;
+                }
+                _ => {}
+            }
+        }
+        undone += undoing.len();
+
+        self.bm.flush_all(self.txnum)?;
+        self.bm.sync()?;
+        Ok(RestoreReport {
+            undone,
+            corrupted: iter.is_corrupted(),
+        })
+    }
+
+    pub fn set_i32(&mut self, buff: &mut Buffer, offset: usize, newval: i32) -> Result<LSN> {
+        let oldval = buff.contents_as_mut().get_i32(offset)?;
+        let blk = buff.block().as_ref().unwrap();
+        let lsn =
+            SetIntRecord::write_to_log(self.lm.clone(), self.txnum, blk, offset, oldval, newval)?;
+        Ok(lsn)
+    }
+
+    pub fn set_string(&self, buff: &mut Buffer, offset: usize, newval: &str) -> Result<LSN> {
+        let oldval = buff.contents_as_mut().get_string(offset)?;
+        let blk = buff.block().as_ref().unwrap();
+        let lsn = SetStringRecord::write_to_log(
+            self.lm.clone(),
+            self.txnum,
+            blk,
+            offset,
+            oldval,
+            newval,
+        )?;
+        Ok(lsn)
+    }
+
+    pub fn set_i64(&self, buff: &mut Buffer, offset: usize, newval: i64) -> Result<LSN> {
+        let oldval = buff.contents_as_ref().get_i64(offset)?;
+        let blk = buff.block().as_ref().unwrap();
+        let lsn =
+            SetLongRecord::write_to_log(self.lm.clone(), self.txnum, blk, offset, oldval, newval)?;
+        Ok(lsn)
+    }
+
+    pub fn set_f64(&self, buff: &mut Buffer, offset: usize, newval: f64) -> Result<LSN> {
+        let oldval = buff.contents_as_ref().get_f64(offset)?;
+        let blk = buff.block().as_ref().unwrap();
+        let lsn = SetDoubleRecord::write_to_log(
+            self.lm.clone(),
+            self.txnum,
+            blk,
+            offset,
+            oldval,
+            newval,
+        )?;
+        Ok(lsn)
+    }
+
+    pub fn set_bytes(&self, buff: &mut Buffer, offset: usize, newval: &[u8]) -> Result<LSN> {
+        let oldval = buff.contents_as_mut().get_bytes(offset)?;
+        let blk = buff.block().as_ref().unwrap();
+        let lsn =
+            SetBytesRecord::write_to_log(self.lm.clone(), self.txnum, blk, offset, oldval, newval)?;
+        Ok(lsn)
+    }
+
+    pub fn max_raw_bytes(&self, blk: &BlockId) -> usize {
+        SetRawBytesRecord::max_value_len(&self.lm, blk)
+    }
+
+    pub fn set_raw_bytes(&self, buff: &mut Buffer, offset: usize, newval: &[u8]) -> Result<LSN> {
+        let oldval = buff.contents_as_ref().get_raw_bytes(offset, newval.len())?;
+        let blk = buff.block().as_ref().unwrap();
+        let lsn = SetRawBytesRecord::write_to_log(
+            self.lm.clone(),
+            self.txnum,
+            blk,
+            offset,
+            oldval,
+            newval,
+        )?;
+        Ok(lsn)
+    }
+
+    pub(crate) fn undo_for_snapshot(
+        &self,
+        blk: &BlockId,
+        bytes: &mut [u8],
+        snapshot: &Snapshot,
+        undo: &mut SnapshotUndo,
+    ) -> Result<()> {
+        match undo.collected {
+            Some(lsn) => self.collect_since(lsn, snapshot, undo)?,
+            None => self.collect_for_snapshot(snapshot, undo)?,
+        }
+        let mut page = Page::for_log(bytes);
+        for bytes in undo.records.get(blk).into_iter().flatten().rev() {
+            create_log_record(bytes.clone())?.undo_page(blk, &mut page)?;
+        }
+        Ok(())
+    }
+
+    fn collect_for_snapshot(&self, snapshot: &Snapshot, undo: &mut SnapshotUndo) -> Result<()> {
+        let mut pending: HashSet<i32> = snapshot.active_txs().clone();
+        let mut iter = self.lm.reverse_iter()?;
+        let mut lsn = iter.latest_lsn();
+        undo.collected = Some(lsn);
+
+        let mut collected = Vec::new();
+        while (!pending.is_empty() || lsn > snapshot.lsn()) && iter.has_next() {
+            let bytes = iter.next().unwrap();
+            let rec = create_log_record(bytes.clone())?;
+            lsn -= 1;
+            if rec.op() == Op::Start {
+                pending.remove(&rec.tx_number());
+                continue;
+            }
+            if let Some(blk) = rec.block() {
+                if !snapshot.is_visible(rec.tx_number()) {
+                    collected.push((blk.clone(), bytes));
+                }
+            }
+        }
+        for (blk, bytes) in collected.into_iter().rev() {
+            undo.records.entry(blk).or_default().push(bytes);
+        }
+        Ok(())
+    }
+
+    fn collect_since(&self, lsn: LSN, snapshot: &Snapshot, undo: &mut SnapshotUndo) -> Result<()> {
+        for (lsn, bytes) in self.lm.forward_iter(lsn + 1)? {
+            undo.collected = Some(lsn);
+            let rec = create_log_record(bytes.clone())?;
+            if let Some(blk) = rec.block() {
+                if !snapshot.is_visible(rec.tx_number()) {
+                    undo.records.entry(blk.clone()).or_default().push(bytes);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn append(&self, blk: &BlockId) -> Result<LSN> {
+        let lsn = AppendRecord::write_to_log(self.lm.clone(), self.txnum, blk)?;
+        self.lm.flush(lsn)?;
+        Ok(lsn)
+    }
+
+    pub fn rename(&self, from: &str, to: &str) -> Result<LSN> {
+        let lsn = RenameRecord::write_to_log(self.lm.clone(), self.txnum, from, to)?;
+        self.lm.flush(lsn)?;
+        Ok(lsn)
+    }
+
+    fn do_rollback(&self, tx: &mut TxInner) -> Result<()> {
+        let mut iter = self.lm.reverse_iter()?;
+        while iter.has_next() {
+            let bytes = iter.next().unwrap();
+            let rec = create_log_record(bytes)?;
+            if rec.tx_number() == self.txnum {
+                if rec.op() == Op::Start {
+                    break;
+                }
+                rec.undo(tx)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn do_recover(&self, tx: &mut TxInner) -> Result<bool> {
+        let mut finished_txs: Vec<i32> = Vec::new();
+        let mut committed_txs: Vec<i32> = Vec::new();
+        let mut records = Vec::new();
+
+        // NOTE: undo pass, collecting the records to redo
+        let mut iter = self.lm.reverse_iter()?;
+        // NOTE: stops at a corrupted record
+        for bytes in iter.by_ref() {
+            let rec = create_log_record(bytes)?;
+            if rec.op() == Op::Checkpoint {
+                break;
+            }
+            if rec.op() == Op::Commit || rec.op() == Op::Rollback {
+                finished_txs.push(rec.tx_number());
+                if rec.op() == Op::Commit {
+                    committed_txs.push(rec.tx_number());
+                }
+            } else if !finished_txs.contains(&rec.tx_number()) {
+                rec.undo(tx)?;
+            }
+            records.push(rec);
+        }
+
+        // NOTE: redo pass (forward)
+        for rec in records.iter().rev() {
+            if committed_txs.contains(&rec.tx_number()) {
+                rec.redo(tx)?;
+            }
+        }
+        Ok(iter.is_corrupted())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{file::file_mgr::FileMgr, server::simple_db::SimpleDB};
+    use std::path::Path;
+    use tempfile::tempdir;
+
+    struct Context {
+        db: SimpleDB,
+        fm: Arc<FileMgr>,
+        bm: Arc<BufferMgr>,
+        block0: BlockId,
+        block1: BlockId,
+    }
+    impl Context {
+        pub fn new(dir: &Path) -> Self {
+            let db = SimpleDB::new_for_test(dir, "test_recovery_mgr.log");
+            let fm = db.file_mgr();
+            let bm = db.buffer_mgr();
+            Context {
+                db,
+                fm: fm.clone(),
+                bm,
+                block0: BlockId::new("test_recovery_mgr_file", 0),
+                block1: BlockId::new("test_recovery_mgr_file", 1),
+            }
+        }
+    }
+
+    #[test]
+    fn test_recovery_mgr() {
+        let dir = tempdir().unwrap();
+        {
+            let mut ctx = Context::new(dir.path());
+            test_initialize(&mut ctx);
+            test_modify(&ctx);
+        }
+        {
+            let mut ctx = Context::new(dir.path());
+            test_recover(&mut ctx);
+        }
+        dir.close().unwrap();
+    }
+
+    fn test_initialize(ctx: &mut Context) {
+        let tx1 = ctx.db.new_tx();
+        let tx2 = ctx.db.new_tx();
+        tx1.lock().unwrap().pin(&ctx.block0).unwrap();
+        tx2.lock().unwrap().pin(&ctx.block1).unwrap();
+
+        let mut pos = 0;
+        for _i in 0..6 {
+            tx1.lock()
+                .unwrap()
+                .set_i32(&ctx.block0, pos, pos as i32, false)
+                .unwrap();
+            tx2.lock()
+                .unwrap()
+                .set_i32(&ctx.block1, pos, pos as i32, false)
+                .unwrap();
+            pos += I32_BYTE_SIZE as usize;
+        }
+
+        tx1.lock()
+            .unwrap()
+            .set_string(&ctx.block0, 30, "abc", false)
+            .unwrap();
+        tx2.lock()
+            .unwrap()
+            .set_string(&ctx.block1, 30, "def", false)
+            .unwrap();
+        ctx.bm.flush_all(tx1.lock().unwrap().txnum()).unwrap();
+        ctx.bm.flush_all(tx2.lock().unwrap().txnum()).unwrap();
+        tx1.lock().unwrap().unpin(&ctx.block0);
+        tx1.lock().unwrap().commit().unwrap();
+        tx2.lock().unwrap().unpin(&ctx.block1);
+        tx2.lock().unwrap().commit().unwrap();
+
+        assert_fm_values(
+            &ctx,
+            [[0, 4, 8, 12, 16, 20], [0, 4, 8, 12, 16, 20]],
+            ["abc", "def"],
+        );
+    }
+
+    fn test_modify(ctx: &Context) {
+        let tx3 = ctx.db.new_tx();
+        let tx4 = ctx.db.new_tx();
+
+        tx3.lock().unwrap().pin(&ctx.block0).unwrap();
+        tx4.lock().unwrap().pin(&ctx.block1).unwrap();
+
+        let mut pos = 0;
+        for _i in 0..6 {
+            tx3.lock()
+                .unwrap()
+                .set_i32(&ctx.block0, pos, pos as i32 + 100, true)
+                .unwrap();
+            tx4.lock()
+                .unwrap()
+                .set_i32(&ctx.block1, pos, pos as i32 + 100, true)
+                .unwrap();
+            pos += I32_BYTE_SIZE as usize;
+        }
+        tx3.lock()
+            .unwrap()
+            .set_string(&ctx.block0, 30, "uvw", true)
+            .unwrap();
+        tx4.lock()
+            .unwrap()
+            .set_string(&ctx.block1, 30, "xyz", true)
+            .unwrap();
+        ctx.bm.flush_all(tx3.lock().unwrap().txnum()).unwrap();
+        ctx.bm.flush_all(tx4.lock().unwrap().txnum()).unwrap();
+        assert_fm_values(
+            &ctx,
+            [
+                [100, 104, 108, 112, 116, 120],
+                [100, 104, 108, 112, 116, 120],
+            ],
+            ["uvw", "xyz"],
+        );
+
+        tx3.lock().unwrap().unpin(&ctx.block0);
+        tx3.lock().unwrap().rollback().unwrap();
+        assert_fm_values(
+            &ctx,
+            [[0, 4, 8, 12, 16, 20], [100, 104, 108, 112, 116, 120]],
             ["abc", "xyz"],
         );
+
+        // NOTE: simulate a crash; dropping tx4 would roll it back
+        std::mem::forget(tx4);
     }
 
     fn test_recover(ctx: &mut Context) {
         let tx = ctx.db.new_tx();
         print_fm_values(ctx, &ctx.block1);
-        tx.borrow_mut().recover().unwrap();
+        tx.lock().unwrap().recover().unwrap();
         print_fm_values(ctx, &ctx.block1);
         assert_fm_values(
             &ctx,
@@ -638,6 +1819,203 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_redo() {
+        let dir = tempdir().unwrap();
+        {
+            let ctx = Context::new(dir.path());
+            let tx = ctx.db.new_tx();
+            tx.lock().unwrap().pin(&ctx.block0).unwrap();
+            tx.lock().unwrap().pin(&ctx.block1).unwrap();
+            let mut pos = 0;
+            for _i in 0..6 {
+                tx.lock()
+                    .unwrap()
+                    .set_i32(&ctx.block0, pos, pos as i32, true)
+                    .unwrap();
+                tx.lock()
+                    .unwrap()
+                    .set_i32(&ctx.block1, pos, pos as i32 + 100, true)
+                    .unwrap();
+                pos += I32_BYTE_SIZE as usize;
+            }
+            tx.lock()
+                .unwrap()
+                .set_string(&ctx.block0, 30, "abc", true)
+                .unwrap();
+            tx.lock()
+                .unwrap()
+                .set_string(&ctx.block1, 30, "xyz", true)
+                .unwrap();
+            tx.lock().unwrap().unpin(&ctx.block0);
+            tx.lock().unwrap().unpin(&ctx.block1);
+            tx.lock().unwrap().commit().unwrap();
+
+            let mut p = Page::for_data(ctx.fm.blocksize());
+            ctx.fm.read(&ctx.block0, &mut p).unwrap();
+            assert_eq!(p.get_i32(4).unwrap(), 0);
+        }
+        {
+            let ctx = Context::new(dir.path());
+            let tx = ctx.db.new_tx();
+            assert!(!tx.lock().unwrap().recover().unwrap().corrupted);
+            assert_fm_values(
+                &ctx,
+                [[0, 4, 8, 12, 16, 20], [100, 104, 108, 112, 116, 120]],
+                ["abc", "xyz"],
+            );
+        }
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_corrupted_log() {
+        let dir = tempdir().unwrap();
+        {
+            let ctx = Context::new(dir.path());
+            let tx = ctx.db.new_tx();
+            tx.lock().unwrap().pin(&ctx.block0).unwrap();
+            tx.lock().unwrap().set_i32(&ctx.block0, 0, 1, true).unwrap();
+            ctx.bm.flush_all(tx.lock().unwrap().txnum()).unwrap();
+            std::mem::forget(tx);
+        }
+
+        // NOTE: [boundary] ... [length] [bytes]
+        let segment = "test_recovery_mgr.log.0001";
+        let fm = FileMgr::new(dir.path(), 4096);
+        let last = BlockId::new(segment, fm.length(segment).unwrap() as i64 - 1);
+        let mut p = Page::for_data(fm.blocksize());
+        fm.read(&last, &mut p).unwrap();
+        let boundary = p.get_i32(0).unwrap() as usize;
+        p.set_i32(boundary + I32_BYTE_SIZE as usize, 0x7f7f7f7f)
+            .unwrap();
+        fm.write(&last, &mut p).unwrap();
+
+        let ctx = Context::new(dir.path());
+        let tx = ctx.db.new_tx();
+        assert!(tx.lock().unwrap().recover().unwrap().corrupted);
+        let mut p = Page::for_data(ctx.fm.blocksize());
+        ctx.fm.read(&ctx.block0, &mut p).unwrap();
+        assert_eq!(p.get_i32(0).unwrap(), 1);
+        drop(ctx);
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_append() {
+        let dir = tempdir().unwrap();
+        let filename = "test_recovery_mgr_append_file";
+        {
+            let ctx = Context::new(dir.path());
+            let tx1 = ctx.db.new_tx();
+            let block0 = tx1.lock().unwrap().append(filename).unwrap();
+            tx1.lock().unwrap().pin(&block0).unwrap();
+            tx1.lock().unwrap().set_i32(&block0, 0, 1, true).unwrap();
+            tx1.lock().unwrap().unpin(&block0);
+            tx1.lock().unwrap().commit().unwrap();
+
+            let tx2 = ctx.db.new_tx();
+            let block1 = tx2.lock().unwrap().append(filename).unwrap();
+            assert_eq!(ctx.fm.length(filename).unwrap(), 2);
+            tx2.lock().unwrap().pin(&block1).unwrap();
+            tx2.lock().unwrap().set_i32(&block1, 0, 123, false).unwrap();
+            tx2.lock().unwrap().unpin(&block1);
+            tx2.lock().unwrap().rollback().unwrap();
+            assert_eq!(ctx.fm.length(filename).unwrap(), 1);
+
+            let tx3 = ctx.db.new_tx();
+            let block1 = tx3.lock().unwrap().append(filename).unwrap();
+            tx3.lock().unwrap().pin(&block1).unwrap();
+            assert_eq!(tx3.lock().unwrap().get_i32(&block1, 0).unwrap(), 0);
+            tx3.lock().unwrap().unpin(&block1);
+            tx3.lock().unwrap().commit().unwrap();
+
+            let tx4 = ctx.db.new_tx();
+            tx4.lock().unwrap().append(filename).unwrap();
+            assert_eq!(ctx.fm.length(filename).unwrap(), 3);
+        }
+        {
+            let ctx = Context::new(dir.path());
+            let tx = ctx.db.new_tx();
+            tx.lock().unwrap().recover().unwrap();
+            assert_eq!(ctx.fm.length(filename).unwrap(), 2);
+        }
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_long_double_and_bytes() {
+        let dir = tempdir().unwrap();
+        {
+            let ctx = Context::new(dir.path());
+            let tx1 = ctx.db.new_tx();
+            tx1.lock().unwrap().pin(&ctx.block0).unwrap();
+            tx1.lock()
+                .unwrap()
+                .set_i64(&ctx.block0, 0, 1 << 40, true)
+                .unwrap();
+            tx1.lock()
+                .unwrap()
+                .set_f64(&ctx.block0, 8, 1.5, true)
+                .unwrap();
+            tx1.lock()
+                .unwrap()
+                .set_bytes(&ctx.block0, 16, &[1, 2, 3], true)
+                .unwrap();
+            tx1.lock().unwrap().unpin(&ctx.block0);
+            tx1.lock().unwrap().commit().unwrap();
+
+            let tx2 = ctx.db.new_tx();
+            tx2.lock().unwrap().pin(&ctx.block0).unwrap();
+            tx2.lock()
+                .unwrap()
+                .set_i64(&ctx.block0, 0, -1, true)
+                .unwrap();
+            tx2.lock()
+                .unwrap()
+                .set_f64(&ctx.block0, 8, -2.5, true)
+                .unwrap();
+            tx2.lock()
+                .unwrap()
+                .set_bytes(&ctx.block0, 16, &[9; 5], true)
+                .unwrap();
+            tx2.lock().unwrap().unpin(&ctx.block0);
+            tx2.lock().unwrap().rollback().unwrap();
+
+            let tx3 = ctx.db.new_tx();
+            tx3.lock().unwrap().pin(&ctx.block0).unwrap();
+            assert_eq!(
+                tx3.lock().unwrap().get_i64(&ctx.block0, 0).unwrap(),
+                1 << 40
+            );
+            assert_eq!(tx3.lock().unwrap().get_f64(&ctx.block0, 8).unwrap(), 1.5);
+            assert_eq!(
+                tx3.lock().unwrap().get_bytes(&ctx.block0, 16).unwrap(),
+                vec![1, 2, 3]
+            );
+
+            tx3.lock()
+                .unwrap()
+                .set_i64(&ctx.block0, 0, 7, true)
+                .unwrap();
+            ctx.bm.flush_all(tx3.lock().unwrap().txnum()).unwrap();
+            // NOTE: simulate a crash; dropping tx3 would roll it back
+            std::mem::forget(tx3);
+        }
+        {
+            let ctx = Context::new(dir.path());
+            let tx = ctx.db.new_tx();
+            tx.lock().unwrap().recover().unwrap();
+
+            let mut p = Page::for_data(ctx.fm.blocksize());
+            ctx.fm.read(&ctx.block0, &mut p).unwrap();
+            assert_eq!(p.get_i64(0).unwrap(), 1 << 40);
+            assert_eq!(p.get_f64(8).unwrap(), 1.5);
+            assert_eq!(p.get_bytes(16).unwrap(), vec![1, 2, 3]);
+        }
+        dir.close().unwrap();
+    }
+
     fn print_fm_values(ctx: &Context, block: &BlockId) {
         let mut p = Page::for_data(ctx.fm.blocksize());
         ctx.fm.read(block, &mut p).unwrap();
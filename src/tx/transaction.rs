@@ -5,23 +5,29 @@
 
 use super::{
     buffer_list::BufferList,
-    concurrency_mgr::ConcurrencyMgr,
+    concurrency_mgr::{ConcurrencyMgr, IsolationLevel},
     lock_table::{LockTable, LockTableError},
-    recovery_mgr::{RecoveryError, RecoveryMgr},
+    recovery_mgr::{
+        RecoveryError, RecoveryMgr, RecoveryReport, ReplayedTx, RestoreReport, RestoreTarget,
+        Result as RecoveryResult, SnapshotUndo,
+    },
 };
 use crate::{
-    buffer_mgr::{BufferError, BufferMgr},
+    buffer_mgr::{Buffer, BufferError, BufferMgr, PinPriority},
     file::{
         block_id::BlockId,
         file_mgr::{FileMgr, FileMgrError},
-        page::PageError,
+        page::{Page, PageError},
     },
-    log_mgr::LogMgr,
+    log_mgr::{LogMgr, LSN},
 };
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
 use std::{
     cell::RefCell,
-    sync::atomic::{AtomicI32, Ordering},
+    collections::{HashMap, HashSet},
+    path::Path,
+    sync::atomic::{AtomicI32, AtomicUsize, Ordering},
 };
 use thiserror::Error;
 
@@ -29,32 +35,61 @@ const END_OF_FILE: i32 = -1;
 
 #[derive(Debug, Error)]
 pub enum TransactionError {
-    #[error("{0:?}")]
+    #[error("{0}")]
     BufferError(#[from] BufferError),
 
-    #[error("{0:?}")]
+    #[error("{0}")]
     LockTableError(#[from] LockTableError),
 
-    #[error("{0:?}")]
+    #[error("{0}")]
     PageError(#[from] PageError),
 
-    #[error("{0:?}")]
+    #[error("{0}")]
     RecoveryError(#[from] RecoveryError),
 
-    #[error("{0:?}")]
+    #[error("{0}")]
     FileMgrError(#[from] FileMgrError),
+
+    #[error("the transaction is read-only")]
+    ReadOnly,
+
+    #[error("the transaction has passed its deadline")]
+    Timeout,
+
+    #[error("too many blocks in {0}")]
+    TooManyBlocks(String),
+}
+
+impl TransactionError {
+    pub(crate) fn is_timeout(e: &anyhow::Error) -> bool {
+        e.chain().any(|e| {
+            matches!(
+                e.downcast_ref::<TransactionError>(),
+                Some(TransactionError::Timeout)
+            )
+        })
+    }
 }
 
 pub type Result<T> = core::result::Result<T, TransactionError>;
 
-pub(crate) struct TxInner<'lm, 'bm> {
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Durability {
+    #[default]
+    Strict,
+    Relaxed,
+}
+
+pub(crate) struct TxInner {
     cm: RefCell<ConcurrencyMgr>,
-    bl: BufferList<'bm, 'lm>,
+    bl: BufferList,
+    fm: Arc<FileMgr>,
     txnum: i32,
 }
-impl TxInner<'_, '_> {
+// NOTE: recovery setters take no locks, as the rollback already holds them
+impl TxInner {
     pub fn pin(&mut self, blk: &BlockId) -> Result<()> {
-        self.bl.pin(blk)?;
+        self.bl.pin(blk, PinPriority::Normal)?;
         Ok(())
     }
 
@@ -63,7 +98,6 @@ impl TxInner<'_, '_> {
     }
 
     pub fn set_i32_for_recovery(&mut self, blk: &BlockId, offset: usize, val: i32) -> Result<()> {
-        self.cm.borrow_mut().xlock(blk)?;
         let mut buff = self.bl.get_buffer(blk).unwrap().lock().unwrap();
         let lsn = -1;
         let p = buff.contents_as_mut();
@@ -78,7 +112,6 @@ impl TxInner<'_, '_> {
         offset: usize,
         val: &str,
     ) -> Result<()> {
-        self.cm.borrow_mut().xlock(blk)?;
         let mut buff = self.bl.get_buffer(blk).unwrap().lock().unwrap();
         let lsn = -1;
         let p = buff.contents_as_mut();
@@ -86,41 +119,218 @@ impl TxInner<'_, '_> {
         buff.set_modified(self.txnum, lsn);
         Ok(())
     }
+
+    pub fn set_i64_for_recovery(&mut self, blk: &BlockId, offset: usize, val: i64) -> Result<()> {
+        let mut buff = self.bl.get_buffer(blk).unwrap().lock().unwrap();
+        let lsn = -1;
+        let p = buff.contents_as_mut();
+        p.set_i64(offset, val)?;
+        buff.set_modified(self.txnum, lsn);
+        Ok(())
+    }
+
+    pub fn set_f64_for_recovery(&mut self, blk: &BlockId, offset: usize, val: f64) -> Result<()> {
+        let mut buff = self.bl.get_buffer(blk).unwrap().lock().unwrap();
+        let lsn = -1;
+        let p = buff.contents_as_mut();
+        p.set_f64(offset, val)?;
+        buff.set_modified(self.txnum, lsn);
+        Ok(())
+    }
+
+    pub fn set_bytes_for_recovery(
+        &mut self,
+        blk: &BlockId,
+        offset: usize,
+        val: &[u8],
+    ) -> Result<()> {
+        let mut buff = self.bl.get_buffer(blk).unwrap().lock().unwrap();
+        let lsn = -1;
+        let p = buff.contents_as_mut();
+        p.set_bytes(offset, val)?;
+        buff.set_modified(self.txnum, lsn);
+        Ok(())
+    }
+
+    pub fn set_raw_bytes_for_recovery(
+        &mut self,
+        blk: &BlockId,
+        offset: usize,
+        val: &[u8],
+    ) -> Result<()> {
+        let mut buff = self.bl.get_buffer(blk).unwrap().lock().unwrap();
+        let lsn = -1;
+        let p = buff.contents_as_mut();
+        p.set_raw_bytes(offset, val)?;
+        buff.set_modified(self.txnum, lsn);
+        Ok(())
+    }
+
+    pub fn truncate_for_recovery(&mut self, blk: &BlockId) -> Result<()> {
+        let dummyblk = BlockId::new(blk.filename(), END_OF_FILE.into());
+        self.cm.borrow_mut().xlock(&dummyblk)?;
+        self.bl.discard(blk);
+        self.fm.truncate(blk.filename(), blk.number_as_u64())?;
+        Ok(())
+    }
+
+    pub fn undo_rename(&mut self, from: &str, to: &str) -> Result<()> {
+        if self.fm.is_empty(from)? {
+            self.rename_for_recovery(to, from)?;
+        }
+        Ok(())
+    }
+
+    pub fn redo_rename(&mut self, from: &str, to: &str) -> Result<()> {
+        if self.fm.is_empty(to)? {
+            self.rename_for_recovery(from, to)?;
+        } else {
+            self.lock_file(from)?;
+            self.bl.flush_file(from)?;
+            self.fm.remove(from)?;
+        }
+        Ok(())
+    }
+
+    fn rename_for_recovery(&mut self, from: &str, to: &str) -> Result<()> {
+        self.lock_file(from)?;
+        self.lock_file(to)?;
+        self.bl.flush_file(from)?;
+        self.bl.flush_file(to)?;
+        self.fm.rename(from, to)?;
+        Ok(())
+    }
+
+    pub fn extend_for_replay(&mut self, blk: &BlockId) -> Result<()> {
+        self.lock_file(blk.filename())?;
+        while self.fm.length(blk.filename())? <= blk.number_as_u64() {
+            self.fm.append(blk.filename())?;
+        }
+        Ok(())
+    }
+
+    pub fn xlock(&mut self, blk: &BlockId) -> Result<()> {
+        self.cm.borrow_mut().xlock(blk)?;
+        Ok(())
+    }
+
+    pub fn lock_file(&mut self, filename: &str) -> Result<()> {
+        self.cm.borrow_mut().xlock_table(filename)?;
+        Ok(())
+    }
 }
 
 pub struct TxNumber {
     next: AtomicI32,
+    active: Mutex<HashSet<i32>>,
+    // NOTE: see Transaction::truncate_on_commit
+    failed_truncations: AtomicUsize,
 }
 impl TxNumber {
     pub fn new() -> Self {
         Self {
             next: AtomicI32::new(1),
+            active: Mutex::new(HashSet::new()),
+            failed_truncations: AtomicUsize::new(0),
         }
     }
 
     pub fn next(&self) -> i32 {
+        let mut active = self.active.lock().unwrap();
+        let txnum = self.next.fetch_add(1, Ordering::SeqCst);
+        active.insert(txnum);
+        txnum
+    }
+
+    pub fn next_read_only(&self) -> i32 {
         self.next.fetch_add(1, Ordering::SeqCst)
     }
+
+    pub fn end(&self, txnum: i32) {
+        self.active.lock().unwrap().remove(&txnum);
+    }
+
+    pub fn when_idle<T>(&self, f: impl FnOnce() -> T) -> Option<T> {
+        let active = self.active.lock().unwrap();
+        active.is_empty().then(f)
+    }
+
+    pub fn failed_truncations(&self) -> usize {
+        self.failed_truncations.load(Ordering::SeqCst)
+    }
+
+    pub fn active_txs(&self) -> HashSet<i32> {
+        self.active.lock().unwrap().clone()
+    }
+
+    fn snapshot(&self, txnum: i32, lm: &LogMgr) -> Snapshot {
+        let active = self.active.lock().unwrap();
+        Snapshot {
+            txnum,
+            active_txs: active.iter().copied().filter(|t| *t != txnum).collect(),
+            next_txnum: self.next.load(Ordering::SeqCst),
+            lsn: lm.latest_lsn(),
+        }
+    }
+}
+
+pub(crate) struct Snapshot {
+    txnum: i32,
+    active_txs: HashSet<i32>,
+    next_txnum: i32,
+    lsn: LSN,
+}
+
+impl Snapshot {
+    pub fn active_txs(&self) -> &HashSet<i32> {
+        &self.active_txs
+    }
+
+    pub fn lsn(&self) -> LSN {
+        self.lsn
+    }
+
+    pub fn is_visible(&self, txnum: i32) -> bool {
+        txnum == self.txnum || (txnum < self.next_txnum && !self.active_txs.contains(&txnum))
+    }
 }
 
-pub struct Transaction<'lm, 'bm> {
-    inner: TxInner<'lm, 'bm>,
+pub struct Transaction {
+    inner: TxInner,
     fm: Arc<FileMgr>,
-    bm: Arc<BufferMgr<'bm, 'lm>>,
-    rm: RecoveryMgr<'lm, 'bm>,
+    bm: Arc<BufferMgr>,
+    rm: RecoveryMgr,
+    tn: Arc<TxNumber>,
+    isolation: IsolationLevel,
+    read_only: bool,
+    snapshot: Option<Snapshot>,
+    snapshot_pages: RefCell<HashMap<BlockId, Vec<u8>>>,
+    snapshot_undo: RefCell<SnapshotUndo>,
+    finished: bool,
+    commit_hooks: Vec<Box<dyn FnOnce() + Send>>,
+    rollback_hooks: Vec<Box<dyn FnOnce() + Send>>,
+    // NOTE: file -> blocks to keep
+    truncations: Vec<(String, u64)>,
+    deadline: Option<Instant>,
+    durability: Durability,
+    // NOTE: None to run any statement
+    user: Option<String>,
 }
 
-impl<'lm, 'bm> Transaction<'lm, 'bm> {
+impl Transaction {
     pub fn new(
-        txnum: i32,
+        tn: Arc<TxNumber>,
+        isolation: IsolationLevel,
         fm: Arc<FileMgr>,
-        lm: Arc<LogMgr<'lm>>,
-        bm: Arc<BufferMgr<'bm, 'lm>>,
+        lm: Arc<LogMgr>,
+        bm: Arc<BufferMgr>,
         lock_table: Arc<LockTable>,
     ) -> Self {
+        let txnum = tn.next();
         let inner = TxInner {
-            cm: RefCell::new(ConcurrencyMgr::new(lock_table)),
-            bl: BufferList::new(bm.clone()),
+            cm: RefCell::new(ConcurrencyMgr::new(txnum, isolation, lock_table)),
+            bl: BufferList::new(bm.clone(), txnum),
+            fm: fm.clone(),
             txnum,
         };
         Self {
@@ -128,15 +338,126 @@ impl<'lm, 'bm> Transaction<'lm, 'bm> {
             fm,
             bm: bm.clone(),
             rm: RecoveryMgr::new(txnum, lm, bm.clone()),
+            tn,
+            isolation,
+            read_only: false,
+            snapshot: None,
+            snapshot_pages: RefCell::new(HashMap::new()),
+            snapshot_undo: RefCell::new(SnapshotUndo::default()),
+            finished: false,
+            commit_hooks: Vec::new(),
+            truncations: Vec::new(),
+            rollback_hooks: Vec::new(),
+            deadline: None,
+            durability: Durability::default(),
+            user: None,
         }
     }
 
+    pub fn new_snapshot(
+        tn: Arc<TxNumber>,
+        fm: Arc<FileMgr>,
+        lm: Arc<LogMgr>,
+        bm: Arc<BufferMgr>,
+        lock_table: Arc<LockTable>,
+    ) -> Self {
+        let mut tx = Transaction::new_read_only(
+            tn,
+            IsolationLevel::default(),
+            fm,
+            lm.clone(),
+            bm,
+            lock_table,
+        );
+        tx.snapshot = Some(tx.tn.snapshot(tx.inner.txnum, &lm));
+        tx
+    }
+
+    pub fn new_read_only(
+        tn: Arc<TxNumber>,
+        isolation: IsolationLevel,
+        fm: Arc<FileMgr>,
+        lm: Arc<LogMgr>,
+        bm: Arc<BufferMgr>,
+        lock_table: Arc<LockTable>,
+    ) -> Self {
+        let txnum = tn.next_read_only();
+        let inner = TxInner {
+            cm: RefCell::new(ConcurrencyMgr::new(txnum, isolation, lock_table)),
+            bl: BufferList::new(bm.clone(), txnum),
+            fm: fm.clone(),
+            txnum,
+        };
+        Self {
+            inner,
+            fm,
+            bm: bm.clone(),
+            rm: RecoveryMgr::new_read_only(txnum, lm, bm.clone()),
+            tn,
+            isolation,
+            read_only: true,
+            snapshot: None,
+            snapshot_pages: RefCell::new(HashMap::new()),
+            snapshot_undo: RefCell::new(SnapshotUndo::default()),
+            finished: false,
+            commit_hooks: Vec::new(),
+            truncations: Vec::new(),
+            rollback_hooks: Vec::new(),
+            deadline: None,
+            durability: Durability::default(),
+            user: None,
+        }
+    }
+
+    pub fn isolation(&self) -> IsolationLevel {
+        self.isolation
+    }
+
+    pub fn is_snapshot(&self) -> bool {
+        self.snapshot.is_some()
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
     pub fn txnum(&self) -> i32 {
         self.inner.txnum
     }
 
+    pub fn set_deadline(&mut self, deadline: Option<Instant>) {
+        self.deadline = deadline;
+    }
+
+    pub fn durability(&self) -> Durability {
+        self.durability
+    }
+
+    pub fn set_durability(&mut self, durability: Durability) {
+        self.durability = durability;
+    }
+
+    pub fn user(&self) -> Option<&str> {
+        self.user.as_deref()
+    }
+
+    pub fn set_user(&mut self, user: Option<String>) {
+        self.user = user;
+    }
+
+    pub fn check_deadline(&self) -> Result<()> {
+        match self.deadline {
+            Some(deadline) if Instant::now() >= deadline => Err(TransactionError::Timeout),
+            _ => Ok(()),
+        }
+    }
+
     pub fn pin(&mut self, blk: &BlockId) -> Result<()> {
-        self.inner.bl.pin(blk)?;
+        self.pin_with_priority(blk, PinPriority::Normal)
+    }
+
+    pub fn pin_with_priority(&mut self, blk: &BlockId, priority: PinPriority) -> Result<()> {
+        self.inner.bl.pin(blk, priority)?;
         Ok(())
     }
 
@@ -145,37 +466,184 @@ impl<'lm, 'bm> Transaction<'lm, 'bm> {
     }
 
     pub fn commit(&mut self) -> Result<()> {
-        self.rm.commit()?;
+        if !self.is_read_only() {
+            self.rm.commit(self.durability)?;
+        }
+        self.release_pins();
+        // NOTE: while the locks are held
+        self.truncate_files();
         self.inner.cm.borrow_mut().release();
-        self.inner.bl.unpin_all();
+        self.tn.end(self.inner.txnum);
+        self.finished = true;
+        self.rollback_hooks.clear();
+        for hook in self.commit_hooks.drain(..) {
+            hook();
+        }
+        if !self.is_read_only() {
+            self.rm.checkpoint_if_due(&self.tn);
+        }
         Ok(())
     }
 
     pub fn rollback(&mut self) -> Result<()> {
-        self.rm.rollback(&mut self.inner)?;
+        if !self.is_read_only() {
+            self.rm.rollback(&mut self.inner)?;
+        }
         self.inner.cm.borrow_mut().release();
-        self.inner.bl.unpin_all();
+        self.release_pins();
+        self.tn.end(self.inner.txnum);
+        self.finished = true;
+        self.commit_hooks.clear();
+        self.truncations.clear();
+        for hook in self.rollback_hooks.drain(..) {
+            hook();
+        }
+        if !self.is_read_only() {
+            self.rm.checkpoint_if_due(&self.tn);
+        }
+        Ok(())
+    }
+
+    pub fn truncate_on_commit(&mut self, filename: &str, num_blocks: u64) -> Result<()> {
+        if self.is_read_only() {
+            return Err(TransactionError::ReadOnly);
+        }
+        let dummyblk = BlockId::new(filename, END_OF_FILE.into());
+        self.inner.cm.borrow_mut().xlock(&dummyblk)?;
+        self.truncations.push((filename.to_string(), num_blocks));
         Ok(())
     }
 
-    pub fn recover(&mut self) -> Result<()> {
+    pub fn rename_file(&mut self, from: &str, to: &str) -> Result<()> {
+        if self.is_read_only() {
+            return Err(TransactionError::ReadOnly);
+        }
+        self.inner.lock_file(from)?;
+        self.inner.lock_file(to)?;
+        self.bm.flush_file(from)?;
+        self.bm.flush_file(to)?;
+        // NOTE: recovery doesn't redo changes across a rename
+        self.bm.sync()?;
+        self.rm.rename(from, to)?;
+        self.fm.rename(from, to)?;
+        Ok(())
+    }
+
+    fn truncate_files(&mut self) {
+        for (filename, num_blocks) in std::mem::take(&mut self.truncations) {
+            let result = self
+                .fm
+                .length(&filename)
+                .map_err(TransactionError::from)
+                .and_then(|len| {
+                    for blknum in num_blocks..len {
+                        let blknum = i64::try_from(blknum)
+                            .map_err(|_| TransactionError::TooManyBlocks(filename.clone()))?;
+                        self.bm.discard(&BlockId::new(&filename, blknum));
+                    }
+                    Ok(self.fm.truncate(&filename, num_blocks)?)
+                });
+            if result.is_err() {
+                self.tn.failed_truncations.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+    }
+
+    fn release_pins(&mut self) {
+        let leaked = self.inner.bl.unpin_all();
+        debug_assert!(
+            !cfg!(test) || std::thread::panicking() || leaked.is_empty(),
+            "transaction {} ended with pinned blocks: {:?}",
+            self.inner.txnum,
+            leaked
+        );
+    }
+
+    pub fn on_commit(&mut self, hook: impl FnOnce() + Send + 'static) {
+        self.commit_hooks.push(Box::new(hook));
+    }
+
+    pub fn on_rollback(&mut self, hook: impl FnOnce() + Send + 'static) {
+        self.rollback_hooks.push(Box::new(hook));
+    }
+
+    pub fn recover(&mut self) -> Result<RecoveryReport> {
+        if self.is_read_only() {
+            return Err(TransactionError::ReadOnly);
+        }
+        self.bm.flush_all(self.inner.txnum)?;
+        let report = self.rm.recover(&mut self.inner)?;
+        Ok(report)
+    }
+
+    pub fn rewind(&mut self, target: RestoreTarget) -> Result<RestoreReport> {
+        if self.is_read_only() {
+            return Err(TransactionError::ReadOnly);
+        }
+        Ok(self.rm.rewind(&mut self.inner, target)?)
+    }
+
+    pub fn replay(&mut self, rtx: &ReplayedTx) -> Result<()> {
+        if self.is_read_only() {
+            return Err(TransactionError::ReadOnly);
+        }
+        rtx.lock(&mut self.inner)?;
+        rtx.replay(&mut self.inner)?;
         self.bm.flush_all(self.inner.txnum)?;
-        self.rm.recover(&mut self.inner)?;
         Ok(())
     }
 
     pub fn get_i32(&self, blk: &BlockId, offset: usize) -> Result<i32> {
-        self.inner.cm.borrow_mut().slock(blk)?;
-        let buff = self.inner.bl.get_buffer(blk).unwrap().lock().unwrap();
-        let val = buff.contents_as_ref().get_i32(offset)?;
-        Ok(val)
+        self.read(blk, None, |p| p.get_i32(offset))
     }
 
     pub fn get_string(&self, blk: &BlockId, offset: usize) -> Result<String> {
-        self.inner.cm.borrow_mut().slock(blk)?;
-        let mut buff = self.inner.bl.get_buffer(blk).unwrap().lock().unwrap();
-        let val = buff.contents_as_mut().get_string(offset)?;
-        Ok(val)
+        self.read(blk, None, |p| p.get_string(offset))
+    }
+
+    pub fn get_i64(&self, blk: &BlockId, offset: usize) -> Result<i64> {
+        self.read(blk, None, |p| p.get_i64(offset))
+    }
+
+    pub fn get_f64(&self, blk: &BlockId, offset: usize) -> Result<f64> {
+        self.read(blk, None, |p| p.get_f64(offset))
+    }
+
+    pub fn get_bytes(&self, blk: &BlockId, offset: usize) -> Result<Vec<u8>> {
+        self.read(blk, None, |p| p.get_bytes(offset))
+    }
+
+    pub fn get_raw_bytes(&self, blk: &BlockId, offset: usize, len: usize) -> Result<Vec<u8>> {
+        self.read(blk, None, |p| {
+            p.get_raw_bytes(offset, len).map(<[u8]>::to_vec)
+        })
+    }
+
+    pub fn get_i32_in_slot(&self, blk: &BlockId, slot: i32, offset: usize) -> Result<i32> {
+        self.read(blk, Some(slot), |p| p.get_i32(offset))
+    }
+
+    pub fn get_string_in_slot(&self, blk: &BlockId, slot: i32, offset: usize) -> Result<String> {
+        self.read(blk, Some(slot), |p| p.get_string(offset))
+    }
+
+    pub fn read_string_in_slot(
+        &self,
+        blk: &BlockId,
+        slot: i32,
+        offset: usize,
+        buf: &mut String,
+    ) -> Result<()> {
+        self.read(blk, Some(slot), |p| {
+            let s = p.get_str(offset)?;
+            buf.clear();
+            buf.push_str(s);
+            Ok(())
+        })
+    }
+
+    pub fn get_bytes_in_slot(&self, blk: &BlockId, slot: i32, offset: usize) -> Result<Vec<u8>> {
+        self.read(blk, Some(slot), |p| p.get_bytes(offset))
     }
 
     pub fn set_i32(
@@ -185,16 +653,13 @@ impl<'lm, 'bm> Transaction<'lm, 'bm> {
         val: i32,
         ok_to_log: bool,
     ) -> Result<()> {
-        self.inner.cm.borrow_mut().xlock(blk)?;
-        let mut buff = self.inner.bl.get_buffer(blk).unwrap().lock().unwrap();
-        let mut lsn = -1;
-        if ok_to_log {
-            lsn = self.rm.set_i32(&mut *buff, offset, val).unwrap();
-        }
-        let p = buff.contents_as_mut();
-        p.set_i32(offset, val)?;
-        buff.set_modified(self.inner.txnum, lsn);
-        Ok(())
+        self.write(
+            blk,
+            None,
+            ok_to_log,
+            |rm, buff| rm.set_i32(buff, offset, val),
+            |p| p.set_i32(offset, val),
+        )
     }
 
     pub fn set_string(
@@ -204,44 +669,329 @@ impl<'lm, 'bm> Transaction<'lm, 'bm> {
         val: &str,
         ok_to_log: bool,
     ) -> Result<()> {
+        self.write(
+            blk,
+            None,
+            ok_to_log,
+            |rm, buff| rm.set_string(buff, offset, val),
+            |p| p.set_string(offset, val),
+        )
+    }
+
+    pub fn set_i64(
+        &mut self,
+        blk: &BlockId,
+        offset: usize,
+        val: i64,
+        ok_to_log: bool,
+    ) -> Result<()> {
+        self.write(
+            blk,
+            None,
+            ok_to_log,
+            |rm, buff| rm.set_i64(buff, offset, val),
+            |p| p.set_i64(offset, val),
+        )
+    }
+
+    pub fn set_f64(
+        &mut self,
+        blk: &BlockId,
+        offset: usize,
+        val: f64,
+        ok_to_log: bool,
+    ) -> Result<()> {
+        self.write(
+            blk,
+            None,
+            ok_to_log,
+            |rm, buff| rm.set_f64(buff, offset, val),
+            |p| p.set_f64(offset, val),
+        )
+    }
+
+    pub fn set_bytes(
+        &mut self,
+        blk: &BlockId,
+        offset: usize,
+        val: &[u8],
+        ok_to_log: bool,
+    ) -> Result<()> {
+        self.write(
+            blk,
+            None,
+            ok_to_log,
+            |rm, buff| rm.set_bytes(buff, offset, val),
+            |p| p.set_bytes(offset, val),
+        )
+    }
+
+    pub fn max_raw_bytes(&self, blk: &BlockId) -> usize {
+        self.rm.max_raw_bytes(blk)
+    }
+
+    pub fn set_raw_bytes(
+        &mut self,
+        blk: &BlockId,
+        offset: usize,
+        val: &[u8],
+        ok_to_log: bool,
+    ) -> Result<()> {
+        self.write(
+            blk,
+            None,
+            ok_to_log,
+            |rm, buff| rm.set_raw_bytes(buff, offset, val),
+            |p| p.set_raw_bytes(offset, val),
+        )
+    }
+
+    pub fn set_i32_in_slot(
+        &mut self,
+        blk: &BlockId,
+        slot: i32,
+        offset: usize,
+        val: i32,
+        ok_to_log: bool,
+    ) -> Result<()> {
+        self.write(
+            blk,
+            Some(slot),
+            ok_to_log,
+            |rm, buff| rm.set_i32(buff, offset, val),
+            |p| p.set_i32(offset, val),
+        )
+    }
+
+    pub fn set_string_in_slot(
+        &mut self,
+        blk: &BlockId,
+        slot: i32,
+        offset: usize,
+        val: &str,
+        ok_to_log: bool,
+    ) -> Result<()> {
+        self.write(
+            blk,
+            Some(slot),
+            ok_to_log,
+            |rm, buff| rm.set_string(buff, offset, val),
+            |p| p.set_string(offset, val),
+        )
+    }
+
+    pub fn set_bytes_in_slot(
+        &mut self,
+        blk: &BlockId,
+        slot: i32,
+        offset: usize,
+        val: &[u8],
+        ok_to_log: bool,
+    ) -> Result<()> {
+        self.write(
+            blk,
+            Some(slot),
+            ok_to_log,
+            |rm, buff| rm.set_bytes(buff, offset, val),
+            |p| p.set_bytes(offset, val),
+        )
+    }
+
+    pub fn xlock(&mut self, blk: &BlockId) -> Result<()> {
+        if self.is_read_only() {
+            return Err(TransactionError::ReadOnly);
+        }
         self.inner.cm.borrow_mut().xlock(blk)?;
-        let mut buff = self.inner.bl.get_buffer(blk).unwrap().lock().unwrap();
-        let mut lsn = -1;
-        if ok_to_log {
-            lsn = self.rm.set_string(&mut *buff, offset, val).unwrap();
+        Ok(())
+    }
+
+    pub fn slock_table(&mut self, filename: &str) -> Result<()> {
+        if !self.is_snapshot() {
+            self.inner.cm.borrow_mut().slock_table(filename)?;
         }
-        let p = buff.contents_as_mut();
-        p.set_string(offset, val)?;
-        buff.set_modified(self.inner.txnum, lsn);
         Ok(())
     }
 
+    pub fn xlock_slot(&mut self, blk: &BlockId, slot: i32) -> Result<()> {
+        if self.is_read_only() {
+            return Err(TransactionError::ReadOnly);
+        }
+        self.inner.cm.borrow_mut().xlock_slot(blk, slot)?;
+        Ok(())
+    }
+
+    pub fn peek_i32(&self, blk: &BlockId, offset: usize) -> Result<i32> {
+        let buff = self.inner.bl.get_buffer(blk).unwrap().lock().unwrap();
+        let val = buff.contents_as_ref().get_i32(offset)?;
+        Ok(val)
+    }
+
     pub fn available_buffs(&self) -> usize {
         self.bm.available()
     }
 
+    pub fn prefetch(&self, blocks: &[BlockId]) {
+        self.bm.prefetch(blocks);
+    }
+
     pub fn size(&self, filename: &str) -> Result<u64> {
         let dummyblk = BlockId::new(filename, END_OF_FILE.into());
-        self.inner.cm.borrow_mut().slock(&dummyblk)?;
+        if !self.is_snapshot() {
+            self.inner.cm.borrow_mut().slock(&dummyblk)?;
+        }
         let len = self.fm.length(filename)?;
+        self.inner.cm.borrow_mut().end_read(&dummyblk);
         Ok(len)
     }
 
     pub fn append(&mut self, filename: &str) -> Result<BlockId> {
+        if self.is_read_only() {
+            return Err(TransactionError::ReadOnly);
+        }
         let dummyblk = BlockId::new(filename, END_OF_FILE.into());
         self.inner.cm.borrow_mut().xlock(&dummyblk)?;
-        let block = self.fm.append(filename)?;
-        Ok(block)
+        // NOTE: the xlock fixes the block number
+        let blknum = i64::try_from(self.fm.length(filename)?)
+            .map_err(|_| TransactionError::TooManyBlocks(filename.to_owned()))?;
+        let block = BlockId::new(filename, blknum);
+        self.rm.append(&block)?;
+        let appended = self.fm.append(filename)?;
+        debug_assert_eq!(appended, block);
+        Ok(appended)
+    }
+
+    pub fn add_tablespace(&mut self, name: &str, path: &Path) -> Result<()> {
+        if self.is_read_only() {
+            return Err(TransactionError::ReadOnly);
+        }
+        self.fm.add_tablespace(name, path)?;
+        Ok(())
+    }
+
+    pub fn place_file(&mut self, filename: &str, tablespace: &str) -> Result<()> {
+        if self.is_read_only() {
+            return Err(TransactionError::ReadOnly);
+        }
+        self.fm.place_file(filename, tablespace)?;
+        let fm = self.fm.clone();
+        let filename = filename.to_string();
+        self.on_rollback(move || fm.unplace_file(&filename));
+        Ok(())
+    }
+
+    pub fn flush(&self) -> Result<()> {
+        self.bm.flush_all(self.inner.txnum)?;
+        Ok(())
     }
 
     pub fn block_size(&self) -> usize {
         self.fm.blocksize()
     }
+
+    fn read<T>(
+        &self,
+        blk: &BlockId,
+        slot: Option<i32>,
+        f: impl FnOnce(&mut Page) -> crate::file::page::Result<T>,
+    ) -> Result<T> {
+        if self.is_snapshot() {
+            return self.read_snapshot(blk, f);
+        }
+        match slot {
+            Some(slot) => self.inner.cm.borrow_mut().slock_slot(blk, slot)?,
+            None => self.inner.cm.borrow_mut().slock(blk)?,
+        }
+        let val = {
+            let mut buff = self.inner.bl.get_buffer(blk).unwrap().lock().unwrap();
+            f(buff.contents_as_mut())?
+        };
+        match slot {
+            Some(slot) => self.inner.cm.borrow_mut().end_read_slot(blk, slot),
+            None => self.inner.cm.borrow_mut().end_read(blk),
+        }
+        Ok(val)
+    }
+
+    fn write(
+        &mut self,
+        blk: &BlockId,
+        slot: Option<i32>,
+        ok_to_log: bool,
+        log: impl FnOnce(&mut RecoveryMgr, &mut Buffer) -> RecoveryResult<LSN>,
+        f: impl FnOnce(&mut Page) -> crate::file::page::Result<()>,
+    ) -> Result<()> {
+        if self.is_read_only() {
+            return Err(TransactionError::ReadOnly);
+        }
+        match slot {
+            Some(slot) => self.inner.cm.borrow_mut().xlock_slot(blk, slot)?,
+            None => self.inner.cm.borrow_mut().xlock(blk)?,
+        }
+        let mut buff = self.inner.bl.get_buffer(blk).unwrap().lock().unwrap();
+        let mut lsn = -1;
+        if ok_to_log {
+            lsn = log(&mut self.rm, &mut buff)?;
+        }
+        f(buff.contents_as_mut())?;
+        buff.set_modified(self.inner.txnum, lsn);
+        Ok(())
+    }
+
+    fn read_snapshot<T>(
+        &self,
+        blk: &BlockId,
+        f: impl FnOnce(&mut Page) -> crate::file::page::Result<T>,
+    ) -> Result<T> {
+        let snapshot = self.snapshot.as_ref().unwrap();
+        let mut pages = self.snapshot_pages.borrow_mut();
+        if !pages.contains_key(blk) {
+            let mut bytes = vec![0u8; self.fm.blocksize()];
+            {
+                let mut buff = self.inner.bl.get_buffer(blk).unwrap().lock().unwrap();
+                buff.contents_as_mut()
+                    .contents()?
+                    .get(&mut bytes)
+                    .map_err(PageError::from)?;
+            }
+            self.rm.undo_for_snapshot(
+                blk,
+                &mut bytes,
+                snapshot,
+                &mut self.snapshot_undo.borrow_mut(),
+            )?;
+            pages.insert(blk.clone(), bytes);
+        }
+        let mut page = Page::for_log(pages.get_mut(blk).unwrap());
+        let val = f(&mut page)?;
+        Ok(val)
+    }
+}
+
+impl Drop for Transaction {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.inner.bl.unpin_all();
+            let _ = self.rollback();
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{file::block_id::BlockId, server::simple_db::SimpleDB};
+    use super::{Durability, Transaction, TransactionError};
+    use crate::{
+        file::block_id::BlockId,
+        query::scan::{Scan, UpdateScan},
+        record::{
+            schema::{Layout, Schema},
+            table_scan::TableScan,
+        },
+        server::simple_db::SimpleDB,
+    };
+    use std::{
+        sync::{Arc, Mutex},
+        thread,
+    };
     use tempfile::tempdir;
 
     #[test]
@@ -252,39 +1002,370 @@ mod tests {
             let block = BlockId::new("test_transaction_file", 1);
 
             let tx1 = db.new_tx();
-            tx1.borrow_mut().pin(&block).unwrap();
-            tx1.borrow_mut().set_i32(&block, 80, 1, false).unwrap();
-            tx1.borrow_mut()
+            tx1.lock().unwrap().pin(&block).unwrap();
+            tx1.lock().unwrap().set_i32(&block, 80, 1, false).unwrap();
+            tx1.lock()
+                .unwrap()
                 .set_string(&block, 40, "one", false)
                 .unwrap();
-            tx1.borrow_mut().commit().unwrap();
+            tx1.lock().unwrap().unpin(&block);
+            tx1.lock().unwrap().commit().unwrap();
 
             let tx2 = db.new_tx();
-            tx2.borrow_mut().pin(&block).unwrap();
-            let ival = tx2.borrow().get_i32(&block, 80).unwrap();
-            let sval = tx2.borrow().get_string(&block, 40).unwrap();
+            tx2.lock().unwrap().pin(&block).unwrap();
+            let ival = tx2.lock().unwrap().get_i32(&block, 80).unwrap();
+            let sval = tx2.lock().unwrap().get_string(&block, 40).unwrap();
             assert_eq!(ival, 1);
             assert_eq!(sval, "one");
             let newival = ival + 1;
             let newsval = sval + "!";
-            tx2.borrow_mut().set_i32(&block, 80, newival, true).unwrap();
-            tx2.borrow_mut()
+            tx2.lock()
+                .unwrap()
+                .set_i32(&block, 80, newival, true)
+                .unwrap();
+            tx2.lock()
+                .unwrap()
                 .set_string(&block, 40, &newsval, true)
                 .unwrap();
-            tx2.borrow_mut().commit().unwrap();
+            tx2.lock().unwrap().unpin(&block);
+            tx2.lock().unwrap().commit().unwrap();
+
+            let tx3 = db.new_tx();
+            tx3.lock().unwrap().pin(&block).unwrap();
+            assert_eq!(tx3.lock().unwrap().get_i32(&block, 80).unwrap(), newival);
+            assert_eq!(tx3.lock().unwrap().get_string(&block, 40).unwrap(), newsval);
+            tx3.lock().unwrap().set_i32(&block, 80, 9999, true).unwrap();
+            assert_eq!(tx3.lock().unwrap().get_i32(&block, 80).unwrap(), 9999);
+            tx3.lock().unwrap().unpin(&block);
+            tx3.lock().unwrap().rollback().unwrap();
+
+            let tx4 = db.new_tx();
+            tx4.lock().unwrap().pin(&block).unwrap();
+            assert_eq!(tx4.lock().unwrap().get_i32(&block, 80).unwrap(), newival);
+            tx4.lock().unwrap().unpin(&block);
+            tx4.lock().unwrap().commit().unwrap();
+        }
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_durability() {
+        let dir = tempdir().unwrap();
+        let db = SimpleDB::new_for_test(dir.path(), "test_transaction_durability.log");
+        {
+            let block = BlockId::new("test_transaction_durability_file", 1);
+            let flushes = || db.log_mgr().stats().flushes;
+
+            let tx1 = db.new_tx();
+            assert_eq!(tx1.lock().unwrap().durability(), Durability::Strict);
+            tx1.lock().unwrap().set_durability(Durability::Relaxed);
+            tx1.lock().unwrap().pin(&block).unwrap();
+            tx1.lock().unwrap().set_i32(&block, 80, 1, true).unwrap();
+            let before = flushes();
+            tx1.lock().unwrap().unpin(&block);
+            tx1.lock().unwrap().commit().unwrap();
+            assert_eq!(flushes(), before);
+
+            let tx2 = db.new_tx();
+            tx2.lock().unwrap().pin(&block).unwrap();
+            assert_eq!(tx2.lock().unwrap().get_i32(&block, 80).unwrap(), 1);
+            tx2.lock().unwrap().set_i32(&block, 80, 2, true).unwrap();
+            tx2.lock().unwrap().unpin(&block);
+            tx2.lock().unwrap().commit().unwrap();
+            assert_eq!(flushes(), before + 1);
+        }
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_rollback_on_drop() {
+        let dir = tempdir().unwrap();
+        let db = SimpleDB::new_for_test(dir.path(), "test_transaction_rollback_on_drop.log");
+        {
+            let block = BlockId::new("test_transaction_rollback_on_drop_file", 1);
+
+            let tx1 = db.new_tx();
+            tx1.lock().unwrap().pin(&block).unwrap();
+            tx1.lock().unwrap().set_i32(&block, 80, 1, true).unwrap();
+            tx1.lock().unwrap().unpin(&block);
+            tx1.lock().unwrap().commit().unwrap();
+
+            let tx2 = db.new_tx();
+            tx2.lock().unwrap().pin(&block).unwrap();
+            tx2.lock().unwrap().set_i32(&block, 80, 2, true).unwrap();
+            drop(tx2);
+
+            let tx3 = db.new_tx();
+            tx3.lock().unwrap().pin(&block).unwrap();
+            assert_eq!(tx3.lock().unwrap().get_i32(&block, 80).unwrap(), 1);
+            tx3.lock().unwrap().set_i32(&block, 80, 3, true).unwrap();
+            tx3.lock().unwrap().unpin(&block);
+            tx3.lock().unwrap().commit().unwrap();
+        }
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_hooks() {
+        let dir = tempdir().unwrap();
+        let db = SimpleDB::new_for_test(dir.path(), "test_transaction_hooks.log");
+        {
+            let events = Arc::new(Mutex::new(Vec::new()));
+            let hooks = |tx: &Arc<Mutex<Transaction>>, name: &'static str| {
+                let e = events.clone();
+                tx.lock()
+                    .unwrap()
+                    .on_commit(move || e.lock().unwrap().push(format!("{} committed", name)));
+                let e = events.clone();
+                tx.lock()
+                    .unwrap()
+                    .on_rollback(move || e.lock().unwrap().push(format!("{} rolled back", name)));
+            };
+
+            let tx1 = db.new_tx();
+            hooks(&tx1, "tx1");
+            let tx2 = db.new_tx();
+            hooks(&tx2, "tx2");
+            let tx3 = db.new_tx();
+            hooks(&tx3, "tx3");
+
+            tx1.lock().unwrap().commit().unwrap();
+            tx2.lock().unwrap().rollback().unwrap();
+            drop(tx3);
+            assert_eq!(
+                *events.lock().unwrap(),
+                vec!["tx1 committed", "tx2 rolled back", "tx3 rolled back"]
+            );
+        }
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_pin_leaks() {
+        let dir = tempdir().unwrap();
+        let db = SimpleDB::new_for_test(dir.path(), "test_transaction_pin_leaks.log");
+        {
+            let bm = db.buffer_mgr();
+            let block1 = BlockId::new("test_transaction_pin_leaks_file", 1);
+            let block2 = BlockId::new("test_transaction_pin_leaks_file", 2);
+            let available = bm.available();
+
+            let tx = db.new_tx();
+            let txnum = tx.lock().unwrap().txnum();
+            tx.lock().unwrap().pin(&block1).unwrap();
+            tx.lock().unwrap().pin(&block2).unwrap();
+            tx.lock().unwrap().pin(&block2).unwrap();
+            tx.lock().unwrap().unpin(&block1);
+            assert_eq!(bm.pinned_blocks(txnum), vec![block2.clone()]);
+
+            drop(tx);
+            assert!(bm.pinned_blocks(txnum).is_empty());
+            assert_eq!(bm.stats().leaked_pins, 2);
+            assert_eq!(bm.available(), available);
+
+            let tx = db.new_tx();
+            tx.lock().unwrap().pin(&block1).unwrap();
+            tx.lock().unwrap().unpin(&block1);
+            tx.lock().unwrap().rollback().unwrap();
+            assert_eq!(bm.stats().leaked_pins, 2);
+        }
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let dir = tempdir().unwrap();
+        let db = SimpleDB::new_for_test(dir.path(), "test_transaction_snapshot.log");
+        {
+            let block = BlockId::new("test_transaction_snapshot_file", 1);
+
+            let tx1 = db.new_tx();
+            tx1.lock().unwrap().pin(&block).unwrap();
+            tx1.lock().unwrap().set_i32(&block, 80, 1, true).unwrap();
+            tx1.lock()
+                .unwrap()
+                .set_string(&block, 40, "one", true)
+                .unwrap();
+            tx1.lock().unwrap().unpin(&block);
+            tx1.lock().unwrap().commit().unwrap();
 
+            let tx2 = db.new_tx();
+            tx2.lock().unwrap().pin(&block).unwrap();
+            tx2.lock().unwrap().set_i32(&block, 80, 2, true).unwrap();
+            tx2.lock()
+                .unwrap()
+                .set_string(&block, 40, "two", true)
+                .unwrap();
+
+            let snap1 = db.new_snapshot_tx();
+            snap1.lock().unwrap().pin(&block).unwrap();
+            assert_eq!(snap1.lock().unwrap().get_i32(&block, 80).unwrap(), 1);
+            assert_eq!(snap1.lock().unwrap().get_string(&block, 40).unwrap(), "one");
+            assert!(matches!(
+                snap1.lock().unwrap().set_i32(&block, 80, 9, true),
+                Err(TransactionError::ReadOnly)
+            ));
+
+            tx2.lock().unwrap().unpin(&block);
+            tx2.lock().unwrap().commit().unwrap();
+            assert_eq!(snap1.lock().unwrap().get_i32(&block, 80).unwrap(), 1);
+
+            let lsn = db.log_mgr().latest_lsn();
+            let snap2 = db.new_snapshot_tx();
+            assert_eq!(db.log_mgr().latest_lsn(), lsn);
+            assert!(snap2.lock().unwrap().tn.when_idle(|| ()).is_some());
             let tx3 = db.new_tx();
-            tx3.borrow_mut().pin(&block).unwrap();
-            assert_eq!(tx3.borrow().get_i32(&block, 80).unwrap(), newival);
-            assert_eq!(tx3.borrow().get_string(&block, 40).unwrap(), newsval);
-            tx3.borrow_mut().set_i32(&block, 80, 9999, true).unwrap();
-            assert_eq!(tx3.borrow().get_i32(&block, 80).unwrap(), 9999);
-            tx3.borrow_mut().rollback().unwrap();
+            tx3.lock().unwrap().pin(&block).unwrap();
+            tx3.lock().unwrap().set_i32(&block, 80, 3, true).unwrap();
+            tx3.lock().unwrap().unpin(&block);
+            tx3.lock().unwrap().commit().unwrap();
 
+            snap2.lock().unwrap().pin(&block).unwrap();
+            assert_eq!(snap2.lock().unwrap().get_i32(&block, 80).unwrap(), 2);
+            assert_eq!(snap2.lock().unwrap().get_string(&block, 40).unwrap(), "two");
+
+            let block2 = BlockId::new("test_transaction_snapshot_file", 2);
             let tx4 = db.new_tx();
-            tx4.borrow_mut().pin(&block).unwrap();
-            assert_eq!(tx4.borrow().get_i32(&block, 80).unwrap(), newival);
-            tx4.borrow_mut().commit().unwrap();
+            tx4.lock().unwrap().pin(&block2).unwrap();
+            tx4.lock().unwrap().set_i32(&block2, 80, 4, true).unwrap();
+            tx4.lock().unwrap().unpin(&block2);
+            tx4.lock().unwrap().commit().unwrap();
+            snap2.lock().unwrap().pin(&block2).unwrap();
+            assert_eq!(snap2.lock().unwrap().get_i32(&block2, 80).unwrap(), 0);
+            snap2.lock().unwrap().unpin(&block2);
+
+            snap1.lock().unwrap().unpin(&block);
+            snap1.lock().unwrap().commit().unwrap();
+            snap2.lock().unwrap().unpin(&block);
+            snap2.lock().unwrap().commit().unwrap();
+        }
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_read_only() {
+        let dir = tempdir().unwrap();
+        let db = SimpleDB::new_for_test(dir.path(), "test_transaction_read_only.log");
+        {
+            let block = BlockId::new("test_transaction_read_only_file", 1);
+
+            let tx1 = db.new_tx();
+            tx1.lock().unwrap().pin(&block).unwrap();
+            tx1.lock().unwrap().set_i32(&block, 80, 1, true).unwrap();
+            tx1.lock().unwrap().unpin(&block);
+            tx1.lock().unwrap().commit().unwrap();
+
+            let num_log_records = || db.log_mgr().reverse_iter().unwrap().count();
+            let before = num_log_records();
+
+            let rtx = db.new_read_tx();
+            assert!(rtx.lock().unwrap().is_read_only());
+            rtx.lock().unwrap().pin(&block).unwrap();
+            assert_eq!(rtx.lock().unwrap().get_i32(&block, 80).unwrap(), 1);
+            assert!(matches!(
+                rtx.lock().unwrap().set_i32(&block, 80, 9, true),
+                Err(TransactionError::ReadOnly)
+            ));
+            assert!(matches!(
+                rtx.lock()
+                    .unwrap()
+                    .append("test_transaction_read_only_file"),
+                Err(TransactionError::ReadOnly)
+            ));
+            rtx.lock().unwrap().unpin(&block);
+            rtx.lock().unwrap().commit().unwrap();
+
+            assert_eq!(num_log_records(), before);
+        }
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_snapshot_scan() {
+        let dir = tempdir().unwrap();
+        let db = SimpleDB::new_for_test(dir.path(), "test_transaction_snapshot_scan.log");
+        {
+            let layout = {
+                let mut schema = Schema::new();
+                schema.add_i32_field("A");
+                Layout::new(schema)
+            };
+            let count = |tx| {
+                let mut ts = TableScan::new(tx, "T".into(), layout.clone()).unwrap();
+                let mut n = 0;
+                while ts.next().unwrap() {
+                    n += 1;
+                }
+                ts.close();
+                n
+            };
+
+            let tx1 = db.new_tx();
+            {
+                let mut ts = TableScan::new(tx1.clone(), "T".into(), layout.clone()).unwrap();
+                for i in 0..10 {
+                    ts.insert().unwrap();
+                    ts.set_i32("A", i).unwrap();
+                }
+                ts.close();
+            }
+            tx1.lock().unwrap().commit().unwrap();
+
+            let tx2 = db.new_tx();
+            {
+                let mut ts = TableScan::new(tx2.clone(), "T".into(), layout.clone()).unwrap();
+                ts.next().unwrap();
+                ts.delete().unwrap();
+                ts.insert().unwrap();
+                ts.set_i32("A", 100).unwrap();
+                ts.close();
+            }
+
+            let snap = db.new_snapshot_tx();
+            assert_eq!(count(snap.clone()), 10);
+            tx2.lock().unwrap().commit().unwrap();
+            assert_eq!(count(snap.clone()), 10);
+            snap.lock().unwrap().commit().unwrap();
+        }
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn test_scans_on_threads() {
+        let dir = tempdir().unwrap();
+        let db = SimpleDB::new_for_test(dir.path(), "test_transaction_scans_on_threads.log");
+        {
+            let layout = {
+                let mut schema = Schema::new();
+                schema.add_i32_field("A");
+                Layout::new(schema)
+            };
+
+            let tx = db.new_tx();
+            thread::scope(|s| {
+                for i in 0..4 {
+                    let mut ts = TableScan::new(tx.clone(), "T".into(), layout.clone()).unwrap();
+                    s.spawn(move || {
+                        for j in 0..10 {
+                            ts.insert().unwrap();
+                            ts.set_i32("A", i * 10 + j).unwrap();
+                        }
+                        ts.close();
+                    });
+                }
+            });
+            tx.lock().unwrap().commit().unwrap();
+
+            let tx = db.new_tx();
+            let mut ts = TableScan::new(tx.clone(), "T".into(), layout.clone()).unwrap();
+            let mut vals = Vec::new();
+            while ts.next().unwrap() {
+                vals.push(ts.get_i32("A").unwrap());
+            }
+            ts.close();
+            vals.sort();
+            assert_eq!(vals, (0..40).collect::<Vec<_>>());
+            tx.lock().unwrap().commit().unwrap();
         }
         dir.close().unwrap();
     }
@@ -0,0 +1,57 @@
+// Copyright (c) 2023 Sho Kuroda <krdlab@gmail.com>
+//
+// This software is released under the MIT License.
+// https://opensource.org/licenses/MIT
+
+use simpledb::record::schema::{Layout, Schema, SchemaError, SqlType};
+
+#[test]
+fn test_schema() {
+    assert_eq!(
+        serde_json::to_string(&SqlType::VarChar).unwrap(),
+        "\"VarChar\""
+    );
+
+    let mut schema = Schema::new();
+    schema.add_string_field("B", 9);
+    schema.add_i32_field("A");
+    let json = serde_json::to_string(&schema).unwrap();
+    assert_eq!(
+        json,
+        r#"[{"name":"B","type":"VarChar","length":9},{"name":"A","type":"Integer","length":0}]"#
+    );
+    let schema2: Schema = serde_json::from_str(&json).unwrap();
+    assert_eq!(schema2.fields_iter().collect::<Vec<_>>(), vec!["B", "A"]);
+    assert_eq!(schema2.field_type("B"), Some(SqlType::VarChar));
+    assert_eq!(schema2.field_length("B"), Some(9));
+
+    let dup =
+        r#"[{"name":"A","type":"Integer","length":0},{"name":"A","type":"Integer","length":0}]"#;
+    let err = serde_json::from_str::<Schema>(dup).err().unwrap();
+    assert!(err
+        .to_string()
+        .contains(&SchemaError::DuplicateField("A".into()).to_string()));
+}
+
+#[test]
+fn test_layout() {
+    let mut schema = Schema::new();
+    schema.add_string_field("B", 9);
+    schema.add_i32_field("A");
+    let mut old = Schema::new();
+    old.add_i32_field("A");
+    let layout = Layout::new(schema).with_older_versions(vec![Layout::new(old)]);
+    let json = serde_json::to_string(&layout).unwrap();
+    let layout2: Layout = serde_json::from_str(&json).unwrap();
+    assert_eq!(layout2.version(), 1);
+    assert_eq!(layout2.slotsize(), layout.slotsize());
+    assert_eq!(layout2.field_offset("A"), layout.field_offset("A"));
+    assert_eq!(layout2.field_offset("B"), Some(4));
+    assert_eq!(layout2.of_version(0).unwrap().field_offset("A"), Some(4));
+    assert_eq!(serde_json::to_string(&layout2).unwrap(), json);
+
+    // NOTE: A at 40 doesn't fit in a slot of 40 bytes
+    let broken = json.replacen(r#""slotsize":48"#, r#""slotsize":40"#, 1);
+    assert_ne!(broken, json);
+    assert!(serde_json::from_str::<Layout>(&broken).is_err());
+}